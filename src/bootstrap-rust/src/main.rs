@@ -0,0 +1,452 @@
+//! OneSol cluster bootstrap
+//!
+//! Seeds a devnet or localnet cluster in one command: creates two test mints and funds
+//! the payer with them, creates token-swap pools of the pair at different depths, and
+//! initializes a 1sol protocol account swapping into the second mint. The 1sol program
+//! must already be deployed, with `solana program deploy`.
+
+use clap::{crate_description, crate_version, App, Arg, ArgMatches};
+use ed25519_dalek::{Keypair, Signer};
+use onesol::{processor::Processor, state::OneSolState};
+use onesol_client::{
+    instruction::initialize, message::VersionedMessage, transaction::VersionedTransaction,
+};
+use rand::rngs::OsRng;
+use serde_json::{json, Value};
+use solana_program::{
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction,
+};
+use std::{error::Error, fs, process::exit, str::FromStr, thread::sleep, time::Duration};
+
+type BootstrapResult<T> = Result<T, Box<dyn Error>>;
+
+/// spl-token-swap program, deployed at the same address on devnet and mainnet-beta.
+const DEFAULT_SWAP_PROGRAM_ID: &str = "SwaPpA9LAaLfeLi3a68M4DjnLqgtticKg6CnyNwgAC8";
+
+/// Length of a token-swap pool account.
+const TOKEN_SWAP_LEN: usize = 324;
+
+/// Decimals of the test mints.
+const DECIMALS: u8 = 6;
+
+/// Number of polls of a transaction status before giving up on it.
+const CONFIRMATION_POLLS: usize = 60;
+
+/// JSON-RPC client of a cluster.
+struct Rpc {
+    url: String,
+}
+
+impl Rpc {
+    fn call(&self, method: &str, params: Value) -> BootstrapResult<Value> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response: Value = serde_json::from_str(
+            &ureq::post(&self.url)
+                .set("Content-Type", "application/json")
+                .send_string(&request.to_string())?
+                .into_string()?,
+        )?;
+        if !response["error"].is_null() {
+            return Err(format!("{} failed: {}", method, response["error"]).into());
+        }
+        Ok(response["result"].clone())
+    }
+
+    fn recent_blockhash(&self) -> BootstrapResult<Hash> {
+        let params = json!([{ "commitment": "confirmed" }]);
+        // getRecentBlockhash is all older nodes serve, newer ones only getLatestBlockhash
+        let result = self
+            .call("getLatestBlockhash", params.clone())
+            .or_else(|_| self.call("getRecentBlockhash", params))?;
+        let blockhash = result["value"]["blockhash"]
+            .as_str()
+            .ok_or("no blockhash in the response")?;
+        Ok(Hash::from_str(blockhash).map_err(|_| "invalid blockhash")?)
+    }
+
+    fn minimum_balance(&self, len: usize) -> BootstrapResult<u64> {
+        self.call("getMinimumBalanceForRentExemption", json!([len]))?
+            .as_u64()
+            .ok_or_else(|| "invalid minimum balance".into())
+    }
+
+    /// Sends a transaction of `instructions` paid by the first of `signers`, then waits
+    /// for its confirmation.
+    fn send(&self, instructions: &[Instruction], signers: &[&Keypair]) -> BootstrapResult<()> {
+        let mut message = Message::new(instructions, Some(&pubkey(signers[0])));
+        message.recent_blockhash = self.recent_blockhash()?;
+        let mut transaction = VersionedTransaction::new_unsigned(VersionedMessage::Legacy(message));
+        let message_data = transaction.message.serialize();
+        for signer in signers.iter() {
+            transaction.add_signature(&pubkey(signer), signer.sign(&message_data).to_bytes())?;
+        }
+        let signature = bs58::encode(transaction.signatures[0]).into_string();
+        self.call(
+            "sendTransaction",
+            json!([
+                base64::encode(transaction.serialize()),
+                { "encoding": "base64", "preflightCommitment": "confirmed" },
+            ]),
+        )?;
+
+        for _ in 0..CONFIRMATION_POLLS {
+            let status = &self.call("getSignatureStatuses", json!([[signature]]))?["value"][0];
+            if status["confirmationStatus"] == "confirmed"
+                || status["confirmationStatus"] == "finalized"
+            {
+                if !status["err"].is_null() {
+                    return Err(
+                        format!("transaction {} failed: {}", signature, status["err"]).into(),
+                    );
+                }
+                return Ok(());
+            }
+            sleep(Duration::from_millis(500));
+        }
+        Err(format!("transaction {} not confirmed", signature).into())
+    }
+
+    fn create_account(
+        &self,
+        payer: &Pubkey,
+        account: &Pubkey,
+        len: usize,
+        owner: &Pubkey,
+    ) -> BootstrapResult<Instruction> {
+        Ok(system_instruction::create_account(
+            payer,
+            account,
+            self.minimum_balance(len)?,
+            len as u64,
+            owner,
+        ))
+    }
+
+    fn create_mint(
+        &self,
+        payer: &Pubkey,
+        mint: &Pubkey,
+        authority: &Pubkey,
+    ) -> BootstrapResult<Vec<Instruction>> {
+        Ok(vec![
+            self.create_account(payer, mint, spl_token::state::Mint::LEN, &spl_token::id())?,
+            spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                mint,
+                authority,
+                None,
+                DECIMALS,
+            )?,
+        ])
+    }
+
+    fn create_token_account(
+        &self,
+        payer: &Pubkey,
+        account: &Pubkey,
+        mint: &Pubkey,
+        owner: &Pubkey,
+    ) -> BootstrapResult<Vec<Instruction>> {
+        Ok(vec![
+            self.create_account(
+                payer,
+                account,
+                spl_token::state::Account::LEN,
+                &spl_token::id(),
+            )?,
+            spl_token::instruction::initialize_account(&spl_token::id(), account, mint, owner)?,
+        ])
+    }
+}
+
+fn pubkey(keypair: &Keypair) -> Pubkey {
+    Pubkey::new(keypair.public.as_bytes())
+}
+
+fn new_keypair() -> Keypair {
+    Keypair::generate(&mut OsRng)
+}
+
+/// Reads a keypair file written by `solana-keygen`: a JSON array of its 64 bytes.
+fn read_keypair(path: &str) -> BootstrapResult<Keypair> {
+    let bytes: Vec<u8> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Keypair::from_bytes(&bytes).map_err(|e| format!("invalid keypair {}: {}", path, e).into())
+}
+
+/// Creates a token-swap `Initialize` instruction of a constant product pool charging
+/// 0.25% trade fees and 0.05% owner fees, in the layout of token-swap 2.x.
+#[allow(clippy::too_many_arguments)]
+fn token_swap_initialize(
+    swap_program_id: &Pubkey,
+    swap: &Pubkey,
+    authority: &Pubkey,
+    nonce: u8,
+    token_a: &Pubkey,
+    token_b: &Pubkey,
+    pool_mint: &Pubkey,
+    fee_account: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    let mut data = vec![0, nonce];
+    for value in [25u64, 10_000, 5, 10_000, 0, 0, 20, 100].iter() {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    // constant product curve, its calculator has no parameter
+    data.push(0);
+    data.extend_from_slice(&[0u8; 32]);
+    Instruction {
+        program_id: *swap_program_id,
+        accounts: vec![
+            AccountMeta::new(*swap, true),
+            AccountMeta::new_readonly(*authority, false),
+            AccountMeta::new_readonly(*token_a, false),
+            AccountMeta::new_readonly(*token_b, false),
+            AccountMeta::new(*pool_mint, false),
+            AccountMeta::new_readonly(*fee_account, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data,
+    }
+}
+
+/// Creates a token-swap pool of `liquidity` of each mint, funded from the payer's token
+/// accounts, returning the pool address.
+fn create_pool(
+    rpc: &Rpc,
+    payer: &Keypair,
+    swap_program_id: &Pubkey,
+    mints: (&Pubkey, &Pubkey),
+    sources: (&Pubkey, &Pubkey),
+    liquidity: u64,
+) -> BootstrapResult<Pubkey> {
+    let payer_key = pubkey(payer);
+    let swap = new_keypair();
+    let (authority, nonce) =
+        Pubkey::find_program_address(&[&pubkey(&swap).to_bytes()[..32]], swap_program_id);
+    let (token_a, token_b, pool_mint) = (new_keypair(), new_keypair(), new_keypair());
+    let mut instructions = rpc.create_mint(&payer_key, &pubkey(&pool_mint), &authority)?;
+    instructions.extend(rpc.create_token_account(
+        &payer_key,
+        &pubkey(&token_a),
+        mints.0,
+        &authority,
+    )?);
+    instructions.extend(rpc.create_token_account(
+        &payer_key,
+        &pubkey(&token_b),
+        mints.1,
+        &authority,
+    )?);
+    rpc.send(&instructions, &[payer, &pool_mint, &token_a, &token_b])?;
+
+    let (fee_account, destination) = (new_keypair(), new_keypair());
+    let mut instructions = rpc.create_token_account(
+        &payer_key,
+        &pubkey(&fee_account),
+        &pubkey(&pool_mint),
+        &payer_key,
+    )?;
+    instructions.extend(rpc.create_token_account(
+        &payer_key,
+        &pubkey(&destination),
+        &pubkey(&pool_mint),
+        &payer_key,
+    )?);
+    instructions.push(spl_token::instruction::transfer(
+        &spl_token::id(),
+        sources.0,
+        &pubkey(&token_a),
+        &payer_key,
+        &[],
+        liquidity,
+    )?);
+    instructions.push(spl_token::instruction::transfer(
+        &spl_token::id(),
+        sources.1,
+        &pubkey(&token_b),
+        &payer_key,
+        &[],
+        liquidity,
+    )?);
+    rpc.send(&instructions, &[payer, &fee_account, &destination])?;
+
+    rpc.send(
+        &[
+            rpc.create_account(&payer_key, &pubkey(&swap), TOKEN_SWAP_LEN, swap_program_id)?,
+            token_swap_initialize(
+                swap_program_id,
+                &pubkey(&swap),
+                &authority,
+                nonce,
+                &pubkey(&token_a),
+                &pubkey(&token_b),
+                &pubkey(&pool_mint),
+                &pubkey(&fee_account),
+                &pubkey(&destination),
+            ),
+        ],
+        &[payer, &swap],
+    )?;
+    Ok(pubkey(&swap))
+}
+
+fn run(matches: &ArgMatches) -> BootstrapResult<()> {
+    let value_of = |name: &str| matches.value_of(name).unwrap_or_default();
+    let rpc = Rpc {
+        url: value_of("url").to_string(),
+    };
+    let payer = read_keypair(value_of("keypair"))?;
+    let payer_key = pubkey(&payer);
+    let program_id =
+        Pubkey::from_str(value_of("program_id")).map_err(|_| "invalid --program-id")?;
+    let swap_program_id =
+        Pubkey::from_str(value_of("swap_program_id")).map_err(|_| "invalid --swap-program-id")?;
+    let pools: u64 = value_of("pools").parse().map_err(|_| "invalid --pools")?;
+    let liquidity: u64 = value_of("liquidity")
+        .parse()
+        .map_err(|_| "invalid --liquidity")?;
+    // the pools get 1, 2, .. times the liquidity, the payer keeps as much as the deepest
+    let supply = liquidity
+        .checked_mul((pools + 1) * (pools + 2) / 2)
+        .ok_or("--liquidity too large")?;
+
+    let (mint_a, mint_b) = (new_keypair(), new_keypair());
+    let mut instructions = rpc.create_mint(&payer_key, &pubkey(&mint_a), &payer_key)?;
+    instructions.extend(rpc.create_mint(&payer_key, &pubkey(&mint_b), &payer_key)?);
+    rpc.send(&instructions, &[&payer, &mint_a, &mint_b])?;
+    println!("Mint A: {}", pubkey(&mint_a));
+    println!("Mint B: {}", pubkey(&mint_b));
+
+    let (source_a, source_b) = (new_keypair(), new_keypair());
+    let mut instructions =
+        rpc.create_token_account(&payer_key, &pubkey(&source_a), &pubkey(&mint_a), &payer_key)?;
+    instructions.extend(rpc.create_token_account(
+        &payer_key,
+        &pubkey(&source_b),
+        &pubkey(&mint_b),
+        &payer_key,
+    )?);
+    for (mint, account) in [(&mint_a, &source_a), (&mint_b, &source_b)].iter() {
+        instructions.push(spl_token::instruction::mint_to(
+            &spl_token::id(),
+            &pubkey(mint),
+            &pubkey(account),
+            &payer_key,
+            &[],
+            supply,
+        )?);
+    }
+    rpc.send(&instructions, &[&payer, &source_a, &source_b])?;
+    println!("Payer token A account: {}", pubkey(&source_a));
+    println!("Payer token B account: {}", pubkey(&source_b));
+
+    for i in 1..=pools {
+        let pool = create_pool(
+            &rpc,
+            &payer,
+            &swap_program_id,
+            (&pubkey(&mint_a), &pubkey(&mint_b)),
+            (&pubkey(&source_a), &pubkey(&source_b)),
+            liquidity * i,
+        )?;
+        println!("Pool {}: {}", i, pool);
+    }
+
+    let (protocol, protocol_token) = (new_keypair(), new_keypair());
+    let (authority, nonce) = Processor::find_authority_address(&program_id, &pubkey(&protocol));
+    let mut instructions = vec![rpc.create_account(
+        &payer_key,
+        &pubkey(&protocol),
+        OneSolState::LEN,
+        &program_id,
+    )?];
+    instructions.extend(rpc.create_token_account(
+        &payer_key,
+        &pubkey(&protocol_token),
+        &pubkey(&mint_b),
+        &authority,
+    )?);
+    instructions.push(initialize(
+        &program_id,
+        &pubkey(&protocol),
+        &authority,
+        &pubkey(&protocol_token),
+        &spl_token::id(),
+        &payer_key,
+        nonce,
+    ));
+    rpc.send(&instructions, &[&payer, &protocol, &protocol_token])?;
+    println!("Protocol (A -> B): {}", pubkey(&protocol));
+    println!("Protocol authority: {} (nonce {})", authority, nonce);
+    Ok(())
+}
+
+fn main() {
+    let default_keypair = format!(
+        "{}/.config/solana/id.json",
+        std::env::var("HOME").unwrap_or_else(|_| ".".to_string())
+    );
+    let matches = App::new("onesol-bootstrap")
+        .about(crate_description!())
+        .version(crate_version!())
+        .arg(
+            Arg::with_name("url")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .default_value("http://127.0.0.1:8899")
+                .help("JSON RPC URL of the cluster"),
+        )
+        .arg(
+            Arg::with_name("keypair")
+                .long("keypair")
+                .value_name("PATH")
+                .takes_value(true)
+                .default_value(&default_keypair)
+                .help("Payer keypair, also the mint authority of the test mints"),
+        )
+        .arg(
+            Arg::with_name("program_id")
+                .long("program-id")
+                .value_name("PROGRAM_ID")
+                .takes_value(true)
+                .required(true)
+                .help("Deployed OneSol program id"),
+        )
+        .arg(
+            Arg::with_name("swap_program_id")
+                .long("swap-program-id")
+                .value_name("PROGRAM_ID")
+                .takes_value(true)
+                .default_value(DEFAULT_SWAP_PROGRAM_ID)
+                .help("Token-Swap program creating the pools"),
+        )
+        .arg(
+            Arg::with_name("pools")
+                .long("pools")
+                .value_name("COUNT")
+                .takes_value(true)
+                .default_value("2")
+                .help("Number of pools of the test pair"),
+        )
+        .arg(
+            Arg::with_name("liquidity")
+                .long("liquidity")
+                .value_name("AMOUNT")
+                .takes_value(true)
+                .default_value("1000000000")
+                .help("Raw amount of each token in the first pool, the next ones get multiples"),
+        )
+        .get_matches();
+
+    if let Err(err) = run(&matches) {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
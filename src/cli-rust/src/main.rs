@@ -0,0 +1,409 @@
+//! OneSol command line interface
+//!
+//! The CLI works offline: accounts are read from raw data files named after their
+//! address, as written by `solana account <ADDRESS> --output-file <DIR>/<ADDRESS>`,
+//! and instructions are printed with an unsigned message to sign and send.
+
+use clap::{crate_description, crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+use onesol::{
+    processor::Processor,
+    state::{GlobalConfig, OneSolState},
+};
+use onesol_client::{
+    dex::SplTokenSwapPool,
+    instruction::{initialize, protocol_authority, SwapAccounts},
+    lookup_table::AddressLookupTable,
+    quote::{Quote, SplTokenSwapQuoter},
+    route::{plan_route, Route, DEFAULT_ROUTE_PARTS},
+    transaction::{estimate_swap_compute_units, swap_message, swap_transaction},
+};
+use solana_program::{
+    hash::Hash,
+    instruction::Instruction,
+    message::Message,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+};
+use std::{error::Error, fs, path::PathBuf, process::exit, str::FromStr};
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+/// spl-token-swap program deployed on mainnet-beta.
+const DEFAULT_SWAP_PROGRAM_ID: &str = "SwaPpA9LAaLfeLi3a68M4DjnLqgtticKg6CnyNwgAC8";
+
+/// Reads account data from the accounts directory.
+struct Accounts {
+    dir: PathBuf,
+}
+
+impl Accounts {
+    fn data(&self, address: &Pubkey) -> CliResult<Vec<u8>> {
+        let path = self.dir.join(address.to_string());
+        fs::read(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e).into())
+    }
+
+    fn unpack<T: Pack + IsInitialized>(&self, address: &Pubkey) -> CliResult<T> {
+        T::unpack(&self.data(address)?)
+            .map_err(|e| format!("cannot unpack {}: {}", address, e).into())
+    }
+
+    fn token_swap_quoter(
+        &self,
+        pool: &Pubkey,
+        swap_program_id: &Pubkey,
+    ) -> CliResult<SplTokenSwapQuoter> {
+        let pool_data = self.data(pool)?;
+        let pool = SplTokenSwapPool::unpack(pool, swap_program_id, &pool_data)?;
+        let token_a_data = self.data(&pool.token_a)?;
+        let token_b_data = self.data(&pool.token_b)?;
+        Ok(SplTokenSwapQuoter::unpack(
+            pool,
+            &pool_data,
+            &token_a_data,
+            &token_b_data,
+        )?)
+    }
+}
+
+fn pubkey_of(matches: &ArgMatches, name: &str) -> CliResult<Pubkey> {
+    let value = matches
+        .value_of(name)
+        .ok_or_else(|| format!("missing --{}", name))?;
+    Pubkey::from_str(value).map_err(|_| format!("invalid pubkey for --{}: {}", name, value).into())
+}
+
+fn value_of<T: FromStr>(matches: &ArgMatches, name: &str) -> CliResult<T> {
+    let value = matches
+        .value_of(name)
+        .ok_or_else(|| format!("missing --{}", name))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for --{}: {}", name, value).into())
+}
+
+fn print_instruction(instruction: &Instruction) {
+    println!("Program: {}", instruction.program_id);
+    for (i, meta) in instruction.accounts.iter().enumerate() {
+        println!(
+            "  {:>2}. {}{}{}",
+            i,
+            meta.pubkey,
+            if meta.is_writable { " [writable]" } else { "" },
+            if meta.is_signer { " [signer]" } else { "" },
+        );
+    }
+    println!("Data: {}", base64::encode(&instruction.data));
+}
+
+fn print_message(message: &Message) -> CliResult<()> {
+    println!(
+        "Unsigned message: {}",
+        base64::encode(bincode::serialize(message)?)
+    );
+    Ok(())
+}
+
+fn plan(matches: &ArgMatches, accounts: &Accounts) -> CliResult<Route> {
+    let swap_program_id = pubkey_of(matches, "swap_program_id")?;
+    let source_mint = pubkey_of(matches, "source_mint")?;
+    let amount: u64 = value_of(matches, "amount")?;
+    let quoters = matches
+        .values_of("pool")
+        .ok_or("missing --pool")?
+        .map(|pool| {
+            let pool = Pubkey::from_str(pool).map_err(|_| format!("invalid pool: {}", pool))?;
+            accounts.token_swap_quoter(&pool, &swap_program_id)
+        })
+        .collect::<CliResult<Vec<_>>>()?;
+    let pools: Vec<&dyn Quote> = quoters.iter().map(|q| q as &dyn Quote).collect();
+    Ok(plan_route(
+        &pools,
+        &source_mint,
+        amount,
+        DEFAULT_ROUTE_PARTS,
+    )?)
+}
+
+fn command_initialize_state(matches: &ArgMatches) -> CliResult<()> {
+    let program_id = pubkey_of(matches, "program_id")?;
+    let protocol = pubkey_of(matches, "protocol")?;
+    let token = pubkey_of(matches, "token")?;
+    let admin = pubkey_of(matches, "admin")?;
+    let (authority, nonce) = Processor::find_authority_address(&program_id, &protocol);
+    println!("Protocol authority: {} (nonce {})", authority, nonce);
+    let instruction = initialize(
+        &program_id,
+        &protocol,
+        &authority,
+        &token,
+        &spl_token::id(),
+        &admin,
+        nonce,
+    );
+    print_instruction(&instruction);
+    print_message(&Message::new(&[instruction], Some(&protocol)))
+}
+
+fn command_quote(matches: &ArgMatches, accounts: &Accounts) -> CliResult<()> {
+    let route = plan(matches, accounts)?;
+    for (i, (leg, ratio)) in route.legs.iter().enumerate() {
+        println!(
+            "Leg {}: pool {} ratio {} bps amount in {} amount out {}",
+            i, leg.accounts[0].pubkey, ratio, route.amounts_in[i], route.amounts_out[i],
+        );
+    }
+    println!("Amount in: {}", route.amount_in);
+    println!("Expected amount out: {}", route.amount_out);
+    Ok(())
+}
+
+fn command_swap(matches: &ArgMatches, accounts: &Accounts) -> CliResult<()> {
+    let program_id = pubkey_of(matches, "program_id")?;
+    let protocol = pubkey_of(matches, "protocol")?;
+    let user = pubkey_of(matches, "user")?;
+    let slippage_bps: u16 = value_of(matches, "slippage_bps")?;
+    let priority_fee: u64 = value_of(matches, "priority_fee")?;
+    let state: OneSolState = accounts.unpack(&protocol)?;
+    let swap_accounts = SwapAccounts {
+        protocol,
+        protocol_authority: protocol_authority(&program_id, &protocol, state.nonce)?,
+        user_transfer_authority: user,
+        protocol_token: state.token,
+        source: pubkey_of(matches, "source")?,
+        destination: pubkey_of(matches, "destination")?,
+        token_program_id: state.token_program_id,
+    };
+    let route = plan(matches, accounts)?;
+    println!(
+        "Expected amount out: {}, minimum: {}",
+        route.amount_out,
+        route.minimum_amount_out(slippage_bps)
+    );
+    println!("Compute units: {}", estimate_swap_compute_units(&route)?);
+    print_instruction(&route.instruction(&program_id, &swap_accounts, slippage_bps)?);
+    let lookup_tables = match matches.values_of("lookup_table") {
+        Some(addresses) => addresses
+            .map(|address| {
+                let address = Pubkey::from_str(address)
+                    .map_err(|_| format!("invalid lookup table: {}", address))?;
+                Ok(AddressLookupTable::unpack(
+                    &address,
+                    &accounts.data(&address)?,
+                )?)
+            })
+            .collect::<CliResult<Vec<_>>>()?,
+        None => {
+            return print_message(&swap_message(
+                &route,
+                &program_id,
+                &swap_accounts,
+                slippage_bps,
+                priority_fee,
+            )?)
+        }
+    };
+    let transaction = swap_transaction(
+        &route,
+        &program_id,
+        &swap_accounts,
+        slippage_bps,
+        priority_fee,
+        &lookup_tables,
+        Hash::default(),
+    )?;
+    transaction
+        .check_size()
+        .map_err(|_| "the transaction exceeds the packet size")?;
+    println!(
+        "Unsigned v0 transaction: {}",
+        base64::encode(transaction.serialize())
+    );
+    Ok(())
+}
+
+fn command_show_config(matches: &ArgMatches, accounts: &Accounts) -> CliResult<()> {
+    let program_id = pubkey_of(matches, "program_id")?;
+    let (address, _) = Processor::find_config_address(&program_id);
+    let config: GlobalConfig = accounts.unpack(&address)?;
+    println!("Config: {}", address);
+    println!("Admin: {}", config.admin);
+    println!("Oracle max age (slots): {}", config.oracle_max_age_slots);
+    println!(
+        "Oracle confidence multiplier: {}",
+        config.oracle_conf_multiplier
+    );
+    println!("Log level: {:?}", config.log_level);
+    Ok(())
+}
+
+fn main() {
+    let default_program_id = onesol::id().to_string();
+    let program_id_arg = Arg::with_name("program_id")
+        .long("program-id")
+        .value_name("PROGRAM_ID")
+        .takes_value(true)
+        .default_value(&default_program_id)
+        .help("OneSol program id");
+    let route_args = [
+        Arg::with_name("source_mint")
+            .long("source-mint")
+            .value_name("MINT")
+            .takes_value(true)
+            .required(true)
+            .help("Mint of the SOURCE token"),
+        Arg::with_name("amount")
+            .long("amount")
+            .value_name("AMOUNT")
+            .takes_value(true)
+            .required(true)
+            .help("SOURCE amount to swap, in raw units"),
+        Arg::with_name("pool")
+            .long("pool")
+            .value_name("POOL")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .required(true)
+            .help("Candidate spl-token-swap pool, may be repeated"),
+        Arg::with_name("swap_program_id")
+            .long("swap-program-id")
+            .value_name("PROGRAM_ID")
+            .takes_value(true)
+            .default_value(DEFAULT_SWAP_PROGRAM_ID)
+            .help("Token-Swap program owning the pools"),
+    ];
+
+    let matches = App::new("onesol")
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("accounts_dir")
+                .long("accounts-dir")
+                .value_name("DIR")
+                .takes_value(true)
+                .global(true)
+                .default_value(".")
+                .help("Directory of the account data files, named after their address"),
+        )
+        .subcommand(
+            SubCommand::with_name("initialize-state")
+                .about("Build the instruction initializing a 1sol protocol account")
+                .arg(program_id_arg.clone())
+                .arg(
+                    Arg::with_name("protocol")
+                        .long("protocol")
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .required(true)
+                        .help("New protocol account, owned by the program"),
+                )
+                .arg(
+                    Arg::with_name("token")
+                        .long("token")
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Protocol token account, owned by the protocol authority"),
+                )
+                .arg(
+                    Arg::with_name("admin")
+                        .long("admin")
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Admin allowed to set the protocol fee rate"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("quote")
+                .about("Plan the split of a swap across pools and quote it")
+                .args(&route_args),
+        )
+        .subcommand(
+            SubCommand::with_name("swap")
+                .about("Build the swap instruction of the planned route")
+                .arg(program_id_arg.clone())
+                .args(&route_args)
+                .arg(
+                    Arg::with_name("protocol")
+                        .long("protocol")
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .required(true)
+                        .help("1sol protocol account of the DESTINATION token"),
+                )
+                .arg(
+                    Arg::with_name("user")
+                        .long("user")
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .required(true)
+                        .help("User transfer authority and fee payer"),
+                )
+                .arg(
+                    Arg::with_name("source")
+                        .long("source")
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .required(true)
+                        .help("User SOURCE token account"),
+                )
+                .arg(
+                    Arg::with_name("destination")
+                        .long("destination")
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .required(true)
+                        .help("User DESTINATION token account"),
+                )
+                .arg(
+                    Arg::with_name("slippage_bps")
+                        .long("slippage-bps")
+                        .value_name("BPS")
+                        .takes_value(true)
+                        .default_value("50")
+                        .help("Tolerated shortfall below the expected output"),
+                )
+                .arg(
+                    Arg::with_name("priority_fee")
+                        .long("priority-fee")
+                        .value_name("MICRO_LAMPORTS")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Priority fee per compute unit, in micro-lamports"),
+                )
+                .arg(
+                    Arg::with_name("lookup_table")
+                        .long("lookup-table")
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Address lookup table to load accounts from, may be repeated; builds a v0 transaction"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show-config")
+                .about("Show the global config")
+                .arg(program_id_arg),
+        )
+        .get_matches();
+
+    let (command, sub_matches) = matches.subcommand();
+    let sub_matches = sub_matches.expect("subcommand required");
+    let accounts = Accounts {
+        dir: PathBuf::from(sub_matches.value_of("accounts_dir").unwrap_or(".")),
+    };
+    let result = match command {
+        "initialize-state" => command_initialize_state(sub_matches),
+        "quote" => command_quote(sub_matches, &accounts),
+        "swap" => command_swap(sub_matches, &accounts),
+        "show-config" => command_show_config(sub_matches, &accounts),
+        _ => unreachable!(),
+    };
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,319 @@
+//! Swap instruction builder
+
+use crate::{
+    dex::DexLeg,
+    instruction::{pay_protocol_fee, relayed_swap, split_swap, swap, tip_swap, SwapAccounts},
+};
+use onesol::{
+    error::OneSolError,
+    instruction::{OneSolInstruction, MAX_LEGS, MAX_SPLIT_PARTS},
+    math::BPS_SCALE,
+};
+use solana_program::{instruction::Instruction, program_error::ProgramError, pubkey::Pubkey};
+
+/// Builds a `Swap` instruction, or a `RelayedSwap` one once given a relayer, from its
+/// legs and options, checking them together before assembling the instruction data and
+/// accounts, e.g. `SwapBuilder::new(amount_in).leg(leg).build(&program_id, &accounts)`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SwapBuilder {
+    amount_in: u64,
+    minimum_amount_out: u64,
+    legs: Vec<(DexLeg, u16)>,
+    relayer_token: Option<Pubkey>,
+    fee_bps: u16,
+    fee_flat: u64,
+    tip: Option<(Pubkey, u64)>,
+    split_parts: u8,
+    treasury: Option<Pubkey>,
+}
+
+impl SwapBuilder {
+    /// Starts a swap of `amount_in` SOURCE tokens, accepting any output until
+    /// [minimum_amount_out](SwapBuilder::minimum_amount_out) is set.
+    pub fn new(amount_in: u64) -> Self {
+        SwapBuilder {
+            amount_in,
+            ..SwapBuilder::default()
+        }
+    }
+
+    /// Sets the least DESTINATION amount the user accepts, net of any relayer fee.
+    pub fn minimum_amount_out(mut self, minimum_amount_out: u64) -> Self {
+        self.minimum_amount_out = minimum_amount_out;
+        self
+    }
+
+    /// Adds a leg swapping the whole amount, a ratio of [BPS_SCALE].
+    pub fn leg(self, leg: DexLeg) -> Self {
+        self.leg_with_ratio(leg, BPS_SCALE)
+    }
+
+    /// Adds a leg swapping `ratio` bps of the amount, the ratios of the legs summing to
+    /// [BPS_SCALE].
+    pub fn leg_with_ratio(mut self, leg: DexLeg, ratio: u16) -> Self {
+        self.legs.push((leg, ratio));
+        self
+    }
+
+    /// Relays the swap, reimbursing the relayer into `relayer_token` with the fees set
+    /// by [fee_bps](SwapBuilder::fee_bps) and [fee_flat](SwapBuilder::fee_flat).
+    pub fn relayer(mut self, relayer_token: Pubkey) -> Self {
+        self.relayer_token = Some(relayer_token);
+        self
+    }
+
+    /// Sets the bps of the output reimbursing the relayer.
+    pub fn fee_bps(mut self, fee_bps: u16) -> Self {
+        self.fee_bps = fee_bps;
+        self
+    }
+
+    /// Sets the flat DESTINATION amount reimbursing the relayer.
+    pub fn fee_flat(mut self, fee_flat: u64) -> Self {
+        self.fee_flat = fee_flat;
+        self
+    }
+
+    /// Tips `lamports` of the user transfer authority to `tip_account`, the tip account
+    /// of the global config.
+    pub fn tip(mut self, tip_account: Pubkey, lamports: u64) -> Self {
+        self.tip = Some((tip_account, lamports));
+        self
+    }
+
+    /// Splits `amount_in` on-chain in `split_parts` chunks across the legs, in place of
+    /// their ratios, wherever the program can quote all of them.
+    pub fn split_parts(mut self, split_parts: u8) -> Self {
+        self.split_parts = split_parts;
+        self
+    }
+
+    /// Pays the protocol fee into `treasury`, the treasury of the 1sol protocol account,
+    /// which swaps of a protocol charging a fee must.
+    pub fn treasury(mut self, treasury: Pubkey) -> Self {
+        self.treasury = Some(treasury);
+        self
+    }
+
+    /// Checks the options fit together and the legs are routable.
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        if self.amount_in == 0 || self.legs.len() > MAX_LEGS {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let fees = self.fee_bps > 0 || self.fee_flat > 0;
+        if self.fee_bps > BPS_SCALE
+            || (fees && self.relayer_token.is_none())
+            // relayed swaps neither tip nor split on-chain
+            || (self.relayer_token.is_some() && (self.tip.is_some() || self.split_parts > 0))
+            || self.split_parts > MAX_SPLIT_PARTS
+        {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        crate::instruction::validate_legs(&self.legs)
+    }
+
+    /// Validates the swap and creates its instruction, checking the program unpacks the
+    /// instruction data back.
+    pub fn build(
+        &self,
+        program_id: &Pubkey,
+        accounts: &SwapAccounts,
+    ) -> Result<Instruction, ProgramError> {
+        self.validate()?;
+        let mut ix = match self.relayer_token {
+            Some(relayer_token) => relayed_swap(
+                program_id,
+                accounts,
+                &relayer_token,
+                self.amount_in,
+                self.minimum_amount_out,
+                self.fee_bps,
+                self.fee_flat,
+                &self.legs,
+            )?,
+            None => {
+                let mut ix = swap(
+                    program_id,
+                    accounts,
+                    self.amount_in,
+                    self.minimum_amount_out,
+                    &self.legs,
+                )?;
+                if let Some((tip_account, lamports)) = self.tip {
+                    tip_swap(&mut ix, &tip_account, lamports)?;
+                }
+                if self.split_parts > 0 {
+                    split_swap(&mut ix, self.split_parts)?;
+                }
+                ix
+            }
+        };
+        if let Some(treasury) = self.treasury {
+            pay_protocol_fee(&mut ix, &treasury);
+        }
+        OneSolInstruction::unpack(&ix.data)?;
+        Ok(ix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::DEX_TYPE_SPL_TOKEN_SWAP;
+    use solana_program::instruction::AccountMeta;
+
+    fn leg(size: usize) -> DexLeg {
+        DexLeg {
+            dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+            accounts: (0..size)
+                .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+                .collect(),
+        }
+    }
+
+    fn swap_accounts() -> SwapAccounts {
+        SwapAccounts {
+            protocol: Pubkey::new_unique(),
+            protocol_authority: Pubkey::new_unique(),
+            user_transfer_authority: Pubkey::new_unique(),
+            protocol_token: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            token_program_id: spl_token::id(),
+        }
+    }
+
+    #[test]
+    fn test_build_swap() {
+        let program_id = Pubkey::new_unique();
+        let accounts = swap_accounts();
+        let (first, second) = (leg(7), leg(8));
+        let ix = SwapBuilder::new(1_000)
+            .minimum_amount_out(900)
+            .leg_with_ratio(first.clone(), 6_000)
+            .leg_with_ratio(second.clone(), 4_000)
+            .build(&program_id, &accounts)
+            .unwrap();
+        assert_eq!(
+            ix,
+            swap(
+                &program_id,
+                &accounts,
+                1_000,
+                900,
+                &[(first.clone(), 6_000), (second, 4_000)]
+            )
+            .unwrap()
+        );
+
+        let tip_account = Pubkey::new_unique();
+        let ix = SwapBuilder::new(1_000)
+            .leg(first)
+            .tip(tip_account, 5_000)
+            .build(&program_id, &accounts)
+            .unwrap();
+        match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::Swap(swap) => {
+                assert_eq!(swap.minimum_amount_out, 0);
+                assert_eq!(swap.tip_lamports, 5_000);
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        assert_eq!(ix.accounts[7 + 7 + 1].pubkey, tip_account);
+
+        let ix = SwapBuilder::new(1_000)
+            .leg(leg(7))
+            .tip(tip_account, 5_000)
+            .split_parts(8)
+            .build(&program_id, &accounts)
+            .unwrap();
+        match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::Swap(swap) => {
+                assert_eq!(swap.tip_lamports, 5_000);
+                assert_eq!(swap.split_parts, 8);
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        // the treasury trails everything else
+        let treasury = Pubkey::new_unique();
+        let ix = SwapBuilder::new(1_000)
+            .leg(leg(7))
+            .tip(tip_account, 5_000)
+            .treasury(treasury)
+            .build(&program_id, &accounts)
+            .unwrap();
+        assert_eq!(ix.accounts.len(), 7 + 7 + 3 + 1);
+        assert_eq!(ix.accounts.last(), Some(&AccountMeta::new(treasury, false)));
+    }
+
+    #[test]
+    fn test_build_relayed_swap() {
+        let relayer_token = Pubkey::new_unique();
+        let ix = SwapBuilder::new(1_000)
+            .minimum_amount_out(900)
+            .leg(leg(7))
+            .relayer(relayer_token)
+            .fee_bps(30)
+            .fee_flat(5)
+            .build(&Pubkey::new_unique(), &swap_accounts())
+            .unwrap();
+        match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::RelayedSwap(swap) => {
+                assert_eq!(swap.amount_in, 1_000);
+                assert_eq!(swap.fee_bps, 30);
+                assert_eq!(swap.fee_flat, 5);
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        assert_eq!(ix.accounts[8].pubkey, relayer_token);
+    }
+
+    #[test]
+    fn test_build_rejects_invalid_swaps() {
+        let program_id = Pubkey::new_unique();
+        let accounts = swap_accounts();
+        let invalid: ProgramError = OneSolError::InvalidInput.into();
+        let builder = SwapBuilder::new(1_000).leg(leg(7));
+        assert!(builder.validate().is_ok());
+
+        assert_eq!(
+            SwapBuilder::new(0)
+                .leg(leg(7))
+                .build(&program_id, &accounts),
+            Err(invalid.clone())
+        );
+        assert_eq!(
+            SwapBuilder::new(1_000).build(&program_id, &accounts),
+            Err(invalid.clone())
+        );
+        // fees without a relayer to pay
+        assert_eq!(
+            builder.clone().fee_bps(30).build(&program_id, &accounts),
+            Err(invalid.clone())
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .relayer(Pubkey::new_unique())
+                .fee_bps(BPS_SCALE + 1)
+                .validate(),
+            Err(invalid.clone())
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .relayer(Pubkey::new_unique())
+                .tip(Pubkey::new_unique(), 1)
+                .validate(),
+            Err(invalid)
+        );
+        assert_eq!(
+            builder.clone().leg_with_ratio(leg(3), 0).validate(),
+            Err(OneSolError::AccountCountMismatch.into())
+        );
+        assert_eq!(
+            SwapBuilder::new(1_000).leg_with_ratio(leg(7), 0).validate(),
+            Err(OneSolError::RatioSumInvalid.into())
+        );
+    }
+}
@@ -0,0 +1,711 @@
+//! Dex pools and the accounts a swap leg through them needs
+
+use arrayref::{array_ref, array_refs};
+use onesol::{error::OneSolError, instruction::DexConfig, processor::Processor};
+use solana_program::{
+    instruction::AccountMeta, program_error::ProgramError, pubkey::Pubkey, sysvar,
+};
+use std::ops::RangeInclusive;
+
+/// `dex_type` of spl-token-swap pools.
+pub const DEX_TYPE_SPL_TOKEN_SWAP: u8 = 0;
+
+/// `dex_type` of fills out of the protocol-owned inventory.
+pub const DEX_TYPE_INVENTORY: u8 = 1;
+
+/// `dex_type` of Serum DEX v3 markets.
+pub const DEX_TYPE_SERUM_DEX: u8 = 2;
+
+/// `dex_type` of Raydium AMM v4 pools.
+pub const DEX_TYPE_RAYDIUM: u8 = 3;
+
+/// `dex_type` of Orca Whirlpool pools.
+pub const DEX_TYPE_WHIRLPOOL: u8 = 4;
+
+/// `dex_type` of Saber StableSwap pools.
+pub const DEX_TYPE_STABLE_SWAP: u8 = 5;
+
+/// Length of the StableSwap account prefix read by [StableSwapPool].
+const STABLE_SWAP_HEADER_LEN: usize = 331;
+
+/// Length of a whirlpool account.
+const WHIRLPOOL_LEN: usize = 653;
+
+/// Ticks a whirlpool tick array holds, in tick spacings.
+const WHIRLPOOL_TICK_ARRAY_SIZE: i32 = 88;
+
+/// Length of a Serum market account: the `serum` head padding, the `MarketState` and the
+/// tail padding.
+const SERUM_MARKET_LEN: usize = 388;
+
+/// Length of the spl-token-swap account prefix read by [SplTokenSwapPool].
+const SPL_TOKEN_SWAP_HEADER_LEN: usize = 227;
+
+/// A swap leg through one dex: its type and its accounts, laid out as the program
+/// expects them after the fixed swap accounts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DexLeg {
+    /// dex type of the leg
+    pub dex_type: u8,
+    /// dex accounts of the leg
+    pub accounts: Vec<AccountMeta>,
+}
+
+impl DexLeg {
+    /// Returns the `DexConfig` routing `ratio` bps of the swap through this leg.
+    pub fn dex_config(&self, ratio: u16) -> DexConfig {
+        DexConfig::new_dex_config(self.dex_type, self.accounts.len(), ratio)
+    }
+
+    /// Checks the program routes through the dex of the leg, and the leg has as many
+    /// accounts as the dex takes.
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        let account_counts =
+            leg_account_counts(self.dex_type).ok_or(OneSolError::DexProgramNotAllowed)?;
+        if !account_counts.contains(&self.accounts.len()) {
+            return Err(OneSolError::AccountCountMismatch.into());
+        }
+        Ok(())
+    }
+}
+
+/// Returns the account counts the program takes for a leg of `dex_type`, none if it does
+/// not route through the dex.
+pub fn leg_account_counts(dex_type: u8) -> Option<RangeInclusive<usize>> {
+    match dex_type {
+        // the host fee account is optional
+        DEX_TYPE_SPL_TOKEN_SWAP => Some(7..=8),
+        DEX_TYPE_INVENTORY => Some(8..=8),
+        DEX_TYPE_SERUM_DEX => Some(11..=11),
+        DEX_TYPE_RAYDIUM => Some(15..=15),
+        DEX_TYPE_WHIRLPOOL => Some(8..=8),
+        DEX_TYPE_STABLE_SWAP => Some(7..=7),
+        _ => None,
+    }
+}
+
+/// spl-token-swap pool, read from its account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SplTokenSwapPool {
+    /// Pool account
+    pub address: Pubkey,
+    /// Token-Swap program owning the pool
+    pub program_id: Pubkey,
+    /// Pool authority, derived from the pool account and its bump seed
+    pub authority: Pubkey,
+    /// Token program of the pool tokens
+    pub token_program_id: Pubkey,
+    /// Token A reserve account
+    pub token_a: Pubkey,
+    /// Token B reserve account
+    pub token_b: Pubkey,
+    /// Pool token mint
+    pub pool_mint: Pubkey,
+    /// Token A mint
+    pub token_a_mint: Pubkey,
+    /// Token B mint
+    pub token_b_mint: Pubkey,
+    /// Pool token account receiving the trading fees
+    pub pool_fee_account: Pubkey,
+}
+
+impl SplTokenSwapPool {
+    /// Reads the pool at `address`, owned by the Token-Swap program `program_id`.
+    pub fn unpack(
+        address: &Pubkey,
+        program_id: &Pubkey,
+        data: &[u8],
+    ) -> Result<Self, ProgramError> {
+        if data.len() < SPL_TOKEN_SWAP_HEADER_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![data, 0, SPL_TOKEN_SWAP_HEADER_LEN];
+        #[rustfmt::skip]
+        let (
+            _version, is_initialized, bump_seed, token_program_id,
+            token_a, token_b, pool_mint, token_a_mint, token_b_mint, pool_fee_account,
+        ) = array_refs![src, 1, 1, 1, 32, 32, 32, 32, 32, 32, 32];
+        if is_initialized[0] != 1 {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        let authority =
+            Pubkey::create_program_address(&[&address.to_bytes()[..32], bump_seed], program_id)
+                .map_err(|_| OneSolError::InvalidProgramAddress)?;
+        Ok(SplTokenSwapPool {
+            address: *address,
+            program_id: *program_id,
+            authority,
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+            pool_fee_account: Pubkey::new_from_array(*pool_fee_account),
+        })
+    }
+
+    /// Returns the leg swapping `source_mint` through the pool, paying the host fee into
+    /// `host_fee_account` if given.
+    pub fn leg(
+        &self,
+        source_mint: &Pubkey,
+        host_fee_account: Option<&Pubkey>,
+    ) -> Result<DexLeg, ProgramError> {
+        let (swap_source, swap_destination) = if *source_mint == self.token_a_mint {
+            (self.token_a, self.token_b)
+        } else if *source_mint == self.token_b_mint {
+            (self.token_b, self.token_a)
+        } else {
+            return Err(OneSolError::InvalidInput.into());
+        };
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.address, false),
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new(swap_source, false),
+            AccountMeta::new(swap_destination, false),
+            AccountMeta::new(self.pool_mint, false),
+            AccountMeta::new(self.pool_fee_account, false),
+            AccountMeta::new_readonly(self.program_id, false),
+        ];
+        if let Some(host_fee_account) = host_fee_account {
+            accounts.push(AccountMeta::new(*host_fee_account, false));
+        }
+        Ok(DexLeg {
+            dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+            accounts,
+        })
+    }
+}
+
+/// Serum DEX v3 market, read from its account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SerumMarket {
+    /// Market account
+    pub address: Pubkey,
+    /// Serum DEX program owning the market
+    pub program_id: Pubkey,
+    /// Vault signer, derived from the market account and its nonce
+    pub vault_signer: Pubkey,
+    /// Base mint
+    pub coin_mint: Pubkey,
+    /// Quote mint
+    pub pc_mint: Pubkey,
+    /// Base vault
+    pub coin_vault: Pubkey,
+    /// Quote vault
+    pub pc_vault: Pubkey,
+    /// Request queue
+    pub request_queue: Pubkey,
+    /// Event queue
+    pub event_queue: Pubkey,
+    /// Bids
+    pub bids: Pubkey,
+    /// Asks
+    pub asks: Pubkey,
+}
+
+impl SerumMarket {
+    /// Reads the market at `address`, owned by the Serum DEX program `program_id`.
+    pub fn unpack(
+        address: &Pubkey,
+        program_id: &Pubkey,
+        data: &[u8],
+    ) -> Result<Self, ProgramError> {
+        if data.len() != SERUM_MARKET_LEN || data[..5] != *b"serum" {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![data, 5, 344];
+        #[rustfmt::skip]
+        let (
+            _account_flags, _own_address, vault_signer_nonce, coin_mint, pc_mint, coin_vault,
+            _coin_deposits, _coin_fees, pc_vault, _pc_deposits, _pc_fees, _pc_dust,
+            request_queue, event_queue, bids, asks,
+        ) = array_refs![src, 8, 32, 8, 32, 32, 32, 8, 8, 32, 8, 8, 8, 32, 32, 32, 32];
+        let vault_signer =
+            Pubkey::create_program_address(&[address.as_ref(), vault_signer_nonce], program_id)
+                .map_err(|_| OneSolError::InvalidProgramAddress)?;
+        Ok(SerumMarket {
+            address: *address,
+            program_id: *program_id,
+            vault_signer,
+            coin_mint: Pubkey::new_from_array(*coin_mint),
+            pc_mint: Pubkey::new_from_array(*pc_mint),
+            coin_vault: Pubkey::new_from_array(*coin_vault),
+            pc_vault: Pubkey::new_from_array(*pc_vault),
+            request_queue: Pubkey::new_from_array(*request_queue),
+            event_queue: Pubkey::new_from_array(*event_queue),
+            bids: Pubkey::new_from_array(*bids),
+            asks: Pubkey::new_from_array(*asks),
+        })
+    }
+
+    /// Returns the leg trading through the market from `open_orders`, the open orders
+    /// account of the user transfer authority on the market. The side follows from the
+    /// mints of the swap's SOURCE and DESTINATION accounts.
+    pub fn leg(&self, open_orders: &Pubkey) -> DexLeg {
+        DexLeg {
+            dex_type: DEX_TYPE_SERUM_DEX,
+            accounts: vec![
+                AccountMeta::new(self.address, false),
+                AccountMeta::new(*open_orders, false),
+                AccountMeta::new(self.request_queue, false),
+                AccountMeta::new(self.event_queue, false),
+                AccountMeta::new(self.bids, false),
+                AccountMeta::new(self.asks, false),
+                AccountMeta::new(self.coin_vault, false),
+                AccountMeta::new(self.pc_vault, false),
+                AccountMeta::new_readonly(self.vault_signer, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(self.program_id, false),
+            ],
+        }
+    }
+}
+
+/// Raydium AMM v4 pool and the Serum market it makes, as listed by Raydium's pool API.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RaydiumPool {
+    /// Raydium AMM program owning the pool
+    pub program_id: Pubkey,
+    /// Amm account
+    pub amm: Pubkey,
+    /// Amm authority
+    pub authority: Pubkey,
+    /// Amm open orders on the market
+    pub open_orders: Pubkey,
+    /// Amm target orders
+    pub target_orders: Pubkey,
+    /// Pool coin token account
+    pub coin_vault: Pubkey,
+    /// Pool pc token account
+    pub pc_vault: Pubkey,
+    /// Market the pool makes
+    pub market: SerumMarket,
+}
+
+impl RaydiumPool {
+    /// Returns the leg swapping through the pool, in either direction: the pool swaps
+    /// from the mint of the swap's SOURCE account.
+    pub fn leg(&self) -> DexLeg {
+        DexLeg {
+            dex_type: DEX_TYPE_RAYDIUM,
+            accounts: vec![
+                AccountMeta::new(self.amm, false),
+                AccountMeta::new_readonly(self.authority, false),
+                AccountMeta::new(self.open_orders, false),
+                AccountMeta::new(self.target_orders, false),
+                AccountMeta::new(self.coin_vault, false),
+                AccountMeta::new(self.pc_vault, false),
+                AccountMeta::new_readonly(self.market.program_id, false),
+                AccountMeta::new(self.market.address, false),
+                AccountMeta::new(self.market.bids, false),
+                AccountMeta::new(self.market.asks, false),
+                AccountMeta::new(self.market.event_queue, false),
+                AccountMeta::new(self.market.coin_vault, false),
+                AccountMeta::new(self.market.pc_vault, false),
+                AccountMeta::new_readonly(self.market.vault_signer, false),
+                AccountMeta::new_readonly(self.program_id, false),
+            ],
+        }
+    }
+}
+
+/// Orca Whirlpool pool, read from its account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WhirlpoolPool {
+    /// Whirlpool account
+    pub address: Pubkey,
+    /// Whirlpool program owning the pool
+    pub program_id: Pubkey,
+    /// Ticks between initializable ticks
+    pub tick_spacing: u16,
+    /// Current tick
+    pub tick_current_index: i32,
+    /// Mint of token A
+    pub token_mint_a: Pubkey,
+    /// Vault of token A
+    pub token_vault_a: Pubkey,
+    /// Mint of token B
+    pub token_mint_b: Pubkey,
+    /// Vault of token B
+    pub token_vault_b: Pubkey,
+}
+
+impl WhirlpoolPool {
+    /// Reads the whirlpool at `address`, owned by the Whirlpool program `program_id`.
+    pub fn unpack(
+        address: &Pubkey,
+        program_id: &Pubkey,
+        data: &[u8],
+    ) -> Result<Self, ProgramError> {
+        if data.len() != WHIRLPOOL_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![data, 41, 204];
+        #[rustfmt::skip]
+        let (
+            tick_spacing, _tick_spacing_seed, _fee_rates, _liquidity, _sqrt_price,
+            tick_current_index, _protocol_fees_owed, token_mint_a, token_vault_a,
+            _fee_growth_a, token_mint_b, token_vault_b,
+        ) = array_refs![src, 2, 2, 4, 16, 16, 4, 16, 32, 32, 16, 32, 32];
+        Ok(WhirlpoolPool {
+            address: *address,
+            program_id: *program_id,
+            tick_spacing: u16::from_le_bytes(*tick_spacing),
+            tick_current_index: i32::from_le_bytes(*tick_current_index),
+            token_mint_a: Pubkey::new_from_array(*token_mint_a),
+            token_vault_a: Pubkey::new_from_array(*token_vault_a),
+            token_mint_b: Pubkey::new_from_array(*token_mint_b),
+            token_vault_b: Pubkey::new_from_array(*token_vault_b),
+        })
+    }
+
+    /// Returns the tick array starting at `start_tick_index`.
+    pub fn tick_array_address(&self, start_tick_index: i32) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                b"tick_array",
+                self.address.as_ref(),
+                start_tick_index.to_string().as_bytes(),
+            ],
+            &self.program_id,
+        )
+        .0
+    }
+
+    /// Returns the leg swapping `source_mint` through the pool, crossing at most the
+    /// tick array of the current tick and the next two in the swap direction.
+    pub fn leg(&self, source_mint: &Pubkey) -> Result<DexLeg, ProgramError> {
+        let a_to_b = if *source_mint == self.token_mint_a {
+            true
+        } else if *source_mint == self.token_mint_b {
+            false
+        } else {
+            return Err(OneSolError::InvalidInput.into());
+        };
+        let ticks_per_array = i32::from(self.tick_spacing) * WHIRLPOOL_TICK_ARRAY_SIZE;
+        if ticks_per_array == 0 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // a swap up from the last tick of an array starts in the next one
+        let tick = if a_to_b {
+            self.tick_current_index
+        } else {
+            self.tick_current_index + i32::from(self.tick_spacing)
+        };
+        let start = tick.div_euclid(ticks_per_array) * ticks_per_array;
+        let step = if a_to_b {
+            -ticks_per_array
+        } else {
+            ticks_per_array
+        };
+        let (oracle, _) =
+            Pubkey::find_program_address(&[b"oracle", self.address.as_ref()], &self.program_id);
+        Ok(DexLeg {
+            dex_type: DEX_TYPE_WHIRLPOOL,
+            accounts: vec![
+                AccountMeta::new(self.address, false),
+                AccountMeta::new(self.token_vault_a, false),
+                AccountMeta::new(self.token_vault_b, false),
+                AccountMeta::new(self.tick_array_address(start), false),
+                AccountMeta::new(self.tick_array_address(start + step), false),
+                AccountMeta::new(self.tick_array_address(start + 2 * step), false),
+                AccountMeta::new_readonly(oracle, false),
+                AccountMeta::new_readonly(self.program_id, false),
+            ],
+        })
+    }
+}
+
+/// Saber StableSwap pool, read from its account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StableSwapPool {
+    /// StableSwap account
+    pub address: Pubkey,
+    /// StableSwap program owning the pool
+    pub program_id: Pubkey,
+    /// Swap authority, derived from the StableSwap account and its nonce
+    pub authority: Pubkey,
+    /// Token A reserve account
+    pub token_a: Pubkey,
+    /// Token B reserve account
+    pub token_b: Pubkey,
+    /// Token A mint
+    pub token_a_mint: Pubkey,
+    /// Token B mint
+    pub token_b_mint: Pubkey,
+    /// Admin fee account of token A
+    pub admin_fee_account_a: Pubkey,
+    /// Admin fee account of token B
+    pub admin_fee_account_b: Pubkey,
+}
+
+impl StableSwapPool {
+    /// Reads the pool at `address`, owned by the StableSwap program `program_id`.
+    pub fn unpack(
+        address: &Pubkey,
+        program_id: &Pubkey,
+        data: &[u8],
+    ) -> Result<Self, ProgramError> {
+        if data.len() < STABLE_SWAP_HEADER_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![data, 0, STABLE_SWAP_HEADER_LEN];
+        #[rustfmt::skip]
+        let (
+            is_initialized, _is_paused, nonce, _amp_ramp, _future_admin, _admin_key,
+            token_a, token_b, _pool_mint, token_a_mint, token_b_mint,
+            admin_fee_account_a, admin_fee_account_b,
+        ) = array_refs![src, 1, 1, 1, 40, 32, 32, 32, 32, 32, 32, 32, 32, 32];
+        if is_initialized[0] != 1 {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        let authority = Pubkey::create_program_address(&[address.as_ref(), nonce], program_id)
+            .map_err(|_| OneSolError::InvalidProgramAddress)?;
+        Ok(StableSwapPool {
+            address: *address,
+            program_id: *program_id,
+            authority,
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+            admin_fee_account_a: Pubkey::new_from_array(*admin_fee_account_a),
+            admin_fee_account_b: Pubkey::new_from_array(*admin_fee_account_b),
+        })
+    }
+
+    /// Returns the leg swapping `source_mint` through the pool.
+    pub fn leg(&self, source_mint: &Pubkey) -> Result<DexLeg, ProgramError> {
+        let (swap_source, swap_destination, admin_destination) =
+            if *source_mint == self.token_a_mint {
+                (self.token_a, self.token_b, self.admin_fee_account_b)
+            } else if *source_mint == self.token_b_mint {
+                (self.token_b, self.token_a, self.admin_fee_account_a)
+            } else {
+                return Err(OneSolError::InvalidInput.into());
+            };
+        Ok(DexLeg {
+            dex_type: DEX_TYPE_STABLE_SWAP,
+            accounts: vec![
+                AccountMeta::new_readonly(self.address, false),
+                AccountMeta::new_readonly(self.authority, false),
+                AccountMeta::new(swap_source, false),
+                AccountMeta::new(swap_destination, false),
+                AccountMeta::new(admin_destination, false),
+                AccountMeta::new_readonly(sysvar::clock::id(), false),
+                AccountMeta::new_readonly(self.program_id, false),
+            ],
+        })
+    }
+}
+
+/// Returns the leg filling a `source_mint` to `destination_mint` swap out of the
+/// protocol-owned inventory of `program_id`, priced by the `oracle` of the inventory pair.
+/// Route through it when the oracle price less the pair spread beats the external venues.
+pub fn inventory_leg(
+    program_id: &Pubkey,
+    source_mint: &Pubkey,
+    destination_mint: &Pubkey,
+    source_vault: &Pubkey,
+    destination_vault: &Pubkey,
+    oracle: &Pubkey,
+) -> DexLeg {
+    let (pair, _) =
+        Processor::find_inventory_pair_address(program_id, source_mint, destination_mint);
+    let (authority, _) = Processor::find_inventory_authority(program_id);
+    let (config, _) = Processor::find_config_address(program_id);
+    DexLeg {
+        dex_type: DEX_TYPE_INVENTORY,
+        accounts: vec![
+            AccountMeta::new_readonly(pair, false),
+            AccountMeta::new_readonly(authority, false),
+            AccountMeta::new(*source_vault, false),
+            AccountMeta::new(*destination_vault, false),
+            AccountMeta::new_readonly(*oracle, false),
+            AccountMeta::new_readonly(*source_mint, false),
+            AccountMeta::new_readonly(*destination_mint, false),
+            AccountMeta::new_readonly(config, false),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool_data(address: &Pubkey, program_id: &Pubkey) -> Vec<u8> {
+        let (_, bump_seed) = Pubkey::find_program_address(&[&address.to_bytes()[..32]], program_id);
+        let mut data = vec![1u8, 1, bump_seed];
+        for byte in 1..=7u8 {
+            data.extend_from_slice(&[byte; 32]);
+        }
+        data.resize(324, 0);
+        data
+    }
+
+    #[test]
+    fn test_unpack_spl_token_swap_pool() {
+        let address = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let data = pool_data(&address, &program_id);
+        let pool = SplTokenSwapPool::unpack(&address, &program_id, &data).unwrap();
+        assert_eq!(
+            pool.authority,
+            Pubkey::find_program_address(&[&address.to_bytes()[..32]], &program_id).0
+        );
+        assert_eq!(pool.token_a, Pubkey::new_from_array([2u8; 32]));
+        assert_eq!(pool.token_b_mint, Pubkey::new_from_array([6u8; 32]));
+        assert_eq!(pool.pool_fee_account, Pubkey::new_from_array([7u8; 32]));
+
+        let mut uninitialized = data.clone();
+        uninitialized[1] = 0;
+        assert!(SplTokenSwapPool::unpack(&address, &program_id, &uninitialized).is_err());
+        assert!(SplTokenSwapPool::unpack(&address, &program_id, &data[..200]).is_err());
+    }
+
+    #[test]
+    fn test_spl_token_swap_leg() {
+        let address = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let pool =
+            SplTokenSwapPool::unpack(&address, &program_id, &pool_data(&address, &program_id))
+                .unwrap();
+
+        let leg = pool.leg(&pool.token_b_mint, None).unwrap();
+        assert_eq!(leg.accounts.len(), 7);
+        assert_eq!(leg.accounts[2].pubkey, pool.token_b);
+        assert_eq!(leg.accounts[3].pubkey, pool.token_a);
+        assert_eq!(
+            leg.dex_config(100),
+            DexConfig::new_dex_config(DEX_TYPE_SPL_TOKEN_SWAP, 7, 100)
+        );
+
+        let host_fee_account = Pubkey::new_unique();
+        let leg = pool
+            .leg(&pool.token_a_mint, Some(&host_fee_account))
+            .unwrap();
+        assert_eq!(leg.accounts[2].pubkey, pool.token_a);
+        assert_eq!(leg.accounts[7].pubkey, host_fee_account);
+
+        assert!(pool.leg(&Pubkey::new_unique(), None).is_err());
+    }
+
+    #[test]
+    fn test_serum_market_leg() {
+        let address = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let (nonce, _) = (0u64..)
+            .map(|nonce| {
+                let seeds = [address.as_ref(), &nonce.to_le_bytes()];
+                (nonce, Pubkey::create_program_address(&seeds, &program_id))
+            })
+            .find(|(_, vault_signer)| vault_signer.is_ok())
+            .unwrap();
+        let mut data = b"serum".to_vec();
+        data.resize(SERUM_MARKET_LEN, 0);
+        data[45..53].copy_from_slice(&nonce.to_le_bytes());
+        data[53..85].copy_from_slice(&[1u8; 32]);
+        data[317..349].copy_from_slice(&[2u8; 32]);
+        let market = SerumMarket::unpack(&address, &program_id, &data).unwrap();
+        assert_eq!(market.coin_mint, Pubkey::new_from_array([1u8; 32]));
+        assert_eq!(market.asks, Pubkey::new_from_array([2u8; 32]));
+
+        let leg = market.leg(&Pubkey::new_unique());
+        assert!(leg.validate().is_ok());
+        assert_eq!(leg.accounts[5].pubkey, market.asks);
+        assert_eq!(leg.accounts[8].pubkey, market.vault_signer);
+        assert!(SerumMarket::unpack(&address, &program_id, &data[..300]).is_err());
+
+        let pool = RaydiumPool {
+            program_id: Pubkey::new_unique(),
+            amm: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            open_orders: Pubkey::new_unique(),
+            target_orders: Pubkey::new_unique(),
+            coin_vault: Pubkey::new_unique(),
+            pc_vault: Pubkey::new_unique(),
+            market,
+        };
+        let leg = pool.leg();
+        assert!(leg.validate().is_ok());
+        assert_eq!(leg.accounts[7].pubkey, pool.market.address);
+        assert_eq!(leg.accounts[14].pubkey, pool.program_id);
+    }
+
+    #[test]
+    fn test_whirlpool_leg() {
+        let address = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let mut data = vec![0u8; WHIRLPOOL_LEN];
+        data[41..43].copy_from_slice(&64u16.to_le_bytes());
+        data[81..85].copy_from_slice(&(-100i32).to_le_bytes());
+        data[101..133].copy_from_slice(&[1u8; 32]);
+        data[181..213].copy_from_slice(&[2u8; 32]);
+        let pool = WhirlpoolPool::unpack(&address, &program_id, &data).unwrap();
+        assert_eq!(pool.tick_spacing, 64);
+        assert_eq!(pool.tick_current_index, -100);
+
+        // 5632 ticks per array, the current tick in the array starting at -5632
+        let leg = pool.leg(&pool.token_mint_a).unwrap();
+        assert!(leg.validate().is_ok());
+        assert_eq!(leg.accounts[3].pubkey, pool.tick_array_address(-5632));
+        assert_eq!(leg.accounts[5].pubkey, pool.tick_array_address(-16896));
+        let leg = pool.leg(&pool.token_mint_b).unwrap();
+        assert_eq!(leg.accounts[4].pubkey, pool.tick_array_address(0));
+        assert!(pool.leg(&Pubkey::new_unique()).is_err());
+    }
+
+    #[test]
+    fn test_stable_swap_leg() {
+        let address = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let (_, nonce) = Pubkey::find_program_address(&[address.as_ref()], &program_id);
+        let mut data = vec![1u8, 0, nonce];
+        data.resize(107, 0);
+        for byte in 1..=8u8 {
+            data.extend_from_slice(&[byte; 32]);
+        }
+        data.resize(395, 0);
+        let pool = StableSwapPool::unpack(&address, &program_id, &data).unwrap();
+        assert_eq!(pool.token_a, Pubkey::new_from_array([1u8; 32]));
+        assert_eq!(pool.token_b_mint, Pubkey::new_from_array([5u8; 32]));
+        assert_eq!(pool.admin_fee_account_b, Pubkey::new_from_array([7u8; 32]));
+
+        let leg = pool.leg(&pool.token_a_mint).unwrap();
+        assert!(leg.validate().is_ok());
+        assert_eq!(leg.accounts[2].pubkey, pool.token_a);
+        assert_eq!(leg.accounts[4].pubkey, pool.admin_fee_account_b);
+        let leg = pool.leg(&pool.token_b_mint).unwrap();
+        assert_eq!(leg.accounts[4].pubkey, pool.admin_fee_account_a);
+        assert!(pool.leg(&Pubkey::new_unique()).is_err());
+    }
+
+    #[test]
+    fn test_inventory_leg() {
+        let program_id = Pubkey::new_unique();
+        let source_mint = Pubkey::new_unique();
+        let destination_mint = Pubkey::new_unique();
+        let oracle = Pubkey::new_unique();
+        let leg = inventory_leg(
+            &program_id,
+            &source_mint,
+            &destination_mint,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &oracle,
+        );
+        assert_eq!(leg.dex_type, DEX_TYPE_INVENTORY);
+        assert_eq!(
+            leg.accounts[0].pubkey,
+            Processor::find_inventory_pair_address(&program_id, &source_mint, &destination_mint).0
+        );
+        assert_eq!(
+            leg.accounts[1].pubkey,
+            Processor::find_inventory_authority(&program_id).0
+        );
+        assert_eq!(leg.accounts[4].pubkey, oracle);
+        assert_eq!(leg.accounts.len(), 8);
+    }
+}
@@ -0,0 +1,2452 @@
+//! Instruction builders
+
+use crate::dex::{DexLeg, SerumMarket};
+use onesol::{
+    bridge,
+    error::OneSolError,
+    instruction::{
+        BatchSwapEntry, BridgeTransfer, CommitSwap, ConfigParams, ConfigureInventoryPair,
+        ConfigurePairGate, ConfigurePairRewards, DexConfig, FlashBorrow, FlashRepay, InitStakePool,
+        Initialize, InventoryAmount, OneSolInstruction, SetConfigAdmin, SetFeeRate,
+        SetReferralFeeCap, StakeAmount, Swap, TransferAdmin, MAX_BATCH_SWAPS, MAX_LEGS,
+        MAX_SPLIT_PARTS, NO_ORACLE_BOUND,
+    },
+    math::BPS_SCALE,
+    processor::Processor,
+    state::{route_hash, swap_commitment, StakeTier, STAKE_TIERS},
+};
+use solana_program::{
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use spl_token::state::Account;
+
+/// Fixed accounts of a swap, preceding the dex accounts of its legs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapAccounts {
+    /// 1sol protocol account
+    pub protocol: Pubkey,
+    /// 1sol protocol authority, see [protocol_authority]
+    pub protocol_authority: Pubkey,
+    /// User transfer authority, signing the swap
+    pub user_transfer_authority: Pubkey,
+    /// 1sol protocol DESTINATION token account
+    pub protocol_token: Pubkey,
+    /// User SOURCE token account
+    pub source: Pubkey,
+    /// User DESTINATION token account
+    pub destination: Pubkey,
+    /// Token program id
+    pub token_program_id: Pubkey,
+}
+
+impl SwapAccounts {
+    /// Checks the swap of `amount_in` can execute against the token accounts it names, as
+    /// fetched: the user transfer authority can move `amount_in` of `source`, and
+    /// `destination` and `protocol_token` hold the output mint, another than the input.
+    pub fn validate_token_accounts(
+        &self,
+        amount_in: u64,
+        source: &Account,
+        destination: &Account,
+        protocol_token: &Account,
+    ) -> Result<(), ProgramError> {
+        if source.mint == destination.mint || protocol_token.mint != destination.mint {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        if source.owner != self.user_transfer_authority {
+            if source.delegate != Some(self.user_transfer_authority).into() {
+                return Err(OneSolError::InvalidOwner.into());
+            }
+            if source.delegated_amount < amount_in {
+                return Err(OneSolError::InsufficientDelegation.into());
+            }
+        }
+        if source.amount < amount_in {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        Ok(())
+    }
+}
+
+/// Fixed accounts of a flash swap, preceding the callback and dex accounts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlashSwapAccounts {
+    /// 1sol protocol account
+    pub protocol: Pubkey,
+    /// 1sol protocol authority, see [protocol_authority]
+    pub protocol_authority: Pubkey,
+    /// 1sol protocol DESTINATION token account
+    pub protocol_token: Pubkey,
+    /// Protocol SOURCE vault, owned by the protocol authority
+    pub vault: Pubkey,
+    /// Borrower DESTINATION token account
+    pub destination: Pubkey,
+    /// Token program id
+    pub token_program_id: Pubkey,
+}
+
+/// A user swap of a batch swap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchSwapUser {
+    /// User transfer authority, signing the swap
+    pub user_transfer_authority: Pubkey,
+    /// User SOURCE token account
+    pub source: Pubkey,
+    /// User DESTINATION token account
+    pub destination: Pubkey,
+    /// SOURCE amount to transfer
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output
+    pub minimum_amount_out: u64,
+    /// Trailing accounts of the swap, e.g. its swap receipt
+    pub trailing_accounts: Vec<AccountMeta>,
+}
+
+/// A pair swap of a swap batch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapBatchPair {
+    /// 1sol protocol DESTINATION token account
+    pub protocol_token: Pubkey,
+    /// User SOURCE token account
+    pub source: Pubkey,
+    /// User DESTINATION token account
+    pub destination: Pubkey,
+    /// SOURCE amount to transfer
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output
+    pub minimum_amount_out: u64,
+    /// Legs of the route of the swap, with their ratios
+    pub legs: Vec<(DexLeg, u16)>,
+    /// Trailing accounts of the swap, e.g. its swap receipt
+    pub trailing_accounts: Vec<AccountMeta>,
+}
+
+/// Token accounts of the stake pool, owned by the [stake_pool_authority].
+#[derive(Clone, Debug, PartialEq)]
+pub struct StakeVaults {
+    /// Vault of the staked 1SOL
+    pub vault: Pubkey,
+    /// Vault of the rewards
+    pub rewards_vault: Pubkey,
+}
+
+/// Returns the authority of the 1sol protocol account `protocol` created with `nonce`.
+pub fn protocol_authority(
+    program_id: &Pubkey,
+    protocol: &Pubkey,
+    nonce: u8,
+) -> Result<Pubkey, ProgramError> {
+    Processor::authority_id(program_id, protocol, nonce).map_err(Into::into)
+}
+
+/// Creates an `Initialize` instruction.
+pub fn initialize(
+    program_id: &Pubkey,
+    protocol: &Pubkey,
+    authority: &Pubkey,
+    token: &Pubkey,
+    token_program_id: &Pubkey,
+    admin: &Pubkey,
+    nonce: u8,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*protocol, true),
+            AccountMeta::new_readonly(*authority, false),
+            AccountMeta::new_readonly(*token, false),
+            AccountMeta::new_readonly(*token_program_id, false),
+            AccountMeta::new_readonly(*admin, true),
+        ],
+        data: OneSolInstruction::Initialize(Initialize { nonce }).pack(),
+    }
+}
+
+/// Creates a `SetFeeRate` instruction making the swaps of `protocol` pay
+/// `fee_numerator / fee_denominator` of their output into `treasury`.
+pub fn set_fee_rate(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    protocol: &Pubkey,
+    treasury: &Pubkey,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Instruction {
+    let data = OneSolInstruction::SetFeeRate(SetFeeRate {
+        fee_numerator,
+        fee_denominator,
+    })
+    .pack();
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*protocol, false),
+            AccountMeta::new_readonly(*treasury, false),
+        ],
+        data,
+    }
+}
+
+/// Creates a `Pause` instruction, or an `Unpause` one if not `is_paused`, halting or
+/// resuming the swaps of `protocol`.
+pub fn set_paused(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    protocol: &Pubkey,
+    is_paused: bool,
+) -> Instruction {
+    let instruction = if is_paused {
+        OneSolInstruction::Pause
+    } else {
+        OneSolInstruction::Unpause
+    };
+    protocol_admin_instruction(program_id, admin, protocol, instruction)
+}
+
+/// Creates a `TransferAdmin` instruction handing the admin of `protocol` over to
+/// `new_admin`.
+pub fn transfer_admin(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    protocol: &Pubkey,
+    new_admin: &Pubkey,
+) -> Instruction {
+    let instruction = OneSolInstruction::TransferAdmin(TransferAdmin {
+        new_admin: *new_admin,
+    });
+    protocol_admin_instruction(program_id, admin, protocol, instruction)
+}
+
+/// Creates a `SetReferralFeeCap` instruction capping the referral fee the swaps of
+/// `protocol` may pay at `max_referral_fee_bps`.
+pub fn set_referral_fee_cap(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    protocol: &Pubkey,
+    max_referral_fee_bps: u16,
+) -> Instruction {
+    let instruction = OneSolInstruction::SetReferralFeeCap(SetReferralFeeCap {
+        max_referral_fee_bps,
+    });
+    protocol_admin_instruction(program_id, admin, protocol, instruction)
+}
+
+/// Creates a `CloseProtocolAccount` instruction sending the balance left in the
+/// `protocol_token` account of `protocol` to `destination`, then returning the lamports
+/// of `protocol` to `receiver`.
+#[allow(clippy::too_many_arguments)]
+pub fn close_protocol_account(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    protocol: &Pubkey,
+    protocol_authority: &Pubkey,
+    protocol_token: &Pubkey,
+    destination: &Pubkey,
+    receiver: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*protocol, false),
+            AccountMeta::new_readonly(*protocol_authority, false),
+            AccountMeta::new(*protocol_token, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new(*receiver, false),
+            AccountMeta::new_readonly(*token_program_id, false),
+        ],
+        data: OneSolInstruction::CloseProtocolAccount.pack(),
+    }
+}
+
+/// Returns the vault of `mint` of the 1sol protocol account `protocol`, the protocol
+/// token account of swaps into `mint`.
+pub fn protocol_vault(program_id: &Pubkey, protocol: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Processor::find_vault_address(program_id, protocol, mint).0
+}
+
+/// Creates an `InitializeVault` instruction creating the vault of `mint` of `protocol`,
+/// see [protocol_vault], `payer` paying its rent.
+pub fn initialize_vault(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    protocol: &Pubkey,
+    protocol_authority: &Pubkey,
+    mint: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*protocol, false),
+            AccountMeta::new_readonly(*protocol_authority, false),
+            AccountMeta::new(protocol_vault(program_id, protocol, mint), false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: OneSolInstruction::InitializeVault.pack(),
+    }
+}
+
+/// Returns the Serum open orders of `market` of the authority of the 1sol protocol
+/// account `protocol`.
+pub fn protocol_open_orders(program_id: &Pubkey, protocol: &Pubkey, market: &Pubkey) -> Pubkey {
+    Processor::find_open_orders_address(program_id, protocol, market).0
+}
+
+/// Creates a `CreateOpenOrders` instruction creating the open orders of `market` of the
+/// authority of `protocol`, see [protocol_open_orders], the admin paying its rent.
+pub fn create_open_orders(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    protocol: &Pubkey,
+    protocol_authority: &Pubkey,
+    market: &SerumMarket,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new_readonly(*protocol, false),
+            AccountMeta::new_readonly(*protocol_authority, false),
+            AccountMeta::new(
+                protocol_open_orders(program_id, protocol, &market.address),
+                false,
+            ),
+            AccountMeta::new_readonly(market.address, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(market.program_id, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: OneSolInstruction::CreateOpenOrders.pack(),
+    }
+}
+
+/// Creates a `CloseOpenOrders` instruction settling the open orders of `market` of the
+/// authority of `protocol` into `coin_wallet` and `pc_wallet`, then closing them,
+/// `receiver` getting their lamports.
+#[allow(clippy::too_many_arguments)]
+pub fn close_open_orders(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    protocol: &Pubkey,
+    protocol_authority: &Pubkey,
+    market: &SerumMarket,
+    coin_wallet: &Pubkey,
+    pc_wallet: &Pubkey,
+    receiver: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(*protocol, false),
+            AccountMeta::new_readonly(*protocol_authority, false),
+            AccountMeta::new(
+                protocol_open_orders(program_id, protocol, &market.address),
+                false,
+            ),
+            AccountMeta::new(market.address, false),
+            AccountMeta::new(market.coin_vault, false),
+            AccountMeta::new(market.pc_vault, false),
+            AccountMeta::new(*coin_wallet, false),
+            AccountMeta::new(*pc_wallet, false),
+            AccountMeta::new_readonly(market.vault_signer, false),
+            AccountMeta::new(*receiver, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(market.program_id, false),
+        ],
+        data: OneSolInstruction::CloseOpenOrders.pack(),
+    }
+}
+
+fn protocol_admin_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    protocol: &Pubkey,
+    instruction: OneSolInstruction,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*protocol, false),
+        ],
+        data: instruction.pack(),
+    }
+}
+
+/// Creates a `Swap` instruction routing `amount_in` through `legs`, each paired with
+/// the bps of the amount it swaps.
+pub fn swap(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    amount_in: u64,
+    minimum_amount_out: u64,
+    legs: &[(DexLeg, u16)],
+) -> Result<Instruction, ProgramError> {
+    if amount_in == 0 || accounts.source == accounts.destination {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    if accounts.protocol_token == accounts.source || accounts.protocol_token == accounts.destination
+    {
+        return Err(OneSolError::IncorrectSwapAccount.into());
+    }
+    let mut data = vec![1];
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+    data.extend_from_slice(&pack_legs(legs)?);
+
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.protocol, false),
+        AccountMeta::new_readonly(accounts.protocol_authority, false),
+        AccountMeta::new_readonly(accounts.user_transfer_authority, true),
+        AccountMeta::new(accounts.protocol_token, false),
+        AccountMeta::new(accounts.source, false),
+        AccountMeta::new(accounts.destination, false),
+        AccountMeta::new_readonly(accounts.token_program_id, false),
+    ];
+    for (leg, _) in legs.iter() {
+        metas.extend_from_slice(&leg.accounts);
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: metas,
+        data,
+    })
+}
+
+/// Makes the `Swap` instruction `ix`, created by [swap], tip `lamports` of the user
+/// transfer authority to `tip_account`, the tip account of the global config.
+pub fn tip_swap(
+    ix: &mut Instruction,
+    tip_account: &Pubkey,
+    lamports: u64,
+) -> Result<(), ProgramError> {
+    // a swap with neither an oracle bound nor a tip: tag, amounts and dex configs
+    let plain_swap = match ix.data.get(17) {
+        Some(&legs) => ix.data[0] == 1 && ix.data.len() == 18 + legs as usize * DexConfig::LEN,
+        None => false,
+    };
+    if !plain_swap || ix.accounts.len() < 7 {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    ix.data.extend_from_slice(&NO_ORACLE_BOUND.to_le_bytes());
+    ix.data.extend_from_slice(&lamports.to_le_bytes());
+    ix.accounts[2].is_writable = true;
+    let (config, _) = Processor::find_config_address(&ix.program_id);
+    ix.accounts.push(AccountMeta::new_readonly(config, false));
+    ix.accounts.push(AccountMeta::new(*tip_account, false));
+    ix.accounts
+        .push(AccountMeta::new_readonly(system_program::id(), false));
+    Ok(())
+}
+
+/// Makes the `Swap` instruction `ix`, created by [swap] and maybe tipped by [tip_swap],
+/// split `amount_in` on-chain in `split_parts` chunks across its legs, in place of their
+/// ratios, wherever the program can quote all of them.
+pub fn split_swap(ix: &mut Instruction, split_parts: u8) -> Result<(), ProgramError> {
+    let configs_end = match ix.data.get(17) {
+        Some(&legs) if ix.data[0] == 1 => 18 + legs as usize * DexConfig::LEN,
+        _ => return Err(OneSolError::InvalidInput.into()),
+    };
+    if split_parts == 0 || split_parts > MAX_SPLIT_PARTS {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    // the oracle bound and tip ahead of the split parts
+    match ix.data.len().checked_sub(configs_end) {
+        Some(0) => {
+            ix.data.extend_from_slice(&NO_ORACLE_BOUND.to_le_bytes());
+            ix.data.extend_from_slice(&0u64.to_le_bytes());
+        }
+        Some(2) => ix.data.extend_from_slice(&0u64.to_le_bytes()),
+        Some(10) => {}
+        _ => return Err(OneSolError::InvalidInput.into()),
+    }
+    ix.data.push(split_parts);
+    Ok(())
+}
+
+/// Makes the `Swap` instruction `ix`, created by [swap], bound the output of each of its
+/// legs by `minimum_amounts_out`, one per leg, a 0 bounding a leg by its share of the
+/// swap's minimum instead. The swap's minimum still bounds the sum of the legs.
+pub fn bound_legs(ix: &mut Instruction, minimum_amounts_out: &[u64]) -> Result<(), ProgramError> {
+    let minimums: Vec<u8> = minimum_amounts_out
+        .iter()
+        .flat_map(|minimum| minimum.to_le_bytes())
+        .collect();
+    let data = match OneSolInstruction::unpack(&ix.data)? {
+        OneSolInstruction::Swap(swap) if !swap.dex_configs.has_minimums() => {
+            OneSolInstruction::Swap(Swap {
+                dex_configs: swap.dex_configs.with_minimums(&minimums)?,
+                ..swap
+            })
+            .pack()
+        }
+        _ => return Err(OneSolError::InvalidInput.into()),
+    };
+    ix.data = data;
+    Ok(())
+}
+
+/// Makes the `Swap` instruction `ix`, created by [swap], pay `referral_fee_bps` of its
+/// output, net of the protocol fee, into `referrer`, a DESTINATION token account. Call
+/// it after [tip_swap] and [gate_swap], before adding the optional accounts, such as
+/// those of [pay_protocol_fee].
+pub fn refer_swap(
+    ix: &mut Instruction,
+    referrer: &Pubkey,
+    referral_fee_bps: u16,
+) -> Result<(), ProgramError> {
+    if referral_fee_bps == 0 || referral_fee_bps > BPS_SCALE {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let data = match OneSolInstruction::unpack(&ix.data)? {
+        OneSolInstruction::Swap(swap) if swap.referral_fee_bps == 0 => {
+            OneSolInstruction::Swap(Swap {
+                referral_fee_bps,
+                ..swap
+            })
+            .pack()
+        }
+        _ => return Err(OneSolError::InvalidInput.into()),
+    };
+    ix.data = data;
+    ix.accounts.push(AccountMeta::new(*referrer, false));
+    Ok(())
+}
+
+/// Creates a `SwapAndBridge` instruction routing `amount_in` through `legs`, then sending
+/// the output through the Wormhole token bridge `bridge_program_id`. `bridge_accounts`
+/// are the accounts of its `TransferNative` or `TransferWrapped` instruction, as
+/// `transfer.wrapped` selects, from the DESTINATION token account.
+#[allow(clippy::too_many_arguments)]
+pub fn swap_and_bridge(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    bridge_program_id: &Pubkey,
+    bridge_accounts: &[AccountMeta],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    transfer: &BridgeTransfer,
+    legs: &[(DexLeg, u16)],
+) -> Result<Instruction, ProgramError> {
+    if bridge_accounts.len() > u8::MAX as usize {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let mut ix = swap(program_id, accounts, amount_in, minimum_amount_out, legs)?;
+    let dex_configs = ix.data.split_off(17);
+    ix.data[0] = 19;
+    ix.data.push(transfer.wrapped as u8);
+    ix.data.extend_from_slice(&transfer.nonce.to_le_bytes());
+    ix.data
+        .extend_from_slice(&transfer.target_chain.to_le_bytes());
+    ix.data.extend_from_slice(&transfer.target_address);
+    ix.data
+        .extend_from_slice(&transfer.relayer_fee.to_le_bytes());
+    ix.data.push(bridge_accounts.len() as u8);
+    ix.data.extend_from_slice(&dex_configs);
+
+    let dex_metas = ix.accounts.split_off(7);
+    ix.accounts
+        .push(AccountMeta::new_readonly(*bridge_program_id, false));
+    ix.accounts.push(AccountMeta::new_readonly(
+        bridge::authority_signer(bridge_program_id),
+        false,
+    ));
+    ix.accounts.extend_from_slice(bridge_accounts);
+    ix.accounts.extend(dex_metas);
+    Ok(ix)
+}
+
+/// Creates a `SwapAndDeposit` instruction routing `amount_in` through `legs`, then
+/// depositing the output into a reserve of the token-lending program
+/// `lending_program_id`. `lending_accounts` are the accounts of its
+/// `DepositReserveLiquidityAndObligationCollateral` instruction, from the DESTINATION
+/// token account.
+pub fn swap_and_deposit(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    lending_program_id: &Pubkey,
+    lending_accounts: &[AccountMeta],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    legs: &[(DexLeg, u16)],
+) -> Result<Instruction, ProgramError> {
+    if lending_accounts.len() > u8::MAX as usize {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let mut ix = swap(program_id, accounts, amount_in, minimum_amount_out, legs)?;
+    let dex_configs = ix.data.split_off(17);
+    ix.data[0] = 33;
+    ix.data.push(lending_accounts.len() as u8);
+    ix.data.extend_from_slice(&dex_configs);
+
+    let dex_metas = ix.accounts.split_off(7);
+    ix.accounts
+        .push(AccountMeta::new_readonly(*lending_program_id, false));
+    ix.accounts.extend_from_slice(lending_accounts);
+    ix.accounts.extend(dex_metas);
+    Ok(ix)
+}
+
+/// Creates a `SwapAndStake` instruction routing `amount_in` through `legs` into the
+/// wrapped SOL account `accounts.destination`, closed once the swap settles, then
+/// depositing the output into an SPL stake pool of `stake_pool_program_id`.
+/// `stake_pool_accounts` are the accounts of its `DepositSol` instruction, funded by the
+/// user transfer authority.
+pub fn swap_and_stake(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    stake_pool_program_id: &Pubkey,
+    stake_pool_accounts: &[AccountMeta],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    legs: &[(DexLeg, u16)],
+) -> Result<Instruction, ProgramError> {
+    if stake_pool_accounts.len() > u8::MAX as usize {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let mut ix = swap(program_id, accounts, amount_in, minimum_amount_out, legs)?;
+    let dex_configs = ix.data.split_off(17);
+    ix.data[0] = 34;
+    ix.data.push(stake_pool_accounts.len() as u8);
+    ix.data.extend_from_slice(&dex_configs);
+
+    // the user receives the unwrapped lamports and funds the deposit with them
+    ix.accounts[2].is_writable = true;
+    let dex_metas = ix.accounts.split_off(7);
+    ix.accounts
+        .push(AccountMeta::new_readonly(*stake_pool_program_id, false));
+    ix.accounts.extend_from_slice(stake_pool_accounts);
+    ix.accounts.extend(dex_metas);
+    Ok(ix)
+}
+
+/// Creates a `SwapTwoSteps` instruction routing `amount_in` through `first_legs` into
+/// `intermediate`, a token account of the intermediate mint owned by the protocol
+/// authority, then all the first step outputs through `second_legs`.
+/// `minimum_amount_out` bounds the output of the second step only. The ratios of the
+/// legs of each step sum to [BPS_SCALE].
+pub fn swap_two_steps(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    intermediate: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+    first_legs: &[(DexLeg, u16)],
+    second_legs: &[(DexLeg, u16)],
+) -> Result<Instruction, ProgramError> {
+    if first_legs.is_empty()
+        || second_legs.is_empty()
+        || first_legs.len() + second_legs.len() > MAX_LEGS
+    {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    if *intermediate == accounts.source || *intermediate == accounts.destination {
+        return Err(OneSolError::IncorrectSwapAccount.into());
+    }
+    let mut ix = swap(
+        program_id,
+        accounts,
+        amount_in,
+        minimum_amount_out,
+        first_legs,
+    )?;
+    let second_step = pack_legs(second_legs)?;
+    let mut dex_configs = ix.data.split_off(17);
+    // a single count of the dex configs of both steps
+    dex_configs[0] += second_step[0];
+    dex_configs.extend_from_slice(&second_step[1..]);
+    ix.data[0] = 38;
+    ix.data.push(first_legs.len() as u8);
+    ix.data.extend_from_slice(&dex_configs);
+
+    let dex_metas = ix.accounts.split_off(7);
+    ix.accounts.push(AccountMeta::new(*intermediate, false));
+    ix.accounts.extend(dex_metas);
+    for (leg, _) in second_legs.iter() {
+        ix.accounts.extend_from_slice(&leg.accounts);
+    }
+    Ok(ix)
+}
+
+/// Creates a `SwapExactOut` instruction routing through `legs` the least SOURCE amount
+/// that outputs `amount_out` net of the protocol fee, each leg its share of it by
+/// ratio, spending at most `maximum_amount_in`.
+pub fn swap_exact_out(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    amount_out: u64,
+    maximum_amount_in: u64,
+    legs: &[(DexLeg, u16)],
+) -> Result<Instruction, ProgramError> {
+    if maximum_amount_in == 0 {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    // laid out as a `Swap` but for the tag, the amounts swapped
+    let mut ix = swap(program_id, accounts, amount_out, maximum_amount_in, legs)?;
+    ix.data[0] = 44;
+    Ok(ix)
+}
+
+/// Creates a `Quote` instruction quoting the swap of `amount_in` through `legs`, each
+/// paired with the bps of the amount it swaps. Simulate it and read the quote from the
+/// `quote_result` of [parse_simulation_response](crate::simulate::parse_simulation_response).
+pub fn quote(
+    program_id: &Pubkey,
+    amount_in: u64,
+    legs: &[(DexLeg, u16)],
+) -> Result<Instruction, ProgramError> {
+    if amount_in == 0 {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let mut data = vec![49];
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&pack_legs(legs)?);
+    // quoting reads the pools only
+    let accounts = legs
+        .iter()
+        .flat_map(|(leg, _)| leg.accounts.iter())
+        .map(|meta| AccountMeta::new_readonly(meta.pubkey, false))
+        .collect();
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `BatchSwap` instruction routing the swap of each of `users` through the
+/// shared `legs`. The protocol, authority and token program of `accounts` are used, its
+/// user accounts ignored.
+pub fn batch_swap(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    users: &[BatchSwapUser],
+    legs: &[(DexLeg, u16)],
+) -> Result<Instruction, ProgramError> {
+    if users.is_empty() || users.len() > MAX_BATCH_SWAPS {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let mut data = vec![32, users.len() as u8];
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.protocol, false),
+        AccountMeta::new_readonly(accounts.protocol_authority, false),
+        AccountMeta::new(accounts.protocol_token, false),
+        AccountMeta::new_readonly(accounts.token_program_id, false),
+    ];
+    for (leg, _) in legs.iter() {
+        metas.extend_from_slice(&leg.accounts);
+    }
+    for user in users.iter() {
+        if user.amount_in == 0 || user.trailing_accounts.len() > u8::MAX as usize {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        BatchSwapEntry {
+            amount_in: user.amount_in,
+            minimum_amount_out: user.minimum_amount_out,
+            trailing_accounts: user.trailing_accounts.len() as u8,
+        }
+        .pack(&mut data);
+        metas.push(AccountMeta::new_readonly(
+            user.user_transfer_authority,
+            true,
+        ));
+        metas.push(AccountMeta::new(user.source, false));
+        metas.push(AccountMeta::new(user.destination, false));
+        metas.extend_from_slice(&user.trailing_accounts);
+    }
+    data.extend_from_slice(&pack_legs(legs)?);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: metas,
+        data,
+    })
+}
+
+/// Creates a `SwapBatch` instruction routing each of `pairs` through its own legs. The
+/// protocol, authority, user transfer authority and token program of `accounts` are
+/// used, its token accounts ignored.
+pub fn swap_batch(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    pairs: &[SwapBatchPair],
+) -> Result<Instruction, ProgramError> {
+    if pairs.is_empty() || pairs.len() > MAX_BATCH_SWAPS {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let mut data = vec![50, pairs.len() as u8];
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.protocol, false),
+        AccountMeta::new_readonly(accounts.protocol_authority, false),
+        AccountMeta::new_readonly(accounts.user_transfer_authority, true),
+        AccountMeta::new_readonly(accounts.token_program_id, false),
+    ];
+    for pair in pairs.iter() {
+        if pair.amount_in == 0 || pair.trailing_accounts.len() > u8::MAX as usize {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        data.extend_from_slice(&pair.amount_in.to_le_bytes());
+        data.extend_from_slice(&pair.minimum_amount_out.to_le_bytes());
+        data.push(pair.trailing_accounts.len() as u8);
+        data.extend_from_slice(&pack_legs(&pair.legs)?);
+        metas.push(AccountMeta::new(pair.protocol_token, false));
+        metas.push(AccountMeta::new(pair.source, false));
+        metas.push(AccountMeta::new(pair.destination, false));
+        for (leg, _) in pair.legs.iter() {
+            metas.extend_from_slice(&leg.accounts);
+        }
+        metas.extend_from_slice(&pair.trailing_accounts);
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: metas,
+        data,
+    })
+}
+
+/// Creates a `RelayedSwap` instruction routing `amount_in` through `legs` and
+/// reimbursing the relayer into `relayer_token` with `fee_flat` plus `fee_bps` of the
+/// output. `minimum_amount_out` bounds what the user receives, net of the reimbursement.
+#[allow(clippy::too_many_arguments)]
+pub fn relayed_swap(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    relayer_token: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+    fee_bps: u16,
+    fee_flat: u64,
+    legs: &[(DexLeg, u16)],
+) -> Result<Instruction, ProgramError> {
+    if fee_bps > 10_000 {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let mut ix = swap(program_id, accounts, amount_in, minimum_amount_out, legs)?;
+    let dex_configs = ix.data.split_off(17);
+    ix.data[0] = 31;
+    ix.data.extend_from_slice(&fee_bps.to_le_bytes());
+    ix.data.extend_from_slice(&fee_flat.to_le_bytes());
+    ix.data.extend_from_slice(&dex_configs);
+
+    let dex_metas = ix.accounts.split_off(7);
+    let (config, _) = Processor::find_config_address(program_id);
+    ix.accounts.push(AccountMeta::new_readonly(config, false));
+    ix.accounts.push(AccountMeta::new(*relayer_token, false));
+    ix.accounts.extend(dex_metas);
+    Ok(ix)
+}
+
+/// Returns the commitment of `user` to swap `amount_in` through `legs`, blinded by
+/// `salt`. Keep the salt secret until the swap is revealed.
+pub fn route_commitment(
+    user: &Pubkey,
+    salt: &[u8; 32],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    legs: &[(DexLeg, u16)],
+) -> Hash {
+    let route = route_hash(
+        legs.iter().map(|(leg, ratio)| leg.dex_config(*ratio)),
+        legs.iter()
+            .flat_map(|(leg, _)| leg.accounts.iter().map(|meta| &meta.pubkey)),
+    );
+    swap_commitment(salt, user, amount_in, minimum_amount_out, &route)
+}
+
+/// Creates a `CommitSwap` instruction committing `user` to the swap a [reveal_swap] of
+/// the same arguments executes from the next slot on.
+pub fn commit_swap(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    salt: &[u8; 32],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    legs: &[(DexLeg, u16)],
+) -> Instruction {
+    let commitment = route_commitment(user, salt, amount_in, minimum_amount_out, legs).to_bytes();
+    let (commitment_address, _) =
+        Processor::find_swap_commitment_address(program_id, user, &commitment);
+    let data = OneSolInstruction::CommitSwap(CommitSwap { commitment }).pack();
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(commitment_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+/// Creates a `RevealSwap` instruction executing the swap committed to by the
+/// [commit_swap] of the same arguments.
+pub fn reveal_swap(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    salt: &[u8; 32],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    legs: &[(DexLeg, u16)],
+) -> Result<Instruction, ProgramError> {
+    let user = &accounts.user_transfer_authority;
+    let commitment = route_commitment(user, salt, amount_in, minimum_amount_out, legs).to_bytes();
+    let (commitment_address, _) =
+        Processor::find_swap_commitment_address(program_id, user, &commitment);
+    let mut ix = swap(program_id, accounts, amount_in, minimum_amount_out, legs)?;
+    ix.data[0] = 21;
+    ix.data.splice(1..1, salt.iter().copied());
+    ix.accounts
+        .insert(7, AccountMeta::new(commitment_address, false));
+    Ok(ix)
+}
+
+/// Creates a `FlashSwap` instruction swapping `amount_in` of the vault through `legs`,
+/// then running `callback`, which must repay the vault `amount_in` plus
+/// [FlashSwap::fee](onesol::instruction::FlashSwap::fee).
+pub fn flash_swap(
+    program_id: &Pubkey,
+    accounts: &FlashSwapAccounts,
+    amount_in: u64,
+    minimum_amount_out: u64,
+    callback: &Instruction,
+    legs: &[(DexLeg, u16)],
+) -> Result<Instruction, ProgramError> {
+    if amount_in == 0 || callback.accounts.len() > u8::MAX as usize {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let mut data = vec![16];
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+    data.push(callback.accounts.len() as u8);
+    data.extend_from_slice(&pack_legs(legs)?);
+    data.extend_from_slice(&callback.data);
+
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.protocol, false),
+        AccountMeta::new_readonly(accounts.protocol_authority, false),
+        AccountMeta::new(accounts.protocol_token, false),
+        AccountMeta::new(accounts.vault, false),
+        AccountMeta::new(accounts.destination, false),
+        AccountMeta::new_readonly(accounts.token_program_id, false),
+        AccountMeta::new_readonly(callback.program_id, false),
+    ];
+    metas.extend_from_slice(&callback.accounts);
+    for (leg, _) in legs.iter() {
+        metas.extend_from_slice(&leg.accounts);
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: metas,
+        data,
+    })
+}
+
+/// Creates a `FlashBorrow` instruction lending `amount` out of `vault` to `destination`.
+/// Follow it with the [flash_repay] of the same amount in the transaction.
+pub fn flash_borrow(
+    program_id: &Pubkey,
+    protocol: &Pubkey,
+    protocol_authority: &Pubkey,
+    vault: &Pubkey,
+    destination: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let data = OneSolInstruction::FlashBorrow(FlashBorrow { amount }).pack();
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*protocol, false),
+            AccountMeta::new_readonly(*protocol_authority, false),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*token_program_id, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+        data,
+    }
+}
+
+/// Creates a `FlashRepay` instruction repaying a flash loan of `amount`, plus its fee,
+/// from `source` into `vault`.
+pub fn flash_repay(
+    program_id: &Pubkey,
+    vault: &Pubkey,
+    source: &Pubkey,
+    authority: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let data = OneSolInstruction::FlashRepay(FlashRepay { amount }).pack();
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*source, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(*token_program_id, false),
+        ],
+        data,
+    }
+}
+
+/// Returns the SPL Governance account governance of `governed_account` in `realm`, the
+/// config admin to hand the config to for proposals to govern it.
+pub fn governance_address(
+    governance_program_id: &Pubkey,
+    realm: &Pubkey,
+    governed_account: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"account-governance",
+            realm.as_ref(),
+            governed_account.as_ref(),
+        ],
+        governance_program_id,
+    )
+    .0
+}
+
+/// Creates an `UpdateConfig` instruction. With a governance admin, it is executed as a
+/// proposal instruction.
+pub fn update_config(program_id: &Pubkey, admin: &Pubkey, params: &ConfigParams) -> Instruction {
+    let data = OneSolInstruction::UpdateConfig(params.clone()).pack();
+    let (config, _) = Processor::find_config_address(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(config, false),
+        ],
+        data,
+    }
+}
+
+/// Creates a `SetConfigAdmin` instruction handing the config to `new_admin`, e.g. a
+/// [governance_address].
+pub fn set_config_admin(program_id: &Pubkey, admin: &Pubkey, new_admin: &Pubkey) -> Instruction {
+    let data = OneSolInstruction::SetConfigAdmin(SetConfigAdmin {
+        new_admin: *new_admin,
+    })
+    .pack();
+    let (config, _) = Processor::find_config_address(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(config, false),
+        ],
+        data,
+    }
+}
+
+/// Returns the stake pool authority, owner of the [StakeVaults].
+pub fn stake_pool_authority(program_id: &Pubkey) -> Pubkey {
+    let (pool, _) = Processor::find_stake_pool_address(program_id);
+    Pubkey::find_program_address(&[&pool.to_bytes()[..32]], program_id).0
+}
+
+/// Creates an `InitStakePool` instruction.
+pub fn init_stake_pool(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    vaults: &StakeVaults,
+    tiers: &[StakeTier; STAKE_TIERS],
+) -> Instruction {
+    let data = OneSolInstruction::InitStakePool(InitStakePool { tiers: *tiers }).pack();
+    let (config, _) = Processor::find_config_address(program_id);
+    let (pool, _) = Processor::find_stake_pool_address(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(pool, false),
+            AccountMeta::new_readonly(vaults.vault, false),
+            AccountMeta::new_readonly(vaults.rewards_vault, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+/// Creates a `Stake` instruction staking `amount` 1SOL of `source`, paying the pending
+/// rewards to `rewards_destination`.
+pub fn stake(
+    program_id: &Pubkey,
+    vaults: &StakeVaults,
+    staker: &Pubkey,
+    source: &Pubkey,
+    rewards_destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut ix = staking_instruction(
+        program_id,
+        OneSolInstruction::Stake(StakeAmount { amount }),
+        vaults,
+        staker,
+        source,
+        rewards_destination,
+    );
+    ix.accounts[0].is_writable = true;
+    ix.accounts
+        .push(AccountMeta::new_readonly(system_program::id(), false));
+    ix
+}
+
+/// Creates an `Unstake` instruction unstaking `amount` 1SOL into `destination`, paying
+/// the pending rewards to `rewards_destination`.
+pub fn unstake(
+    program_id: &Pubkey,
+    vaults: &StakeVaults,
+    staker: &Pubkey,
+    destination: &Pubkey,
+    rewards_destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    staking_instruction(
+        program_id,
+        OneSolInstruction::Unstake(StakeAmount { amount }),
+        vaults,
+        staker,
+        destination,
+        rewards_destination,
+    )
+}
+
+fn staking_instruction(
+    program_id: &Pubkey,
+    instruction: OneSolInstruction,
+    vaults: &StakeVaults,
+    staker: &Pubkey,
+    token_account: &Pubkey,
+    rewards_destination: &Pubkey,
+) -> Instruction {
+    let (pool, _) = Processor::find_stake_pool_address(program_id);
+    let (stake, _) = Processor::find_stake_address(program_id, staker);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*staker, true),
+            AccountMeta::new(pool, false),
+            AccountMeta::new_readonly(stake_pool_authority(program_id), false),
+            AccountMeta::new(stake, false),
+            AccountMeta::new(*token_account, false),
+            AccountMeta::new(vaults.vault, false),
+            AccountMeta::new(vaults.rewards_vault, false),
+            AccountMeta::new(*rewards_destination, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: instruction.pack(),
+    }
+}
+
+/// Creates a `FundStakeRewards` instruction funding `amount` of `source` as rewards.
+pub fn fund_stake_rewards(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    source: &Pubkey,
+    rewards_vault: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let data = OneSolInstruction::FundStakeRewards(StakeAmount { amount }).pack();
+    let (config, _) = Processor::find_config_address(program_id);
+    let (pool, _) = Processor::find_stake_pool_address(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(pool, false),
+            AccountMeta::new(*source, false),
+            AccountMeta::new(*rewards_vault, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data,
+    }
+}
+
+/// Makes the [flash_swap] or [flash_repay] instruction `ix` discount its fee by the
+/// stake of `staker`, the borrower DESTINATION token account owner of a flash swap or
+/// the signer of a repay.
+pub fn discount_by_stake(ix: &mut Instruction, staker: &Pubkey) {
+    let (pool, _) = Processor::find_stake_pool_address(&ix.program_id);
+    let (stake, _) = Processor::find_stake_address(&ix.program_id, staker);
+    ix.accounts.push(AccountMeta::new_readonly(pool, false));
+    ix.accounts.push(AccountMeta::new_readonly(stake, false));
+}
+
+/// Creates a `ConfigurePairRewards` instruction setting the points a swap from
+/// `source_mint` to `destination_mint` earns per
+/// [POINTS_VOLUME_UNIT](onesol::state::POINTS_VOLUME_UNIT) swapped.
+pub fn configure_pair_rewards(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    source_mint: &Pubkey,
+    destination_mint: &Pubkey,
+    points_per_unit: u64,
+) -> Instruction {
+    let data =
+        OneSolInstruction::ConfigurePairRewards(ConfigurePairRewards { points_per_unit }).pack();
+    let (config, _) = Processor::find_config_address(program_id);
+    let (rewards, _) =
+        Processor::find_pair_rewards_address(program_id, source_mint, destination_mint);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(rewards, false),
+            AccountMeta::new_readonly(*source_mint, false),
+            AccountMeta::new_readonly(*destination_mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+/// Creates a `ConfigurePairGate` instruction requiring traders from `source_mint` to
+/// `destination_mint` to hold `min_balance` of `credential_mint` in `permissioned`
+/// builds. The default `credential_mint` opens the pair.
+pub fn configure_pair_gate(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    source_mint: &Pubkey,
+    destination_mint: &Pubkey,
+    credential_mint: &Pubkey,
+    min_balance: u64,
+) -> Instruction {
+    let data = OneSolInstruction::ConfigurePairGate(ConfigurePairGate {
+        credential_mint: *credential_mint,
+        min_balance,
+    })
+    .pack();
+    let (config, _) = Processor::find_config_address(program_id);
+    let (gate, _) = Processor::find_pair_gate_address(program_id, source_mint, destination_mint);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(gate, false),
+            AccountMeta::new_readonly(*source_mint, false),
+            AccountMeta::new_readonly(*destination_mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+/// Creates a `ConfigureInventoryPair` instruction, filling `source_mint` to
+/// `destination_mint` swaps out of the protocol-owned inventory at the `oracle` price less
+/// `spread_bps`.
+pub fn configure_inventory_pair(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    source_mint: &Pubkey,
+    destination_mint: &Pubkey,
+    oracle: &Pubkey,
+    spread_bps: u16,
+) -> Instruction {
+    let data = OneSolInstruction::ConfigureInventoryPair(ConfigureInventoryPair {
+        oracle: *oracle,
+        spread_bps,
+    })
+    .pack();
+    let (config, _) = Processor::find_config_address(program_id);
+    let (pair, _) =
+        Processor::find_inventory_pair_address(program_id, source_mint, destination_mint);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(pair, false),
+            AccountMeta::new_readonly(*source_mint, false),
+            AccountMeta::new_readonly(*destination_mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+/// Creates a `FundInventory` instruction, moving `amount` tokens of `admin_token` into
+/// the inventory `vault`.
+pub fn fund_inventory(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    admin_token: &Pubkey,
+    vault: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let data = OneSolInstruction::FundInventory(InventoryAmount { amount }).pack();
+    let (config, _) = Processor::find_config_address(program_id);
+    let (authority, _) = Processor::find_inventory_authority(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(*admin_token, false),
+            AccountMeta::new_readonly(authority, false),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data,
+    }
+}
+
+/// Creates a `WithdrawInventory` instruction, moving `amount` tokens of the inventory
+/// `vault` to `destination`.
+pub fn withdraw_inventory(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    vault: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let data = OneSolInstruction::WithdrawInventory(InventoryAmount { amount }).pack();
+    let (config, _) = Processor::find_config_address(program_id);
+    let (authority, _) = Processor::find_inventory_authority(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new_readonly(authority, false),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data,
+    }
+}
+
+/// Adds the pair gate accounts a swap of a `permissioned` build takes to the swap `ix`,
+/// or to a `FillOrder`, `ExecuteRecurringPayment` or `FlashSwap`: the gate of the
+/// `source_mint` / `destination_mint` pair and the `credential` token account of the
+/// trader. Add them before any optional account, such as those of [earn_points].
+pub fn gate_swap(
+    ix: &mut Instruction,
+    source_mint: &Pubkey,
+    destination_mint: &Pubkey,
+    credential: &Pubkey,
+) {
+    let (gate, _) =
+        Processor::find_pair_gate_address(&ix.program_id, source_mint, destination_mint);
+    ix.accounts.push(AccountMeta::new_readonly(gate, false));
+    ix.accounts
+        .push(AccountMeta::new_readonly(*credential, false));
+}
+
+/// Creates an `InitRewardPoints` instruction for the points of `owner`.
+pub fn init_reward_points(program_id: &Pubkey, payer: &Pubkey, owner: &Pubkey) -> Instruction {
+    let (points, _) = Processor::find_reward_points_address(program_id, owner);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(points, false),
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: OneSolInstruction::InitRewardPoints.pack(),
+    }
+}
+
+/// Creates a `ClaimRewards` instruction redeeming the points of `owner` from
+/// `rewards_vault` into `destination`.
+pub fn claim_rewards(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    rewards_vault: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    let (points, _) = Processor::find_reward_points_address(program_id, owner);
+    let (authority, _) = Processor::find_rewards_vault_authority(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(points, false),
+            AccountMeta::new_readonly(authority, false),
+            AccountMeta::new(*rewards_vault, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: OneSolInstruction::ClaimRewards.pack(),
+    }
+}
+
+/// Makes the `Swap` instruction `ix`, created by [swap], earn its user transfer authority
+/// the liquidity-mining points of the `source_mint` / `destination_mint` pair.
+pub fn earn_points(ix: &mut Instruction, source_mint: &Pubkey, destination_mint: &Pubkey) {
+    let (rewards, _) =
+        Processor::find_pair_rewards_address(&ix.program_id, source_mint, destination_mint);
+    let (points, _) = Processor::find_reward_points_address(&ix.program_id, &ix.accounts[2].pubkey);
+    ix.accounts.push(AccountMeta::new(rewards, false));
+    ix.accounts.push(AccountMeta::new(points, false));
+}
+
+/// Makes the swap instruction `ix` pay the protocol fee into `treasury`, the treasury
+/// of its 1sol protocol account.
+pub fn pay_protocol_fee(ix: &mut Instruction, treasury: &Pubkey) {
+    ix.accounts.push(AccountMeta::new(*treasury, false));
+}
+
+/// Makes the swap instruction `ix` pay out with `TransferChecked` against
+/// `destination_mint`, as Token-2022 mints with a transfer fee require.
+pub fn check_destination_mint(ix: &mut Instruction, destination_mint: &Pubkey) {
+    ix.accounts
+        .push(AccountMeta::new_readonly(*destination_mint, false));
+}
+
+/// Returns the swap statistics account of `mint`.
+pub fn mint_stats_address(program_id: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Processor::find_mint_stats_address(program_id, mint).0
+}
+
+/// Creates an `InitMintStats` instruction for the swap statistics of `mint`.
+pub fn init_mint_stats(program_id: &Pubkey, payer: &Pubkey, mint: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(mint_stats_address(program_id, mint), false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: OneSolInstruction::InitMintStats.pack(),
+    }
+}
+
+/// Makes the swap instruction `ix` record itself in the statistics of `mints`, each
+/// its source or destination mint.
+pub fn record_mint_stats(ix: &mut Instruction, mints: &[Pubkey]) {
+    let program_id = ix.program_id;
+    ix.accounts.extend(
+        mints
+            .iter()
+            .map(|mint| AccountMeta::new(mint_stats_address(&program_id, mint), false)),
+    );
+}
+
+/// Checks `legs` as the program does before executing them: at most [MAX_LEGS] legs,
+/// ratios summing to [BPS_SCALE], each leg through a dex the program routes through
+/// with the accounts it takes.
+pub fn validate_legs(legs: &[(DexLeg, u16)]) -> Result<(), ProgramError> {
+    if legs.is_empty() || legs.len() > MAX_LEGS {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    if legs.iter().map(|(_, ratio)| *ratio as u32).sum::<u32>() != BPS_SCALE as u32 {
+        return Err(OneSolError::RatioSumInvalid.into());
+    }
+    legs.iter().try_for_each(|(leg, _)| leg.validate())
+}
+
+/// Validates `legs` and packs their dex configs.
+fn pack_legs(legs: &[(DexLeg, u16)]) -> Result<Vec<u8>, ProgramError> {
+    validate_legs(legs)?;
+    let dex_configs: Vec<DexConfig> = legs
+        .iter()
+        .map(|(leg, ratio)| leg.dex_config(*ratio))
+        .collect();
+    pack_dex_configs(&dex_configs)
+}
+
+/// Packs dex configs as the program unpacks them: their count, then the type, account
+/// count and ratio of each. The program routes through at most [MAX_LEGS] dexes.
+pub fn pack_dex_configs(dex_configs: &[DexConfig]) -> Result<Vec<u8>, ProgramError> {
+    if dex_configs.is_empty() || dex_configs.len() > MAX_LEGS {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let mut data = Vec::with_capacity(1 + dex_configs.len() * DexConfig::LEN);
+    data.push(dex_configs.len() as u8);
+    for dex_config in dex_configs.iter() {
+        dex_config.pack(&mut data)?;
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::DEX_TYPE_SPL_TOKEN_SWAP;
+    use onesol::state::LogLevel;
+
+    /// A spl-token-swap leg of unique accounts.
+    fn spl_token_swap_leg() -> DexLeg {
+        DexLeg {
+            dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+            accounts: (0..7)
+                .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+                .collect(),
+        }
+    }
+
+    fn swap_accounts() -> SwapAccounts {
+        SwapAccounts {
+            protocol: Pubkey::new_unique(),
+            protocol_authority: Pubkey::new_unique(),
+            user_transfer_authority: Pubkey::new_unique(),
+            protocol_token: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            token_program_id: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn test_initialize() {
+        let ix = initialize(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            254,
+        );
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::Initialize(Initialize { nonce: 254 })
+        );
+    }
+
+    #[test]
+    fn test_initialize_vault() {
+        let (program_id, protocol, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let ix = initialize_vault(
+            &program_id,
+            &Pubkey::new_unique(),
+            &protocol,
+            &Pubkey::new_unique(),
+            &mint,
+        );
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::InitializeVault
+        );
+        assert!(ix.accounts[0].is_signer);
+        assert_eq!(
+            ix.accounts[3].pubkey,
+            protocol_vault(&program_id, &protocol, &mint)
+        );
+        assert!(ix.accounts[3].is_writable);
+    }
+
+    #[test]
+    fn test_open_orders_instructions() {
+        let (program_id, admin, protocol, authority) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let market = SerumMarket {
+            address: Pubkey::new_unique(),
+            program_id: Pubkey::new_unique(),
+            vault_signer: Pubkey::new_unique(),
+            coin_mint: Pubkey::new_unique(),
+            pc_mint: Pubkey::new_unique(),
+            coin_vault: Pubkey::new_unique(),
+            pc_vault: Pubkey::new_unique(),
+            request_queue: Pubkey::new_unique(),
+            event_queue: Pubkey::new_unique(),
+            bids: Pubkey::new_unique(),
+            asks: Pubkey::new_unique(),
+        };
+        let open_orders = protocol_open_orders(&program_id, &protocol, &market.address);
+        let ix = create_open_orders(&program_id, &admin, &protocol, &authority, &market);
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::CreateOpenOrders
+        );
+        assert_eq!(ix.accounts[3].pubkey, open_orders);
+        assert_eq!(ix.accounts[6].pubkey, market.program_id);
+        let (coin_wallet, pc_wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = close_open_orders(
+            &program_id,
+            &admin,
+            &protocol,
+            &authority,
+            &market,
+            &coin_wallet,
+            &pc_wallet,
+            &admin,
+        );
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::CloseOpenOrders
+        );
+        assert_eq!(ix.accounts[3].pubkey, open_orders);
+        assert_eq!(ix.accounts.len(), 13);
+    }
+
+    #[test]
+    fn test_swap() {
+        let leg = |size: usize| DexLeg {
+            dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+            accounts: (0..size)
+                .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+                .collect(),
+        };
+        let accounts = swap_accounts();
+        let legs = vec![(leg(7), 6_000), (leg(8), 4_000)];
+        let ix = swap(&Pubkey::new_unique(), &accounts, 1_000, 900, &legs).unwrap();
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::Swap(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.amount_in, 1_000);
+        assert_eq!(unpacked.minimum_amount_out, 900);
+        assert_eq!(
+            unpacked.dex_configs.iter().collect::<Vec<_>>(),
+            vec![
+                DexConfig::new_dex_config(DEX_TYPE_SPL_TOKEN_SWAP, 7, 6_000),
+                DexConfig::new_dex_config(DEX_TYPE_SPL_TOKEN_SWAP, 8, 4_000),
+            ]
+        );
+        assert_eq!(ix.accounts.len(), 7 + 7 + 8);
+        assert!(ix.accounts[2].is_signer);
+        assert_eq!(ix.accounts[7], legs[0].0.accounts[0]);
+        assert_eq!(ix.accounts[14], legs[1].0.accounts[0]);
+
+        assert!(swap(&Pubkey::new_unique(), &accounts, 1_000, 900, &[]).is_err());
+    }
+
+    #[test]
+    fn test_flash_swap() {
+        let accounts = FlashSwapAccounts {
+            protocol: Pubkey::new_unique(),
+            protocol_authority: Pubkey::new_unique(),
+            protocol_token: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            token_program_id: Pubkey::new_unique(),
+        };
+        let callback = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new(Pubkey::new_unique(), true)],
+            data: vec![7, 7, 7],
+        };
+        let leg = spl_token_swap_leg();
+        let ix = flash_swap(
+            &Pubkey::new_unique(),
+            &accounts,
+            1_000,
+            900,
+            &callback,
+            &[(leg.clone(), 10_000)],
+        )
+        .unwrap();
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::FlashSwap(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.amount_in, 1_000);
+        assert_eq!(unpacked.minimum_amount_out, 900);
+        assert_eq!(unpacked.callback_accounts, 1);
+        assert_eq!(unpacked.dex_configs.len(), 1);
+        assert_eq!(unpacked.callback_data, &[7, 7, 7]);
+        assert_eq!(ix.accounts[6].pubkey, callback.program_id);
+        assert_eq!(ix.accounts[7], callback.accounts[0]);
+        assert_eq!(ix.accounts[8], leg.accounts[0]);
+    }
+
+    #[test]
+    fn test_flash_borrow_and_repay() {
+        let (program_id, vault) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let borrow = flash_borrow(
+            &program_id,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &vault,
+            &Pubkey::new_unique(),
+            &spl_token::id(),
+            1_000,
+        );
+        assert_eq!(
+            OneSolInstruction::unpack(&borrow.data).unwrap(),
+            OneSolInstruction::FlashBorrow(FlashBorrow { amount: 1_000 })
+        );
+        assert_eq!(borrow.accounts[2].pubkey, vault);
+        assert_eq!(borrow.accounts[5].pubkey, sysvar::instructions::id());
+
+        let authority = Pubkey::new_unique();
+        let repay = flash_repay(
+            &program_id,
+            &vault,
+            &Pubkey::new_unique(),
+            &authority,
+            &spl_token::id(),
+            1_000,
+        );
+        assert_eq!(
+            OneSolInstruction::unpack(&repay.data).unwrap(),
+            OneSolInstruction::FlashRepay(FlashRepay { amount: 1_000 })
+        );
+        assert_eq!(repay.accounts[0].pubkey, vault);
+        assert_eq!(
+            repay.accounts[2],
+            AccountMeta::new_readonly(authority, true)
+        );
+    }
+
+    #[test]
+    fn test_swap_and_bridge() {
+        let accounts = swap_accounts();
+        let bridge_program_id = Pubkey::new_unique();
+        let bridge_accounts = vec![
+            AccountMeta::new(accounts.destination, false),
+            AccountMeta::new(Pubkey::new_unique(), false),
+        ];
+        let leg = spl_token_swap_leg();
+        let transfer = BridgeTransfer {
+            wrapped: false,
+            nonce: 3,
+            target_chain: 2,
+            target_address: [1u8; 32],
+            relayer_fee: 5,
+        };
+        let ix = swap_and_bridge(
+            &Pubkey::new_unique(),
+            &accounts,
+            &bridge_program_id,
+            &bridge_accounts,
+            1_000,
+            900,
+            &transfer,
+            &[(leg.clone(), 10_000)],
+        )
+        .unwrap();
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::SwapAndBridge(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.amount_in, 1_000);
+        assert_eq!(unpacked.minimum_amount_out, 900);
+        assert_eq!(unpacked.transfer, transfer);
+        assert_eq!(unpacked.bridge_accounts, 2);
+        assert_eq!(unpacked.dex_configs.len(), 1);
+        assert_eq!(ix.accounts[2].pubkey, accounts.user_transfer_authority);
+        assert_eq!(ix.accounts[7].pubkey, bridge_program_id);
+        assert_eq!(
+            ix.accounts[8].pubkey,
+            bridge::authority_signer(&bridge_program_id)
+        );
+        assert_eq!(ix.accounts[9..11], bridge_accounts[..]);
+        assert_eq!(ix.accounts[11], leg.accounts[0]);
+    }
+
+    #[test]
+    fn test_swap_and_deposit() {
+        let accounts = swap_accounts();
+        let lending_program_id = Pubkey::new_unique();
+        let lending_accounts = vec![
+            AccountMeta::new(accounts.destination, false),
+            AccountMeta::new(Pubkey::new_unique(), false),
+        ];
+        let leg = spl_token_swap_leg();
+        let ix = swap_and_deposit(
+            &Pubkey::new_unique(),
+            &accounts,
+            &lending_program_id,
+            &lending_accounts,
+            1_000,
+            900,
+            &[(leg.clone(), 10_000)],
+        )
+        .unwrap();
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::SwapAndDeposit(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.amount_in, 1_000);
+        assert_eq!(unpacked.minimum_amount_out, 900);
+        assert_eq!(unpacked.lending_accounts, 2);
+        assert_eq!(ix.accounts[7].pubkey, lending_program_id);
+        assert_eq!(ix.accounts[8..10], lending_accounts[..]);
+        assert_eq!(ix.accounts[10], leg.accounts[0]);
+    }
+
+    #[test]
+    fn test_swap_and_stake() {
+        let accounts = swap_accounts();
+        let stake_pool_program_id = Pubkey::new_unique();
+        let stake_pool_accounts = vec![
+            AccountMeta::new(Pubkey::new_unique(), false),
+            AccountMeta::new(accounts.user_transfer_authority, true),
+        ];
+        let leg = spl_token_swap_leg();
+        let ix = swap_and_stake(
+            &Pubkey::new_unique(),
+            &accounts,
+            &stake_pool_program_id,
+            &stake_pool_accounts,
+            1_000,
+            900,
+            &[(leg.clone(), 10_000)],
+        )
+        .unwrap();
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::SwapAndStake(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.amount_in, 1_000);
+        assert_eq!(unpacked.minimum_amount_out, 900);
+        assert_eq!(unpacked.stake_pool_accounts, 2);
+        assert!(ix.accounts[2].is_writable);
+        assert_eq!(ix.accounts[7].pubkey, stake_pool_program_id);
+        assert_eq!(ix.accounts[8..10], stake_pool_accounts[..]);
+        assert_eq!(ix.accounts[10], leg.accounts[0]);
+    }
+
+    #[test]
+    fn test_swap_two_steps() {
+        let accounts = swap_accounts();
+        let intermediate = Pubkey::new_unique();
+        let leg = spl_token_swap_leg();
+        let ix = swap_two_steps(
+            &Pubkey::new_unique(),
+            &accounts,
+            &intermediate,
+            1_000,
+            900,
+            &[(leg.clone(), 10_000)],
+            &[(leg.clone(), 5_000), (leg.clone(), 5_000)],
+        )
+        .unwrap();
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::SwapTwoSteps(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.amount_in, 1_000);
+        assert_eq!(unpacked.minimum_amount_out, 900);
+        assert_eq!(unpacked.steps().1.len(), 2);
+        assert_eq!(ix.accounts[7], AccountMeta::new(intermediate, false));
+        assert_eq!(ix.accounts[8], leg.accounts[0]);
+        assert_eq!(ix.accounts.len(), 8 + 3 * leg.accounts.len());
+
+        assert!(swap_two_steps(
+            &Pubkey::new_unique(),
+            &accounts,
+            &intermediate,
+            1_000,
+            900,
+            &[(leg.clone(), 10_000)],
+            &[],
+        )
+        .is_err());
+        // the ratios of each step sum to 10_000 bps
+        assert_eq!(
+            swap_two_steps(
+                &Pubkey::new_unique(),
+                &accounts,
+                &intermediate,
+                1_000,
+                900,
+                &[(leg.clone(), 5_000)],
+                &[(leg, 5_000)],
+            ),
+            Err(OneSolError::RatioSumInvalid.into())
+        );
+    }
+
+    #[test]
+    fn test_batch_swap() {
+        let accounts = swap_accounts();
+        let leg = spl_token_swap_leg();
+        let user = |trailing_accounts: Vec<AccountMeta>| BatchSwapUser {
+            user_transfer_authority: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            amount_in: 1_000,
+            minimum_amount_out: 900,
+            trailing_accounts,
+        };
+        let receipt = AccountMeta::new(Pubkey::new_unique(), false);
+        let users = vec![user(vec![]), user(vec![receipt.clone()])];
+        let ix = batch_swap(
+            &Pubkey::new_unique(),
+            &accounts,
+            &users,
+            &[(leg.clone(), 10_000)],
+        )
+        .unwrap();
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::BatchSwap(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        let entries = unpacked.swaps.iter().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].trailing_accounts, 1);
+        assert_eq!(unpacked.dex_configs.len(), 1);
+        assert_eq!(ix.accounts.len(), 4 + 7 + 3 + 4);
+        assert_eq!(ix.accounts[4..11], leg.accounts[..]);
+        assert_eq!(
+            ix.accounts[14],
+            AccountMeta::new_readonly(users[1].user_transfer_authority, true)
+        );
+        assert_eq!(ix.accounts[17], receipt);
+
+        assert!(batch_swap(&Pubkey::new_unique(), &accounts, &[], &[(leg, 10_000)]).is_err());
+    }
+
+    #[test]
+    fn test_swap_batch() {
+        let accounts = swap_accounts();
+        let leg = spl_token_swap_leg();
+        let receipt = AccountMeta::new(Pubkey::new_unique(), false);
+        let pair = |legs: Vec<(DexLeg, u16)>, trailing_accounts: Vec<AccountMeta>| SwapBatchPair {
+            protocol_token: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            amount_in: 1_000,
+            minimum_amount_out: 900,
+            legs,
+            trailing_accounts,
+        };
+        let pairs = vec![
+            pair(vec![(leg.clone(), 10_000)], vec![]),
+            pair(
+                vec![(leg.clone(), 5_000), (leg.clone(), 5_000)],
+                vec![receipt.clone()],
+            ),
+        ];
+        let ix = swap_batch(&Pubkey::new_unique(), &accounts, &pairs).unwrap();
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::SwapBatch(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        let entries = unpacked.swaps.iter().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].dex_configs.len(), 1);
+        assert_eq!(entries[1].dex_configs.len(), 2);
+        assert_eq!(entries[1].trailing_accounts, 1);
+        assert_eq!(
+            ix.accounts[2],
+            AccountMeta::new_readonly(accounts.user_transfer_authority, true)
+        );
+        assert_eq!(ix.accounts.len(), 4 + (3 + 7) + (3 + 14 + 1));
+        assert_eq!(
+            ix.accounts[4],
+            AccountMeta::new(pairs[0].protocol_token, false)
+        );
+        assert_eq!(ix.accounts[7..14], leg.accounts[..]);
+        assert_eq!(
+            ix.accounts[14],
+            AccountMeta::new(pairs[1].protocol_token, false)
+        );
+        assert_eq!(ix.accounts[31], receipt);
+
+        assert!(swap_batch(&Pubkey::new_unique(), &accounts, &[]).is_err());
+        let unbalanced = pair(vec![(leg, 5_000)], vec![]);
+        assert!(swap_batch(&Pubkey::new_unique(), &accounts, &[unbalanced]).is_err());
+    }
+
+    #[test]
+    fn test_relayed_swap() {
+        let program_id = Pubkey::new_unique();
+        let accounts = swap_accounts();
+        let relayer_token = Pubkey::new_unique();
+        let leg = spl_token_swap_leg();
+        let ix = relayed_swap(
+            &program_id,
+            &accounts,
+            &relayer_token,
+            1_000,
+            900,
+            30,
+            5,
+            &[(leg.clone(), 10_000)],
+        )
+        .unwrap();
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::RelayedSwap(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.amount_in, 1_000);
+        assert_eq!(unpacked.minimum_amount_out, 900);
+        assert_eq!(unpacked.fee_bps, 30);
+        assert_eq!(unpacked.fee_flat, 5);
+        assert_eq!(unpacked.dex_configs.len(), 1);
+        assert_eq!(
+            ix.accounts[7].pubkey,
+            Processor::find_config_address(&program_id).0
+        );
+        assert_eq!(ix.accounts[8], AccountMeta::new(relayer_token, false));
+        assert_eq!(ix.accounts[9], leg.accounts[0]);
+    }
+
+    #[test]
+    fn test_commit_and_reveal_swap() {
+        let program_id = Pubkey::new_unique();
+        let accounts = swap_accounts();
+        let legs = vec![(spl_token_swap_leg(), 10_000)];
+        let salt = [9u8; 32];
+        let user = accounts.user_transfer_authority;
+        let commit = commit_swap(&program_id, &user, &salt, 1_000, 900, &legs);
+        let commitment = match OneSolInstruction::unpack(&commit.data).unwrap() {
+            OneSolInstruction::CommitSwap(commit) => commit.commitment,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(
+            commitment,
+            route_commitment(&user, &salt, 1_000, 900, &legs).to_bytes()
+        );
+        // the commitment discloses neither the route nor the amounts
+        assert_ne!(
+            commitment,
+            route_commitment(&user, &salt, 1_000, 901, &legs).to_bytes()
+        );
+
+        let reveal = reveal_swap(&program_id, &accounts, &salt, 1_000, 900, &legs).unwrap();
+        let unpacked = match OneSolInstruction::unpack(&reveal.data).unwrap() {
+            OneSolInstruction::RevealSwap(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.salt, salt);
+        assert_eq!(unpacked.amount_in, 1_000);
+        assert_eq!(unpacked.minimum_amount_out, 900);
+        assert_eq!(reveal.accounts[7], commit.accounts[1]);
+        assert_eq!(reveal.accounts[8], legs[0].0.accounts[0]);
+    }
+
+    #[test]
+    fn test_tip_swap() {
+        let program_id = Pubkey::new_unique();
+        let leg = spl_token_swap_leg();
+        let mut ix = swap(&program_id, &swap_accounts(), 1_000, 900, &[(leg, 10_000)]).unwrap();
+        let tip_account = Pubkey::new_unique();
+        tip_swap(&mut ix, &tip_account, 10_000).unwrap();
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::Swap(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.oracle_max_deviation_bps, None);
+        assert_eq!(unpacked.tip_lamports, 10_000);
+        assert!(ix.accounts[2].is_signer && ix.accounts[2].is_writable);
+        assert_eq!(
+            ix.accounts[14].pubkey,
+            Processor::find_config_address(&program_id).0
+        );
+        assert_eq!(ix.accounts[15], AccountMeta::new(tip_account, false));
+        // a tip is appended once, to a plain swap
+        assert!(tip_swap(&mut ix, &tip_account, 10_000).is_err());
+    }
+
+    #[test]
+    fn test_mint_stats_instructions() {
+        let program_id = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let stats = mint_stats_address(&program_id, &mint);
+        let ix = init_mint_stats(&program_id, &payer, &mint);
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::InitMintStats
+        );
+        assert_eq!(ix.accounts[0], AccountMeta::new(payer, true));
+        assert_eq!(ix.accounts[1], AccountMeta::new(stats, false));
+
+        let leg = spl_token_swap_leg();
+        let mut ix = swap(&program_id, &swap_accounts(), 1_000, 900, &[(leg, 10_000)]).unwrap();
+        let len = ix.accounts.len();
+        let other_mint = Pubkey::new_unique();
+        record_mint_stats(&mut ix, &[mint, other_mint]);
+        assert_eq!(ix.accounts[len], AccountMeta::new(stats, false));
+        assert_eq!(
+            ix.accounts[len + 1].pubkey,
+            mint_stats_address(&program_id, &other_mint)
+        );
+    }
+
+    #[test]
+    fn test_swap_exact_out() {
+        let program_id = Pubkey::new_unique();
+        let legs = [(spl_token_swap_leg(), 10_000)];
+        let ix = swap_exact_out(&program_id, &swap_accounts(), 990, 1_000, &legs).unwrap();
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::SwapExactOut(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.amount_out, 990);
+        assert_eq!(unpacked.maximum_amount_in, 1_000);
+        assert_eq!(unpacked.dex_configs.len(), 1);
+        assert_eq!(ix.accounts.len(), 7 + legs[0].0.accounts.len());
+        assert!(swap_exact_out(&program_id, &swap_accounts(), 990, 0, &legs).is_err());
+    }
+
+    #[test]
+    fn test_quote() {
+        let legs = vec![(spl_token_swap_leg(), 4_000), (spl_token_swap_leg(), 6_000)];
+        let ix = quote(&Pubkey::new_unique(), 1_000, &legs).unwrap();
+        match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::Quote(data) => {
+                assert_eq!(data.amount_in, 1_000);
+                assert_eq!(data.dex_configs.len(), 2);
+            }
+            instruction => panic!("unexpected {:?}", instruction),
+        }
+        assert_eq!(ix.accounts.len(), 14);
+        assert!(ix.accounts.iter().all(|meta| !meta.is_writable));
+        assert!(quote(&Pubkey::new_unique(), 0, &legs).is_err());
+    }
+
+    #[test]
+    fn test_split_swap() {
+        let program_id = Pubkey::new_unique();
+        let leg = spl_token_swap_leg();
+        let mut ix = swap(&program_id, &swap_accounts(), 1_000, 900, &[(leg, 10_000)]).unwrap();
+        assert!(split_swap(&mut ix, MAX_SPLIT_PARTS + 1).is_err());
+        split_swap(&mut ix, 10).unwrap();
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::Swap(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.oracle_max_deviation_bps, None);
+        assert_eq!(unpacked.tip_lamports, 0);
+        assert_eq!(unpacked.split_parts, 10);
+        assert!(split_swap(&mut ix, 10).is_err());
+    }
+
+    #[test]
+    fn test_bound_legs() {
+        let program_id = Pubkey::new_unique();
+        let legs = [(spl_token_swap_leg(), 4_000), (spl_token_swap_leg(), 6_000)];
+        let mut ix = swap(&program_id, &swap_accounts(), 1_000, 900, &legs).unwrap();
+        assert!(bound_legs(&mut ix, &[350]).is_err());
+        bound_legs(&mut ix, &[0, 550]).unwrap();
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::Swap(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.minimum_amount_out, 900);
+        let minimums: Vec<_> = unpacked
+            .dex_configs
+            .iter()
+            .map(|config| config.minimum_amount_out)
+            .collect();
+        assert_eq!(minimums, vec![None, Some(550)]);
+        assert!(bound_legs(&mut ix, &[0, 550]).is_err());
+    }
+
+    #[test]
+    fn test_refer_swap() {
+        let program_id = Pubkey::new_unique();
+        let referrer = Pubkey::new_unique();
+        let legs = [(spl_token_swap_leg(), 10_000)];
+        let mut ix = swap(&program_id, &swap_accounts(), 1_000, 900, &legs).unwrap();
+        bound_legs(&mut ix, &[850]).unwrap();
+        assert!(refer_swap(&mut ix, &referrer, 0).is_err());
+        refer_swap(&mut ix, &referrer, 20).unwrap();
+        assert_eq!(ix.accounts.last().unwrap().pubkey, referrer);
+        assert!(ix.accounts.last().unwrap().is_writable);
+
+        let unpacked = match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::Swap(unpacked) => unpacked,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(unpacked.referral_fee_bps, 20);
+        assert_eq!(
+            unpacked.dex_configs.get(0).unwrap().minimum_amount_out,
+            Some(850)
+        );
+        assert!(refer_swap(&mut ix, &referrer, 20).is_err());
+    }
+
+    #[test]
+    fn test_config_admin_instructions() {
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let params = ConfigParams {
+            oracle_max_age_slots: 25,
+            oracle_conf_multiplier: 2,
+            log_level: LogLevel::Errors,
+            tip_account: Pubkey::new_unique(),
+            relayer_fee_max_bps: 30,
+            relayer_fee_max_flat: 5_000,
+            split_parts: 8,
+        };
+        let ix = update_config(&program_id, &admin, &params);
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::UpdateConfig(params)
+        );
+
+        let governance = governance_address(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &ix.accounts[1].pubkey,
+        );
+        let ix = set_config_admin(&program_id, &admin, &governance);
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::SetConfigAdmin(SetConfigAdmin {
+                new_admin: governance
+            })
+        );
+        assert!(ix.accounts[0].is_signer);
+        assert_eq!(
+            ix.accounts[1].pubkey,
+            Processor::find_config_address(&program_id).0
+        );
+    }
+
+    #[test]
+    fn test_set_fee_rate() {
+        let program_id = Pubkey::new_unique();
+        let (admin, protocol, treasury) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let ix = set_fee_rate(&program_id, &admin, &protocol, &treasury, 25, 10_000);
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::SetFeeRate(SetFeeRate {
+                fee_numerator: 25,
+                fee_denominator: 10_000,
+            })
+        );
+        assert!(ix.accounts[0].is_signer);
+        assert!(ix.accounts[1].is_writable);
+        assert_eq!(ix.accounts[2].pubkey, treasury);
+    }
+
+    #[test]
+    fn test_protocol_admin_instructions() {
+        let program_id = Pubkey::new_unique();
+        let (admin, protocol, new_admin) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let ix = set_paused(&program_id, &admin, &protocol, true);
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::Pause
+        );
+        assert!(ix.accounts[0].is_signer);
+        assert!(ix.accounts[1].is_writable);
+        let ix = set_paused(&program_id, &admin, &protocol, false);
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::Unpause
+        );
+        let ix = transfer_admin(&program_id, &admin, &protocol, &new_admin);
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::TransferAdmin(TransferAdmin { new_admin })
+        );
+        let ix = set_referral_fee_cap(&program_id, &admin, &protocol, 50);
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::SetReferralFeeCap(SetReferralFeeCap {
+                max_referral_fee_bps: 50
+            })
+        );
+    }
+
+    #[test]
+    fn test_staking_instructions() {
+        let program_id = Pubkey::new_unique();
+        let vaults = StakeVaults {
+            vault: Pubkey::new_unique(),
+            rewards_vault: Pubkey::new_unique(),
+        };
+        let tiers = [
+            StakeTier {
+                min_stake: 100,
+                discount_bps: 1_000,
+            },
+            StakeTier::default(),
+            StakeTier::default(),
+        ];
+        let ix = init_stake_pool(&program_id, &Pubkey::new_unique(), &vaults, &tiers);
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::InitStakePool(InitStakePool { tiers })
+        );
+
+        let staker = Pubkey::new_unique();
+        let ix = stake(
+            &program_id,
+            &vaults,
+            &staker,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            500,
+        );
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::Stake(StakeAmount { amount: 500 })
+        );
+        assert_eq!(ix.accounts.len(), 10);
+        assert_eq!(
+            ix.accounts[3].pubkey,
+            Processor::find_stake_address(&program_id, &staker).0
+        );
+
+        let mut ix = flash_repay(
+            &program_id,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &staker,
+            &spl_token::id(),
+            1_000,
+        );
+        discount_by_stake(&mut ix, &staker);
+        assert_eq!(ix.accounts.len(), 6);
+        assert_eq!(
+            ix.accounts[4].pubkey,
+            Processor::find_stake_pool_address(&program_id).0
+        );
+    }
+
+    #[test]
+    fn test_inventory_instructions() {
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let source_mint = Pubkey::new_unique();
+        let destination_mint = Pubkey::new_unique();
+        let oracle = Pubkey::new_unique();
+        let ix = configure_inventory_pair(
+            &program_id,
+            &admin,
+            &source_mint,
+            &destination_mint,
+            &oracle,
+            25,
+        );
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::ConfigureInventoryPair(ConfigureInventoryPair {
+                oracle,
+                spread_bps: 25
+            })
+        );
+        assert_eq!(
+            ix.accounts[2].pubkey,
+            Processor::find_inventory_pair_address(&program_id, &source_mint, &destination_mint).0
+        );
+
+        let vault = Pubkey::new_unique();
+        let ix = fund_inventory(&program_id, &admin, &Pubkey::new_unique(), &vault, 500);
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::FundInventory(InventoryAmount { amount: 500 })
+        );
+        assert_eq!(
+            ix.accounts[3].pubkey,
+            Processor::find_inventory_authority(&program_id).0
+        );
+
+        let ix = withdraw_inventory(&program_id, &admin, &vault, &Pubkey::new_unique(), 500);
+        assert_eq!(
+            OneSolInstruction::unpack(&ix.data).unwrap(),
+            OneSolInstruction::WithdrawInventory(InventoryAmount { amount: 500 })
+        );
+        assert_eq!(ix.accounts[3], AccountMeta::new(vault, false));
+    }
+
+    #[test]
+    fn test_validate_before_sending() {
+        let program_id = Pubkey::new_unique();
+        let accounts = swap_accounts();
+        let leg = spl_token_swap_leg();
+        let swap_error = |accounts: &SwapAccounts, amount_in: u64, legs: &[(DexLeg, u16)]| {
+            swap(&program_id, accounts, amount_in, 0, legs).unwrap_err()
+        };
+        assert_eq!(
+            swap_error(&accounts, 1_000, &[(leg.clone(), 0), (leg.clone(), 0)]),
+            OneSolError::RatioSumInvalid.into()
+        );
+        let mut short = leg.clone();
+        short.accounts.truncate(6);
+        assert_eq!(
+            swap_error(&accounts, 1_000, &[(short, 10_000)]),
+            OneSolError::AccountCountMismatch.into()
+        );
+        let mut unknown = leg.clone();
+        unknown.dex_type = 9;
+        assert_eq!(
+            swap_error(&accounts, 1_000, &[(unknown, 10_000)]),
+            OneSolError::DexProgramNotAllowed.into()
+        );
+        assert_eq!(
+            swap_error(&accounts, 0, &[(leg.clone(), 10_000)]),
+            OneSolError::InvalidInput.into()
+        );
+        let mut same = accounts.clone();
+        same.destination = same.protocol_token;
+        assert_eq!(
+            swap_error(&same, 1_000, &[(leg, 10_000)]),
+            OneSolError::IncorrectSwapAccount.into()
+        );
+
+        let token = |mint: Pubkey, owner: Pubkey, amount: u64| Account {
+            mint,
+            owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Account::default()
+        };
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let user = accounts.user_transfer_authority;
+        let source = token(mint_a, user, 1_000);
+        let destination = token(mint_b, user, 0);
+        let protocol_token = token(mint_b, accounts.protocol_authority, 0);
+        assert!(accounts
+            .validate_token_accounts(1_000, &source, &destination, &protocol_token)
+            .is_ok());
+        assert_eq!(
+            accounts.validate_token_accounts(1_001, &source, &destination, &protocol_token),
+            Err(OneSolError::InvalidInput.into())
+        );
+        assert_eq!(
+            accounts.validate_token_accounts(1_000, &source, &source, &protocol_token),
+            Err(OneSolError::InvalidInput.into())
+        );
+        let mut delegated = token(mint_a, Pubkey::new_unique(), 1_000);
+        delegated.delegate = Some(user).into();
+        delegated.delegated_amount = 500;
+        assert_eq!(
+            accounts.validate_token_accounts(1_000, &delegated, &destination, &protocol_token),
+            Err(OneSolError::InsufficientDelegation.into())
+        );
+        delegated.delegate = None.into();
+        assert_eq!(
+            accounts.validate_token_accounts(1_000, &delegated, &destination, &protocol_token),
+            Err(OneSolError::InvalidOwner.into())
+        );
+    }
+}
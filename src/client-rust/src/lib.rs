@@ -0,0 +1,19 @@
+#![deny(missing_docs)]
+#![forbid(unsafe_code)]
+
+//! OneSol client - builds OneSol instructions from dex pool accounts
+
+pub mod builder;
+pub mod dex;
+pub mod instruction;
+pub mod lookup_table;
+pub mod message;
+pub mod pool_cache;
+pub mod prelude;
+pub mod quote;
+pub mod route;
+pub mod simulate;
+pub mod transaction;
+
+// export
+pub use onesol;
@@ -0,0 +1,251 @@
+//! Address lookup tables holding the recurring accounts of routes
+
+use crate::{instruction::SwapAccounts, route::Route};
+use onesol::error::OneSolError;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+};
+
+/// Address Lookup Table program
+pub mod address_lookup_table {
+    solana_program::declare_id!("AddressLookupTab1e1111111111111111111111111");
+}
+
+/// Length of the lookup table account header, preceding its addresses.
+pub const LOOKUP_TABLE_META_SIZE: usize = 56;
+
+/// Most addresses a lookup table holds.
+pub const LOOKUP_TABLE_MAX_ADDRESSES: usize = 256;
+
+/// Most addresses an `ExtendLookupTable` instruction adds within a transaction.
+pub const MAX_EXTEND_ADDRESSES: usize = 20;
+
+/// Returns the address of the lookup table created by `authority` at `recent_slot`, and
+/// its bump seed.
+pub fn find_lookup_table_address(authority: &Pubkey, recent_slot: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[authority.as_ref(), &recent_slot.to_le_bytes()],
+        &address_lookup_table::id(),
+    )
+}
+
+/// Creates a `CreateLookupTable` instruction, returning it with the table address.
+/// `recent_slot` must be a slot of the last 150.
+pub fn create_lookup_table(
+    authority: &Pubkey,
+    payer: &Pubkey,
+    recent_slot: u64,
+) -> (Instruction, Pubkey) {
+    let (lookup_table, bump_seed) = find_lookup_table_address(authority, recent_slot);
+    let mut data = 0u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&recent_slot.to_le_bytes());
+    data.push(bump_seed);
+    let instruction = Instruction {
+        program_id: address_lookup_table::id(),
+        accounts: vec![
+            AccountMeta::new(lookup_table, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    };
+    (instruction, lookup_table)
+}
+
+/// Creates an `ExtendLookupTable` instruction appending `new_addresses` to
+/// `lookup_table`, `payer` funding the rent of the larger table.
+pub fn extend_lookup_table(
+    lookup_table: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    new_addresses: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    if new_addresses.is_empty() || new_addresses.len() > MAX_EXTEND_ADDRESSES {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&(new_addresses.len() as u64).to_le_bytes());
+    for address in new_addresses.iter() {
+        data.extend_from_slice(address.as_ref());
+    }
+    Ok(Instruction {
+        program_id: address_lookup_table::id(),
+        accounts: vec![
+            AccountMeta::new(*lookup_table, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    })
+}
+
+/// Creates the `ExtendLookupTable` instructions adding the `addresses` missing from
+/// `lookup_table`, at most [MAX_EXTEND_ADDRESSES] per instruction.
+pub fn extend_lookup_table_with_missing(
+    lookup_table: &AddressLookupTable,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    addresses: &[Pubkey],
+) -> Result<Vec<Instruction>, ProgramError> {
+    let mut missing: Vec<Pubkey> = Vec::new();
+    for address in addresses.iter() {
+        if !lookup_table.addresses.contains(address) && !missing.contains(address) {
+            missing.push(*address);
+        }
+    }
+    if lookup_table.addresses.len() + missing.len() > LOOKUP_TABLE_MAX_ADDRESSES {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    missing
+        .chunks(MAX_EXTEND_ADDRESSES)
+        .map(|chunk| extend_lookup_table(&lookup_table.address, authority, payer, chunk))
+        .collect()
+}
+
+/// Address lookup table, read from its account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AddressLookupTable {
+    /// Lookup table account
+    pub address: Pubkey,
+    /// Authority allowed to extend the table, none once frozen
+    pub authority: Option<Pubkey>,
+    /// Slot the table was deactivated at, `u64::MAX` while active
+    pub deactivation_slot: u64,
+    /// Addresses of the table, in index order
+    pub addresses: Vec<Pubkey>,
+}
+
+impl AddressLookupTable {
+    /// Reads the lookup table at `address`.
+    pub fn unpack(address: &Pubkey, data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < LOOKUP_TABLE_META_SIZE
+            || !(data.len() - LOOKUP_TABLE_META_SIZE).is_multiple_of(32)
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // the bincode encoded `ProgramState::LookupTable` variant
+        if data[0..4] != 1u32.to_le_bytes() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        let mut deactivation_slot = [0u8; 8];
+        deactivation_slot.copy_from_slice(&data[4..12]);
+        let authority = match data[21] {
+            0 => None,
+            1 => Some(Pubkey::new(&data[22..54])),
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(AddressLookupTable {
+            address: *address,
+            authority,
+            deactivation_slot: u64::from_le_bytes(deactivation_slot),
+            addresses: data[LOOKUP_TABLE_META_SIZE..]
+                .chunks(32)
+                .map(Pubkey::new)
+                .collect(),
+        })
+    }
+}
+
+/// Returns the accounts a swap through `route` shares with other users' swaps: the
+/// protocol accounts, the token program and the dex accounts of its legs. A lookup
+/// table holding them shrinks the swap transaction by 31 bytes per account.
+pub fn route_addresses(route: &Route, accounts: &SwapAccounts) -> Vec<Pubkey> {
+    let mut addresses = vec![
+        accounts.protocol,
+        accounts.protocol_authority,
+        accounts.protocol_token,
+        accounts.token_program_id,
+    ];
+    for (leg, _) in route.legs.iter() {
+        for meta in leg.accounts.iter() {
+            if !addresses.contains(&meta.pubkey) {
+                addresses.push(meta.pubkey);
+            }
+        }
+    }
+    addresses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup_table_data(authority: Option<&Pubkey>, addresses: &[Pubkey]) -> Vec<u8> {
+        let mut data = 1u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&u64::MAX.to_le_bytes());
+        data.extend_from_slice(&[0u8; 9]);
+        match authority {
+            Some(authority) => {
+                data.push(1);
+                data.extend_from_slice(authority.as_ref());
+            }
+            None => data.extend_from_slice(&[0u8; 33]),
+        }
+        data.extend_from_slice(&[0u8; 2]);
+        for address in addresses.iter() {
+            data.extend_from_slice(address.as_ref());
+        }
+        data
+    }
+
+    #[test]
+    fn test_unpack_lookup_table() {
+        let address = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let addresses = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let data = lookup_table_data(Some(&authority), &addresses);
+        assert_eq!(
+            AddressLookupTable::unpack(&address, &data).unwrap(),
+            AddressLookupTable {
+                address,
+                authority: Some(authority),
+                deactivation_slot: u64::MAX,
+                addresses,
+            }
+        );
+
+        let frozen = AddressLookupTable::unpack(&address, &lookup_table_data(None, &[])).unwrap();
+        assert_eq!(frozen.authority, None);
+        assert!(frozen.addresses.is_empty());
+
+        assert!(AddressLookupTable::unpack(&address, &data[..60]).is_err());
+        let mut uninitialized = data;
+        uninitialized[0] = 0;
+        assert!(AddressLookupTable::unpack(&address, &uninitialized).is_err());
+    }
+
+    #[test]
+    fn test_create_and_extend_lookup_table() {
+        let authority = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let (ix, lookup_table) = create_lookup_table(&authority, &payer, 42);
+        let (_, bump_seed) = find_lookup_table_address(&authority, 42);
+        assert_eq!(ix.accounts[0].pubkey, lookup_table);
+        assert_eq!(
+            ix.data,
+            vec![0, 0, 0, 0, 42, 0, 0, 0, 0, 0, 0, 0, bump_seed]
+        );
+
+        let table = AddressLookupTable {
+            address: lookup_table,
+            authority: Some(authority),
+            deactivation_slot: u64::MAX,
+            addresses: vec![Pubkey::new_unique()],
+        };
+        let mut addresses: Vec<Pubkey> = (0..25).map(|_| Pubkey::new_unique()).collect();
+        addresses.push(table.addresses[0]);
+        addresses.push(addresses[0]);
+        let ixs = extend_lookup_table_with_missing(&table, &authority, &payer, &addresses).unwrap();
+        assert_eq!(ixs.len(), 2);
+        assert_eq!(ixs[0].data[..12], [2, 0, 0, 0, 20, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(ixs[0].data.len(), 12 + 20 * 32);
+        assert_eq!(ixs[1].data[4], 5);
+        assert_eq!(ixs[0].data[12..44], *addresses[0].as_ref());
+        assert!(extend_lookup_table(&lookup_table, &authority, &payer, &[]).is_err());
+    }
+}
@@ -0,0 +1,395 @@
+//! Version 0 messages, loading accounts from address lookup tables
+
+use crate::lookup_table::AddressLookupTable;
+use onesol::error::OneSolError;
+use solana_program::{
+    hash::Hash,
+    instruction::{CompiledInstruction, Instruction},
+    message::{Message, MessageHeader},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+/// Prefix of version 0 serialized messages; legacy messages start with their signature
+/// count, always below it.
+pub const MESSAGE_VERSION_PREFIX_V0: u8 = 0x80;
+
+/// Accounts a message loads from a lookup table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MessageAddressTableLookup {
+    /// Lookup table account
+    pub account_key: Pubkey,
+    /// Indexes of the writable accounts in the table
+    pub writable_indexes: Vec<u8>,
+    /// Indexes of the readonly accounts in the table
+    pub readonly_indexes: Vec<u8>,
+}
+
+/// Version 0 message. Instructions index the static account keys, then the writable
+/// accounts loaded from lookup tables, then the readonly ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MessageV0 {
+    /// Signature and readonly counts of the static account keys
+    pub header: MessageHeader,
+    /// Accounts not loaded from lookup tables: signers and invoked programs first
+    pub account_keys: Vec<Pubkey>,
+    /// Recent blockhash
+    pub recent_blockhash: Hash,
+    /// Compiled instructions
+    pub instructions: Vec<CompiledInstruction>,
+    /// Accounts loaded from lookup tables
+    pub address_table_lookups: Vec<MessageAddressTableLookup>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct KeyFlags {
+    is_signer: bool,
+    is_writable: bool,
+    is_invoked: bool,
+}
+
+impl MessageV0 {
+    /// Compiles `instructions` paid by `payer`, loading the accounts found in
+    /// `lookup_tables` from them. Signers and invoked programs are never loaded.
+    pub fn try_compile(
+        payer: &Pubkey,
+        instructions: &[Instruction],
+        lookup_tables: &[AddressLookupTable],
+        recent_blockhash: Hash,
+    ) -> Result<Self, ProgramError> {
+        let mut keys: Vec<(Pubkey, KeyFlags)> = vec![(
+            *payer,
+            KeyFlags {
+                is_signer: true,
+                is_writable: true,
+                is_invoked: false,
+            },
+        )];
+        let mut upsert = |key: &Pubkey, update: &dyn Fn(&mut KeyFlags)| match keys
+            .iter_mut()
+            .find(|(k, _)| k == key)
+        {
+            Some((_, flags)) => update(flags),
+            None => {
+                let mut flags = KeyFlags::default();
+                update(&mut flags);
+                keys.push((*key, flags));
+            }
+        };
+        for instruction in instructions.iter() {
+            upsert(&instruction.program_id, &|flags| flags.is_invoked = true);
+            for meta in instruction.accounts.iter() {
+                upsert(&meta.pubkey, &|flags| {
+                    flags.is_signer |= meta.is_signer;
+                    flags.is_writable |= meta.is_writable;
+                });
+            }
+        }
+
+        let mut address_table_lookups = Vec::new();
+        let mut loaded_writable = Vec::new();
+        let mut loaded_readonly = Vec::new();
+        for table in lookup_tables.iter() {
+            let mut lookup = MessageAddressTableLookup {
+                account_key: table.address,
+                writable_indexes: vec![],
+                readonly_indexes: vec![],
+            };
+            keys.retain(|(key, flags)| {
+                if flags.is_signer || flags.is_invoked {
+                    return true;
+                }
+                match table.addresses.iter().position(|address| address == key) {
+                    Some(index) if index <= u8::MAX as usize => {
+                        if flags.is_writable {
+                            lookup.writable_indexes.push(index as u8);
+                            loaded_writable.push(*key);
+                        } else {
+                            lookup.readonly_indexes.push(index as u8);
+                            loaded_readonly.push(*key);
+                        }
+                        false
+                    }
+                    _ => true,
+                }
+            });
+            if !lookup.writable_indexes.is_empty() || !lookup.readonly_indexes.is_empty() {
+                address_table_lookups.push(lookup);
+            }
+        }
+
+        // the sort is stable: the payer stays first among the writable signers
+        keys.sort_by_key(|(_, flags)| match (flags.is_signer, flags.is_writable) {
+            (true, true) => 0,
+            (true, false) => 1,
+            (false, true) => 2,
+            (false, false) => 3,
+        });
+        let count = |signer: bool, writable: bool| {
+            keys.iter()
+                .filter(|(_, f)| f.is_signer == signer && f.is_writable == writable)
+                .count()
+        };
+        let header = MessageHeader {
+            num_required_signatures: to_u8(count(true, true) + count(true, false))?,
+            num_readonly_signed_accounts: to_u8(count(true, false))?,
+            num_readonly_unsigned_accounts: to_u8(count(false, false))?,
+        };
+
+        let account_keys: Vec<Pubkey> = keys.into_iter().map(|(key, _)| key).collect();
+        let all_keys: Vec<&Pubkey> = account_keys
+            .iter()
+            .chain(loaded_writable.iter())
+            .chain(loaded_readonly.iter())
+            .collect();
+        let index_of = |key: &Pubkey| {
+            to_u8(
+                all_keys
+                    .iter()
+                    .position(|k| *k == key)
+                    .ok_or(OneSolError::InternalError)?,
+            )
+        };
+        let instructions = instructions
+            .iter()
+            .map(|instruction| {
+                Ok(CompiledInstruction {
+                    program_id_index: index_of(&instruction.program_id)?,
+                    accounts: instruction
+                        .accounts
+                        .iter()
+                        .map(|meta| index_of(&meta.pubkey))
+                        .collect::<Result<_, ProgramError>>()?,
+                    data: instruction.data.clone(),
+                })
+            })
+            .collect::<Result<_, ProgramError>>()?;
+
+        Ok(MessageV0 {
+            header,
+            account_keys,
+            recent_blockhash,
+            instructions,
+            address_table_lookups,
+        })
+    }
+
+    /// Serializes the message, version prefix included, as it is signed.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = vec![
+            MESSAGE_VERSION_PREFIX_V0,
+            self.header.num_required_signatures,
+            self.header.num_readonly_signed_accounts,
+            self.header.num_readonly_unsigned_accounts,
+        ];
+        append_compact_u16(&mut data, self.account_keys.len());
+        for key in self.account_keys.iter() {
+            data.extend_from_slice(key.as_ref());
+        }
+        data.extend_from_slice(self.recent_blockhash.as_ref());
+        append_compact_u16(&mut data, self.instructions.len());
+        for instruction in self.instructions.iter() {
+            data.push(instruction.program_id_index);
+            append_compact_u16(&mut data, instruction.accounts.len());
+            data.extend_from_slice(&instruction.accounts);
+            append_compact_u16(&mut data, instruction.data.len());
+            data.extend_from_slice(&instruction.data);
+        }
+        append_compact_u16(&mut data, self.address_table_lookups.len());
+        for lookup in self.address_table_lookups.iter() {
+            data.extend_from_slice(lookup.account_key.as_ref());
+            append_compact_u16(&mut data, lookup.writable_indexes.len());
+            data.extend_from_slice(&lookup.writable_indexes);
+            append_compact_u16(&mut data, lookup.readonly_indexes.len());
+            data.extend_from_slice(&lookup.readonly_indexes);
+        }
+        data
+    }
+}
+
+/// Message of a transaction, legacy or version 0.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VersionedMessage {
+    /// Legacy message, all accounts static
+    Legacy(Message),
+    /// Version 0 message, loading accounts from lookup tables
+    V0(MessageV0),
+}
+
+impl VersionedMessage {
+    /// Signature and readonly counts of the static account keys.
+    pub fn header(&self) -> &MessageHeader {
+        match self {
+            VersionedMessage::Legacy(message) => &message.header,
+            VersionedMessage::V0(message) => &message.header,
+        }
+    }
+
+    /// Accounts stored in the message, the signers first.
+    pub fn static_account_keys(&self) -> &[Pubkey] {
+        match self {
+            VersionedMessage::Legacy(message) => &message.account_keys,
+            VersionedMessage::V0(message) => &message.account_keys,
+        }
+    }
+
+    /// Sets the recent blockhash the message expires with.
+    pub fn set_recent_blockhash(&mut self, recent_blockhash: Hash) {
+        match self {
+            VersionedMessage::Legacy(message) => message.recent_blockhash = recent_blockhash,
+            VersionedMessage::V0(message) => message.recent_blockhash = recent_blockhash,
+        }
+    }
+
+    /// Serializes the message as it is signed.
+    pub fn serialize(&self) -> Vec<u8> {
+        match self {
+            VersionedMessage::Legacy(message) => message.serialize(),
+            VersionedMessage::V0(message) => message.serialize(),
+        }
+    }
+}
+
+/// Appends `len` as a compact-u16: 7 bits per byte, the high bit flagging a next byte.
+pub(crate) fn append_compact_u16(data: &mut Vec<u8>, len: usize) {
+    let mut rem = len as u16;
+    loop {
+        let byte = (rem & 0x7f) as u8;
+        rem >>= 7;
+        if rem == 0 {
+            data.push(byte);
+            break;
+        }
+        data.push(byte | 0x80);
+    }
+}
+
+fn to_u8(value: usize) -> Result<u8, ProgramError> {
+    if value > u8::MAX as usize {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    Ok(value as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::instruction::AccountMeta;
+
+    #[test]
+    fn test_compact_u16() {
+        let encode = |len| {
+            let mut data = vec![];
+            append_compact_u16(&mut data, len);
+            data
+        };
+        assert_eq!(encode(0), vec![0]);
+        assert_eq!(encode(0x7f), vec![0x7f]);
+        assert_eq!(encode(0x80), vec![0x80, 0x01]);
+        assert_eq!(encode(0x3fff), vec![0xff, 0x7f]);
+        assert_eq!(encode(0xffff), vec![0xff, 0xff, 0x03]);
+    }
+
+    #[test]
+    fn test_compile_with_lookup_table() {
+        let payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let signer = Pubkey::new_unique();
+        let pool = Pubkey::new_unique();
+        let reserve = Pubkey::new_unique();
+        let user_token = Pubkey::new_unique();
+        let instruction = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(pool, false),
+                AccountMeta::new_readonly(signer, true),
+                AccountMeta::new(reserve, false),
+                AccountMeta::new(user_token, false),
+            ],
+            data: vec![9],
+        };
+        let table = AddressLookupTable {
+            address: Pubkey::new_unique(),
+            authority: None,
+            deactivation_slot: u64::MAX,
+            // the signer and the program are in the table, but can't be loaded
+            addresses: vec![signer, reserve, program_id, pool],
+        };
+        let message = MessageV0::try_compile(
+            &payer,
+            &[instruction],
+            std::slice::from_ref(&table),
+            Hash::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            message.account_keys,
+            vec![payer, signer, user_token, program_id]
+        );
+        assert_eq!(message.header.num_required_signatures, 2);
+        assert_eq!(message.header.num_readonly_signed_accounts, 1);
+        assert_eq!(message.header.num_readonly_unsigned_accounts, 1);
+        assert_eq!(
+            message.address_table_lookups,
+            vec![MessageAddressTableLookup {
+                account_key: table.address,
+                writable_indexes: vec![1],
+                readonly_indexes: vec![3],
+            }]
+        );
+        // static keys, then loaded writable `reserve`, then loaded readonly `pool`
+        assert_eq!(message.instructions[0].program_id_index, 3);
+        assert_eq!(message.instructions[0].accounts, vec![5, 1, 4, 2]);
+
+        let data = message.serialize();
+        assert_eq!(data[..4], [MESSAGE_VERSION_PREFIX_V0, 2, 1, 1]);
+        assert_eq!(
+            data.len(),
+            4 + 1 + 4 * 32 + 32 + (1 + 1 + 1 + 4 + 1 + 1) + 1 + 32 + 2 + 2
+        );
+    }
+
+    #[test]
+    fn test_versioned_message() {
+        let payer = Pubkey::new_unique();
+        let instruction = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+            data: vec![1, 2],
+        };
+        let legacy = Message::new(std::slice::from_ref(&instruction), Some(&payer));
+        let mut v0 = VersionedMessage::V0(
+            MessageV0::try_compile(&payer, &[instruction], &[], Hash::default()).unwrap(),
+        );
+
+        // without lookups, a v0 message is the legacy one with a prefix and no lookups
+        let mut expected = vec![MESSAGE_VERSION_PREFIX_V0];
+        expected.extend_from_slice(&legacy.serialize());
+        expected.push(0);
+        assert_eq!(v0.serialize(), expected);
+        assert_eq!(v0.header(), &legacy.header);
+        assert_eq!(v0.static_account_keys(), &legacy.account_keys[..]);
+
+        let blockhash = Hash::new_unique();
+        v0.set_recent_blockhash(blockhash);
+        assert_eq!(
+            v0.serialize()[1 + 3 + 1 + 3 * 32..][..32],
+            *blockhash.as_ref()
+        );
+    }
+
+    #[test]
+    fn test_compile_without_lookup_tables() {
+        let payer = Pubkey::new_unique();
+        let instruction = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new(payer, true)],
+            data: vec![],
+        };
+        let message = MessageV0::try_compile(&payer, &[instruction], &[], Hash::default()).unwrap();
+        assert_eq!(message.account_keys.len(), 2);
+        assert!(message.address_table_lookups.is_empty());
+        assert_eq!(message.instructions[0].accounts, vec![0]);
+    }
+}
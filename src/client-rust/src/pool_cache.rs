@@ -0,0 +1,400 @@
+//! Pool state cache kept warm by account subscriptions, serving quotes without RPC
+//! round trips
+//!
+//! The cache is fed `accountSubscribe` messages of an RPC WebSocket: send the requests
+//! of [PoolCache::subscribe_requests], pass each message received to
+//! [PoolCache::handle_message]. With the `websocket` feature, [subscribe] runs that loop
+//! over a WebSocket connection.
+
+use crate::{
+    dex::SplTokenSwapPool,
+    quote::{Quote, SplTokenSwapQuoter},
+    route::{plan_route, Route},
+};
+use onesol::error::OneSolError;
+use serde_json::{json, Value};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug)]
+struct CachedAccount {
+    slot: u64,
+    data: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+struct CachedPool {
+    program_id: Pubkey,
+    quoter: SplTokenSwapQuoter,
+}
+
+/// spl-token-swap pools with their quoters, updated as their accounts change.
+#[derive(Clone, Debug, Default)]
+pub struct PoolCache {
+    pools: HashMap<Pubkey, CachedPool>,
+    accounts: HashMap<Pubkey, CachedAccount>,
+    /// Account of each subscription request awaiting its subscription id
+    pending_requests: HashMap<u64, Pubkey>,
+    /// Account of each subscription id
+    subscriptions: HashMap<u64, Pubkey>,
+    next_request_id: u64,
+}
+
+impl PoolCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the pool at `address`, owned by the Token-Swap program `program_id`, with
+    /// the data of its account and its token A and B accounts at `slot`.
+    pub fn register_pool(
+        &mut self,
+        address: &Pubkey,
+        program_id: &Pubkey,
+        slot: u64,
+        pool_data: &[u8],
+        token_a_data: &[u8],
+        token_b_data: &[u8],
+    ) -> Result<(), ProgramError> {
+        let pool = SplTokenSwapPool::unpack(address, program_id, pool_data)?;
+        let (token_a, token_b) = (pool.token_a, pool.token_b);
+        let quoter = SplTokenSwapQuoter::unpack(pool, pool_data, token_a_data, token_b_data)?;
+        for (account, data) in [
+            (*address, pool_data),
+            (token_a, token_a_data),
+            (token_b, token_b_data),
+        ]
+        .iter()
+        {
+            self.accounts.insert(
+                *account,
+                CachedAccount {
+                    slot,
+                    data: data.to_vec(),
+                },
+            );
+        }
+        self.pools.insert(
+            *address,
+            CachedPool {
+                program_id: *program_id,
+                quoter,
+            },
+        );
+        Ok(())
+    }
+
+    /// Returns the accounts the registered pools are read from.
+    pub fn accounts(&self) -> Vec<Pubkey> {
+        self.accounts.keys().copied().collect()
+    }
+
+    /// Returns the quoter of the registered pool at `address`.
+    pub fn quoter(&self, address: &Pubkey) -> Option<&SplTokenSwapQuoter> {
+        self.pools.get(address).map(|pool| &pool.quoter)
+    }
+
+    /// Returns the quoters of the registered pools.
+    pub fn quoters(&self) -> Vec<&dyn Quote> {
+        self.pools
+            .values()
+            .map(|pool| &pool.quoter as &dyn Quote)
+            .collect()
+    }
+
+    /// Plans a swap across the registered pools trading `source_mint`, see [plan_route].
+    pub fn plan_route(
+        &self,
+        source_mint: &Pubkey,
+        amount_in: u64,
+        parts: u8,
+    ) -> Result<Route, ProgramError> {
+        plan_route(&self.quoters(), source_mint, amount_in, parts)
+    }
+
+    /// Returns the `accountSubscribe` requests of the accounts of the registered pools.
+    pub fn subscribe_requests(&mut self) -> Vec<String> {
+        let mut requests = Vec::with_capacity(self.accounts.len());
+        for account in self.accounts() {
+            self.next_request_id += 1;
+            self.pending_requests.insert(self.next_request_id, account);
+            requests.push(
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": self.next_request_id,
+                    "method": "accountSubscribe",
+                    "params": [
+                        account.to_string(),
+                        { "encoding": "base64", "commitment": "processed" },
+                    ],
+                })
+                .to_string(),
+            );
+        }
+        requests
+    }
+
+    /// Handles a message of the subscriptions, returning the pools whose state changed.
+    /// Messages unrelated to the subscriptions are ignored.
+    pub fn handle_message(&mut self, message: &str) -> Result<Vec<Pubkey>, ProgramError> {
+        let message: Value =
+            serde_json::from_str(message).map_err(|_| OneSolError::InvalidInput)?;
+        if let (Some(id), Some(subscription)) = (message["id"].as_u64(), message["result"].as_u64())
+        {
+            if let Some(account) = self.pending_requests.remove(&id) {
+                self.subscriptions.insert(subscription, account);
+            }
+            return Ok(vec![]);
+        }
+        if message["method"] != "accountNotification" {
+            return Ok(vec![]);
+        }
+        let params = &message["params"];
+        let account = match params["subscription"]
+            .as_u64()
+            .and_then(|subscription| self.subscriptions.get(&subscription))
+        {
+            Some(account) => *account,
+            None => return Ok(vec![]),
+        };
+        let result = &params["result"];
+        let slot = result["context"]["slot"]
+            .as_u64()
+            .ok_or(OneSolError::InvalidInput)?;
+        let data = result["value"]["data"][0]
+            .as_str()
+            .and_then(|data| base64::decode(data).ok())
+            .ok_or(OneSolError::InvalidInput)?;
+        self.update_account(&account, slot, data)
+    }
+
+    /// Updates the data of `account` at `slot`, returning the pools whose state changed.
+    /// Updates older than the cached data are ignored.
+    pub fn update_account(
+        &mut self,
+        account: &Pubkey,
+        slot: u64,
+        data: Vec<u8>,
+    ) -> Result<Vec<Pubkey>, ProgramError> {
+        match self.accounts.get_mut(account) {
+            Some(cached) if cached.slot <= slot => *cached = CachedAccount { slot, data },
+            _ => return Ok(vec![]),
+        }
+        let updated: Vec<Pubkey> = self
+            .pools
+            .iter()
+            .filter(|(address, pool)| {
+                *address == account
+                    || pool.quoter.pool.token_a == *account
+                    || pool.quoter.pool.token_b == *account
+            })
+            .map(|(address, _)| *address)
+            .collect();
+        for address in updated.iter() {
+            let pool = &self.pools[address];
+            let pool_data = &self.accounts[address].data;
+            let quoter = SplTokenSwapQuoter::unpack(
+                SplTokenSwapPool::unpack(address, &pool.program_id, pool_data)?,
+                pool_data,
+                &self.accounts[&pool.quoter.pool.token_a].data,
+                &self.accounts[&pool.quoter.pool.token_b].data,
+            )?;
+            self.pools.get_mut(address).unwrap().quoter = quoter;
+        }
+        Ok(updated)
+    }
+}
+
+/// Subscribes to the accounts of the pools of `cache` on the RPC WebSocket at `url`,
+/// then keeps the cache updated until the connection closes. Blocks: run it on its own
+/// thread and read the cache from others.
+#[cfg(feature = "websocket")]
+pub fn subscribe(
+    cache: &std::sync::RwLock<PoolCache>,
+    url: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use tungstenite::Message;
+
+    let (mut socket, _) = tungstenite::connect(url)?;
+    let requests = cache
+        .write()
+        .map_err(|e| e.to_string())?
+        .subscribe_requests();
+    for request in requests {
+        socket.write_message(Message::Text(request))?;
+    }
+    loop {
+        match socket.read_message()? {
+            Message::Text(text) => {
+                cache
+                    .write()
+                    .map_err(|e| e.to_string())?
+                    .handle_message(&text)?;
+            }
+            Message::Close(_) => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::program_pack::Pack;
+
+    struct Fixture {
+        address: Pubkey,
+        program_id: Pubkey,
+        token_a: Pubkey,
+        token_b: Pubkey,
+        mint_a: Pubkey,
+        pool_data: Vec<u8>,
+    }
+
+    fn fixture() -> Fixture {
+        let address = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let (_, bump_seed) =
+            Pubkey::find_program_address(&[&address.to_bytes()[..32]], &program_id);
+        let (token_a, token_b, mint_a) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut pool_data = vec![1u8, 1, bump_seed];
+        for key in [
+            spl_token::id(),
+            token_a,
+            token_b,
+            Pubkey::new_unique(),
+            mint_a,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ]
+        .iter()
+        {
+            pool_data.extend_from_slice(key.as_ref());
+        }
+        // no fees, constant product
+        for value in [0u64, 1, 0, 1, 0, 1, 0, 1].iter() {
+            pool_data.extend_from_slice(&value.to_le_bytes());
+        }
+        pool_data.resize(324, 0);
+        Fixture {
+            address,
+            program_id,
+            token_a,
+            token_b,
+            mint_a,
+            pool_data,
+        }
+    }
+
+    fn token_data(amount: u64) -> Vec<u8> {
+        let mut data = vec![0u8; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        data
+    }
+
+    fn notification(subscription: u64, slot: u64, data: &[u8]) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "accountNotification",
+            "params": {
+                "result": {
+                    "context": { "slot": slot },
+                    "value": {
+                        "data": [base64::encode(data), "base64"],
+                        "executable": false,
+                        "lamports": 2039280,
+                        "owner": spl_token::id().to_string(),
+                        "rentEpoch": 0,
+                    },
+                },
+                "subscription": subscription,
+            },
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_pool_cache_follows_subscriptions() {
+        let f = fixture();
+        let mut cache = PoolCache::new();
+        cache
+            .register_pool(
+                &f.address,
+                &f.program_id,
+                10,
+                &f.pool_data,
+                &token_data(1_000_000),
+                &token_data(1_000_000),
+            )
+            .unwrap();
+        assert_eq!(cache.accounts().len(), 3);
+        assert!(cache.accounts().contains(&f.token_a));
+        assert_eq!(
+            cache.quoter(&f.address).unwrap().quote(&f.mint_a, 10_000),
+            Some(9_900)
+        );
+
+        let requests = cache.subscribe_requests();
+        assert_eq!(requests.len(), 3);
+        let token_b_request: Value = requests
+            .iter()
+            .map(|request| serde_json::from_str::<Value>(request).unwrap())
+            .find(|request| request["params"][0] == f.token_b.to_string())
+            .unwrap();
+        assert_eq!(token_b_request["method"], "accountSubscribe");
+        let id = token_b_request["id"].as_u64().unwrap();
+        let confirmation = json!({ "jsonrpc": "2.0", "result": 42, "id": id }).to_string();
+        assert!(cache.handle_message(&confirmation).unwrap().is_empty());
+
+        let updated = cache
+            .handle_message(&notification(42, 11, &token_data(2_000_000)))
+            .unwrap();
+        assert_eq!(updated, vec![f.address]);
+        let quoter = cache.quoter(&f.address).unwrap();
+        assert_eq!(quoter.reserve_b, 2_000_000);
+        assert_eq!(quoter.quote(&f.mint_a, 10_000), Some(19_801));
+        assert_eq!(
+            cache.plan_route(&f.mint_a, 10_000, 10).unwrap().amount_out,
+            19_801
+        );
+
+        // stale updates and unknown subscriptions are ignored
+        assert!(cache
+            .handle_message(&notification(42, 10, &token_data(1)))
+            .unwrap()
+            .is_empty());
+        assert!(cache
+            .handle_message(&notification(7, 12, &token_data(1)))
+            .unwrap()
+            .is_empty());
+        assert_eq!(cache.quoter(&f.address).unwrap().reserve_b, 2_000_000);
+        assert!(cache.handle_message("not json").is_err());
+    }
+
+    #[test]
+    fn test_pool_cache_rejects_invalid_pools() {
+        let f = fixture();
+        let mut cache = PoolCache::new();
+        assert!(cache
+            .register_pool(
+                &f.address,
+                &f.program_id,
+                10,
+                &f.pool_data[..100],
+                &token_data(1),
+                &token_data(1),
+            )
+            .is_err());
+        assert!(cache.accounts().is_empty());
+    }
+}
@@ -0,0 +1,15 @@
+//! Re-exports of the instruction builders along with [onesol::prelude], for clients to
+//! pull in with `use onesol_client::prelude::*`
+
+pub use crate::{
+    builder::SwapBuilder,
+    dex::{
+        inventory_leg, DexLeg, RaydiumPool, SerumMarket, SplTokenSwapPool, StableSwapPool,
+        WhirlpoolPool, DEX_TYPE_INVENTORY, DEX_TYPE_RAYDIUM, DEX_TYPE_SERUM_DEX,
+        DEX_TYPE_SPL_TOKEN_SWAP, DEX_TYPE_STABLE_SWAP, DEX_TYPE_WHIRLPOOL,
+    },
+    instruction::*,
+    quote::Quote,
+    route::{plan_route, Route},
+};
+pub use onesol::prelude::*;
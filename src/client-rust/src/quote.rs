@@ -0,0 +1,373 @@
+//! Off-chain quotes, mirroring the swap math each dex executes on-chain
+
+use crate::dex::{DexLeg, SplTokenSwapPool};
+use arrayref::{array_ref, array_refs};
+use onesol::curve::{self, Curve, CurveFees, PoolCurve, SwapCurve};
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+use std::convert::TryFrom;
+
+/// Offset of the fees in a spl-token-swap account, following the pool accounts.
+const SPL_TOKEN_SWAP_FEES_OFFSET: usize = 227;
+
+/// Length of the fees and swap curve of a spl-token-swap account.
+const SPL_TOKEN_SWAP_FEES_AND_CURVE_LEN: usize = 97;
+
+/// A pool a swap can be routed through, quoted off-chain.
+pub trait Quote {
+    /// Returns the leg swapping `source_mint` through the pool.
+    fn leg(&self, source_mint: &Pubkey) -> Result<DexLeg, ProgramError>;
+
+    /// Returns the DESTINATION amount a swap of `amount_in` `source_mint` tokens
+    /// receives, or None if the pool would refuse the swap.
+    fn quote(&self, source_mint: &Pubkey, amount_in: u64) -> Option<u64>;
+}
+
+/// Trading fees of a spl-token-swap pool, charged on the SOURCE amount.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SplTokenSwapFees {
+    /// Trade fee numerator, the fee staying in the pool
+    pub trade_fee_numerator: u64,
+    /// Trade fee denominator
+    pub trade_fee_denominator: u64,
+    /// Owner trade fee numerator, the fee minted to the pool owner
+    pub owner_trade_fee_numerator: u64,
+    /// Owner trade fee denominator
+    pub owner_trade_fee_denominator: u64,
+}
+
+impl SplTokenSwapFees {
+    /// Returns the total fee charged on a swap of `amount` SOURCE tokens.
+    pub fn total_fee(&self, amount: u128) -> Option<u128> {
+        calculate_fee(amount, self.trade_fee_numerator, self.trade_fee_denominator)?.checked_add(
+            calculate_fee(
+                amount,
+                self.owner_trade_fee_numerator,
+                self.owner_trade_fee_denominator,
+            )?,
+        )
+    }
+
+    /// Returns the fees as the on-chain [CurveFees], None if a denominator is 0.
+    pub fn curve_fees(&self) -> Option<CurveFees> {
+        CurveFees::new(
+            self.trade_fee_numerator,
+            self.trade_fee_denominator,
+            self.owner_trade_fee_numerator,
+            self.owner_trade_fee_denominator,
+        )
+        .ok()
+    }
+}
+
+/// Fee of `numerator / denominator` on `amount`, rounded down but at least 1 if the
+/// fee is not zero.
+fn calculate_fee(amount: u128, numerator: u64, denominator: u64) -> Option<u128> {
+    if numerator == 0 || amount == 0 {
+        return Some(0);
+    }
+    let fee = amount
+        .checked_mul(numerator as u128)?
+        .checked_div(denominator as u128)?;
+    Some(fee.max(1))
+}
+
+/// Swap curve of a spl-token-swap pool.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplTokenSwapCurve {
+    /// x * y = k
+    ConstantProduct,
+    /// token B priced at `token_b_price` token A
+    ConstantPrice {
+        /// price of one token B in token A
+        token_b_price: u64,
+    },
+    /// stable curve
+    Stable {
+        /// amplification coefficient
+        amp: u64,
+    },
+    /// constant product with a virtual `token_b_offset` added to the token B reserve
+    Offset {
+        /// virtual token B reserve
+        token_b_offset: u64,
+    },
+}
+
+impl SplTokenSwapCurve {
+    /// Returns the DESTINATION amount a swap of `amount` SOURCE tokens, net of fees,
+    /// receives. `a_to_b` gives the swap direction.
+    fn swap_without_fees(
+        &self,
+        amount: u128,
+        source_reserve: u128,
+        destination_reserve: u128,
+        a_to_b: bool,
+    ) -> Option<u128> {
+        match *self {
+            SplTokenSwapCurve::ConstantProduct => {
+                constant_product_swap(amount, source_reserve, destination_reserve)
+            }
+            SplTokenSwapCurve::ConstantPrice { token_b_price } => {
+                let amount_out = if a_to_b {
+                    amount.checked_div(token_b_price as u128)?
+                } else {
+                    amount.checked_mul(token_b_price as u128)?
+                };
+                Some(amount_out).filter(|amount_out| *amount_out > 0)
+            }
+            SplTokenSwapCurve::Stable { amp } => {
+                curve::stable_swap(amount, source_reserve, destination_reserve, amp)
+                    .filter(|amount_out| *amount_out > 0)
+            }
+            SplTokenSwapCurve::Offset { token_b_offset } => {
+                let offset = token_b_offset as u128;
+                if a_to_b {
+                    constant_product_swap(
+                        amount,
+                        source_reserve,
+                        destination_reserve.checked_add(offset)?,
+                    )
+                } else {
+                    constant_product_swap(
+                        amount,
+                        source_reserve.checked_add(offset)?,
+                        destination_reserve,
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// Constant product swap, refusing swaps receiving nothing as the pool does.
+fn constant_product_swap(
+    amount: u128,
+    source_reserve: u128,
+    destination_reserve: u128,
+) -> Option<u128> {
+    curve::constant_product_swap(amount, source_reserve, destination_reserve)
+        .filter(|amount_out| *amount_out > 0)
+}
+
+/// spl-token-swap pool with its fees, curve and reserves, quoting swaps exactly as the
+/// Token-Swap program executes them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SplTokenSwapQuoter {
+    /// Pool accounts
+    pub pool: SplTokenSwapPool,
+    /// Pool fees
+    pub fees: SplTokenSwapFees,
+    /// Pool curve
+    pub curve: SplTokenSwapCurve,
+    /// Token A reserve amount
+    pub reserve_a: u64,
+    /// Token B reserve amount
+    pub reserve_b: u64,
+}
+
+impl SplTokenSwapQuoter {
+    /// Returns the pool in the direction of a swap from `source_reserve` into
+    /// `destination_reserve` as the router simulates it on-chain, None for the curves
+    /// the router doesn't quote.
+    pub fn pool_curve(&self, source_reserve: u64, destination_reserve: u64) -> Option<PoolCurve> {
+        let curve = match self.curve {
+            SplTokenSwapCurve::ConstantProduct => SwapCurve::ConstantProduct,
+            SplTokenSwapCurve::Stable { amp } => SwapCurve::Stable { amp },
+            SplTokenSwapCurve::ConstantPrice { .. } | SplTokenSwapCurve::Offset { .. } => {
+                return None
+            }
+        };
+        Some(PoolCurve {
+            curve,
+            fees: self.fees.curve_fees()?,
+            source_reserve,
+            destination_reserve,
+        })
+    }
+
+    /// Reads the fees and curve from the pool account data and the reserves from the
+    /// data of its token A and token B accounts.
+    pub fn unpack(
+        pool: SplTokenSwapPool,
+        pool_data: &[u8],
+        token_a_data: &[u8],
+        token_b_data: &[u8],
+    ) -> Result<Self, ProgramError> {
+        if pool_data.len() < SPL_TOKEN_SWAP_FEES_OFFSET + SPL_TOKEN_SWAP_FEES_AND_CURVE_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![
+            pool_data,
+            SPL_TOKEN_SWAP_FEES_OFFSET,
+            SPL_TOKEN_SWAP_FEES_AND_CURVE_LEN
+        ];
+        #[rustfmt::skip]
+        let (
+            trade_fee_numerator, trade_fee_denominator,
+            owner_trade_fee_numerator, owner_trade_fee_denominator,
+            _owner_withdraw_fees, _host_fees, curve_type, curve_parameter, _curve_rest,
+        ) = array_refs![src, 8, 8, 8, 8, 16, 16, 1, 8, 24];
+        let curve_parameter = u64::from_le_bytes(*curve_parameter);
+        let curve = match curve_type[0] {
+            0 => SplTokenSwapCurve::ConstantProduct,
+            1 => SplTokenSwapCurve::ConstantPrice {
+                token_b_price: curve_parameter,
+            },
+            2 => SplTokenSwapCurve::Stable {
+                amp: curve_parameter,
+            },
+            3 => SplTokenSwapCurve::Offset {
+                token_b_offset: curve_parameter,
+            },
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(SplTokenSwapQuoter {
+            pool,
+            fees: SplTokenSwapFees {
+                trade_fee_numerator: u64::from_le_bytes(*trade_fee_numerator),
+                trade_fee_denominator: u64::from_le_bytes(*trade_fee_denominator),
+                owner_trade_fee_numerator: u64::from_le_bytes(*owner_trade_fee_numerator),
+                owner_trade_fee_denominator: u64::from_le_bytes(*owner_trade_fee_denominator),
+            },
+            curve,
+            reserve_a: spl_token::state::Account::unpack(token_a_data)?.amount,
+            reserve_b: spl_token::state::Account::unpack(token_b_data)?.amount,
+        })
+    }
+}
+
+impl Quote for SplTokenSwapQuoter {
+    fn leg(&self, source_mint: &Pubkey) -> Result<DexLeg, ProgramError> {
+        self.pool.leg(source_mint, None)
+    }
+
+    fn quote(&self, source_mint: &Pubkey, amount_in: u64) -> Option<u64> {
+        let (a_to_b, source_reserve, destination_reserve) =
+            if *source_mint == self.pool.token_a_mint {
+                (true, self.reserve_a, self.reserve_b)
+            } else if *source_mint == self.pool.token_b_mint {
+                (false, self.reserve_b, self.reserve_a)
+            } else {
+                return None;
+            };
+        // the curves the router quotes on-chain share its simulation
+        if let Some(pool) = self.pool_curve(source_reserve, destination_reserve) {
+            return pool
+                .simulate_swap(amount_in)
+                .filter(|amount_out| *amount_out > 0);
+        }
+        let amount_in = amount_in as u128;
+        let amount_less_fees = amount_in.checked_sub(self.fees.total_fee(amount_in)?)?;
+        let amount_out = self.curve.swap_without_fees(
+            amount_less_fees,
+            source_reserve as u128,
+            destination_reserve as u128,
+            a_to_b,
+        )?;
+        u64::try_from(amount_out).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quoter(curve: SplTokenSwapCurve, reserve_a: u64, reserve_b: u64) -> SplTokenSwapQuoter {
+        SplTokenSwapQuoter {
+            pool: SplTokenSwapPool {
+                address: Pubkey::new_unique(),
+                program_id: Pubkey::new_unique(),
+                authority: Pubkey::new_unique(),
+                token_program_id: spl_token::id(),
+                token_a: Pubkey::new_unique(),
+                token_b: Pubkey::new_unique(),
+                pool_mint: Pubkey::new_unique(),
+                token_a_mint: Pubkey::new_from_array([1u8; 32]),
+                token_b_mint: Pubkey::new_from_array([2u8; 32]),
+                pool_fee_account: Pubkey::new_unique(),
+            },
+            fees: SplTokenSwapFees {
+                trade_fee_numerator: 25,
+                trade_fee_denominator: 10_000,
+                owner_trade_fee_numerator: 5,
+                owner_trade_fee_denominator: 10_000,
+            },
+            curve,
+            reserve_a,
+            reserve_b,
+        }
+    }
+
+    #[test]
+    fn test_constant_product_quote() {
+        let quoter = quoter(SplTokenSwapCurve::ConstantProduct, 1_000_000, 2_000_000);
+        let mint_a = quoter.pool.token_a_mint;
+        let mint_b = quoter.pool.token_b_mint;
+        // fees 25 + 5, new reserve B ceil(2e12 / 1_009_970) = 1_980_257
+        assert_eq!(quoter.quote(&mint_a, 10_000), Some(19_743));
+        // fees 1 + 1 (rounded up), new reserve A ceil(2e12 / 2_000_098) = 999_952
+        assert_eq!(quoter.quote(&mint_b, 100), Some(48));
+        assert_eq!(quoter.quote(&mint_a, 0), None);
+        assert_eq!(quoter.quote(&Pubkey::new_unique(), 10_000), None);
+    }
+
+    #[test]
+    fn test_other_curves_quote() {
+        let constant_price = quoter(
+            SplTokenSwapCurve::ConstantPrice { token_b_price: 4 },
+            1_000_000,
+            1_000_000,
+        );
+        let mint_a = constant_price.pool.token_a_mint;
+        let mint_b = constant_price.pool.token_b_mint;
+        // 10_000 - 30 fees = 9_970 A buys 2_492 B
+        assert_eq!(constant_price.quote(&mint_a, 10_000), Some(2_492));
+        assert_eq!(constant_price.quote(&mint_b, 10_000), Some(39_880));
+
+        let offset = quoter(
+            SplTokenSwapCurve::Offset {
+                token_b_offset: 2_000_000,
+            },
+            1_000_000,
+            0,
+        );
+        assert_eq!(offset.quote(&mint_a, 10_000), Some(19_743));
+
+        // fees 25 + 5, nearly 1:1 at the peg
+        let stable = quoter(SplTokenSwapCurve::Stable { amp: 100 }, 1_000_000, 1_000_000);
+        assert_eq!(stable.quote(&mint_a, 10_000), Some(9_970));
+        assert_eq!(stable.quote(&mint_a, 0), None);
+    }
+
+    #[test]
+    fn test_unpack_quoter() {
+        let pool = quoter(SplTokenSwapCurve::ConstantProduct, 0, 0).pool;
+        let mut pool_data = vec![0u8; SPL_TOKEN_SWAP_FEES_OFFSET];
+        for value in [25u64, 10_000, 5, 10_000, 0, 1, 0, 1].iter() {
+            pool_data.extend_from_slice(&value.to_le_bytes());
+        }
+        pool_data.push(3);
+        pool_data.extend_from_slice(&7u64.to_le_bytes());
+        pool_data.resize(324, 0);
+
+        let token_data = |amount: u64| {
+            let account = spl_token::state::Account {
+                amount,
+                state: spl_token::state::AccountState::Initialized,
+                ..Default::default()
+            };
+            let mut data = vec![0u8; spl_token::state::Account::LEN];
+            spl_token::state::Account::pack(account, &mut data).unwrap();
+            data
+        };
+        let quoter =
+            SplTokenSwapQuoter::unpack(pool, &pool_data, &token_data(10), &token_data(20)).unwrap();
+        assert_eq!(quoter.fees.trade_fee_numerator, 25);
+        assert_eq!(quoter.fees.owner_trade_fee_denominator, 10_000);
+        assert_eq!(
+            quoter.curve,
+            SplTokenSwapCurve::Offset { token_b_offset: 7 }
+        );
+        assert_eq!((quoter.reserve_a, quoter.reserve_b), (10, 20));
+    }
+}
@@ -0,0 +1,258 @@
+//! Off-chain route planning: splits a swap across candidate pools
+
+use crate::{builder::SwapBuilder, dex::DexLeg, instruction::SwapAccounts, quote::Quote};
+use onesol::{
+    error::{LegError, OneSolError},
+    math::{less_bps, BPS_SCALE},
+};
+use solana_program::{instruction::Instruction, program_error::ProgramError, pubkey::Pubkey};
+
+/// Number of parts a swap is split into while planning, the granularity of the ratios.
+pub const DEFAULT_ROUTE_PARTS: u8 = 100;
+
+/// A planned swap: its legs with their ratios, the amount each leg swaps and the
+/// expected output.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Route {
+    /// Legs with the ratio of the swap they take in bps, in instruction order
+    pub legs: Vec<(DexLeg, u16)>,
+    /// SOURCE amount each leg swaps, as the program splits `amount_in` by ratio
+    pub amounts_in: Vec<u64>,
+    /// Expected DESTINATION amount of each leg
+    pub amounts_out: Vec<u64>,
+    /// SOURCE amount of the swap
+    pub amount_in: u64,
+    /// Expected DESTINATION amount of the swap
+    pub amount_out: u64,
+}
+
+impl Route {
+    /// Returns the expected output less `slippage_bps`, rounded down.
+    pub fn minimum_amount_out(&self, slippage_bps: u16) -> u64 {
+        // never fails, the tolerance being capped at 10_000 bps
+        less_bps(self.amount_out, slippage_bps).unwrap_or(0)
+    }
+
+    /// Creates the `Swap` instruction executing the route, tolerating `slippage_bps`
+    /// below the expected output.
+    pub fn instruction(
+        &self,
+        program_id: &Pubkey,
+        accounts: &SwapAccounts,
+        slippage_bps: u16,
+    ) -> Result<Instruction, ProgramError> {
+        self.legs
+            .iter()
+            .fold(
+                SwapBuilder::new(self.amount_in)
+                    .minimum_amount_out(self.minimum_amount_out(slippage_bps)),
+                |builder, (leg, ratio)| builder.leg_with_ratio(leg.clone(), *ratio),
+            )
+            .build(program_id, accounts)
+    }
+
+    /// Returns the leg of the route the custom error `code` of its swap reports failed, if
+    /// any. Re-plan without the pool of that leg to retry the swap through the others.
+    pub fn failed_leg(&self, code: u32) -> Option<(usize, &DexLeg)> {
+        let leg_index = LegError::from_code(code)?.leg_index as usize;
+        self.legs.get(leg_index).map(|(leg, _)| (leg_index, leg))
+    }
+}
+
+/// Plans the swap of `amount_in` `source_mint` tokens across `pools`, split in `parts`
+/// parts each given to the pool adding the most output. The pools' curves are concave,
+/// so the greedy split is optimal at the `parts` granularity.
+pub fn plan_route(
+    pools: &[&dyn Quote],
+    source_mint: &Pubkey,
+    amount_in: u64,
+    parts: u8,
+) -> Result<Route, ProgramError> {
+    if pools.is_empty() || amount_in == 0 || parts == 0 {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let part_amount = |part: u8| (amount_in as u128 * part as u128 / parts as u128) as u64;
+    let quote = |pool: &dyn Quote, part: u8| match part {
+        0 => Some(0),
+        _ => pool.quote(source_mint, part_amount(part)),
+    };
+
+    let mut allocated = vec![0u8; pools.len()];
+    let mut outputs = vec![0u64; pools.len()];
+    for _ in 0..parts {
+        let best = pools
+            .iter()
+            .enumerate()
+            .filter_map(|(i, pool)| {
+                let output = quote(*pool, allocated[i] + 1)?;
+                Some((i, output, output.saturating_sub(outputs[i])))
+            })
+            .max_by_key(|(_, _, gain)| *gain);
+        match best {
+            Some((i, output, _)) => {
+                allocated[i] += 1;
+                outputs[i] = output;
+            }
+            None => return Err(OneSolError::ZeroTradingTokens.into()),
+        }
+    }
+
+    let mut chosen: Vec<(usize, u8)> = allocated
+        .iter()
+        .enumerate()
+        .filter(|(_, allocated)| **allocated > 0)
+        .map(|(i, allocated)| (i, *allocated))
+        .collect();
+    // the last leg takes the rounding remainder of the split, give it to the largest leg
+    chosen.sort_by_key(|a| a.1);
+
+    let mut ratios: Vec<u16> = chosen
+        .iter()
+        .map(|(_, allocated)| (*allocated as u32 * BPS_SCALE as u32 / parts as u32) as u16)
+        .collect();
+    let rounding = BPS_SCALE - ratios.iter().sum::<u16>();
+    if let Some(last) = ratios.last_mut() {
+        *last += rounding;
+    }
+    let amounts_in = split_by_ratio(amount_in, &ratios);
+    let mut legs = Vec::with_capacity(chosen.len());
+    let mut amounts_out = Vec::with_capacity(chosen.len());
+    for (((i, _), ratio), leg_amount_in) in chosen.iter().zip(ratios.iter()).zip(amounts_in.iter())
+    {
+        legs.push((pools[*i].leg(source_mint)?, *ratio));
+        amounts_out.push(
+            pools[*i]
+                .quote(source_mint, *leg_amount_in)
+                .ok_or(OneSolError::ZeroTradingTokens)?,
+        );
+    }
+    let amount_out = amounts_out.iter().sum();
+    Ok(Route {
+        legs,
+        amounts_in,
+        amounts_out,
+        amount_in,
+        amount_out,
+    })
+}
+
+/// Splits `amount` by `ratios` in bps, summing to [BPS_SCALE], as the program does:
+/// proportionally, rounded down, the last leg taking the remainder.
+pub fn split_by_ratio(amount: u64, ratios: &[u16]) -> Vec<u64> {
+    let mut remaining = amount;
+    ratios
+        .iter()
+        .enumerate()
+        .map(|(i, ratio)| {
+            let part = if i + 1 == ratios.len() {
+                remaining
+            } else {
+                (amount as u128 * *ratio as u128 / BPS_SCALE as u128) as u64
+            };
+            remaining -= part;
+            part
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::DEX_TYPE_SPL_TOKEN_SWAP;
+
+    /// Constant product pool without fees.
+    struct TestPool {
+        reserve_in: u64,
+        reserve_out: u64,
+    }
+
+    impl Quote for TestPool {
+        fn leg(&self, _source_mint: &Pubkey) -> Result<DexLeg, ProgramError> {
+            Ok(DexLeg {
+                dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+                accounts: vec![],
+            })
+        }
+
+        fn quote(&self, _source_mint: &Pubkey, amount_in: u64) -> Option<u64> {
+            let out = self.reserve_out as u128 * amount_in as u128
+                / (self.reserve_in as u128 + amount_in as u128);
+            Some(out as u64).filter(|out| *out > 0)
+        }
+    }
+
+    #[test]
+    fn test_plan_route_splits_across_pools() {
+        let deep = TestPool {
+            reserve_in: 3_000_000,
+            reserve_out: 3_000_000,
+        };
+        let shallow = TestPool {
+            reserve_in: 1_000_000,
+            reserve_out: 1_000_000,
+        };
+        let mint = Pubkey::new_unique();
+        let route = plan_route(&[&shallow, &deep], &mint, 400_000, 100).unwrap();
+
+        assert_eq!(
+            route
+                .legs
+                .iter()
+                .map(|(_, ratio)| *ratio)
+                .collect::<Vec<_>>(),
+            vec![2_500, 7_500]
+        );
+        assert_eq!(route.amounts_in, vec![100_000, 300_000]);
+        assert_eq!(route.amount_out, route.amounts_out.iter().sum::<u64>());
+        let single = deep.quote(&mint, 400_000).unwrap();
+        assert!(route.amount_out > single);
+        assert_eq!(route.minimum_amount_out(100), route.amount_out * 99 / 100);
+    }
+
+    #[test]
+    fn test_plan_route_skips_useless_pools() {
+        let pool = TestPool {
+            reserve_in: 1_000_000,
+            reserve_out: 1_000_000,
+        };
+        let dry = TestPool {
+            reserve_in: 1_000_000,
+            reserve_out: 0,
+        };
+        let mint = Pubkey::new_unique();
+        let route = plan_route(&[&dry, &pool], &mint, 1_000, 10).unwrap();
+        assert_eq!(route.legs.len(), 1);
+        assert_eq!(route.legs[0].1, BPS_SCALE);
+        assert!(plan_route(&[&dry], &mint, 1_000, 10).is_err());
+        assert!(plan_route(&[], &mint, 1_000, 10).is_err());
+    }
+
+    #[test]
+    fn test_failed_leg() {
+        let deep = TestPool {
+            reserve_in: 3_000_000,
+            reserve_out: 3_000_000,
+        };
+        let shallow = TestPool {
+            reserve_in: 1_000_000,
+            reserve_out: 1_000_000,
+        };
+        let mint = Pubkey::new_unique();
+        let route = plan_route(&[&shallow, &deep], &mint, 400_000, 100).unwrap();
+
+        let code = LegError::new(1, DEX_TYPE_SPL_TOKEN_SWAP, &ProgramError::Custom(6)).to_code();
+        assert_eq!(route.failed_leg(code), Some((1, &route.legs[1].0)));
+        let code = LegError::new(2, DEX_TYPE_SPL_TOKEN_SWAP, &ProgramError::Custom(6)).to_code();
+        assert_eq!(route.failed_leg(code), None);
+        assert_eq!(route.failed_leg(OneSolError::ExceededSlippage as u32), None);
+    }
+
+    #[test]
+    fn test_split_by_ratio() {
+        assert_eq!(
+            split_by_ratio(100, &[3_333, 3_333, 3_334]),
+            vec![33, 33, 34]
+        );
+        assert_eq!(split_by_ratio(7, &[BPS_SCALE]), vec![7]);
+    }
+}
@@ -0,0 +1,364 @@
+//! Transaction simulation: checks a route executes as quoted before the user signs it
+//!
+//! Send the request of [simulate_transaction_request] to an RPC node, then pass its
+//! response to [parse_simulation_response] and the result to [verify_route].
+
+use crate::{route::Route, transaction::VersionedTransaction};
+use onesol::{
+    error::{error_code, LegError, OneSolError},
+    state::{QuoteResult, SwapResult},
+};
+use serde_json::{json, Value};
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+
+/// Shortfall below the quoted output tolerated by default, in basis points.
+pub const DEFAULT_DIVERGENCE_TOLERANCE_BPS: u16 = 10;
+
+/// Returns the `simulateTransaction` request of the unsigned `transaction`, skipping the
+/// signature checks and replacing its blockhash with a recent one.
+pub fn simulate_transaction_request(id: u64, transaction: &VersionedTransaction) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "simulateTransaction",
+        "params": [
+            base64::encode(transaction.serialize()),
+            {
+                "encoding": "base64",
+                "sigVerify": false,
+                "replaceRecentBlockhash": true,
+                "commitment": "processed",
+            },
+        ],
+    })
+    .to_string()
+}
+
+/// Outcome of a simulated transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulationResult {
+    /// Error the transaction failed with, as reported by the RPC node
+    pub error: Option<String>,
+    /// Program logs
+    pub logs: Vec<String>,
+    /// Compute units consumed, if reported
+    pub units_consumed: Option<u64>,
+    /// Result returned by the 1sol swap, if it completed
+    pub swap_result: Option<SwapResult>,
+    /// Result returned by a 1sol `Quote`, if it completed
+    pub quote_result: Option<QuoteResult>,
+}
+
+/// Parses the response to a `simulateTransaction` request of a transaction calling the
+/// 1sol program `program_id`. The swap or quote result is read from the return data the
+/// node reports, if the program set it.
+pub fn parse_simulation_response(
+    program_id: &Pubkey,
+    response: &str,
+) -> Result<SimulationResult, ProgramError> {
+    let response: Value = serde_json::from_str(response).map_err(|_| OneSolError::InvalidInput)?;
+    if let Some(message) = response["error"]["message"].as_str() {
+        return Ok(SimulationResult {
+            error: Some(message.to_string()),
+            logs: vec![],
+            units_consumed: None,
+            swap_result: None,
+            quote_result: None,
+        });
+    }
+    let value = &response["result"]["value"];
+    if value.is_null() {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let logs: Vec<String> = value["logs"]
+        .as_array()
+        .map(|logs| {
+            logs.iter()
+                .filter_map(|log| log.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let program_id = program_id.to_string();
+    let return_data = if value["returnData"]["programId"] == program_id.as_str() {
+        value["returnData"]["data"][0].as_str()
+    } else {
+        None
+    };
+    let (mut swap_result, mut quote_result) = (None, None);
+    if let Some(data) = return_data {
+        let data = base64::decode(data).map_err(|_| OneSolError::InvalidInput)?;
+        match data.len() {
+            SwapResult::LEN => swap_result = Some(SwapResult::unpack_from_slice(&data)?),
+            QuoteResult::LEN => quote_result = Some(QuoteResult::unpack_from_slice(&data)?),
+            _ => return Err(OneSolError::InvalidInput.into()),
+        }
+    }
+
+    Ok(SimulationResult {
+        error: if value["err"].is_null() {
+            None
+        } else {
+            Some(value["err"].to_string())
+        },
+        logs,
+        units_consumed: value["unitsConsumed"].as_u64(),
+        swap_result,
+        quote_result,
+    })
+}
+
+/// Renders the custom error code a 1sol instruction failed with, naming the failed leg
+/// of leg errors.
+pub fn describe_error_code(code: u32) -> String {
+    let describe = |code: u32| match error_code(code) {
+        Some(error) => format!("{}: {}", error.name, error.description),
+        None => format!("unknown error {}", code),
+    };
+    match LegError::from_code(code) {
+        Some(leg_error) => format!(
+            "leg {} (dex type {}) failed: {}",
+            leg_error.leg_index,
+            leg_error.dex_type,
+            describe(leg_error.downstream_code as u32)
+        ),
+        None => describe(code),
+    }
+}
+
+/// Comparison of a simulated swap with its route.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteVerification {
+    /// Output the route was quoted for
+    pub expected_amount_out: u64,
+    /// Result of the simulated swap, none if it failed
+    pub simulated: Option<SwapResult>,
+    /// Why the simulation diverges from the route, none if it matches
+    pub divergence: Option<String>,
+}
+
+impl RouteVerification {
+    /// Whether the simulated swap executes as quoted.
+    pub fn matches(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// Compares the simulated swap with `route`, flagging a divergence if the simulation
+/// failed, spent another amount, ran another number of legs or returned more than
+/// `tolerance_bps` below the quoted output.
+pub fn verify_route(
+    route: &Route,
+    simulation: &SimulationResult,
+    tolerance_bps: u16,
+) -> RouteVerification {
+    let minimum_amount_out = route.minimum_amount_out(tolerance_bps);
+    let divergence = match (&simulation.error, &simulation.swap_result) {
+        (Some(error), _) => Some(format!("simulation failed: {}", error)),
+        (None, None) => Some("no swap result in the simulation".to_string()),
+        (None, Some(result)) if result.amount_in_spent != route.amount_in => Some(format!(
+            "spent {} instead of {}",
+            result.amount_in_spent, route.amount_in
+        )),
+        (None, Some(result)) if result.legs_executed as usize != route.legs.len() => Some(format!(
+            "executed {} legs instead of {}",
+            result.legs_executed,
+            route.legs.len()
+        )),
+        (None, Some(result)) if result.amount_out < minimum_amount_out => Some(format!(
+            "returned {} for a quote of {}",
+            result.amount_out, route.amount_out
+        )),
+        (None, Some(_)) => None,
+    };
+    RouteVerification {
+        expected_amount_out: route.amount_out,
+        simulated: simulation.swap_result,
+        divergence,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::{DexLeg, DEX_TYPE_INVENTORY, DEX_TYPE_SPL_TOKEN_SWAP};
+    use onesol::error::AdapterError;
+
+    fn route() -> Route {
+        let leg = DexLeg {
+            dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+            accounts: vec![],
+        };
+        Route {
+            legs: vec![(leg.clone(), 4_000), (leg, 6_000)],
+            amounts_in: vec![400, 600],
+            amounts_out: vec![3_990, 5_990],
+            amount_in: 1_000,
+            amount_out: 9_980,
+        }
+    }
+
+    fn return_data(amount_out: u64) -> String {
+        let mut data = vec![0u8; SwapResult::LEN];
+        SwapResult {
+            amount_in_spent: 1_000,
+            amount_out,
+            fee_paid: 0,
+            legs_executed: 2,
+        }
+        .pack_into_slice(&mut data);
+        base64::encode(data)
+    }
+
+    #[test]
+    fn test_parse_simulation_response() {
+        let program_id = Pubkey::new_unique();
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "context": { "slot": 100 },
+                "value": {
+                    "err": null,
+                    "logs": [
+                        format!("Program {} invoke [1]", program_id),
+                        // any program can log this: only the return data counts
+                        format!("Program log: Program return: {} {}", program_id, return_data(9_999)),
+                        format!("Program {} success", program_id),
+                    ],
+                    "returnData": {
+                        "programId": program_id.to_string(),
+                        "data": [return_data(9_975), "base64"],
+                    },
+                    "unitsConsumed": 123_456,
+                },
+            },
+        })
+        .to_string();
+        let simulation = parse_simulation_response(&program_id, &response).unwrap();
+        assert_eq!(simulation.error, None);
+        assert_eq!(simulation.units_consumed, Some(123_456));
+        assert_eq!(simulation.swap_result.unwrap().amount_out, 9_975);
+
+        let verification = verify_route(&route(), &simulation, DEFAULT_DIVERGENCE_TOLERANCE_BPS);
+        assert!(verification.matches());
+
+        let verification = verify_route(&route(), &simulation, 0);
+        assert_eq!(
+            verification.divergence,
+            Some("returned 9975 for a quote of 9980".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_simulation_response_from_return_data() {
+        let program_id = Pubkey::new_unique();
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "context": { "slot": 100 },
+                "value": {
+                    "err": null,
+                    "logs": [],
+                    "returnData": {
+                        "programId": program_id.to_string(),
+                        "data": [return_data(9_980), "base64"],
+                    },
+                },
+            },
+        })
+        .to_string();
+        let simulation = parse_simulation_response(&program_id, &response).unwrap();
+        assert!(verify_route(&route(), &simulation, 0).matches());
+        // another program's return data is not the swap result
+        assert_eq!(
+            parse_simulation_response(&Pubkey::new_unique(), &response)
+                .unwrap()
+                .swap_result,
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_quote_simulation_response() {
+        let program_id = Pubkey::new_unique();
+        let mut quote = QuoteResult {
+            amount_out: 9_980,
+            legs_quoted: 2,
+            leg_amounts_out: [0; onesol::instruction::MAX_LEGS],
+        };
+        quote.leg_amounts_out[..2].copy_from_slice(&[3_990, 5_990]);
+        let mut data = vec![0u8; QuoteResult::LEN];
+        quote.pack_into_slice(&mut data);
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "context": { "slot": 100 },
+                "value": {
+                    "err": null,
+                    "logs": [],
+                    "returnData": {
+                        "programId": program_id.to_string(),
+                        "data": [base64::encode(data), "base64"],
+                    },
+                },
+            },
+        })
+        .to_string();
+        let simulation = parse_simulation_response(&program_id, &response).unwrap();
+        assert_eq!(simulation.quote_result, Some(quote));
+        assert_eq!(simulation.swap_result, None);
+    }
+
+    #[test]
+    fn test_failed_simulation_diverges() {
+        let program_id = Pubkey::new_unique();
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "context": { "slot": 100 },
+                "value": {
+                    "err": { "InstructionError": [0, { "Custom": 17 }] },
+                    "logs": [],
+                },
+            },
+        })
+        .to_string();
+        let simulation = parse_simulation_response(&program_id, &response).unwrap();
+        let verification = verify_route(&route(), &simulation, 100);
+        assert!(!verification.matches());
+        assert_eq!(verification.simulated, None);
+
+        let rpc_error =
+            r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32602,"message":"invalid transaction"}}"#;
+        let simulation = parse_simulation_response(&program_id, rpc_error).unwrap();
+        assert_eq!(simulation.error, Some("invalid transaction".to_string()));
+        assert!(parse_simulation_response(&program_id, "{}").is_err());
+    }
+
+    #[test]
+    fn test_describe_error_code() {
+        assert_eq!(
+            describe_error_code(OneSolError::ExceededSlippage as u32),
+            "ExceededSlippage: Swap instruction exceeds desired slippage limit"
+        );
+        let code = LegError::new(1, 0, &OneSolError::InvalidOracle.into()).to_code();
+        assert_eq!(
+            describe_error_code(code),
+            "leg 1 (dex type 0) failed: InvalidOracle: Invalid oracle account"
+        );
+        let pair_disabled = AdapterError {
+            dex_type: DEX_TYPE_INVENTORY,
+            code: 0,
+        };
+        let code = LegError::new(0, DEX_TYPE_INVENTORY, &pair_disabled.into()).to_code();
+        assert_eq!(
+            describe_error_code(code),
+            "leg 0 (dex type 1) failed: PairDisabled: Inventory pair disabled"
+        );
+        assert_eq!(describe_error_code(9_999), "unknown error 9999");
+    }
+}
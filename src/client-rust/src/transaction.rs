@@ -0,0 +1,407 @@
+//! Transaction building: prepends the compute budget a route needs and its priority fee
+
+use crate::{
+    dex::{
+        DEX_TYPE_RAYDIUM, DEX_TYPE_SERUM_DEX, DEX_TYPE_SPL_TOKEN_SWAP, DEX_TYPE_STABLE_SWAP,
+        DEX_TYPE_WHIRLPOOL,
+    },
+    instruction::SwapAccounts,
+    lookup_table::AddressLookupTable,
+    message::{append_compact_u16, MessageV0, VersionedMessage},
+    route::Route,
+};
+use onesol::error::OneSolError;
+use solana_program::{
+    hash::Hash, instruction::Instruction, message::Message, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+/// Compute Budget program
+pub mod compute_budget {
+    solana_program::declare_id!("ComputeBudget111111111111111111111111111111");
+}
+
+/// Compute units a transaction gets without a `SetComputeUnitLimit` instruction.
+pub const DEFAULT_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+
+/// Largest compute unit limit a transaction may request.
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Heap frame a program built with the `large-heap` feature needs, the largest one a
+/// transaction can request.
+pub const LARGE_HEAP_FRAME_BYTES: u32 = 256 * 1024;
+
+/// Largest serialized transaction the cluster accepts.
+pub const PACKET_DATA_SIZE: usize = 1232;
+
+/// Compute units of a swap outside of its legs: account checks, the transfer to the
+/// user and the events.
+pub const SWAP_BASE_COMPUTE_UNITS: u32 = 40_000;
+
+/// Compute units of a spl-token-swap leg, its CPI and token transfers included.
+pub const SPL_TOKEN_SWAP_LEG_COMPUTE_UNITS: u32 = 80_000;
+
+/// Compute units of a Serum DEX leg, its order matching and settlement included.
+pub const SERUM_DEX_LEG_COMPUTE_UNITS: u32 = 120_000;
+
+/// Compute units of a Raydium AMM leg, its CPI and token transfers included.
+pub const RAYDIUM_LEG_COMPUTE_UNITS: u32 = 90_000;
+
+/// Compute units of a whirlpool leg crossing its three tick arrays.
+pub const WHIRLPOOL_LEG_COMPUTE_UNITS: u32 = 110_000;
+
+/// Compute units of a StableSwap leg, its invariant iterations included.
+pub const STABLE_SWAP_LEG_COMPUTE_UNITS: u32 = 90_000;
+
+/// Creates a Compute Budget `RequestHeapFrame` instruction, `bytes` being a multiple of
+/// 1KB from 32KB to [LARGE_HEAP_FRAME_BYTES].
+pub fn request_heap_frame(bytes: u32) -> Result<Instruction, ProgramError> {
+    if !(32 * 1024..=LARGE_HEAP_FRAME_BYTES).contains(&bytes) || bytes % 1024 > 0 {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let mut data = vec![1];
+    data.extend_from_slice(&bytes.to_le_bytes());
+    Ok(Instruction {
+        program_id: compute_budget::id(),
+        accounts: vec![],
+        data,
+    })
+}
+
+/// Creates a Compute Budget `SetComputeUnitLimit` instruction.
+pub fn set_compute_unit_limit(units: u32) -> Instruction {
+    let mut data = vec![2];
+    data.extend_from_slice(&units.to_le_bytes());
+    Instruction {
+        program_id: compute_budget::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Creates a Compute Budget `SetComputeUnitPrice` instruction, the priority fee in
+/// micro-lamports per compute unit.
+pub fn set_compute_unit_price(micro_lamports: u64) -> Instruction {
+    let mut data = vec![3];
+    data.extend_from_slice(&micro_lamports.to_le_bytes());
+    Instruction {
+        program_id: compute_budget::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Estimates the compute units of a `Swap` through `route`, erring on the high side.
+pub fn estimate_swap_compute_units(route: &Route) -> Result<u32, ProgramError> {
+    route
+        .legs
+        .iter()
+        .try_fold(SWAP_BASE_COMPUTE_UNITS, |units, (leg, _)| {
+            match leg.dex_type {
+                DEX_TYPE_SPL_TOKEN_SWAP => {
+                    Ok(units.saturating_add(SPL_TOKEN_SWAP_LEG_COMPUTE_UNITS))
+                }
+                DEX_TYPE_SERUM_DEX => Ok(units.saturating_add(SERUM_DEX_LEG_COMPUTE_UNITS)),
+                DEX_TYPE_RAYDIUM => Ok(units.saturating_add(RAYDIUM_LEG_COMPUTE_UNITS)),
+                DEX_TYPE_WHIRLPOOL => Ok(units.saturating_add(WHIRLPOOL_LEG_COMPUTE_UNITS)),
+                DEX_TYPE_STABLE_SWAP => Ok(units.saturating_add(STABLE_SWAP_LEG_COMPUTE_UNITS)),
+                _ => Err(OneSolError::InvalidInput.into()),
+            }
+        })
+}
+
+/// Prepends to `instructions` a request of `compute_units` if above the default limit
+/// and a priority fee of `priority_fee_micro_lamports` per compute unit if nonzero.
+fn with_compute_budget(
+    instructions: &[Instruction],
+    compute_units: u32,
+    priority_fee_micro_lamports: u64,
+) -> Vec<Instruction> {
+    let mut all_instructions = Vec::with_capacity(instructions.len() + 2);
+    if compute_units > DEFAULT_COMPUTE_UNIT_LIMIT {
+        all_instructions.push(set_compute_unit_limit(
+            compute_units.min(MAX_COMPUTE_UNIT_LIMIT),
+        ));
+    }
+    if priority_fee_micro_lamports > 0 {
+        all_instructions.push(set_compute_unit_price(priority_fee_micro_lamports));
+    }
+    all_instructions.extend_from_slice(instructions);
+    all_instructions
+}
+
+/// Creates the message of a transaction running `instructions` paid by `payer`,
+/// requesting `compute_units` if above the default limit and paying
+/// `priority_fee_micro_lamports` per compute unit if nonzero.
+pub fn message_with_compute_budget(
+    instructions: &[Instruction],
+    compute_units: u32,
+    priority_fee_micro_lamports: u64,
+    payer: &Pubkey,
+) -> Message {
+    Message::new(
+        &with_compute_budget(instructions, compute_units, priority_fee_micro_lamports),
+        Some(payer),
+    )
+}
+
+/// Same as [message_with_compute_budget], compiling a version 0 message loading
+/// accounts from `lookup_tables`.
+pub fn versioned_message_with_compute_budget(
+    instructions: &[Instruction],
+    compute_units: u32,
+    priority_fee_micro_lamports: u64,
+    payer: &Pubkey,
+    lookup_tables: &[AddressLookupTable],
+    recent_blockhash: Hash,
+) -> Result<VersionedMessage, ProgramError> {
+    Ok(VersionedMessage::V0(MessageV0::try_compile(
+        payer,
+        &with_compute_budget(instructions, compute_units, priority_fee_micro_lamports),
+        lookup_tables,
+        recent_blockhash,
+    )?))
+}
+
+/// Creates the message of a transaction executing `route`, paid by the user transfer
+/// authority, with the compute budget the route needs.
+pub fn swap_message(
+    route: &Route,
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    slippage_bps: u16,
+    priority_fee_micro_lamports: u64,
+) -> Result<Message, ProgramError> {
+    Ok(message_with_compute_budget(
+        &[route.instruction(program_id, accounts, slippage_bps)?],
+        estimate_swap_compute_units(route)?,
+        priority_fee_micro_lamports,
+        &accounts.user_transfer_authority,
+    ))
+}
+
+/// Same as [swap_message], creating an unsigned version 0 transaction loading the
+/// route accounts from `lookup_tables`.
+pub fn swap_transaction(
+    route: &Route,
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    slippage_bps: u16,
+    priority_fee_micro_lamports: u64,
+    lookup_tables: &[AddressLookupTable],
+    recent_blockhash: Hash,
+) -> Result<VersionedTransaction, ProgramError> {
+    Ok(VersionedTransaction::new_unsigned(
+        versioned_message_with_compute_budget(
+            &[route.instruction(program_id, accounts, slippage_bps)?],
+            estimate_swap_compute_units(route)?,
+            priority_fee_micro_lamports,
+            &accounts.user_transfer_authority,
+            lookup_tables,
+            recent_blockhash,
+        )?,
+    ))
+}
+
+/// Transaction with a legacy or version 0 message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionedTransaction {
+    /// Ed25519 signatures of the message, in signer order
+    pub signatures: Vec<[u8; 64]>,
+    /// Signed message
+    pub message: VersionedMessage,
+}
+
+impl VersionedTransaction {
+    /// Creates a transaction of `message` with all its signatures zeroed.
+    pub fn new_unsigned(message: VersionedMessage) -> Self {
+        let signature_count = message.header().num_required_signatures as usize;
+        VersionedTransaction {
+            signatures: vec![[0u8; 64]; signature_count],
+            message,
+        }
+    }
+
+    /// Returns the accounts that must sign the message, in signature order.
+    pub fn signers(&self) -> &[Pubkey] {
+        &self.message.static_account_keys()[..self.signatures.len()]
+    }
+
+    /// Sets the signature of `signer`, made over [VersionedMessage::serialize].
+    pub fn add_signature(
+        &mut self,
+        signer: &Pubkey,
+        signature: [u8; 64],
+    ) -> Result<(), ProgramError> {
+        let index = self
+            .signers()
+            .iter()
+            .position(|key| key == signer)
+            .ok_or(OneSolError::InvalidInput)?;
+        self.signatures[index] = signature;
+        Ok(())
+    }
+
+    /// Serializes the transaction in its wire format.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(PACKET_DATA_SIZE);
+        append_compact_u16(&mut data, self.signatures.len());
+        for signature in self.signatures.iter() {
+            data.extend_from_slice(signature);
+        }
+        data.extend_from_slice(&self.message.serialize());
+        data
+    }
+
+    /// Returns an error if the serialized transaction exceeds [PACKET_DATA_SIZE].
+    pub fn check_size(&self) -> Result<(), ProgramError> {
+        if self.serialize().len() > PACKET_DATA_SIZE {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dex::DexLeg, lookup_table::route_addresses, message::MESSAGE_VERSION_PREFIX_V0};
+    use onesol::math::BPS_SCALE;
+    use solana_program::instruction::AccountMeta;
+
+    fn route(leg_count: usize) -> Route {
+        let leg = || DexLeg {
+            dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+            accounts: (0..7)
+                .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+                .collect(),
+        };
+        // even ratios, the last leg taking the rounding remainder
+        let ratio = BPS_SCALE / leg_count as u16;
+        let last_ratio = BPS_SCALE - ratio * (leg_count as u16 - 1);
+        Route {
+            legs: (0..leg_count)
+                .map(|i| {
+                    (
+                        leg(),
+                        if i + 1 == leg_count {
+                            last_ratio
+                        } else {
+                            ratio
+                        },
+                    )
+                })
+                .collect(),
+            amounts_in: vec![100; leg_count],
+            amounts_out: vec![90; leg_count],
+            amount_in: 100 * leg_count as u64,
+            amount_out: 90 * leg_count as u64,
+        }
+    }
+
+    fn accounts() -> SwapAccounts {
+        SwapAccounts {
+            protocol: Pubkey::new_unique(),
+            protocol_authority: Pubkey::new_unique(),
+            user_transfer_authority: Pubkey::new_unique(),
+            protocol_token: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            token_program_id: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn test_compute_budget_instructions() {
+        let ix = set_compute_unit_limit(300_000);
+        assert_eq!(
+            ix.program_id.to_string(),
+            "ComputeBudget111111111111111111111111111111"
+        );
+        assert_eq!(ix.data, vec![2, 0xe0, 0x93, 0x04, 0x00]);
+        assert_eq!(
+            set_compute_unit_price(1).data,
+            vec![3, 1, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            request_heap_frame(LARGE_HEAP_FRAME_BYTES).unwrap().data,
+            vec![1, 0x00, 0x00, 0x04, 0x00]
+        );
+        assert!(request_heap_frame(16 * 1024).is_err());
+        assert!(request_heap_frame(32 * 1024 + 1).is_err());
+        assert!(request_heap_frame(512 * 1024).is_err());
+    }
+
+    #[test]
+    fn test_swap_message() {
+        let program_id = Pubkey::new_unique();
+        let accounts = accounts();
+
+        // a single leg fits in the default budget
+        let message = swap_message(&route(1), &program_id, &accounts, 50, 0).unwrap();
+        assert_eq!(message.instructions.len(), 1);
+        assert_eq!(message.account_keys[0], accounts.user_transfer_authority);
+
+        let message = swap_message(&route(3), &program_id, &accounts, 50, 5_000).unwrap();
+        assert_eq!(message.instructions.len(), 3);
+        let budget = message.program_id(0).unwrap();
+        assert_eq!(*budget, compute_budget::id());
+        assert_eq!(
+            message.instructions[0].data,
+            set_compute_unit_limit(SWAP_BASE_COMPUTE_UNITS + 3 * SPL_TOKEN_SWAP_LEG_COMPUTE_UNITS)
+                .data
+        );
+        assert_eq!(
+            message.instructions[1].data,
+            set_compute_unit_price(5_000).data
+        );
+        assert_eq!(*message.program_id(2).unwrap(), program_id);
+    }
+
+    #[test]
+    fn test_compute_unit_limit_is_capped() {
+        let message = message_with_compute_budget(&[], u32::MAX, 0, &Pubkey::new_unique());
+        assert_eq!(
+            message.instructions[0].data,
+            set_compute_unit_limit(MAX_COMPUTE_UNIT_LIMIT).data
+        );
+    }
+
+    #[test]
+    fn test_swap_transaction_with_lookup_table() {
+        let program_id = Pubkey::new_unique();
+        let accounts = accounts();
+        let route = route(4);
+        let legacy = swap_message(&route, &program_id, &accounts, 50, 1_000).unwrap();
+        let legacy_size = 1 + 64 + legacy.serialize().len();
+        assert!(legacy_size > PACKET_DATA_SIZE);
+
+        let table = AddressLookupTable {
+            address: Pubkey::new_unique(),
+            authority: None,
+            deactivation_slot: u64::MAX,
+            addresses: route_addresses(&route, &accounts),
+        };
+        let mut transaction = swap_transaction(
+            &route,
+            &program_id,
+            &accounts,
+            50,
+            1_000,
+            &[table],
+            Hash::default(),
+        )
+        .unwrap();
+        transaction.check_size().unwrap();
+        assert_eq!(transaction.signers(), &[accounts.user_transfer_authority]);
+        assert_eq!(transaction.serialize()[65], MESSAGE_VERSION_PREFIX_V0);
+
+        transaction
+            .add_signature(&accounts.user_transfer_authority, [7u8; 64])
+            .unwrap();
+        assert_eq!(transaction.serialize()[1..65], [7u8; 64][..]);
+        assert!(transaction
+            .add_signature(&Pubkey::new_unique(), [7u8; 64])
+            .is_err());
+    }
+}
@@ -313,6 +313,7 @@ export class OneSolProtocol{
    * @param minimumAmountOut Minimum amount of tokens the user will receive
    * @param tokenSwap0Info 
    * @param tokenSwap1Info 
+   * @param ratios Share of amountIn each token swap takes, in bps summing to 10000
    */
   async swap(
     userTransferAuthority: Account,
@@ -395,7 +396,7 @@ export class OneSolProtocol{
       bflStruct.concat([
         BufferLayout.u8('tokenSwap' + index + 'Type'),
         BufferLayout.u8('tokenSwap' + index + 'AccountsSize'),
-        BufferLayout.u8('tokenSwap' + index + 'Ratio'),
+        BufferLayout.u16('tokenSwap' + index + 'Ratio'),
       ]);
       const swapKeys = element.toKeys();
       dataMap = {...dataMap,
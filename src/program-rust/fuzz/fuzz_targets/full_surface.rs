@@ -0,0 +1,299 @@
+//! Fuzzes the whole processor: the instruction data, the accounts passed and their
+//! contents and owners.
+//!
+//! Each input starts from one of a few valid instructions over a seeded ledger, then
+//! edits its data, swaps, drops or duplicates its accounts and tampers with the data,
+//! owner and lamports of the accounts it passes. It runs against the in-memory ledger of
+//! the integration tests, whose syscall stubs run the token program and the emulated
+//! swap programs. Findings are panics, arithmetic wraps (the release profile keeps the
+//! overflow checks) and successful instructions not conserving lamports.
+//!
+//! Run from this directory with `cargo hfuzz run full_surface`.
+
+#[path = "../../tests/common/mod.rs"]
+mod common;
+
+use arbitrary::Arbitrary;
+use common::{mock_swap_program_id, Harness, TestAccount};
+use honggfuzz::fuzz;
+use onesol::{instruction::ConfigParams, processor::Processor, state::LogLevel};
+use onesol_client::{
+    dex::{inventory_leg, DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{swap, update_config, withdraw_inventory, SwapAccounts},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
+
+/// Ledger and valid instructions the inputs start from.
+struct Fixture {
+    harness: Harness,
+    seeds: Vec<Instruction>,
+}
+
+/// Mint A / mint B through a mock pool, a spl-token-swap pool and the inventory, a
+/// protocol of mint B and the config of `admin`.
+fn fixture() -> Fixture {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let spl_pool = harness.create_spl_token_swap_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let admin = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    harness.create_config(&admin, &Pubkey::default());
+    let oracle = harness.create_pyth_price(100_000_000, -8, 0);
+    harness.create_inventory_pair(&mint_a, &mint_b, &oracle, 50);
+    let (authority, _) = Processor::find_inventory_authority(&harness.program_id);
+    let vault_a = harness.create_token_account(&mint_a, &authority, 100_000);
+    let vault_b = harness.create_token_account(&mint_b, &authority, 100_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+
+    let program_id = harness.program_id;
+    let legs = vec![
+        (
+            DexLeg {
+                dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+                accounts: pool.leg_a_to_b(),
+            },
+            3_334,
+        ),
+        (spl_pool.leg(&mint_a, None).unwrap(), 3_333),
+        (
+            inventory_leg(&program_id, &mint_a, &mint_b, &vault_a, &vault_b, &oracle),
+            3_333,
+        ),
+    ];
+    let accounts = SwapAccounts {
+        protocol: protocol.protocol,
+        protocol_authority: protocol.authority,
+        user_transfer_authority: user,
+        protocol_token: protocol.token,
+        source,
+        destination,
+        token_program_id: spl_token::id(),
+    };
+    let seeds = vec![
+        swap(&program_id, &accounts, 10_000, 0, &legs).unwrap(),
+        withdraw_inventory(&program_id, &admin, &vault_b, &destination, 1_000),
+        update_config(
+            &program_id,
+            &admin,
+            &ConfigParams {
+                oracle_max_age_slots: 25,
+                oracle_conf_multiplier: 0,
+                log_level: LogLevel::Off,
+                tip_account: Pubkey::default(),
+                relayer_fee_max_bps: 30,
+                relayer_fee_max_flat: 1_000,
+                split_parts: 0,
+            },
+        ),
+    ];
+    Fixture { harness, seeds }
+}
+
+#[derive(Arbitrary, Debug)]
+struct FuzzInput {
+    /// Valid instruction to start from
+    seed: u8,
+    /// Edits of the instruction data, in order
+    data: Vec<DataEdit>,
+    /// Edits of the accounts passed, in order
+    metas: Vec<MetaEdit>,
+    /// Edits of the accounts passed, by index into the edited accounts
+    accounts: Vec<(u8, AccountEdit)>,
+    slot: u8,
+}
+
+#[derive(Arbitrary, Debug)]
+enum DataEdit {
+    SetByte(u16, u8),
+    SetU64(u16, u64),
+    Truncate(u16),
+    Append(Vec<u8>),
+}
+
+#[derive(Arbitrary, Debug)]
+enum MetaEdit {
+    /// Passes another account of the ledger instead
+    Substitute(u8, u8),
+    /// Passes an unknown, empty account instead
+    Fresh(u8),
+    ToggleSigner(u8),
+    ToggleWritable(u8),
+    Remove(u8),
+    Duplicate(u8),
+}
+
+#[derive(Arbitrary, Debug)]
+enum AccountEdit {
+    Owner(Owner),
+    SetByte(u16, u8),
+    /// Sets the `amount` of a token account
+    Amount(u64),
+    Truncate(u16),
+    Extend(u8),
+    Lamports(u64),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Owner {
+    Program,
+    Token,
+    System,
+    MockSwap,
+    Other,
+}
+
+/// Index of the `index`-th item of `len`, if any.
+fn pick(index: impl Into<usize>, len: usize) -> Option<usize> {
+    if len == 0 {
+        None
+    } else {
+        Some(index.into() % len)
+    }
+}
+
+fn edit_data(data: &mut Vec<u8>, edit: &DataEdit) {
+    match edit {
+        DataEdit::SetByte(offset, value) => {
+            if let Some(offset) = pick(*offset, data.len()) {
+                data[offset] = *value;
+            }
+        }
+        DataEdit::SetU64(offset, value) => {
+            if let Some(offset) = pick(*offset, data.len().saturating_sub(7)) {
+                data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+            }
+        }
+        DataEdit::Truncate(len) => data.truncate(*len as usize),
+        DataEdit::Append(bytes) => data.extend_from_slice(bytes),
+    }
+}
+
+fn edit_metas(metas: &mut Vec<AccountMeta>, ledger: &[TestAccount], edit: &MetaEdit) {
+    let index = match edit {
+        MetaEdit::Substitute(index, _)
+        | MetaEdit::Fresh(index)
+        | MetaEdit::ToggleSigner(index)
+        | MetaEdit::ToggleWritable(index)
+        | MetaEdit::Remove(index)
+        | MetaEdit::Duplicate(index) => pick(*index, metas.len()),
+    };
+    let index = match index {
+        Some(index) => index,
+        None => return,
+    };
+    match edit {
+        MetaEdit::Substitute(_, account) => {
+            if let Some(account) = pick(*account, ledger.len()) {
+                metas[index].pubkey = ledger[account].key;
+            }
+        }
+        MetaEdit::Fresh(_) => metas[index].pubkey = Pubkey::new_unique(),
+        MetaEdit::ToggleSigner(_) => metas[index].is_signer = !metas[index].is_signer,
+        MetaEdit::ToggleWritable(_) => metas[index].is_writable = !metas[index].is_writable,
+        MetaEdit::Remove(_) => {
+            metas.remove(index);
+        }
+        MetaEdit::Duplicate(_) => {
+            let meta = metas[index].clone();
+            metas.insert(index, meta);
+        }
+    }
+}
+
+fn edit_account(account: &mut TestAccount, program_id: &Pubkey, edit: &AccountEdit) {
+    match edit {
+        AccountEdit::Owner(owner) => {
+            account.owner = match owner {
+                Owner::Program => *program_id,
+                Owner::Token => spl_token::id(),
+                Owner::System => system_program::id(),
+                Owner::MockSwap => mock_swap_program_id(),
+                Owner::Other => Pubkey::new_unique(),
+            }
+        }
+        AccountEdit::SetByte(offset, value) => {
+            if let Some(offset) = pick(*offset, account.data.len()) {
+                account.data[offset] = *value;
+            }
+        }
+        AccountEdit::Amount(amount) => {
+            if account.data.len() == spl_token::state::Account::LEN {
+                account.data[64..72].copy_from_slice(&amount.to_le_bytes());
+            }
+        }
+        AccountEdit::Truncate(len) => account.data.truncate(*len as usize),
+        AccountEdit::Extend(len) => {
+            let len = account.data.len() + *len as usize;
+            account.data.resize(len, 0);
+        }
+        AccountEdit::Lamports(lamports) => account.lamports = *lamports,
+    }
+}
+
+fn run(fixture: &Fixture, input: &FuzzInput) {
+    let mut harness = Harness {
+        program_id: fixture.harness.program_id,
+        accounts: fixture
+            .harness
+            .accounts
+            .iter()
+            .map(|account| TestAccount {
+                key: account.key,
+                owner: account.owner,
+                lamports: account.lamports,
+                data: account.data.clone(),
+            })
+            .collect(),
+    };
+    harness.set_slot(input.slot as u64);
+    let mut ix = fixture.seeds[input.seed as usize % fixture.seeds.len()].clone();
+    for edit in input.data.iter() {
+        edit_data(&mut ix.data, edit);
+    }
+    for edit in input.metas.iter() {
+        edit_metas(&mut ix.accounts, &harness.accounts, edit);
+    }
+    for (index, edit) in input.accounts.iter() {
+        let key = match pick(*index, ix.accounts.len()) {
+            Some(index) => ix.accounts[index].pubkey,
+            None => continue,
+        };
+        if let Some(account) = harness
+            .accounts
+            .iter_mut()
+            .find(|account| account.key == key)
+        {
+            edit_account(account, &fixture.harness.program_id, edit);
+        }
+    }
+
+    let lamports = |harness: &Harness| {
+        harness
+            .accounts
+            .iter()
+            .map(|account| account.lamports as u128)
+            .sum::<u128>()
+    };
+    let before = lamports(&harness);
+    if harness.process(&ix).is_ok() {
+        assert_eq!(lamports(&harness), before, "lamports not conserved");
+    }
+}
+
+fn main() {
+    let fixture = fixture();
+    loop {
+        fuzz!(|input: FuzzInput| {
+            run(&fixture, &input);
+        });
+    }
+}
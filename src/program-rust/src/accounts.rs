@@ -0,0 +1,320 @@
+//! Declarative account lists of instructions
+//!
+//! [instruction_accounts] declares the accounts of an instruction once, with their
+//! constraints, and expands into both the checks the processor runs and the account list
+//! documented on the instruction, so the two can't drift apart: a test compares the
+//! generated list with the doc comments of `instruction.rs`.
+
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::error::OneSolError;
+
+/// Declares the accounts of an instruction as a struct borrowing them, in order.
+///
+/// Each account lists its constraints in brackets, then optionally the seeds of the
+/// program address it must be, naming the field the bump seed found is stored in:
+///
+/// - `signer`: the account signs the instruction
+/// - `writable`: the account is passed writable
+/// - `program_owned`: the account is owned by the program
+///
+/// The struct gets a `from_account_infos(program_id, accounts)` constructor running the
+/// checks, and a `docs()` function listing the accounts as `instruction.rs` documents
+/// them: `` N. `[writable, signer]` `` then the doc comment of the field, followed by the
+/// owner and seeds constraints.
+macro_rules! instruction_accounts {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[doc = $doc:literal])*
+                $field:ident: [$($constraint:ident),*]
+                    $(seeds [$($seed:expr),*] bump $bump:ident)?,
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name<'a, 'info> {
+            $(
+                $(#[doc = $doc])*
+                pub $field: &'a solana_program::account_info::AccountInfo<'info>,
+                $(
+                    /// Bump seed of the program address
+                    pub $bump: u8,
+                )?
+            )*
+        }
+
+        impl<'a, 'info> $name<'a, 'info> {
+            /// Takes the accounts in order, checking their constraints.
+            #[allow(unused_variables)]
+            pub fn from_account_infos(
+                program_id: &solana_program::pubkey::Pubkey,
+                accounts: &'a [solana_program::account_info::AccountInfo<'info>],
+            ) -> Result<Self, solana_program::program_error::ProgramError> {
+                let account_info_iter = &mut accounts.iter();
+                $(
+                    let $field = solana_program::account_info::next_account_info(
+                        account_info_iter,
+                    )?;
+                    $($crate::accounts::check_constraint(
+                        $crate::accounts::Constraint::$constraint,
+                        $field,
+                        program_id,
+                    )?;)*
+                    $(
+                        let $bump = $crate::accounts::check_seeds(
+                            $field,
+                            &[$($seed),*],
+                            program_id,
+                        )?;
+                    )?
+                )*
+                Ok($name {
+                    $($field, $($bump,)?)*
+                })
+            }
+
+            /// Lists the accounts as documented on the instruction.
+            #[allow(dead_code, unused_mut)]
+            pub fn docs() -> Vec<String> {
+                let mut docs = Vec::new();
+                $(
+                    let flags: &[&str] = &[$(
+                        $crate::accounts::Constraint::$constraint.flag()
+                    ),*];
+                    let flags: Vec<&str> =
+                        flags.iter().copied().filter(|flag| !flag.is_empty()).collect();
+                    let mut doc = [$($doc),*].join("").trim().to_string();
+                    $(
+                        if $crate::accounts::Constraint::$constraint
+                            == $crate::accounts::Constraint::program_owned
+                        {
+                            doc.push_str(", owned by 1sol");
+                        }
+                    )*
+                    $(
+                        let _ = stringify!($bump);
+                        doc.push_str(&format!(
+                            ", derived from `[{}]`",
+                            stringify!($($seed),*)
+                        ));
+                    )?
+                    docs.push(format!("{}. `[{}]` {}", docs.len(), flags.join(", "), doc));
+                )*
+                docs
+            }
+        }
+    };
+}
+
+/// Constraint of an account of [instruction_accounts].
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Constraint {
+    /// The account signs the instruction
+    signer,
+    /// The account is passed writable
+    writable,
+    /// The account is owned by the program
+    program_owned,
+}
+
+impl Constraint {
+    /// Flag of the constraint in the documented account list, empty if documented in
+    /// words.
+    pub(crate) fn flag(self) -> &'static str {
+        match self {
+            Constraint::signer => "signer",
+            Constraint::writable => "writable",
+            Constraint::program_owned => "",
+        }
+    }
+}
+
+/// Checks `account_info` meets `constraint`.
+pub(crate) fn check_constraint(
+    constraint: Constraint,
+    account_info: &AccountInfo,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    match constraint {
+        Constraint::signer if !account_info.is_signer => {
+            Err(ProgramError::MissingRequiredSignature)
+        }
+        Constraint::writable if !account_info.is_writable => {
+            Err(OneSolError::AccountNotWritable.into())
+        }
+        Constraint::program_owned if account_info.owner != program_id => {
+            Err(ProgramError::IncorrectProgramId)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks `account_info` is the program address of `seeds`, returning its bump seed.
+pub(crate) fn check_seeds(
+    account_info: &AccountInfo,
+    seeds: &[&[u8]],
+    program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let (key, bump_seed) = Pubkey::find_program_address(seeds, program_id);
+    if *account_info.key != key {
+        return Err(OneSolError::InvalidProgramAddress.into());
+    }
+    Ok(bump_seed)
+}
+
+instruction_accounts! {
+    /// Accounts of `InitConfig`
+    pub(crate) struct InitConfigAccounts {
+        /// admin, payer of the config rent
+        admin: [writable, signer],
+        /// global config
+        config: [writable] seeds [b"config"] bump config_bump,
+        /// System program
+        system_program: [],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts of `UpdateConfig` and `SetConfigAdmin`
+    pub(crate) struct ConfigAdminAccounts {
+        /// config admin
+        admin: [signer],
+        /// global config
+        config: [writable, program_owned],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts of `SetFeeRate`
+    pub(crate) struct SetFeeRateAccounts {
+        /// protocol admin
+        admin: [signer],
+        /// 1sol protocol account
+        protocol: [writable, program_owned],
+        /// treasury token account of the protocol token mint
+        treasury: [],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts of `Pause`, `Unpause` and `TransferAdmin`
+    pub(crate) struct ProtocolAdminAccounts {
+        /// protocol admin
+        admin: [signer],
+        /// 1sol protocol account
+        protocol: [writable, program_owned],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts of `CloseProtocolAccount`
+    pub(crate) struct CloseProtocolAccounts {
+        /// protocol admin
+        admin: [signer],
+        /// 1sol protocol account
+        protocol: [writable, program_owned],
+        /// protocol authority
+        protocol_authority: [],
+        /// protocol token account
+        protocol_token: [writable],
+        /// token account of the protocol token mint receiving the balance left
+        destination: [writable],
+        /// account receiving the lamports of the protocol account
+        receiver: [writable],
+        /// Token program id
+        token_program: [],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::clock::Epoch;
+
+    #[test]
+    fn test_docs_match_instructions() {
+        let source = include_str!("instruction.rs");
+        let docs = [
+            InitConfigAccounts::docs(),
+            ConfigAdminAccounts::docs(),
+            SetFeeRateAccounts::docs(),
+            ProtocolAdminAccounts::docs(),
+            CloseProtocolAccounts::docs(),
+        ];
+        for line in docs.iter().flatten() {
+            assert!(
+                source.contains(&format!("///   {}\n", line)),
+                "instruction.rs does not document `{}`",
+                line
+            );
+        }
+        assert_eq!(
+            InitConfigAccounts::docs()[1],
+            "1. `[writable]` global config, derived from `[b\"config\"]`"
+        );
+    }
+
+    #[test]
+    fn test_from_account_infos() {
+        let program_id = Pubkey::new_unique();
+        let keys = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut lamports = [0u64; 2];
+        let (mut admin_data, mut config_data) = (vec![], vec![]);
+        let [admin_lamports, config_lamports] = &mut lamports;
+        let admin = AccountInfo::new(
+            &keys[0],
+            true,
+            true,
+            admin_lamports,
+            &mut admin_data,
+            &keys[0],
+            false,
+            Epoch::default(),
+        );
+        let mut config = AccountInfo::new(
+            &keys[1],
+            false,
+            true,
+            config_lamports,
+            &mut config_data,
+            &program_id,
+            false,
+            Epoch::default(),
+        );
+        let account_infos = [admin.clone(), config.clone()];
+        let accounts =
+            ConfigAdminAccounts::from_account_infos(&program_id, &account_infos).unwrap();
+        assert_eq!(accounts.config.key, &keys[1]);
+
+        assert_eq!(
+            ConfigAdminAccounts::from_account_infos(&program_id, std::slice::from_ref(&admin))
+                .err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            ConfigAdminAccounts::from_account_infos(&keys[0], &[admin.clone(), config.clone()])
+                .err(),
+            Some(ProgramError::IncorrectProgramId)
+        );
+        // the config isn't the program address of `[b"config"]`
+        assert_eq!(
+            InitConfigAccounts::from_account_infos(
+                &program_id,
+                &[admin.clone(), config.clone(), config.clone()]
+            )
+            .err(),
+            Some(OneSolError::InvalidProgramAddress.into())
+        );
+        config.is_writable = false;
+        assert_eq!(
+            ConfigAdminAccounts::from_account_infos(&program_id, &[admin, config]).err(),
+            Some(OneSolError::AccountNotWritable.into())
+        );
+    }
+}
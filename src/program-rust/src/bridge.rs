@@ -0,0 +1,80 @@
+//! Wormhole token bridge transfers
+
+use crate::instruction::BridgeTransfer;
+use solana_program::pubkey::Pubkey;
+
+/// Token bridge instruction sending a Wormhole-wrapped token back to its origin chain
+pub const TRANSFER_WRAPPED: u8 = 4;
+/// Token bridge instruction locking a native token in the bridge custody
+pub const TRANSFER_NATIVE: u8 = 5;
+/// Seed of the token bridge authority signer, the delegate it transfers tokens as
+pub const AUTHORITY_SIGNER_SEED: &[u8] = b"authority_signer";
+/// Length of the token bridge transfer instruction data
+pub const TRANSFER_DATA_LEN: usize = 55;
+
+/// Returns the authority signer of the token bridge `bridge_program_id`.
+pub fn authority_signer(bridge_program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[AUTHORITY_SIGNER_SEED], bridge_program_id).0
+}
+
+/// Encodes the data of the token bridge transfer of `amount` described by `transfer`:
+/// the instruction index, then the Borsh-encoded nonce, amount, relayer fee, target
+/// address and target chain.
+pub fn transfer_data(transfer: &BridgeTransfer, amount: u64) -> [u8; TRANSFER_DATA_LEN] {
+    let mut data = [0u8; TRANSFER_DATA_LEN];
+    data[0] = if transfer.wrapped {
+        TRANSFER_WRAPPED
+    } else {
+        TRANSFER_NATIVE
+    };
+    data[1..5].copy_from_slice(&transfer.nonce.to_le_bytes());
+    data[5..13].copy_from_slice(&amount.to_le_bytes());
+    data[13..21].copy_from_slice(&transfer.relayer_fee.to_le_bytes());
+    data[21..53].copy_from_slice(&transfer.target_address);
+    data[53..55].copy_from_slice(&transfer.target_chain.to_le_bytes());
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshSerialize;
+
+    /// `TransferNativeData` and `TransferWrappedData` of the token bridge
+    #[derive(BorshSerialize)]
+    struct TransferData {
+        nonce: u32,
+        amount: u64,
+        fee: u64,
+        target_address: [u8; 32],
+        target_chain: u16,
+    }
+
+    #[test]
+    fn test_transfer_data() {
+        let transfer = BridgeTransfer {
+            wrapped: false,
+            nonce: 42,
+            target_chain: 2,
+            target_address: [9u8; 32],
+            relayer_fee: 10,
+        };
+        let mut expected = vec![TRANSFER_NATIVE];
+        TransferData {
+            nonce: 42,
+            amount: 1_000,
+            fee: 10,
+            target_address: [9u8; 32],
+            target_chain: 2,
+        }
+        .serialize(&mut expected)
+        .unwrap();
+        assert_eq!(transfer_data(&transfer, 1_000).to_vec(), expected);
+
+        let transfer = BridgeTransfer {
+            wrapped: true,
+            ..transfer
+        };
+        assert_eq!(transfer_data(&transfer, 1_000)[0], TRANSFER_WRAPPED);
+    }
+}
@@ -0,0 +1,226 @@
+//! Typed wrappers invoking 1sol from other programs
+//!
+//! A program composing with the aggregator builds with the `no-entrypoint` feature and
+//! swaps through [swap], e.g.
+//! `cpi::swap(CpiContext::new(onesol_program, accounts), &SwapParams { .. })`.
+
+use crate::{
+    error::OneSolError,
+    instruction::{DexConfig, MAX_LEGS},
+};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    program_error::ProgramError,
+};
+
+/// Program, accounts and signer seeds of a CPI into 1sol.
+pub struct CpiContext<'a, 'b, 'c, 'info, T> {
+    /// 1sol program
+    pub program: &'a AccountInfo<'info>,
+    /// Accounts of the instruction
+    pub accounts: T,
+    /// Seeds of the PDAs signing the instruction, empty unless the calling program
+    /// signs for the user transfer authority
+    pub signer_seeds: &'b [&'c [&'c [u8]]],
+}
+
+impl<'a, 'b, 'c, 'info, T> CpiContext<'a, 'b, 'c, 'info, T> {
+    /// Creates a context whose accounts are signed for by the outer transaction.
+    pub fn new(program: &'a AccountInfo<'info>, accounts: T) -> Self {
+        CpiContext {
+            program,
+            accounts,
+            signer_seeds: &[],
+        }
+    }
+
+    /// Creates a context signing with the PDAs of `signer_seeds`.
+    pub fn new_with_signer(
+        program: &'a AccountInfo<'info>,
+        accounts: T,
+        signer_seeds: &'b [&'c [&'c [u8]]],
+    ) -> Self {
+        CpiContext {
+            program,
+            accounts,
+            signer_seeds,
+        }
+    }
+}
+
+/// Accounts of a `Swap`, in the order the program expects them.
+pub struct SwapAccounts<'a, 'info> {
+    /// 1sol state account
+    pub protocol: &'a AccountInfo<'info>,
+    /// 1sol authority, derived from the state account
+    pub protocol_authority: &'a AccountInfo<'info>,
+    /// User transfer authority, signing
+    pub user_transfer_authority: &'a AccountInfo<'info>,
+    /// 1sol token account of the DESTINATION mint
+    pub protocol_token: &'a AccountInfo<'info>,
+    /// SOURCE token account of the user
+    pub source: &'a AccountInfo<'info>,
+    /// DESTINATION token account of the user
+    pub destination: &'a AccountInfo<'info>,
+    /// Token program
+    pub token_program: &'a AccountInfo<'info>,
+    /// Accounts of the dex legs, in the order of their dex configs
+    pub dex_accounts: &'a [AccountInfo<'info>],
+}
+
+/// Parameters of a `Swap`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SwapParams<'a> {
+    /// SOURCE amount to swap
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output, prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// Dexes the swap is routed through, each taking `account_size` of the dex accounts
+    pub dex_configs: &'a [DexConfig],
+}
+
+/// Creates the `Swap` instruction of `accounts` and `params`, failing unless the dex
+/// configs account for exactly the dex accounts.
+pub fn swap_instruction<'info>(
+    ctx: &CpiContext<'_, '_, '_, 'info, SwapAccounts<'_, 'info>>,
+    params: &SwapParams,
+) -> Result<Instruction, ProgramError> {
+    let accounts = &ctx.accounts;
+    if params.dex_configs.is_empty() || params.dex_configs.len() > MAX_LEGS {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    let dex_account_count: usize = params.dex_configs.iter().map(|c| c.account_size).sum();
+    if dex_account_count != accounts.dex_accounts.len() {
+        return Err(OneSolError::AccountCountMismatch.into());
+    }
+
+    let mut data = vec![1];
+    data.extend_from_slice(&params.amount_in.to_le_bytes());
+    data.extend_from_slice(&params.minimum_amount_out.to_le_bytes());
+    data.push(params.dex_configs.len() as u8);
+    for dex_config in params.dex_configs.iter() {
+        dex_config.pack(&mut data)?;
+    }
+
+    let mut metas = vec![
+        AccountMeta::new_readonly(*accounts.protocol.key, false),
+        AccountMeta::new_readonly(*accounts.protocol_authority.key, false),
+        AccountMeta::new_readonly(*accounts.user_transfer_authority.key, true),
+        AccountMeta::new(*accounts.protocol_token.key, false),
+        AccountMeta::new(*accounts.source.key, false),
+        AccountMeta::new(*accounts.destination.key, false),
+        AccountMeta::new_readonly(*accounts.token_program.key, false),
+    ];
+    // dex accounts keep the access the outer instruction granted them
+    metas.extend(accounts.dex_accounts.iter().map(|account| AccountMeta {
+        pubkey: *account.key,
+        is_signer: account.is_signer,
+        is_writable: account.is_writable,
+    }));
+    Ok(Instruction {
+        program_id: *ctx.program.key,
+        accounts: metas,
+        data,
+    })
+}
+
+/// Swaps `params.amount_in` of SOURCE into DESTINATION through 1sol.
+pub fn swap<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, SwapAccounts<'_, 'info>>,
+    params: &SwapParams,
+) -> ProgramResult {
+    let ix = swap_instruction(&ctx, params)?;
+    let accounts = &ctx.accounts;
+    let mut account_infos = vec![
+        accounts.protocol.clone(),
+        accounts.protocol_authority.clone(),
+        accounts.user_transfer_authority.clone(),
+        accounts.protocol_token.clone(),
+        accounts.source.clone(),
+        accounts.destination.clone(),
+        accounts.token_program.clone(),
+    ];
+    account_infos.extend_from_slice(accounts.dex_accounts);
+    account_infos.push(ctx.program.clone());
+    invoke_signed(&ix, &account_infos, ctx.signer_seeds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::OneSolInstruction;
+    use solana_program::{clock::Epoch, pubkey::Pubkey};
+
+    #[test]
+    fn test_swap_instruction() {
+        let keys: Vec<Pubkey> = (0..11).map(|_| Pubkey::new_unique()).collect();
+        let mut lamports: Vec<u64> = vec![0; keys.len()];
+        let mut data: Vec<Vec<u8>> = vec![vec![]; keys.len()];
+        let owner = Pubkey::new_unique();
+        let infos: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .enumerate()
+            .map(|(i, ((key, lamports), data))| {
+                AccountInfo::new(
+                    key,
+                    i == 2,
+                    i != 0,
+                    lamports,
+                    data,
+                    &owner,
+                    false,
+                    Epoch::default(),
+                )
+            })
+            .collect();
+        let ctx = CpiContext::new(
+            &infos[0],
+            SwapAccounts {
+                protocol: &infos[1],
+                protocol_authority: &infos[1],
+                user_transfer_authority: &infos[2],
+                protocol_token: &infos[3],
+                source: &infos[4],
+                destination: &infos[5],
+                token_program: &infos[6],
+                dex_accounts: &infos[7..],
+            },
+        );
+        let dex_configs = [
+            DexConfig::new_dex_config(0, 3, 60),
+            DexConfig::new_dex_config(0, 1, 40),
+        ];
+        let params = SwapParams {
+            amount_in: 1_000,
+            minimum_amount_out: 900,
+            dex_configs: &dex_configs,
+        };
+        let ix = swap_instruction(&ctx, &params).unwrap();
+        assert_eq!(ix.program_id, keys[0]);
+        assert_eq!(ix.accounts.len(), 7 + 4);
+        assert!(ix.accounts[2].is_signer);
+        assert_eq!(ix.accounts[7].pubkey, keys[7]);
+        match OneSolInstruction::unpack(&ix.data).unwrap() {
+            OneSolInstruction::Swap(swap) => {
+                assert_eq!(swap.amount_in, 1_000);
+                assert_eq!(swap.minimum_amount_out, 900);
+                assert_eq!(swap.dex_configs.iter().collect::<Vec<_>>(), dex_configs);
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+
+        let params = SwapParams {
+            dex_configs: &dex_configs[..1],
+            ..params
+        };
+        assert_eq!(
+            swap_instruction(&ctx, &params),
+            Err(OneSolError::AccountCountMismatch.into())
+        );
+    }
+}
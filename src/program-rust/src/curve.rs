@@ -0,0 +1,510 @@
+//! Swap curves of the pools a route can split across, quoting on-chain what a leg of a
+//! given amount receives.
+//!
+//! The math is spl-token-swap's: fees are charged on the SOURCE amount, the constant
+//! product rounds the new DESTINATION reserve up, and the stable curve solves the
+//! StableSwap invariant by Newton's method in 256 bits, as Curve's reference pools do.
+
+use crate::{
+    error::OneSolError,
+    math::{price_impact_bps, Fraction},
+};
+use u256::U256;
+
+mod u256 {
+    #![allow(
+        clippy::assign_op_pattern,
+        clippy::manual_div_ceil,
+        clippy::ptr_offset_with_cast
+    )]
+    uint::construct_uint! {
+        pub struct U256(4);
+    }
+}
+
+/// Number of tokens of a pool.
+const N_COINS: u8 = 2;
+
+/// Newton's method iterations, enough for any pair of u64 reserves to converge.
+const ITERATIONS: u8 = 32;
+
+/// A venue whose swaps can be simulated from its reserves and parameters.
+///
+/// Each adapter implements it once, so the router splitting a route, quotes and
+/// off-chain clients all get the output a venue pays from the same code.
+pub trait Curve {
+    /// Returns the DESTINATION amount a swap of `amount_in` SOURCE tokens receives,
+    /// fees included, None if it can't be computed or the venue can't pay it.
+    fn simulate_swap(&self, amount_in: u64) -> Option<u64>;
+
+    /// Estimated compute units of a simulation, bounding the simulations a split may
+    /// take.
+    fn simulate_compute_units(&self) -> u64;
+}
+
+/// Swap curve of a pool.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SwapCurve {
+    /// x * y = k
+    ConstantProduct,
+    /// StableSwap invariant
+    Stable {
+        /// amplification coefficient
+        amp: u64,
+    },
+}
+
+impl SwapCurve {
+    /// Estimated compute units of a quote, bounding the quotes a split may take.
+    pub fn quote_compute_units(&self) -> u64 {
+        match self {
+            SwapCurve::ConstantProduct => 1_000,
+            // two Newton's methods in 256 bits
+            SwapCurve::Stable { .. } => 12_000,
+        }
+    }
+
+    /// Returns the DESTINATION amount a swap of `amount` SOURCE tokens, net of fees,
+    /// receives.
+    pub fn swap_without_fees(
+        &self,
+        amount: u128,
+        source_reserve: u128,
+        destination_reserve: u128,
+    ) -> Option<u128> {
+        match *self {
+            SwapCurve::ConstantProduct => {
+                constant_product_swap(amount, source_reserve, destination_reserve)
+            }
+            SwapCurve::Stable { amp } => {
+                stable_swap(amount, source_reserve, destination_reserve, amp)
+            }
+        }
+    }
+
+    /// Returns the marginal price of SOURCE in DESTINATION units at the given reserves,
+    /// before fees: the rate an infinitely small swap would get.
+    pub fn spot_price(&self, source_reserve: u128, destination_reserve: u128) -> Option<Fraction> {
+        match *self {
+            SwapCurve::ConstantProduct => Fraction::new(destination_reserve, source_reserve).ok(),
+            SwapCurve::Stable { amp } => {
+                stable_spot_price(source_reserve, destination_reserve, amp)
+            }
+        }
+    }
+}
+
+/// Constant product swap, rounding the new DESTINATION reserve up as the pool does.
+pub fn constant_product_swap(
+    amount: u128,
+    source_reserve: u128,
+    destination_reserve: u128,
+) -> Option<u128> {
+    let invariant = source_reserve.checked_mul(destination_reserve)?;
+    let new_source_reserve = source_reserve.checked_add(amount)?;
+    let new_destination_reserve = ceil_div(invariant, new_source_reserve)?;
+    destination_reserve.checked_sub(new_destination_reserve)
+}
+
+/// `numerator / denominator` rounded up, as the quotient of spl-math's `checked_ceil_div`,
+/// which rounds a quotient below 1 to the nearest instead.
+fn ceil_div(numerator: u128, denominator: u128) -> Option<u128> {
+    let quotient = numerator.checked_div(denominator)?;
+    if quotient == 0 {
+        return Some(if numerator.checked_mul(2)? >= denominator {
+            1
+        } else {
+            0
+        });
+    }
+    if numerator.checked_rem(denominator)? > 0 {
+        quotient.checked_add(1)
+    } else {
+        Some(quotient)
+    }
+}
+
+/// StableSwap swap of amplification `amp`.
+pub fn stable_swap(
+    amount: u128,
+    source_reserve: u128,
+    destination_reserve: u128,
+    amp: u64,
+) -> Option<u128> {
+    // spl-token-swap's A is the whitepaper's A * n^(n - 1)
+    let leverage = amp.checked_mul(N_COINS.into())?;
+    let d = compute_d(leverage, source_reserve, destination_reserve)?;
+    let new_source_reserve = source_reserve.checked_add(amount)?;
+    let new_destination_reserve = compute_new_destination_amount(leverage, new_source_reserve, d)?;
+    destination_reserve.checked_sub(new_destination_reserve)
+}
+
+/// Returns D, the StableSwap invariant of reserves `amount_a` and `amount_b`.
+///
+/// Solves `A * n^n * S + D = A * D * n^n + D^(n + 1) / (n^n * P)` for D, S being the sum
+/// and P the product of the reserves.
+fn compute_d(leverage: u64, amount_a: u128, amount_b: u128) -> Option<u128> {
+    let sum_x = amount_a.checked_add(amount_b)?;
+    if sum_x == 0 {
+        return Some(0);
+    }
+    let amount_a_times_coins = U256::from(amount_a).checked_mul(N_COINS.into())?;
+    let amount_b_times_coins = U256::from(amount_b).checked_mul(N_COINS.into())?;
+    let leverage = U256::from(leverage);
+    let mut d = U256::from(sum_x);
+    for _ in 0..ITERATIONS {
+        // D^(n + 1) / (n^n * P)
+        let d_product = d
+            .checked_mul(d)?
+            .checked_div(amount_a_times_coins)?
+            .checked_mul(d)?
+            .checked_div(amount_b_times_coins)?;
+        let d_previous = d;
+        // (A * n^n * S + n * D_P) * D / ((A * n^n - 1) * D + (n + 1) * D_P)
+        let numerator = leverage
+            .checked_mul(sum_x.into())?
+            .checked_add(d_product.checked_mul(N_COINS.into())?)?
+            .checked_mul(d)?;
+        let denominator = d
+            .checked_mul(leverage.checked_sub(1.into())?)?
+            .checked_add(d_product.checked_mul((N_COINS + 1).into())?)?;
+        d = numerator.checked_div(denominator)?;
+        if d == d_previous {
+            break;
+        }
+    }
+    u128_of(d)
+}
+
+/// Returns the DESTINATION reserve keeping the invariant `d` once the SOURCE reserve is
+/// `new_source_amount`.
+///
+/// Solves `y^2 + b * y = c` for y, with `b = x + D / (A * n^n)` and
+/// `c = D^(n + 1) / (n^(2n) * x * A * n^n)`.
+fn compute_new_destination_amount(leverage: u64, new_source_amount: u128, d: u128) -> Option<u128> {
+    let leverage = U256::from(leverage);
+    let new_source_amount = U256::from(new_source_amount);
+    let d = U256::from(d);
+    let c = d.checked_mul(d)?.checked_mul(d)?.checked_div(
+        new_source_amount
+            .checked_mul((N_COINS * N_COINS).into())?
+            .checked_mul(leverage)?,
+    )?;
+    let b = new_source_amount.checked_add(d.checked_div(leverage)?)?;
+    let mut y = d;
+    for _ in 0..ITERATIONS {
+        let y_previous = y;
+        // (y^2 + c) / (2y + b - D)
+        y = y
+            .checked_mul(y)?
+            .checked_add(c)?
+            .checked_div(y.checked_mul(2.into())?.checked_add(b)?.checked_sub(d)?)?;
+        if y == y_previous {
+            break;
+        }
+    }
+    u128_of(y)
+}
+
+/// Marginal price of a StableSwap pool of amplification `amp`.
+///
+/// With `f(x, y) = A * n^n * (x + y) + D - A * D * n^n - D^3 / (n^n * x * y)` the
+/// invariant, the price is `-dy/dx = f'x / f'y`, that is
+/// `y * (4 * A * n^n * x^2 * y + D^3) / (x * (4 * A * n^n * x * y^2 + D^3))` for two tokens.
+fn stable_spot_price(
+    source_reserve: u128,
+    destination_reserve: u128,
+    amp: u64,
+) -> Option<Fraction> {
+    let leverage = amp.checked_mul(N_COINS.into())?;
+    let d = U256::from(compute_d(leverage, source_reserve, destination_reserve)?);
+    let d_cubed = d.checked_mul(d)?.checked_mul(d)?;
+    let x = U256::from(source_reserve);
+    let y = U256::from(destination_reserve);
+    // 4 * A * n^n * x * y
+    let xy_leverage = U256::from(leverage)
+        .checked_mul((N_COINS * N_COINS).into())?
+        .checked_mul(x)?
+        .checked_mul(y)?;
+    let numerator = y.checked_mul(xy_leverage.checked_mul(x)?.checked_add(d_cubed)?)?;
+    let denominator = x.checked_mul(xy_leverage.checked_mul(y)?.checked_add(d_cubed)?)?;
+    if denominator.is_zero() {
+        return None;
+    }
+    // keep the most significant 64 bits of both terms, so the price times any amount
+    // fits in u128
+    let shift = numerator.max(denominator).bits().saturating_sub(64);
+    Fraction::new(
+        u128_of(numerator >> shift)?,
+        u128_of(denominator >> shift)?.max(1),
+    )
+    .ok()
+}
+
+fn u128_of(value: U256) -> Option<u128> {
+    if value > U256::from(u128::MAX) {
+        None
+    } else {
+        Some(value.as_u128())
+    }
+}
+
+/// Trading fees of a pool, charged on the SOURCE amount.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CurveFees {
+    /// Fee staying in the pool
+    pub trade_fee: Fraction,
+    /// Fee minted to the pool owner
+    pub owner_trade_fee: Fraction,
+}
+
+impl CurveFees {
+    /// Creates the fees of a pool from their numerators and denominators, a zero
+    /// numerator being no fee whatever the denominator.
+    pub fn new(
+        trade_fee_numerator: u64,
+        trade_fee_denominator: u64,
+        owner_trade_fee_numerator: u64,
+        owner_trade_fee_denominator: u64,
+    ) -> Result<Self, OneSolError> {
+        Ok(CurveFees {
+            trade_fee: fee(trade_fee_numerator, trade_fee_denominator)?,
+            owner_trade_fee: fee(owner_trade_fee_numerator, owner_trade_fee_denominator)?,
+        })
+    }
+
+    /// Returns the total fee charged on a swap of `amount` SOURCE tokens.
+    pub fn total_fee(&self, amount: u64) -> Option<u64> {
+        calculate_fee(&self.trade_fee, amount)?
+            .checked_add(calculate_fee(&self.owner_trade_fee, amount)?)
+    }
+}
+
+fn fee(numerator: u64, denominator: u64) -> Result<Fraction, OneSolError> {
+    if numerator == 0 {
+        return Fraction::new(0u64, 1u64);
+    }
+    Fraction::new(numerator, denominator)
+}
+
+/// `fee` of `amount`, rounded down but at least 1 if the fee is not zero.
+fn calculate_fee(fee: &Fraction, amount: u64) -> Option<u64> {
+    if fee.numerator == 0 || amount == 0 {
+        return Some(0);
+    }
+    Some(fee.mul_floor(amount).ok()?.max(1))
+}
+
+/// A pool of a route, with its curve, fees and reserves in the direction of the swap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PoolCurve {
+    /// Pool curve
+    pub curve: SwapCurve,
+    /// Pool fees
+    pub fees: CurveFees,
+    /// Reserve of the token swapped from
+    pub source_reserve: u64,
+    /// Reserve of the token swapped to
+    pub destination_reserve: u64,
+}
+
+impl Curve for PoolCurve {
+    fn simulate_swap(&self, amount_in: u64) -> Option<u64> {
+        let amount_less_fees = amount_in.checked_sub(self.fees.total_fee(amount_in)?)?;
+        let amount_out = self.curve.swap_without_fees(
+            amount_less_fees.into(),
+            self.source_reserve.into(),
+            self.destination_reserve.into(),
+        )?;
+        if amount_out > u64::MAX.into() {
+            None
+        } else {
+            Some(amount_out as u64)
+        }
+    }
+
+    fn simulate_compute_units(&self) -> u64 {
+        self.curve.quote_compute_units()
+    }
+}
+
+impl PoolCurve {
+    /// Returns the marginal price of SOURCE in DESTINATION units, before fees.
+    pub fn spot_price(&self) -> Option<Fraction> {
+        self.curve
+            .spot_price(self.source_reserve.into(), self.destination_reserve.into())
+    }
+
+    /// Returns the DESTINATION amount `amount_in` is worth at the spot price, with
+    /// neither fees nor slippage.
+    pub fn spot_amount_out(&self, amount_in: u64) -> Option<u64> {
+        self.spot_price()?.mul_floor(amount_in).ok()
+    }
+
+    /// Returns the price a swap of `amount_in` executes at, in DESTINATION units per
+    /// SOURCE token, fees included.
+    pub fn execution_price(&self, amount_in: u64) -> Option<Fraction> {
+        Fraction::new(self.simulate_swap(amount_in)?, amount_in).ok()
+    }
+
+    /// Returns the price impact in bps of a swap of `amount_in`: how far below the spot
+    /// price it executes, fees included.
+    pub fn price_impact_bps(&self, amount_in: u64) -> Option<u16> {
+        Some(price_impact_bps(
+            self.spot_amount_out(amount_in)?,
+            self.simulate_swap(amount_in)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expected amounts are those of spl-token-swap's `ConstantProductCurve` and
+    // `StableCurve`, computed by a transcription of their math in arbitrary precision.
+
+    fn pool(curve: SwapCurve, source_reserve: u64, destination_reserve: u64) -> PoolCurve {
+        PoolCurve {
+            curve,
+            fees: CurveFees::new(25, 10_000, 5, 10_000).unwrap(),
+            source_reserve,
+            destination_reserve,
+        }
+    }
+
+    #[test]
+    fn test_constant_product_swap() {
+        // new reserve B ceil(1e12 / 1_010_000) = 990_100
+        assert_eq!(
+            constant_product_swap(10_000, 1_000_000, 1_000_000),
+            Some(9_900)
+        );
+        // a quotient below 1 rounds to the nearest
+        assert_eq!(constant_product_swap(1_000, 1, 1), Some(1));
+        assert_eq!(constant_product_swap(1, 1, 1), Some(0));
+        assert_eq!(constant_product_swap(0, 0, 0), None);
+        assert_eq!(constant_product_swap(1, u128::MAX, 2), None);
+    }
+
+    #[test]
+    fn test_compute_d() {
+        // D is the sum of balanced reserves
+        assert_eq!(compute_d(200, 1_000_000, 1_000_000), Some(2_000_000));
+        assert_eq!(compute_d(2, 1_000_000, 3_000_000), Some(3_717_778));
+        assert_eq!(compute_d(20, 1_000_000, 3_000_000), Some(3_941_882));
+        assert_eq!(compute_d(200, 1_000_000, 3_000_000), Some(3_993_431));
+        assert_eq!(compute_d(2_000, 1_000_000, 3_000_000), Some(3_999_334));
+        // D^3 exceeds u128
+        assert_eq!(
+            compute_d(200, 1_000_000_000_000_000, 2_000_000_000_000_000),
+            Some(2_998_146_985_239_894)
+        );
+        assert_eq!(compute_d(200, 0, 0), Some(0));
+        assert_eq!(compute_d(200, 0, 1), None);
+    }
+
+    #[test]
+    fn test_stable_swap() {
+        // the higher the amplification, the closer to 1:1
+        let reference = [
+            (1, 9_951, 633_975),
+            (10, 9_991, 820_872),
+            (100, 10_000, 934_113),
+        ];
+        for (amp, small, large) in reference.iter() {
+            assert_eq!(
+                stable_swap(10_000, 1_000_000, 1_000_000, *amp),
+                Some(*small),
+                "amp {}",
+                amp
+            );
+            assert_eq!(
+                stable_swap(1_000_000, 1_000_000, 1_000_000, *amp),
+                Some(*large),
+                "amp {}",
+                amp
+            );
+        }
+        assert_eq!(stable_swap(10_000, 1_000_000, 3_000_000, 100), Some(10_174));
+        assert_eq!(
+            stable_swap(
+                100_000_000_000_000_000,
+                1_000_000_000_000_000_000,
+                1_000_000_000_000_000_000,
+                100
+            ),
+            Some(99_900_110_864_758_515)
+        );
+        assert_eq!(stable_swap(0, 1_000_000, 1_000_000, 100), Some(0));
+        assert_eq!(stable_swap(10_000, 1_000_000, 1_000_000, 0), None);
+    }
+
+    #[test]
+    fn test_simulate_swap() {
+        // fees 25 + 5
+        let constant_product = pool(SwapCurve::ConstantProduct, 1_000_000, 1_000_000);
+        assert_eq!(constant_product.simulate_swap(10_000), Some(9_871));
+        assert_eq!(constant_product.simulate_swap(20_000), Some(19_550));
+        assert_eq!(constant_product.simulate_swap(0), Some(0));
+        // the fees round up to 1 each, more than the amount
+        assert_eq!(constant_product.simulate_swap(1), None);
+
+        let stable = pool(SwapCurve::Stable { amp: 100 }, 1_000_000, 1_000_000);
+        assert_eq!(stable.simulate_swap(10_000), Some(9_970));
+        assert!(stable.simulate_swap(10_000) > constant_product.simulate_swap(10_000));
+        assert!(stable.simulate_compute_units() > constant_product.simulate_compute_units());
+    }
+
+    #[test]
+    fn test_curve_fees() {
+        let no_trade_fee = CurveFees::new(0, 0, 5, 10_000).unwrap();
+        assert_eq!(no_trade_fee.trade_fee, Fraction::new(0u64, 1u64).unwrap());
+        assert_eq!(no_trade_fee.total_fee(10_000), Some(5));
+        assert_eq!(
+            CurveFees::new(25, 0, 5, 10_000),
+            Err(OneSolError::ConversionFailure)
+        );
+    }
+
+    #[test]
+    fn test_spot_price() {
+        let constant_product = pool(SwapCurve::ConstantProduct, 1_000_000, 2_000_000);
+        assert_eq!(constant_product.spot_amount_out(10_000), Some(20_000));
+        assert_eq!(SwapCurve::ConstantProduct.spot_price(0, 1), None);
+
+        let stable = SwapCurve::Stable { amp: 100 };
+        let balanced = stable.spot_price(1_000_000, 1_000_000).unwrap();
+        assert_eq!(balanced.numerator, balanced.denominator);
+        // the scarcer SOURCE is, the more DESTINATION it is worth, a bit more than the
+        // 10_174 a swap of 10_000 gets
+        let imbalanced = stable.spot_price(1_000_000, 3_000_000).unwrap();
+        assert_eq!(imbalanced.mul_floor(1_000_000u64), Ok(1_017_535));
+        assert_eq!(imbalanced.mul_floor(10_000u64), Ok(10_175));
+        // terms beyond u64 are truncated, not overflowed
+        let large = stable
+            .spot_price(1_000_000_000_000_000_000, 3_000_000_000_000_000_000)
+            .unwrap();
+        assert_eq!(large.mul_floor(1_000_000u64), Ok(1_017_535));
+        assert_eq!(stable.spot_price(0, 0), None);
+    }
+
+    #[test]
+    fn test_price_impact() {
+        let constant_product = pool(SwapCurve::ConstantProduct, 1_000_000, 1_000_000);
+        // 9_871 out of 10_000 at spot
+        assert_eq!(constant_product.price_impact_bps(10_000), Some(129));
+        assert_eq!(
+            constant_product.execution_price(10_000),
+            Fraction::new(9_871u64, 10_000u64).ok()
+        );
+        // the larger the swap, the larger the impact
+        assert_eq!(constant_product.price_impact_bps(100_000), Some(934));
+        assert_eq!(constant_product.price_impact_bps(0), Some(0));
+        assert_eq!(constant_product.execution_price(0), None);
+
+        // fees only
+        let stable = pool(SwapCurve::Stable { amp: 100 }, 1_000_000, 1_000_000);
+        assert_eq!(stable.price_impact_bps(10_000), Some(30));
+    }
+}
@@ -6,6 +6,22 @@ use solana_program::{
     program_error::PrintProgramError, pubkey::Pubkey,
 };
 
+/// Heap of the `large-heap` feature, the largest heap frame a transaction can request.
+/// Routes through many dexes clone their accounts infos into the heap, overflowing the
+/// default 32KB. Transactions must request the frame with a Compute Budget
+/// `RequestHeapFrame` instruction, else the program faults on the first allocation
+/// past 32KB.
+#[cfg(feature = "large-heap")]
+const LARGE_HEAP_LENGTH: usize = 256 * 1024;
+
+#[cfg(all(feature = "large-heap", target_arch = "bpf"))]
+#[global_allocator]
+static ALLOCATOR: solana_program::entrypoint::BumpAllocator =
+    solana_program::entrypoint::BumpAllocator {
+        start: solana_program::entrypoint::HEAP_START_ADDRESS,
+        len: LARGE_HEAP_LENGTH,
+    };
+
 entrypoint!(process_instruction);
 fn process_instruction(
     program_id: &Pubkey,
@@ -14,7 +30,9 @@ fn process_instruction(
 ) -> ProgramResult {
     if let Err(error) = Processor::process(program_id, accounts, instruction_data) {
         // catch the error so we can print it
-        error.print::<OneSolError>();
+        if Processor::log_level(program_id, accounts).logs_errors() {
+            error.print::<OneSolError>();
+        }
         return Err(error);
     }
     Ok(())
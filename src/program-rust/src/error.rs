@@ -1,63 +1,207 @@
 //! Error types
 
+use crate::swappers;
 use num_derive::FromPrimitive;
 use solana_program::{decode_error::DecodeError, program_error::ProgramError};
+use std::ops::RangeInclusive;
 use thiserror::Error;
 
 /// Errors that may be returned by the OneSol program.
+///
+/// The discriminants are the custom error codes clients decode, stable across releases:
+/// never renumber or reuse one, new variants take the next free code.
 #[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
 pub enum OneSolError {
     /// Unknown error.
     #[error("Unknown error")]
-    Unknown,
+    Unknown = 0,
     /// Swap instruction exceeds desired slippage limit
     #[error("Swap instruction exceeds desired slippage limit")]
-    ExceededSlippage,
+    ExceededSlippage = 1,
     /// Address of the provided swap token account is incorrect.
     #[error("Address of the provided swap token account is incorrect")]
-    IncorrectSwapAccount,
+    IncorrectSwapAccount = 2,
     /// Invalid instruction number passed in.
     #[error("Invalid instruction")]
-    InvalidInstruction,
+    InvalidInstruction = 3,
 
     /// The input token is invalid for swap.
     #[error("InvalidInput")]
-    InvalidInput,
+    InvalidInput = 4,
 
     /// The provided token account has a delegate.
     #[error("Token account has a delegate")]
-    InvalidDelegate,
+    InvalidDelegate = 5,
 
     /// The provided token account has a close authority.
     #[error("Token account has a close authority")]
-    InvalidCloseAuthority,
+    InvalidCloseAuthority = 6,
 
     /// The owner of the input isn't set to the program address generated by the program.
     #[error("Input account owner is not the program address")]
-    InvalidOwner,
+    InvalidOwner = 7,
 
     /// The program address provided doesn't match the value generated by the program.
     #[error("Invalid program address generated from nonce and key")]
-    InvalidProgramAddress,
+    InvalidProgramAddress = 8,
 
     /// The deserialization of the account returned something besides State::Account.
     #[error("Deserialized account is not an SPL Token account")]
-    ExpectedAccount,
+    ExpectedAccount = 9,
 
     /// The provided token program does not match the token program expected by the swap
     #[error("The provided token program does not match the token program expected by the swap")]
-    IncorrectTokenProgramId,
+    IncorrectTokenProgramId = 10,
 
     /// ConversionFailure
     #[error("Conversion to u64 failed with an overflow or underflow")]
-    ConversionFailure,
+    ConversionFailure = 11,
 
     /// Given pool token amount results in zero trading tokens
     #[error("Given pool token amount results in zero trading tokens")]
-    ZeroTradingTokens,
+    ZeroTradingTokens = 12,
     /// Internal error
     #[error("internal error")]
-    InternalError,
+    InternalError = 13,
+
+    /// The next slice of the order is not fillable yet.
+    #[error("Order slice is not fillable yet")]
+    OrderNotReady = 14,
+
+    /// Every slice of the order has already been filled.
+    #[error("Order is already filled")]
+    OrderFilled = 15,
+
+    /// The oracle account is not a valid, trading price feed.
+    #[error("Invalid oracle account")]
+    InvalidOracle = 16,
+
+    /// The oracle price has not crossed the order trigger price.
+    #[error("Order trigger price not reached")]
+    OrderNotTriggered = 17,
+
+    /// The order is past its expiry slot.
+    #[error("Order is expired")]
+    OrderExpired = 18,
+
+    /// The order can neither be filled further nor has it expired yet.
+    #[error("Order is still active")]
+    OrderActive = 19,
+
+    /// The current recurring payment period has already been paid.
+    #[error("Recurring payment is not due yet")]
+    PaymentNotDue = 20,
+
+    /// The payment would exceed the recurring payment lifetime cap.
+    #[error("Recurring payment cap exceeded")]
+    PaymentCapExceeded = 21,
+
+    /// The order page has no free slot left.
+    #[error("Order page is full")]
+    OrderPageFull = 22,
+
+    /// The destination account is not the one the order owner declared.
+    #[error("Incorrect order destination account")]
+    IncorrectDestinationAccount = 23,
+
+    /// The oracle price is older than the configured max age.
+    #[error("Oracle price is stale")]
+    OracleStale = 24,
+
+    /// The swap executed too far from the oracle price.
+    #[error("Swap price deviates from the oracle price")]
+    OraclePriceDeviation = 25,
+
+    /// Deserialized account is not an SPL Token mint
+    #[error("Deserialized account is not an SPL Token mint")]
+    ExpectedMint = 26,
+
+    /// The swap price moved too far within the pair's circuit breaker window.
+    #[error("Pair circuit breaker tripped")]
+    CircuitBreakerTripped = 27,
+
+    // 28 was never released: the compute budget check it failed had no syscall to read.
+    /// The callback of a flash swap did not repay the vault with its fee.
+    #[error("Flash swap not repaid")]
+    FlashSwapNotRepaid = 29,
+
+    /// A flash borrow is not followed by the repayment of its loan in its transaction.
+    #[error("Flash loan not repaid")]
+    FlashLoanNotRepaid = 30,
+
+    /// A revealed swap does not match the commitment it claims.
+    #[error("Swap commitment mismatch")]
+    CommitmentMismatch = 31,
+
+    /// A committed swap is revealed in the slot it was committed in.
+    #[error("Swap commitment revealed too early")]
+    CommitmentTooRecent = 32,
+
+    /// A swap tips an account other than the tip account of the global config.
+    #[error("Invalid tip account")]
+    InvalidTipAccount = 33,
+
+    /// A stake account is not the stake of the account it is passed for.
+    #[error("Invalid stake account")]
+    InvalidStakeAccount = 34,
+
+    /// An unstake exceeds the amount staked.
+    #[error("Insufficient stake")]
+    InsufficientStake = 35,
+
+    /// The trader of a gated pair does not hold the credential of its gate.
+    #[error("Missing credential")]
+    MissingCredential = 36,
+
+    /// The reimbursement of a relayed swap exceeds the caps of the global config.
+    #[error("Relayer fee too high")]
+    RelayerFeeTooHigh = 37,
+
+    /// The protocol-owned inventory can't fill the swap.
+    #[error("Insufficient inventory")]
+    InsufficientInventory = 38,
+
+    /// The ratios of the legs of a route don't sum to 10_000 bps.
+    #[error("Leg ratios don't sum to 10_000 bps")]
+    RatioSumInvalid = 39,
+
+    /// A leg routes through a dex the program does not route through.
+    #[error("Dex program not allowed")]
+    DexProgramNotAllowed = 40,
+
+    /// The accounts passed for a leg or a composed instruction are not as many as it
+    /// takes.
+    #[error("Account count mismatch")]
+    AccountCountMismatch = 41,
+
+    /// The protocol is paused.
+    #[error("Protocol is paused")]
+    Paused = 42,
+
+    /// The delegation of a token account can't cover the amount pulled from it.
+    #[error("Insufficient delegation")]
+    InsufficientDelegation = 43,
+
+    /// An account the instruction writes to is passed read-only.
+    #[error("Account not writable")]
+    AccountNotWritable = 44,
+
+    /// A swap owing the protocol a fee is not passed the protocol treasury.
+    #[error("Invalid treasury")]
+    InvalidTreasury = 45,
+
+    /// An exact output swap routes through a leg the program can't quote on-chain.
+    #[error("Leg can't be quoted on-chain")]
+    QuoteUnavailable = 46,
+
+    /// A swap's referral fee exceeds the cap set by the protocol admin.
+    #[error("Referral fee too high")]
+    ReferralFeeTooHigh = 47,
+
+    /// The legs of a swap signed by the program spent a different amount of its source
+    /// than routed through them.
+    #[error("Source amount mismatch")]
+    SourceAmountMismatch = 48,
 }
 impl From<OneSolError> for ProgramError {
     fn from(e: OneSolError) -> Self {
@@ -69,3 +213,525 @@ impl<T> DecodeError<T> for OneSolError {
         "OneSolError"
     }
 }
+
+/// Name and description of a custom error code of the program.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ErrorCode {
+    /// Custom error code, as in `ProgramError::Custom`
+    pub code: u32,
+    /// Name of the [OneSolError] variant
+    pub name: &'static str,
+    /// Human readable description
+    pub description: &'static str,
+}
+
+/// Every [OneSolError] code, in code order, for clients rendering
+/// `ProgramError::Custom` without linking the program. Codes with [LEG_ERROR_FLAG] set
+/// are leg errors: decode them with [LegError::from_code], their downstream code
+/// being one of these or a code of the dex.
+pub const ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode {
+        code: 0,
+        name: "Unknown",
+        description: "Unknown error",
+    },
+    ErrorCode {
+        code: 1,
+        name: "ExceededSlippage",
+        description: "Swap instruction exceeds desired slippage limit",
+    },
+    ErrorCode {
+        code: 2,
+        name: "IncorrectSwapAccount",
+        description: "Address of the provided swap token account is incorrect",
+    },
+    ErrorCode {
+        code: 3,
+        name: "InvalidInstruction",
+        description: "Invalid instruction",
+    },
+    ErrorCode {
+        code: 4,
+        name: "InvalidInput",
+        description: "InvalidInput",
+    },
+    ErrorCode {
+        code: 5,
+        name: "InvalidDelegate",
+        description: "Token account has a delegate",
+    },
+    ErrorCode {
+        code: 6,
+        name: "InvalidCloseAuthority",
+        description: "Token account has a close authority",
+    },
+    ErrorCode {
+        code: 7,
+        name: "InvalidOwner",
+        description: "Input account owner is not the program address",
+    },
+    ErrorCode {
+        code: 8,
+        name: "InvalidProgramAddress",
+        description: "Invalid program address generated from nonce and key",
+    },
+    ErrorCode {
+        code: 9,
+        name: "ExpectedAccount",
+        description: "Deserialized account is not an SPL Token account",
+    },
+    ErrorCode {
+        code: 10,
+        name: "IncorrectTokenProgramId",
+        description:
+            "The provided token program does not match the token program expected by the swap",
+    },
+    ErrorCode {
+        code: 11,
+        name: "ConversionFailure",
+        description: "Conversion to u64 failed with an overflow or underflow",
+    },
+    ErrorCode {
+        code: 12,
+        name: "ZeroTradingTokens",
+        description: "Given pool token amount results in zero trading tokens",
+    },
+    ErrorCode {
+        code: 13,
+        name: "InternalError",
+        description: "internal error",
+    },
+    ErrorCode {
+        code: 14,
+        name: "OrderNotReady",
+        description: "Order slice is not fillable yet",
+    },
+    ErrorCode {
+        code: 15,
+        name: "OrderFilled",
+        description: "Order is already filled",
+    },
+    ErrorCode {
+        code: 16,
+        name: "InvalidOracle",
+        description: "Invalid oracle account",
+    },
+    ErrorCode {
+        code: 17,
+        name: "OrderNotTriggered",
+        description: "Order trigger price not reached",
+    },
+    ErrorCode {
+        code: 18,
+        name: "OrderExpired",
+        description: "Order is expired",
+    },
+    ErrorCode {
+        code: 19,
+        name: "OrderActive",
+        description: "Order is still active",
+    },
+    ErrorCode {
+        code: 20,
+        name: "PaymentNotDue",
+        description: "Recurring payment is not due yet",
+    },
+    ErrorCode {
+        code: 21,
+        name: "PaymentCapExceeded",
+        description: "Recurring payment cap exceeded",
+    },
+    ErrorCode {
+        code: 22,
+        name: "OrderPageFull",
+        description: "Order page is full",
+    },
+    ErrorCode {
+        code: 23,
+        name: "IncorrectDestinationAccount",
+        description: "Incorrect order destination account",
+    },
+    ErrorCode {
+        code: 24,
+        name: "OracleStale",
+        description: "Oracle price is stale",
+    },
+    ErrorCode {
+        code: 25,
+        name: "OraclePriceDeviation",
+        description: "Swap price deviates from the oracle price",
+    },
+    ErrorCode {
+        code: 26,
+        name: "ExpectedMint",
+        description: "Deserialized account is not an SPL Token mint",
+    },
+    ErrorCode {
+        code: 27,
+        name: "CircuitBreakerTripped",
+        description: "Pair circuit breaker tripped",
+    },
+    ErrorCode {
+        code: 29,
+        name: "FlashSwapNotRepaid",
+        description: "Flash swap not repaid",
+    },
+    ErrorCode {
+        code: 30,
+        name: "FlashLoanNotRepaid",
+        description: "Flash loan not repaid",
+    },
+    ErrorCode {
+        code: 31,
+        name: "CommitmentMismatch",
+        description: "Swap commitment mismatch",
+    },
+    ErrorCode {
+        code: 32,
+        name: "CommitmentTooRecent",
+        description: "Swap commitment revealed too early",
+    },
+    ErrorCode {
+        code: 33,
+        name: "InvalidTipAccount",
+        description: "Invalid tip account",
+    },
+    ErrorCode {
+        code: 34,
+        name: "InvalidStakeAccount",
+        description: "Invalid stake account",
+    },
+    ErrorCode {
+        code: 35,
+        name: "InsufficientStake",
+        description: "Insufficient stake",
+    },
+    ErrorCode {
+        code: 36,
+        name: "MissingCredential",
+        description: "Missing credential",
+    },
+    ErrorCode {
+        code: 37,
+        name: "RelayerFeeTooHigh",
+        description: "Relayer fee too high",
+    },
+    ErrorCode {
+        code: 38,
+        name: "InsufficientInventory",
+        description: "Insufficient inventory",
+    },
+    ErrorCode {
+        code: 39,
+        name: "RatioSumInvalid",
+        description: "Leg ratios don't sum to 10_000 bps",
+    },
+    ErrorCode {
+        code: 40,
+        name: "DexProgramNotAllowed",
+        description: "Dex program not allowed",
+    },
+    ErrorCode {
+        code: 41,
+        name: "AccountCountMismatch",
+        description: "Account count mismatch",
+    },
+    ErrorCode {
+        code: 42,
+        name: "Paused",
+        description: "Protocol is paused",
+    },
+    ErrorCode {
+        code: 43,
+        name: "InsufficientDelegation",
+        description: "Insufficient delegation",
+    },
+    ErrorCode {
+        code: 44,
+        name: "AccountNotWritable",
+        description: "Account not writable",
+    },
+    ErrorCode {
+        code: 45,
+        name: "InvalidTreasury",
+        description: "Invalid treasury",
+    },
+    ErrorCode {
+        code: 46,
+        name: "QuoteUnavailable",
+        description: "Leg can't be quoted on-chain",
+    },
+    ErrorCode {
+        code: 47,
+        name: "ReferralFeeTooHigh",
+        description: "Referral fee too high",
+    },
+    ErrorCode {
+        code: 48,
+        name: "SourceAmountMismatch",
+        description: "Source amount mismatch",
+    },
+];
+
+/// Looks up the name and description of the custom error code `code`, of the core
+/// program or of a dex adapter.
+pub fn error_code(code: u32) -> Option<&'static ErrorCode> {
+    match AdapterError::from_code(code) {
+        Some(adapter_error) => swappers::adapter_error_codes(adapter_error.dex_type)
+            .iter()
+            .find(|error| error.code == code),
+        None => ERROR_CODES.iter().find(|error| error.code == code),
+    }
+}
+
+/// First custom error code of the dex adapters. The codes below are the core
+/// [OneSolError] codes.
+pub const ADAPTER_ERROR_BASE: u32 = 0x1000;
+/// Number of custom error codes reserved for the adapter of each dex type.
+pub const ADAPTER_ERROR_SPAN: u32 = 0x40;
+
+/// Error specific to the adapter of a dex type, in the range of codes reserved for it:
+/// `ADAPTER_ERROR_BASE + dex_type * ADAPTER_ERROR_SPAN + code`.
+///
+/// Adapters define their errors without colliding with the core errors or with each
+/// other, and the codes fit the downstream code of a [LegError].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AdapterError {
+    /// Dex type of the adapter
+    pub dex_type: u8,
+    /// Error of the adapter, below [ADAPTER_ERROR_SPAN]
+    pub code: u8,
+}
+
+impl AdapterError {
+    /// Encodes the adapter error as a custom error code.
+    pub fn to_code(&self) -> u32 {
+        ADAPTER_ERROR_BASE + self.dex_type as u32 * ADAPTER_ERROR_SPAN + self.code as u32
+    }
+
+    /// Decodes a custom error code, if it is in the range of an adapter.
+    pub fn from_code(code: u32) -> Option<Self> {
+        let offset = code.checked_sub(ADAPTER_ERROR_BASE)?;
+        let dex_type = offset / ADAPTER_ERROR_SPAN;
+        if dex_type > u8::MAX as u32 {
+            return None;
+        }
+        Some(AdapterError {
+            dex_type: dex_type as u8,
+            code: (offset % ADAPTER_ERROR_SPAN) as u8,
+        })
+    }
+
+    /// Returns the range of the codes reserved for the adapter of `dex_type`.
+    pub fn code_range(dex_type: u8) -> RangeInclusive<u32> {
+        let start = ADAPTER_ERROR_BASE + dex_type as u32 * ADAPTER_ERROR_SPAN;
+        start..=start + ADAPTER_ERROR_SPAN - 1
+    }
+}
+
+impl From<AdapterError> for ProgramError {
+    fn from(e: AdapterError) -> Self {
+        ProgramError::Custom(e.to_code())
+    }
+}
+
+/// Flag set in the custom error code of a failed dex leg.
+pub const LEG_ERROR_FLAG: u32 = 0x8000_0000;
+/// Downstream code of a leg failing with a builtin (non-custom) program error.
+const LEG_ERROR_BUILTIN: u16 = 0x7fff;
+/// Shift of the leg index in the code of a leg error.
+const LEG_INDEX_SHIFT: u32 = 23;
+
+/// Context of a failed dex leg, encoded in a custom error code as
+/// `LEG_ERROR_FLAG | leg_index << 23 | dex_type << 15 | downstream_code`.
+///
+/// The leg error codes are partitioned by leg: leg `i` fails with a code of
+/// [LegError::leg_code_range]`(i)`, from `LEG_ERROR_FLAG + (i << 23)` on, whatever its
+/// dex and downstream error, so clients can tell the failed venue and retry a route
+/// without it.
+///
+/// The runtime aborts the whole transaction when a dex program itself fails, so this
+/// covers the failures the program observes: invoking the dex, or its own leg checks.
+/// Dex failures are attributed by the leg log line preceding each CPI.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LegError {
+    /// Index of the failed leg
+    pub leg_index: u8,
+    /// Dex type of the failed leg
+    pub dex_type: u8,
+    /// Custom error code of the failure, truncated to 15 bits, or `0x7fff` for
+    /// builtin program errors
+    pub downstream_code: u16,
+}
+
+impl LegError {
+    /// Wraps the `error` a leg failed with.
+    pub fn new(leg_index: u8, dex_type: u8, error: &ProgramError) -> Self {
+        let downstream_code = match error {
+            ProgramError::Custom(code) => (*code).min(LEG_ERROR_BUILTIN as u32 - 1) as u16,
+            _ => LEG_ERROR_BUILTIN,
+        };
+        LegError {
+            leg_index,
+            dex_type,
+            downstream_code,
+        }
+    }
+
+    /// Encodes the leg error as a custom error code.
+    pub fn to_code(&self) -> u32 {
+        LEG_ERROR_FLAG
+            | (self.leg_index as u32) << LEG_INDEX_SHIFT
+            | (self.dex_type as u32) << 15
+            | self.downstream_code as u32
+    }
+
+    /// Decodes a custom error code, if it is a leg error.
+    pub fn from_code(code: u32) -> Option<Self> {
+        if code & LEG_ERROR_FLAG == 0 {
+            return None;
+        }
+        Some(LegError {
+            leg_index: (code >> LEG_INDEX_SHIFT) as u8,
+            dex_type: (code >> 15) as u8,
+            downstream_code: (code & 0x7fff) as u16,
+        })
+    }
+
+    /// Returns the range of the codes leg `leg_index` fails with.
+    pub fn leg_code_range(leg_index: u8) -> RangeInclusive<u32> {
+        let start = LEG_ERROR_FLAG + ((leg_index as u32) << LEG_INDEX_SHIFT);
+        start..=start + ((1 << LEG_INDEX_SHIFT) - 1)
+    }
+}
+
+impl From<LegError> for ProgramError {
+    fn from(e: LegError) -> Self {
+        ProgramError::Custom(e.to_code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leg_error_code() {
+        let error = LegError::new(3, 1, &ProgramError::Custom(17));
+        assert_eq!(error.downstream_code, 17);
+        assert_eq!(LegError::from_code(error.to_code()), Some(error));
+        let error = LegError::new(255, 255, &ProgramError::InvalidAccountData);
+        assert_eq!(error.downstream_code, 0x7fff);
+        assert_eq!(LegError::from_code(error.to_code()), Some(error));
+        assert_eq!(
+            LegError::new(0, 0, &ProgramError::Custom(u32::MAX)).downstream_code,
+            0x7ffe
+        );
+        assert_eq!(
+            LegError::from_code(OneSolError::ExceededSlippage as u32),
+            None
+        );
+    }
+
+    #[test]
+    fn test_adapter_error_code() {
+        let error = AdapterError {
+            dex_type: 1,
+            code: 2,
+        };
+        assert_eq!(error.to_code(), 0x1042);
+        assert_eq!(AdapterError::from_code(error.to_code()), Some(error));
+        assert!(AdapterError::code_range(1).contains(&error.to_code()));
+        assert_eq!(
+            AdapterError::code_range(0).end() + 1,
+            *AdapterError::code_range(1).start()
+        );
+        // the core codes stay below the adapter ranges
+        assert!((ERROR_CODES.len() as u32) < ADAPTER_ERROR_BASE);
+        assert_eq!(AdapterError::from_code(ERROR_CODES.len() as u32), None);
+        // the last adapter code is a leg error downstream code of its own
+        let last = *AdapterError::code_range(u8::MAX).end();
+        assert_eq!(
+            LegError::new(0, 0, &ProgramError::Custom(last)).downstream_code as u32,
+            last
+        );
+    }
+
+    #[test]
+    fn test_leg_code_range() {
+        for leg_index in [0u8, 1, 7, 255].iter() {
+            let range = LegError::leg_code_range(*leg_index);
+            for (dex_type, error) in [
+                (0, ProgramError::Custom(0)),
+                (255, ProgramError::Custom(u32::MAX)),
+                (255, ProgramError::InvalidAccountData),
+            ]
+            .iter()
+            {
+                let code = LegError::new(*leg_index, *dex_type, error).to_code();
+                assert!(range.contains(&code));
+                assert_eq!(LegError::from_code(code).unwrap().leg_index, *leg_index);
+            }
+        }
+        assert_eq!(*LegError::leg_code_range(0).start(), LEG_ERROR_FLAG);
+        assert_eq!(
+            LegError::leg_code_range(0).end() + 1,
+            *LegError::leg_code_range(1).start()
+        );
+        assert_eq!(*LegError::leg_code_range(255).end(), u32::MAX);
+    }
+
+    #[test]
+    fn test_error_codes_are_stable() {
+        // released codes, decoded by clients: never change one
+        let codes = [
+            (OneSolError::Unknown, 0),
+            (OneSolError::ExceededSlippage, 1),
+            (OneSolError::InvalidInput, 4),
+            (OneSolError::InternalError, 13),
+            (OneSolError::InvalidOracle, 16),
+            (OneSolError::OracleStale, 24),
+            (OneSolError::RelayerFeeTooHigh, 37),
+            (OneSolError::InsufficientInventory, 38),
+            (OneSolError::RatioSumInvalid, 39),
+            (OneSolError::DexProgramNotAllowed, 40),
+            (OneSolError::AccountCountMismatch, 41),
+            (OneSolError::Paused, 42),
+            (OneSolError::InsufficientDelegation, 43),
+            (OneSolError::AccountNotWritable, 44),
+            (OneSolError::InvalidTreasury, 45),
+            (OneSolError::QuoteUnavailable, 46),
+            (OneSolError::ReferralFeeTooHigh, 47),
+            (OneSolError::SourceAmountMismatch, 48),
+        ];
+        for (error, code) in codes.iter() {
+            assert_eq!(error.clone() as u32, *code);
+            assert_eq!(
+                ProgramError::from(error.clone()),
+                ProgramError::Custom(*code)
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_codes_table() {
+        use num_traits::FromPrimitive;
+
+        for (index, entry) in ERROR_CODES.iter().enumerate() {
+            // in order, with 28 left out
+            let expected = if index < 28 { index } else { index + 1 };
+            assert_eq!(entry.code as usize, expected);
+            let error = OneSolError::from_u32(entry.code).unwrap();
+            assert_eq!(format!("{:?}", error), entry.name);
+            assert_eq!(error.to_string(), entry.description);
+            assert_eq!(error_code(entry.code), Some(entry));
+        }
+        // every variant is in the table
+        assert!(OneSolError::from_u32(28).is_none());
+        assert_eq!(error_code(28), None);
+        let next = ERROR_CODES.len() as u32 + 1;
+        assert!(OneSolError::from_u32(next).is_none());
+        assert_eq!(error_code(next), None);
+        assert_eq!(error_code(LEG_ERROR_FLAG), None);
+    }
+}
@@ -0,0 +1,325 @@
+//! Structured program events
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{msg, pubkey::Pubkey};
+
+/// An event logged by the program, prefixed with its Anchor discriminator: the first
+/// 8 bytes of `sha256("event:<name>")`.
+pub trait Event: BorshSerialize {
+    /// Anchor discriminator of the event
+    const DISCRIMINATOR: [u8; 8];
+}
+
+/// Layout version of [SwapEvent], bumped whenever its fields change.
+pub const SWAP_EVENT_VERSION: u8 = 1;
+
+/// Execution report of one dex leg of a swap.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct LegReport {
+    /// Dex type of the leg
+    pub dex_type: u8,
+    /// SOURCE amount routed through the leg
+    pub amount_in: u64,
+    /// DESTINATION amount the leg realized
+    pub amount_out: u64,
+    /// Price impact in bps of the leg against the pool's spot price before it, fees
+    /// included; None if the venue can't be quoted on-chain
+    pub price_impact_bps: Option<u16>,
+}
+
+/// Emitted at the end of every swap, with the fill of each of its dex legs.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct SwapEvent {
+    /// `SWAP_EVENT_VERSION` of the layout
+    pub version: u8,
+    /// Mint of the SOURCE token
+    pub input_mint: Pubkey,
+    /// Mint of the DESTINATION token
+    pub output_mint: Pubkey,
+    /// SOURCE amount swapped
+    pub amount_in: u64,
+    /// DESTINATION amount delivered to the user
+    pub amount_out: u64,
+    /// DESTINATION amount kept as fees, `protocol_fee` included
+    pub fee: u64,
+    /// Part of `fee` paid to the protocol treasury
+    pub protocol_fee: u64,
+    /// User transfer authority
+    pub user: Pubkey,
+    /// Executed legs, in execution order
+    pub legs: Vec<LegReport>,
+}
+
+impl Event for SwapEvent {
+    const DISCRIMINATOR: [u8; 8] = [64, 198, 205, 232, 38, 8, 113, 226];
+}
+
+/// Kind of a collected fee.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeeKind {
+    /// Protocol fee, kept by the protocol treasury
+    Protocol = 0,
+    /// Host fee, paid to the integrating front-end
+    Host = 1,
+    /// Referral fee, paid to a referrer
+    Referral = 2,
+    /// Keeper fee, paid to the keeper cranking an order
+    Keeper = 3,
+    /// Flash swap fee, repaid into the protocol vault
+    FlashSwap = 4,
+    /// Flash loan fee, repaid into the protocol vault
+    FlashLoan = 5,
+    /// Relayer fee, reimbursing the relayer of a swap out of its output
+    Relayer = 6,
+}
+
+/// Emitted whenever a fee is taken, so treasury accounting can be rebuilt from logs.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct FeeCollectedEvent {
+    /// `FeeKind` of the fee
+    pub kind: u8,
+    /// Owner of the tokens the fee is taken from
+    pub payer: Pubkey,
+    /// Mint of the fee
+    pub mint: Pubkey,
+    /// Fee amount
+    pub amount: u64,
+    /// Token account receiving the fee
+    pub recipient: Pubkey,
+}
+
+impl Event for FeeCollectedEvent {
+    const DISCRIMINATOR: [u8; 8] = [142, 253, 94, 133, 187, 191, 46, 40];
+}
+
+/// Emitted when the output of a swap is sent cross-chain through the Wormhole token
+/// bridge.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct BridgeTransferEvent {
+    /// Mint of the bridged token
+    pub mint: Pubkey,
+    /// Amount bridged, relayer fee included
+    pub amount: u64,
+    /// Wormhole chain id of the recipient
+    pub target_chain: u16,
+    /// Recipient on the target chain
+    pub target_address: [u8; 32],
+    /// Nonce of the Wormhole message
+    pub nonce: u32,
+    /// User transfer authority
+    pub user: Pubkey,
+}
+
+impl Event for BridgeTransferEvent {
+    const DISCRIMINATOR: [u8; 8] = [43, 28, 60, 241, 25, 242, 55, 208];
+}
+
+/// Emitted when the output of a swap is deposited into a token-lending reserve.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct LendingDepositEvent {
+    /// Mint of the deposited token
+    pub mint: Pubkey,
+    /// Amount deposited
+    pub amount: u64,
+    /// Token-lending program of the reserve
+    pub lending_program: Pubkey,
+    /// Obligation credited with the collateral
+    pub obligation: Pubkey,
+    /// User transfer authority
+    pub user: Pubkey,
+}
+
+impl Event for LendingDepositEvent {
+    const DISCRIMINATOR: [u8; 8] = [172, 16, 170, 237, 255, 186, 129, 203];
+}
+
+/// Emitted when the output of a swap is deposited into an SPL stake pool.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct StakePoolDepositEvent {
+    /// Stake pool deposited into
+    pub stake_pool: Pubkey,
+    /// Lamports deposited
+    pub lamports: u64,
+    /// Pool tokens received
+    pub pool_tokens: u64,
+    /// User transfer authority
+    pub user: Pubkey,
+}
+
+impl Event for StakePoolDepositEvent {
+    const DISCRIMINATOR: [u8; 8] = [30, 99, 94, 89, 206, 101, 71, 115];
+}
+
+/// Emitted when a swap tips the configured tip account, tracking the tips bundles built
+/// around 1sol swaps pay.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct TipPaidEvent {
+    /// User transfer authority paying the tip
+    pub user: Pubkey,
+    /// Account tipped
+    pub tip_account: Pubkey,
+    /// Lamports tipped
+    pub lamports: u64,
+}
+
+impl Event for TipPaidEvent {
+    const DISCRIMINATOR: [u8; 8] = [91, 169, 148, 176, 24, 97, 172, 22];
+}
+
+/// Logs a discriminator-prefixed, Borsh-encoded event as a base64 `Program data:` line,
+/// the format the `sol_log_data` syscall logs in, so indexers decode it without parsing
+/// `msg!` text.
+pub fn emit<T: Event>(event: &T) {
+    let mut data = T::DISCRIMINATOR.to_vec();
+    if event.serialize(&mut data).is_ok() {
+        msg!("Program data: {}", base64::encode(data));
+    }
+}
+
+/// Decodes the base64-decoded data of a `Program data:` line into a `T` event, None if
+/// the line logs another event.
+pub fn decode<T: Event + BorshDeserialize>(data: &[u8]) -> Option<T> {
+    if data.len() < 8 || data[..8] != T::DISCRIMINATOR {
+        return None;
+    }
+    T::try_from_slice(&data[8..]).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::hash::hash;
+
+    #[test]
+    fn test_swap_event_encoding() {
+        let event = SwapEvent {
+            version: SWAP_EVENT_VERSION,
+            input_mint: Pubkey::new_from_array([1u8; 32]),
+            output_mint: Pubkey::new_from_array([2u8; 32]),
+            amount_in: 1_000,
+            amount_out: 985,
+            fee: 0,
+            protocol_fee: 4,
+            user: Pubkey::new_from_array([3u8; 32]),
+            legs: vec![
+                LegReport {
+                    dex_type: 0,
+                    amount_in: 600,
+                    amount_out: 594,
+                    price_impact_bps: Some(101),
+                },
+                LegReport {
+                    dex_type: 0,
+                    amount_in: 400,
+                    amount_out: 395,
+                    price_impact_bps: None,
+                },
+            ],
+        };
+        let data = event.try_to_vec().unwrap();
+        // an Option is a 1 byte tag, followed by the value when Some
+        assert_eq!(data.len(), 1 + 32 + 32 + 8 * 4 + 32 + 4 + 2 * 18 + 2);
+        assert_eq!(data[0], SWAP_EVENT_VERSION);
+        assert_eq!(data[65..73], 1_000u64.to_le_bytes());
+        assert_eq!(SwapEvent::try_from_slice(&data).unwrap(), event);
+        assert_eq!(
+            SwapEvent::DISCRIMINATOR,
+            hash(b"event:SwapEvent").to_bytes()[..8]
+        );
+
+        let mut logged = SwapEvent::DISCRIMINATOR.to_vec();
+        logged.extend_from_slice(&data);
+        assert_eq!(decode::<SwapEvent>(&logged), Some(event));
+        assert_eq!(decode::<TipPaidEvent>(&logged), None);
+        assert_eq!(decode::<SwapEvent>(&logged[..8]), None);
+    }
+
+    #[test]
+    fn test_fee_collected_event_encoding() {
+        let event = FeeCollectedEvent {
+            kind: FeeKind::Keeper as u8,
+            payer: Pubkey::new_from_array([1u8; 32]),
+            mint: Pubkey::new_from_array([2u8; 32]),
+            amount: 15,
+            recipient: Pubkey::new_from_array([3u8; 32]),
+        };
+        let data = event.try_to_vec().unwrap();
+        assert_eq!(data[0], 3);
+        assert_eq!(FeeCollectedEvent::try_from_slice(&data).unwrap(), event);
+        assert_eq!(
+            FeeCollectedEvent::DISCRIMINATOR,
+            hash(b"event:FeeCollectedEvent").to_bytes()[..8]
+        );
+    }
+
+    #[test]
+    fn test_bridge_transfer_event_encoding() {
+        let event = BridgeTransferEvent {
+            mint: Pubkey::new_from_array([1u8; 32]),
+            amount: 990,
+            target_chain: 2,
+            target_address: [4u8; 32],
+            nonce: 7,
+            user: Pubkey::new_from_array([3u8; 32]),
+        };
+        let data = event.try_to_vec().unwrap();
+        assert_eq!(data.len(), 32 + 8 + 2 + 32 + 4 + 32);
+        assert_eq!(BridgeTransferEvent::try_from_slice(&data).unwrap(), event);
+        assert_eq!(
+            BridgeTransferEvent::DISCRIMINATOR,
+            hash(b"event:BridgeTransferEvent").to_bytes()[..8]
+        );
+    }
+
+    #[test]
+    fn test_lending_deposit_event_encoding() {
+        let event = LendingDepositEvent {
+            mint: Pubkey::new_from_array([1u8; 32]),
+            amount: 990,
+            lending_program: Pubkey::new_from_array([2u8; 32]),
+            obligation: Pubkey::new_from_array([4u8; 32]),
+            user: Pubkey::new_from_array([3u8; 32]),
+        };
+        let data = event.try_to_vec().unwrap();
+        assert_eq!(data.len(), 32 + 8 + 32 + 32 + 32);
+        assert_eq!(LendingDepositEvent::try_from_slice(&data).unwrap(), event);
+        assert_eq!(
+            LendingDepositEvent::DISCRIMINATOR,
+            hash(b"event:LendingDepositEvent").to_bytes()[..8]
+        );
+    }
+
+    #[test]
+    fn test_stake_pool_deposit_event_encoding() {
+        let event = StakePoolDepositEvent {
+            stake_pool: Pubkey::new_from_array([1u8; 32]),
+            lamports: 990,
+            pool_tokens: 950,
+            user: Pubkey::new_from_array([3u8; 32]),
+        };
+        let data = event.try_to_vec().unwrap();
+        assert_eq!(data.len(), 32 + 8 + 8 + 32);
+        assert_eq!(StakePoolDepositEvent::try_from_slice(&data).unwrap(), event);
+        assert_eq!(
+            StakePoolDepositEvent::DISCRIMINATOR,
+            hash(b"event:StakePoolDepositEvent").to_bytes()[..8]
+        );
+    }
+
+    #[test]
+    fn test_tip_paid_event_encoding() {
+        let event = TipPaidEvent {
+            user: Pubkey::new_from_array([1u8; 32]),
+            tip_account: Pubkey::new_from_array([2u8; 32]),
+            lamports: 10_000,
+        };
+        let data = event.try_to_vec().unwrap();
+        assert_eq!(data.len(), 32 + 32 + 8);
+        assert_eq!(TipPaidEvent::try_from_slice(&data).unwrap(), event);
+        assert_eq!(
+            TipPaidEvent::DISCRIMINATOR,
+            hash(b"event:TipPaidEvent").to_bytes()[..8]
+        );
+    }
+}
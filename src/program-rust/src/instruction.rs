@@ -1,11 +1,41 @@
 //! Instruction types
 
-use crate::error::OneSolError;
-use solana_program::program_error::ProgramError;
-use std::convert::TryInto;
+use crate::{
+    error::OneSolError,
+    math::fee_of,
+    state::{LogLevel, OneSolState, OrderKind, StakeTier, STAKE_TIERS},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use std::{
+    convert::{TryFrom, TryInto},
+    io::{Result as IoResult, Write},
+    mem::size_of,
+};
+
+/// Most dex legs an instruction can route through
+pub const MAX_LEGS: usize = 16;
+
+/// Most swaps a `BatchSwap` executes
+pub const MAX_BATCH_SWAPS: usize = 8;
+
+/// Most chunks the global config may split a swap into
+pub const MAX_SPLIT_PARTS: u8 = 32;
+
+/// `Swap` oracle deviation leaving the output unbounded by the oracle, to tip without
+/// an oracle bound
+pub const NO_ORACLE_BOUND: u16 = u16::MAX;
+
+/// First byte of a versioned instruction, above the tags of the v1 encoding. The
+/// version byte follows it, then the instruction in the encoding of that version.
+pub const VERSIONED_INSTRUCTION: u8 = u8::MAX;
+
+/// Version of the Borsh encoding: the v1 tag of the instruction, then the Borsh
+/// encoding of its data, the dex configs a `u32` count followed by the packed configs.
+pub const INSTRUCTION_V2: u8 = 2;
 
 /// Initialize instruction data
-#[derive(Clone, Debug, PartialEq)]
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
 pub struct Initialize {
     /// nonce used to create validate program address
     pub nonce: u8,
@@ -13,51 +43,808 @@ pub struct Initialize {
 
 /// Swap instruction data
 #[derive(Clone, Debug, PartialEq)]
-pub struct Swap {
+pub struct Swap<'a> {
     /// SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
     pub amount_in: u64,
     /// Minimum amount of DESTINATION token to output, prevents excessive slippage
     pub minimum_amount_out: u64,
-    /// dexes configs
-    pub dex_configs: Vec<DexConfig>,
+    /// dexes configs, with the minimum output of each leg if bounded
+    pub dex_configs: DexConfigs<'a>,
+    /// Maximum shortfall, in bps, of the output against the oracle price, if bounded
+    pub oracle_max_deviation_bps: Option<u16>,
+    /// Lamports tipped by the user transfer authority to the configured tip account
+    pub tip_lamports: u64,
+    /// Chunks `amount_in` is split into on-chain across the legs, replacing their ratios,
+    /// 0 leaving it to the global config
+    pub split_parts: u8,
+    /// Share of the output, in bps and net of the protocol fee, paid to the referrer,
+    /// capped by the protocol admin
+    pub referral_fee_bps: u16,
     // /// supportTokenSwap
     // pub token_swap_config: (bool, usize),
     // /// second token swap config
     // pub token_swap_2_config: (bool, usize),
 }
 
+/// The fields in order, then the minimums of the legs as a Borsh `Vec<u64>` if bounded,
+/// then the referral fee if set.
+impl BorshSerialize for Swap<'_> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        self.amount_in.serialize(writer)?;
+        self.minimum_amount_out.serialize(writer)?;
+        self.dex_configs.serialize(writer)?;
+        self.oracle_max_deviation_bps.serialize(writer)?;
+        self.tip_lamports.serialize(writer)?;
+        self.split_parts.serialize(writer)?;
+        if self.dex_configs.has_minimums() {
+            (self.dex_configs.len() as u32).serialize(writer)?;
+            writer.write_all(self.dex_configs.minimums)?;
+        }
+        if self.referral_fee_bps > 0 {
+            self.referral_fee_bps.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// OracleSwap instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct OracleSwap<'a> {
+    /// SOURCE amount to transfer
+    pub amount_in: u64,
+    /// Maximum shortfall, in bps, of the output against the oracle price
+    pub max_deviation_bps: u16,
+    /// dexes configs
+    pub dex_configs: DexConfigs<'a>,
+}
+
+/// InitConfig / UpdateConfig instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ConfigParams {
+    /// oldest oracle price, in slots, oracle-consuming paths accept
+    pub oracle_max_age_slots: u64,
+    /// multiple of the oracle confidence interval widening oracle-bounded swaps deviation
+    pub oracle_conf_multiplier: u16,
+    /// verbosity of the logs: 0 off, 1 errors only, 2 full
+    pub log_level: LogLevel,
+    /// account swaps may tip lamports to, the default pubkey disabling tips
+    pub tip_account: Pubkey,
+    /// largest share, in bps of the output, a relayed swap reimburses its relayer
+    pub relayer_fee_max_bps: u16,
+    /// largest flat amount a relayed swap reimburses its relayer
+    pub relayer_fee_max_flat: u64,
+    /// chunks swaps split their input into across the legs quoted on-chain, 0 disabling
+    pub split_parts: u8,
+}
+
+/// ConfigureCircuitBreaker instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ConfigureCircuitBreaker {
+    /// largest price move, in bps, within a window, 0 disabling the circuit breaker
+    pub max_move_bps: u16,
+    /// slots a window lasts
+    pub window_slots: u64,
+}
+
 /// DexConfig
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct DexConfig {
     /// dex_type is dex type:
     ///     0: spl_token_swap
     pub dex_type: u8,
     /// account_size: the size of accountInfos
     pub account_size: usize,
-    /// ratio: the ratio of exchange
-    pub ratio: u8,
+    /// ratio: the share of `amount_in` the dex swaps, in basis points
+    pub ratio: u16,
+    /// minimum_amount_out: the least output of the dex, None bounding it by its share
+    /// of the route's minimum. Packed apart from the config, see [DexConfigs::minimums]
+    pub minimum_amount_out: Option<u64>,
+}
+
+/// Dex configs of an instruction, borrowed from its data and decoded on access
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DexConfigs<'a> {
+    data: &'a [u8],
+    /// Packed `u64` minimum output of each config, 0 leaving it unbounded; empty when
+    /// none of them is bounded
+    minimums: &'a [u8],
+}
+
+impl<'a> DexConfigs<'a> {
+    /// Wraps packed dex configs, failing unless there are 1 to [MAX_LEGS] of them.
+    pub fn new(data: &'a [u8]) -> Result<Self, ProgramError> {
+        if data.is_empty()
+            || data.len() > MAX_LEGS * DexConfig::LEN
+            || !data.chunks_exact(DexConfig::LEN).remainder().is_empty()
+        {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        Ok(DexConfigs {
+            data,
+            minimums: &[],
+        })
+    }
+
+    /// Bounds the output of each dex config by the packed `u64` of `minimums`, 0 leaving
+    /// it unbounded, failing unless there is one per config.
+    pub fn with_minimums(self, minimums: &'a [u8]) -> Result<Self, ProgramError> {
+        if minimums.len() != self.len() * size_of::<u64>() {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        Ok(DexConfigs { minimums, ..self })
+    }
+
+    /// Returns true if the output of the dex configs is bounded one by one.
+    pub fn has_minimums(&self) -> bool {
+        !self.minimums.is_empty()
+    }
+
+    /// Packs the minimum output of each dex config into `output`, if bounded one by one.
+    pub fn pack_minimums(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(self.minimums);
+    }
+
+    /// Number of dex configs.
+    pub fn len(&self) -> usize {
+        self.data.len() / DexConfig::LEN
+    }
+
+    /// Always false, there is at least one dex config.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the dex config `index`.
+    pub fn get(&self, index: usize) -> Option<DexConfig> {
+        self.iter().nth(index)
+    }
+
+    /// Iterates over the dex configs.
+    pub fn iter(&self) -> impl Iterator<Item = DexConfig> + 'a {
+        let (minimums, width) = (self.minimums, size_of::<u64>());
+        self.data
+            .chunks_exact(DexConfig::LEN)
+            .enumerate()
+            .map(move |(i, bytes)| DexConfig {
+                minimum_amount_out: minimums
+                    .get(i * width..(i + 1) * width)
+                    .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+                    .filter(|&minimum| minimum > 0),
+                ..DexConfig::from_bytes(bytes)
+            })
+    }
+
+    /// Packs the number of dex configs then the configs into `output`.
+    pub fn pack(&self, output: &mut Vec<u8>) {
+        output.push(self.len() as u8);
+        output.extend_from_slice(self.data);
+    }
+
+    /// Splits the dex configs before `mid`, which must leave configs on both sides.
+    fn split_at(&self, mid: usize) -> (DexConfigs<'a>, DexConfigs<'a>) {
+        let (first, second) = self.data.split_at(mid * DexConfig::LEN);
+        let minimums = if self.has_minimums() {
+            self.minimums.split_at(mid * size_of::<u64>())
+        } else {
+            (self.minimums, self.minimums)
+        };
+        (
+            DexConfigs {
+                data: first,
+                minimums: minimums.0,
+            },
+            DexConfigs {
+                data: second,
+                minimums: minimums.1,
+            },
+        )
+    }
+}
+
+/// A Borsh `Vec` of dex configs, each of which packs as the Borsh `(u8, u8, u16)`.
+impl BorshSerialize for DexConfigs<'_> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        (self.len() as u32).serialize(writer)?;
+        writer.write_all(self.data)
+    }
+}
+
+/// A swap of a `BatchSwap`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BatchSwapEntry {
+    /// SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output, prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// number of trailing accounts following the user accounts of the swap
+    pub trailing_accounts: u8,
+}
+
+impl BatchSwapEntry {
+    /// Length of a packed batch swap entry
+    pub const LEN: usize = 17;
+
+    fn from_bytes(bytes: &[u8]) -> BatchSwapEntry {
+        BatchSwapEntry {
+            amount_in: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            minimum_amount_out: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            trailing_accounts: bytes[16],
+        }
+    }
+
+    /// Packs the entry into `output`.
+    pub fn pack(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&self.amount_in.to_le_bytes());
+        output.extend_from_slice(&self.minimum_amount_out.to_le_bytes());
+        output.push(self.trailing_accounts);
+    }
+}
+
+/// Swaps of a `BatchSwap`, borrowed from its data and decoded on access
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BatchSwapEntries<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BatchSwapEntries<'a> {
+    /// Wraps packed entries, failing unless there are 1 to [MAX_BATCH_SWAPS] of them.
+    pub fn new(data: &'a [u8]) -> Result<Self, ProgramError> {
+        if data.is_empty()
+            || data.len() > MAX_BATCH_SWAPS * BatchSwapEntry::LEN
+            || !data
+                .chunks_exact(BatchSwapEntry::LEN)
+                .remainder()
+                .is_empty()
+        {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        Ok(BatchSwapEntries { data })
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.data.len() / BatchSwapEntry::LEN
+    }
+
+    /// Always false, there is at least one entry.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Iterates over the entries.
+    pub fn iter(&self) -> impl Iterator<Item = BatchSwapEntry> + 'a {
+        self.data
+            .chunks_exact(BatchSwapEntry::LEN)
+            .map(BatchSwapEntry::from_bytes)
+    }
+
+    /// Packs the number of entries then the entries into `output`.
+    pub fn pack(&self, output: &mut Vec<u8>) {
+        output.push(self.len() as u8);
+        output.extend_from_slice(self.data);
+    }
+}
+
+/// A Borsh `Vec` of entries, each of which packs as the Borsh `(u64, u64, u8)`.
+impl BorshSerialize for BatchSwapEntries<'_> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        (self.len() as u32).serialize(writer)?;
+        writer.write_all(self.data)
+    }
+}
+
+/// BatchSwap instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct BatchSwap<'a> {
+    /// swaps of the batch, in account order
+    pub swaps: BatchSwapEntries<'a>,
+    /// dexes configs, shared by the swaps
+    pub dex_configs: DexConfigs<'a>,
+}
+
+/// A swap of a `SwapBatch`, through a route of its own
+#[derive(BorshSerialize, Clone, Copy, Debug, PartialEq)]
+pub struct SwapBatchEntry<'a> {
+    /// SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output, prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// number of trailing accounts following the dex accounts of the swap
+    pub trailing_accounts: u8,
+    /// dexes configs of the swap
+    pub dex_configs: DexConfigs<'a>,
+}
+
+impl<'a> SwapBatchEntry<'a> {
+    /// Length of the fields of a packed entry ahead of its dex configs
+    const HEAD_LEN: usize = 17;
+
+    /// Splits an entry off the front of `input`, the number of its dex configs packed on
+    /// `count_width` little-endian bytes.
+    fn split(input: &'a [u8], count_width: usize) -> Result<(Self, &'a [u8]), ProgramError> {
+        if input.len() < Self::HEAD_LEN + count_width {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let (head, rest) = input.split_at(Self::HEAD_LEN);
+        let (count, rest) = rest.split_at(count_width);
+        let len = count
+            .iter()
+            .rev()
+            .fold(0usize, |count, byte| count << 8 | *byte as usize)
+            .checked_mul(DexConfig::LEN)
+            .filter(|&len| len <= rest.len())
+            .ok_or(OneSolError::InvalidInput)?;
+        let (dex_configs, rest) = rest.split_at(len);
+        let entry = SwapBatchEntry {
+            amount_in: u64::from_le_bytes(head[0..8].try_into().unwrap()),
+            minimum_amount_out: u64::from_le_bytes(head[8..16].try_into().unwrap()),
+            trailing_accounts: head[16],
+            dex_configs: DexConfigs::new(dex_configs)?,
+        };
+        Ok((entry, rest))
+    }
+
+    /// Packs the entry into `output`.
+    pub fn pack(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&self.amount_in.to_le_bytes());
+        output.extend_from_slice(&self.minimum_amount_out.to_le_bytes());
+        output.push(self.trailing_accounts);
+        self.dex_configs.pack(output);
+    }
+}
+
+/// Swaps of a `SwapBatch`, borrowed from its data and decoded on access
+#[derive(Clone, Copy, Debug)]
+pub struct SwapBatchEntries<'a> {
+    data: &'a [u8],
+    len: usize,
+    /// Bytes counting the dex configs of an entry, 1 packed and 4 in the Borsh encoding
+    count_width: usize,
+}
+
+impl<'a> SwapBatchEntries<'a> {
+    /// Wraps `len` packed entries filling `data`, failing unless there are 1 to
+    /// [MAX_BATCH_SWAPS] of them.
+    pub fn new(data: &'a [u8], len: usize) -> Result<Self, ProgramError> {
+        match Self::split(data, len, 1)? {
+            (entries, []) => Ok(entries),
+            _ => Err(OneSolError::InvalidInput.into()),
+        }
+    }
+
+    /// Borrows `len` entries off the front of `input`, failing unless there are 1 to
+    /// [MAX_BATCH_SWAPS] of them.
+    fn split(
+        input: &'a [u8],
+        len: usize,
+        count_width: usize,
+    ) -> Result<(Self, &'a [u8]), ProgramError> {
+        if len == 0 || len > MAX_BATCH_SWAPS {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let mut rest = input;
+        for _ in 0..len {
+            rest = SwapBatchEntry::split(rest, count_width)?.1;
+        }
+        let entries = SwapBatchEntries {
+            data: &input[..input.len() - rest.len()],
+            len,
+            count_width,
+        };
+        Ok((entries, rest))
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Always false, there is at least one entry.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over the entries.
+    pub fn iter(&self) -> impl Iterator<Item = SwapBatchEntry<'a>> + 'a {
+        let (mut rest, count_width) = (self.data, self.count_width);
+        (0..self.len).map(move |_| {
+            // checked when wrapped
+            let (entry, next) = SwapBatchEntry::split(rest, count_width).unwrap();
+            rest = next;
+            entry
+        })
+    }
+
+    /// Packs the number of entries then the entries into `output`.
+    pub fn pack(&self, output: &mut Vec<u8>) {
+        output.push(self.len as u8);
+        for entry in self.iter() {
+            entry.pack(output);
+        }
+    }
+}
+
+/// Entries are equal whichever the encoding they were unpacked from.
+impl PartialEq for SwapBatchEntries<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+/// A Borsh `Vec` of entries.
+impl BorshSerialize for SwapBatchEntries<'_> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        (self.len as u32).serialize(writer)?;
+        self.iter().try_for_each(|entry| entry.serialize(writer))
+    }
+}
+
+/// SwapBatch instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct SwapBatch<'a> {
+    /// swaps of the batch, in account order
+    pub swaps: SwapBatchEntries<'a>,
+}
+
+/// PlaceOrder instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct PlaceOrder {
+    /// nonce used to create validate order authority address
+    pub nonce: u8,
+    /// order kind
+    pub kind: OrderKind,
+    /// SOURCE amount escrowed by the order
+    pub amount_in: u64,
+    /// DESTINATION amount expected for the whole `amount_in`
+    pub expected_amount_out: u64,
+    /// max deviation below the expected price per slice, in bps
+    pub max_deviation_bps: u16,
+    /// number of slices, must be 1 for limit orders
+    pub num_slices: u16,
+    /// seconds between two slices
+    pub slice_interval: u32,
+    /// oracle (or pool) price triggering conditional orders, 0 for other kinds
+    pub trigger_price: u64,
+    /// last slot the order can be filled at
+    pub expires_at_slot: u64,
+    /// keeper fee per fill, in bps of the filled amount
+    pub keeper_fee_bps: u16,
+    /// keeper fee per completed slice and for the expiry crank
+    pub keeper_fee_flat: u64,
+}
+
+/// FillOrder instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct FillOrder<'a> {
+    /// SOURCE amount to fill, at most the unfilled part of the current slice;
+    /// 0 fills all of it
+    pub amount_in: u64,
+    /// dexes configs
+    pub dex_configs: DexConfigs<'a>,
+}
+
+/// CreateRecurringPayment instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct CreateRecurringPayment {
+    /// nonce used to create validate recurring payment authority address
+    pub nonce: u8,
+    /// SOURCE amount swapped each period
+    pub amount_in: u64,
+    /// minimum settlement amount delivered each period
+    pub minimum_amount_out: u64,
+    /// seconds between two payments
+    pub period: u32,
+    /// cap on the SOURCE amount swapped over the lifetime of the payment
+    pub max_total_in: u64,
+    /// nonce used to create validate escrow authority address
+    pub escrow_nonce: u8,
+}
+
+/// ExecuteRecurringPayment instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct ExecuteRecurringPayment<'a> {
+    /// dexes configs
+    pub dex_configs: DexConfigs<'a>,
+}
+
+/// AddOrderPage instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct AddOrderPage {
+    /// index of the new page among the pages of the mint pair
+    pub page_index: u32,
+}
+
+/// BatchFillOrders instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct BatchFillOrders<'a> {
+    /// nonce of the batch authority, derived from the batch SOURCE token account
+    pub nonce: u8,
+    /// number of orders filled, each filling the rest of its current slice
+    pub num_orders: u8,
+    /// dexes the combined amount is routed through
+    pub dex_configs: DexConfigs<'a>,
+}
+
+/// FlashSwap instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct FlashSwap<'a> {
+    /// SOURCE amount taken from the protocol vault and swapped
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output, prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// number of accounts passed to the callback program
+    pub callback_accounts: u8,
+    /// dexes configs
+    pub dex_configs: DexConfigs<'a>,
+    /// instruction data of the callback, the rest of the instruction data
+    pub callback_data: &'a [u8],
+}
+
+impl<'a> FlashSwap<'a> {
+    /// Returns the fee of a flash swap of `amount_in`, see [flash_fee].
+    pub fn fee(amount_in: u64) -> Option<u64> {
+        flash_fee(amount_in)
+    }
+}
+
+/// FlashBorrow instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct FlashBorrow {
+    /// amount lent out of the vault
+    pub amount: u64,
+}
+
+/// FlashRepay instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct FlashRepay {
+    /// amount borrowed, repaid with its [flash_fee]
+    pub amount: u64,
+}
+
+/// Wormhole token bridge transfer of the output of a swap
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct BridgeTransfer {
+    /// whether the DESTINATION token is a Wormhole-wrapped token, sent back to its
+    /// origin chain, rather than a native one locked in the bridge custody
+    pub wrapped: bool,
+    /// nonce of the Wormhole message
+    pub nonce: u32,
+    /// Wormhole chain id of the recipient
+    pub target_chain: u16,
+    /// recipient on the target chain, left-padded to 32 bytes
+    pub target_address: [u8; 32],
+    /// part of the bridged amount paid to the relayer redeeming the transfer
+    pub relayer_fee: u64,
+}
+
+/// SwapAndBridge instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct SwapAndBridge<'a> {
+    /// SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output, prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// transfer of the output
+    pub transfer: BridgeTransfer,
+    /// number of accounts passed to the token bridge
+    pub bridge_accounts: u8,
+    /// dexes configs
+    pub dex_configs: DexConfigs<'a>,
+}
+
+/// RelayedSwap instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct RelayedSwap<'a> {
+    /// SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token paid to the user, net of the reimbursement
+    pub minimum_amount_out: u64,
+    /// share, in bps of the output, reimbursing the relayer
+    pub fee_bps: u16,
+    /// flat amount of DESTINATION token reimbursing the relayer
+    pub fee_flat: u64,
+    /// dexes configs
+    pub dex_configs: DexConfigs<'a>,
+}
+
+/// SwapAndDeposit instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct SwapAndDeposit<'a> {
+    /// SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output, prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// number of accounts passed to the lending program
+    pub lending_accounts: u8,
+    /// dexes configs
+    pub dex_configs: DexConfigs<'a>,
+}
+
+/// SwapTwoSteps instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct SwapTwoSteps<'a> {
+    /// SOURCE amount to transfer into the first step
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token the second step outputs, prevents excessive
+    /// slippage
+    pub minimum_amount_out: u64,
+    /// number of dex configs of the first step, the rest being of the second
+    pub first_step_legs: u8,
+    /// dexes configs of both steps, the first step's first
+    pub dex_configs: DexConfigs<'a>,
+}
+
+impl<'a> SwapTwoSteps<'a> {
+    /// Dex configs of the first step, swapping into the intermediate mint, and of the
+    /// second, swapping out of it.
+    pub fn steps(&self) -> (DexConfigs<'a>, DexConfigs<'a>) {
+        self.dex_configs.split_at(self.first_step_legs as usize)
+    }
+}
+
+/// SwapAndStake instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct SwapAndStake<'a> {
+    /// SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
+    pub amount_in: u64,
+    /// Minimum amount of SOL to output, prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// number of accounts passed to the stake pool program
+    pub stake_pool_accounts: u8,
+    /// dexes configs
+    pub dex_configs: DexConfigs<'a>,
+}
+
+/// CommitSwap instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct CommitSwap {
+    /// commitment to the swap, see [crate::state::swap_commitment]
+    pub commitment: [u8; 32],
+}
+
+/// SetConfigAdmin instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct SetConfigAdmin {
+    /// new config admin, a wallet or an SPL Governance governance account
+    pub new_admin: Pubkey,
+}
+
+/// SetFeeRate instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct SetFeeRate {
+    /// numerator of the share of the output swaps pay the treasury
+    pub fee_numerator: u64,
+    /// denominator of the share, 0 only along with a numerator of 0
+    pub fee_denominator: u64,
+}
+
+/// TransferAdmin instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct TransferAdmin {
+    /// new protocol admin
+    pub new_admin: Pubkey,
+}
+
+/// SetReferralFeeCap instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct SetReferralFeeCap {
+    /// largest referral fee, in bps of the output net of the protocol fee, 0 disabling them
+    pub max_referral_fee_bps: u16,
+}
+
+/// SwapExactOut instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct SwapExactOut<'a> {
+    /// Amount of DESTINATION token the user receives, net of the protocol fee
+    pub amount_out: u64,
+    /// Maximum amount of SOURCE token to transfer, prevents excessive slippage
+    pub maximum_amount_in: u64,
+    /// dexes configs, whose ratios split `amount_out` across the legs
+    pub dex_configs: DexConfigs<'a>,
+}
+
+/// Quote instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct Quote<'a> {
+    /// Amount of SOURCE token to quote the swap of
+    pub amount_in: u64,
+    /// dexes configs, whose ratios split `amount_in` across the legs
+    pub dex_configs: DexConfigs<'a>,
+}
+
+/// InitStakePool instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct InitStakePool {
+    /// fee discount tiers, by increasing `min_stake`, unused ones zeroed
+    pub tiers: [StakeTier; STAKE_TIERS],
+}
+
+/// Stake / Unstake / FundStakeRewards instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct StakeAmount {
+    /// amount of 1SOL staked or unstaked, or of rewards funded
+    pub amount: u64,
+}
+
+/// ConfigurePairRewards instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ConfigurePairRewards {
+    /// points earned per [crate::state::POINTS_VOLUME_UNIT] swapped, 0 pausing the rewards
+    pub points_per_unit: u64,
+}
+
+/// ConfigurePairGate instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ConfigurePairGate {
+    /// mint of the credential traders of the pair must hold, the default pubkey opening
+    /// the pair
+    pub credential_mint: Pubkey,
+    /// least credential balance of a trader
+    pub min_balance: u64,
+}
+
+/// ConfigureInventoryPair instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ConfigureInventoryPair {
+    /// oracle pricing SOURCE in DESTINATION, the default pubkey disabling fills
+    pub oracle: Pubkey,
+    /// discount, in bps of the oracle price, the inventory fills at
+    pub spread_bps: u16,
+}
+
+/// FundInventory / WithdrawInventory instruction data
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct InventoryAmount {
+    /// amount funded or withdrawn
+    pub amount: u64,
+}
+
+/// RevealSwap instruction data
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct RevealSwap<'a> {
+    /// salt blinding the commitment
+    pub salt: [u8; 32],
+    /// SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output, prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// dexes configs
+    pub dex_configs: DexConfigs<'a>,
+}
+
+/// Flash swap and flash loan fee, in bps of the amount taken out of a vault
+pub const FLASH_FEE_BPS: u16 = 9;
+
+/// Returns the fee repaid into a vault on top of `amount`, rounded up.
+pub fn flash_fee(amount: u64) -> Option<u64> {
+    fee_of(amount, FLASH_FEE_BPS).ok().map(|(fee, _)| fee)
 }
 
 /// Instructions supported by the 1sol constracts program
 #[repr(C)]
-#[derive(Debug, PartialEq)]
-pub enum OneSolInstruction {
+#[derive(BorshSerialize, Debug, PartialEq)]
+pub enum OneSolInstruction<'a> {
     /// Initializes a new 1solProtocol
     /// 0. `[writable, signer]` New 1solProtocol to create.
     /// 1. `[]` swap authority derived from `create_program_address(&[Token-swap account])`
     /// 2. `[]` token Account. Must be non zero, owned by 1sol.
     /// 3. '[]` Token program id
+    /// 4. `[signer]` admin allowed to set the fee rate
     Initialize(Initialize),
 
     /// Swap the tokens in the pool.
     ///
+    /// A swap through a single leg owing no protocol or referral fee outputs straight
+    /// into DESTINATION, leaving the protocol token account untouched.
+    ///
     ///   0. `[]` onesolProotcol account
     ///   1. `[]` onesolProotcol authority
     ///   2. `[]` user transfer authority
-    ///   3. `[writeable]` onesolProotcol token account
+    ///   3. `[writeable]` onesolProotcol token account, either its token account or the
+    ///      vault of the DESTINATION mint, derived from `[b"vault", protocol, mint]`
     ///   4. `[writable]` token_A SOURCE Account, amount is transferable by user transfer authority,
     ///   5. `[writable]` token_B DESTINATION Account to swap FROM.  Must be the DESTINATION token.
-    ///   6. '[]` Token program id
+    ///   6. '[]` Token program id, spl_token or Token-2022
     ///
     ///   7. `[]` token-swap account
     ///   8. `[]` token-swap authority
@@ -67,36 +854,1688 @@ pub enum OneSolInstruction {
     ///   12. `[writable]` Fee account, to receive trading fees
     ///   13. '[]` Token-Swap program id
     ///   14 `[optional, writable]` Host fee account to receive additional trading fees
-    Swap(Swap),
+    ///
+    ///   Serum DEX v3 legs (`dex_type` 2) fill an immediate-or-cancel order from the
+    ///   SOURCE account, then settle into SOURCE and DESTINATION:
+    ///   0. `[writable]` market
+    ///   1. `[writable]` open orders, owned by the user transfer authority, those of
+    ///      `CreateOpenOrders` on legs the protocol authority signs
+    ///   2. `[writable]` request queue
+    ///   3. `[writable]` event queue
+    ///   4. `[writable]` bids
+    ///   5. `[writable]` asks
+    ///   6. `[writable]` coin vault
+    ///   7. `[writable]` pc vault
+    ///   8. `[]` vault signer
+    ///   9. `[]` Rent sysvar
+    ///   10. `[]` Serum DEX program id
+    ///
+    ///   Raydium AMM v4 legs (`dex_type` 3) swap with `SwapBaseIn`:
+    ///   0. `[writable]` amm
+    ///   1. `[]` amm authority
+    ///   2. `[writable]` amm open orders
+    ///   3. `[writable]` amm target orders
+    ///   4. `[writable]` pool coin token account
+    ///   5. `[writable]` pool pc token account
+    ///   6. `[]` Serum DEX program id
+    ///   7. `[writable]` Serum market
+    ///   8. `[writable]` Serum bids
+    ///   9. `[writable]` Serum asks
+    ///   10. `[writable]` Serum event queue
+    ///   11. `[writable]` Serum coin vault
+    ///   12. `[writable]` Serum pc vault
+    ///   13. `[]` Serum vault signer
+    ///   14. `[]` Raydium AMM program id
+    ///
+    ///   Orca Whirlpool legs (`dex_type` 4) swap from the SOURCE mint side of the pool,
+    ///   up to the price limit of that direction:
+    ///   0. `[writable]` whirlpool
+    ///   1. `[writable]` token A vault
+    ///   2. `[writable]` token B vault
+    ///   3. `[writable]` tick array 0, holding the current tick
+    ///   4. `[writable]` tick array 1, next in the swap direction
+    ///   5. `[writable]` tick array 2, next in the swap direction
+    ///   6. `[]` oracle, derived from `[b"oracle", whirlpool]`
+    ///   7. `[]` Whirlpool program id
+    ///
+    ///   Saber StableSwap legs (`dex_type` 5):
+    ///   0. `[]` StableSwap account
+    ///   1. `[]` swap authority
+    ///   2. `[writable]` reserve of the SOURCE token
+    ///   3. `[writable]` reserve of the DESTINATION token
+    ///   4. `[writable]` admin fee account of the DESTINATION token
+    ///   5. `[]` Clock sysvar
+    ///   6. `[]` StableSwap program id
+    ///
+    ///   After the dex accounts, only if `oracle_max_deviation_bps` is set:
+    ///   0. `[]` Pyth or Switchboard price account, pricing SOURCE in DESTINATION units
+    ///   1. `[]` SOURCE mint
+    ///   2. `[]` DESTINATION mint
+    ///   3. `[]` global config
+    ///
+    ///   Then, only if `tip_lamports` is set, with the user transfer authority writable:
+    ///   0. `[]` global config
+    ///   1. `[writable]` tip account of the global config
+    ///   2. `[]` System program
+    ///
+    ///   Then, only if `referral_fee_bps` is set:
+    ///   0. `[writable]` referrer token account of the DESTINATION mint
+    ///
+    ///   Last, optionally and in any order:
+    ///   `[writable]` price accumulator of the SOURCE / DESTINATION pair, whose circuit
+    ///   breaker refuses the swap on an abnormal price move
+    ///   `[writable]` mint stats of the SOURCE and / or DESTINATION mint
+    ///   `[writable]` fresh swap receipt account, owned by 1sol, to record the swap into
+    ///   `[writable]` pair rewards of the SOURCE / DESTINATION pair, directly followed by
+    ///   the `[writable]` reward points of the user transfer authority, earning it points
+    ///   `[]` global config, whose `split_parts` re-split `amount_in` across the legs of
+    ///   pools quoted on-chain, unless the swap sets its own `split_parts`
+    ///   `[writable]` treasury of the 1sol protocol account, required once the admin sets
+    ///   a fee rate, receiving its share of the output
+    ///   `[]` DESTINATION mint, paying out with `TransferChecked`, as Token-2022 mints with
+    ///   a transfer fee require, and bounding the output net of that fee
+    ///
+    ///   `permissioned` builds take, before the referrer and the optional accounts:
+    ///   0. `[]` pair gate of the SOURCE / DESTINATION pair, created or not
+    ///   1. `[]` credential token account of the user transfer authority, any account if
+    ///      the pair is open
+    Swap(Swap<'a>),
+
+    /// Places an order, escrowing `amount_in` SOURCE tokens plus the keeper fee budget
+    /// until keepers fill it.
+    ///
+    ///   0. `[writable]` Order account to initialize, owned by 1sol.
+    ///   1. `[]` order authority derived from `create_program_address(&[Order account])`
+    ///   2. `[signer]` order owner
+    ///   3. `[writable]` owner SOURCE token account
+    ///   4. `[writable]` escrow token account, owned by the order authority.
+    ///   5. `[]` owner DESTINATION token account to receive the proceeds, owned by the owner.
+    ///      Fills can only pay into this account.
+    ///   6. '[]` Token program id
+    ///   7. `[writable]` order page of the mint pair with a free slot
+    ///   8. `[]` Pyth or Switchboard price account, only for stop-loss / take-profit orders, or
+    ///      spl-token-swap pool of the pair, only for pool stop-loss / take-profit orders.
+    PlaceOrder(PlaceOrder),
+
+    /// Fills (part of) the current slice of an order through the given dexes.
+    ///
+    ///   0. `[writable]` Order account
+    ///   1. `[]` order authority
+    ///   2. `[writable]` escrow token account
+    ///   3. `[writable]` owner DESTINATION token account
+    ///   4. '[]` Token program id
+    ///   5. `[writable]` keeper SOURCE token account to receive the keeper fee
+    ///   6. `[]` Pyth or Switchboard price account, only for stop-loss / take-profit orders, or
+    ///      spl-token-swap pool, only for pool stop-loss / take-profit orders.
+    ///   7. `[]` global config, only for stop-loss / take-profit orders, or
+    ///      pool token A reserve account, only for pool stop-loss / take-profit orders.
+    ///   8. `[]` pool token B reserve account, only for pool stop-loss / take-profit orders.
+    ///
+    ///   6.. (or 7.., 9..) dex accounts, laid out as in `Swap` from the token-swap account on.
+    ///
+    ///   `permissioned` builds then take the pair gate and the credential token account of
+    ///   the order owner, as `Swap` does.
+    FillOrder(FillOrder<'a>),
+
+    /// Refunds the escrow of an expired (or filled) order to its owner and closes it.
+    /// Anyone can crank this instruction.
+    ///
+    ///   0. `[writable]` Order account
+    ///   1. `[]` order authority
+    ///   2. `[writable]` escrow token account
+    ///   3. `[writable]` owner SOURCE token account to receive the refund
+    ///   4. `[writable]` order owner, receiving the order and escrow rent
+    ///   5. '[]` Token program id
+    ///   6. `[writable]` keeper SOURCE token account to receive the keeper fee
+    ///   7. `[writable]` order page listing the order
+    ExpireOrder,
+
+    /// Creates a recurring payment and approves its authority as delegate of the payer's
+    /// SOURCE token account for `max_total_in`. The token account has a single delegate,
+    /// so each recurring payment needs its own SOURCE account. The authority only ever
+    /// moves a period's `amount_in` into the escrow, the dex legs swapping from the escrow
+    /// under the escrow authority.
+    ///
+    ///   0. `[writable]` RecurringPayment account to initialize, owned by 1sol.
+    ///   1. `[]` recurring payment authority derived from
+    ///      `create_program_address(&[RecurringPayment account])`
+    ///   2. `[signer]` payer
+    ///   3. `[writable]` payer SOURCE token account
+    ///   4. `[]` merchant DESTINATION token account
+    ///   5. '[]` Token program id
+    ///   6. `[]` escrow token account of the SOURCE mint, owned by the escrow authority
+    ///      derived from `create_program_address(&[escrow account])`
+    CreateRecurringPayment(CreateRecurringPayment),
+
+    /// Moves the current period's `amount_in` into the escrow and swaps it into the
+    /// merchant's token account. Anyone can crank this instruction.
+    ///
+    ///   0. `[writable]` RecurringPayment account
+    ///   1. `[]` recurring payment authority
+    ///   2. `[writable]` payer SOURCE token account
+    ///   3. `[writable]` merchant DESTINATION token account
+    ///   4. '[]` Token program id
+    ///   5. `[writable]` escrow token account
+    ///   6. `[]` escrow authority
+    ///
+    ///   7.. dex accounts, laid out as in `Swap` from the token-swap account on.
+    ///
+    ///   `permissioned` builds then take the pair gate and the credential token account of
+    ///   the payer, as `Swap` does.
+    ExecuteRecurringPayment(ExecuteRecurringPayment<'a>),
+
+    /// Cancels a recurring payment, revoking its delegation and closing the account and
+    /// its escrow.
+    ///
+    ///   0. `[writable]` RecurringPayment account
+    ///   1. `[writable, signer]` payer, receiving the account rent
+    ///   2. `[writable]` payer SOURCE token account
+    ///   3. '[]` Token program id
+    ///   4. `[writable]` escrow token account
+    ///   5. `[]` escrow authority
+    CancelRecurringPayment,
+
+    /// Creates the next order page of a mint pair.
+    ///
+    ///   0. `[writable, signer]` payer of the page rent
+    ///   1. `[writable]` order page, derived from
+    ///      `[b"orders", SOURCE mint, DESTINATION mint, page_index]`
+    ///   2. `[]` SOURCE mint
+    ///   3. `[]` DESTINATION mint
+    ///   4. `[]` previous order page of the pair, unless `page_index` is 0
+    ///   5. `[]` System program
+    AddOrderPage(AddOrderPage),
+
+    /// Fills the rest of the current slice of several limit / TWAP orders of the same pair
+    /// through one route execution, sharing the output pro-rata to the orders' inputs.
+    ///
+    ///   0. `[]` batch authority derived from `create_program_address(&[batch SOURCE account])`
+    ///   1. `[writable]` batch SOURCE token account, owned by the batch authority
+    ///   2. `[writable]` batch DESTINATION token account, owned by the batch authority
+    ///   3. '[]` Token program id
+    ///   4. `[writable]` keeper SOURCE token account to receive the keeper fees
+    ///
+    ///   5.. `num_orders` times, each order at most once:
+    ///     `[writable]` Order account, `[]` order authority, `[writable]` escrow token account,
+    ///     `[writable]` owner DESTINATION token account, then in `permissioned` builds the
+    ///     pair gate and the credential token account of the order owner, as in `Swap`
+    ///
+    ///   then dex accounts, laid out as in `Swap` from the token-swap account on.
+    BatchFillOrders(BatchFillOrders<'a>),
+
+    /// Creates the price accumulator of a mint pair.
+    ///
+    ///   0. `[writable, signer]` payer of the accumulator rent
+    ///   1. `[writable]` price accumulator, derived from
+    ///      `[b"twap", SOURCE mint, DESTINATION mint]`
+    ///   2. `[]` SOURCE mint
+    ///   3. `[]` DESTINATION mint
+    ///   4. `[]` System program
+    InitPriceAccumulator,
+
+    /// Swaps like `Swap`, deriving the minimum output on-chain from the oracle price and
+    /// the mints decimals instead of taking an absolute amount from the client.
+    ///
+    ///   Accounts as in `Swap` with the oracle price account and mints.
+    OracleSwap(OracleSwap<'a>),
+
+    /// Creates the global config, its payer becoming the config admin.
+    ///
+    ///   0. `[writable, signer]` admin, payer of the config rent
+    ///   1. `[writable]` global config, derived from `[b"config"]`
+    ///   2. `[]` System program
+    InitConfig(ConfigParams),
+
+    /// Updates the global config.
+    ///
+    ///   Instructions passed the global config among their accounts log at its log level,
+    ///   others log fully.
+    ///
+    ///   0. `[signer]` config admin
+    ///   1. `[writable]` global config, owned by 1sol
+    UpdateConfig(ConfigParams),
+
+    /// Configures the circuit breaker of a pair, resetting its reference price so the
+    /// next swap re-anchors it.
+    ///
+    ///   0. `[signer]` config admin
+    ///   1. `[]` global config
+    ///   2. `[writable]` price accumulator of the pair
+    ConfigureCircuitBreaker(ConfigureCircuitBreaker),
+
+    /// Creates the swap statistics account of a mint.
+    ///
+    ///   0. `[writable, signer]` payer of the stats rent
+    ///   1. `[writable]` mint stats, derived from `[b"stats", mint]`
+    ///   2. `[]` mint
+    ///   3. `[]` System program
+    InitMintStats,
+
+    /// Swaps `amount_in` SOURCE tokens of a protocol vault, pays the output to the
+    /// borrower, then calls the callback program, which must repay the vault `amount_in`
+    /// plus [FlashSwap::fee], less any stake discount, before the instruction ends.
+    ///
+    ///   0. `[]` onesolProotcol account
+    ///   1. `[]` onesolProotcol authority
+    ///   2. `[writeable]` onesolProotcol token account
+    ///   3. `[writable]` protocol SOURCE vault, owned by the protocol authority
+    ///   4. `[writable]` borrower DESTINATION token account
+    ///   5. '[]` Token program id
+    ///   6. `[]` callback program
+    ///
+    ///   7.. `callback_accounts` accounts passed to the callback program, as they are
+    ///
+    ///   then dex accounts, laid out as in `Swap` from the token-swap account on, then, in
+    ///   `permissioned` builds, the pair gate and the credential token account of the
+    ///   borrower DESTINATION token account owner, as in `Swap`, then optionally the stake
+    ///   pool and the stake account of that owner, discounting the fee by its stake.
+    FlashSwap(FlashSwap<'a>),
+
+    /// Lends `amount` out of a protocol vault, as a top-level instruction only. A
+    /// top-level `FlashRepay` of the same amount into the same vault must follow in the
+    /// transaction, with no other borrow from the vault in between.
+    ///
+    ///   0. `[]` onesolProotcol account
+    ///   1. `[]` onesolProotcol authority
+    ///   2. `[writable]` protocol vault, owned by the protocol authority
+    ///   3. `[writable]` borrower token account
+    ///   4. '[]` Token program id
+    ///   5. `[]` Instructions sysvar
+    FlashBorrow(FlashBorrow),
+
+    /// Repays a flash loan of `amount`, plus [flash_fee] less the stake discount of the
+    /// signer, into the vault it came from.
+    ///
+    ///   0. `[writable]` protocol vault
+    ///   1. `[writable]` borrower token account, repaying the loan
+    ///   2. `[signer]` borrower token account owner or delegate
+    ///   3. '[]` Token program id
+    ///   4. `[]` optional stake pool
+    ///   5. `[]` optional stake account of the signer, discounting the fee by its stake
+    FlashRepay(FlashRepay),
+
+    /// Swaps as `Swap` does, then sends the whole output from the user DESTINATION token
+    /// account to `transfer.target_address` on `transfer.target_chain` through the
+    /// Wormhole token bridge. The user transfer authority approves the bridge authority
+    /// signer as delegate of the output, the Wormhole message fee must be paid into the
+    /// fee collector beforehand.
+    ///
+    ///   0-6. accounts of `Swap`
+    ///   7. `[]` Wormhole token bridge program
+    ///   8. `[]` token bridge authority signer, derived from `[b"authority_signer"]`
+    ///
+    ///   9.. `bridge_accounts` accounts of the token bridge `TransferNative` or
+    ///   `TransferWrapped` instruction, as they are, the DESTINATION token account
+    ///   among them
+    ///
+    ///   then dex accounts, laid out as in `Swap` from the token-swap account on.
+    SwapAndBridge(SwapAndBridge<'a>),
+
+    /// Commits to a swap without disclosing its route or amounts, to be revealed and
+    /// executed by `RevealSwap` from the next slot on.
+    ///
+    ///   0. `[writable, signer]` user transfer authority, paying the commitment rent
+    ///   1. `[writable]` swap commitment, derived from `[b"commit", user, commitment]`
+    ///   2. `[]` System program
+    CommitSwap(CommitSwap),
+
+    /// Reveals a committed swap and executes it as `Swap` does, closing the commitment
+    /// into the user transfer authority.
+    ///
+    ///   0-6. accounts of `Swap`
+    ///   7. `[writable]` swap commitment
+    ///
+    ///   then dex accounts, laid out as in `Swap` from the token-swap account on.
+    RevealSwap(RevealSwap<'a>),
+
+    /// Hands the global config over to `new_admin`, which does not sign.
+    ///
+    ///   Handing it to an SPL Governance governance account, e.g. the one governing the
+    ///   config, gates every admin instruction behind a realm vote: the governance
+    ///   program only signs for it when executing the instructions of a passed
+    ///   proposal.
+    ///
+    ///   0. `[signer]` config admin
+    ///   1. `[writable]` global config, owned by 1sol
+    SetConfigAdmin(SetConfigAdmin),
+
+    /// Creates the 1SOL stake pool. Its vaults must be owned by the pool authority.
+    ///
+    ///   0. `[writable, signer]` config admin, payer of the pool rent
+    ///   1. `[]` global config
+    ///   2. `[writable]` stake pool, derived from `[b"stake_pool"]`
+    ///   3. `[]` stake vault, a 1SOL token account
+    ///   4. `[]` rewards vault
+    ///   5. `[]` System program
+    InitStakePool(InitStakePool),
+
+    /// Stakes `amount` 1SOL, creating the stake account on the first stake, and pays out
+    /// the pending rewards.
+    ///
+    ///   0. `[writable, signer]` staker, payer of the stake account rent
+    ///   1. `[writable]` stake pool
+    ///   2. `[]` stake pool authority
+    ///   3. `[writable]` stake account, derived from `[b"stake", staker]`
+    ///   4. `[writable]` staker 1SOL token account
+    ///   5. `[writable]` stake vault
+    ///   6. `[writable]` rewards vault
+    ///   7. `[writable]` staker rewards token account
+    ///   8. `[]` Token program id
+    ///   9. `[]` System program
+    Stake(StakeAmount),
+
+    /// Unstakes `amount` 1SOL and pays out the pending rewards. Unstaking nothing only
+    /// claims the rewards.
+    ///
+    ///   0. `[signer]` staker
+    ///   1. `[writable]` stake pool
+    ///   2. `[]` stake pool authority
+    ///   3. `[writable]` stake account
+    ///   4. `[writable]` staker 1SOL token account
+    ///   5. `[writable]` stake vault
+    ///   6. `[writable]` rewards vault
+    ///   7. `[writable]` staker rewards token account
+    ///   8. `[]` Token program id
+    Unstake(StakeAmount),
+
+    /// Funds `amount` of rewards, shared among the current stakers by stake.
+    ///
+    ///   0. `[signer]` config admin
+    ///   1. `[]` global config
+    ///   2. `[writable]` stake pool
+    ///   3. `[writable]` admin rewards token account
+    ///   4. `[writable]` rewards vault
+    ///   5. `[]` Token program id
+    FundStakeRewards(StakeAmount),
+
+    /// Sets the liquidity-mining rate of a mint pair, creating its pair rewards on the
+    /// first call.
+    ///
+    ///   0. `[writable, signer]` config admin, payer of the pair rewards rent
+    ///   1. `[]` global config
+    ///   2. `[writable]` pair rewards, derived from
+    ///      `[b"rewards", source mint, destination mint]`
+    ///   3. `[]` SOURCE mint
+    ///   4. `[]` DESTINATION mint
+    ///   5. `[]` System program
+    ConfigurePairRewards(ConfigurePairRewards),
+
+    /// Creates the reward points account of a user.
+    ///
+    ///   0. `[writable, signer]` payer of the points rent
+    ///   1. `[writable]` reward points, derived from `[b"points", owner]`
+    ///   2. `[]` owner
+    ///   3. `[]` System program
+    InitRewardPoints,
+
+    /// Redeems the points of a user for as many raw units of the rewards vault, as far
+    /// as the vault holds them.
+    ///
+    ///   0. `[signer]` points owner
+    ///   1. `[writable]` reward points
+    ///   2. `[]` rewards vault authority, derived from `[b"mining"]`
+    ///   3. `[writable]` rewards vault, owned by the rewards vault authority
+    ///   4. `[writable]` owner token account receiving the rewards
+    ///   5. `[]` Token program id
+    ClaimRewards,
+
+    /// Sets the credential traders of a mint pair must hold in `permissioned` builds,
+    /// creating the pair gate on the first call. A credential is a token account of the
+    /// credential mint, frozen ones included, so non-transferable passes qualify.
+    ///
+    ///   0. `[writable, signer]` config admin, payer of the gate rent
+    ///   1. `[]` global config
+    ///   2. `[writable]` pair gate, derived from `[b"gate", source mint, destination mint]`
+    ///   3. `[]` SOURCE mint
+    ///   4. `[]` DESTINATION mint
+    ///   5. `[]` System program
+    ConfigurePairGate(ConfigurePairGate),
+
+    /// Swaps as `Swap` does for a user whose transaction a relayer submits and pays the
+    /// fees of, reimbursing the relayer out of the output with `fee_flat` plus `fee_bps`
+    /// of it. Both parts are signed by the user and capped by the global config.
+    ///
+    ///   0-6. accounts of `Swap`
+    ///   7. `[]` global config
+    ///   8. `[writable]` relayer DESTINATION token account
+    ///
+    ///   then dex and trailing accounts, laid out as in `Swap` from the token-swap
+    ///   account on.
+    RelayedSwap(RelayedSwap<'a>),
+
+    /// Executes independent swaps of several users through the same dex accounts, one
+    /// after the other, each swap seeing the pools as the previous one left them. A
+    /// failing swap fails the whole batch. Each swap emits its events, the return data
+    /// holds the result of the last one.
+    ///
+    ///   0. `[]` 1sol protocol account
+    ///   1. `[]` 1sol protocol authority
+    ///   2. `[writable]` 1sol protocol DESTINATION token account
+    ///   3. `[]` Token program id
+    ///
+    ///   then dex accounts, laid out as in `Swap` from the token-swap account on, then
+    ///   per swap:
+    ///
+    ///   0. `[signer]` user transfer authority
+    ///   1. `[writable]` user SOURCE token account
+    ///   2. `[writable]` user DESTINATION token account
+    ///
+    ///   followed by its `trailing_accounts` trailing accounts, as in `Swap`.
+    BatchSwap(BatchSwap<'a>),
+
+    /// Swaps as `Swap` does, then deposits the whole output from the user DESTINATION
+    /// token account into a token-lending reserve (Solend, Port Finance) through its
+    /// `DepositReserveLiquidityAndObligationCollateral`, crediting the obligation of the
+    /// user. The user transfer authority signs the deposit.
+    ///
+    ///   0-6. accounts of `Swap`
+    ///   7. `[]` token-lending program
+    ///
+    ///   8.. `lending_accounts` accounts of the deposit instruction, as they are, the
+    ///   DESTINATION token account first as its source liquidity
+    ///
+    ///   then dex accounts, laid out as in `Swap` from the token-swap account on.
+    SwapAndDeposit(SwapAndDeposit<'a>),
+
+    /// Swaps as `Swap` does into wrapped SOL, then unwraps the DESTINATION token account,
+    /// closing it to the user transfer authority, and deposits the output into an SPL
+    /// stake pool through its `DepositSol`, the pool tokens going to the user.
+    ///
+    ///   0-6. accounts of `Swap`, the DESTINATION token account a wrapped SOL account
+    ///   7. `[]` stake pool program
+    ///
+    ///   8.. `stake_pool_accounts` accounts of the `DepositSol` instruction, as they are,
+    ///   the user transfer authority funding the deposit
+    ///
+    ///   then dex accounts, laid out as in `Swap` from the token-swap account on.
+    SwapAndStake(SwapAndStake<'a>),
+
+    /// Sets the oracle and spread protocol-owned inventory fills a mint pair at,
+    /// creating the inventory pair on the first call. Routes fill from the inventory
+    /// through legs of dex type 1.
+    ///
+    ///   0. `[writable, signer]` config admin, payer of the inventory pair rent
+    ///   1. `[]` global config
+    ///   2. `[writable]` inventory pair, derived from
+    ///      `[b"inventory", source mint, destination mint]`
+    ///   3. `[]` SOURCE mint
+    ///   4. `[]` DESTINATION mint
+    ///   5. `[]` System program
+    ConfigureInventoryPair(ConfigureInventoryPair),
+
+    /// Moves tokens of the config admin into an inventory vault.
+    ///
+    ///   0. `[signer]` config admin
+    ///   1. `[]` global config
+    ///   2. `[writable]` admin token account funding the inventory
+    ///   3. `[]` inventory authority, derived from `[b"inventory"]`
+    ///   4. `[writable]` inventory vault, owned by the inventory authority
+    ///   5. `[]` Token program id
+    FundInventory(InventoryAmount),
+
+    /// Moves tokens out of an inventory vault.
+    ///
+    ///   0. `[signer]` config admin
+    ///   1. `[]` global config
+    ///   2. `[]` inventory authority, derived from `[b"inventory"]`
+    ///   3. `[writable]` inventory vault, owned by the inventory authority
+    ///   4. `[writable]` token account receiving the tokens
+    ///   5. `[]` Token program id
+    WithdrawInventory(InventoryAmount),
+
+    /// Swaps through an intermediate mint: the legs of the first step swap the SOURCE
+    /// tokens into the intermediate token account, the legs of the second swap all they
+    /// received into the 1sol protocol token account, the protocol authority signing.
+    /// Only the output of the second step is bound by `minimum_amount_out`.
+    ///
+    ///   0-6. accounts of `Swap`
+    ///   7. `[writable]` 1sol protocol intermediate token account, owned by the protocol
+    ///      authority
+    ///
+    ///   then dex accounts of the legs of the first step, then of the second, laid out as
+    ///   in `Swap` from the token-swap account on, then trailing accounts as in `Swap`.
+    SwapTwoSteps(SwapTwoSteps<'a>),
+
+    /// Sets the share of the output the swaps of a 1sol protocol account pay its
+    /// treasury, 0 / 0 charging none. Protocol accounts initialized before fees are too
+    /// short to hold one.
+    ///
+    ///   0. `[signer]` protocol admin
+    ///   1. `[writable]` 1sol protocol account, owned by 1sol
+    ///   2. `[]` treasury token account of the protocol token mint
+    SetFeeRate(SetFeeRate),
+
+    /// Pauses the swaps of a 1sol protocol account, failing them with `Paused` until
+    /// unpaused. Protocol accounts initialized before the pause flag are too short to
+    /// hold it.
+    ///
+    ///   0. `[signer]` protocol admin
+    ///   1. `[writable]` 1sol protocol account, owned by 1sol
+    Pause,
+
+    /// Resumes the swaps of a paused 1sol protocol account.
+    ///
+    ///   0. `[signer]` protocol admin
+    ///   1. `[writable]` 1sol protocol account, owned by 1sol
+    Unpause,
+
+    /// Hands the admin of a 1sol protocol account over to a new key.
+    ///
+    ///   0. `[signer]` protocol admin
+    ///   1. `[writable]` 1sol protocol account, owned by 1sol
+    TransferAdmin(TransferAdmin),
+
+    /// Closes a 1sol protocol account no longer needed: sends the balance left in the
+    /// protocol token account to the destination, then zeroes the state and returns its
+    /// lamports to the receiver.
+    ///
+    ///   0. `[signer]` protocol admin
+    ///   1. `[writable]` 1sol protocol account, owned by 1sol
+    ///   2. `[]` protocol authority
+    ///   3. `[writable]` protocol token account
+    ///   4. `[writable]` token account of the protocol token mint receiving the balance left
+    ///   5. `[writable]` account receiving the lamports of the protocol account
+    ///   6. `[]` Token program id
+    CloseProtocolAccount,
+
+    /// Swaps as little SOURCE token as the legs need to output `amount_out`, each leg
+    /// its share of it by ratio, quoted on-chain. SOURCE is only debited what the legs
+    /// take, leaving the rest of `maximum_amount_in` to the user. Fails with
+    /// `QuoteUnavailable` on legs through venues the program can't quote.
+    ///
+    ///   Accounts as in `Swap`.
+    SwapExactOut(SwapExactOut<'a>),
+
+    /// Caps the referral fee the swaps of a 1sol protocol account may pay. Protocol
+    /// accounts initialized before referral fees are too short to hold the cap.
+    ///
+    ///   0. `[signer]` protocol admin
+    ///   1. `[writable]` 1sol protocol account, owned by 1sol
+    SetReferralFeeCap(SetReferralFeeCap),
+
+    /// Creates the vault of a mint, a token account owned by the protocol authority that
+    /// swaps into that mint may route their output through.
+    ///
+    ///   0. `[writable, signer]` payer of the vault rent
+    ///   1. `[]` 1sol protocol account, owned by 1sol
+    ///   2. `[]` protocol authority
+    ///   3. `[writable]` vault, derived from `[b"vault", protocol, mint]`
+    ///   4. `[]` mint
+    ///   5. `[]` spl_token program id
+    ///   6. `[]` Rent sysvar
+    ///   7. `[]` System program
+    InitializeVault,
+
+    /// Creates the Serum open orders account of a market owned by the protocol authority,
+    /// which the Serum legs the authority signs fill their orders through.
+    ///
+    ///   0. `[writable, signer]` protocol admin, payer of the open orders rent
+    ///   1. `[]` 1sol protocol account, owned by 1sol
+    ///   2. `[]` protocol authority
+    ///   3. `[writable]` open orders, derived from `[b"open_orders", protocol, market]`
+    ///   4. `[]` Serum market
+    ///   5. `[]` Rent sysvar
+    ///   6. `[]` Serum DEX program id
+    ///   7. `[]` System program
+    CreateOpenOrders,
+
+    /// Settles the funds left in the open orders account of `CreateOpenOrders`, then
+    /// closes it. Fails while it holds orders.
+    ///
+    ///   0. `[signer]` protocol admin
+    ///   1. `[]` 1sol protocol account, owned by 1sol
+    ///   2. `[]` protocol authority
+    ///   3. `[writable]` open orders, derived from `[b"open_orders", protocol, market]`
+    ///   4. `[writable]` Serum market
+    ///   5. `[writable]` Serum coin vault
+    ///   6. `[writable]` Serum pc vault
+    ///   7. `[writable]` token account of the coin mint receiving the settled coin
+    ///   8. `[writable]` token account of the pc mint receiving the settled pc
+    ///   9. `[]` Serum vault signer
+    ///   10. `[writable]` account receiving the lamports of the open orders
+    ///   11. `[]` Token program id
+    ///   12. `[]` Serum DEX program id
+    CloseOpenOrders,
+
+    /// Quotes the DESTINATION amount a swap of `amount_in` through the legs outputs, each
+    /// leg its share by ratio, from the reserves and curves of their pools, moving no
+    /// tokens. Returns a packed `QuoteResult`, which clients read by simulating the
+    /// transaction. Fails with `QuoteUnavailable` on legs through venues the program
+    /// can't quote.
+    ///
+    ///   Accounts: the dex accounts of the legs, as in `Swap`.
+    Quote(Quote<'a>),
+
+    /// Executes independent swaps of one user across pairs, each through a route of its
+    /// own, one after the other. A failing swap fails the whole batch. Each swap emits
+    /// its events, the return data holds the result of the last one. Unlike `BatchSwap`,
+    /// the swaps share only the protocol accounts.
+    ///
+    ///   0. `[]` 1sol protocol account
+    ///   1. `[]` 1sol protocol authority
+    ///   2. `[signer]` user transfer authority
+    ///   3. `[]` Token program id
+    ///
+    ///   then per swap:
+    ///
+    ///   0. `[writable]` 1sol protocol DESTINATION token account
+    ///   1. `[writable]` user SOURCE token account
+    ///   2. `[writable]` user DESTINATION token account
+    ///
+    ///   followed by its dex accounts, laid out as in `Swap` from the token-swap account
+    ///   on, then its `trailing_accounts` trailing accounts, as in `Swap`.
+    SwapBatch(SwapBatch<'a>),
 }
 
-impl OneSolInstruction {
-    /// Unpacks a byte buffer into a [OneSolInstruction](enum.OneSolInstruction.html).
-    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+impl<'a> OneSolInstruction<'a> {
+    /// Unpacks a byte buffer into a [OneSolInstruction](enum.OneSolInstruction.html),
+    /// borrowing the dex configs from it. The buffer is either in the v1 encoding, the
+    /// tag then the packed fields, or [VERSIONED_INSTRUCTION] then the version byte then
+    /// the instruction in the encoding of that version.
+    pub fn unpack(input: &'a [u8]) -> Result<Self, ProgramError> {
         let (&tag, rest) = input.split_first().ok_or(OneSolError::InvalidInput)?;
+        let instruction = if tag == VERSIONED_INSTRUCTION {
+            let (&version, rest) = rest.split_first().ok_or(OneSolError::InvalidInput)?;
+            match version {
+                INSTRUCTION_V2 => Self::unpack_v2(rest)?,
+                _ => return Err(OneSolError::InvalidInstruction.into()),
+            }
+        } else {
+            Self::unpack_v1(tag, rest)?
+        };
+        instruction.validate()?;
+        Ok(instruction)
+    }
+
+    /// Unpacks the fields, in the v1 encoding, of the instruction `tag`.
+    fn unpack_v1(tag: u8, rest: &'a [u8]) -> Result<Self, ProgramError> {
         Ok(match tag {
             0 => {
                 let (&nonce, _rest) = rest.split_first().ok_or(OneSolError::InvalidInput)?;
                 Self::Initialize(Initialize { nonce })
             }
-            1 => {
-                let (amount_in, _rest) = Self::unpack_u64(rest)?;
-                let (minimum_amount_out, _rest) = Self::unpack_u64(_rest)?;
-                let (dex_configs, _rest) = Self::unpack_dexes_configs(_rest)?;
-
-                if dex_configs.len() == 0 {
-                    return Err(OneSolError::InvalidInstruction.into());
+            1 => {
+                let (amount_in, _rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, _rest) = Self::unpack_u64(_rest)?;
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(_rest)?;
+
+                let (oracle_max_deviation_bps, _rest) = if _rest.is_empty() {
+                    (None, _rest)
+                } else {
+                    let (bps, _rest) = Self::unpack_u16(_rest)?;
+                    (Some(bps).filter(|&bps| bps != NO_ORACLE_BOUND), _rest)
+                };
+                let (tip_lamports, _rest) = if _rest.is_empty() {
+                    (0, _rest)
+                } else {
+                    Self::unpack_u64(_rest)?
+                };
+                let (split_parts, _rest) = match _rest.split_first() {
+                    Some((&split_parts, _rest)) => (split_parts, _rest),
+                    None => (0, _rest),
+                };
+                // a u64 per leg, then the u16 referral fee
+                let (minimums, _rest) =
+                    _rest.split_at(_rest.len() - _rest.len() % size_of::<u64>());
+                let dex_configs = if minimums.is_empty() {
+                    dex_configs
+                } else {
+                    dex_configs.with_minimums(minimums)?
+                };
+                let referral_fee_bps = match _rest.len() {
+                    0 => 0,
+                    2 => Self::unpack_u16(_rest)?.0,
+                    _ => return Err(OneSolError::InvalidInput.into()),
+                };
+                Self::Swap(Swap {
+                    amount_in,
+                    minimum_amount_out,
+                    dex_configs,
+                    oracle_max_deviation_bps,
+                    tip_lamports,
+                    split_parts,
+                    referral_fee_bps,
+                })
+            }
+            2 => {
+                let (&nonce, rest) = rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let (&kind, rest) = rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let kind =
+                    OrderKind::try_from(kind).map_err(|_| OneSolError::InvalidInstruction)?;
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (expected_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (max_deviation_bps, rest) = Self::unpack_u16(rest)?;
+                let (num_slices, rest) = Self::unpack_u16(rest)?;
+                let (slice_interval, rest) = Self::unpack_u32(rest)?;
+                let (trigger_price, rest) = Self::unpack_u64(rest)?;
+                let (expires_at_slot, rest) = Self::unpack_u64(rest)?;
+                let (keeper_fee_bps, rest) = Self::unpack_u16(rest)?;
+                let (keeper_fee_flat, _rest) = Self::unpack_u64(rest)?;
+                Self::PlaceOrder(PlaceOrder {
+                    nonce,
+                    kind,
+                    amount_in,
+                    expected_amount_out,
+                    max_deviation_bps,
+                    num_slices,
+                    slice_interval,
+                    trigger_price,
+                    expires_at_slot,
+                    keeper_fee_bps,
+                    keeper_fee_flat,
+                })
+            }
+            3 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                Self::FillOrder(FillOrder {
+                    amount_in,
+                    dex_configs,
+                })
+            }
+            4 => Self::ExpireOrder,
+            5 => {
+                let (&nonce, rest) = rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (period, rest) = Self::unpack_u32(rest)?;
+                let (max_total_in, rest) = Self::unpack_u64(rest)?;
+                let (&escrow_nonce, _rest) = rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                Self::CreateRecurringPayment(CreateRecurringPayment {
+                    nonce,
+                    amount_in,
+                    minimum_amount_out,
+                    period,
+                    max_total_in,
+                    escrow_nonce,
+                })
+            }
+            6 => {
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                Self::ExecuteRecurringPayment(ExecuteRecurringPayment { dex_configs })
+            }
+            7 => Self::CancelRecurringPayment,
+            8 => {
+                let (page_index, _rest) = Self::unpack_u32(rest)?;
+                Self::AddOrderPage(AddOrderPage { page_index })
+            }
+            9 => {
+                let (&nonce, rest) = rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let (&num_orders, rest) = rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                Self::BatchFillOrders(BatchFillOrders {
+                    nonce,
+                    num_orders,
+                    dex_configs,
+                })
+            }
+            10 => Self::InitPriceAccumulator,
+            11 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (max_deviation_bps, rest) = Self::unpack_u16(rest)?;
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                Self::OracleSwap(OracleSwap {
+                    amount_in,
+                    max_deviation_bps,
+                    dex_configs,
+                })
+            }
+            12 | 13 => {
+                let (oracle_max_age_slots, rest) = Self::unpack_u64(rest)?;
+                let (oracle_conf_multiplier, rest) = Self::unpack_u16(rest)?;
+                let (&log_level, rest) = rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let log_level =
+                    LogLevel::try_from(log_level).map_err(|_| OneSolError::InvalidInstruction)?;
+                let (tip_account, rest) = if rest.is_empty() {
+                    (Pubkey::default(), rest)
+                } else {
+                    let (tip_account, rest) = Self::unpack_bytes32(rest)?;
+                    (Pubkey::new_from_array(tip_account), rest)
+                };
+                let (relayer_fee_max_bps, relayer_fee_max_flat, rest) = if rest.is_empty() {
+                    (0, 0, rest)
+                } else {
+                    let (relayer_fee_max_bps, rest) = Self::unpack_u16(rest)?;
+                    let (relayer_fee_max_flat, rest) = Self::unpack_u64(rest)?;
+                    (relayer_fee_max_bps, relayer_fee_max_flat, rest)
+                };
+                let split_parts = rest.first().copied().unwrap_or(0);
+                let params = ConfigParams {
+                    oracle_max_age_slots,
+                    oracle_conf_multiplier,
+                    log_level,
+                    tip_account,
+                    relayer_fee_max_bps,
+                    relayer_fee_max_flat,
+                    split_parts,
+                };
+                if tag == 12 {
+                    Self::InitConfig(params)
+                } else {
+                    Self::UpdateConfig(params)
+                }
+            }
+            14 => {
+                let (max_move_bps, rest) = Self::unpack_u16(rest)?;
+                let (window_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::ConfigureCircuitBreaker(ConfigureCircuitBreaker {
+                    max_move_bps,
+                    window_slots,
+                })
+            }
+            15 => Self::InitMintStats,
+            16 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (&callback_accounts, rest) =
+                    rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let (dex_configs, callback_data) = Self::unpack_dexes_configs(rest)?;
+                Self::FlashSwap(FlashSwap {
+                    amount_in,
+                    minimum_amount_out,
+                    callback_accounts,
+                    dex_configs,
+                    callback_data,
+                })
+            }
+            17 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::FlashBorrow(FlashBorrow { amount })
+            }
+            18 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::FlashRepay(FlashRepay { amount })
+            }
+            19 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (&wrapped, rest) = rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let (nonce, rest) = Self::unpack_u32(rest)?;
+                let (target_chain, rest) = Self::unpack_u16(rest)?;
+                let (target_address, rest) = Self::unpack_bytes32(rest)?;
+                let (relayer_fee, rest) = Self::unpack_u64(rest)?;
+                let (&bridge_accounts, rest) =
+                    rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                let wrapped = match wrapped {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(OneSolError::InvalidInput.into()),
+                };
+                Self::SwapAndBridge(SwapAndBridge {
+                    amount_in,
+                    minimum_amount_out,
+                    transfer: BridgeTransfer {
+                        wrapped,
+                        nonce,
+                        target_chain,
+                        target_address,
+                        relayer_fee,
+                    },
+                    bridge_accounts,
+                    dex_configs,
+                })
+            }
+            20 => {
+                let (commitment, _rest) = Self::unpack_bytes32(rest)?;
+                Self::CommitSwap(CommitSwap { commitment })
+            }
+            21 => {
+                let (salt, rest) = Self::unpack_bytes32(rest)?;
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                Self::RevealSwap(RevealSwap {
+                    salt,
+                    amount_in,
+                    minimum_amount_out,
+                    dex_configs,
+                })
+            }
+            22 => {
+                let (new_admin, _rest) = Self::unpack_bytes32(rest)?;
+                Self::SetConfigAdmin(SetConfigAdmin {
+                    new_admin: Pubkey::new_from_array(new_admin),
+                })
+            }
+            23 => {
+                let mut tiers = [StakeTier::default(); STAKE_TIERS];
+                let mut rest = rest;
+                for tier in tiers.iter_mut() {
+                    if rest.len() < StakeTier::LEN {
+                        return Err(OneSolError::InvalidInstruction.into());
+                    }
+                    let (packed, _rest) = rest.split_at(StakeTier::LEN);
+                    let packed = packed
+                        .try_into()
+                        .map_err(|_| OneSolError::InvalidInstruction)?;
+                    *tier = StakeTier::unpack(packed);
+                    rest = _rest;
+                }
+                Self::InitStakePool(InitStakePool { tiers })
+            }
+            24..=26 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                let data = StakeAmount { amount };
+                match tag {
+                    24 => Self::Stake(data),
+                    25 => Self::Unstake(data),
+                    _ => Self::FundStakeRewards(data),
+                }
+            }
+            27 => {
+                let (points_per_unit, _rest) = Self::unpack_u64(rest)?;
+                Self::ConfigurePairRewards(ConfigurePairRewards { points_per_unit })
+            }
+            28 => Self::InitRewardPoints,
+            29 => Self::ClaimRewards,
+            30 => {
+                let (credential_mint, rest) = Self::unpack_bytes32(rest)?;
+                let (min_balance, _rest) = Self::unpack_u64(rest)?;
+                Self::ConfigurePairGate(ConfigurePairGate {
+                    credential_mint: Pubkey::new_from_array(credential_mint),
+                    min_balance,
+                })
+            }
+            31 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (fee_bps, rest) = Self::unpack_u16(rest)?;
+                let (fee_flat, rest) = Self::unpack_u64(rest)?;
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                Self::RelayedSwap(RelayedSwap {
+                    amount_in,
+                    minimum_amount_out,
+                    fee_bps,
+                    fee_flat,
+                    dex_configs,
+                })
+            }
+            32 => {
+                let (&swap_count, rest) = rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let swaps_size = swap_count as usize * BatchSwapEntry::LEN;
+                if rest.len() < swaps_size {
+                    return Err(OneSolError::InvalidInput.into());
                 }
-                Self::Swap(Swap {
+                let (swaps, rest) = rest.split_at(swaps_size);
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                Self::BatchSwap(BatchSwap {
+                    swaps: BatchSwapEntries::new(swaps)?,
+                    dex_configs,
+                })
+            }
+            33 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (&lending_accounts, rest) =
+                    rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                Self::SwapAndDeposit(SwapAndDeposit {
+                    amount_in,
+                    minimum_amount_out,
+                    lending_accounts,
+                    dex_configs,
+                })
+            }
+            34 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (&stake_pool_accounts, rest) =
+                    rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                Self::SwapAndStake(SwapAndStake {
+                    amount_in,
+                    minimum_amount_out,
+                    stake_pool_accounts,
+                    dex_configs,
+                })
+            }
+            35 => {
+                let (oracle, rest) = Self::unpack_bytes32(rest)?;
+                let (spread_bps, _rest) = Self::unpack_u16(rest)?;
+                Self::ConfigureInventoryPair(ConfigureInventoryPair {
+                    oracle: Pubkey::new_from_array(oracle),
+                    spread_bps,
+                })
+            }
+            36 | 37 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                let data = InventoryAmount { amount };
+                if tag == 36 {
+                    Self::FundInventory(data)
+                } else {
+                    Self::WithdrawInventory(data)
+                }
+            }
+            38 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (&first_step_legs, rest) =
+                    rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                Self::SwapTwoSteps(SwapTwoSteps {
                     amount_in,
                     minimum_amount_out,
+                    first_step_legs,
+                    dex_configs,
+                })
+            }
+            39 => {
+                let (fee_numerator, rest) = Self::unpack_u64(rest)?;
+                let (fee_denominator, _rest) = Self::unpack_u64(rest)?;
+                Self::SetFeeRate(SetFeeRate {
+                    fee_numerator,
+                    fee_denominator,
+                })
+            }
+            40 => Self::Pause,
+            41 => Self::Unpause,
+            42 => {
+                let (new_admin, _rest) = Self::unpack_bytes32(rest)?;
+                Self::TransferAdmin(TransferAdmin {
+                    new_admin: Pubkey::new_from_array(new_admin),
+                })
+            }
+            43 => Self::CloseProtocolAccount,
+            44 => {
+                let (amount_out, rest) = Self::unpack_u64(rest)?;
+                let (maximum_amount_in, rest) = Self::unpack_u64(rest)?;
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                Self::SwapExactOut(SwapExactOut {
+                    amount_out,
+                    maximum_amount_in,
+                    dex_configs,
+                })
+            }
+            45 => {
+                let (max_referral_fee_bps, _rest) = Self::unpack_u16(rest)?;
+                Self::SetReferralFeeCap(SetReferralFeeCap {
+                    max_referral_fee_bps,
+                })
+            }
+            46 => Self::InitializeVault,
+            47 => Self::CreateOpenOrders,
+            48 => Self::CloseOpenOrders,
+            49 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (dex_configs, _rest) = Self::unpack_dexes_configs(rest)?;
+                Self::Quote(Quote {
+                    amount_in,
                     dex_configs,
                 })
             }
+            50 => {
+                let (&swap_count, rest) = rest.split_first().ok_or(OneSolError::InvalidInput)?;
+                let (swaps, _rest) = SwapBatchEntries::split(rest, swap_count as usize, 1)?;
+                Self::SwapBatch(SwapBatch { swaps })
+            }
             _ => return Err(OneSolError::InvalidInstruction.into()),
         })
     }
 
+    /// Unpacks the instruction in the v2 encoding: the tag, then the Borsh encoding of
+    /// the instruction data, with no bytes left over.
+    fn unpack_v2(input: &'a [u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input.split_first().ok_or(OneSolError::InvalidInput)?;
+        let input = &mut &rest[..];
+        let instruction = match tag {
+            0 => Self::Initialize(Self::read(input)?),
+            1 => {
+                let mut swap = Swap {
+                    amount_in: Self::read(input)?,
+                    minimum_amount_out: Self::read(input)?,
+                    dex_configs: Self::read_dex_configs(input)?,
+                    oracle_max_deviation_bps: Self::read(input)?,
+                    tip_lamports: Self::read(input)?,
+                    split_parts: Self::read(input)?,
+                    referral_fee_bps: 0,
+                };
+                // the count of the minimums doesn't fit in what a lone referral fee leaves
+                if input.len() > size_of::<u16>() {
+                    let minimums = Self::read_slice(input, size_of::<u64>())?;
+                    swap.dex_configs = swap.dex_configs.with_minimums(minimums)?;
+                }
+                if !input.is_empty() {
+                    swap.referral_fee_bps = Self::read(input)?;
+                }
+                Self::Swap(swap)
+            }
+            2 => Self::PlaceOrder(Self::read(input)?),
+            3 => Self::FillOrder(FillOrder {
+                amount_in: Self::read(input)?,
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            4 => Self::ExpireOrder,
+            5 => Self::CreateRecurringPayment(Self::read(input)?),
+            6 => Self::ExecuteRecurringPayment(ExecuteRecurringPayment {
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            7 => Self::CancelRecurringPayment,
+            8 => Self::AddOrderPage(Self::read(input)?),
+            9 => Self::BatchFillOrders(BatchFillOrders {
+                nonce: Self::read(input)?,
+                num_orders: Self::read(input)?,
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            10 => Self::InitPriceAccumulator,
+            11 => Self::OracleSwap(OracleSwap {
+                amount_in: Self::read(input)?,
+                max_deviation_bps: Self::read(input)?,
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            12 => Self::InitConfig(Self::read(input)?),
+            13 => Self::UpdateConfig(Self::read(input)?),
+            14 => Self::ConfigureCircuitBreaker(Self::read(input)?),
+            15 => Self::InitMintStats,
+            16 => Self::FlashSwap(FlashSwap {
+                amount_in: Self::read(input)?,
+                minimum_amount_out: Self::read(input)?,
+                callback_accounts: Self::read(input)?,
+                dex_configs: Self::read_dex_configs(input)?,
+                callback_data: Self::read_slice(input, 1)?,
+            }),
+            17 => Self::FlashBorrow(Self::read(input)?),
+            18 => Self::FlashRepay(Self::read(input)?),
+            19 => Self::SwapAndBridge(SwapAndBridge {
+                amount_in: Self::read(input)?,
+                minimum_amount_out: Self::read(input)?,
+                transfer: Self::read(input)?,
+                bridge_accounts: Self::read(input)?,
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            20 => Self::CommitSwap(Self::read(input)?),
+            21 => Self::RevealSwap(RevealSwap {
+                salt: Self::read(input)?,
+                amount_in: Self::read(input)?,
+                minimum_amount_out: Self::read(input)?,
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            22 => Self::SetConfigAdmin(Self::read(input)?),
+            23 => Self::InitStakePool(Self::read(input)?),
+            24 => Self::Stake(Self::read(input)?),
+            25 => Self::Unstake(Self::read(input)?),
+            26 => Self::FundStakeRewards(Self::read(input)?),
+            27 => Self::ConfigurePairRewards(Self::read(input)?),
+            28 => Self::InitRewardPoints,
+            29 => Self::ClaimRewards,
+            30 => Self::ConfigurePairGate(Self::read(input)?),
+            31 => Self::RelayedSwap(RelayedSwap {
+                amount_in: Self::read(input)?,
+                minimum_amount_out: Self::read(input)?,
+                fee_bps: Self::read(input)?,
+                fee_flat: Self::read(input)?,
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            32 => Self::BatchSwap(BatchSwap {
+                swaps: BatchSwapEntries::new(Self::read_slice(input, BatchSwapEntry::LEN)?)?,
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            33 => Self::SwapAndDeposit(SwapAndDeposit {
+                amount_in: Self::read(input)?,
+                minimum_amount_out: Self::read(input)?,
+                lending_accounts: Self::read(input)?,
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            34 => Self::SwapAndStake(SwapAndStake {
+                amount_in: Self::read(input)?,
+                minimum_amount_out: Self::read(input)?,
+                stake_pool_accounts: Self::read(input)?,
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            35 => Self::ConfigureInventoryPair(Self::read(input)?),
+            36 => Self::FundInventory(Self::read(input)?),
+            37 => Self::WithdrawInventory(Self::read(input)?),
+            38 => Self::SwapTwoSteps(SwapTwoSteps {
+                amount_in: Self::read(input)?,
+                minimum_amount_out: Self::read(input)?,
+                first_step_legs: Self::read(input)?,
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            39 => Self::SetFeeRate(Self::read(input)?),
+            40 => Self::Pause,
+            41 => Self::Unpause,
+            42 => Self::TransferAdmin(Self::read(input)?),
+            43 => Self::CloseProtocolAccount,
+            44 => Self::SwapExactOut(SwapExactOut {
+                amount_out: Self::read(input)?,
+                maximum_amount_in: Self::read(input)?,
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            45 => Self::SetReferralFeeCap(Self::read(input)?),
+            46 => Self::InitializeVault,
+            47 => Self::CreateOpenOrders,
+            48 => Self::CloseOpenOrders,
+            49 => Self::Quote(Quote {
+                amount_in: Self::read(input)?,
+                dex_configs: Self::read_dex_configs(input)?,
+            }),
+            50 => {
+                let swap_count: u32 = Self::read(input)?;
+                let (swaps, rest) =
+                    SwapBatchEntries::split(input, swap_count as usize, size_of::<u32>())?;
+                *input = rest;
+                Self::SwapBatch(SwapBatch { swaps })
+            }
+            _ => return Err(OneSolError::InvalidInstruction.into()),
+        };
+        if !input.is_empty() {
+            return Err(OneSolError::InvalidInstruction.into());
+        }
+        Ok(instruction)
+    }
+
+    /// Checks the ranges of the fields, whichever the encoding they were unpacked from.
+    fn validate(&self) -> Result<(), ProgramError> {
+        let valid = match self {
+            Self::Swap(Swap {
+                oracle_max_deviation_bps,
+                split_parts,
+                referral_fee_bps,
+                ..
+            }) => {
+                oracle_max_deviation_bps.unwrap_or(0) <= 10_000
+                    && *split_parts <= MAX_SPLIT_PARTS
+                    && *referral_fee_bps <= 10_000
+            }
+            Self::OracleSwap(OracleSwap {
+                max_deviation_bps, ..
+            }) => *max_deviation_bps <= 10_000,
+            Self::InitConfig(params) | Self::UpdateConfig(params) => {
+                params.relayer_fee_max_bps <= 10_000 && params.split_parts <= MAX_SPLIT_PARTS
+            }
+            Self::RelayedSwap(RelayedSwap { fee_bps, .. }) => *fee_bps <= 10_000,
+            Self::ConfigureInventoryPair(ConfigureInventoryPair { spread_bps, .. }) => {
+                *spread_bps <= 10_000
+            }
+            Self::SwapTwoSteps(SwapTwoSteps {
+                first_step_legs,
+                dex_configs,
+                ..
+            }) => *first_step_legs > 0 && (*first_step_legs as usize) < dex_configs.len(),
+            Self::SetFeeRate(SetFeeRate {
+                fee_numerator,
+                fee_denominator,
+            }) => {
+                OneSolState::check_fee_rate(*fee_numerator, *fee_denominator)?;
+                true
+            }
+            Self::SetReferralFeeCap(SetReferralFeeCap {
+                max_referral_fee_bps,
+            }) => *max_referral_fee_bps <= 10_000,
+            _ => true,
+        };
+        if !valid {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        Ok(())
+    }
+
+    /// Packs a [OneSolInstruction](enum.OneSolInstruction.html) into a byte buffer in the
+    /// v2 encoding, [VERSIONED_INSTRUCTION] then [INSTRUCTION_V2] then the tag of the
+    /// instruction and the Borsh encoding of its data, which
+    /// [unpack](OneSolInstruction::unpack) reads back.
+    pub fn pack_v2(&self) -> Vec<u8> {
+        let mut buf = vec![VERSIONED_INSTRUCTION, INSTRUCTION_V2];
+        self.serialize(&mut buf).unwrap();
+        buf
+    }
+
+    /// Packs a [OneSolInstruction](enum.OneSolInstruction.html) into a byte buffer
+    /// [unpack](OneSolInstruction::unpack) reads back, leaving out the trailing optional
+    /// fields of a `Swap` left unset.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match self {
+            Self::Initialize(Initialize { nonce }) => {
+                buf.push(0);
+                buf.push(*nonce);
+            }
+            Self::Swap(Swap {
+                amount_in,
+                minimum_amount_out,
+                dex_configs,
+                oracle_max_deviation_bps,
+                tip_lamports,
+                split_parts,
+                referral_fee_bps,
+            }) => {
+                buf.push(1);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                dex_configs.pack(&mut buf);
+                let trailing = dex_configs.has_minimums() || *referral_fee_bps > 0;
+                if oracle_max_deviation_bps.is_some()
+                    || *tip_lamports > 0
+                    || *split_parts > 0
+                    || trailing
+                {
+                    let bps = oracle_max_deviation_bps.unwrap_or(NO_ORACLE_BOUND);
+                    buf.extend_from_slice(&bps.to_le_bytes());
+                }
+                if *tip_lamports > 0 || *split_parts > 0 || trailing {
+                    buf.extend_from_slice(&tip_lamports.to_le_bytes());
+                }
+                if *split_parts > 0 || trailing {
+                    buf.push(*split_parts);
+                }
+                dex_configs.pack_minimums(&mut buf);
+                if *referral_fee_bps > 0 {
+                    buf.extend_from_slice(&referral_fee_bps.to_le_bytes());
+                }
+            }
+            Self::PlaceOrder(PlaceOrder {
+                nonce,
+                kind,
+                amount_in,
+                expected_amount_out,
+                max_deviation_bps,
+                num_slices,
+                slice_interval,
+                trigger_price,
+                expires_at_slot,
+                keeper_fee_bps,
+                keeper_fee_flat,
+            }) => {
+                buf.push(2);
+                buf.push(*nonce);
+                buf.push(*kind as u8);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&expected_amount_out.to_le_bytes());
+                buf.extend_from_slice(&max_deviation_bps.to_le_bytes());
+                buf.extend_from_slice(&num_slices.to_le_bytes());
+                buf.extend_from_slice(&slice_interval.to_le_bytes());
+                buf.extend_from_slice(&trigger_price.to_le_bytes());
+                buf.extend_from_slice(&expires_at_slot.to_le_bytes());
+                buf.extend_from_slice(&keeper_fee_bps.to_le_bytes());
+                buf.extend_from_slice(&keeper_fee_flat.to_le_bytes());
+            }
+            Self::FillOrder(FillOrder {
+                amount_in,
+                dex_configs,
+            }) => {
+                buf.push(3);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                dex_configs.pack(&mut buf);
+            }
+            Self::ExpireOrder => buf.push(4),
+            Self::CreateRecurringPayment(CreateRecurringPayment {
+                nonce,
+                amount_in,
+                minimum_amount_out,
+                period,
+                max_total_in,
+                escrow_nonce,
+            }) => {
+                buf.push(5);
+                buf.push(*nonce);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.extend_from_slice(&period.to_le_bytes());
+                buf.extend_from_slice(&max_total_in.to_le_bytes());
+                buf.push(*escrow_nonce);
+            }
+            Self::ExecuteRecurringPayment(ExecuteRecurringPayment { dex_configs }) => {
+                buf.push(6);
+                dex_configs.pack(&mut buf);
+            }
+            Self::CancelRecurringPayment => buf.push(7),
+            Self::AddOrderPage(AddOrderPage { page_index }) => {
+                buf.push(8);
+                buf.extend_from_slice(&page_index.to_le_bytes());
+            }
+            Self::BatchFillOrders(BatchFillOrders {
+                nonce,
+                num_orders,
+                dex_configs,
+            }) => {
+                buf.push(9);
+                buf.push(*nonce);
+                buf.push(*num_orders);
+                dex_configs.pack(&mut buf);
+            }
+            Self::InitPriceAccumulator => buf.push(10),
+            Self::OracleSwap(OracleSwap {
+                amount_in,
+                max_deviation_bps,
+                dex_configs,
+            }) => {
+                buf.push(11);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&max_deviation_bps.to_le_bytes());
+                dex_configs.pack(&mut buf);
+            }
+            Self::InitConfig(params) | Self::UpdateConfig(params) => {
+                buf.push(match self {
+                    Self::InitConfig(_) => 12,
+                    _ => 13,
+                });
+                buf.extend_from_slice(&params.oracle_max_age_slots.to_le_bytes());
+                buf.extend_from_slice(&params.oracle_conf_multiplier.to_le_bytes());
+                buf.push(params.log_level as u8);
+                buf.extend_from_slice(params.tip_account.as_ref());
+                buf.extend_from_slice(&params.relayer_fee_max_bps.to_le_bytes());
+                buf.extend_from_slice(&params.relayer_fee_max_flat.to_le_bytes());
+                buf.push(params.split_parts);
+            }
+            Self::ConfigureCircuitBreaker(ConfigureCircuitBreaker {
+                max_move_bps,
+                window_slots,
+            }) => {
+                buf.push(14);
+                buf.extend_from_slice(&max_move_bps.to_le_bytes());
+                buf.extend_from_slice(&window_slots.to_le_bytes());
+            }
+            Self::InitMintStats => buf.push(15),
+            Self::FlashSwap(FlashSwap {
+                amount_in,
+                minimum_amount_out,
+                callback_accounts,
+                dex_configs,
+                callback_data,
+            }) => {
+                buf.push(16);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.push(*callback_accounts);
+                dex_configs.pack(&mut buf);
+                buf.extend_from_slice(callback_data);
+            }
+            Self::FlashBorrow(FlashBorrow { amount }) => {
+                buf.push(17);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::FlashRepay(FlashRepay { amount }) => {
+                buf.push(18);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::SwapAndBridge(SwapAndBridge {
+                amount_in,
+                minimum_amount_out,
+                transfer,
+                bridge_accounts,
+                dex_configs,
+            }) => {
+                buf.push(19);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.push(transfer.wrapped as u8);
+                buf.extend_from_slice(&transfer.nonce.to_le_bytes());
+                buf.extend_from_slice(&transfer.target_chain.to_le_bytes());
+                buf.extend_from_slice(&transfer.target_address);
+                buf.extend_from_slice(&transfer.relayer_fee.to_le_bytes());
+                buf.push(*bridge_accounts);
+                dex_configs.pack(&mut buf);
+            }
+            Self::CommitSwap(CommitSwap { commitment }) => {
+                buf.push(20);
+                buf.extend_from_slice(commitment);
+            }
+            Self::RevealSwap(RevealSwap {
+                salt,
+                amount_in,
+                minimum_amount_out,
+                dex_configs,
+            }) => {
+                buf.push(21);
+                buf.extend_from_slice(salt);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                dex_configs.pack(&mut buf);
+            }
+            Self::SetConfigAdmin(SetConfigAdmin { new_admin }) => {
+                buf.push(22);
+                buf.extend_from_slice(new_admin.as_ref());
+            }
+            Self::InitStakePool(InitStakePool { tiers }) => {
+                buf.push(23);
+                for tier in tiers.iter() {
+                    let mut packed = [0u8; StakeTier::LEN];
+                    tier.pack(&mut packed);
+                    buf.extend_from_slice(&packed);
+                }
+            }
+            Self::Stake(StakeAmount { amount })
+            | Self::Unstake(StakeAmount { amount })
+            | Self::FundStakeRewards(StakeAmount { amount }) => {
+                buf.push(match self {
+                    Self::Stake(_) => 24,
+                    Self::Unstake(_) => 25,
+                    _ => 26,
+                });
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::ConfigurePairRewards(ConfigurePairRewards { points_per_unit }) => {
+                buf.push(27);
+                buf.extend_from_slice(&points_per_unit.to_le_bytes());
+            }
+            Self::InitRewardPoints => buf.push(28),
+            Self::ClaimRewards => buf.push(29),
+            Self::ConfigurePairGate(ConfigurePairGate {
+                credential_mint,
+                min_balance,
+            }) => {
+                buf.push(30);
+                buf.extend_from_slice(credential_mint.as_ref());
+                buf.extend_from_slice(&min_balance.to_le_bytes());
+            }
+            Self::RelayedSwap(RelayedSwap {
+                amount_in,
+                minimum_amount_out,
+                fee_bps,
+                fee_flat,
+                dex_configs,
+            }) => {
+                buf.push(31);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.extend_from_slice(&fee_bps.to_le_bytes());
+                buf.extend_from_slice(&fee_flat.to_le_bytes());
+                dex_configs.pack(&mut buf);
+            }
+            Self::BatchSwap(BatchSwap { swaps, dex_configs }) => {
+                buf.push(32);
+                swaps.pack(&mut buf);
+                dex_configs.pack(&mut buf);
+            }
+            Self::SwapAndDeposit(SwapAndDeposit {
+                amount_in,
+                minimum_amount_out,
+                lending_accounts,
+                dex_configs,
+            }) => {
+                buf.push(33);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.push(*lending_accounts);
+                dex_configs.pack(&mut buf);
+            }
+            Self::SwapAndStake(SwapAndStake {
+                amount_in,
+                minimum_amount_out,
+                stake_pool_accounts,
+                dex_configs,
+            }) => {
+                buf.push(34);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.push(*stake_pool_accounts);
+                dex_configs.pack(&mut buf);
+            }
+            Self::ConfigureInventoryPair(ConfigureInventoryPair { oracle, spread_bps }) => {
+                buf.push(35);
+                buf.extend_from_slice(oracle.as_ref());
+                buf.extend_from_slice(&spread_bps.to_le_bytes());
+            }
+            Self::FundInventory(InventoryAmount { amount })
+            | Self::WithdrawInventory(InventoryAmount { amount }) => {
+                buf.push(match self {
+                    Self::FundInventory(_) => 36,
+                    _ => 37,
+                });
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::SwapTwoSteps(SwapTwoSteps {
+                amount_in,
+                minimum_amount_out,
+                first_step_legs,
+                dex_configs,
+            }) => {
+                buf.push(38);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.push(*first_step_legs);
+                dex_configs.pack(&mut buf);
+            }
+            Self::SetFeeRate(SetFeeRate {
+                fee_numerator,
+                fee_denominator,
+            }) => {
+                buf.push(39);
+                buf.extend_from_slice(&fee_numerator.to_le_bytes());
+                buf.extend_from_slice(&fee_denominator.to_le_bytes());
+            }
+            Self::Pause => buf.push(40),
+            Self::Unpause => buf.push(41),
+            Self::TransferAdmin(TransferAdmin { new_admin }) => {
+                buf.push(42);
+                buf.extend_from_slice(new_admin.as_ref());
+            }
+            Self::CloseProtocolAccount => buf.push(43),
+            Self::SwapExactOut(SwapExactOut {
+                amount_out,
+                maximum_amount_in,
+                dex_configs,
+            }) => {
+                buf.push(44);
+                buf.extend_from_slice(&amount_out.to_le_bytes());
+                buf.extend_from_slice(&maximum_amount_in.to_le_bytes());
+                dex_configs.pack(&mut buf);
+            }
+            Self::SetReferralFeeCap(SetReferralFeeCap {
+                max_referral_fee_bps,
+            }) => {
+                buf.push(45);
+                buf.extend_from_slice(&max_referral_fee_bps.to_le_bytes());
+            }
+            Self::InitializeVault => buf.push(46),
+            Self::CreateOpenOrders => buf.push(47),
+            Self::CloseOpenOrders => buf.push(48),
+            Self::Quote(Quote {
+                amount_in,
+                dex_configs,
+            }) => {
+                buf.push(49);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                dex_configs.pack(&mut buf);
+            }
+            Self::SwapBatch(SwapBatch { swaps }) => {
+                buf.push(50);
+                swaps.pack(&mut buf);
+            }
+        }
+        buf
+    }
+
     fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
         if input.len() >= 8 {
             let (amount, rest) = input.split_at(8);
@@ -111,45 +2550,112 @@ impl OneSolInstruction {
         }
     }
 
-    /// dexes_configs
-    /// u8: size, [u8: dex_type, u8: account_size, u8: ratio]
-    fn unpack_dexes_configs(input: &[u8]) -> Result<(Vec<DexConfig>, &[u8]), ProgramError> {
-        let (&dexes_config_size, _rest) = input.split_first().ok_or(OneSolError::InvalidInput)?;
-        if dexes_config_size < 1 {
-            return Err(OneSolError::InvalidInput.into());
+    fn unpack_u32(input: &[u8]) -> Result<(u32, &[u8]), ProgramError> {
+        if input.len() >= 4 {
+            let (amount, rest) = input.split_at(4);
+            let amount = amount
+                .try_into()
+                .map(u32::from_le_bytes)
+                .map_err(|_| OneSolError::InvalidInstruction)?;
+            Ok((amount, rest))
+        } else {
+            Err(OneSolError::InvalidInstruction.into())
         }
-        let dexes_config_real_size = (dexes_config_size * 3) as usize;
-        if _rest.len() < dexes_config_real_size {
-            return Err(OneSolError::InvalidInput.into());
+    }
+
+    fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
+        if input.len() >= 2 {
+            let (amount, rest) = input.split_at(2);
+            let amount = amount
+                .try_into()
+                .map(u16::from_le_bytes)
+                .map_err(|_| OneSolError::InvalidInstruction)?;
+            Ok((amount, rest))
+        } else {
+            Err(OneSolError::InvalidInstruction.into())
         }
-        let (dexes_configs, _rest) = _rest.split_at(dexes_config_real_size);
-        let mut dexes_iter = dexes_configs.chunks(3);
-        let mut result = vec![];
-        loop {
-            let next = dexes_iter.next();
-            if next.is_none() {
-                break;
-            }
-            let r = next.unwrap();
-            result.push(DexConfig {
-                dex_type: r[0],
-                account_size: r[1] as usize,
-                ratio: r[2],
-            });
+    }
+
+    fn unpack_bytes32(input: &[u8]) -> Result<([u8; 32], &[u8]), ProgramError> {
+        if input.len() >= 32 {
+            let (bytes, rest) = input.split_at(32);
+            let bytes = bytes
+                .try_into()
+                .map_err(|_| OneSolError::InvalidInstruction)?;
+            Ok((bytes, rest))
+        } else {
+            Err(OneSolError::InvalidInstruction.into())
         }
-        Ok((result, _rest))
+    }
+
+    /// Reads a Borsh value off the front of `input`.
+    fn read<T: BorshDeserialize>(input: &mut &[u8]) -> Result<T, ProgramError> {
+        T::deserialize(input).map_err(|_| OneSolError::InvalidInstruction.into())
+    }
+
+    /// Borrows a Borsh `Vec` of items of `size` bytes off the front of `input`.
+    fn read_slice(input: &mut &'a [u8], size: usize) -> Result<&'a [u8], ProgramError> {
+        let count: u32 = Self::read(input)?;
+        let len = (count as usize)
+            .checked_mul(size)
+            .filter(|&len| len <= input.len())
+            .ok_or(OneSolError::InvalidInstruction)?;
+        let (items, rest) = input.split_at(len);
+        *input = rest;
+        Ok(items)
+    }
+
+    /// Borrows a Borsh `Vec` of dex configs off the front of `input`.
+    fn read_dex_configs(input: &mut &'a [u8]) -> Result<DexConfigs<'a>, ProgramError> {
+        DexConfigs::new(Self::read_slice(input, DexConfig::LEN)?)
+    }
+
+    /// dexes_configs
+    /// u8: size, [u8: dex_type, u8: account_size, u16: ratio in bps]
+    fn unpack_dexes_configs(input: &'a [u8]) -> Result<(DexConfigs<'a>, &'a [u8]), ProgramError> {
+        let (&dexes_config_size, rest) = input.split_first().ok_or(OneSolError::InvalidInput)?;
+        let dexes_config_real_size = dexes_config_size as usize * DexConfig::LEN;
+        if rest.len() < dexes_config_real_size {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let (dexes_configs, rest) = rest.split_at(dexes_config_real_size);
+        Ok((DexConfigs::new(dexes_configs)?, rest))
     }
 }
 
 impl DexConfig {
+    /// Length of a packed dex config
+    pub const LEN: usize = 4;
+
     /// new DexConfig struct
-    pub fn new_dex_config(dex_type: u8, account_size: usize, ratio: u8) -> DexConfig {
+    pub fn new_dex_config(dex_type: u8, account_size: usize, ratio: u16) -> DexConfig {
         return DexConfig {
             dex_type,
             account_size,
             ratio,
+            minimum_amount_out: None,
         };
     }
+
+    fn from_bytes(bytes: &[u8]) -> DexConfig {
+        DexConfig {
+            dex_type: bytes[0],
+            account_size: bytes[1] as usize,
+            ratio: u16::from_le_bytes([bytes[2], bytes[3]]),
+            minimum_amount_out: None,
+        }
+    }
+
+    /// Packs the dex config into `output`, failing on more than 255 accounts.
+    pub fn pack(&self, output: &mut Vec<u8>) -> Result<(), ProgramError> {
+        if self.account_size > u8::MAX as usize {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        output.push(self.dex_type);
+        output.push(self.account_size as u8);
+        output.extend_from_slice(&self.ratio.to_le_bytes());
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -165,14 +2671,30 @@ mod tests {
         assert_eq!(r.is_err(), true);
         let r = OneSolInstruction::unpack_dexes_configs(&[1, 0]);
         assert_eq!(r.is_err(), true);
-        let r = OneSolInstruction::unpack_dexes_configs(&[1, 1, 1]);
+        let r = OneSolInstruction::unpack_dexes_configs(&[1, 1, 1, 0x10]);
         assert_eq!(r.is_err(), true);
-        let r = OneSolInstruction::unpack_dexes_configs(&[1, 1, 1, 1]);
+        let r = OneSolInstruction::unpack_dexes_configs(&[1, 1, 1, 0x10, 0x27]);
         assert_eq!(r.is_ok(), true);
         let (v, rest) = r.unwrap();
-        assert_eq!(v, vec![DexConfig::new_dex_config(1, 1, 1)]);
-        assert_ne!(v, vec![DexConfig::new_dex_config(1, 1, 2)]);
+        assert_eq!(v.len(), 1);
+        assert_eq!(v.get(0), Some(DexConfig::new_dex_config(1, 1, 10_000)));
+        assert_eq!(v.get(1), None);
+        assert_eq!(
+            v.iter().collect::<Vec<_>>(),
+            vec![DexConfig::new_dex_config(1, 1, 10_000)]
+        );
+        assert_eq!(rest.len(), 0);
+
+        let mut data = vec![MAX_LEGS as u8];
+        data.extend_from_slice(&[0, 7, 0x71, 0x02].repeat(MAX_LEGS));
+        let (v, rest) = OneSolInstruction::unpack_dexes_configs(&data).unwrap();
+        assert_eq!(v.len(), MAX_LEGS);
         assert_eq!(rest.len(), 0);
+        // the size of 64 configs overflows a u8
+        let mut data = vec![64u8];
+        data.extend_from_slice(&[0, 7, 0x71, 0x02].repeat(64));
+        assert!(OneSolInstruction::unpack_dexes_configs(&data).is_err());
+        assert!(DexConfigs::new(&[0, 7, 0x10]).is_err());
         // let r = OneSolInstruction::unpack_dexes_configs(&[1, 1, 1, 2]);
         // assert_eq!(r.is_ok(), true);
         // let (v, rest) = r.unwrap();
@@ -185,4 +2707,830 @@ mod tests {
         // assert_eq!(rest.len(), 1);
         // assert_eq!(rest, &[3]);
     }
+
+    #[test]
+    fn test_unpack_swap() {
+        let mut data = vec![1u8];
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        data.extend_from_slice(&900u64.to_le_bytes());
+        data.extend_from_slice(&[1, 0, 7, 0x10, 0x27]);
+        let swap = Swap {
+            amount_in: 1_000,
+            minimum_amount_out: 900,
+            dex_configs: DexConfigs::new(&[0, 7, 0x10, 0x27]).unwrap(),
+            oracle_max_deviation_bps: None,
+            tip_lamports: 0,
+            split_parts: 0,
+            referral_fee_bps: 0,
+        };
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::Swap(swap.clone())
+        );
+        data.extend_from_slice(&50u16.to_le_bytes());
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::Swap(Swap {
+                oracle_max_deviation_bps: Some(50),
+                ..swap.clone()
+            })
+        );
+        data.truncate(data.len() - 1);
+        assert!(OneSolInstruction::unpack(&data).is_err());
+
+        data.truncate(data.len() - 1);
+        data.extend_from_slice(&NO_ORACLE_BOUND.to_le_bytes());
+        data.extend_from_slice(&5_000u64.to_le_bytes());
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::Swap(Swap {
+                tip_lamports: 5_000,
+                ..swap.clone()
+            })
+        );
+        data.push(10);
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::Swap(Swap {
+                tip_lamports: 5_000,
+                split_parts: 10,
+                ..swap.clone()
+            })
+        );
+
+        // then the minimum output of each leg
+        data.extend_from_slice(&850u64.to_le_bytes());
+        match OneSolInstruction::unpack(&data).unwrap() {
+            OneSolInstruction::Swap(unpacked) => {
+                let config = unpacked.dex_configs.get(0).unwrap();
+                assert_eq!(config.minimum_amount_out, Some(850));
+                assert_eq!(config.ratio, 10_000);
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        data.push(0);
+        assert!(OneSolInstruction::unpack(&data).is_err());
+        data.truncate(data.len() - 9);
+
+        *data.last_mut().unwrap() = MAX_SPLIT_PARTS + 1;
+        assert!(OneSolInstruction::unpack(&data).is_err());
+    }
+
+    #[test]
+    fn test_unpack_oracle_swap() {
+        let mut data = vec![11u8];
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        data.extend_from_slice(&30u16.to_le_bytes());
+        data.extend_from_slice(&[1, 0, 7, 0x10, 0x27]);
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::OracleSwap(OracleSwap {
+                amount_in: 1_000,
+                max_deviation_bps: 30,
+                dex_configs: DexConfigs::new(&[0, 7, 0x10, 0x27]).unwrap(),
+            })
+        );
+        data[9..11].copy_from_slice(&10_001u16.to_le_bytes());
+        assert!(OneSolInstruction::unpack(&data).is_err());
+    }
+
+    #[test]
+    fn test_unpack_place_order() {
+        let mut data = vec![2u8, 254, 1];
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        data.extend_from_slice(&3_000u64.to_le_bytes());
+        data.extend_from_slice(&25u16.to_le_bytes());
+        data.extend_from_slice(&4u16.to_le_bytes());
+        data.extend_from_slice(&600u32.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&5_000u64.to_le_bytes());
+        data.extend_from_slice(&10u16.to_le_bytes());
+        data.extend_from_slice(&20u64.to_le_bytes());
+        let r = OneSolInstruction::unpack(&data).unwrap();
+        assert_eq!(
+            r,
+            OneSolInstruction::PlaceOrder(PlaceOrder {
+                nonce: 254,
+                kind: OrderKind::Twap,
+                amount_in: 1_000,
+                expected_amount_out: 3_000,
+                max_deviation_bps: 25,
+                num_slices: 4,
+                slice_interval: 600,
+                trigger_price: 0,
+                expires_at_slot: 5_000,
+                keeper_fee_bps: 10,
+                keeper_fee_flat: 20,
+            })
+        );
+        assert!(OneSolInstruction::unpack(&data[..data.len() - 1]).is_err());
+        data[2] = 7;
+        assert!(OneSolInstruction::unpack(&data).is_err());
+    }
+
+    #[test]
+    fn test_unpack_fill_order() {
+        let mut data = vec![3u8];
+        data.extend_from_slice(&250u64.to_le_bytes());
+        data.extend_from_slice(&[1, 0, 7, 0x10, 0x27]);
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::FillOrder(FillOrder {
+                amount_in: 250,
+                dex_configs: DexConfigs::new(&[0, 7, 0x10, 0x27]).unwrap(),
+            })
+        );
+        assert!(OneSolInstruction::unpack(&data[..9]).is_err());
+    }
+
+    #[test]
+    fn test_unpack_batch_fill_orders() {
+        let data = [9u8, 254, 3, 1, 0, 7, 0x10, 0x27];
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::BatchFillOrders(BatchFillOrders {
+                nonce: 254,
+                num_orders: 3,
+                dex_configs: DexConfigs::new(&[0, 7, 0x10, 0x27]).unwrap(),
+            })
+        );
+        assert!(OneSolInstruction::unpack(&data[..2]).is_err());
+    }
+
+    #[test]
+    fn test_unpack_config() {
+        let mut data = vec![13u8];
+        data.extend_from_slice(&25u64.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.push(1);
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::UpdateConfig(ConfigParams {
+                oracle_max_age_slots: 25,
+                oracle_conf_multiplier: 2,
+                log_level: LogLevel::Errors,
+                tip_account: Pubkey::default(),
+                relayer_fee_max_bps: 0,
+                relayer_fee_max_flat: 0,
+                split_parts: 0,
+            })
+        );
+        data.extend_from_slice(&[7u8; 32]);
+        match OneSolInstruction::unpack(&data).unwrap() {
+            OneSolInstruction::UpdateConfig(params) => {
+                assert_eq!(params.tip_account, Pubkey::new_from_array([7u8; 32]))
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        let mut relayer_data = data.clone();
+        relayer_data.extend_from_slice(&30u16.to_le_bytes());
+        relayer_data.extend_from_slice(&5_000u64.to_le_bytes());
+        match OneSolInstruction::unpack(&relayer_data).unwrap() {
+            OneSolInstruction::UpdateConfig(params) => {
+                assert_eq!(params.relayer_fee_max_bps, 30);
+                assert_eq!(params.relayer_fee_max_flat, 5_000);
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        assert!(OneSolInstruction::unpack(&relayer_data[..relayer_data.len() - 1]).is_err());
+        relayer_data.push(8);
+        match OneSolInstruction::unpack(&relayer_data).unwrap() {
+            OneSolInstruction::UpdateConfig(params) => assert_eq!(params.split_parts, 8),
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        *relayer_data.last_mut().unwrap() = MAX_SPLIT_PARTS + 1;
+        assert!(OneSolInstruction::unpack(&relayer_data).is_err());
+        data.truncate(data.len() - 32);
+        *data.last_mut().unwrap() = 3;
+        assert!(OneSolInstruction::unpack(&data).is_err());
+    }
+
+    #[test]
+    fn test_unpack_swap_and_bridge() {
+        let mut data = vec![19u8];
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        data.extend_from_slice(&990u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&7u32.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&[5u8; 32]);
+        data.extend_from_slice(&3u64.to_le_bytes());
+        data.extend_from_slice(&[17, 1, 1, 8, 0x10, 0x27]);
+        let swap = match OneSolInstruction::unpack(&data).unwrap() {
+            OneSolInstruction::SwapAndBridge(swap) => swap,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(swap.amount_in, 1_000);
+        assert_eq!(swap.minimum_amount_out, 990);
+        assert_eq!(
+            swap.transfer,
+            BridgeTransfer {
+                wrapped: true,
+                nonce: 7,
+                target_chain: 2,
+                target_address: [5u8; 32],
+                relayer_fee: 3,
+            }
+        );
+        assert_eq!(swap.bridge_accounts, 17);
+        assert_eq!(swap.dex_configs.len(), 1);
+
+        data[17] = 2;
+        assert!(OneSolInstruction::unpack(&data).is_err());
+        assert!(OneSolInstruction::unpack(&data[..40]).is_err());
+    }
+
+    #[test]
+    fn test_unpack_commit_and_reveal_swap() {
+        let mut data = vec![20u8];
+        data.extend_from_slice(&[3u8; 32]);
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::CommitSwap(CommitSwap {
+                commitment: [3u8; 32]
+            })
+        );
+        assert!(OneSolInstruction::unpack(&data[..32]).is_err());
+
+        let mut data = vec![21u8];
+        data.extend_from_slice(&[4u8; 32]);
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        data.extend_from_slice(&990u64.to_le_bytes());
+        data.extend_from_slice(&[1, 1, 8, 0x10, 0x27]);
+        let reveal = match OneSolInstruction::unpack(&data).unwrap() {
+            OneSolInstruction::RevealSwap(reveal) => reveal,
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        };
+        assert_eq!(reveal.salt, [4u8; 32]);
+        assert_eq!(reveal.amount_in, 1_000);
+        assert_eq!(reveal.minimum_amount_out, 990);
+        assert_eq!(reveal.dex_configs.len(), 1);
+    }
+
+    #[test]
+    fn test_unpack_set_config_admin() {
+        let mut data = vec![22u8];
+        data.extend_from_slice(&[6u8; 32]);
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::SetConfigAdmin(SetConfigAdmin {
+                new_admin: Pubkey::new_from_array([6u8; 32]),
+            })
+        );
+        assert!(OneSolInstruction::unpack(&data[..32]).is_err());
+    }
+
+    #[test]
+    fn test_unpack_staking() {
+        let mut data = vec![23u8];
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.extend_from_slice(&1_000u16.to_le_bytes());
+        data.extend_from_slice(&[0u8; 20]);
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::InitStakePool(InitStakePool {
+                tiers: [
+                    StakeTier {
+                        min_stake: 100,
+                        discount_bps: 1_000,
+                    },
+                    StakeTier::default(),
+                    StakeTier::default(),
+                ],
+            })
+        );
+        assert!(OneSolInstruction::unpack(&data[..30]).is_err());
+
+        let mut data = vec![25u8];
+        data.extend_from_slice(&500u64.to_le_bytes());
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::Unstake(StakeAmount { amount: 500 })
+        );
+        assert!(OneSolInstruction::unpack(&data[..8]).is_err());
+    }
+
+    #[test]
+    fn test_unpack_liquidity_mining() {
+        let mut data = vec![27u8];
+        data.extend_from_slice(&5_000u64.to_le_bytes());
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::ConfigurePairRewards(ConfigurePairRewards {
+                points_per_unit: 5_000
+            })
+        );
+        assert!(OneSolInstruction::unpack(&data[..8]).is_err());
+        assert_eq!(
+            OneSolInstruction::unpack(&[29]).unwrap(),
+            OneSolInstruction::ClaimRewards
+        );
+    }
+
+    #[test]
+    fn test_unpack_configure_pair_gate() {
+        let mut data = vec![30u8];
+        data.extend_from_slice(&[8u8; 32]);
+        data.extend_from_slice(&1u64.to_le_bytes());
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::ConfigurePairGate(ConfigurePairGate {
+                credential_mint: Pubkey::new_from_array([8u8; 32]),
+                min_balance: 1,
+            })
+        );
+        assert!(OneSolInstruction::unpack(&data[..40]).is_err());
+    }
+
+    #[test]
+    fn test_unpack_relayed_swap() {
+        let mut data = vec![31u8];
+        data.extend_from_slice(&10_000u64.to_le_bytes());
+        data.extend_from_slice(&9_800u64.to_le_bytes());
+        data.extend_from_slice(&30u16.to_le_bytes());
+        data.extend_from_slice(&50u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&[0, 7, 0x10, 0x27]);
+        match OneSolInstruction::unpack(&data).unwrap() {
+            OneSolInstruction::RelayedSwap(swap) => {
+                assert_eq!(swap.amount_in, 10_000);
+                assert_eq!(swap.minimum_amount_out, 9_800);
+                assert_eq!(swap.fee_bps, 30);
+                assert_eq!(swap.fee_flat, 50);
+                assert_eq!(swap.dex_configs.len(), 1);
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        data[17..19].copy_from_slice(&10_001u16.to_le_bytes());
+        assert!(OneSolInstruction::unpack(&data).is_err());
+    }
+
+    #[test]
+    fn test_unpack_batch_swap() {
+        let entries = [
+            BatchSwapEntry {
+                amount_in: 10_000,
+                minimum_amount_out: 9_800,
+                trailing_accounts: 0,
+            },
+            BatchSwapEntry {
+                amount_in: 20_000,
+                minimum_amount_out: 19_000,
+                trailing_accounts: 2,
+            },
+        ];
+        let mut data = vec![32u8, 2];
+        for entry in entries.iter() {
+            entry.pack(&mut data);
+        }
+        data.push(1);
+        data.extend_from_slice(&[0, 7, 0x10, 0x27]);
+        match OneSolInstruction::unpack(&data).unwrap() {
+            OneSolInstruction::BatchSwap(batch) => {
+                assert_eq!(batch.swaps.iter().collect::<Vec<_>>(), entries.to_vec());
+                assert_eq!(batch.dex_configs.len(), 1);
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        assert!(OneSolInstruction::unpack(&data[..20]).is_err());
+        data[1] = 0;
+        assert!(OneSolInstruction::unpack(&data).is_err());
+    }
+
+    #[test]
+    fn test_unpack_swap_batch() {
+        let mut data = vec![50u8, 2];
+        data.extend_from_slice(&10_000u64.to_le_bytes());
+        data.extend_from_slice(&9_800u64.to_le_bytes());
+        data.extend_from_slice(&[0, 1, 0, 7, 0x10, 0x27]);
+        data.extend_from_slice(&20_000u64.to_le_bytes());
+        data.extend_from_slice(&19_000u64.to_le_bytes());
+        data.extend_from_slice(&[2, 2, 0, 7, 0x88, 0x13, 2, 11, 0x88, 0x13]);
+        match OneSolInstruction::unpack(&data).unwrap() {
+            OneSolInstruction::SwapBatch(batch) => {
+                let swaps = batch.swaps.iter().collect::<Vec<_>>();
+                assert_eq!(swaps.len(), 2);
+                assert_eq!(swaps[0].amount_in, 10_000);
+                assert_eq!(swaps[0].dex_configs.len(), 1);
+                assert_eq!(swaps[1].minimum_amount_out, 19_000);
+                assert_eq!(swaps[1].trailing_accounts, 2);
+                assert_eq!(swaps[1].dex_configs.len(), 2);
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        // the second swap is cut short
+        assert!(OneSolInstruction::unpack(&data[..data.len() - 1]).is_err());
+        data[1] = 0;
+        assert!(OneSolInstruction::unpack(&data).is_err());
+        data[1] = MAX_BATCH_SWAPS as u8 + 1;
+        assert!(OneSolInstruction::unpack(&data).is_err());
+    }
+
+    #[test]
+    fn test_unpack_swap_and_deposit() {
+        let mut data = vec![33u8];
+        data.extend_from_slice(&10_000u64.to_le_bytes());
+        data.extend_from_slice(&9_800u64.to_le_bytes());
+        data.push(15);
+        data.push(1);
+        data.extend_from_slice(&[0, 7, 0x10, 0x27]);
+        match OneSolInstruction::unpack(&data).unwrap() {
+            OneSolInstruction::SwapAndDeposit(swap) => {
+                assert_eq!(swap.amount_in, 10_000);
+                assert_eq!(swap.minimum_amount_out, 9_800);
+                assert_eq!(swap.lending_accounts, 15);
+                assert_eq!(swap.dex_configs.len(), 1);
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        assert!(OneSolInstruction::unpack(&data[..17]).is_err());
+    }
+
+    #[test]
+    fn test_unpack_swap_and_stake() {
+        let mut data = vec![34u8];
+        data.extend_from_slice(&10_000u64.to_le_bytes());
+        data.extend_from_slice(&9_800u64.to_le_bytes());
+        data.push(10);
+        data.push(1);
+        data.extend_from_slice(&[0, 7, 0x10, 0x27]);
+        match OneSolInstruction::unpack(&data).unwrap() {
+            OneSolInstruction::SwapAndStake(swap) => {
+                assert_eq!(swap.amount_in, 10_000);
+                assert_eq!(swap.minimum_amount_out, 9_800);
+                assert_eq!(swap.stake_pool_accounts, 10);
+                assert_eq!(swap.dex_configs.len(), 1);
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        assert!(OneSolInstruction::unpack(&data[..17]).is_err());
+    }
+
+    #[test]
+    fn test_unpack_inventory() {
+        let mut data = vec![35u8];
+        data.extend_from_slice(&[6u8; 32]);
+        data.extend_from_slice(&25u16.to_le_bytes());
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::ConfigureInventoryPair(ConfigureInventoryPair {
+                oracle: Pubkey::new_from_array([6u8; 32]),
+                spread_bps: 25,
+            })
+        );
+        data[33..35].copy_from_slice(&10_001u16.to_le_bytes());
+        assert!(OneSolInstruction::unpack(&data).is_err());
+
+        let mut data = vec![37u8];
+        data.extend_from_slice(&500u64.to_le_bytes());
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::WithdrawInventory(InventoryAmount { amount: 500 })
+        );
+        data[0] = 36;
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::FundInventory(InventoryAmount { amount: 500 })
+        );
+    }
+
+    #[test]
+    fn test_unpack_swap_two_steps() {
+        let mut data = vec![38u8];
+        data.extend_from_slice(&10_000u64.to_le_bytes());
+        data.extend_from_slice(&9_800u64.to_le_bytes());
+        data.push(1);
+        data.push(3);
+        data.extend_from_slice(&[0, 7, 0x10, 0x27, 4, 8, 0x88, 0x13, 5, 7, 0x88, 0x13]);
+        match OneSolInstruction::unpack(&data).unwrap() {
+            OneSolInstruction::SwapTwoSteps(swap) => {
+                assert_eq!(swap.amount_in, 10_000);
+                assert_eq!(swap.minimum_amount_out, 9_800);
+                let (first, second) = swap.steps();
+                assert_eq!(first.len(), 1);
+                assert_eq!(second.len(), 2);
+                assert_eq!(second.get(0).unwrap().dex_type, 4);
+            }
+            instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+        // both steps need a leg
+        data[17] = 3;
+        assert!(OneSolInstruction::unpack(&data).is_err());
+        data[17] = 0;
+        assert!(OneSolInstruction::unpack(&data).is_err());
+    }
+    #[test]
+    fn test_unpack_set_fee_rate() {
+        let mut data = vec![39u8];
+        data.extend_from_slice(&25u64.to_le_bytes());
+        data.extend_from_slice(&10_000u64.to_le_bytes());
+        assert_eq!(
+            OneSolInstruction::unpack(&data).unwrap(),
+            OneSolInstruction::SetFeeRate(SetFeeRate {
+                fee_numerator: 25,
+                fee_denominator: 10_000,
+            })
+        );
+        // more than the whole output
+        data[9..].copy_from_slice(&24u64.to_le_bytes());
+        assert!(OneSolInstruction::unpack(&data).is_err());
+        assert!(OneSolInstruction::unpack(&data[..12]).is_err());
+    }
+
+    #[test]
+    fn test_pack_unpack_roundtrip() {
+        let dex_configs = DexConfigs::new(&[0, 7, 0x70, 0x17, 2, 11, 0xa0, 0x0f]).unwrap();
+        let swaps = [1u8; 2 * BatchSwapEntry::LEN];
+        let mut swap_batch = vec![];
+        for (amount_in, trailing_accounts) in [(1_000u64, 0), (2_000, 1)].iter() {
+            SwapBatchEntry {
+                amount_in: *amount_in,
+                minimum_amount_out: amount_in * 9 / 10,
+                trailing_accounts: *trailing_accounts,
+                dex_configs,
+            }
+            .pack(&mut swap_batch);
+        }
+        let minimums = [[0u8; 8], 850u64.to_le_bytes()].concat();
+        let params = ConfigParams {
+            oracle_max_age_slots: 25,
+            oracle_conf_multiplier: 2,
+            log_level: LogLevel::Errors,
+            tip_account: Pubkey::new_from_array([3u8; 32]),
+            relayer_fee_max_bps: 30,
+            relayer_fee_max_flat: 5_000,
+            split_parts: 8,
+        };
+        let swap = Swap {
+            amount_in: 1_000,
+            minimum_amount_out: 900,
+            dex_configs,
+            oracle_max_deviation_bps: None,
+            tip_lamports: 0,
+            split_parts: 0,
+            referral_fee_bps: 0,
+        };
+        let mut tiers = [StakeTier::default(); STAKE_TIERS];
+        tiers[0] = StakeTier {
+            min_stake: 1_000,
+            discount_bps: 500,
+        };
+        let instructions = vec![
+            OneSolInstruction::Initialize(Initialize { nonce: 254 }),
+            OneSolInstruction::Swap(swap.clone()),
+            OneSolInstruction::Swap(Swap {
+                oracle_max_deviation_bps: Some(50),
+                ..swap.clone()
+            }),
+            OneSolInstruction::Swap(Swap {
+                tip_lamports: 5_000,
+                ..swap.clone()
+            }),
+            OneSolInstruction::Swap(Swap {
+                split_parts: 8,
+                ..swap.clone()
+            }),
+            OneSolInstruction::Swap(Swap {
+                dex_configs: dex_configs.with_minimums(&minimums).unwrap(),
+                ..swap.clone()
+            }),
+            OneSolInstruction::Swap(Swap {
+                referral_fee_bps: 20,
+                ..swap.clone()
+            }),
+            OneSolInstruction::Swap(Swap {
+                dex_configs: dex_configs.with_minimums(&minimums).unwrap(),
+                referral_fee_bps: 20,
+                ..swap
+            }),
+            OneSolInstruction::PlaceOrder(PlaceOrder {
+                nonce: 1,
+                kind: OrderKind::Twap,
+                amount_in: 2,
+                expected_amount_out: 3,
+                max_deviation_bps: 4,
+                num_slices: 5,
+                slice_interval: 6,
+                trigger_price: 7,
+                expires_at_slot: 8,
+                keeper_fee_bps: 9,
+                keeper_fee_flat: 10,
+            }),
+            OneSolInstruction::FillOrder(FillOrder {
+                amount_in: 0,
+                dex_configs,
+            }),
+            OneSolInstruction::ExpireOrder,
+            OneSolInstruction::CreateRecurringPayment(CreateRecurringPayment {
+                nonce: 1,
+                amount_in: 2,
+                minimum_amount_out: 3,
+                period: 4,
+                max_total_in: 5,
+                escrow_nonce: 6,
+            }),
+            OneSolInstruction::ExecuteRecurringPayment(ExecuteRecurringPayment { dex_configs }),
+            OneSolInstruction::CancelRecurringPayment,
+            OneSolInstruction::AddOrderPage(AddOrderPage { page_index: 3 }),
+            OneSolInstruction::BatchFillOrders(BatchFillOrders {
+                nonce: 1,
+                num_orders: 4,
+                dex_configs,
+            }),
+            OneSolInstruction::InitPriceAccumulator,
+            OneSolInstruction::OracleSwap(OracleSwap {
+                amount_in: 1_000,
+                max_deviation_bps: 30,
+                dex_configs,
+            }),
+            OneSolInstruction::InitConfig(params.clone()),
+            OneSolInstruction::UpdateConfig(params),
+            OneSolInstruction::ConfigureCircuitBreaker(ConfigureCircuitBreaker {
+                max_move_bps: 500,
+                window_slots: 150,
+            }),
+            OneSolInstruction::InitMintStats,
+            OneSolInstruction::FlashSwap(FlashSwap {
+                amount_in: 1_000,
+                minimum_amount_out: 900,
+                callback_accounts: 2,
+                dex_configs,
+                callback_data: &[9, 8, 7],
+            }),
+            OneSolInstruction::FlashBorrow(FlashBorrow { amount: 500 }),
+            OneSolInstruction::FlashRepay(FlashRepay { amount: 500 }),
+            OneSolInstruction::SwapAndBridge(SwapAndBridge {
+                amount_in: 1_000,
+                minimum_amount_out: 900,
+                transfer: BridgeTransfer {
+                    wrapped: true,
+                    nonce: 7,
+                    target_chain: 2,
+                    target_address: [4u8; 32],
+                    relayer_fee: 10,
+                },
+                bridge_accounts: 12,
+                dex_configs,
+            }),
+            OneSolInstruction::CommitSwap(CommitSwap {
+                commitment: [5u8; 32],
+            }),
+            OneSolInstruction::RevealSwap(RevealSwap {
+                salt: [6u8; 32],
+                amount_in: 1_000,
+                minimum_amount_out: 900,
+                dex_configs,
+            }),
+            OneSolInstruction::SetConfigAdmin(SetConfigAdmin {
+                new_admin: Pubkey::new_from_array([7u8; 32]),
+            }),
+            OneSolInstruction::InitStakePool(InitStakePool { tiers }),
+            OneSolInstruction::Stake(StakeAmount { amount: 1 }),
+            OneSolInstruction::Unstake(StakeAmount { amount: 2 }),
+            OneSolInstruction::FundStakeRewards(StakeAmount { amount: 3 }),
+            OneSolInstruction::ConfigurePairRewards(ConfigurePairRewards {
+                points_per_unit: 10,
+            }),
+            OneSolInstruction::InitRewardPoints,
+            OneSolInstruction::ClaimRewards,
+            OneSolInstruction::ConfigurePairGate(ConfigurePairGate {
+                credential_mint: Pubkey::new_from_array([8u8; 32]),
+                min_balance: 1,
+            }),
+            OneSolInstruction::RelayedSwap(RelayedSwap {
+                amount_in: 1_000,
+                minimum_amount_out: 900,
+                fee_bps: 30,
+                fee_flat: 10,
+                dex_configs,
+            }),
+            OneSolInstruction::BatchSwap(BatchSwap {
+                swaps: BatchSwapEntries::new(&swaps).unwrap(),
+                dex_configs,
+            }),
+            OneSolInstruction::SwapAndDeposit(SwapAndDeposit {
+                amount_in: 1_000,
+                minimum_amount_out: 900,
+                lending_accounts: 9,
+                dex_configs,
+            }),
+            OneSolInstruction::SwapAndStake(SwapAndStake {
+                amount_in: 1_000,
+                minimum_amount_out: 900,
+                stake_pool_accounts: 10,
+                dex_configs,
+            }),
+            OneSolInstruction::ConfigureInventoryPair(ConfigureInventoryPair {
+                oracle: Pubkey::new_from_array([9u8; 32]),
+                spread_bps: 25,
+            }),
+            OneSolInstruction::FundInventory(InventoryAmount { amount: 500 }),
+            OneSolInstruction::WithdrawInventory(InventoryAmount { amount: 500 }),
+            OneSolInstruction::SwapTwoSteps(SwapTwoSteps {
+                amount_in: 1_000,
+                minimum_amount_out: 900,
+                first_step_legs: 1,
+                dex_configs,
+            }),
+            OneSolInstruction::SetFeeRate(SetFeeRate {
+                fee_numerator: 25,
+                fee_denominator: 10_000,
+            }),
+            OneSolInstruction::Pause,
+            OneSolInstruction::Unpause,
+            OneSolInstruction::TransferAdmin(TransferAdmin {
+                new_admin: Pubkey::new_from_array([10u8; 32]),
+            }),
+            OneSolInstruction::CloseProtocolAccount,
+            OneSolInstruction::SwapExactOut(SwapExactOut {
+                amount_out: 990,
+                maximum_amount_in: 1_000,
+                dex_configs,
+            }),
+            OneSolInstruction::SetReferralFeeCap(SetReferralFeeCap {
+                max_referral_fee_bps: 50,
+            }),
+            OneSolInstruction::InitializeVault,
+            OneSolInstruction::CreateOpenOrders,
+            OneSolInstruction::CloseOpenOrders,
+            OneSolInstruction::Quote(Quote {
+                amount_in: 1_000,
+                dex_configs,
+            }),
+            OneSolInstruction::SwapBatch(SwapBatch {
+                swaps: SwapBatchEntries::new(&swap_batch, 2).unwrap(),
+            }),
+        ];
+        for instruction in instructions.iter() {
+            let packed = instruction.pack();
+            assert_eq!(&OneSolInstruction::unpack(&packed).unwrap(), instruction);
+            let packed_v2 = instruction.pack_v2();
+            assert_eq!(
+                packed_v2[..3],
+                [VERSIONED_INSTRUCTION, INSTRUCTION_V2, packed[0]]
+            );
+            assert_eq!(&OneSolInstruction::unpack(&packed_v2).unwrap(), instruction);
+        }
+        // a swap leaves its unset optional fields out
+        assert_eq!(instructions[1].pack().len(), 1 + 16 + 9);
+    }
+
+    #[test]
+    fn test_unpack_v2() {
+        let dex_configs = DexConfigs::new(&[0, 7, 0x10, 0x27]).unwrap();
+        let swap = OneSolInstruction::Swap(Swap {
+            amount_in: 1_000,
+            minimum_amount_out: 900,
+            dex_configs,
+            oracle_max_deviation_bps: Some(50),
+            tip_lamports: 0,
+            split_parts: 0,
+            referral_fee_bps: 0,
+        });
+        let packed = swap.pack_v2();
+        // tag, amounts, a config, then the Borsh option and the trailing fields
+        assert_eq!(packed.len(), 2 + 1 + 16 + 4 + 4 + 3 + 9);
+        assert_eq!(packed[19..27], [1, 0, 0, 0, 0, 7, 0x10, 0x27]);
+
+        let mut unknown_version = packed.clone();
+        unknown_version[1] = 3;
+        assert_eq!(
+            OneSolInstruction::unpack(&unknown_version),
+            Err(OneSolError::InvalidInstruction.into())
+        );
+        let mut trailing = packed.clone();
+        trailing.push(0);
+        assert_eq!(
+            OneSolInstruction::unpack(&trailing),
+            Err(OneSolError::InvalidInstruction.into())
+        );
+        assert_eq!(
+            OneSolInstruction::unpack(&packed[..packed.len() - 1]),
+            Err(OneSolError::InvalidInstruction.into())
+        );
+
+        // the v1 range checks hold for v2 too
+        let swap = OneSolInstruction::Swap(Swap {
+            amount_in: 1_000,
+            minimum_amount_out: 900,
+            dex_configs,
+            oracle_max_deviation_bps: Some(10_001),
+            tip_lamports: 0,
+            split_parts: 0,
+            referral_fee_bps: 0,
+        });
+        assert_eq!(
+            OneSolInstruction::unpack(&swap.pack_v2()),
+            Err(OneSolError::InvalidInput.into())
+        );
+        let set_fee_rate = OneSolInstruction::SetFeeRate(SetFeeRate {
+            fee_numerator: 1,
+            fee_denominator: 0,
+        });
+        assert!(OneSolInstruction::unpack(&set_fee_rate.pack_v2()).is_err());
+        let set_referral_fee_cap = OneSolInstruction::SetReferralFeeCap(SetReferralFeeCap {
+            max_referral_fee_bps: 10_001,
+        });
+        assert!(OneSolInstruction::unpack(&set_referral_fee_cap.pack()).is_err());
+    }
 }
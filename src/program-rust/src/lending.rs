@@ -0,0 +1,32 @@
+//! Token-lending reserve deposits
+
+/// Token-lending instruction depositing liquidity into a reserve and the collateral it
+/// mints into an obligation, as Solend and Port Finance number it
+pub const DEPOSIT_RESERVE_LIQUIDITY_AND_OBLIGATION_COLLATERAL: u8 = 14;
+/// Length of the deposit instruction data
+pub const DEPOSIT_DATA_LEN: usize = 9;
+/// Index of the source liquidity token account among the deposit accounts
+pub const DEPOSIT_SOURCE_LIQUIDITY_INDEX: usize = 0;
+/// Index of the obligation among the deposit accounts
+pub const DEPOSIT_OBLIGATION_INDEX: usize = 8;
+
+/// Encodes the data of the deposit of `liquidity_amount`: the instruction index, then
+/// the amount.
+pub fn deposit_data(liquidity_amount: u64) -> [u8; DEPOSIT_DATA_LEN] {
+    let mut data = [0u8; DEPOSIT_DATA_LEN];
+    data[0] = DEPOSIT_RESERVE_LIQUIDITY_AND_OBLIGATION_COLLATERAL;
+    data[1..].copy_from_slice(&liquidity_amount.to_le_bytes());
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deposit_data() {
+        let data = deposit_data(1_000);
+        assert_eq!(data[0], 14);
+        assert_eq!(data[1..], 1_000u64.to_le_bytes());
+    }
+}
@@ -3,15 +3,39 @@
 
 //! OneSol - DEX Aggregator
 
+mod accounts;
+pub mod bridge;
+pub mod cpi;
+pub mod curve;
 pub mod error;
+pub mod event;
 pub mod instruction;
+pub mod lending;
+pub mod liquid_staking;
+pub mod math;
+pub mod oracle;
+pub mod pda;
+pub mod prelude;
 pub mod processor;
 pub mod state;
 mod swappers;
+pub mod token_2022;
 mod util;
 
 #[cfg(not(feature = "no-entrypoint"))]
 mod entrypoint;
 
+#[cfg(not(any(
+    feature = "spl-token-swap",
+    feature = "inventory",
+    feature = "serum-dex",
+    feature = "raydium",
+    feature = "whirlpool",
+    feature = "stable-swap"
+)))]
+compile_error!("at least one dex adapter feature must be enabled");
+
 // export
 pub use solana_program;
+
+solana_program::declare_id!("26XgL6X46AHxcMkfDNfnfQHrqZGzYEcTLj9SmAV5dLrV");
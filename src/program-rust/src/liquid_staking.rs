@@ -0,0 +1,33 @@
+//! SPL stake pool SOL deposits
+
+/// Stake pool instruction depositing SOL into the reserve of the pool for pool tokens
+pub const DEPOSIT_SOL: u8 = 14;
+/// Length of the deposit instruction data
+pub const DEPOSIT_SOL_DATA_LEN: usize = 9;
+/// Index of the stake pool among the deposit accounts
+pub const DEPOSIT_STAKE_POOL_INDEX: usize = 0;
+/// Index of the account funding the deposit among the deposit accounts
+pub const DEPOSIT_LAMPORTS_FROM_INDEX: usize = 3;
+/// Index of the token account receiving the pool tokens among the deposit accounts
+pub const DEPOSIT_POOL_TOKENS_TO_INDEX: usize = 4;
+
+/// Encodes the data of the deposit of `lamports`: the instruction index, then the
+/// Borsh-encoded lamports.
+pub fn deposit_sol_data(lamports: u64) -> [u8; DEPOSIT_SOL_DATA_LEN] {
+    let mut data = [0u8; DEPOSIT_SOL_DATA_LEN];
+    data[0] = DEPOSIT_SOL;
+    data[1..].copy_from_slice(&lamports.to_le_bytes());
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deposit_sol_data() {
+        let data = deposit_sol_data(1_000);
+        assert_eq!(data[0], 14);
+        assert_eq!(data[1..], 1_000u64.to_le_bytes());
+    }
+}
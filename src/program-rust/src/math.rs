@@ -0,0 +1,435 @@
+//! Amount arithmetic: checked mul-div in u128, fractions and basis points.
+//!
+//! Amounts are multiplied in u128 before being divided, so the product of two u64 never
+//! overflows, and results are converted back to u64 only once rounded. Every function
+//! states its rounding; an overflow or a zero denominator is a `ConversionFailure`.
+//!
+//! # Rounding policy
+//!
+//! Rounding never creates tokens: whichever way an amount is rounded, the unit lost goes
+//! to the side that holds the tokens, never to the side being paid.
+//!
+//! - Amounts paid to a user (swap outputs, pro-rata fills, rewards, oracle-priced
+//!   amounts) round [Rounding::Down].
+//! - Fees the protocol charges round [Rounding::Up], the payer keeping `amount` less the
+//!   fee rounded down, so fee and remainder sum to exactly `amount`.
+//! - Fees paid out to a third party on behalf of a user (keeper and relayer fees) and
+//!   discounts on protocol fees round [Rounding::Down], never exceeding what the user
+//!   agreed to.
+//! - Minimum amounts derived from a tolerance round [Rounding::Down], so an output at
+//!   exactly the tolerance never fails on rounding.
+//! - Amounts a user must pay in, such as the input of an exact output swap, round
+//!   [Rounding::Up].
+//!
+//! Raw amounts of mints with different decimals are not comparable: 1 unit of a 6
+//! decimals mint is worth 1_000 units of a 9 decimals one. Amounts of different mints
+//! are converted to `COMMON_DECIMALS` before being compared.
+
+use crate::{error::OneSolError, util::to_u64};
+use std::convert::TryFrom;
+
+/// Basis points in a whole.
+pub const BPS_SCALE: u16 = 10_000;
+
+/// Decimals amounts of different mints are normalized to, the most of any SPL mint.
+pub const COMMON_DECIMALS: u8 = 18;
+
+/// Returns `a * b / c` rounded down, `None` on overflow or if `c` is 0.
+pub fn checked_mul_div(a: impl Into<u128>, b: impl Into<u128>, c: impl Into<u128>) -> Option<u128> {
+    a.into().checked_mul(b.into())?.checked_div(c.into())
+}
+
+/// Direction an amount is rounded in, chosen by the rounding policy of the module.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    /// Toward zero
+    Down,
+    /// Away from zero
+    Up,
+}
+
+/// Returns `a * b / c` rounded in the `rounding` direction, as an amount.
+pub fn mul_div(
+    a: impl Into<u128>,
+    b: impl Into<u128>,
+    c: impl Into<u128>,
+    rounding: Rounding,
+) -> Result<u64, OneSolError> {
+    let c = c.into();
+    let product = a
+        .into()
+        .checked_mul(b.into())
+        .ok_or(OneSolError::ConversionFailure)?;
+    let quotient = product
+        .checked_div(c)
+        .ok_or(OneSolError::ConversionFailure)?;
+    to_u64(match rounding {
+        Rounding::Up if quotient * c < product => quotient + 1,
+        _ => quotient,
+    })
+}
+
+/// Returns `a * b / c` rounded down, as an amount.
+pub fn mul_div_floor(
+    a: impl Into<u128>,
+    b: impl Into<u128>,
+    c: impl Into<u128>,
+) -> Result<u64, OneSolError> {
+    mul_div(a, b, c, Rounding::Down)
+}
+
+/// Returns `a * b / c` rounded up, as an amount.
+pub fn mul_div_ceil(
+    a: impl Into<u128>,
+    b: impl Into<u128>,
+    c: impl Into<u128>,
+) -> Result<u64, OneSolError> {
+    mul_div(a, b, c, Rounding::Up)
+}
+
+/// A non-negative fraction of u128 terms, such as a share of a route or a price.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fraction {
+    /// Numerator
+    pub numerator: u128,
+    /// Denominator, never 0
+    pub denominator: u128,
+}
+
+impl Fraction {
+    /// Creates `numerator / denominator`, failing if `denominator` is 0.
+    pub fn new(
+        numerator: impl Into<u128>,
+        denominator: impl Into<u128>,
+    ) -> Result<Self, OneSolError> {
+        let denominator = denominator.into();
+        if denominator == 0 {
+            return Err(OneSolError::ConversionFailure);
+        }
+        Ok(Fraction {
+            numerator: numerator.into(),
+            denominator,
+        })
+    }
+
+    /// Creates the fraction of `bps` basis points.
+    pub fn from_bps(bps: u16) -> Self {
+        Fraction {
+            numerator: bps.into(),
+            denominator: BPS_SCALE.into(),
+        }
+    }
+
+    /// Creates `10^exponent`, a fraction when `exponent` is negative.
+    pub fn from_exponent(exponent: i64) -> Result<Self, OneSolError> {
+        let scale = 10u128
+            .checked_pow(
+                u32::try_from(exponent.unsigned_abs())
+                    .map_err(|_| OneSolError::ConversionFailure)?,
+            )
+            .ok_or(OneSolError::ConversionFailure)?;
+        if exponent >= 0 {
+            Fraction::new(scale, 1u8)
+        } else {
+            Fraction::new(1u8, scale)
+        }
+    }
+
+    /// Returns the product of two fractions.
+    pub fn checked_mul(&self, other: &Fraction) -> Result<Self, OneSolError> {
+        Fraction::new(
+            self.numerator
+                .checked_mul(other.numerator)
+                .ok_or(OneSolError::ConversionFailure)?,
+            self.denominator
+                .checked_mul(other.denominator)
+                .ok_or(OneSolError::ConversionFailure)?,
+        )
+    }
+
+    /// Returns one less the fraction, 0 if the fraction exceeds one.
+    pub fn complement(&self) -> Self {
+        Fraction {
+            numerator: self.denominator.saturating_sub(self.numerator),
+            denominator: self.denominator,
+        }
+    }
+
+    /// Returns `amount` times the fraction, rounded in the `rounding` direction.
+    pub fn mul(&self, amount: impl Into<u128>, rounding: Rounding) -> Result<u64, OneSolError> {
+        mul_div(amount, self.numerator, self.denominator, rounding)
+    }
+
+    /// Returns `amount` times the fraction, rounded down.
+    pub fn mul_floor(&self, amount: impl Into<u128>) -> Result<u64, OneSolError> {
+        self.mul(amount, Rounding::Down)
+    }
+
+    /// Returns `amount` times the fraction, rounded up.
+    pub fn mul_ceil(&self, amount: impl Into<u128>) -> Result<u64, OneSolError> {
+        self.mul(amount, Rounding::Up)
+    }
+}
+
+/// Returns the price impact in bps of a swap paying `amount_out` where the spot price
+/// pays `spot_amount_out`, rounded up, 0 if it pays at least the spot price.
+pub fn price_impact_bps(spot_amount_out: u64, amount_out: u64) -> u16 {
+    let shortfall = spot_amount_out.saturating_sub(amount_out);
+    if shortfall == 0 {
+        return 0;
+    }
+    // the shortfall never exceeds the spot amount, so neither does the result 10_000
+    mul_div_ceil(shortfall, BPS_SCALE, spot_amount_out).map_or(BPS_SCALE, |bps| bps as u16)
+}
+
+/// Returns `bps` basis points of `amount`, rounded in the `rounding` direction, failing
+/// past 10_000 bps.
+pub fn apply_bps(amount: u64, bps: u16, rounding: Rounding) -> Result<u64, OneSolError> {
+    if bps > BPS_SCALE {
+        return Err(OneSolError::ConversionFailure);
+    }
+    Fraction::from_bps(bps).mul(amount, rounding)
+}
+
+/// Splits `bps` basis points off `amount` for a third party, returning the share,
+/// rounded down, and the remainder, which sum to exactly `amount`.
+pub fn split_by_bps(amount: u64, bps: u16) -> Result<(u64, u64), OneSolError> {
+    let share = apply_bps(amount, bps, Rounding::Down)?;
+    Ok((share, amount - share))
+}
+
+/// Takes a fee of `bps` basis points of `amount` for the protocol, returning the fee,
+/// rounded up, and the remainder, which sum to exactly `amount`.
+pub fn fee_of(amount: u64, bps: u16) -> Result<(u64, u64), OneSolError> {
+    let fee = apply_bps(amount, bps, Rounding::Up)?;
+    Ok((fee, amount - fee))
+}
+
+/// Returns `amount` less a tolerance of `bps` basis points, rounded down, 0 past 10_000
+/// bps: the least output a swap expecting `amount` accepts.
+pub fn less_bps(amount: u64, bps: u16) -> Result<u64, OneSolError> {
+    Ok(fee_of(amount, bps.min(BPS_SCALE))?.1)
+}
+
+/// Returns the fraction converting raw amounts of a mint with `from_decimals` into raw
+/// amounts of a mint with `to_decimals`.
+pub fn decimal_shift(from_decimals: u8, to_decimals: u8) -> Result<Fraction, OneSolError> {
+    Fraction::from_exponent(to_decimals as i64 - from_decimals as i64)
+}
+
+/// Converts a raw amount of a mint with `from_decimals` into a raw amount of a mint with
+/// `to_decimals`, rounded down.
+pub fn rescale_floor(amount: u64, from_decimals: u8, to_decimals: u8) -> Result<u64, OneSolError> {
+    decimal_shift(from_decimals, to_decimals)?.mul_floor(amount)
+}
+
+/// Converts a raw amount of a mint with `from_decimals` into a raw amount of a mint with
+/// `to_decimals`, rounded up.
+pub fn rescale_ceil(amount: u64, from_decimals: u8, to_decimals: u8) -> Result<u64, OneSolError> {
+    decimal_shift(from_decimals, to_decimals)?.mul_ceil(amount)
+}
+
+/// Converts a raw amount of a mint with `decimals` to `COMMON_DECIMALS`, exactly.
+pub fn to_common_precision(amount: u64, decimals: u8) -> Result<u128, OneSolError> {
+    let shift = decimal_shift(decimals, COMMON_DECIMALS)?;
+    if shift.denominator != 1 {
+        return Err(OneSolError::ConversionFailure);
+    }
+    (amount as u128)
+        .checked_mul(shift.numerator)
+        .ok_or(OneSolError::ConversionFailure)
+}
+
+/// Converts an amount at `COMMON_DECIMALS` to a raw amount of a mint with `decimals`,
+/// rounded down.
+pub fn from_common_precision_floor(amount: u128, decimals: u8) -> Result<u64, OneSolError> {
+    decimal_shift(COMMON_DECIMALS, decimals)?.mul_floor(amount)
+}
+
+/// Converts an amount at `COMMON_DECIMALS` to a raw amount of a mint with `decimals`,
+/// rounded up.
+pub fn from_common_precision_ceil(amount: u128, decimals: u8) -> Result<u64, OneSolError> {
+    decimal_shift(COMMON_DECIMALS, decimals)?.mul_ceil(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_mul_div() {
+        assert_eq!(mul_div_floor(7u64, 3u64, 2u64), Ok(10));
+        assert_eq!(mul_div_ceil(7u64, 3u64, 2u64), Ok(11));
+        assert_eq!(mul_div_ceil(8u64, 3u64, 2u64), Ok(12));
+        assert_eq!(mul_div(7u64, 3u64, 2u64, Rounding::Down), Ok(10));
+        assert_eq!(mul_div(7u64, 3u64, 2u64, Rounding::Up), Ok(11));
+        // the product exceeds u64, the result does not
+        assert_eq!(mul_div_floor(u64::MAX, u64::MAX, u64::MAX), Ok(u64::MAX));
+        assert_eq!(
+            mul_div_floor(u64::MAX, 2u64, 1u64),
+            Err(OneSolError::ConversionFailure)
+        );
+        assert_eq!(
+            mul_div_ceil(u128::MAX, 2u64, 1u64),
+            Err(OneSolError::ConversionFailure)
+        );
+        assert_eq!(
+            mul_div_floor(1u64, 1u64, 0u64),
+            Err(OneSolError::ConversionFailure)
+        );
+        assert_eq!(
+            checked_mul_div(u64::MAX, u64::MAX, 1u64),
+            Some(u64::MAX as u128 * u64::MAX as u128)
+        );
+        assert_eq!(checked_mul_div(1u64, 1u64, 0u64), None);
+    }
+
+    #[test]
+    fn test_fraction() {
+        assert_eq!(
+            Fraction::new(1u64, 0u64),
+            Err(OneSolError::ConversionFailure)
+        );
+        let third = Fraction::new(1u64, 3u64).unwrap();
+        assert_eq!(third.mul_floor(100u64), Ok(33));
+        assert_eq!(third.mul_ceil(100u64), Ok(34));
+        assert_eq!(third.complement().mul_floor(100u64), Ok(66));
+        assert_eq!(Fraction::new(4u64, 3u64).unwrap().complement().numerator, 0);
+    }
+
+    #[test]
+    fn test_bps() {
+        assert_eq!(apply_bps(10_001, 30, Rounding::Down), Ok(30));
+        assert_eq!(apply_bps(10_001, 30, Rounding::Up), Ok(31));
+        assert_eq!(apply_bps(10_000, 30, Rounding::Up), Ok(30));
+        assert_eq!(apply_bps(u64::MAX, BPS_SCALE, Rounding::Down), Ok(u64::MAX));
+        assert_eq!(
+            apply_bps(10_000, BPS_SCALE + 1, Rounding::Down),
+            Err(OneSolError::ConversionFailure)
+        );
+        assert_eq!(split_by_bps(10_001, 30), Ok((30, 9_971)));
+        assert_eq!(fee_of(10_001, 30), Ok((31, 9_970)));
+        assert_eq!(fee_of(10_000, 0), Ok((0, 10_000)));
+        assert!(split_by_bps(10_000, u16::MAX).is_err());
+        assert_eq!(less_bps(10_001, 30), Ok(9_970));
+        assert_eq!(less_bps(10_000, 10_000), Ok(0));
+        assert_eq!(less_bps(10_000, u16::MAX), Ok(0));
+        assert_eq!(less_bps(u64::MAX, 0), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn test_price_impact_bps() {
+        assert_eq!(price_impact_bps(10_000, 9_871), 129);
+        assert_eq!(price_impact_bps(30_000, 29_999), 1);
+        assert_eq!(price_impact_bps(10_000, 10_001), 0);
+        assert_eq!(price_impact_bps(10_000, 0), BPS_SCALE);
+        assert_eq!(price_impact_bps(0, 0), 0);
+    }
+
+    #[test]
+    fn test_decimals() {
+        // 1.5 USDC (6 decimals) is 1.5 of a 9 decimals mint
+        assert_eq!(rescale_floor(1_500_000, 6, 9), Ok(1_500_000_000));
+        assert_eq!(rescale_floor(1_500_000_999, 9, 6), Ok(1_500_000));
+        assert_eq!(rescale_ceil(1_500_000_001, 9, 6), Ok(1_500_001));
+        assert_eq!(rescale_ceil(1_500_000_000, 9, 6), Ok(1_500_000));
+        assert_eq!(rescale_floor(42, 6, 6), Ok(42));
+        assert_eq!(
+            rescale_floor(u64::MAX, 0, 18),
+            Err(OneSolError::ConversionFailure)
+        );
+
+        // raw amounts compare the wrong way round, normalized ones do not
+        let usdc = 2_000_000;
+        let sol = 1_000_000_000;
+        assert!(usdc < sol);
+        assert!(to_common_precision(usdc, 6).unwrap() > to_common_precision(sol, 9).unwrap());
+        assert_eq!(
+            to_common_precision(u64::MAX, 0),
+            Ok(u64::MAX as u128 * 10u128.pow(18))
+        );
+        assert_eq!(
+            to_common_precision(1, COMMON_DECIMALS + 1),
+            Err(OneSolError::ConversionFailure)
+        );
+        let common = to_common_precision(1_234_567, 6).unwrap() + 1;
+        assert_eq!(from_common_precision_floor(common, 6), Ok(1_234_567));
+        assert_eq!(from_common_precision_ceil(common, 6), Ok(1_234_568));
+
+        assert_eq!(
+            Fraction::from_exponent(-2).unwrap().mul_floor(12_345u64),
+            Ok(123)
+        );
+        assert_eq!(
+            Fraction::from_exponent(39),
+            Err(OneSolError::ConversionFailure)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_mul_div_brackets_exact_quotient(
+            a in any::<u64>(),
+            b in any::<u64>(),
+            c in 1..=u64::MAX,
+        ) {
+            let exact = a as u128 * b as u128;
+            let c = c as u128;
+            match (mul_div_floor(a, b, c), mul_div_ceil(a, b, c)) {
+                (Ok(floor), Ok(ceil)) => {
+                    prop_assert!(floor as u128 * c <= exact);
+                    prop_assert!(exact <= ceil as u128 * c);
+                    prop_assert!(ceil - floor <= 1);
+                    prop_assert_eq!(floor == ceil, exact.is_multiple_of(c));
+                }
+                // only the rounded up quotient can be the one past u64
+                (Ok(floor), Err(_)) => prop_assert_eq!(floor, u64::MAX),
+                (Err(_), ceil) => prop_assert!(ceil.is_err()),
+            }
+        }
+
+        #[test]
+        fn test_fee_and_remainder_sum_to_amount(amount in any::<u64>(), bps in 0..=BPS_SCALE) {
+            // fees up, payout down: the two never exceed the amount, nor lose a unit
+            let (fee, remainder) = fee_of(amount, bps).unwrap();
+            prop_assert_eq!(fee, apply_bps(amount, bps, Rounding::Up).unwrap());
+            prop_assert_eq!(remainder, less_bps(amount, bps).unwrap());
+            let (share, remainder) = split_by_bps(amount, bps).unwrap();
+            prop_assert_eq!(share, apply_bps(amount, bps, Rounding::Down).unwrap());
+            prop_assert!(share <= fee && fee - share <= 1);
+            prop_assert_eq!(share as u128 + remainder as u128, amount as u128);
+        }
+
+        #[test]
+        fn test_fraction_shares_never_exceed_amount(
+            amount in any::<u64>(),
+            numerator in any::<u64>(),
+            denominator in 1..=u64::MAX,
+        ) {
+            prop_assume!(numerator <= denominator);
+            let share = Fraction::new(numerator, denominator).unwrap();
+            let rest = share.complement();
+            let floors = share.mul_floor(amount).unwrap() as u128
+                + rest.mul_floor(amount).unwrap() as u128;
+            prop_assert!(floors <= amount as u128);
+            prop_assert!(amount as u128 - floors <= 1);
+        }
+
+        #[test]
+        fn test_rescale_round_trip_never_gains(
+            amount in any::<u64>(),
+            from_decimals in 0..=COMMON_DECIMALS,
+            to_decimals in 0..=COMMON_DECIMALS,
+        ) {
+            if let Ok(rescaled) = rescale_floor(amount, from_decimals, to_decimals) {
+                prop_assert!(rescale_floor(rescaled, to_decimals, from_decimals).unwrap() <= amount);
+            }
+            if let Ok(rescaled) = rescale_ceil(amount, from_decimals, to_decimals) {
+                if let Ok(back) = rescale_ceil(rescaled, to_decimals, from_decimals) {
+                    prop_assert!(back >= amount);
+                }
+            }
+        }
+    }
+}
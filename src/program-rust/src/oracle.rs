@@ -0,0 +1,510 @@
+//! Oracle price feeds
+
+use crate::{
+    error::OneSolError,
+    math::{decimal_shift, less_bps, mul_div_floor, Fraction, BPS_SCALE},
+    util::unpack_token_account,
+};
+use arrayref::{array_ref, array_refs};
+use solana_program::{account_info::AccountInfo, pubkey::Pubkey};
+use std::convert::TryFrom;
+
+/// Pyth account magic number
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+/// Pyth account layout version
+const PYTH_VERSION: u32 = 2;
+/// Pyth account type of a price account
+const PYTH_ACCOUNT_TYPE_PRICE: u32 = 3;
+/// Pyth aggregate price status meaning the price is valid
+const PYTH_STATUS_TRADING: u32 = 1;
+/// Length of the Pyth price account header, up to and including the aggregate price
+const PYTH_PRICE_HEADER_LEN: usize = 240;
+
+/// Anchor discriminator of Switchboard v2 `AggregatorAccountData` accounts
+const SWITCHBOARD_AGGREGATOR_DISCRIMINATOR: [u8; 8] = [217, 230, 65, 101, 201, 162, 27, 125];
+/// Offset of the latest confirmed round in a Switchboard v2 aggregator account
+const SWITCHBOARD_ROUND_OFFSET: usize = 341;
+/// Length of the latest confirmed round, up to and including its standard deviation
+const SWITCHBOARD_ROUND_LEN: usize = 65;
+/// Decimals Switchboard prices are normalized to
+const SWITCHBOARD_SCALE: u32 = 9;
+
+/// Length of a spl-token-swap pool account, up to and including the token B mint
+const TOKEN_SWAP_HEADER_LEN: usize = 195;
+
+/// Pool prices are DESTINATION raw units per SOURCE raw unit, scaled by this factor.
+pub const POOL_PRICE_SCALE: u64 = 1_000_000_000;
+
+/// Price read from an oracle: the real price is `price * 10^expo`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OraclePrice {
+    /// Price, in units of `10^expo`
+    pub price: i64,
+    /// Confidence interval around the price, in units of `10^expo`
+    pub conf: u64,
+    /// Price exponent
+    pub expo: i32,
+    /// Slot the price was published at
+    pub publish_slot: u64,
+}
+
+/// A price feed account layout the oracle module can read prices from.
+pub trait PriceFeed {
+    /// Returns true if `data` looks like an account of this feed.
+    fn is_feed(data: &[u8]) -> bool;
+
+    /// Reads the current price of a feed account.
+    fn unpack_price(data: &[u8]) -> Result<OraclePrice, OneSolError>;
+}
+
+/// Pyth price accounts.
+pub struct Pyth;
+
+impl PriceFeed for Pyth {
+    fn is_feed(data: &[u8]) -> bool {
+        data.len() >= 4 && data[..4] == PYTH_MAGIC.to_le_bytes()
+    }
+
+    fn unpack_price(data: &[u8]) -> Result<OraclePrice, OneSolError> {
+        if data.len() < PYTH_PRICE_HEADER_LEN {
+            return Err(OneSolError::InvalidOracle);
+        }
+        let src = array_ref![data, 0, PYTH_PRICE_HEADER_LEN];
+        #[rustfmt::skip]
+        let (
+            magic, version, account_type, _size, _price_type, expo, _rest,
+            price, conf, status, _corp_act, publish_slot,
+        ) = array_refs![src, 4, 4, 4, 4, 4, 4, 184, 8, 8, 4, 4, 8];
+        if u32::from_le_bytes(*magic) != PYTH_MAGIC
+            || u32::from_le_bytes(*version) != PYTH_VERSION
+            || u32::from_le_bytes(*account_type) != PYTH_ACCOUNT_TYPE_PRICE
+        {
+            return Err(OneSolError::InvalidOracle);
+        }
+        if u32::from_le_bytes(*status) != PYTH_STATUS_TRADING {
+            return Err(OneSolError::InvalidOracle);
+        }
+        let price = i64::from_le_bytes(*price);
+        if price <= 0 {
+            return Err(OneSolError::InvalidOracle);
+        }
+        Ok(OraclePrice {
+            price,
+            conf: u64::from_le_bytes(*conf),
+            expo: i32::from_le_bytes(*expo),
+            publish_slot: u64::from_le_bytes(*publish_slot),
+        })
+    }
+}
+
+/// Switchboard v2 aggregator accounts, read from their latest confirmed round.
+pub struct Switchboard;
+
+impl PriceFeed for Switchboard {
+    fn is_feed(data: &[u8]) -> bool {
+        data.len() >= 8 && data[..8] == SWITCHBOARD_AGGREGATOR_DISCRIMINATOR
+    }
+
+    fn unpack_price(data: &[u8]) -> Result<OraclePrice, OneSolError> {
+        if !Self::is_feed(data) || data.len() < SWITCHBOARD_ROUND_OFFSET + SWITCHBOARD_ROUND_LEN {
+            return Err(OneSolError::InvalidOracle);
+        }
+        let src = array_ref![data, SWITCHBOARD_ROUND_OFFSET, SWITCHBOARD_ROUND_LEN];
+        #[rustfmt::skip]
+        let (
+            num_success, _num_error, _is_closed, round_open_slot, _round_open_timestamp,
+            mantissa, scale, std_mantissa, std_scale,
+        ) = array_refs![src, 4, 4, 1, 8, 8, 16, 4, 16, 4];
+        if u32::from_le_bytes(*num_success) == 0 {
+            return Err(OneSolError::InvalidOracle);
+        }
+        let mantissa = i128::from_le_bytes(*mantissa);
+        if mantissa <= 0 {
+            return Err(OneSolError::InvalidOracle);
+        }
+        let price = rescale_decimal(mantissa as u128, u32::from_le_bytes(*scale))
+            .and_then(|price| i64::try_from(price).ok())
+            .filter(|price| *price > 0)
+            .ok_or(OneSolError::InvalidOracle)?;
+        let conf = rescale_decimal(
+            i128::from_le_bytes(*std_mantissa).unsigned_abs(),
+            u32::from_le_bytes(*std_scale),
+        )
+        .map_or(u64::MAX, |conf| u64::try_from(conf).unwrap_or(u64::MAX));
+        Ok(OraclePrice {
+            price,
+            conf,
+            expo: -(SWITCHBOARD_SCALE as i32),
+            publish_slot: u64::from_le_bytes(*round_open_slot),
+        })
+    }
+}
+
+/// Rescales `mantissa * 10^-scale` to `SWITCHBOARD_SCALE` decimals, so triggers keep one
+/// exponent whatever scale each round is reported in.
+fn rescale_decimal(mantissa: u128, scale: u32) -> Option<u128> {
+    if scale >= SWITCHBOARD_SCALE {
+        Some(mantissa / 10u128.checked_pow(scale - SWITCHBOARD_SCALE)?)
+    } else {
+        mantissa.checked_mul(10u128.pow(SWITCHBOARD_SCALE - scale))
+    }
+}
+
+/// Reads the current price of a Pyth or Switchboard feed account.
+pub fn load_oracle_price(account_info: &AccountInfo) -> Result<OraclePrice, OneSolError> {
+    let data = account_info.data.borrow();
+    unpack_oracle_price(&data)
+}
+
+fn unpack_oracle_price(data: &[u8]) -> Result<OraclePrice, OneSolError> {
+    if Pyth::is_feed(data) {
+        Pyth::unpack_price(data)
+    } else if Switchboard::is_feed(data) {
+        Switchboard::unpack_price(data)
+    } else {
+        Err(OneSolError::InvalidOracle)
+    }
+}
+
+impl OraclePrice {
+    /// Returns the DESTINATION amount `amount_in` SOURCE tokens are worth at this price,
+    /// for a price of SOURCE in DESTINATION units, in raw token units.
+    pub fn amount_out(
+        &self,
+        amount_in: u64,
+        source_decimals: u8,
+        destination_decimals: u8,
+    ) -> Result<u64, OneSolError> {
+        let price = Fraction::new(self.price as u128, 1u8)?
+            .checked_mul(&Fraction::from_exponent(self.expo as i64)?)?
+            .checked_mul(&decimal_shift(source_decimals, destination_decimals)?)?;
+        price.mul_floor(amount_in)
+    }
+
+    /// Returns the minimum DESTINATION amount `amount_in` SOURCE tokens must yield to
+    /// stay within `max_deviation_bps` of this price.
+    pub fn minimum_amount_out(
+        &self,
+        amount_in: u64,
+        source_decimals: u8,
+        destination_decimals: u8,
+        max_deviation_bps: u16,
+    ) -> Result<u64, OneSolError> {
+        let amount_out = self.amount_out(amount_in, source_decimals, destination_decimals)?;
+        less_bps(amount_out, max_deviation_bps)
+    }
+
+    /// Returns the confidence interval in bps of the price.
+    pub fn confidence_bps(&self) -> u64 {
+        mul_div_floor(self.conf, BPS_SCALE, self.price.max(1) as u64).unwrap_or(u64::MAX)
+    }
+
+    /// Returns `max_deviation_bps` widened by `conf_multiplier` times the confidence
+    /// interval: volatile prices with wide intervals tolerate more deviation, while
+    /// tight intervals keep the bound close to `max_deviation_bps`.
+    pub fn widened_deviation_bps(&self, max_deviation_bps: u16, conf_multiplier: u16) -> u16 {
+        let widened = (max_deviation_bps as u64)
+            .saturating_add(self.confidence_bps().saturating_mul(conf_multiplier as u64));
+        widened.min(BPS_SCALE.into()) as u16
+    }
+
+    /// Returns true if the price was published more than `max_age_slots` before `slot`.
+    pub fn is_stale(&self, slot: u64, max_age_slots: u64) -> bool {
+        slot.saturating_sub(self.publish_slot) > max_age_slots
+    }
+}
+
+/// Scores a route leg of `amount_in` SOURCE tokens by its expected DESTINATION output.
+///
+/// Legs whose adapter can quote on-chain are scored by their `quote`; legs on venues
+/// that cannot quote (e.g. order books) fall back to the oracle mid-price, less
+/// `haircut_bps` for the spread and fees the mid-price does not account for. Returns
+/// `None` when the leg can neither be quoted nor priced, so it is left out of the split.
+pub fn score_leg(
+    quote: Option<u64>,
+    amount_in: u64,
+    mid_price: Option<&OraclePrice>,
+    source_decimals: u8,
+    destination_decimals: u8,
+    haircut_bps: u16,
+) -> Option<u64> {
+    match (quote, mid_price) {
+        (Some(quote), _) => Some(quote),
+        (None, Some(price)) => price
+            .minimum_amount_out(
+                amount_in,
+                source_decimals,
+                destination_decimals,
+                haircut_bps,
+            )
+            .ok(),
+        (None, None) => None,
+    }
+}
+
+/// Reserve accounts and mints of a spl-token-swap pool.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TokenSwapPool {
+    /// Token A reserve account
+    pub token_a: Pubkey,
+    /// Token B reserve account
+    pub token_b: Pubkey,
+    /// Token A mint
+    pub token_a_mint: Pubkey,
+    /// Token B mint
+    pub token_b_mint: Pubkey,
+}
+
+impl TokenSwapPool {
+    /// Returns true if the pool trades `mint_x` against `mint_y`, in either order.
+    pub fn pairs(&self, mint_x: &Pubkey, mint_y: &Pubkey) -> bool {
+        (self.token_a_mint == *mint_x && self.token_b_mint == *mint_y)
+            || (self.token_a_mint == *mint_y && self.token_b_mint == *mint_x)
+    }
+}
+
+/// Reads the reserve accounts and mints of a spl-token-swap pool.
+pub fn load_token_swap_pool(account_info: &AccountInfo) -> Result<TokenSwapPool, OneSolError> {
+    let data = account_info.data.borrow();
+    unpack_token_swap_pool(&data)
+}
+
+fn unpack_token_swap_pool(data: &[u8]) -> Result<TokenSwapPool, OneSolError> {
+    if data.len() < TOKEN_SWAP_HEADER_LEN {
+        return Err(OneSolError::InvalidOracle);
+    }
+    let src = array_ref![data, 0, TOKEN_SWAP_HEADER_LEN];
+    #[rustfmt::skip]
+    let (
+        _version, is_initialized, _bump_seed, _token_program_id,
+        token_a, token_b, _pool_mint, token_a_mint, token_b_mint,
+    ) = array_refs![src, 1, 1, 1, 32, 32, 32, 32, 32, 32];
+    if is_initialized[0] != 1 {
+        return Err(OneSolError::InvalidOracle);
+    }
+    Ok(TokenSwapPool {
+        token_a: Pubkey::new_from_array(*token_a),
+        token_b: Pubkey::new_from_array(*token_b),
+        token_a_mint: Pubkey::new_from_array(*token_a_mint),
+        token_b_mint: Pubkey::new_from_array(*token_b_mint),
+    })
+}
+
+/// Reads the spot price of a spl-token-swap pool from its reserves, in DESTINATION
+/// per SOURCE scaled by [POOL_PRICE_SCALE](constant.POOL_PRICE_SCALE.html).
+///
+/// The spot price can be moved within a transaction, so it is only suitable as a
+/// trigger for orders whose fills are still bounded by their own minimum output.
+pub fn load_token_swap_price(
+    pool_info: &AccountInfo,
+    token_a_info: &AccountInfo,
+    token_b_info: &AccountInfo,
+    token_program_id: &Pubkey,
+    source_mint: &Pubkey,
+) -> Result<u64, OneSolError> {
+    let pool = load_token_swap_pool(pool_info)?;
+    if *token_a_info.key != pool.token_a || *token_b_info.key != pool.token_b {
+        return Err(OneSolError::InvalidOracle);
+    }
+    let reserve_a = unpack_token_account(token_a_info, token_program_id)?.amount;
+    let reserve_b = unpack_token_account(token_b_info, token_program_id)?.amount;
+    if *source_mint == pool.token_a_mint {
+        pool_price(reserve_a, reserve_b)
+    } else if *source_mint == pool.token_b_mint {
+        pool_price(reserve_b, reserve_a)
+    } else {
+        Err(OneSolError::InvalidOracle)
+    }
+}
+
+fn pool_price(source_reserve: u64, destination_reserve: u64) -> Result<u64, OneSolError> {
+    if source_reserve == 0 || destination_reserve == 0 {
+        return Err(OneSolError::InvalidOracle);
+    }
+    mul_div_floor(destination_reserve, POOL_PRICE_SCALE, source_reserve)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pyth_price_data(price: i64, status: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 3312];
+        data[0..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&PYTH_VERSION.to_le_bytes());
+        data[8..12].copy_from_slice(&PYTH_ACCOUNT_TYPE_PRICE.to_le_bytes());
+        data[20..24].copy_from_slice(&(-8i32).to_le_bytes());
+        data[208..216].copy_from_slice(&price.to_le_bytes());
+        data[216..224].copy_from_slice(&1_500u64.to_le_bytes());
+        data[224..228].copy_from_slice(&status.to_le_bytes());
+        data[232..240].copy_from_slice(&42u64.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_unpack_pyth_price() {
+        let data = pyth_price_data(4_200_000_000, PYTH_STATUS_TRADING);
+        assert_eq!(
+            Pyth::unpack_price(&data),
+            Ok(OraclePrice {
+                price: 4_200_000_000,
+                conf: 1_500,
+                expo: -8,
+                publish_slot: 42,
+            })
+        );
+        assert_eq!(
+            Pyth::unpack_price(&data[..239]),
+            Err(OneSolError::InvalidOracle)
+        );
+        let data = pyth_price_data(4_200_000_000, 0);
+        assert_eq!(Pyth::unpack_price(&data), Err(OneSolError::InvalidOracle));
+        let data = pyth_price_data(-1, PYTH_STATUS_TRADING);
+        assert_eq!(Pyth::unpack_price(&data), Err(OneSolError::InvalidOracle));
+        let mut data = pyth_price_data(1, PYTH_STATUS_TRADING);
+        data[0] = 0;
+        assert_eq!(Pyth::unpack_price(&data), Err(OneSolError::InvalidOracle));
+    }
+
+    fn switchboard_aggregator_data(mantissa: i128, scale: u32, num_success: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 1000];
+        data[..8].copy_from_slice(&SWITCHBOARD_AGGREGATOR_DISCRIMINATOR);
+        data[341..345].copy_from_slice(&num_success.to_le_bytes());
+        data[350..358].copy_from_slice(&77u64.to_le_bytes());
+        data[366..382].copy_from_slice(&mantissa.to_le_bytes());
+        data[382..386].copy_from_slice(&scale.to_le_bytes());
+        data[386..402].copy_from_slice(&25i128.to_le_bytes());
+        data[402..406].copy_from_slice(&3u32.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_unpack_switchboard_price() {
+        let data = switchboard_aggregator_data(42_125, 3, 5);
+        assert!(!Pyth::is_feed(&data));
+        assert_eq!(
+            unpack_oracle_price(&data),
+            Ok(OraclePrice {
+                price: 42_125_000_000,
+                conf: 25_000_000,
+                expo: -9,
+                publish_slot: 77,
+            })
+        );
+        let data = switchboard_aggregator_data(42_125 * 10i128.pow(20), 23, 5);
+        assert_eq!(
+            Switchboard::unpack_price(&data).unwrap().price,
+            42_125_000_000
+        );
+        let data = switchboard_aggregator_data(i128::MAX, 0, 5);
+        assert_eq!(
+            Switchboard::unpack_price(&data),
+            Err(OneSolError::InvalidOracle)
+        );
+
+        let data = switchboard_aggregator_data(42_125, 3, 0);
+        assert_eq!(
+            Switchboard::unpack_price(&data),
+            Err(OneSolError::InvalidOracle)
+        );
+        let data = switchboard_aggregator_data(-1, 3, 5);
+        assert_eq!(
+            Switchboard::unpack_price(&data),
+            Err(OneSolError::InvalidOracle)
+        );
+        assert_eq!(
+            unpack_oracle_price(&data[1..]),
+            Err(OneSolError::InvalidOracle)
+        );
+    }
+
+    #[test]
+    fn test_oracle_amount_out() {
+        // 1 SOL (9 decimals) at 42.00000000 USD (expo -8) into USDC (6 decimals)
+        let price = OraclePrice {
+            price: 4_200_000_000,
+            conf: 0,
+            expo: -8,
+            publish_slot: 100,
+        };
+        assert_eq!(price.amount_out(1_000_000_000, 9, 6), Ok(42_000_000));
+        assert_eq!(
+            price.minimum_amount_out(1_000_000_000, 9, 6, 50),
+            Ok(41_790_000)
+        );
+        // 42 USDC into a 12 decimals token
+        assert_eq!(price.amount_out(1_000_000, 6, 12), Ok(42_000_000_000_000));
+        assert_eq!(
+            price.amount_out(u64::MAX, 0, 18),
+            Err(OneSolError::ConversionFailure)
+        );
+        let wide = OraclePrice {
+            conf: 21_000_000,
+            ..price
+        };
+        assert_eq!(wide.confidence_bps(), 50);
+        assert_eq!(wide.widened_deviation_bps(30, 2), 130);
+        assert_eq!(wide.widened_deviation_bps(9_990, 2), 10_000);
+        assert_eq!(price.widened_deviation_bps(30, 2), 30);
+        assert!(!price.is_stale(125, 25));
+        assert!(price.is_stale(126, 25));
+        assert!(!price.is_stale(90, 0));
+    }
+
+    #[test]
+    fn test_score_leg() {
+        let price = OraclePrice {
+            price: 4_200_000_000,
+            conf: 0,
+            expo: -8,
+            publish_slot: 100,
+        };
+        let amount_in = 1_000_000_000;
+        assert_eq!(
+            score_leg(Some(41_000_000), amount_in, Some(&price), 9, 6, 30),
+            Some(41_000_000)
+        );
+        assert_eq!(
+            score_leg(None, amount_in, Some(&price), 9, 6, 30),
+            Some(41_874_000)
+        );
+        assert_eq!(score_leg(None, amount_in, None, 9, 6, 30), None);
+    }
+
+    #[test]
+    fn test_unpack_token_swap_pool() {
+        let mut data = vec![0u8; 324];
+        data[0] = 1;
+        data[1] = 1;
+        data[35..67].copy_from_slice(&[1u8; 32]);
+        data[67..99].copy_from_slice(&[2u8; 32]);
+        data[131..163].copy_from_slice(&[4u8; 32]);
+        data[163..195].copy_from_slice(&[5u8; 32]);
+        let pool = unpack_token_swap_pool(&data).unwrap();
+        assert_eq!(pool.token_a, Pubkey::new_from_array([1u8; 32]));
+        assert_eq!(pool.token_b, Pubkey::new_from_array([2u8; 32]));
+        let mint_a = Pubkey::new_from_array([4u8; 32]);
+        let mint_b = Pubkey::new_from_array([5u8; 32]);
+        assert!(pool.pairs(&mint_a, &mint_b));
+        assert!(pool.pairs(&mint_b, &mint_a));
+        assert!(!pool.pairs(&mint_a, &pool.token_a));
+
+        data[1] = 0;
+        assert_eq!(
+            unpack_token_swap_pool(&data),
+            Err(OneSolError::InvalidOracle)
+        );
+        assert_eq!(
+            unpack_token_swap_pool(&data[..194]),
+            Err(OneSolError::InvalidOracle)
+        );
+    }
+
+    #[test]
+    fn test_pool_price() {
+        assert_eq!(pool_price(1_000, 2_500), Ok(2_500_000_000));
+        assert_eq!(pool_price(3, 1), Ok(333_333_333));
+        assert_eq!(pool_price(0, 1), Err(OneSolError::InvalidOracle));
+        assert_eq!(pool_price(1, u64::MAX), Err(OneSolError::ConversionFailure));
+    }
+}
@@ -0,0 +1,59 @@
+//! Program addresses of the deployed program, derived from [crate::id]
+//!
+//! On- and off-chain code derives the addresses through these rather than repeating
+//! their seeds. Programs deployed at another address use the `find_*` functions of
+//! [Processor], which take the program id.
+
+use crate::processor::Processor;
+use solana_program::pubkey::Pubkey;
+
+/// Authority of the 1sol state `state`, owning its token accounts, and its nonce.
+pub fn derive_authority(state: &Pubkey) -> (Pubkey, u8) {
+    Processor::find_authority_address(&crate::id(), state)
+}
+
+/// Global config and its bump seed.
+pub fn derive_config() -> (Pubkey, u8) {
+    Processor::find_config_address(&crate::id())
+}
+
+/// Vault of `mint` of the 1sol state `state` and its bump seed.
+pub fn derive_vault(state: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Processor::find_vault_address(&crate::id(), state, mint)
+}
+
+/// Serum open orders of `market` owned by the authority of the 1sol state `state`, and
+/// its bump seed.
+pub fn derive_open_orders(state: &Pubkey, market: &Pubkey) -> (Pubkey, u8) {
+    Processor::find_open_orders_address(&crate::id(), state, market)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_authority() {
+        let state = Pubkey::new_unique();
+        let (authority, nonce) = derive_authority(&state);
+        assert_eq!(
+            Processor::authority_id(&crate::id(), &state, nonce),
+            Ok(authority)
+        );
+        assert_eq!(
+            derive_config().0,
+            Pubkey::create_program_address(&[b"config", &[derive_config().1]], &crate::id())
+                .unwrap()
+        );
+        let mint = Pubkey::new_unique();
+        let (vault, bump_seed) = derive_vault(&state, &mint);
+        assert_eq!(
+            Pubkey::create_program_address(
+                &[b"vault", state.as_ref(), mint.as_ref(), &[bump_seed]],
+                &crate::id()
+            ),
+            Ok(vault)
+        );
+        assert_ne!(derive_open_orders(&state, &mint).0, vault);
+    }
+}
@@ -0,0 +1,18 @@
+//! Re-exports of the public API, for integrators to pull in with `use onesol::prelude::*`
+//!
+//! PDAs of the deployed program are derived by [derive_authority] and [derive_config],
+//! those of any other by the `find_*` functions of [Processor]. Instructions are those
+//! of [OneSolInstruction].
+
+pub use crate::{
+    error::{AdapterError, LegError, OneSolError},
+    event::Event,
+    instruction::{
+        flash_fee, ConfigParams, DexConfig, DexConfigs, OneSolInstruction, FLASH_FEE_BPS,
+        MAX_BATCH_SWAPS, MAX_LEGS, MAX_SPLIT_PARTS, NO_ORACLE_BOUND,
+    },
+    math::{apply_bps, fee_of, less_bps, split_by_bps, Rounding, BPS_SCALE},
+    pda::{derive_authority, derive_config},
+    processor::Processor,
+    state::{GlobalConfig, LogLevel, OneSolState},
+};
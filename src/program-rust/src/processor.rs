@@ -1,26 +1,70 @@
 //! Program state processor
 
+#[cfg(feature = "serum-dex")]
+use crate::swappers::serum_dex;
 use crate::{
-    error::OneSolError,
-    instruction::{DexConfig, Initialize, OneSolInstruction, Swap},
-    state::OneSolState,
-    swappers::{token_swap::TokenSwap, Swapper},
-    util::unpack_token_account,
+    accounts::{
+        CloseProtocolAccounts, ConfigAdminAccounts, InitConfigAccounts, ProtocolAdminAccounts,
+        SetFeeRateAccounts,
+    },
+    bridge,
+    curve::{Curve, PoolCurve},
+    error::{LegError, OneSolError},
+    event::{
+        self, BridgeTransferEvent, FeeCollectedEvent, FeeKind, LegReport, LendingDepositEvent,
+        StakePoolDepositEvent, SwapEvent, TipPaidEvent, SWAP_EVENT_VERSION,
+    },
+    instruction::{
+        flash_fee, AddOrderPage, BatchFillOrders, BatchSwap, CommitSwap, ConfigParams,
+        ConfigureCircuitBreaker, ConfigureInventoryPair, ConfigurePairGate, ConfigurePairRewards,
+        CreateRecurringPayment, DexConfig, DexConfigs, ExecuteRecurringPayment, FillOrder,
+        FlashBorrow, FlashRepay, FlashSwap, InitStakePool, Initialize, InventoryAmount,
+        OneSolInstruction, OracleSwap, PlaceOrder, Quote, RelayedSwap, RevealSwap, SetConfigAdmin,
+        SetFeeRate, SetReferralFeeCap, StakeAmount, Swap, SwapAndBridge, SwapAndDeposit,
+        SwapAndStake, SwapBatch, SwapExactOut, SwapTwoSteps, TransferAdmin, MAX_LEGS,
+    },
+    lending, liquid_staking,
+    math::{
+        apply_bps, less_bps, mul_div_ceil, mul_div_floor, price_impact_bps, Rounding, BPS_SCALE,
+    },
+    oracle::{load_oracle_price, load_token_swap_pool, load_token_swap_price, OraclePrice},
+    state::{
+        route_hash, swap_commitment, GlobalConfig, InventoryPair, LogLevel, MintStats, OneSolState,
+        Order, OrderKind, OrderPage, OrderPageEntry, OrderPageHeader, PairGate, PairRewards,
+        PriceAccumulator, QuoteResult, RecurringPayment, RewardPoints, StakeAccount, StakePool,
+        SwapCommitment, SwapReceipt, SwapResult, SWAP_PRICE_SCALE,
+    },
+    swappers::{self, DexSwapper, SwapContext, Swapper},
+    token_2022,
+    util::{
+        debug, token_account_amount, token_account_mint, token_transfer_data, unpack_mint,
+        unpack_token_account, verbose,
+    },
 };
 
+use arrayvec::ArrayVec;
 use num_traits::FromPrimitive;
 use solana_program::{
     account_info::{next_account_info, next_account_infos, AccountInfo},
+    clock::Clock,
     decode_error::DecodeError,
     entrypoint::ProgramResult,
-    log::sol_log_compute_units,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
     msg,
-    program::invoke_signed,
+    program::{invoke, invoke_signed},
     program_error::{PrintProgramError, ProgramError},
-    program_pack::Pack,
+    program_option::COption,
+    program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::{self, Sysvar},
 };
 
+/// Compute units budgeted for quoting the chunks of a split, bounding its parts.
+const SPLIT_COMPUTE_UNITS: u64 = 50_000;
+
 /// Program state handler.
 pub struct Processor {}
 
@@ -28,25 +72,279 @@ impl Processor {
     /// Processes an [Instruction](enum.Instruction.html).
     pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
         let instruction = OneSolInstruction::unpack(input)?;
+        let log_level = Self::log_level(program_id, accounts);
         match instruction {
             OneSolInstruction::Initialize(Initialize { nonce }) => {
-                msg!("Instruction: Initialize");
+                verbose!(log_level, "Instruction: Initialize");
                 Self::process_initialize(program_id, nonce, accounts)
             }
             OneSolInstruction::Swap(Swap {
                 amount_in,
                 minimum_amount_out,
                 dex_configs,
+                oracle_max_deviation_bps,
+                tip_lamports,
+                split_parts,
+                referral_fee_bps,
             }) => {
-                msg!("Instruction: Swap");
+                verbose!(log_level, "Instruction: Swap");
                 Self::process_swap(
                     program_id,
                     amount_in,
                     minimum_amount_out,
-                    &dex_configs[..],
+                    dex_configs,
+                    oracle_max_deviation_bps,
+                    tip_lamports,
+                    split_parts,
+                    referral_fee_bps,
+                    log_level,
+                    accounts,
+                )
+            }
+            OneSolInstruction::PlaceOrder(data) => {
+                verbose!(log_level, "Instruction: PlaceOrder");
+                Self::process_place_order(program_id, &data, accounts)
+            }
+            OneSolInstruction::FillOrder(FillOrder {
+                amount_in,
+                dex_configs,
+            }) => {
+                verbose!(log_level, "Instruction: FillOrder");
+                Self::process_fill_order(program_id, amount_in, dex_configs, log_level, accounts)
+            }
+            OneSolInstruction::ExpireOrder => {
+                verbose!(log_level, "Instruction: ExpireOrder");
+                Self::process_expire_order(program_id, log_level, accounts)
+            }
+            OneSolInstruction::CreateRecurringPayment(data) => {
+                verbose!(log_level, "Instruction: CreateRecurringPayment");
+                Self::process_create_recurring_payment(program_id, &data, accounts)
+            }
+            OneSolInstruction::ExecuteRecurringPayment(ExecuteRecurringPayment { dex_configs }) => {
+                verbose!(log_level, "Instruction: ExecuteRecurringPayment");
+                Self::process_execute_recurring_payment(
+                    program_id,
+                    dex_configs,
+                    log_level,
+                    accounts,
+                )
+            }
+            OneSolInstruction::CancelRecurringPayment => {
+                verbose!(log_level, "Instruction: CancelRecurringPayment");
+                Self::process_cancel_recurring_payment(program_id, accounts)
+            }
+            OneSolInstruction::AddOrderPage(AddOrderPage { page_index }) => {
+                verbose!(log_level, "Instruction: AddOrderPage");
+                Self::process_add_order_page(program_id, page_index, accounts)
+            }
+            OneSolInstruction::BatchFillOrders(BatchFillOrders {
+                nonce,
+                num_orders,
+                dex_configs,
+            }) => {
+                verbose!(log_level, "Instruction: BatchFillOrders");
+                Self::process_batch_fill_orders(
+                    program_id,
+                    nonce,
+                    num_orders,
+                    dex_configs,
+                    log_level,
+                    accounts,
+                )
+            }
+            OneSolInstruction::InitPriceAccumulator => {
+                verbose!(log_level, "Instruction: InitPriceAccumulator");
+                Self::process_init_price_accumulator(program_id, accounts)
+            }
+            OneSolInstruction::OracleSwap(OracleSwap {
+                amount_in,
+                max_deviation_bps,
+                dex_configs,
+            }) => {
+                verbose!(log_level, "Instruction: OracleSwap");
+                Self::process_swap(
+                    program_id,
+                    amount_in,
+                    0,
+                    dex_configs,
+                    Some(max_deviation_bps),
+                    0,
+                    0,
+                    0,
+                    log_level,
                     accounts,
                 )
             }
+            OneSolInstruction::InitConfig(params) => {
+                verbose!(log_level, "Instruction: InitConfig");
+                Self::process_init_config(program_id, &params, accounts)
+            }
+            OneSolInstruction::UpdateConfig(params) => {
+                verbose!(log_level, "Instruction: UpdateConfig");
+                Self::process_update_config(program_id, &params, accounts)
+            }
+            OneSolInstruction::ConfigureCircuitBreaker(data) => {
+                verbose!(log_level, "Instruction: ConfigureCircuitBreaker");
+                Self::process_configure_circuit_breaker(program_id, &data, accounts)
+            }
+            OneSolInstruction::InitMintStats => {
+                verbose!(log_level, "Instruction: InitMintStats");
+                Self::process_init_mint_stats(program_id, accounts)
+            }
+            OneSolInstruction::FlashSwap(data) => {
+                verbose!(log_level, "Instruction: FlashSwap");
+                Self::process_flash_swap(program_id, &data, log_level, accounts)
+            }
+            OneSolInstruction::FlashBorrow(data) => {
+                verbose!(log_level, "Instruction: FlashBorrow");
+                Self::process_flash_borrow(program_id, &data, accounts)
+            }
+            OneSolInstruction::FlashRepay(data) => {
+                verbose!(log_level, "Instruction: FlashRepay");
+                Self::process_flash_repay(program_id, &data, accounts)
+            }
+            OneSolInstruction::SwapAndBridge(data) => {
+                verbose!(log_level, "Instruction: SwapAndBridge");
+                Self::process_swap_and_bridge(program_id, &data, log_level, accounts)
+            }
+            OneSolInstruction::CommitSwap(data) => {
+                verbose!(log_level, "Instruction: CommitSwap");
+                Self::process_commit_swap(program_id, &data, accounts)
+            }
+            OneSolInstruction::RevealSwap(data) => {
+                verbose!(log_level, "Instruction: RevealSwap");
+                Self::process_reveal_swap(program_id, &data, log_level, accounts)
+            }
+            OneSolInstruction::SetConfigAdmin(data) => {
+                verbose!(log_level, "Instruction: SetConfigAdmin");
+                Self::process_set_config_admin(program_id, &data, accounts)
+            }
+            OneSolInstruction::InitStakePool(data) => {
+                verbose!(log_level, "Instruction: InitStakePool");
+                Self::process_init_stake_pool(program_id, &data, accounts)
+            }
+            OneSolInstruction::Stake(data) => {
+                verbose!(log_level, "Instruction: Stake");
+                Self::process_stake(program_id, &data, accounts)
+            }
+            OneSolInstruction::Unstake(data) => {
+                verbose!(log_level, "Instruction: Unstake");
+                Self::process_unstake(program_id, &data, accounts)
+            }
+            OneSolInstruction::FundStakeRewards(data) => {
+                verbose!(log_level, "Instruction: FundStakeRewards");
+                Self::process_fund_stake_rewards(program_id, &data, accounts)
+            }
+            OneSolInstruction::ConfigurePairRewards(data) => {
+                verbose!(log_level, "Instruction: ConfigurePairRewards");
+                Self::process_configure_pair_rewards(program_id, &data, accounts)
+            }
+            OneSolInstruction::InitRewardPoints => {
+                verbose!(log_level, "Instruction: InitRewardPoints");
+                Self::process_init_reward_points(program_id, accounts)
+            }
+            OneSolInstruction::ClaimRewards => {
+                verbose!(log_level, "Instruction: ClaimRewards");
+                Self::process_claim_rewards(program_id, accounts)
+            }
+            OneSolInstruction::ConfigurePairGate(data) => {
+                verbose!(log_level, "Instruction: ConfigurePairGate");
+                Self::process_configure_pair_gate(program_id, &data, accounts)
+            }
+            OneSolInstruction::RelayedSwap(data) => {
+                verbose!(log_level, "Instruction: RelayedSwap");
+                Self::process_relayed_swap(program_id, &data, log_level, accounts)
+            }
+            OneSolInstruction::BatchSwap(data) => {
+                verbose!(log_level, "Instruction: BatchSwap");
+                Self::process_batch_swap(program_id, &data, log_level, accounts)
+            }
+            OneSolInstruction::SwapAndDeposit(data) => {
+                verbose!(log_level, "Instruction: SwapAndDeposit");
+                Self::process_swap_and_deposit(program_id, &data, log_level, accounts)
+            }
+            OneSolInstruction::SwapAndStake(data) => {
+                verbose!(log_level, "Instruction: SwapAndStake");
+                Self::process_swap_and_stake(program_id, &data, log_level, accounts)
+            }
+            OneSolInstruction::ConfigureInventoryPair(data) => {
+                verbose!(log_level, "Instruction: ConfigureInventoryPair");
+                Self::process_configure_inventory_pair(program_id, &data, accounts)
+            }
+            OneSolInstruction::FundInventory(data) => {
+                verbose!(log_level, "Instruction: FundInventory");
+                Self::process_fund_inventory(program_id, &data, accounts)
+            }
+            OneSolInstruction::WithdrawInventory(data) => {
+                verbose!(log_level, "Instruction: WithdrawInventory");
+                Self::process_withdraw_inventory(program_id, &data, accounts)
+            }
+            OneSolInstruction::SwapTwoSteps(data) => {
+                verbose!(log_level, "Instruction: SwapTwoSteps");
+                Self::process_swap_two_steps(program_id, &data, log_level, accounts)
+            }
+            OneSolInstruction::SetFeeRate(data) => {
+                verbose!(log_level, "Instruction: SetFeeRate");
+                Self::process_set_fee_rate(program_id, &data, accounts)
+            }
+            OneSolInstruction::Pause => {
+                verbose!(log_level, "Instruction: Pause");
+                Self::process_set_paused(program_id, true, accounts)
+            }
+            OneSolInstruction::Unpause => {
+                verbose!(log_level, "Instruction: Unpause");
+                Self::process_set_paused(program_id, false, accounts)
+            }
+            OneSolInstruction::TransferAdmin(data) => {
+                verbose!(log_level, "Instruction: TransferAdmin");
+                Self::process_transfer_admin(program_id, &data, accounts)
+            }
+            OneSolInstruction::CloseProtocolAccount => {
+                verbose!(log_level, "Instruction: CloseProtocolAccount");
+                Self::process_close_protocol_account(program_id, accounts)
+            }
+            OneSolInstruction::SwapExactOut(data) => {
+                verbose!(log_level, "Instruction: SwapExactOut");
+                Self::process_swap_exact_out(program_id, &data, log_level, accounts)
+            }
+            OneSolInstruction::SetReferralFeeCap(data) => {
+                verbose!(log_level, "Instruction: SetReferralFeeCap");
+                Self::process_set_referral_fee_cap(program_id, &data, accounts)
+            }
+            OneSolInstruction::InitializeVault => {
+                verbose!(log_level, "Instruction: InitializeVault");
+                Self::process_initialize_vault(program_id, accounts)
+            }
+            OneSolInstruction::CreateOpenOrders => {
+                verbose!(log_level, "Instruction: CreateOpenOrders");
+                #[cfg(feature = "serum-dex")]
+                {
+                    Self::process_create_open_orders(program_id, accounts)
+                }
+                #[cfg(not(feature = "serum-dex"))]
+                {
+                    Err(OneSolError::DexProgramNotAllowed.into())
+                }
+            }
+            OneSolInstruction::Quote(data) => {
+                verbose!(log_level, "Instruction: Quote");
+                Self::process_quote(&data, accounts)
+            }
+            OneSolInstruction::SwapBatch(data) => {
+                verbose!(log_level, "Instruction: SwapBatch");
+                Self::process_swap_batch(program_id, &data, log_level, accounts)
+            }
+            OneSolInstruction::CloseOpenOrders => {
+                verbose!(log_level, "Instruction: CloseOpenOrders");
+                #[cfg(feature = "serum-dex")]
+                {
+                    Self::process_close_open_orders(program_id, accounts)
+                }
+                #[cfg(not(feature = "serum-dex"))]
+                {
+                    Err(OneSolError::DexProgramNotAllowed.into())
+                }
+            }
         }
     }
 
@@ -61,9 +359,19 @@ impl Processor {
         let authority_info = next_account_info(account_info_iter)?;
         let token_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
 
         let token_program_id = *token_program_info.key;
 
+        if onesol_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if OneSolState::unpack_unchecked(&onesol_info.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        if !admin_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
         if *authority_info.key != Self::authority_id(program_id, onesol_info.key, nonce)? {
             return Err(OneSolError::InvalidProgramAddress.into());
         }
@@ -84,157 +392,4746 @@ impl Processor {
             token_program_id,
             token: *token_info.key,
             token_mint: token.mint,
+            admin: *admin_info.key,
+            // no fee until the admin sets one
+            fee_numerator: 0,
+            fee_denominator: 0,
+            treasury: Pubkey::default(),
+            is_paused: false,
+            max_referral_fee_bps: 0,
         };
         OneSolState::pack(obj, &mut onesol_info.data.borrow_mut())?;
         Ok(())
     }
 
-    /// Processes an [Swap](enum.Instruction.html).
-    pub fn process_swap(
+    /// Processes a [SetFeeRate](enum.Instruction.html).
+    pub fn process_set_fee_rate(
         program_id: &Pubkey,
-        amount_in: u64,
-        minimum_amount_out: u64,
-        dex_configs: &[DexConfig],
+        data: &SetFeeRate,
         accounts: &[AccountInfo],
     ) -> ProgramResult {
-        msg!("start process swap");
-        if amount_in < 1 {
+        let SetFeeRateAccounts {
+            admin: admin_info,
+            protocol: protocol_info,
+            treasury: treasury_info,
+        } = SetFeeRateAccounts::from_account_infos(program_id, accounts)?;
+        OneSolState::check_fee_rate(data.fee_numerator, data.fee_denominator)?;
+
+        let mut state = Self::load_protocol_as_admin(protocol_info, admin_info)?;
+        let treasury = unpack_token_account(treasury_info, &state.token_program_id)?;
+        if treasury.mint != state.token_mint {
             return Err(OneSolError::InvalidInput.into());
         }
+        state.fee_numerator = data.fee_numerator;
+        state.fee_denominator = data.fee_denominator;
+        state.treasury = *treasury_info.key;
+        OneSolState::pack(state, &mut protocol_info.data.borrow_mut())
+    }
+
+    /// Processes a [Pause or an Unpause](enum.Instruction.html).
+    pub fn process_set_paused(
+        program_id: &Pubkey,
+        is_paused: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let ProtocolAdminAccounts {
+            admin: admin_info,
+            protocol: protocol_info,
+        } = ProtocolAdminAccounts::from_account_infos(program_id, accounts)?;
+
+        let mut state = Self::load_protocol_as_admin(protocol_info, admin_info)?;
+        state.is_paused = is_paused;
+        OneSolState::pack(state, &mut protocol_info.data.borrow_mut())
+    }
+
+    /// Processes a [TransferAdmin](enum.Instruction.html).
+    pub fn process_transfer_admin(
+        program_id: &Pubkey,
+        data: &TransferAdmin,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let ProtocolAdminAccounts {
+            admin: admin_info,
+            protocol: protocol_info,
+        } = ProtocolAdminAccounts::from_account_infos(program_id, accounts)?;
+
+        let mut state = Self::load_protocol_as_admin(protocol_info, admin_info)?;
+        state.admin = data.new_admin;
+        OneSolState::pack(state, &mut protocol_info.data.borrow_mut())
+    }
+
+    /// Processes a [SetReferralFeeCap](enum.Instruction.html).
+    pub fn process_set_referral_fee_cap(
+        program_id: &Pubkey,
+        data: &SetReferralFeeCap,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let ProtocolAdminAccounts {
+            admin: admin_info,
+            protocol: protocol_info,
+        } = ProtocolAdminAccounts::from_account_infos(program_id, accounts)?;
 
+        let mut state = Self::load_protocol_as_admin(protocol_info, admin_info)?;
+        state.max_referral_fee_bps = data.max_referral_fee_bps;
+        OneSolState::pack(state, &mut protocol_info.data.borrow_mut())
+    }
+
+    /// Processes an [InitializeVault](enum.Instruction.html).
+    pub fn process_initialize_vault(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let protocol_account = next_account_info(account_info_iter)?;
-        let protocol_authority = next_account_info(account_info_iter)?;
-        let user_transfer_authority_info = next_account_info(account_info_iter)?;
-        let protocol_token_account = next_account_info(account_info_iter)?;
-        let source_info = next_account_info(account_info_iter)?;
-        let destination_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let protocol_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
 
-        if protocol_account.owner != program_id {
+        if !payer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if protocol_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let nonce = OneSolState::view(&protocol_info.data.borrow())?.nonce();
+        if *authority_info.key != Self::authority_id(program_id, protocol_info.key, nonce)? {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        if *token_program_info.key != spl_token::id() {
+            return Err(OneSolError::IncorrectTokenProgramId.into());
+        }
+        let (vault_key, bump_seed) =
+            Self::find_vault_address(program_id, protocol_info.key, mint_info.key);
+        if *vault_info.key != vault_key {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+
+        let space = spl_token::state::Account::LEN;
+        let ix = system_instruction::create_account(
+            payer_info.key,
+            vault_info.key,
+            Rent::from_account_info(rent_info)?.minimum_balance(space),
+            space as u64,
+            token_program_info.key,
+        );
+        invoke_signed(
+            &ix,
+            &[
+                payer_info.clone(),
+                vault_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[&[
+                b"vault",
+                &protocol_info.key.to_bytes(),
+                &mint_info.key.to_bytes(),
+                &[bump_seed],
+            ]],
+        )?;
+        let ix = spl_token::instruction::initialize_account2(
+            token_program_info.key,
+            vault_info.key,
+            mint_info.key,
+            authority_info.key,
+        )?;
+        invoke(
+            &ix,
+            &[
+                vault_info.clone(),
+                mint_info.clone(),
+                rent_info.clone(),
+                token_program_info.clone(),
+            ],
+        )
+    }
+
+    /// Processes a [CreateOpenOrders](enum.Instruction.html).
+    #[cfg(feature = "serum-dex")]
+    pub fn process_create_open_orders(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let protocol_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let open_orders_info = next_account_info(account_info_iter)?;
+        let market_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let dex_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let nonce = Self::check_open_orders_accounts(
+            program_id,
+            admin_info,
+            protocol_info,
+            authority_info,
+        )?;
+        if market_info.owner != dex_program_info.key {
             return Err(ProgramError::IncorrectProgramId);
         }
-        let protocol_info = OneSolState::unpack(&protocol_account.data.borrow())?;
+        let (open_orders_key, bump_seed) =
+            Self::find_open_orders_address(program_id, protocol_info.key, market_info.key);
+        if *open_orders_info.key != open_orders_key {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+
+        let space = serum_dex::OPEN_ORDERS_LEN;
+        let ix = system_instruction::create_account(
+            admin_info.key,
+            open_orders_info.key,
+            Rent::from_account_info(rent_info)?.minimum_balance(space),
+            space as u64,
+            dex_program_info.key,
+        );
+        invoke_signed(
+            &ix,
+            &[
+                admin_info.clone(),
+                open_orders_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[&[
+                b"open_orders",
+                &protocol_info.key.to_bytes(),
+                &market_info.key.to_bytes(),
+                &[bump_seed],
+            ]],
+        )?;
+        serum_dex::init_open_orders(
+            open_orders_info,
+            authority_info,
+            market_info,
+            rent_info,
+            dex_program_info,
+            &[&[&protocol_info.key.to_bytes()[..32], &[nonce]]],
+        )
+    }
+
+    /// Processes a [CloseOpenOrders](enum.Instruction.html).
+    #[cfg(feature = "serum-dex")]
+    pub fn process_close_open_orders(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let protocol_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let open_orders_info = next_account_info(account_info_iter)?;
+        let market_info = next_account_info(account_info_iter)?;
+        let coin_vault_info = next_account_info(account_info_iter)?;
+        let pc_vault_info = next_account_info(account_info_iter)?;
+        let coin_wallet_info = next_account_info(account_info_iter)?;
+        let pc_wallet_info = next_account_info(account_info_iter)?;
+        let vault_signer_info = next_account_info(account_info_iter)?;
+        let receiver_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let dex_program_info = next_account_info(account_info_iter)?;
 
-        if *protocol_authority.key
-            != Self::authority_id(program_id, protocol_account.key, protocol_info.nonce)?
+        let nonce = Self::check_open_orders_accounts(
+            program_id,
+            admin_info,
+            protocol_info,
+            authority_info,
+        )?;
+        if *open_orders_info.key
+            != Self::find_open_orders_address(program_id, protocol_info.key, market_info.key).0
         {
             return Err(OneSolError::InvalidProgramAddress.into());
         }
 
-        if *destination_info.key == protocol_info.token || *source_info.key == protocol_info.token {
+        let protocol_bytes = protocol_info.key.to_bytes();
+        let signers_seeds: &[&[&[u8]]] = &[&[&protocol_bytes[..32], &[nonce]]];
+        // the funds left by the fills of past legs, closing needs the open orders empty
+        serum_dex::SettleFunds {
+            market: market_info,
+            open_orders: open_orders_info,
+            owner: authority_info,
+            coin_vault: coin_vault_info,
+            pc_vault: pc_vault_info,
+            coin_wallet: coin_wallet_info,
+            pc_wallet: pc_wallet_info,
+            vault_signer: vault_signer_info,
+            token_program: token_program_info,
+            dex_program: dex_program_info,
+        }
+        .invoke(signers_seeds)?;
+        serum_dex::close_open_orders(
+            open_orders_info,
+            authority_info,
+            receiver_info,
+            market_info,
+            dex_program_info,
+            signers_seeds,
+        )
+    }
+
+    /// Checks `admin_info` signs as the admin of `protocol_info`, whose authority is
+    /// `authority_info`, returning the nonce of the authority.
+    #[cfg(feature = "serum-dex")]
+    fn check_open_orders_accounts(
+        program_id: &Pubkey,
+        admin_info: &AccountInfo,
+        protocol_info: &AccountInfo,
+        authority_info: &AccountInfo,
+    ) -> Result<u8, ProgramError> {
+        if !admin_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if protocol_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let state = Self::load_protocol_as_admin(protocol_info, admin_info)?;
+        if *authority_info.key != Self::authority_id(program_id, protocol_info.key, state.nonce)? {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        Ok(state.nonce)
+    }
+
+    /// Processes a [CloseProtocolAccount](enum.Instruction.html).
+    pub fn process_close_protocol_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let CloseProtocolAccounts {
+            admin: admin_info,
+            protocol: protocol_info,
+            protocol_authority: authority_info,
+            protocol_token: protocol_token_info,
+            destination: destination_info,
+            receiver: receiver_info,
+            token_program: token_program_info,
+        } = CloseProtocolAccounts::from_account_infos(program_id, accounts)?;
+
+        let state = Self::load_protocol_as_admin(protocol_info, admin_info)?;
+        if *authority_info.key != Self::authority_id(program_id, protocol_info.key, state.nonce)? {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        if *protocol_token_info.key != state.token {
             return Err(OneSolError::IncorrectSwapAccount.into());
         }
+        if *token_program_info.key != state.token_program_id {
+            return Err(OneSolError::IncorrectTokenProgramId.into());
+        }
+        if receiver_info.key == protocol_info.key {
+            return Err(OneSolError::InvalidInput.into());
+        }
+
+        let protocol_token = unpack_token_account(protocol_token_info, &state.token_program_id)?;
+        Payout::new(
+            protocol_info.key,
+            state.nonce,
+            token_program_info,
+            protocol_token_info,
+            authority_info,
+        )
+        .transfer(destination_info, protocol_token.amount)?;
+
+        let protocol_lamports = protocol_info.lamports();
+        **protocol_info.lamports.borrow_mut() = 0;
+        **receiver_info.lamports.borrow_mut() = receiver_info
+            .lamports()
+            .checked_add(protocol_lamports)
+            .ok_or(OneSolError::ConversionFailure)?;
+        protocol_info.data.borrow_mut().fill(0);
+        Ok(())
+    }
+
+    /// Unpacks the state of `protocol_info`, checking `admin_info` is its admin.
+    fn load_protocol_as_admin(
+        protocol_info: &AccountInfo,
+        admin_info: &AccountInfo,
+    ) -> Result<OneSolState, ProgramError> {
+        let state = OneSolState::unpack(&protocol_info.data.borrow())?;
+        if *admin_info.key != state.admin {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        Ok(state)
+    }
 
-        if *source_info.key == *destination_info.key {
+    /// Processes an [Swap](enum.Instruction.html).
+    ///
+    /// Runs in phases, each in a stack frame of its own: parsing the accounts, validating
+    /// them, executing the legs, tipping and settling the output.
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_swap(
+        program_id: &Pubkey,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        dex_configs: DexConfigs,
+        oracle_max_deviation_bps: Option<u16>,
+        tip_lamports: u64,
+        split_parts: u8,
+        referral_fee_bps: u16,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        debug!(log_level, "start process swap");
+        if amount_in < 1 {
             return Err(OneSolError::InvalidInput.into());
         }
 
-        let token_program_id = *token_program_info.key;
+        let account_info_iter = &mut accounts.iter();
+        let swap_accounts = SwapAccounts::parse(account_info_iter)?;
+        let validated = Self::validate_swap(program_id, &swap_accounts)?;
+        let dex_accounts = account_info_iter.as_slice();
+        // a lone leg owing no fee outputs straight into DESTINATION, sparing the payout
+        let direct =
+            dex_configs.len() == 1 && validated.fee_numerator == 0 && referral_fee_bps == 0;
+        let mut execution = Self::execute_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            amount_in,
+            minimum_amount_out,
+            dex_configs,
+            oracle_max_deviation_bps,
+            split_parts,
+            direct,
+            log_level,
+            account_info_iter,
+        )?;
+        execution.referral_fee_bps = referral_fee_bps;
+        if tip_lamports > 0 {
+            Self::pay_tip(
+                program_id,
+                swap_accounts.user_transfer_authority,
+                tip_lamports,
+                account_info_iter,
+            )?;
+        }
+        Self::settle_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            amount_in,
+            minimum_amount_out,
+            dex_configs,
+            dex_accounts,
+            execution,
+            log_level,
+            account_info_iter,
+        )?;
+        Ok(())
+    }
 
-        let protocol_token = unpack_token_account(protocol_token_account, &token_program_id)?;
-        let destination_token = unpack_token_account(destination_info, &token_program_id)?;
-        if protocol_token.mint != destination_token.mint {
+    /// Checks the accounts of a swap, returning what the later phases need of them.
+    #[inline(never)]
+    fn validate_swap(
+        program_id: &Pubkey,
+        accounts: &SwapAccounts,
+    ) -> Result<ValidatedSwap, ProgramError> {
+        if accounts.protocol.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let protocol_data = accounts.protocol.data.borrow();
+        let protocol = OneSolState::view(&protocol_data)?;
+        if protocol.is_paused() {
+            return Err(OneSolError::Paused.into());
+        }
+        let protocol_token_key = protocol.token();
+        if *accounts.destination.key == protocol_token_key
+            || *accounts.source.key == protocol_token_key
+        {
+            return Err(OneSolError::IncorrectSwapAccount.into());
+        }
+
+        if *accounts.source.key == *accounts.destination.key {
+            return Err(OneSolError::InvalidInput.into());
+        }
+
+        let token_program_id = accounts.token_program.key;
+        let destination_mint = token_account_mint(accounts.destination, token_program_id)?;
+        // any other account than the protocol token account must be the destination vault
+        if *accounts.protocol_token.key != protocol_token_key
+            && *accounts.protocol_token.key
+                != Self::find_vault_address(program_id, accounts.protocol.key, &destination_mint).0
+        {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        if token_account_mint(accounts.protocol_token, token_program_id)? != destination_mint {
             return Err(OneSolError::InvalidInput.into());
         }
 
         // if *user_transfer_authority_info.key != source_info.delegate {
         //     return Err(OneSolError::InvalidOwner.into());
         // }
+        Ok(ValidatedSwap {
+            nonce: protocol.nonce(),
+            protocol_token_amount: token_account_amount(accounts.protocol_token)?,
+            destination_mint,
+            fee_numerator: protocol.fee_numerator(),
+            fee_denominator: protocol.fee_denominator(),
+            treasury: protocol.treasury(),
+            max_referral_fee_bps: protocol.max_referral_fee_bps(),
+        })
+    }
+
+    /// Swaps `amount_in` through the legs of `dex_configs` into the protocol token
+    /// account, or DESTINATION if `direct`, split on-chain in `split_parts` chunks, or
+    /// those of the global config when 0.
+    #[inline(never)]
+    #[allow(clippy::too_many_arguments)]
+    fn execute_swap<'i, 'b: 'i>(
+        program_id: &Pubkey,
+        accounts: &SwapAccounts<'i, 'b>,
+        validated: &ValidatedSwap,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        dex_configs: DexConfigs,
+        oracle_max_deviation_bps: Option<u16>,
+        split_parts: u8,
+        direct: bool,
+        log_level: LogLevel,
+        account_info_iter: &mut std::slice::Iter<'i, AccountInfo<'b>>,
+    ) -> Result<SwapExecution, ProgramError> {
+        let (destination, amount1) = if direct {
+            let amount = token_account_amount(accounts.destination)?;
+            (accounts.destination, amount)
+        } else {
+            (accounts.protocol_token, validated.protocol_token_amount)
+        };
+        let context = SwapContext {
+            program_id,
+            token_program: accounts.token_program,
+            user_transfer_authority: accounts.user_transfer_authority,
+            source: accounts.source,
+            destination,
+        };
+        let split_parts = match split_parts {
+            0 => Self::find_config(program_id, account_info_iter.as_slice())
+                .map_or(0, |config| config.split_parts),
+            split_parts => split_parts,
+        };
+        let mut amounts = Self::split_by_ratio(amount_in, dex_configs)?;
+        let mut swappers =
+            Self::load_swap_swappers(dex_configs, &amounts, account_info_iter, &context)?;
+        let minimum_amounts: ArrayVec<u64, MAX_LEGS> =
+            match Self::best_split(amount_in, split_parts, &swappers)? {
+                Some(split) => {
+                    verbose!(
+                        log_level,
+                        "best split: {:?}, quoted output: {:?}",
+                        split,
+                        Self::quote_split(&swappers, &split)
+                    );
+                    // legs the split gives nothing are skipped
+                    for (swapper, amount) in swappers.iter_mut().zip(split.iter()) {
+                        if *amount == 0 {
+                            *swapper = None;
+                        }
+                    }
+                    amounts = split;
+                    // the route's minimum applies to the sum of the legs only, their own
+                    // minimums bounding the amounts of their ratios
+                    amounts.iter().map(|_| 0).collect()
+                }
+                None => Self::leg_minimums(minimum_amount_out, dex_configs)?,
+            };
 
-        let mut swappers: Vec<TokenSwap> = vec![];
+        let oracle_minimum_amount_out = match oracle_max_deviation_bps {
+            Some(max_deviation_bps) => Self::oracle_minimum_amount_out(
+                program_id,
+                account_info_iter,
+                accounts.token_program.key,
+                accounts.source,
+                &validated.destination_mint,
+                amount_in,
+                max_deviation_bps,
+            )?,
+            None => 0,
+        };
 
-        for dex_config in dex_configs.iter() {
-            #[allow(unused_parens)]
-            if (dex_config.dex_type == 0) {
-                let dex_accounts = next_account_infos(account_info_iter, dex_config.account_size)?;
-                swappers.push(TokenSwap::new_spl_token_swap(
-                    token_program_info.clone(),
-                    user_transfer_authority_info.clone(),
-                    source_info.clone(),
-                    protocol_token_account.clone(),
-                    dex_accounts,
-                )?);
-            }
-        }
-
-        let dest_account1 =
-            spl_token::state::Account::unpack(&protocol_token_account.data.borrow())?;
-
-        let amount1 = dest_account1.amount;
-
-        // let (best, parts) = if swappers.len() > 1 {
-        //     let _parts = find_best_parts(amount_in, swappers.len() as u64);
-        //     msg!("best parts: {}", _parts);
-        //     sol_log_compute_units();
-        //     let _best = Self::get_expected_return_with_gas(amount_in, _parts, &swappers[..]);
-        //     sol_log_compute_units();
-        //     msg!("Best split is {:?}", _best);
-        //     (_best, _parts)
-        // } else {
-        //     (vec![1], 1)
-        // };
-
-        // let mut best_index: usize = 0;
-        for i in 0..swappers.len() {
-            let ratio = dex_configs[i].ratio as u64;
-            let token_swap_amount_in = amount_in * ratio;
-            let token_swap_minimum_amount_out = minimum_amount_out * ratio;
-            // best_index += 1;
-            if token_swap_amount_in <= 0 {
-                continue;
-            }
-            msg!(
+        let (legs, leg_end_amount) = Self::run_legs(
+            &swappers,
+            dex_configs,
+            &amounts,
+            &minimum_amounts,
+            destination,
+            amount1,
+            &[],
+            log_level,
+        )?;
+
+        // the balance after the last leg is the balance after all of them
+        let amount_out = leg_end_amount
+            .checked_sub(amount1)
+            .ok_or(OneSolError::InternalError)?;
+        Ok(SwapExecution {
+            amount_out,
+            fee: 0,
+            oracle_minimum_amount_out,
+            legs,
+            referral_fee_bps: 0,
+            direct,
+        })
+    }
+
+    /// Runs the legs of a route into `destination`, holding `start_amount` before the
+    /// first one, reporting the output of each leg along with the balance after the last.
+    /// `signers_seeds` sign for the transfer authority of the legs, if a program address.
+    #[allow(clippy::too_many_arguments)]
+    fn run_legs<S: Swapper>(
+        swappers: &[Option<S>],
+        dex_configs: DexConfigs,
+        amounts: &[u64],
+        minimum_amounts: &[u64],
+        destination: &AccountInfo,
+        start_amount: u64,
+        signers_seeds: &[&[&[u8]]],
+        log_level: LogLevel,
+    ) -> Result<(Vec<LegReport>, u64), ProgramError> {
+        let mut legs = Vec::with_capacity(swappers.len());
+        let mut leg_start_amount = start_amount;
+        for (i, (swapper, dex_config)) in swappers.iter().zip(dex_configs.iter()).enumerate() {
+            let swapper = match swapper {
+                Some(swapper) => swapper,
+                None => continue,
+            };
+            let token_swap_amount_in = amounts[i];
+            let token_swap_minimum_amount_out = minimum_amounts[i];
+            debug!(
+                log_level,
                 "swap onesolA -> onesolB using token-swap[{}], amount_in: {}, minimum_amount_out: {}",
                 i,
                 token_swap_amount_in,
                 token_swap_minimum_amount_out,
             );
-            swappers[i].invoke_swap(token_swap_amount_in, token_swap_minimum_amount_out)?;
+            let spot_amount_out = swapper
+                .pool_curve()
+                .and_then(|pool| pool.spot_amount_out(token_swap_amount_in));
+            Self::log_leg_compute_units(i, &dex_config, "before");
+            swapper
+                .invoke_swap_signed(
+                    token_swap_amount_in,
+                    token_swap_minimum_amount_out,
+                    signers_seeds,
+                )
+                .map_err(|e| Self::leg_error(log_level, i, &dex_config, e))?;
+            Self::log_leg_compute_units(i, &dex_config, "after");
+            let leg_end_amount = token_account_amount(destination)?;
+            let amount_out = leg_end_amount.saturating_sub(leg_start_amount);
+            // whether or not the dex bounds its output as asked
+            if amount_out < token_swap_minimum_amount_out {
+                if log_level.logs_errors() {
+                    msg!(
+                        "leg[{}] amount_out: {} < minimum_amount_out: {}",
+                        i,
+                        amount_out,
+                        token_swap_minimum_amount_out
+                    );
+                }
+                return Err(OneSolError::ExceededSlippage.into());
+            }
+            legs.push(LegReport {
+                dex_type: dex_config.dex_type,
+                amount_in: token_swap_amount_in,
+                amount_out,
+                price_impact_bps: spot_amount_out.map(|spot| price_impact_bps(spot, amount_out)),
+            });
+            leg_start_amount = leg_end_amount;
         }
+        Ok((legs, leg_start_amount))
+    }
 
-        let dest_account =
-            spl_token::state::Account::unpack(&protocol_token_account.data.borrow())?;
-        let result_amount = dest_account.amount - amount1;
-
-        // TODO 计算手续费
-        // msg!(
-        //     "onesol_destination amount: {}, should tranfer: {}",
-        //     dest_account.amount,
-        //     result_amount,
-        // );
-        if result_amount < minimum_amount_out {
-            return Err(OneSolError::ExceededSlippage.into());
+    /// Splits `amount_in` across the legs of a route as [find_best_split] does, returning
+    /// the amount of each leg. None, keeping the split of the route, unless `split_parts`
+    /// is set and at least two legs swap, all through pools quoted on-chain.
+    ///
+    /// [find_best_split]: Processor::find_best_split
+    fn best_split<S: Swapper>(
+        amount_in: u64,
+        split_parts: u8,
+        swappers: &[Option<S>],
+    ) -> Result<Option<ArrayVec<u64, MAX_LEGS>>, ProgramError> {
+        if split_parts == 0 {
+            return Ok(None);
         }
-        // Transfer OnesolB -> AliceB
-        msg!("transfer OneSolB -> AliceB");
-        sol_log_compute_units();
-        Self::token_transfer(
-            protocol_account.key,
-            token_program_info.clone(),
-            protocol_token_account.clone(),
-            destination_info.clone(),
-            protocol_authority.clone(),
-            protocol_info.nonce,
-            result_amount,
-        )
-        .unwrap();
+        let pools = match swappers
+            .iter()
+            .flatten()
+            .map(Swapper::pool_curve)
+            .collect::<Option<ArrayVec<PoolCurve, MAX_LEGS>>>()
+        {
+            Some(pools) if pools.len() > 1 => pools,
+            _ => return Ok(None),
+        };
+        let parts = Self::bounded_split_parts(split_parts, &pools);
+        if parts == 0 {
+            return Ok(None);
+        }
+        let gas = [0; MAX_LEGS];
+        let mut split =
+            Self::find_best_split(amount_in, parts, &pools, &gas[..pools.len()])?.into_iter();
+        Ok(Some(
+            swappers
+                .iter()
+                .map(|swapper| match swapper {
+                    Some(_) => split.next().unwrap_or(0),
+                    None => 0,
+                })
+                .collect(),
+        ))
+    }
 
-        Ok(())
+    /// Sums the quotes of the legs of a split, None if a leg can't be quoted.
+    fn quote_split<S: Swapper>(swappers: &[Option<S>], split: &[u64]) -> Option<u64> {
+        swappers
+            .iter()
+            .zip(split)
+            .try_fold(0u64, |total, (swapper, amount)| match swapper {
+                Some(swapper) => total.checked_add(swapper.quote(*amount)?),
+                None => Some(total),
+            })
     }
 
-    /// Calculates the authority id by generating a program address.
-    pub fn authority_id(
-        program_id: &Pubkey,
-        my_info: &Pubkey,
-        nonce: u8,
-    ) -> Result<Pubkey, OneSolError> {
-        Pubkey::create_program_address(&[&my_info.to_bytes()[..32], &[nonce]], program_id)
-            .or(Err(OneSolError::InvalidProgramAddress))
+    /// Returns `split_parts` lowered so quoting every part through every pool fits in
+    /// [SPLIT_COMPUTE_UNITS].
+    fn bounded_split_parts<C: Curve>(split_parts: u8, pools: &[C]) -> u64 {
+        let units_per_part: u64 = pools.iter().map(Curve::simulate_compute_units).sum();
+        (split_parts as u64).min(SPLIT_COMPUTE_UNITS / units_per_part.max(1))
     }
 
-    /// Issue a spl_token `Transfer` instruction.
-    pub fn token_transfer<'a>(
+    /// Splits `amount_in` across `pools` in `parts` chunks, each going to the pool whose
+    /// expected return, net of `gas`, it raises the most, and returns the amount of each
+    /// pool. The rounding remainder goes to the pool of the most chunks.
+    pub fn find_best_split<C: Curve>(
+        amount_in: u64,
+        parts: u64,
+        pools: &[C],
+        gas: &[u64],
+    ) -> Result<Vec<u64>, ProgramError> {
+        if pools.is_empty() {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let returns = Self::get_expected_return_with_gas(amount_in, parts, pools, gas)?;
+        let mut chunks = vec![0usize; pools.len()];
+        for _ in 0..parts {
+            // the first pool of the best marginal return
+            let mut best = (0, i128::MIN);
+            for (i, (returns, chunks)) in returns.iter().zip(chunks.iter()).enumerate() {
+                let marginal = returns[chunks + 1] as i128 - returns[*chunks] as i128;
+                if marginal > best.1 {
+                    best = (i, marginal);
+                }
+            }
+            chunks[best.0] += 1;
+        }
+        let mut amounts = chunks
+            .iter()
+            .map(|chunks| mul_div_floor(amount_in, *chunks as u64, parts))
+            .collect::<Result<Vec<_>, _>>()?;
+        // the floored amounts sum to at most amount_in
+        let remainder = amount_in - amounts.iter().sum::<u64>();
+        let most =
+            (0..chunks.len()).fold(0, |most, i| if chunks[i] > chunks[most] { i } else { most });
+        amounts[most] += remainder;
+        Ok(amounts)
+    }
+
+    /// Returns the expected output of each share of `amount_in` through each pool:
+    /// `returns[i][j]` is what `j` of `parts` parts of `amount_in` receive through
+    /// `pools[i]`, less the `gas[i]` DESTINATION tokens a leg through it costs, 0 for a
+    /// share the curve can't quote.
+    pub fn get_expected_return_with_gas<C: Curve>(
+        amount_in: u64,
+        parts: u64,
+        pools: &[C],
+        gas: &[u64],
+    ) -> Result<Vec<Vec<u64>>, ProgramError> {
+        if parts == 0 || pools.len() != gas.len() {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let shares = (1..=parts)
+            .map(|part| mul_div_floor(amount_in, part, parts))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(pools
+            .iter()
+            .zip(gas.iter())
+            .map(|(pool, gas)| {
+                let mut returns = Vec::with_capacity(shares.len() + 1);
+                returns.push(0);
+                returns.extend(
+                    shares
+                        .iter()
+                        .map(|share| pool.simulate_swap(*share).unwrap_or(0).saturating_sub(*gas)),
+                );
+                returns
+            })
+            .collect())
+    }
+
+    /// Tips `lamports` of `user_info` to the tip account of the global config.
+    fn pay_tip<'b>(
+        program_id: &Pubkey,
+        user_info: &AccountInfo<'b>,
+        lamports: u64,
+        account_info_iter: &mut std::slice::Iter<AccountInfo<'b>>,
+    ) -> ProgramResult {
+        let config_info = next_account_info(account_info_iter)?;
+        let tip_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let config = Self::load_config(program_id, config_info)?;
+        if config.tip_account == Pubkey::default() || *tip_info.key != config.tip_account {
+            return Err(OneSolError::InvalidTipAccount.into());
+        }
+        invoke(
+            &system_instruction::transfer(user_info.key, tip_info.key, lamports),
+            &[
+                user_info.clone(),
+                tip_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+        event::emit(&TipPaidEvent {
+            user: *user_info.key,
+            tip_account: *tip_info.key,
+            lamports,
+        });
+        Ok(())
+    }
+
+    /// Checks the output of an executed swap, records it in the trailing accounts and
+    /// pays it out to the user, net of the protocol fee, returning what the user got.
+    #[inline(never)]
+    #[allow(clippy::too_many_arguments)]
+    fn settle_swap<'b>(
+        program_id: &Pubkey,
+        accounts: &SwapAccounts<'_, 'b>,
+        validated: &ValidatedSwap,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        dex_configs: DexConfigs,
+        dex_accounts: &[AccountInfo],
+        execution: SwapExecution,
+        log_level: LogLevel,
+        account_info_iter: &mut std::slice::Iter<AccountInfo<'b>>,
+    ) -> Result<u64, ProgramError> {
+        let protocol_fee = validated.protocol_fee(execution.amount_out)?;
+        let amount_out = execution
+            .amount_out
+            .checked_sub(protocol_fee)
+            .ok_or(OneSolError::ConversionFailure)?;
+        let referral_fee = validated.referral_fee(amount_out, execution.referral_fee_bps)?;
+        let result_amount = amount_out
+            .checked_sub(referral_fee)
+            .ok_or(OneSolError::ConversionFailure)?;
+        let fee = execution.fee + protocol_fee + referral_fee;
+        if result_amount < minimum_amount_out {
+            return Err(OneSolError::ExceededSlippage.into());
+        }
+        // the fee takes nothing off the price the legs executed at
+        if execution.amount_out < execution.oracle_minimum_amount_out {
+            return Err(OneSolError::OraclePriceDeviation.into());
+        }
+        let source_mint = token_account_mint(accounts.source, accounts.token_program.key)?;
+        Self::check_pair_gate(
+            program_id,
+            accounts.user_transfer_authority.key,
+            &source_mint,
+            &validated.destination_mint,
+            account_info_iter,
+        )?;
+        // the referrer, if paid, ahead of the optional accounts
+        let referrer_info = if execution.referral_fee_bps > 0 {
+            let referrer_info = next_account_info(account_info_iter)?;
+            let referrer = unpack_token_account(referrer_info, accounts.token_program.key)?;
+            if referrer.mint != validated.destination_mint {
+                return Err(OneSolError::InvalidInput.into());
+            }
+            Some(referrer_info)
+        } else {
+            None
+        };
+        let mut treasury_info = None;
+        let mut mint_info = None;
+        while let Some(trailing_info) = account_info_iter.next() {
+            if *trailing_info.key == validated.treasury {
+                treasury_info = Some(trailing_info);
+            } else if *trailing_info.key == validated.destination_mint {
+                mint_info = Some(trailing_info);
+            } else if trailing_info.data_len() == MintStats::LEN {
+                Self::record_mint_stats(
+                    program_id,
+                    trailing_info,
+                    &source_mint,
+                    &validated.destination_mint,
+                    amount_in,
+                    result_amount,
+                    fee,
+                )?;
+            } else if trailing_info.data_len() == SwapReceipt::LEN {
+                Self::write_swap_receipt(
+                    program_id,
+                    trailing_info,
+                    &SwapReceipt {
+                        version: 1,
+                        user: *accounts.user_transfer_authority.key,
+                        source_mint,
+                        destination_mint: validated.destination_mint,
+                        slot: Clock::get()?.slot,
+                        route_hash: Self::route_hash(dex_configs, dex_accounts).to_bytes(),
+                        amount_in,
+                        amount_out: result_amount,
+                        executed_price: mul_div_floor(result_amount, SWAP_PRICE_SCALE, amount_in)?,
+                        fee,
+                    },
+                )?;
+            } else if trailing_info.data_len() == GlobalConfig::LEN {
+                // the config, already read for the split of the legs
+            } else if trailing_info.data_len() == PairRewards::LEN {
+                let points_info = next_account_info(account_info_iter)?;
+                Self::accrue_points(
+                    program_id,
+                    trailing_info,
+                    points_info,
+                    accounts.user_transfer_authority.key,
+                    &source_mint,
+                    &validated.destination_mint,
+                    amount_in,
+                )?;
+            } else {
+                Self::record_swap_price(
+                    program_id,
+                    trailing_info,
+                    &source_mint,
+                    &validated.destination_mint,
+                    amount_in,
+                    result_amount,
+                )?;
+            }
+        }
+        // Transfer OnesolB -> AliceB
+        if !execution.direct {
+            debug!(log_level, "transfer OneSolB -> AliceB");
+        }
+        Self::log_settle_compute_units(log_level);
+        let mut payout = Payout::new(
+            accounts.protocol.key,
+            validated.nonce,
+            accounts.token_program,
+            accounts.protocol_token,
+            accounts.protocol_authority,
+        );
+        // the DESTINATION mint, when passed, checks the transfers and tells their fee
+        if let Some(mint_info) = mint_info {
+            let decimals = unpack_mint(mint_info, accounts.token_program.key)?.decimals;
+            if *mint_info.owner == token_2022::id() {
+                let transfer_fee = token_2022::transfer_fee(
+                    &mint_info.data.borrow(),
+                    mint_info.owner,
+                    Clock::get()?.epoch,
+                )?;
+                // the fee withheld from DESTINATION takes off what the user receives, already
+                // off the output of legs swapping into it
+                let amount_received = match transfer_fee {
+                    Some(transfer_fee) if !execution.direct => {
+                        result_amount.saturating_sub(transfer_fee.fee(result_amount)?)
+                    }
+                    _ => result_amount,
+                };
+                if amount_received < minimum_amount_out {
+                    return Err(OneSolError::ExceededSlippage.into());
+                }
+            }
+            payout = payout.with_mint(mint_info, decimals);
+        }
+        if protocol_fee > 0 {
+            let treasury_info = treasury_info.ok_or(OneSolError::InvalidTreasury)?;
+            payout.transfer(treasury_info, protocol_fee)?;
+            event::emit(&FeeCollectedEvent {
+                kind: FeeKind::Protocol as u8,
+                payer: *accounts.user_transfer_authority.key,
+                mint: validated.destination_mint,
+                amount: protocol_fee,
+                recipient: *treasury_info.key,
+            });
+        }
+        if let Some(referrer_info) = referrer_info.filter(|_| referral_fee > 0) {
+            payout.transfer(referrer_info, referral_fee)?;
+            event::emit(&FeeCollectedEvent {
+                kind: FeeKind::Referral as u8,
+                payer: *accounts.user_transfer_authority.key,
+                mint: validated.destination_mint,
+                amount: referral_fee,
+                recipient: *referrer_info.key,
+            });
+        }
+        if !execution.direct {
+            payout.transfer(accounts.destination, result_amount)?;
+        }
+
+        let legs_executed = execution.legs.len() as u8;
+        event::emit(&SwapEvent {
+            version: SWAP_EVENT_VERSION,
+            input_mint: source_mint,
+            output_mint: validated.destination_mint,
+            amount_in,
+            amount_out: result_amount,
+            fee,
+            protocol_fee,
+            user: *accounts.user_transfer_authority.key,
+            legs: execution.legs,
+        });
+        Self::set_return_data(&SwapResult {
+            amount_in_spent: amount_in,
+            amount_out: result_amount,
+            fee_paid: fee,
+            legs_executed,
+        });
+        Ok(result_amount)
+    }
+
+    /// Processes a [SwapExactOut](enum.Instruction.html).
+    ///
+    /// Quotes the SOURCE amount each leg needs to output its share of `amount_out`,
+    /// grossed up by the protocol fee, runs the legs on those amounts, then settles as a
+    /// swap of their sum bounded by `amount_out`.
+    pub fn process_swap_exact_out(
+        program_id: &Pubkey,
+        data: &SwapExactOut,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if data.amount_out < 1 || data.maximum_amount_in < 1 {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_accounts = SwapAccounts::parse(account_info_iter)?;
+        let validated = Self::validate_swap(program_id, &swap_accounts)?;
+        let dex_accounts = account_info_iter.as_slice();
+        let context = SwapContext {
+            program_id,
+            token_program: swap_accounts.token_program,
+            user_transfer_authority: swap_accounts.user_transfer_authority,
+            source: swap_accounts.source,
+            destination: swap_accounts.protocol_token,
+        };
+        let gross_amount_out = validated.gross_amount_out(data.amount_out)?;
+        let amounts_out = Self::split_by_ratio(gross_amount_out, data.dex_configs)?;
+        let swappers =
+            Self::load_swappers(data.dex_configs, &amounts_out, account_info_iter, &context)?;
+        let amounts = Self::quote_amounts_in(&swappers, &amounts_out, data.maximum_amount_in)?;
+        let amount_in: u64 = amounts.iter().sum();
+        verbose!(
+            log_level,
+            "exact out {} for amounts in {:?}",
+            gross_amount_out,
+            amounts
+        );
+
+        let (legs, leg_end_amount) = Self::run_legs(
+            &swappers,
+            data.dex_configs,
+            &amounts,
+            &amounts_out,
+            swap_accounts.protocol_token,
+            validated.protocol_token_amount,
+            &[],
+            log_level,
+        )?;
+        let execution = SwapExecution {
+            amount_out: leg_end_amount
+                .checked_sub(validated.protocol_token_amount)
+                .ok_or(OneSolError::InternalError)?,
+            fee: 0,
+            oracle_minimum_amount_out: 0,
+            legs,
+            referral_fee_bps: 0,
+            direct: false,
+        };
+        Self::settle_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            amount_in,
+            data.amount_out,
+            data.dex_configs,
+            dex_accounts,
+            execution,
+            log_level,
+            account_info_iter,
+        )?;
+        Ok(())
+    }
+
+    /// Processes a [Quote](enum.Instruction.html).
+    pub fn process_quote(data: &Quote, accounts: &[AccountInfo]) -> ProgramResult {
+        if data.amount_in < 1 {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let amounts = Self::split_by_ratio(data.amount_in, data.dex_configs)?;
+        let mut result = QuoteResult {
+            amount_out: 0,
+            legs_quoted: amounts.len() as u8,
+            leg_amounts_out: [0; MAX_LEGS],
+        };
+        for (i, (dex_config, amount)) in data.dex_configs.iter().zip(amounts).enumerate() {
+            let dex_accounts = next_account_infos(account_info_iter, dex_config.account_size)?;
+            if amount == 0 {
+                continue;
+            }
+            let amount_out = Self::quote_leg(&dex_config, dex_accounts, amount)?;
+            result.leg_amounts_out[i] = amount_out;
+            result.amount_out = result
+                .amount_out
+                .checked_add(amount_out)
+                .ok_or(OneSolError::ConversionFailure)?;
+        }
+        Self::set_return_data(&result);
+        Ok(())
+    }
+
+    /// Quotes the DESTINATION amount a swap of `amount_in` through the leg of
+    /// `dex_config` outputs, from the reserves and curve of its pool.
+    fn quote_leg(
+        dex_config: &DexConfig,
+        dex_accounts: &[AccountInfo],
+        amount_in: u64,
+    ) -> Result<u64, ProgramError> {
+        let pool_curve =
+            swappers::Adapter::from_accounts(dex_config, dex_accounts)?.pool_curve()?;
+        Ok(pool_curve
+            .simulate_swap(amount_in)
+            .ok_or(OneSolError::QuoteUnavailable)?)
+    }
+
+    /// Quotes the least SOURCE amount each leg needs to output its amount of
+    /// `amounts_out`, all of them within `maximum_amount_in`.
+    fn quote_amounts_in<S: Swapper>(
+        swappers: &[Option<S>],
+        amounts_out: &[u64],
+        maximum_amount_in: u64,
+    ) -> Result<ArrayVec<u64, MAX_LEGS>, ProgramError> {
+        let mut amounts = ArrayVec::new();
+        let mut remaining = maximum_amount_in;
+        for (swapper, amount_out) in swappers.iter().zip(amounts_out) {
+            let amount_in = match swapper {
+                Some(swapper) => swapper.quote_exact_out(*amount_out, remaining)?,
+                None => 0,
+            };
+            remaining -= amount_in;
+            amounts.push(amount_in);
+        }
+        Ok(amounts)
+    }
+
+    /// Processes an [RelayedSwap](enum.Instruction.html).
+    ///
+    /// Runs the phases of a swap, reimbursing the relayer out of the output before
+    /// settling the rest.
+    pub fn process_relayed_swap(
+        program_id: &Pubkey,
+        data: &RelayedSwap,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if data.amount_in < 1 {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_accounts = SwapAccounts::parse(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let relayer_token_info = next_account_info(account_info_iter)?;
+
+        let config = Self::load_config(program_id, config_info)?;
+        let validated = Self::validate_swap(program_id, &swap_accounts)?;
+        let relayer_token =
+            unpack_token_account(relayer_token_info, swap_accounts.token_program.key)?;
+        if relayer_token.mint != validated.destination_mint {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let dex_accounts = account_info_iter.as_slice();
+        let mut execution = Self::execute_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            data.amount_in,
+            data.minimum_amount_out,
+            data.dex_configs,
+            None,
+            0,
+            false,
+            log_level,
+            account_info_iter,
+        )?;
+        let fee = config
+            .relayer_fee(execution.amount_out, data.fee_bps, data.fee_flat)
+            .ok_or(OneSolError::RelayerFeeTooHigh)?;
+        execution.amount_out = execution
+            .amount_out
+            .checked_sub(fee)
+            .ok_or(OneSolError::ExceededSlippage)?;
+        execution.fee = fee;
+        verbose!(log_level, "reimburse relayer {}", fee);
+        Payout::new(
+            swap_accounts.protocol.key,
+            validated.nonce,
+            swap_accounts.token_program,
+            swap_accounts.protocol_token,
+            swap_accounts.protocol_authority,
+        )
+        .transfer(relayer_token_info, fee)?;
+        if fee > 0 {
+            event::emit(&FeeCollectedEvent {
+                kind: FeeKind::Relayer as u8,
+                payer: *swap_accounts.user_transfer_authority.key,
+                mint: validated.destination_mint,
+                amount: fee,
+                recipient: *relayer_token_info.key,
+            });
+        }
+        Self::settle_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            data.amount_in,
+            data.minimum_amount_out,
+            data.dex_configs,
+            dex_accounts,
+            execution,
+            log_level,
+            account_info_iter,
+        )?;
+        Ok(())
+    }
+
+    /// Processes an [BatchSwap](enum.Instruction.html).
+    ///
+    /// Runs the phases of a swap for each user of the batch, over the shared dex accounts.
+    pub fn process_batch_swap(
+        program_id: &Pubkey,
+        data: &BatchSwap,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let protocol_info = next_account_info(account_info_iter)?;
+        let protocol_authority_info = next_account_info(account_info_iter)?;
+        let protocol_token_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let dex_accounts_len = data
+            .dex_configs
+            .iter()
+            .map(|dex_config| dex_config.account_size)
+            .sum();
+        let dex_accounts = next_account_infos(account_info_iter, dex_accounts_len)?;
+
+        for (i, entry) in data.swaps.iter().enumerate() {
+            if entry.amount_in < 1 {
+                return Err(OneSolError::InvalidInput.into());
+            }
+            verbose!(log_level, "batch swap {}", i);
+            let swap_accounts = SwapAccounts {
+                protocol: protocol_info,
+                protocol_authority: protocol_authority_info,
+                user_transfer_authority: next_account_info(account_info_iter)?,
+                protocol_token: protocol_token_info,
+                source: next_account_info(account_info_iter)?,
+                destination: next_account_info(account_info_iter)?,
+                token_program: token_program_info,
+            };
+            let trailing_accounts =
+                next_account_infos(account_info_iter, entry.trailing_accounts as usize)?;
+            let validated = Self::validate_swap(program_id, &swap_accounts)?;
+            let execution = Self::execute_swap(
+                program_id,
+                &swap_accounts,
+                &validated,
+                entry.amount_in,
+                entry.minimum_amount_out,
+                data.dex_configs,
+                None,
+                0,
+                false,
+                log_level,
+                &mut dex_accounts.iter(),
+            )?;
+            Self::settle_swap(
+                program_id,
+                &swap_accounts,
+                &validated,
+                entry.amount_in,
+                entry.minimum_amount_out,
+                data.dex_configs,
+                dex_accounts,
+                execution,
+                log_level,
+                &mut trailing_accounts.iter(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Processes a [SwapBatch](enum.Instruction.html).
+    ///
+    /// Runs the phases of a swap for each entry in turn, through the dex accounts that
+    /// follow its token accounts.
+    pub fn process_swap_batch(
+        program_id: &Pubkey,
+        data: &SwapBatch,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let protocol_info = next_account_info(account_info_iter)?;
+        let protocol_authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        for (i, entry) in data.swaps.iter().enumerate() {
+            if entry.amount_in < 1 {
+                return Err(OneSolError::InvalidInput.into());
+            }
+            verbose!(log_level, "swap batch {}", i);
+            let swap_accounts = SwapAccounts {
+                protocol: protocol_info,
+                protocol_authority: protocol_authority_info,
+                user_transfer_authority: user_transfer_authority_info,
+                protocol_token: next_account_info(account_info_iter)?,
+                source: next_account_info(account_info_iter)?,
+                destination: next_account_info(account_info_iter)?,
+                token_program: token_program_info,
+            };
+            let dex_accounts_len = entry
+                .dex_configs
+                .iter()
+                .map(|dex_config| dex_config.account_size)
+                .sum();
+            let dex_accounts = next_account_infos(account_info_iter, dex_accounts_len)?;
+            let trailing_accounts =
+                next_account_infos(account_info_iter, entry.trailing_accounts as usize)?;
+            let validated = Self::validate_swap(program_id, &swap_accounts)?;
+            let direct = entry.dex_configs.len() == 1 && validated.fee_numerator == 0;
+            let execution = Self::execute_swap(
+                program_id,
+                &swap_accounts,
+                &validated,
+                entry.amount_in,
+                entry.minimum_amount_out,
+                entry.dex_configs,
+                None,
+                0,
+                direct,
+                log_level,
+                &mut dex_accounts.iter(),
+            )?;
+            Self::settle_swap(
+                program_id,
+                &swap_accounts,
+                &validated,
+                entry.amount_in,
+                entry.minimum_amount_out,
+                entry.dex_configs,
+                dex_accounts,
+                execution,
+                log_level,
+                &mut trailing_accounts.iter(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Processes an [SwapAndBridge](enum.Instruction.html).
+    ///
+    /// Runs the phases of a swap, then bridges what it paid out to the user.
+    pub fn process_swap_and_bridge(
+        program_id: &Pubkey,
+        data: &SwapAndBridge,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if data.amount_in < 1 {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_accounts = SwapAccounts::parse(account_info_iter)?;
+        let bridge_program_info = next_account_info(account_info_iter)?;
+        let bridge_authority_info = next_account_info(account_info_iter)?;
+        let bridge_infos = next_account_infos(account_info_iter, data.bridge_accounts as usize)?;
+        if *bridge_authority_info.key != bridge::authority_signer(bridge_program_info.key) {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        if !bridge_infos
+            .iter()
+            .any(|info| info.key == swap_accounts.destination.key)
+        {
+            return Err(OneSolError::IncorrectSwapAccount.into());
+        }
+
+        let validated = Self::validate_swap(program_id, &swap_accounts)?;
+        let dex_accounts = account_info_iter.as_slice();
+        let execution = Self::execute_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            data.amount_in,
+            data.minimum_amount_out,
+            data.dex_configs,
+            None,
+            0,
+            false,
+            log_level,
+            account_info_iter,
+        )?;
+        let amount_out = Self::settle_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            data.amount_in,
+            data.minimum_amount_out,
+            data.dex_configs,
+            dex_accounts,
+            execution,
+            log_level,
+            account_info_iter,
+        )?;
+        if data.transfer.relayer_fee > amount_out {
+            return Err(OneSolError::InvalidInput.into());
+        }
+
+        // the bridge pulls the output as the delegate of the user, never of the protocol
+        verbose!(
+            log_level,
+            "bridge {} to chain {}",
+            amount_out,
+            data.transfer.target_chain
+        );
+        let approve = spl_token::instruction::approve(
+            swap_accounts.token_program.key,
+            swap_accounts.destination.key,
+            bridge_authority_info.key,
+            swap_accounts.user_transfer_authority.key,
+            &[],
+            amount_out,
+        )?;
+        invoke(
+            &approve,
+            &[
+                swap_accounts.destination.clone(),
+                bridge_authority_info.clone(),
+                swap_accounts.user_transfer_authority.clone(),
+                swap_accounts.token_program.clone(),
+            ],
+        )?;
+        let transfer = Instruction {
+            program_id: *bridge_program_info.key,
+            accounts: bridge_infos
+                .iter()
+                .map(|info| AccountMeta {
+                    pubkey: *info.key,
+                    is_signer: info.is_signer,
+                    is_writable: info.is_writable,
+                })
+                .collect(),
+            data: bridge::transfer_data(&data.transfer, amount_out).to_vec(),
+        };
+        let mut transfer_account_infos = bridge_infos.to_vec();
+        transfer_account_infos.push(bridge_program_info.clone());
+        invoke(&transfer, &transfer_account_infos)?;
+
+        event::emit(&BridgeTransferEvent {
+            mint: validated.destination_mint,
+            amount: amount_out,
+            target_chain: data.transfer.target_chain,
+            target_address: data.transfer.target_address,
+            nonce: data.transfer.nonce,
+            user: *swap_accounts.user_transfer_authority.key,
+        });
+        Ok(())
+    }
+
+    /// Processes an [SwapAndDeposit](enum.Instruction.html).
+    ///
+    /// Runs the phases of a swap, then deposits what it paid out to the user into the
+    /// lending reserve.
+    pub fn process_swap_and_deposit(
+        program_id: &Pubkey,
+        data: &SwapAndDeposit,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if data.amount_in < 1 {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_accounts = SwapAccounts::parse(account_info_iter)?;
+        let lending_program_info = next_account_info(account_info_iter)?;
+        let lending_infos = next_account_infos(account_info_iter, data.lending_accounts as usize)?;
+        if lending_infos.len() <= lending::DEPOSIT_OBLIGATION_INDEX {
+            return Err(OneSolError::AccountCountMismatch.into());
+        }
+        // the deposit may only take the output of the swap
+        if lending_infos[lending::DEPOSIT_SOURCE_LIQUIDITY_INDEX].key
+            != swap_accounts.destination.key
+        {
+            return Err(OneSolError::IncorrectSwapAccount.into());
+        }
+
+        let validated = Self::validate_swap(program_id, &swap_accounts)?;
+        let dex_accounts = account_info_iter.as_slice();
+        let execution = Self::execute_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            data.amount_in,
+            data.minimum_amount_out,
+            data.dex_configs,
+            None,
+            0,
+            false,
+            log_level,
+            account_info_iter,
+        )?;
+        let amount_out = Self::settle_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            data.amount_in,
+            data.minimum_amount_out,
+            data.dex_configs,
+            dex_accounts,
+            execution,
+            log_level,
+            account_info_iter,
+        )?;
+
+        verbose!(log_level, "deposit {} into the lending reserve", amount_out);
+        let deposit = Instruction {
+            program_id: *lending_program_info.key,
+            accounts: lending_infos
+                .iter()
+                .map(|info| AccountMeta {
+                    pubkey: *info.key,
+                    is_signer: info.is_signer,
+                    is_writable: info.is_writable,
+                })
+                .collect(),
+            data: lending::deposit_data(amount_out).to_vec(),
+        };
+        let mut deposit_account_infos = lending_infos.to_vec();
+        deposit_account_infos.push(lending_program_info.clone());
+        invoke(&deposit, &deposit_account_infos)?;
+
+        event::emit(&LendingDepositEvent {
+            mint: validated.destination_mint,
+            amount: amount_out,
+            lending_program: *lending_program_info.key,
+            obligation: *lending_infos[lending::DEPOSIT_OBLIGATION_INDEX].key,
+            user: *swap_accounts.user_transfer_authority.key,
+        });
+        Ok(())
+    }
+
+    /// Processes an [SwapAndStake](enum.Instruction.html).
+    ///
+    /// Runs the phases of a swap into wrapped SOL, then unwraps what it paid out to the
+    /// user and deposits it into the stake pool.
+    pub fn process_swap_and_stake(
+        program_id: &Pubkey,
+        data: &SwapAndStake,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if data.amount_in < 1 {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_accounts = SwapAccounts::parse(account_info_iter)?;
+        let stake_pool_program_info = next_account_info(account_info_iter)?;
+        let stake_pool_infos =
+            next_account_infos(account_info_iter, data.stake_pool_accounts as usize)?;
+        if stake_pool_infos.len() <= liquid_staking::DEPOSIT_POOL_TOKENS_TO_INDEX {
+            return Err(OneSolError::AccountCountMismatch.into());
+        }
+        // the deposit may only spend the unwrapped output of the swap
+        if stake_pool_infos[liquid_staking::DEPOSIT_LAMPORTS_FROM_INDEX].key
+            != swap_accounts.user_transfer_authority.key
+        {
+            return Err(OneSolError::IncorrectSwapAccount.into());
+        }
+
+        let validated = Self::validate_swap(program_id, &swap_accounts)?;
+        if validated.destination_mint != spl_token::native_mint::id() {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let dex_accounts = account_info_iter.as_slice();
+        let execution = Self::execute_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            data.amount_in,
+            data.minimum_amount_out,
+            data.dex_configs,
+            None,
+            0,
+            false,
+            log_level,
+            account_info_iter,
+        )?;
+        let lamports = Self::settle_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            data.amount_in,
+            data.minimum_amount_out,
+            data.dex_configs,
+            dex_accounts,
+            execution,
+            log_level,
+            account_info_iter,
+        )?;
+
+        verbose!(log_level, "unwrap and stake {} lamports", lamports);
+        let close = spl_token::instruction::close_account(
+            swap_accounts.token_program.key,
+            swap_accounts.destination.key,
+            swap_accounts.user_transfer_authority.key,
+            swap_accounts.user_transfer_authority.key,
+            &[],
+        )?;
+        invoke(
+            &close,
+            &[
+                swap_accounts.destination.clone(),
+                swap_accounts.user_transfer_authority.clone(),
+                swap_accounts.token_program.clone(),
+            ],
+        )?;
+        let pool_tokens_info = &stake_pool_infos[liquid_staking::DEPOSIT_POOL_TOKENS_TO_INDEX];
+        let pool_tokens_before = token_account_amount(pool_tokens_info)?;
+        let deposit = Instruction {
+            program_id: *stake_pool_program_info.key,
+            accounts: stake_pool_infos
+                .iter()
+                .map(|info| AccountMeta {
+                    pubkey: *info.key,
+                    is_signer: info.is_signer,
+                    is_writable: info.is_writable,
+                })
+                .collect(),
+            data: liquid_staking::deposit_sol_data(lamports).to_vec(),
+        };
+        let mut deposit_account_infos = stake_pool_infos.to_vec();
+        deposit_account_infos.push(stake_pool_program_info.clone());
+        invoke(&deposit, &deposit_account_infos)?;
+        let pool_tokens =
+            token_account_amount(pool_tokens_info)?.saturating_sub(pool_tokens_before);
+
+        event::emit(&StakePoolDepositEvent {
+            stake_pool: *stake_pool_infos[liquid_staking::DEPOSIT_STAKE_POOL_INDEX].key,
+            lamports,
+            pool_tokens,
+            user: *swap_accounts.user_transfer_authority.key,
+        });
+        Ok(())
+    }
+
+    /// Processes an [SwapTwoSteps](enum.Instruction.html).
+    ///
+    /// Runs the first step into the intermediate token account, then swaps what it
+    /// received through the second step, settling the output as `Swap` does.
+    pub fn process_swap_two_steps(
+        program_id: &Pubkey,
+        data: &SwapTwoSteps,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if data.amount_in < 1 {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_accounts = SwapAccounts::parse(account_info_iter)?;
+        let intermediate_info = next_account_info(account_info_iter)?;
+
+        let validated = Self::validate_swap(program_id, &swap_accounts)?;
+        // the authority signs the second step, spending the intermediate tokens
+        if *swap_accounts.protocol_authority.key
+            != Self::authority_id(program_id, swap_accounts.protocol.key, validated.nonce)?
+        {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        let token_program_id = swap_accounts.token_program.key;
+        let source = unpack_token_account(swap_accounts.source, token_program_id)?;
+        let intermediate = unpack_token_account(intermediate_info, token_program_id)?;
+        if intermediate.owner != *swap_accounts.protocol_authority.key {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        if intermediate.mint == source.mint || intermediate.mint == validated.destination_mint {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let dex_accounts = account_info_iter.as_slice();
+        let (first_step, second_step) = data.steps();
+        // only the output of the second step is bound
+        let minimum_amounts = [0; MAX_LEGS];
+
+        let first_context = SwapContext {
+            program_id,
+            token_program: swap_accounts.token_program,
+            user_transfer_authority: swap_accounts.user_transfer_authority,
+            source: swap_accounts.source,
+            destination: intermediate_info,
+        };
+        let amounts = Self::split_by_ratio(data.amount_in, first_step)?;
+        let swappers =
+            Self::load_swappers(first_step, &amounts, account_info_iter, &first_context)?;
+        let (mut legs, intermediate_end_amount) = Self::run_legs(
+            &swappers,
+            first_step,
+            &amounts,
+            &minimum_amounts,
+            intermediate_info,
+            intermediate.amount,
+            &[],
+            log_level,
+        )?;
+        let intermediate_amount = intermediate_end_amount
+            .checked_sub(intermediate.amount)
+            .ok_or(OneSolError::InternalError)?;
+        verbose!(
+            log_level,
+            "first step output: {}, swapping it through the second step",
+            intermediate_amount
+        );
+
+        let second_context = SwapContext {
+            program_id,
+            token_program: swap_accounts.token_program,
+            user_transfer_authority: swap_accounts.protocol_authority,
+            source: intermediate_info,
+            destination: swap_accounts.protocol_token,
+        };
+        let amounts = Self::split_by_ratio(intermediate_amount, second_step)?;
+        let swappers =
+            Self::load_swappers(second_step, &amounts, account_info_iter, &second_context)?;
+        let protocol_bytes = swap_accounts.protocol.key.to_bytes();
+        let authority_signature_seeds = [&protocol_bytes[..32], &[validated.nonce]];
+        let (second_legs, end_amount) = Self::run_legs(
+            &swappers,
+            second_step,
+            &amounts,
+            &minimum_amounts,
+            swap_accounts.protocol_token,
+            validated.protocol_token_amount,
+            &[&authority_signature_seeds[..]],
+            log_level,
+        )?;
+        legs.extend(second_legs);
+        let amount_out = end_amount
+            .checked_sub(validated.protocol_token_amount)
+            .ok_or(OneSolError::InternalError)?;
+
+        Self::settle_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            data.amount_in,
+            data.minimum_amount_out,
+            data.dex_configs,
+            dex_accounts,
+            SwapExecution {
+                amount_out,
+                fee: 0,
+                oracle_minimum_amount_out: 0,
+                legs,
+                referral_fee_bps: 0,
+                direct: false,
+            },
+            log_level,
+            account_info_iter,
+        )?;
+        Ok(())
+    }
+
+    /// Processes an [CommitSwap](enum.Instruction.html).
+    pub fn process_commit_swap(
+        program_id: &Pubkey,
+        data: &CommitSwap,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let user_info = next_account_info(account_info_iter)?;
+        let commitment_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !user_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let (commitment_key, bump_seed) =
+            Self::find_swap_commitment_address(program_id, user_info.key, &data.commitment);
+        if *commitment_info.key != commitment_key {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+
+        Self::create_program_account(
+            program_id,
+            user_info,
+            commitment_info,
+            system_program_info,
+            SwapCommitment::LEN,
+            &[
+                b"commit",
+                &user_info.key.to_bytes(),
+                &data.commitment,
+                &[bump_seed],
+            ],
+        )?;
+        let commitment = SwapCommitment {
+            version: 1,
+            bump_seed,
+            user: *user_info.key,
+            commitment: data.commitment,
+            slot: Clock::get()?.slot,
+        };
+        SwapCommitment::pack(commitment, &mut commitment_info.data.borrow_mut())
+    }
+
+    /// Processes an [RevealSwap](enum.Instruction.html).
+    ///
+    /// Checks the swap against its commitment, closes the commitment, then runs the phases
+    /// of a swap.
+    pub fn process_reveal_swap(
+        program_id: &Pubkey,
+        data: &RevealSwap,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if data.amount_in < 1 {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_accounts = SwapAccounts::parse(account_info_iter)?;
+        let commitment_info = next_account_info(account_info_iter)?;
+        let dex_accounts = account_info_iter.as_slice();
+
+        if commitment_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let commitment = SwapCommitment::unpack(&commitment_info.data.borrow())?;
+        let user_info = swap_accounts.user_transfer_authority;
+        if commitment.user != *user_info.key {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        if Clock::get()?.slot <= commitment.slot {
+            return Err(OneSolError::CommitmentTooRecent.into());
+        }
+        let route_hash = Self::route_hash(data.dex_configs, dex_accounts);
+        let revealed = swap_commitment(
+            &data.salt,
+            user_info.key,
+            data.amount_in,
+            data.minimum_amount_out,
+            &route_hash,
+        );
+        if revealed.to_bytes() != commitment.commitment {
+            return Err(OneSolError::CommitmentMismatch.into());
+        }
+
+        let commitment_lamports = commitment_info.lamports();
+        **commitment_info.lamports.borrow_mut() = 0;
+        **user_info.lamports.borrow_mut() = user_info
+            .lamports()
+            .checked_add(commitment_lamports)
+            .ok_or(OneSolError::ConversionFailure)?;
+        commitment_info.data.borrow_mut().fill(0);
+
+        let validated = Self::validate_swap(program_id, &swap_accounts)?;
+        let execution = Self::execute_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            data.amount_in,
+            data.minimum_amount_out,
+            data.dex_configs,
+            None,
+            0,
+            false,
+            log_level,
+            account_info_iter,
+        )?;
+        Self::settle_swap(
+            program_id,
+            &swap_accounts,
+            &validated,
+            data.amount_in,
+            data.minimum_amount_out,
+            data.dex_configs,
+            dex_accounts,
+            execution,
+            log_level,
+            account_info_iter,
+        )?;
+        Ok(())
+    }
+
+    /// Processes an [FlashSwap](enum.Instruction.html).
+    pub fn process_flash_swap(
+        program_id: &Pubkey,
+        data: &FlashSwap,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if data.amount_in < 1 {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let protocol_account = next_account_info(account_info_iter)?;
+        let protocol_authority = next_account_info(account_info_iter)?;
+        let protocol_token_account = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let callback_program_info = next_account_info(account_info_iter)?;
+        let callback_infos =
+            next_account_infos(account_info_iter, data.callback_accounts as usize)?;
+
+        if protocol_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let (nonce, protocol_token_key) = {
+            let protocol_data = protocol_account.data.borrow();
+            let protocol = OneSolState::view(&protocol_data)?;
+            if protocol.is_paused() {
+                return Err(OneSolError::Paused.into());
+            }
+            (protocol.nonce(), protocol.token())
+        };
+        if *protocol_token_account.key != protocol_token_key
+            || *vault_info.key == protocol_token_key
+            || *destination_info.key == protocol_token_key
+        {
+            return Err(OneSolError::IncorrectSwapAccount.into());
+        }
+        let token_program_id = *token_program_info.key;
+        let protocol_token = unpack_token_account(protocol_token_account, &token_program_id)?;
+        let vault = unpack_token_account(vault_info, &token_program_id)?;
+        let destination = unpack_token_account(destination_info, &token_program_id)?;
+        if vault.owner != *protocol_authority.key {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        if destination.mint != protocol_token.mint || vault.mint == protocol_token.mint {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let context = SwapContext {
+            program_id,
+            token_program: token_program_info,
+            user_transfer_authority: protocol_authority,
+            source: vault_info,
+            destination: protocol_token_account,
+        };
+        let amounts = Self::split_by_ratio(data.amount_in, data.dex_configs)?;
+        let swappers =
+            Self::load_swappers(data.dex_configs, &amounts, account_info_iter, &context)?;
+        Self::check_pair_gate(
+            program_id,
+            &destination.owner,
+            &vault.mint,
+            &protocol_token.mint,
+            account_info_iter,
+        )?;
+        let fee = flash_fee(data.amount_in).ok_or(OneSolError::ConversionFailure)?;
+        let fee =
+            Self::stake_discounted_fee(program_id, fee, &destination.owner, account_info_iter)?;
+        let repaid_amount = vault
+            .amount
+            .checked_add(fee)
+            .ok_or(OneSolError::ConversionFailure)?;
+        let protocol_bytes = protocol_account.key.to_bytes();
+        let authority_signature_seeds = [&protocol_bytes[..32], &[nonce]];
+        let signers = &[&authority_signature_seeds[..]];
+        for (i, (swapper, dex_config)) in swappers.iter().zip(data.dex_configs.iter()).enumerate() {
+            let swapper = match swapper {
+                Some(swapper) => swapper,
+                None => continue,
+            };
+            verbose!(
+                log_level,
+                "flash swap using dex[{}], amount_in: {}",
+                i,
+                amounts[i]
+            );
+            Self::log_leg_compute_units(i, &dex_config, "before");
+            swapper
+                .invoke_swap_signed(amounts[i], 0, signers)
+                .map_err(|e| Self::leg_error(log_level, i, &dex_config, e))?;
+            Self::log_leg_compute_units(i, &dex_config, "after");
+        }
+        let amount_out = token_account_amount(protocol_token_account)?
+            .checked_sub(protocol_token.amount)
+            .ok_or(OneSolError::InternalError)?;
+        if amount_out < data.minimum_amount_out {
+            return Err(OneSolError::ExceededSlippage.into());
+        }
+        Payout::new(
+            protocol_account.key,
+            nonce,
+            token_program_info,
+            protocol_token_account,
+            protocol_authority,
+        )
+        .transfer(destination_info, amount_out)?;
+
+        // the callback runs unsigned: the protocol authority signs nothing past this point
+        verbose!(log_level, "flash swap callback, repaying {}", repaid_amount);
+        let callback = Instruction {
+            program_id: *callback_program_info.key,
+            accounts: callback_infos
+                .iter()
+                .map(|info| AccountMeta {
+                    pubkey: *info.key,
+                    is_signer: info.is_signer,
+                    is_writable: info.is_writable,
+                })
+                .collect(),
+            data: data.callback_data.to_vec(),
+        };
+        let mut callback_account_infos = callback_infos.to_vec();
+        callback_account_infos.push(callback_program_info.clone());
+        invoke(&callback, &callback_account_infos)?;
+
+        if token_account_amount(vault_info)? < repaid_amount {
+            return Err(OneSolError::FlashSwapNotRepaid.into());
+        }
+        event::emit(&FeeCollectedEvent {
+            kind: FeeKind::FlashSwap as u8,
+            payer: destination.owner,
+            mint: vault.mint,
+            amount: fee,
+            recipient: *vault_info.key,
+        });
+        Self::set_return_data(&SwapResult {
+            amount_in_spent: data.amount_in,
+            amount_out,
+            fee_paid: fee,
+            legs_executed: amounts.iter().filter(|amount| **amount > 0).count() as u8,
+        });
+        Ok(())
+    }
+
+    /// Processes an [FlashBorrow](enum.Instruction.html).
+    pub fn process_flash_borrow(
+        program_id: &Pubkey,
+        data: &FlashBorrow,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if data.amount < 1 {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let protocol_account = next_account_info(account_info_iter)?;
+        let protocol_authority = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let instructions_info = next_account_info(account_info_iter)?;
+
+        if protocol_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let nonce = OneSolState::view(&protocol_account.data.borrow())?.nonce();
+        let vault = unpack_token_account(vault_info, token_program_info.key)?;
+        if vault.owner != *protocol_authority.key {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        if *instructions_info.key != sysvar::instructions::id() {
+            return Err(ProgramError::UnsupportedSysvar);
+        }
+        Self::check_flash_repay(program_id, instructions_info, vault_info.key, data.amount)?;
+
+        Payout::new(
+            protocol_account.key,
+            nonce,
+            token_program_info,
+            vault_info,
+            protocol_authority,
+        )
+        .transfer(destination_info, data.amount)
+    }
+
+    /// Checks, in the Instructions sysvar `instructions_info`, that the current instruction
+    /// is the top-level flash borrow of `amount` from `vault`, followed by the repayment of
+    /// `amount` into `vault` before any other borrow from it, so that each repayment
+    /// settles a single borrow.
+    fn check_flash_repay(
+        program_id: &Pubkey,
+        instructions_info: &AccountInfo,
+        vault: &Pubkey,
+        amount: u64,
+    ) -> ProgramResult {
+        if instructions_info.data_len() < 2 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let current_index =
+            sysvar::instructions::load_current_index_checked(instructions_info)? as usize;
+        // the sysvar lists top-level instructions only: a CPI would find its caller here
+        let current =
+            sysvar::instructions::load_instruction_at_checked(current_index, instructions_info)
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+        let is_this_borrow = current.program_id == *program_id
+            && current.accounts.get(2).map(|meta| &meta.pubkey) == Some(vault)
+            && matches!(
+                OneSolInstruction::unpack(&current.data),
+                Ok(OneSolInstruction::FlashBorrow(borrow)) if borrow.amount == amount
+            );
+        if !is_this_borrow {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let mut index = current_index + 1;
+        while let Ok(instruction) =
+            sysvar::instructions::load_instruction_at_checked(index, instructions_info)
+        {
+            index += 1;
+            if instruction.program_id != *program_id {
+                continue;
+            }
+            match OneSolInstruction::unpack(&instruction.data) {
+                Ok(OneSolInstruction::FlashRepay(repay))
+                    if instruction.accounts.first().map(|meta| &meta.pubkey) == Some(vault) =>
+                {
+                    return if repay.amount == amount {
+                        Ok(())
+                    } else {
+                        Err(OneSolError::FlashLoanNotRepaid.into())
+                    };
+                }
+                Ok(OneSolInstruction::FlashBorrow(_))
+                    if instruction.accounts.get(2).map(|meta| &meta.pubkey) == Some(vault) =>
+                {
+                    return Err(OneSolError::FlashLoanNotRepaid.into());
+                }
+                _ => {}
+            }
+        }
+        Err(OneSolError::FlashLoanNotRepaid.into())
+    }
+
+    /// Processes an [FlashRepay](enum.Instruction.html).
+    pub fn process_flash_repay(
+        program_id: &Pubkey,
+        data: &FlashRepay,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let vault_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let vault = unpack_token_account(vault_info, token_program_info.key)?;
+        let fee = flash_fee(data.amount).ok_or(OneSolError::ConversionFailure)?;
+        let fee = Self::stake_discounted_fee(
+            program_id,
+            fee,
+            user_transfer_authority_info.key,
+            account_info_iter,
+        )?;
+        let repaid_amount = data
+            .amount
+            .checked_add(fee)
+            .ok_or(OneSolError::ConversionFailure)?;
+        let ix = spl_token::instruction::transfer(
+            token_program_info.key,
+            source_info.key,
+            vault_info.key,
+            user_transfer_authority_info.key,
+            &[],
+            repaid_amount,
+        )?;
+        invoke(
+            &ix,
+            &[
+                source_info.clone(),
+                vault_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+        event::emit(&FeeCollectedEvent {
+            kind: FeeKind::FlashLoan as u8,
+            payer: *user_transfer_authority_info.key,
+            mint: vault.mint,
+            amount: fee,
+            recipient: *vault_info.key,
+        });
+        Ok(())
+    }
+
+    /// Processes an [PlaceOrder](enum.Instruction.html).
+    pub fn process_place_order(
+        program_id: &Pubkey,
+        data: &PlaceOrder,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let order_info = next_account_info(account_info_iter)?;
+        let order_authority_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let escrow_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let page_info = next_account_info(account_info_iter)?;
+
+        if order_info.owner != program_id || page_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if Order::unpack_unchecked(&order_info.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        if data.amount_in < 1
+            || data.expected_amount_out < 1
+            || data.max_deviation_bps > 10_000
+            || data.keeper_fee_bps > 10_000
+            || data.num_slices < 1
+            || data.amount_in < data.num_slices as u64
+            || (data.kind != OrderKind::Twap && data.num_slices != 1)
+            || (data.kind.is_conditional() != (data.trigger_price > 0))
+        {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        if *order_authority_info.key != Self::authority_id(program_id, order_info.key, data.nonce)?
+        {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+
+        let token_program_id = *token_program_info.key;
+        let escrow = unpack_token_account(escrow_info, &token_program_id)?;
+        if escrow.owner != *order_authority_info.key {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        if escrow.delegate.is_some() {
+            return Err(OneSolError::InvalidDelegate.into());
+        }
+        if escrow.close_authority.is_some() {
+            return Err(OneSolError::InvalidCloseAuthority.into());
+        }
+        let source = unpack_token_account(source_info, &token_program_id)?;
+        let destination = unpack_token_account(destination_info, &token_program_id)?;
+        if source.mint != escrow.mint || destination.mint == escrow.mint {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        if destination.owner != *owner_info.key {
+            return Err(OneSolError::IncorrectDestinationAccount.into());
+        }
+        let page = OrderPage::header(&page_info.data.borrow())?;
+        if !page.is_initialized()
+            || page.source_mint != escrow.mint
+            || page.destination_mint != destination.mint
+        {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let oracle = if data.kind.is_pool_conditional() {
+            let pool_info = next_account_info(account_info_iter)?;
+            if !load_token_swap_pool(pool_info)?.pairs(&escrow.mint, &destination.mint) {
+                return Err(OneSolError::InvalidOracle.into());
+            }
+            *pool_info.key
+        } else if data.kind.is_conditional() {
+            let oracle_info = next_account_info(account_info_iter)?;
+            load_oracle_price(oracle_info)?;
+            *oracle_info.key
+        } else {
+            Pubkey::default()
+        };
+
+        let order = Order {
+            version: 1,
+            nonce: data.nonce,
+            kind: data.kind,
+            owner: *owner_info.key,
+            token_program_id,
+            source_mint: escrow.mint,
+            destination_mint: destination.mint,
+            escrow: *escrow_info.key,
+            destination: *destination_info.key,
+            amount_in: data.amount_in,
+            expected_amount_out: data.expected_amount_out,
+            max_deviation_bps: data.max_deviation_bps,
+            num_slices: data.num_slices,
+            slice_interval: data.slice_interval,
+            start_time: Clock::get()?.unix_timestamp,
+            filled_amount_in: 0,
+            filled_amount_out: 0,
+            slices_filled: 0,
+            oracle,
+            trigger_price: data.trigger_price,
+            expires_at_slot: data.expires_at_slot,
+            keeper_fee_bps: data.keeper_fee_bps,
+            keeper_fee_flat: data.keeper_fee_flat,
+            page: *page_info.key,
+        };
+        let deposit = order
+            .keeper_fee_budget()
+            .and_then(|budget| budget.checked_add(data.amount_in))
+            .ok_or(OneSolError::ConversionFailure)?;
+        OrderPage::push(
+            &mut page_info.data.borrow_mut(),
+            &OrderPageEntry {
+                order: *order_info.key,
+                kind: order.kind,
+                expires_at_slot: order.expires_at_slot,
+            },
+        )?;
+        Order::pack(order, &mut order_info.data.borrow_mut())?;
+
+        let ix = spl_token::instruction::transfer(
+            token_program_info.key,
+            source_info.key,
+            escrow_info.key,
+            owner_info.key,
+            &[],
+            deposit,
+        )?;
+        invoke(
+            &ix,
+            &[
+                source_info.clone(),
+                escrow_info.clone(),
+                owner_info.clone(),
+                token_program_info.clone(),
+            ],
+        )
+    }
+
+    /// Processes an [FillOrder](enum.Instruction.html).
+    pub fn process_fill_order(
+        program_id: &Pubkey,
+        amount_in: u64,
+        dex_configs: DexConfigs,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let order_info = next_account_info(account_info_iter)?;
+        let order_authority_info = next_account_info(account_info_iter)?;
+        let escrow_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let keeper_fee_info = next_account_info(account_info_iter)?;
+
+        if order_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut order = Order::unpack(&order_info.data.borrow())?;
+        if *escrow_info.key != order.escrow {
+            return Err(OneSolError::IncorrectSwapAccount.into());
+        }
+        if *token_program_info.key != order.token_program_id {
+            return Err(OneSolError::IncorrectTokenProgramId.into());
+        }
+        Self::check_order_destination(&order, destination_info)?;
+        if order.is_filled() {
+            return Err(OneSolError::OrderFilled.into());
+        }
+        let clock = Clock::get()?;
+        if order.is_expired(clock.slot) {
+            return Err(OneSolError::OrderExpired.into());
+        }
+        if order.kind.is_conditional() {
+            let oracle_info = next_account_info(account_info_iter)?;
+            if *oracle_info.key != order.oracle {
+                return Err(OneSolError::InvalidOracle.into());
+            }
+            let price = if order.kind.is_pool_conditional() {
+                let token_a_info = next_account_info(account_info_iter)?;
+                let token_b_info = next_account_info(account_info_iter)?;
+                load_token_swap_price(
+                    oracle_info,
+                    token_a_info,
+                    token_b_info,
+                    &order.token_program_id,
+                    &order.source_mint,
+                )?
+            } else {
+                let config_info = next_account_info(account_info_iter)?;
+                let config = Self::load_config(program_id, config_info)?;
+                Self::load_fresh_oracle_price(&config, oracle_info)?.price as u64
+            };
+            let triggered = if order.kind.is_stop_loss() {
+                price <= order.trigger_price
+            } else {
+                price >= order.trigger_price
+            };
+            if !triggered {
+                return Err(OneSolError::OrderNotTriggered.into());
+            }
+        }
+
+        let (fill_amount_in, fill_minimum_amount_out) =
+            Self::order_fill_amounts(&order, amount_in, clock.unix_timestamp)?;
+
+        let context = SwapContext {
+            program_id,
+            token_program: token_program_info,
+            user_transfer_authority: order_authority_info,
+            source: escrow_info,
+            destination: destination_info,
+        };
+        let amounts = Self::split_by_ratio(fill_amount_in, dex_configs)?;
+        let swappers = Self::load_swappers(dex_configs, &amounts, account_info_iter, &context)?;
+        Self::check_pair_gate(
+            program_id,
+            &order.owner,
+            &order.source_mint,
+            &order.destination_mint,
+            account_info_iter,
+        )?;
+
+        let escrow_before = unpack_token_account(escrow_info, &order.token_program_id)?;
+        let destination_before = unpack_token_account(destination_info, &order.token_program_id)?;
+        let order_bytes = order_info.key.to_bytes();
+        let authority_signature_seeds = [&order_bytes[..32], &[order.nonce]];
+        let signers = &[&authority_signature_seeds[..]];
+        for (i, (swapper, dex_config)) in swappers.iter().zip(dex_configs.iter()).enumerate() {
+            let swapper = match swapper {
+                Some(swapper) => swapper,
+                None => continue,
+            };
+            verbose!(
+                log_level,
+                "fill order slice {} using dex[{}], amount_in: {}",
+                order.slices_filled,
+                i,
+                amounts[i],
+            );
+            Self::log_leg_compute_units(i, &dex_config, "before");
+            swapper
+                .invoke_swap_signed(amounts[i], 0, signers)
+                .map_err(|e| Self::leg_error(log_level, i, &dex_config, e))?;
+            Self::log_leg_compute_units(i, &dex_config, "after");
+        }
+        Self::check_source_spent(
+            escrow_info,
+            &order.token_program_id,
+            escrow_before.amount,
+            fill_amount_in,
+        )?;
+        let destination_after = unpack_token_account(destination_info, &order.token_program_id)?;
+        let fill_amount_out = destination_after
+            .amount
+            .checked_sub(destination_before.amount)
+            .ok_or(OneSolError::InternalError)?;
+        if fill_amount_out < fill_minimum_amount_out {
+            return Err(OneSolError::ExceededSlippage.into());
+        }
+
+        let completes_slice = Self::record_order_fill(&mut order, fill_amount_in, fill_amount_out)?;
+        Order::pack(order, &mut order_info.data.borrow_mut())?;
+
+        let keeper_fee = order.keeper_fee(fill_amount_in, completes_slice);
+        let payout = Payout::new(
+            order_info.key,
+            order.nonce,
+            token_program_info,
+            escrow_info,
+            order_authority_info,
+        );
+        Self::pay_keeper_fee(&payout, &order, keeper_fee_info, keeper_fee)?;
+        Ok(())
+    }
+
+    /// Processes an [BatchFillOrders](enum.Instruction.html).
+    pub fn process_batch_fill_orders(
+        program_id: &Pubkey,
+        nonce: u8,
+        num_orders: u8,
+        dex_configs: DexConfigs,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if num_orders < 1 {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let batch_authority_info = next_account_info(account_info_iter)?;
+        let batch_source_info = next_account_info(account_info_iter)?;
+        let batch_destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let keeper_fee_info = next_account_info(account_info_iter)?;
+
+        if *batch_authority_info.key
+            != Self::authority_id(program_id, batch_source_info.key, nonce)?
+        {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        let token_program_id = *token_program_info.key;
+        let batch_source = unpack_token_account(batch_source_info, &token_program_id)?;
+        let batch_destination = unpack_token_account(batch_destination_info, &token_program_id)?;
+        if batch_source.owner != *batch_authority_info.key
+            || batch_destination.owner != *batch_authority_info.key
+        {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        if batch_source.delegate.is_some() || batch_destination.delegate.is_some() {
+            return Err(OneSolError::InvalidDelegate.into());
+        }
+
+        let now = Clock::get()?;
+        let mut fills = Vec::with_capacity(num_orders as usize);
+        let mut order_keys = Vec::with_capacity(num_orders as usize);
+        let mut total_amount_in: u64 = 0;
+        for _ in 0..num_orders {
+            let order_info = next_account_info(account_info_iter)?;
+            let order_authority_info = next_account_info(account_info_iter)?;
+            let escrow_info = next_account_info(account_info_iter)?;
+            let destination_info = next_account_info(account_info_iter)?;
+
+            if order_info.owner != program_id {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            // a repeated order would pull a second slice out of its escrow, recording one
+            if order_keys.contains(&order_info.key) {
+                return Err(OneSolError::InvalidInput.into());
+            }
+            order_keys.push(order_info.key);
+            let order = Order::unpack(&order_info.data.borrow())?;
+            if *escrow_info.key != order.escrow {
+                return Err(OneSolError::IncorrectSwapAccount.into());
+            }
+            if order.token_program_id != token_program_id {
+                return Err(OneSolError::IncorrectTokenProgramId.into());
+            }
+            Self::check_order_destination(&order, destination_info)?;
+            if order.source_mint != batch_source.mint
+                || order.destination_mint != batch_destination.mint
+                || order.kind.is_conditional()
+            {
+                return Err(OneSolError::InvalidInput.into());
+            }
+            Self::check_pair_gate(
+                program_id,
+                &order.owner,
+                &order.source_mint,
+                &order.destination_mint,
+                account_info_iter,
+            )?;
+            if order.is_filled() {
+                return Err(OneSolError::OrderFilled.into());
+            }
+            if order.is_expired(now.slot) {
+                return Err(OneSolError::OrderExpired.into());
+            }
+            let (fill_amount_in, fill_minimum_amount_out) =
+                Self::order_fill_amounts(&order, 0, now.unix_timestamp)?;
+            total_amount_in = total_amount_in
+                .checked_add(fill_amount_in)
+                .ok_or(OneSolError::ConversionFailure)?;
+            let escrow_payout = Payout::new(
+                order_info.key,
+                order.nonce,
+                token_program_info,
+                escrow_info,
+                order_authority_info,
+            );
+            fills.push((
+                order_info,
+                escrow_payout,
+                destination_info,
+                order,
+                fill_amount_in,
+                fill_minimum_amount_out,
+            ));
+        }
+
+        // every order checked, repeated ones included, before pulling any slice
+        for (_, escrow_payout, _, _, fill_amount_in, _) in fills.iter() {
+            escrow_payout.transfer(batch_source_info, *fill_amount_in)?;
+        }
+
+        let context = SwapContext {
+            program_id,
+            token_program: token_program_info,
+            user_transfer_authority: batch_authority_info,
+            source: batch_source_info,
+            destination: batch_destination_info,
+        };
+        let amounts = Self::split_by_ratio(total_amount_in, dex_configs)?;
+        let swappers = Self::load_swappers(dex_configs, &amounts, account_info_iter, &context)?;
+        let batch_source_before = unpack_token_account(batch_source_info, &token_program_id)?;
+        let batch_source_bytes = batch_source_info.key.to_bytes();
+        let authority_signature_seeds = [&batch_source_bytes[..32], &[nonce]];
+        let signers = &[&authority_signature_seeds[..]];
+        for (i, (swapper, dex_config)) in swappers.iter().zip(dex_configs.iter()).enumerate() {
+            let swapper = match swapper {
+                Some(swapper) => swapper,
+                None => continue,
+            };
+            verbose!(
+                log_level,
+                "batch fill {} orders using dex[{}], amount_in: {}",
+                num_orders,
+                i,
+                amounts[i]
+            );
+            Self::log_leg_compute_units(i, &dex_config, "before");
+            swapper
+                .invoke_swap_signed(amounts[i], 0, signers)
+                .map_err(|e| Self::leg_error(log_level, i, &dex_config, e))?;
+            Self::log_leg_compute_units(i, &dex_config, "after");
+        }
+        Self::check_source_spent(
+            batch_source_info,
+            &token_program_id,
+            batch_source_before.amount,
+            total_amount_in,
+        )?;
+        let batch_destination_after =
+            unpack_token_account(batch_destination_info, &token_program_id)?;
+        let total_amount_out = batch_destination_after
+            .amount
+            .checked_sub(batch_destination.amount)
+            .ok_or(OneSolError::InternalError)?;
+
+        let batch_payout = Payout::new(
+            batch_source_info.key,
+            nonce,
+            token_program_info,
+            batch_destination_info,
+            batch_authority_info,
+        );
+        let mut remaining_amount_out = total_amount_out;
+        let last = fills.len() - 1;
+        for (i, fill) in fills.into_iter().enumerate() {
+            let (
+                order_info,
+                escrow_payout,
+                destination_info,
+                mut order,
+                fill_amount_in,
+                fill_minimum_amount_out,
+            ) = fill;
+            // pro-rata share of the route output, the last order taking the rounding remainder
+            let fill_amount_out = if i == last {
+                remaining_amount_out
+            } else {
+                mul_div_floor(total_amount_out, fill_amount_in, total_amount_in)?
+            };
+            remaining_amount_out -= fill_amount_out;
+            if fill_amount_out < fill_minimum_amount_out {
+                return Err(OneSolError::ExceededSlippage.into());
+            }
+            batch_payout.transfer(destination_info, fill_amount_out)?;
+
+            let completes_slice =
+                Self::record_order_fill(&mut order, fill_amount_in, fill_amount_out)?;
+            Order::pack(order, &mut order_info.data.borrow_mut())?;
+            let keeper_fee = order.keeper_fee(fill_amount_in, completes_slice);
+            Self::pay_keeper_fee(&escrow_payout, &order, keeper_fee_info, keeper_fee)?;
+        }
+        Ok(())
+    }
+
+    /// Processes an [ExpireOrder](enum.Instruction.html).
+    pub fn process_expire_order(
+        program_id: &Pubkey,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let order_info = next_account_info(account_info_iter)?;
+        let order_authority_info = next_account_info(account_info_iter)?;
+        let escrow_info = next_account_info(account_info_iter)?;
+        let refund_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let keeper_fee_info = next_account_info(account_info_iter)?;
+        let page_info = next_account_info(account_info_iter)?;
+
+        if order_info.owner != program_id || page_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let order = Order::unpack(&order_info.data.borrow())?;
+        if *escrow_info.key != order.escrow
+            || *owner_info.key != order.owner
+            || *page_info.key != order.page
+        {
+            return Err(OneSolError::IncorrectSwapAccount.into());
+        }
+        if *token_program_info.key != order.token_program_id {
+            return Err(OneSolError::IncorrectTokenProgramId.into());
+        }
+        if !order.is_filled() && !order.is_expired(Clock::get()?.slot) {
+            return Err(OneSolError::OrderActive.into());
+        }
+        let refund = unpack_token_account(refund_info, &order.token_program_id)?;
+        if refund.owner != order.owner || refund.mint != order.source_mint {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+
+        let escrow = unpack_token_account(escrow_info, &order.token_program_id)?;
+        let keeper_fee = order.keeper_fee_flat.min(escrow.amount);
+        let payout = Payout::new(
+            order_info.key,
+            order.nonce,
+            token_program_info,
+            escrow_info,
+            order_authority_info,
+        );
+        Self::pay_keeper_fee(&payout, &order, keeper_fee_info, keeper_fee)?;
+        let refund_amount = escrow.amount - keeper_fee;
+        if refund_amount > 0 {
+            verbose!(log_level, "refund {} to order owner", refund_amount);
+            payout.transfer(refund_info, refund_amount)?;
+        }
+        Self::token_close_account(
+            order_info.key,
+            token_program_info.clone(),
+            escrow_info.clone(),
+            owner_info.clone(),
+            order_authority_info.clone(),
+            order.nonce,
+        )?;
+        OrderPage::remove(&mut page_info.data.borrow_mut(), order_info.key)?;
+
+        let order_lamports = order_info.lamports();
+        **order_info.lamports.borrow_mut() = 0;
+        **owner_info.lamports.borrow_mut() = owner_info
+            .lamports()
+            .checked_add(order_lamports)
+            .ok_or(OneSolError::ConversionFailure)?;
+        order_info.data.borrow_mut().fill(0);
+        Ok(())
+    }
+
+    /// Processes an [CreateRecurringPayment](enum.Instruction.html).
+    pub fn process_create_recurring_payment(
+        program_id: &Pubkey,
+        data: &CreateRecurringPayment,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payment_info = next_account_info(account_info_iter)?;
+        let payment_authority_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let escrow_info = next_account_info(account_info_iter)?;
+
+        if payment_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !payer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if RecurringPayment::unpack_unchecked(&payment_info.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        if data.amount_in < 1
+            || data.minimum_amount_out < 1
+            || data.period < 1
+            || data.max_total_in < data.amount_in
+        {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        if *payment_authority_info.key
+            != Self::authority_id(program_id, payment_info.key, data.nonce)?
+        {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+
+        let token_program_id = *token_program_info.key;
+        let source = unpack_token_account(source_info, &token_program_id)?;
+        if source.owner != *payer_info.key {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        let destination = unpack_token_account(destination_info, &token_program_id)?;
+        if destination.mint == source.mint {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let escrow = unpack_token_account(escrow_info, &token_program_id)?;
+        if escrow.owner != Self::authority_id(program_id, escrow_info.key, data.escrow_nonce)? {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        if escrow.delegate.is_some() {
+            return Err(OneSolError::InvalidDelegate.into());
+        }
+        if escrow.close_authority.is_some() {
+            return Err(OneSolError::InvalidCloseAuthority.into());
+        }
+        if escrow.mint != source.mint {
+            return Err(OneSolError::InvalidInput.into());
+        }
+
+        let payment = RecurringPayment {
+            version: 1,
+            nonce: data.nonce,
+            payer: *payer_info.key,
+            token_program_id,
+            source: *source_info.key,
+            source_mint: source.mint,
+            destination: *destination_info.key,
+            destination_mint: destination.mint,
+            amount_in: data.amount_in,
+            minimum_amount_out: data.minimum_amount_out,
+            period: data.period,
+            max_total_in: data.max_total_in,
+            total_in: 0,
+            total_out: 0,
+            next_payment_time: Clock::get()?.unix_timestamp,
+            escrow: *escrow_info.key,
+            escrow_nonce: data.escrow_nonce,
+        };
+        RecurringPayment::pack(payment, &mut payment_info.data.borrow_mut())?;
+
+        let ix = spl_token::instruction::approve(
+            token_program_info.key,
+            source_info.key,
+            payment_authority_info.key,
+            payer_info.key,
+            &[],
+            data.max_total_in,
+        )?;
+        invoke(
+            &ix,
+            &[
+                source_info.clone(),
+                payment_authority_info.clone(),
+                payer_info.clone(),
+                token_program_info.clone(),
+            ],
+        )
+    }
+
+    /// Processes an [ExecuteRecurringPayment](enum.Instruction.html).
+    pub fn process_execute_recurring_payment(
+        program_id: &Pubkey,
+        dex_configs: DexConfigs,
+        log_level: LogLevel,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payment_info = next_account_info(account_info_iter)?;
+        let payment_authority_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let escrow_info = next_account_info(account_info_iter)?;
+        let escrow_authority_info = next_account_info(account_info_iter)?;
+
+        if payment_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut payment = RecurringPayment::unpack(&payment_info.data.borrow())?;
+        if *source_info.key != payment.source
+            || *destination_info.key != payment.destination
+            || *escrow_info.key != payment.escrow
+        {
+            return Err(OneSolError::IncorrectSwapAccount.into());
+        }
+        if *escrow_authority_info.key
+            != Self::authority_id(program_id, escrow_info.key, payment.escrow_nonce)?
+        {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        if *token_program_info.key != payment.token_program_id {
+            return Err(OneSolError::IncorrectTokenProgramId.into());
+        }
+        let now = Clock::get()?.unix_timestamp;
+        if now < payment.next_payment_time {
+            return Err(OneSolError::PaymentNotDue.into());
+        }
+        let total_in = payment
+            .total_in
+            .checked_add(payment.amount_in)
+            .ok_or(OneSolError::ConversionFailure)?;
+        if total_in > payment.max_total_in {
+            return Err(OneSolError::PaymentCapExceeded.into());
+        }
+
+        // the legs swap from the escrow under its own authority: the payment authority,
+        // delegated the rest of `max_total_in`, never signs for a dex
+        let context = SwapContext {
+            program_id,
+            token_program: token_program_info,
+            user_transfer_authority: escrow_authority_info,
+            source: escrow_info,
+            destination: destination_info,
+        };
+        let amounts = Self::split_by_ratio(payment.amount_in, dex_configs)?;
+        let swappers = Self::load_swappers(dex_configs, &amounts, account_info_iter, &context)?;
+        Self::check_pair_gate(
+            program_id,
+            &payment.payer,
+            &payment.source_mint,
+            &payment.destination_mint,
+            account_info_iter,
+        )?;
+
+        // the payer may have revoked or lowered the approval of the payment authority
+        let source = unpack_token_account(source_info, &payment.token_program_id)?;
+        if source.delegate != COption::Some(*payment_authority_info.key)
+            || source.delegated_amount < payment.amount_in
+        {
+            return Err(OneSolError::InsufficientDelegation.into());
+        }
+        Payout::new(
+            payment_info.key,
+            payment.nonce,
+            token_program_info,
+            source_info,
+            payment_authority_info,
+        )
+        .transfer(escrow_info, payment.amount_in)?;
+
+        let escrow_before = unpack_token_account(escrow_info, &payment.token_program_id)?;
+        let destination_before = unpack_token_account(destination_info, &payment.token_program_id)?;
+        let escrow_bytes = payment.escrow.to_bytes();
+        let authority_signature_seeds = [&escrow_bytes[..32], &[payment.escrow_nonce]];
+        let signers = &[&authority_signature_seeds[..]];
+        for (i, (swapper, dex_config)) in swappers.iter().zip(dex_configs.iter()).enumerate() {
+            let swapper = match swapper {
+                Some(swapper) => swapper,
+                None => continue,
+            };
+            verbose!(
+                log_level,
+                "recurring payment using dex[{}], amount_in: {}",
+                i,
+                amounts[i]
+            );
+            Self::log_leg_compute_units(i, &dex_config, "before");
+            swapper
+                .invoke_swap_signed(amounts[i], 0, signers)
+                .map_err(|e| Self::leg_error(log_level, i, &dex_config, e))?;
+            Self::log_leg_compute_units(i, &dex_config, "after");
+        }
+        Self::check_source_spent(
+            escrow_info,
+            &payment.token_program_id,
+            escrow_before.amount,
+            payment.amount_in,
+        )?;
+        let destination_after = unpack_token_account(destination_info, &payment.token_program_id)?;
+        let amount_out = destination_after
+            .amount
+            .checked_sub(destination_before.amount)
+            .ok_or(OneSolError::InternalError)?;
+        if amount_out < payment.minimum_amount_out {
+            return Err(OneSolError::ExceededSlippage.into());
+        }
+
+        payment.total_in = total_in;
+        payment.total_out = payment
+            .total_out
+            .checked_add(amount_out)
+            .ok_or(OneSolError::ConversionFailure)?;
+        // stay on the original schedule: periods missed by late cranks are skipped
+        let period = i64::from(payment.period);
+        payment.next_payment_time = now
+            .checked_sub(payment.next_payment_time)
+            .and_then(|late| late.checked_div(period))
+            .and_then(|missed| missed.checked_add(1))
+            .and_then(|periods| periods.checked_mul(period))
+            .and_then(|elapsed| payment.next_payment_time.checked_add(elapsed))
+            .ok_or(OneSolError::ConversionFailure)?;
+        RecurringPayment::pack(payment, &mut payment_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes an [CancelRecurringPayment](enum.Instruction.html).
+    pub fn process_cancel_recurring_payment(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payment_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let escrow_info = next_account_info(account_info_iter)?;
+        let escrow_authority_info = next_account_info(account_info_iter)?;
+
+        if payment_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let payment = RecurringPayment::unpack(&payment_info.data.borrow())?;
+        if *payer_info.key != payment.payer
+            || *source_info.key != payment.source
+            || *escrow_info.key != payment.escrow
+        {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        if !payer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *token_program_info.key != payment.token_program_id {
+            return Err(OneSolError::IncorrectTokenProgramId.into());
+        }
+
+        let ix = spl_token::instruction::revoke(
+            token_program_info.key,
+            source_info.key,
+            payer_info.key,
+            &[],
+        )?;
+        invoke(
+            &ix,
+            &[
+                source_info.clone(),
+                payer_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+        // returns anything left in the escrow, then its rent
+        let escrow = unpack_token_account(escrow_info, &payment.token_program_id)?;
+        Payout::new(
+            escrow_info.key,
+            payment.escrow_nonce,
+            token_program_info,
+            escrow_info,
+            escrow_authority_info,
+        )
+        .transfer(source_info, escrow.amount)?;
+        Self::token_close_account(
+            escrow_info.key,
+            token_program_info.clone(),
+            escrow_info.clone(),
+            payer_info.clone(),
+            escrow_authority_info.clone(),
+            payment.escrow_nonce,
+        )?;
+
+        let payment_lamports = payment_info.lamports();
+        **payment_info.lamports.borrow_mut() = 0;
+        **payer_info.lamports.borrow_mut() = payer_info
+            .lamports()
+            .checked_add(payment_lamports)
+            .ok_or(OneSolError::ConversionFailure)?;
+        payment_info.data.borrow_mut().fill(0);
+        Ok(())
+    }
+
+    /// Processes an [InitPriceAccumulator](enum.Instruction.html).
+    pub fn process_init_price_accumulator(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer_info = next_account_info(account_info_iter)?;
+        let accumulator_info = next_account_info(account_info_iter)?;
+        let source_mint_info = next_account_info(account_info_iter)?;
+        let destination_mint_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !payer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if source_mint_info.key == destination_mint_info.key {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let (accumulator_key, bump_seed) = Self::find_price_accumulator_address(
+            program_id,
+            source_mint_info.key,
+            destination_mint_info.key,
+        );
+        if *accumulator_info.key != accumulator_key {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+
+        Self::create_program_account(
+            program_id,
+            payer_info,
+            accumulator_info,
+            system_program_info,
+            PriceAccumulator::LEN,
+            &[
+                b"twap",
+                &source_mint_info.key.to_bytes(),
+                &destination_mint_info.key.to_bytes(),
+                &[bump_seed],
+            ],
+        )?;
+
+        let accumulator = PriceAccumulator {
+            version: 1,
+            bump_seed,
+            source_mint: *source_mint_info.key,
+            destination_mint: *destination_mint_info.key,
+            last_update_time: 0,
+            last_price: 0,
+            cumulative_price: 0,
+            cumulative_amount_in: 0,
+            cumulative_amount_out: 0,
+            breaker_max_move_bps: 0,
+            breaker_window_slots: 0,
+            reference_price: 0,
+            reference_slot: 0,
+        };
+        PriceAccumulator::pack(accumulator, &mut accumulator_info.data.borrow_mut())
+    }
+
+    /// Processes an [InitMintStats](enum.Instruction.html).
+    pub fn process_init_mint_stats(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer_info = next_account_info(account_info_iter)?;
+        let stats_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !payer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let (stats_key, bump_seed) = Self::find_mint_stats_address(program_id, mint_info.key);
+        if *stats_info.key != stats_key {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+
+        Self::create_program_account(
+            program_id,
+            payer_info,
+            stats_info,
+            system_program_info,
+            MintStats::LEN,
+            &[b"stats", &mint_info.key.to_bytes(), &[bump_seed]],
+        )?;
+
+        let stats = MintStats {
+            version: 1,
+            bump_seed,
+            mint: *mint_info.key,
+            volume_in: 0,
+            volume_out: 0,
+            swap_count: 0,
+            fees: 0,
+        };
+        MintStats::pack(stats, &mut stats_info.data.borrow_mut())
+    }
+
+    /// Processes an [ConfigurePairRewards](enum.Instruction.html).
+    pub fn process_configure_pair_rewards(
+        program_id: &Pubkey,
+        data: &ConfigurePairRewards,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let rewards_info = next_account_info(account_info_iter)?;
+        let source_mint_info = next_account_info(account_info_iter)?;
+        let destination_mint_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let config = Self::load_config(program_id, config_info)?;
+        Self::check_config_admin(&config, admin_info)?;
+        let mut rewards = if rewards_info.data_is_empty() {
+            let (rewards_key, bump_seed) = Self::find_pair_rewards_address(
+                program_id,
+                source_mint_info.key,
+                destination_mint_info.key,
+            );
+            if *rewards_info.key != rewards_key {
+                return Err(OneSolError::InvalidProgramAddress.into());
+            }
+            Self::create_program_account(
+                program_id,
+                admin_info,
+                rewards_info,
+                system_program_info,
+                PairRewards::LEN,
+                &[
+                    b"rewards",
+                    &source_mint_info.key.to_bytes(),
+                    &destination_mint_info.key.to_bytes(),
+                    &[bump_seed],
+                ],
+            )?;
+            PairRewards {
+                version: 1,
+                bump_seed,
+                source_mint: *source_mint_info.key,
+                destination_mint: *destination_mint_info.key,
+                points_per_unit: 0,
+                total_points: 0,
+            }
+        } else {
+            if rewards_info.owner != program_id {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let rewards = PairRewards::unpack(&rewards_info.data.borrow())?;
+            if rewards.source_mint != *source_mint_info.key
+                || rewards.destination_mint != *destination_mint_info.key
+            {
+                return Err(OneSolError::InvalidInput.into());
+            }
+            rewards
+        };
+        rewards.points_per_unit = data.points_per_unit;
+        PairRewards::pack(rewards, &mut rewards_info.data.borrow_mut())
+    }
+
+    /// Processes an [ConfigurePairGate](enum.Instruction.html).
+    pub fn process_configure_pair_gate(
+        program_id: &Pubkey,
+        data: &ConfigurePairGate,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let gate_info = next_account_info(account_info_iter)?;
+        let source_mint_info = next_account_info(account_info_iter)?;
+        let destination_mint_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let config = Self::load_config(program_id, config_info)?;
+        Self::check_config_admin(&config, admin_info)?;
+        let mut gate = if gate_info.data_is_empty() {
+            let (gate_key, bump_seed) = Self::find_pair_gate_address(
+                program_id,
+                source_mint_info.key,
+                destination_mint_info.key,
+            );
+            if *gate_info.key != gate_key {
+                return Err(OneSolError::InvalidProgramAddress.into());
+            }
+            Self::create_program_account(
+                program_id,
+                admin_info,
+                gate_info,
+                system_program_info,
+                PairGate::LEN,
+                &[
+                    b"gate",
+                    &source_mint_info.key.to_bytes(),
+                    &destination_mint_info.key.to_bytes(),
+                    &[bump_seed],
+                ],
+            )?;
+            PairGate {
+                version: 1,
+                bump_seed,
+                source_mint: *source_mint_info.key,
+                destination_mint: *destination_mint_info.key,
+                credential_mint: Pubkey::default(),
+                min_balance: 0,
+            }
+        } else {
+            if gate_info.owner != program_id {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let gate = PairGate::unpack(&gate_info.data.borrow())?;
+            if gate.source_mint != *source_mint_info.key
+                || gate.destination_mint != *destination_mint_info.key
+            {
+                return Err(OneSolError::InvalidInput.into());
+            }
+            gate
+        };
+        gate.credential_mint = data.credential_mint;
+        gate.min_balance = data.min_balance.max(1);
+        PairGate::pack(gate, &mut gate_info.data.borrow_mut())
+    }
+
+    /// Processes an [ConfigureInventoryPair](enum.Instruction.html).
+    pub fn process_configure_inventory_pair(
+        program_id: &Pubkey,
+        data: &ConfigureInventoryPair,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let pair_info = next_account_info(account_info_iter)?;
+        let source_mint_info = next_account_info(account_info_iter)?;
+        let destination_mint_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let config = Self::load_config(program_id, config_info)?;
+        Self::check_config_admin(&config, admin_info)?;
+        let mut pair = if pair_info.data_is_empty() {
+            let (pair_key, bump_seed) = Self::find_inventory_pair_address(
+                program_id,
+                source_mint_info.key,
+                destination_mint_info.key,
+            );
+            if *pair_info.key != pair_key {
+                return Err(OneSolError::InvalidProgramAddress.into());
+            }
+            Self::create_program_account(
+                program_id,
+                admin_info,
+                pair_info,
+                system_program_info,
+                InventoryPair::LEN,
+                &[
+                    b"inventory",
+                    &source_mint_info.key.to_bytes(),
+                    &destination_mint_info.key.to_bytes(),
+                    &[bump_seed],
+                ],
+            )?;
+            InventoryPair {
+                version: 1,
+                bump_seed,
+                source_mint: *source_mint_info.key,
+                destination_mint: *destination_mint_info.key,
+                oracle: Pubkey::default(),
+                spread_bps: 0,
+            }
+        } else {
+            if pair_info.owner != program_id {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let pair = InventoryPair::unpack(&pair_info.data.borrow())?;
+            if pair.source_mint != *source_mint_info.key
+                || pair.destination_mint != *destination_mint_info.key
+            {
+                return Err(OneSolError::InvalidInput.into());
+            }
+            pair
+        };
+        pair.oracle = data.oracle;
+        pair.spread_bps = data.spread_bps;
+        InventoryPair::pack(pair, &mut pair_info.data.borrow_mut())
+    }
+
+    /// Processes an [FundInventory](enum.Instruction.html).
+    pub fn process_fund_inventory(
+        program_id: &Pubkey,
+        data: &InventoryAmount,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let config = Self::load_config(program_id, config_info)?;
+        Self::check_config_admin(&config, admin_info)?;
+        let (authority, _) = Self::find_inventory_authority(program_id);
+        if *authority_info.key != authority {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        let vault = unpack_token_account(vault_info, token_program_info.key)?;
+        if vault.owner != authority {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+
+        let ix = spl_token::instruction::transfer(
+            token_program_info.key,
+            source_info.key,
+            vault_info.key,
+            admin_info.key,
+            &[],
+            data.amount,
+        )?;
+        invoke(
+            &ix,
+            &[
+                source_info.clone(),
+                vault_info.clone(),
+                admin_info.clone(),
+                token_program_info.clone(),
+            ],
+        )
+    }
+
+    /// Processes an [WithdrawInventory](enum.Instruction.html).
+    pub fn process_withdraw_inventory(
+        program_id: &Pubkey,
+        data: &InventoryAmount,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let config = Self::load_config(program_id, config_info)?;
+        Self::check_config_admin(&config, admin_info)?;
+        let (authority, bump_seed) = Self::find_inventory_authority(program_id);
+        if *authority_info.key != authority {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        // the signature of the authority must only ever reach the token program
+        if *token_program_info.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let ix = spl_token::instruction::transfer(
+            token_program_info.key,
+            vault_info.key,
+            destination_info.key,
+            authority_info.key,
+            &[],
+            data.amount,
+        )?;
+        invoke_signed(
+            &ix,
+            &[
+                vault_info.clone(),
+                destination_info.clone(),
+                authority_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[&[b"inventory", &[bump_seed]]],
+        )
+    }
+
+    /// Processes an [InitRewardPoints](enum.Instruction.html).
+    pub fn process_init_reward_points(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer_info = next_account_info(account_info_iter)?;
+        let points_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !payer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let (points_key, bump_seed) = Self::find_reward_points_address(program_id, owner_info.key);
+        if *points_info.key != points_key {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+
+        Self::create_program_account(
+            program_id,
+            payer_info,
+            points_info,
+            system_program_info,
+            RewardPoints::LEN,
+            &[b"points", &owner_info.key.to_bytes(), &[bump_seed]],
+        )?;
+
+        let points = RewardPoints {
+            version: 1,
+            bump_seed,
+            owner: *owner_info.key,
+            points: 0,
+            claimed: 0,
+        };
+        RewardPoints::pack(points, &mut points_info.data.borrow_mut())
+    }
+
+    /// Processes an [ClaimRewards](enum.Instruction.html).
+    pub fn process_claim_rewards(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let owner_info = next_account_info(account_info_iter)?;
+        let points_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if points_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut points = RewardPoints::unpack(&points_info.data.borrow())?;
+        if points.owner != *owner_info.key {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        let (authority, bump_seed) = Self::find_rewards_vault_authority(program_id);
+        if *authority_info.key != authority {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        // the signature of the authority must only ever reach the token program
+        if *token_program_info.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let vault = unpack_token_account(vault_info, token_program_info.key)?;
+        if vault.owner != authority {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+
+        let amount = points.points.min(vault.amount);
+        if amount > 0 {
+            let ix = spl_token::instruction::transfer(
+                token_program_info.key,
+                vault_info.key,
+                destination_info.key,
+                authority_info.key,
+                &[],
+                amount,
+            )?;
+            invoke_signed(
+                &ix,
+                &[
+                    vault_info.clone(),
+                    destination_info.clone(),
+                    authority_info.clone(),
+                    token_program_info.clone(),
+                ],
+                &[&[b"mining", &[bump_seed]]],
+            )?;
+        }
+        points.points -= amount;
+        points.claimed = points.claimed.saturating_add(amount);
+        RewardPoints::pack(points, &mut points_info.data.borrow_mut())
+    }
+
+    /// Processes an [InitConfig](enum.Instruction.html).
+    pub fn process_init_config(
+        program_id: &Pubkey,
+        params: &ConfigParams,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let InitConfigAccounts {
+            admin: admin_info,
+            config: config_info,
+            config_bump: bump_seed,
+            system_program: system_program_info,
+        } = InitConfigAccounts::from_account_infos(program_id, accounts)?;
+
+        Self::create_program_account(
+            program_id,
+            admin_info,
+            config_info,
+            system_program_info,
+            GlobalConfig::LEN,
+            &[b"config", &[bump_seed]],
+        )?;
+
+        let config = GlobalConfig {
+            version: 1,
+            bump_seed,
+            admin: *admin_info.key,
+            oracle_max_age_slots: params.oracle_max_age_slots,
+            oracle_conf_multiplier: params.oracle_conf_multiplier,
+            log_level: params.log_level,
+            tip_account: params.tip_account,
+            relayer_fee_max_bps: params.relayer_fee_max_bps,
+            relayer_fee_max_flat: params.relayer_fee_max_flat,
+            split_parts: params.split_parts,
+        };
+        GlobalConfig::pack(config, &mut config_info.data.borrow_mut())
+    }
+
+    /// Processes an [UpdateConfig](enum.Instruction.html).
+    pub fn process_update_config(
+        program_id: &Pubkey,
+        params: &ConfigParams,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let ConfigAdminAccounts {
+            admin: admin_info,
+            config: config_info,
+        } = ConfigAdminAccounts::from_account_infos(program_id, accounts)?;
+
+        let mut config = Self::load_config(program_id, config_info)?;
+        Self::check_config_admin(&config, admin_info)?;
+        config.oracle_max_age_slots = params.oracle_max_age_slots;
+        config.oracle_conf_multiplier = params.oracle_conf_multiplier;
+        config.log_level = params.log_level;
+        config.tip_account = params.tip_account;
+        config.relayer_fee_max_bps = params.relayer_fee_max_bps;
+        config.relayer_fee_max_flat = params.relayer_fee_max_flat;
+        config.split_parts = params.split_parts;
+        GlobalConfig::pack(config, &mut config_info.data.borrow_mut())
+    }
+
+    /// Processes an [SetConfigAdmin](enum.Instruction.html).
+    pub fn process_set_config_admin(
+        program_id: &Pubkey,
+        data: &SetConfigAdmin,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let ConfigAdminAccounts {
+            admin: admin_info,
+            config: config_info,
+        } = ConfigAdminAccounts::from_account_infos(program_id, accounts)?;
+
+        let mut config = Self::load_config(program_id, config_info)?;
+        Self::check_config_admin(&config, admin_info)?;
+        config.admin = data.new_admin;
+        GlobalConfig::pack(config, &mut config_info.data.borrow_mut())
+    }
+
+    /// Processes an [InitStakePool](enum.Instruction.html).
+    pub fn process_init_stake_pool(
+        program_id: &Pubkey,
+        data: &InitStakePool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let pool_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let rewards_vault_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let config = Self::load_config(program_id, config_info)?;
+        Self::check_config_admin(&config, admin_info)?;
+        let (pool_key, bump_seed) = Self::find_stake_pool_address(program_id);
+        if *pool_info.key != pool_key {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        if data.tiers.iter().any(|tier| tier.discount_bps > 10_000)
+            || vault_info.key == rewards_vault_info.key
+        {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let (authority, nonce) =
+            Pubkey::find_program_address(&[&pool_key.to_bytes()[..32]], program_id);
+        let vault = unpack_token_account(vault_info, &spl_token::id())?;
+        let rewards_vault = unpack_token_account(rewards_vault_info, &spl_token::id())?;
+        if vault.owner != authority || rewards_vault.owner != authority {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+
+        Self::create_program_account(
+            program_id,
+            admin_info,
+            pool_info,
+            system_program_info,
+            StakePool::LEN,
+            &[b"stake_pool", &[bump_seed]],
+        )?;
+        let pool = StakePool {
+            version: 1,
+            bump_seed,
+            nonce,
+            mint: vault.mint,
+            vault: *vault_info.key,
+            rewards_vault: *rewards_vault_info.key,
+            total_staked: 0,
+            reward_per_share: 0,
+            tiers: data.tiers,
+        };
+        StakePool::pack(pool, &mut pool_info.data.borrow_mut())
+    }
+
+    /// Processes an [Stake](enum.Instruction.html).
+    pub fn process_stake(
+        program_id: &Pubkey,
+        data: &StakeAmount,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let user_info = next_account_info(account_info_iter)?;
+        let pool_info = next_account_info(account_info_iter)?;
+        let pool_authority_info = next_account_info(account_info_iter)?;
+        let stake_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let rewards_vault_info = next_account_info(account_info_iter)?;
+        let rewards_destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !user_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut pool = Self::load_stake_pool(program_id, pool_info)?;
+        Self::check_stake_vaults(
+            program_id,
+            &pool,
+            pool_info,
+            pool_authority_info,
+            vault_info,
+            rewards_vault_info,
+            token_program_info,
+        )?;
+        let mut stake = if stake_info.data_is_empty() {
+            let (stake_key, bump_seed) = Self::find_stake_address(program_id, user_info.key);
+            if *stake_info.key != stake_key {
+                return Err(OneSolError::InvalidProgramAddress.into());
+            }
+            Self::create_program_account(
+                program_id,
+                user_info,
+                stake_info,
+                system_program_info,
+                StakeAccount::LEN,
+                &[b"stake", &user_info.key.to_bytes(), &[bump_seed]],
+            )?;
+            StakeAccount {
+                version: 1,
+                bump_seed,
+                owner: *user_info.key,
+                amount: 0,
+                reward_debt: 0,
+            }
+        } else {
+            Self::load_stake_account(program_id, stake_info, user_info.key)?
+        };
+
+        let rewards = stake
+            .pending_rewards(&pool)
+            .ok_or(OneSolError::ConversionFailure)?;
+        Payout::new(
+            pool_info.key,
+            pool.nonce,
+            token_program_info,
+            rewards_vault_info,
+            pool_authority_info,
+        )
+        .transfer(rewards_destination_info, rewards)?;
+        if data.amount > 0 {
+            let ix = spl_token::instruction::transfer(
+                token_program_info.key,
+                source_info.key,
+                vault_info.key,
+                user_info.key,
+                &[],
+                data.amount,
+            )?;
+            invoke(
+                &ix,
+                &[
+                    source_info.clone(),
+                    vault_info.clone(),
+                    user_info.clone(),
+                    token_program_info.clone(),
+                ],
+            )?;
+        }
+
+        stake.amount = stake
+            .amount
+            .checked_add(data.amount)
+            .ok_or(OneSolError::ConversionFailure)?;
+        pool.total_staked = pool
+            .total_staked
+            .checked_add(data.amount)
+            .ok_or(OneSolError::ConversionFailure)?;
+        stake.reward_debt = pool
+            .accrued_rewards(stake.amount)
+            .ok_or(OneSolError::ConversionFailure)?;
+        StakeAccount::pack(stake, &mut stake_info.data.borrow_mut())?;
+        StakePool::pack(pool, &mut pool_info.data.borrow_mut())
+    }
+
+    /// Processes an [Unstake](enum.Instruction.html).
+    pub fn process_unstake(
+        program_id: &Pubkey,
+        data: &StakeAmount,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let user_info = next_account_info(account_info_iter)?;
+        let pool_info = next_account_info(account_info_iter)?;
+        let pool_authority_info = next_account_info(account_info_iter)?;
+        let stake_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let rewards_vault_info = next_account_info(account_info_iter)?;
+        let rewards_destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if !user_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut pool = Self::load_stake_pool(program_id, pool_info)?;
+        Self::check_stake_vaults(
+            program_id,
+            &pool,
+            pool_info,
+            pool_authority_info,
+            vault_info,
+            rewards_vault_info,
+            token_program_info,
+        )?;
+        let mut stake = Self::load_stake_account(program_id, stake_info, user_info.key)?;
+        if data.amount > stake.amount {
+            return Err(OneSolError::InsufficientStake.into());
+        }
+
+        let rewards = stake
+            .pending_rewards(&pool)
+            .ok_or(OneSolError::ConversionFailure)?;
+        Payout::new(
+            pool_info.key,
+            pool.nonce,
+            token_program_info,
+            rewards_vault_info,
+            pool_authority_info,
+        )
+        .transfer(rewards_destination_info, rewards)?;
+        Payout::new(
+            pool_info.key,
+            pool.nonce,
+            token_program_info,
+            vault_info,
+            pool_authority_info,
+        )
+        .transfer(destination_info, data.amount)?;
+
+        stake.amount -= data.amount;
+        pool.total_staked -= data.amount;
+        stake.reward_debt = pool
+            .accrued_rewards(stake.amount)
+            .ok_or(OneSolError::ConversionFailure)?;
+        StakeAccount::pack(stake, &mut stake_info.data.borrow_mut())?;
+        StakePool::pack(pool, &mut pool_info.data.borrow_mut())
+    }
+
+    /// Processes an [FundStakeRewards](enum.Instruction.html).
+    pub fn process_fund_stake_rewards(
+        program_id: &Pubkey,
+        data: &StakeAmount,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let pool_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let rewards_vault_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let config = Self::load_config(program_id, config_info)?;
+        Self::check_config_admin(&config, admin_info)?;
+        let mut pool = Self::load_stake_pool(program_id, pool_info)?;
+        if *rewards_vault_info.key != pool.rewards_vault {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        if *token_program_info.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // without stakers, nobody could ever claim the rewards
+        pool.fund(data.amount).ok_or(OneSolError::InvalidInput)?;
+        let ix = spl_token::instruction::transfer(
+            token_program_info.key,
+            source_info.key,
+            rewards_vault_info.key,
+            admin_info.key,
+            &[],
+            data.amount,
+        )?;
+        invoke(
+            &ix,
+            &[
+                source_info.clone(),
+                rewards_vault_info.clone(),
+                admin_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+        StakePool::pack(pool, &mut pool_info.data.borrow_mut())
+    }
+
+    /// Processes an [ConfigureCircuitBreaker](enum.Instruction.html).
+    pub fn process_configure_circuit_breaker(
+        program_id: &Pubkey,
+        data: &ConfigureCircuitBreaker,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let accumulator_info = next_account_info(account_info_iter)?;
+
+        let config = Self::load_config(program_id, config_info)?;
+        Self::check_config_admin(&config, admin_info)?;
+        if accumulator_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if data.max_move_bps > 10_000 {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let mut accumulator = PriceAccumulator::unpack(&accumulator_info.data.borrow())?;
+        accumulator.breaker_max_move_bps = data.max_move_bps;
+        accumulator.breaker_window_slots = data.window_slots;
+        accumulator.reference_price = 0;
+        accumulator.reference_slot = 0;
+        PriceAccumulator::pack(accumulator, &mut accumulator_info.data.borrow_mut())
+    }
+
+    /// Processes an [AddOrderPage](enum.Instruction.html).
+    pub fn process_add_order_page(
+        program_id: &Pubkey,
+        page_index: u32,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer_info = next_account_info(account_info_iter)?;
+        let page_info = next_account_info(account_info_iter)?;
+        let source_mint_info = next_account_info(account_info_iter)?;
+        let destination_mint_info = next_account_info(account_info_iter)?;
+
+        if !payer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if source_mint_info.key == destination_mint_info.key {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        if page_index > 0 {
+            let previous_info = next_account_info(account_info_iter)?;
+            let (previous_key, _) = Self::find_order_page_address(
+                program_id,
+                source_mint_info.key,
+                destination_mint_info.key,
+                page_index - 1,
+            );
+            if *previous_info.key != previous_key || previous_info.owner != program_id {
+                return Err(OneSolError::InvalidProgramAddress.into());
+            }
+        }
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let (page_key, bump_seed) = Self::find_order_page_address(
+            program_id,
+            source_mint_info.key,
+            destination_mint_info.key,
+            page_index,
+        );
+        if *page_info.key != page_key {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+
+        let page_index_bytes = page_index.to_le_bytes();
+        Self::create_program_account(
+            program_id,
+            payer_info,
+            page_info,
+            system_program_info,
+            OrderPage::LEN,
+            &[
+                b"orders",
+                &source_mint_info.key.to_bytes(),
+                &destination_mint_info.key.to_bytes(),
+                &page_index_bytes,
+                &[bump_seed],
+            ],
+        )?;
+
+        let header = OrderPageHeader {
+            version: 1,
+            bump_seed,
+            page_index,
+            source_mint: *source_mint_info.key,
+            destination_mint: *destination_mint_info.key,
+            len: 0,
+        };
+        OrderPageHeader::pack(
+            header,
+            &mut page_info.data.borrow_mut()[..OrderPageHeader::LEN],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the minimum output of a swap of `amount_in` SOURCE tokens bounded by
+    /// the oracle price account following the dex accounts.
+    fn oracle_minimum_amount_out(
+        program_id: &Pubkey,
+        account_info_iter: &mut std::slice::Iter<AccountInfo>,
+        token_program_id: &Pubkey,
+        source_info: &AccountInfo,
+        destination_mint: &Pubkey,
+        amount_in: u64,
+        max_deviation_bps: u16,
+    ) -> Result<u64, ProgramError> {
+        let oracle_info = next_account_info(account_info_iter)?;
+        let source_mint_info = next_account_info(account_info_iter)?;
+        let destination_mint_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+
+        let source = unpack_token_account(source_info, token_program_id)?;
+        if source.mint != *source_mint_info.key || *destination_mint != *destination_mint_info.key {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let source_decimals = unpack_mint(source_mint_info, token_program_id)?.decimals;
+        let destination_decimals = unpack_mint(destination_mint_info, token_program_id)?.decimals;
+
+        let config = Self::load_config(program_id, config_info)?;
+        let price = Self::load_fresh_oracle_price(&config, oracle_info)?;
+        let max_deviation_bps =
+            price.widened_deviation_bps(max_deviation_bps, config.oracle_conf_multiplier);
+        let minimum_amount_out = price.minimum_amount_out(
+            amount_in,
+            source_decimals,
+            destination_decimals,
+            max_deviation_bps,
+        )?;
+        verbose!(
+            config.log_level,
+            "oracle minimum_amount_out: {}",
+            minimum_amount_out
+        );
+        Ok(minimum_amount_out)
+    }
+
+    /// Loads the global config.
+    pub(crate) fn load_config(
+        program_id: &Pubkey,
+        config_info: &AccountInfo,
+    ) -> Result<GlobalConfig, ProgramError> {
+        if config_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let config = GlobalConfig::unpack(&config_info.data.borrow())?;
+        if *config_info.key
+            != Pubkey::create_program_address(&[b"config", &[config.bump_seed]], program_id)?
+        {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        Ok(config)
+    }
+
+    /// Checks `admin_info` is the signing config admin. A governance admin signs through
+    /// the governance program executing a passed proposal.
+    fn check_config_admin(config: &GlobalConfig, admin_info: &AccountInfo) -> ProgramResult {
+        if *admin_info.key != config.admin {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        if !admin_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    }
+
+    /// Reads the stake pool, checking it is the program address of the pool.
+    fn load_stake_pool(
+        program_id: &Pubkey,
+        pool_info: &AccountInfo,
+    ) -> Result<StakePool, ProgramError> {
+        if pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let pool = StakePool::unpack(&pool_info.data.borrow())?;
+        if *pool_info.key
+            != Pubkey::create_program_address(&[b"stake_pool", &[pool.bump_seed]], program_id)?
+        {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        Ok(pool)
+    }
+
+    /// Reads the stake account of `owner`.
+    fn load_stake_account(
+        program_id: &Pubkey,
+        stake_info: &AccountInfo,
+        owner: &Pubkey,
+    ) -> Result<StakeAccount, ProgramError> {
+        if stake_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stake = StakeAccount::unpack(&stake_info.data.borrow())?;
+        if stake.owner != *owner {
+            return Err(OneSolError::InvalidStakeAccount.into());
+        }
+        if *stake_info.key
+            != Pubkey::create_program_address(
+                &[b"stake", &owner.to_bytes(), &[stake.bump_seed]],
+                program_id,
+            )?
+        {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        Ok(stake)
+    }
+
+    /// Checks the authority and vaults passed with the stake pool are its own.
+    fn check_stake_vaults(
+        program_id: &Pubkey,
+        pool: &StakePool,
+        pool_info: &AccountInfo,
+        pool_authority_info: &AccountInfo,
+        vault_info: &AccountInfo,
+        rewards_vault_info: &AccountInfo,
+        token_program_info: &AccountInfo,
+    ) -> ProgramResult {
+        if *pool_authority_info.key
+            != Pubkey::create_program_address(
+                &[&pool_info.key.to_bytes()[..32], &[pool.nonce]],
+                program_id,
+            )?
+        {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        if *vault_info.key != pool.vault || *rewards_vault_info.key != pool.rewards_vault {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        // a foreign token program could fake the deposit of a stake
+        if *token_program_info.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Ok(())
+    }
+
+    /// Returns `fee` less the stake discount of `payer` if the stake pool and the stake
+    /// account of `payer` are the next accounts, or `fee` if there are no more accounts.
+    fn stake_discounted_fee(
+        program_id: &Pubkey,
+        fee: u64,
+        payer: &Pubkey,
+        account_info_iter: &mut std::slice::Iter<AccountInfo>,
+    ) -> Result<u64, ProgramError> {
+        let pool_info = match account_info_iter.next() {
+            Some(pool_info) => pool_info,
+            None => return Ok(fee),
+        };
+        let stake_info = next_account_info(account_info_iter)?;
+        let pool = Self::load_stake_pool(program_id, pool_info)?;
+        let stake = Self::load_stake_account(program_id, stake_info, payer)?;
+        Ok(pool.discounted_fee(fee, stake.amount))
+    }
+
+    /// Returns the log level of the global config if passed among `accounts`, or full
+    /// logging. The program only writes accounts of exactly their state size, so an
+    /// initialized program-owned account of the config size is the config. `no-logs`
+    /// builds skip the lookup, logging nothing.
+    pub fn log_level(program_id: &Pubkey, accounts: &[AccountInfo]) -> LogLevel {
+        if cfg!(feature = "no-logs") {
+            return LogLevel::Off;
+        }
+        Self::find_config(program_id, accounts).map_or(LogLevel::Full, |config| config.log_level)
+    }
+
+    /// Returns the global config if passed among `accounts`, see [Processor::log_level].
+    fn find_config(program_id: &Pubkey, accounts: &[AccountInfo]) -> Option<GlobalConfig> {
+        accounts
+            .iter()
+            .filter(|account| {
+                account.owner == program_id && account.data_len() == GlobalConfig::LEN
+            })
+            .find_map(|account| GlobalConfig::unpack(&account.data.borrow()).ok())
+    }
+
+    /// Reads an oracle price, rejecting it if older than the configured max age.
+    pub(crate) fn load_fresh_oracle_price(
+        config: &GlobalConfig,
+        oracle_info: &AccountInfo,
+    ) -> Result<OraclePrice, ProgramError> {
+        let price = load_oracle_price(oracle_info)?;
+        if price.is_stale(Clock::get()?.slot, config.oracle_max_age_slots) {
+            return Err(OneSolError::OracleStale.into());
+        }
+        Ok(price)
+    }
+
+    /// Pays a keeper fee out of the `payout` of an order escrow.
+    fn pay_keeper_fee<'a>(
+        payout: &Payout<'_, 'a>,
+        order: &Order,
+        keeper_fee_info: &AccountInfo<'a>,
+        keeper_fee: u64,
+    ) -> ProgramResult {
+        if keeper_fee == 0 {
+            return Ok(());
+        }
+        payout.transfer(keeper_fee_info, keeper_fee)?;
+        event::emit(&FeeCollectedEvent {
+            kind: FeeKind::Keeper as u8,
+            payer: order.owner,
+            mint: order.source_mint,
+            amount: keeper_fee,
+            recipient: *keeper_fee_info.key,
+        });
+        Ok(())
+    }
+
+    /// Checks that fill proceeds go to the destination declared at placement, still
+    /// owned by the order owner, whatever accounts the keeper passes.
+    fn check_order_destination(order: &Order, destination_info: &AccountInfo) -> ProgramResult {
+        if *destination_info.key != order.destination {
+            return Err(OneSolError::IncorrectDestinationAccount.into());
+        }
+        let destination = unpack_token_account(destination_info, &order.token_program_id)?;
+        if destination.owner != order.owner || destination.mint != order.destination_mint {
+            return Err(OneSolError::IncorrectDestinationAccount.into());
+        }
+        Ok(())
+    }
+
+    /// Checks the legs signed by the program spent exactly `amount_in` of `source_info`,
+    /// holding `amount_before` ahead of them: a leg can't pull more of the source than
+    /// routed through it.
+    fn check_source_spent(
+        source_info: &AccountInfo,
+        token_program_id: &Pubkey,
+        amount_before: u64,
+        amount_in: u64,
+    ) -> ProgramResult {
+        let source = unpack_token_account(source_info, token_program_id)?;
+        if amount_before.checked_sub(source.amount) != Some(amount_in) {
+            return Err(OneSolError::SourceAmountMismatch.into());
+        }
+        Ok(())
+    }
+
+    /// Returns the amount of an order fill, `amount_in` or the rest of the current slice
+    /// if 0, and its minimum output: the pro-rata share of the expected output, less the
+    /// allowed deviation.
+    fn order_fill_amounts(
+        order: &Order,
+        amount_in: u64,
+        now: i64,
+    ) -> Result<(u64, u64), ProgramError> {
+        let slice_start =
+            order.start_time + order.slices_filled as i64 * order.slice_interval as i64;
+        if now < slice_start {
+            return Err(OneSolError::OrderNotReady.into());
+        }
+
+        let slice_remaining = order.current_slice_target() - order.filled_amount_in;
+        let fill_amount_in = if amount_in == 0 {
+            slice_remaining
+        } else if amount_in <= slice_remaining {
+            amount_in
+        } else {
+            return Err(OneSolError::InvalidInput.into());
+        };
+        let fill_expected_amount_out =
+            mul_div_floor(order.expected_amount_out, fill_amount_in, order.amount_in)?;
+        let fill_minimum_amount_out = less_bps(fill_expected_amount_out, order.max_deviation_bps)?;
+        Ok((fill_amount_in, fill_minimum_amount_out))
+    }
+
+    /// Records an order fill, returning true if it completes the current slice.
+    fn record_order_fill(
+        order: &mut Order,
+        fill_amount_in: u64,
+        fill_amount_out: u64,
+    ) -> Result<bool, ProgramError> {
+        let slice_target = order.current_slice_target();
+        order.filled_amount_in += fill_amount_in;
+        order.filled_amount_out = order
+            .filled_amount_out
+            .checked_add(fill_amount_out)
+            .ok_or(OneSolError::ConversionFailure)?;
+        let completes_slice = order.filled_amount_in == slice_target;
+        if completes_slice {
+            order.slices_filled += 1;
+        }
+        Ok(completes_slice)
+    }
+
+    /// Builds a swapper per dex config, consuming each dex's accounts from the iterator.
+    ///
+    /// Legs swapping none of `amounts` only have their accounts skipped: they get no
+    /// swapper, nor any check of their dex.
+    fn load_swappers<'a, 'i: 'a, 'b: 'i>(
+        dex_configs: DexConfigs,
+        amounts: &[u64],
+        account_info_iter: &mut std::slice::Iter<'i, AccountInfo<'b>>,
+        context: &'a SwapContext<'a, 'b>,
+    ) -> Result<ArrayVec<Option<DexSwapper<'a, 'b>>, MAX_LEGS>, ProgramError> {
+        let mut swappers = ArrayVec::new();
+        for (dex_config, amount) in dex_configs.iter().zip(amounts) {
+            let dex_accounts = next_account_infos(account_info_iter, dex_config.account_size)?;
+            if *amount == 0 {
+                swappers.push(None);
+                continue;
+            }
+            swappers.push(Some(DexSwapper::from_accounts(
+                context,
+                &dex_config,
+                dex_accounts,
+            )?));
+        }
+        Ok(swappers)
+    }
+
+    /// Builds the swappers of a swap route as [Processor::load_swappers] does, skipping
+    /// the legs of dex types no adapter is written for without consuming their accounts,
+    /// as `Swap` always has.
+    fn load_swap_swappers<'a, 'i: 'a, 'b: 'i>(
+        dex_configs: DexConfigs,
+        amounts: &[u64],
+        account_info_iter: &mut std::slice::Iter<'i, AccountInfo<'b>>,
+        context: &'a SwapContext<'a, 'b>,
+    ) -> Result<ArrayVec<Option<DexSwapper<'a, 'b>>, MAX_LEGS>, ProgramError> {
+        let mut swappers = ArrayVec::new();
+        for (dex_config, amount) in dex_configs.iter().zip(amounts) {
+            if !swappers::KNOWN_DEX_TYPES.contains(&dex_config.dex_type) {
+                swappers.push(None);
+                continue;
+            }
+            let dex_accounts = next_account_infos(account_info_iter, dex_config.account_size)?;
+            if *amount == 0 {
+                swappers.push(None);
+                continue;
+            }
+            swappers.push(Some(DexSwapper::from_accounts(
+                context,
+                &dex_config,
+                dex_accounts,
+            )?));
+        }
+        Ok(swappers)
+    }
+
+    /// Logs the compute units left `phase` ("before" / "after") the CPI of dex leg
+    /// `leg_index`, in a line per phase tools can diff across releases. Compiled in only
+    /// with the `cu-telemetry` feature.
+    #[cfg(feature = "cu-telemetry")]
+    fn log_leg_compute_units(leg_index: usize, dex_config: &DexConfig, phase: &str) {
+        msg!(
+            "cu-telemetry: leg={} dex_type={} phase={}",
+            leg_index,
+            dex_config.dex_type,
+            phase
+        );
+        solana_program::log::sol_log_compute_units();
+    }
+
+    #[cfg(not(feature = "cu-telemetry"))]
+    #[inline(always)]
+    fn log_leg_compute_units(_leg_index: usize, _dex_config: &DexConfig, _phase: &str) {}
+
+    /// Logs the compute units left ahead of the payout of a swap, as
+    /// [Processor::log_leg_compute_units] does for its legs, and at full log level in
+    /// `debug-logs` builds.
+    #[cfg(any(feature = "cu-telemetry", feature = "debug-logs"))]
+    fn log_settle_compute_units(log_level: LogLevel) {
+        if cfg!(feature = "cu-telemetry") {
+            msg!("cu-telemetry: phase=settle");
+        } else if !log_level.logs_progress() {
+            return;
+        }
+        solana_program::log::sol_log_compute_units();
+    }
+
+    #[cfg(not(any(feature = "cu-telemetry", feature = "debug-logs")))]
+    #[inline(always)]
+    fn log_settle_compute_units(_log_level: LogLevel) {}
+
+    /// Logs and wraps the error dex leg `leg_index` failed with.
+    fn leg_error(
+        log_level: LogLevel,
+        leg_index: usize,
+        dex_config: &DexConfig,
+        error: ProgramError,
+    ) -> ProgramError {
+        let leg_error = LegError::new(leg_index as u8, dex_config.dex_type, &error);
+        if log_level.logs_errors() {
+            msg!(
+                "leg {} (dex_type {}) failed: {}",
+                leg_index,
+                dex_config.dex_type,
+                error
+            );
+        }
+        leg_error.into()
+    }
+
+    /// Bounds the output of each leg of a route by its own minimum, or else by its share
+    /// of the route's `minimum_amount_out`.
+    fn leg_minimums(
+        minimum_amount_out: u64,
+        dex_configs: DexConfigs,
+    ) -> Result<ArrayVec<u64, MAX_LEGS>, ProgramError> {
+        let mut minimums = Self::split_by_ratio(minimum_amount_out, dex_configs)?;
+        for (minimum, dex_config) in minimums.iter_mut().zip(dex_configs.iter()) {
+            if let Some(leg_minimum) = dex_config.minimum_amount_out {
+                *minimum = leg_minimum;
+            }
+        }
+        Ok(minimums)
+    }
+
+    /// Splits `amount` across the dexes by their ratios in basis points, which must sum
+    /// to [BPS_SCALE], the last dex receiving the rounding remainder.
+    fn split_by_ratio(
+        amount: u64,
+        dex_configs: DexConfigs,
+    ) -> Result<ArrayVec<u64, MAX_LEGS>, ProgramError> {
+        let total_ratio: u32 = dex_configs.iter().map(|c| c.ratio as u32).sum();
+        if total_ratio != BPS_SCALE as u32 {
+            return Err(OneSolError::RatioSumInvalid.into());
+        }
+        let mut amounts = ArrayVec::new();
+        let mut remaining = amount;
+        for (i, dex_config) in dex_configs.iter().enumerate() {
+            let part = if i + 1 == dex_configs.len() {
+                remaining
+            } else {
+                (amount as u128)
+                    .checked_mul(dex_config.ratio as u128)
+                    .and_then(|part| part.checked_div(BPS_SCALE as u128))
+                    .and_then(u64::from_u128)
+                    .ok_or(OneSolError::ConversionFailure)?
+            };
+            remaining -= part;
+            amounts.push(part);
+        }
+        Ok(amounts)
+    }
+
+    /// Calculates the authority id by generating a program address.
+    ///
+    /// Instructions check the authority of a nonce from their data, the ones creating a
+    /// state then persisting it. Instructions reading the nonce from a state sign with
+    /// it instead of deriving the authority again: the runtime only grants the signature
+    /// to the address derived from it, so the token program rejects any other authority
+    /// moving the state's tokens.
+    pub fn authority_id(
+        program_id: &Pubkey,
+        my_info: &Pubkey,
+        nonce: u8,
+    ) -> Result<Pubkey, OneSolError> {
+        Pubkey::create_program_address(&[&my_info.to_bytes()[..32], &[nonce]], program_id)
+            .or(Err(OneSolError::InvalidProgramAddress))
+    }
+
+    /// Finds the authority of the state `state` and its nonce, see [authority_id].
+    ///
+    /// [authority_id]: Processor::authority_id
+    pub fn find_authority_address(program_id: &Pubkey, state: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[&state.to_bytes()[..32]], program_id)
+    }
+
+    /// Records a swap in the price accumulator of its pair.
+    fn record_swap_price(
+        program_id: &Pubkey,
+        accumulator_info: &AccountInfo,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+        amount_in: u64,
+        amount_out: u64,
+    ) -> ProgramResult {
+        if accumulator_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut accumulator = PriceAccumulator::unpack(&accumulator_info.data.borrow())?;
+        if accumulator.source_mint != *source_mint
+            || accumulator.destination_mint != *destination_mint
+        {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let clock = Clock::get()?;
+        accumulator.record(clock.unix_timestamp, clock.slot, amount_in, amount_out)?;
+        PriceAccumulator::pack(accumulator, &mut accumulator_info.data.borrow_mut())
+    }
+
+    /// Records a swap in the statistics of its SOURCE or DESTINATION mint.
+    fn record_mint_stats(
+        program_id: &Pubkey,
+        stats_info: &AccountInfo,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+        amount_in: u64,
+        amount_out: u64,
+        fee: u64,
+    ) -> ProgramResult {
+        if stats_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stats = MintStats::unpack(&stats_info.data.borrow())?;
+        if stats.mint == *source_mint {
+            stats.record_in(amount_in);
+        } else if stats.mint == *destination_mint {
+            stats.record_out(amount_out, fee);
+        } else {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        MintStats::pack(stats, &mut stats_info.data.borrow_mut())
+    }
+
+    /// Checks, in `permissioned` builds, that `user` holds the credential the gate of the
+    /// pair requires, taking the gate and credential accounts off `account_info_iter`.
+    fn check_pair_gate(
+        program_id: &Pubkey,
+        user: &Pubkey,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+        account_info_iter: &mut std::slice::Iter<AccountInfo>,
+    ) -> ProgramResult {
+        if !cfg!(feature = "permissioned") {
+            return Ok(());
+        }
+        let gate_info = next_account_info(account_info_iter)?;
+        let credential_info = next_account_info(account_info_iter)?;
+        Self::check_credential(
+            program_id,
+            gate_info,
+            credential_info,
+            user,
+            source_mint,
+            destination_mint,
+        )
+    }
+
+    /// Checks the trader holds the credential the gate of the pair requires. A gate that
+    /// was never created, or lost its credential mint, leaves the pair open.
+    fn check_credential(
+        program_id: &Pubkey,
+        gate_info: &AccountInfo,
+        credential_info: &AccountInfo,
+        user: &Pubkey,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+    ) -> ProgramResult {
+        let (gate_key, _) = Self::find_pair_gate_address(program_id, source_mint, destination_mint);
+        if *gate_info.key != gate_key {
+            return Err(OneSolError::InvalidProgramAddress.into());
+        }
+        if gate_info.owner != program_id {
+            return Ok(());
+        }
+        let gate = PairGate::unpack(&gate_info.data.borrow())?;
+        if !gate.is_flagged() {
+            return Ok(());
+        }
+        let credential = unpack_token_account(credential_info, &spl_token::id())
+            .map_err(|_| OneSolError::MissingCredential)?;
+        if credential.mint != gate.credential_mint
+            || credential.owner != *user
+            || credential.amount < gate.min_balance
+        {
+            return Err(OneSolError::MissingCredential.into());
+        }
+        Ok(())
+    }
+
+    /// Credits the user with the liquidity-mining points a swap of `amount_in` earns on
+    /// the pair of `rewards_info`.
+    #[allow(clippy::too_many_arguments)]
+    fn accrue_points(
+        program_id: &Pubkey,
+        rewards_info: &AccountInfo,
+        points_info: &AccountInfo,
+        user: &Pubkey,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+        amount_in: u64,
+    ) -> ProgramResult {
+        if rewards_info.owner != program_id || points_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut rewards = PairRewards::unpack(&rewards_info.data.borrow())?;
+        if rewards.source_mint != *source_mint || rewards.destination_mint != *destination_mint {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let mut points = RewardPoints::unpack(&points_info.data.borrow())?;
+        if points.owner != *user {
+            return Err(OneSolError::InvalidOwner.into());
+        }
+        let earned = rewards.points(amount_in);
+        points.points = points.points.saturating_add(earned);
+        rewards.total_points = rewards.total_points.saturating_add(earned);
+        PairRewards::pack(rewards, &mut rewards_info.data.borrow_mut())?;
+        RewardPoints::pack(points, &mut points_info.data.borrow_mut())
+    }
+
+    /// Writes the receipt of a swap into a fresh receipt account, owned by the program.
+    /// Receipts are never overwritten.
+    fn write_swap_receipt(
+        program_id: &Pubkey,
+        receipt_info: &AccountInfo,
+        receipt: &SwapReceipt,
+    ) -> ProgramResult {
+        if receipt_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if SwapReceipt::unpack_unchecked(&receipt_info.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        SwapReceipt::pack(*receipt, &mut receipt_info.data.borrow_mut())
+    }
+
+    /// Hashes the route of a swap: each dex type and ratio followed by the keys of its
+    /// accounts.
+    fn route_hash(dex_configs: DexConfigs, dex_accounts: &[AccountInfo]) -> Hash {
+        route_hash(
+            dex_configs.iter(),
+            dex_accounts.iter().map(|account| account.key),
+        )
+    }
+
+    /// Finds the address of the swap commitment `commitment` of `user`.
+    pub fn find_swap_commitment_address(
+        program_id: &Pubkey,
+        user: &Pubkey,
+        commitment: &[u8; 32],
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"commit", &user.to_bytes(), commitment], program_id)
+    }
+
+    /// Finds the liquidity-mining rewards address of a mint pair.
+    pub fn find_pair_rewards_address(
+        program_id: &Pubkey,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                b"rewards",
+                &source_mint.to_bytes(),
+                &destination_mint.to_bytes(),
+            ],
+            program_id,
+        )
+    }
+
+    /// Finds the credential gate address of a mint pair.
+    pub fn find_pair_gate_address(
+        program_id: &Pubkey,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                b"gate",
+                &source_mint.to_bytes(),
+                &destination_mint.to_bytes(),
+            ],
+            program_id,
+        )
+    }
+
+    /// Finds the reward points address of a user.
+    pub fn find_reward_points_address(program_id: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"points", &owner.to_bytes()], program_id)
+    }
+
+    /// Finds the authority of the liquidity-mining rewards vault.
+    pub fn find_rewards_vault_authority(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"mining"], program_id)
+    }
+
+    /// Finds the authority of the protocol-owned inventory vaults.
+    pub fn find_inventory_authority(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"inventory"], program_id)
+    }
+
+    /// Finds the inventory pair address of a mint pair.
+    pub fn find_inventory_pair_address(
+        program_id: &Pubkey,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                b"inventory",
+                &source_mint.to_bytes(),
+                &destination_mint.to_bytes(),
+            ],
+            program_id,
+        )
+    }
+
+    /// Finds the stake pool address.
+    pub fn find_stake_pool_address(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"stake_pool"], program_id)
+    }
+
+    /// Finds the stake account address of a staker.
+    pub fn find_stake_address(program_id: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"stake", &owner.to_bytes()], program_id)
+    }
+
+    /// Finds the swap statistics address of a mint.
+    pub fn find_mint_stats_address(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"stats", &mint.to_bytes()], program_id)
+    }
+
+    /// Finds the vault of `mint` of the 1sol protocol account `protocol`.
+    pub fn find_vault_address(
+        program_id: &Pubkey,
+        protocol: &Pubkey,
+        mint: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[b"vault", &protocol.to_bytes(), &mint.to_bytes()],
+            program_id,
+        )
+    }
+
+    /// Finds the Serum open orders of `market` owned by the authority of the 1sol
+    /// protocol account `protocol`.
+    pub fn find_open_orders_address(
+        program_id: &Pubkey,
+        protocol: &Pubkey,
+        market: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[b"open_orders", &protocol.to_bytes(), &market.to_bytes()],
+            program_id,
+        )
+    }
+
+    /// Finds the price accumulator address of a mint pair.
+    pub fn find_price_accumulator_address(
+        program_id: &Pubkey,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                b"twap",
+                &source_mint.to_bytes(),
+                &destination_mint.to_bytes(),
+            ],
+            program_id,
+        )
+    }
+
+    /// Returns a packed result to the caller, through the return data of the instruction.
+    fn set_return_data<T: Pack>(result: &T) {
+        let mut data = vec![0u8; T::LEN];
+        result.pack_into_slice(&mut data);
+        solana_program::program::set_return_data(&data);
+    }
+
+    /// Creates a rent-exempt account of `space` bytes owned by the program at the
+    /// program address derived from `seeds`.
+    fn create_program_account<'a>(
+        program_id: &Pubkey,
+        payer_info: &AccountInfo<'a>,
+        account_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        space: usize,
+        seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let ix = system_instruction::create_account(
+            payer_info.key,
+            account_info.key,
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            program_id,
+        );
+        invoke_signed(
+            &ix,
+            &[
+                payer_info.clone(),
+                account_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[seeds],
+        )
+    }
+
+    /// Finds the global config address.
+    pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"config"], program_id)
+    }
+
+    /// Finds the order page address of a mint pair.
+    pub fn find_order_page_address(
+        program_id: &Pubkey,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+        page_index: u32,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                b"orders",
+                &source_mint.to_bytes(),
+                &destination_mint.to_bytes(),
+                &page_index.to_le_bytes(),
+            ],
+            program_id,
+        )
+    }
+
+    /// Issue a spl_token `Transfer` instruction.
+    pub fn token_transfer<'a>(
         swap: &Pubkey,
         token_program: AccountInfo<'a>,
         source: AccountInfo<'a>,
@@ -242,27 +5139,218 @@ impl Processor {
         authority: AccountInfo<'a>,
         nonce: u8,
         amount: u64,
+    ) -> Result<(), ProgramError> {
+        Payout::new(swap, nonce, &token_program, &source, &authority).transfer(&destination, amount)
+    }
+
+    /// Issue a spl_token `CloseAccount` instruction.
+    pub fn token_close_account<'a>(
+        swap: &Pubkey,
+        token_program: AccountInfo<'a>,
+        account: AccountInfo<'a>,
+        destination: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        nonce: u8,
     ) -> Result<(), ProgramError> {
         let swap_bytes = swap.to_bytes();
         let authority_signature_seeds = [&swap_bytes[..32], &[nonce]];
         let signers = &[&authority_signature_seeds[..]];
-        let ix = spl_token::instruction::transfer(
+        let ix = spl_token::instruction::close_account(
             token_program.key,
-            source.key,
+            account.key,
             destination.key,
             authority.key,
             &[],
-            amount,
         )?;
-        // invoke(&ix, &[source, destination, authority, token_program])
         invoke_signed(
             &ix,
-            &[source, destination, authority, token_program],
+            &[account, destination, authority, token_program],
             signers,
         )
     }
 }
 
+/// Fixed accounts of a swap, ahead of the accounts of its legs.
+struct SwapAccounts<'a, 'b> {
+    protocol: &'a AccountInfo<'b>,
+    protocol_authority: &'a AccountInfo<'b>,
+    user_transfer_authority: &'a AccountInfo<'b>,
+    protocol_token: &'a AccountInfo<'b>,
+    source: &'a AccountInfo<'b>,
+    destination: &'a AccountInfo<'b>,
+    token_program: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> SwapAccounts<'a, 'b> {
+    fn parse(
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    ) -> Result<Self, ProgramError> {
+        Ok(Self {
+            protocol: next_account_info(account_info_iter)?,
+            protocol_authority: next_account_info(account_info_iter)?,
+            user_transfer_authority: next_account_info(account_info_iter)?,
+            protocol_token: next_account_info(account_info_iter)?,
+            source: next_account_info(account_info_iter)?,
+            destination: next_account_info(account_info_iter)?,
+            token_program: next_account_info(account_info_iter)?,
+        })
+    }
+}
+
+/// What a swap keeps of its accounts once validated, rather than their unpacked states.
+struct ValidatedSwap {
+    nonce: u8,
+    protocol_token_amount: u64,
+    destination_mint: Pubkey,
+    fee_numerator: u64,
+    fee_denominator: u64,
+    treasury: Pubkey,
+    max_referral_fee_bps: u16,
+}
+
+impl ValidatedSwap {
+    /// Protocol fee of a swap outputting `amount_out`, rounded down.
+    fn protocol_fee(&self, amount_out: u64) -> Result<u64, OneSolError> {
+        OneSolState::fee(amount_out, self.fee_numerator, self.fee_denominator)
+    }
+
+    /// Output of a swap leaving at least `amount_out` to the user, net of the protocol
+    /// fee.
+    fn gross_amount_out(&self, amount_out: u64) -> Result<u64, OneSolError> {
+        if self.fee_numerator == 0 {
+            return Ok(amount_out);
+        }
+        let net_denominator = self
+            .fee_denominator
+            .checked_sub(self.fee_numerator)
+            .filter(|&denominator| denominator > 0)
+            .ok_or(OneSolError::ConversionFailure)?;
+        mul_div_ceil(amount_out, self.fee_denominator, net_denominator)
+    }
+
+    /// Referral fee of `referral_fee_bps` of `amount_out`, rounded down, failing past
+    /// the cap of the protocol.
+    fn referral_fee(&self, amount_out: u64, referral_fee_bps: u16) -> Result<u64, OneSolError> {
+        if referral_fee_bps > self.max_referral_fee_bps {
+            return Err(OneSolError::ReferralFeeTooHigh);
+        }
+        apply_bps(amount_out, referral_fee_bps, Rounding::Down)
+    }
+}
+
+/// Outcome of the legs of a swap, before settling it.
+struct SwapExecution {
+    /// Output paid to the user, net of `fee`
+    amount_out: u64,
+    /// Part of the output reimbursing a relayer
+    fee: u64,
+    oracle_minimum_amount_out: u64,
+    /// Heap-held, so growing routes don't grow the frames settling them
+    legs: Vec<LegReport>,
+    /// Share of the output net of the protocol fee owed to a referrer, in bps
+    referral_fee_bps: u16,
+    /// Whether the legs output into DESTINATION, leaving nothing to pay out
+    direct: bool,
+}
+
+/// Token account paying out of a program authority.
+///
+/// Holds the signer seeds and accounts of the authority once, so the fee and user
+/// transfers closing an instruction each cost a single CPI and nothing more.
+pub struct Payout<'a, 'b> {
+    token_program: &'a AccountInfo<'b>,
+    source: &'a AccountInfo<'b>,
+    authority: &'a AccountInfo<'b>,
+    mint: Option<(&'a AccountInfo<'b>, u8)>,
+    seed: [u8; 32],
+    nonce: [u8; 1],
+}
+
+impl<'a, 'b> Payout<'a, 'b> {
+    /// Pays out of `source`, owned by the authority of `seed` and `nonce`.
+    pub fn new(
+        seed: &Pubkey,
+        nonce: u8,
+        token_program: &'a AccountInfo<'b>,
+        source: &'a AccountInfo<'b>,
+        authority: &'a AccountInfo<'b>,
+    ) -> Self {
+        Self {
+            token_program,
+            source,
+            authority,
+            mint: None,
+            seed: seed.to_bytes(),
+            nonce: [nonce],
+        }
+    }
+
+    /// Pays with `TransferChecked` against `mint` of `decimals`, as Token-2022 mints
+    /// with a transfer fee require.
+    pub fn with_mint(mut self, mint: &'a AccountInfo<'b>, decimals: u8) -> Self {
+        self.mint = Some((mint, decimals));
+        self
+    }
+
+    /// Transfers `amount` to `destination`, skipping the CPI for nothing.
+    pub fn transfer(&self, destination: &AccountInfo<'b>, amount: u64) -> ProgramResult {
+        if amount == 0 {
+            return Ok(());
+        }
+        // the signature of the authority must only ever reach the token program
+        if !token_2022::is_token_program(self.token_program.key) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let signers_seeds: &[&[&[u8]]] = &[&[&self.seed[..], &self.nonce[..]]];
+        match self.mint {
+            Some((mint, decimals)) => {
+                let ix = Instruction {
+                    program_id: *self.token_program.key,
+                    accounts: vec![
+                        AccountMeta::new(*self.source.key, false),
+                        AccountMeta::new_readonly(*mint.key, false),
+                        AccountMeta::new(*destination.key, false),
+                        AccountMeta::new_readonly(*self.authority.key, true),
+                    ],
+                    data: token_2022::transfer_checked_data(amount, decimals).to_vec(),
+                };
+                invoke_signed(
+                    &ix,
+                    &[
+                        self.source.clone(),
+                        mint.clone(),
+                        destination.clone(),
+                        self.authority.clone(),
+                        self.token_program.clone(),
+                    ],
+                    signers_seeds,
+                )
+            }
+            None => {
+                let ix = Instruction {
+                    program_id: *self.token_program.key,
+                    accounts: vec![
+                        AccountMeta::new(*self.source.key, false),
+                        AccountMeta::new(*destination.key, false),
+                        AccountMeta::new_readonly(*self.authority.key, true),
+                    ],
+                    data: token_transfer_data(amount).to_vec(),
+                };
+                invoke_signed(
+                    &ix,
+                    &[
+                        self.source.clone(),
+                        destination.clone(),
+                        self.authority.clone(),
+                        self.token_program.clone(),
+                    ],
+                    signers_seeds,
+                )
+            }
+        }
+    }
+}
+
 impl PrintProgramError for OneSolError {
     fn print<E>(&self)
     where
@@ -283,23 +5371,897 @@ impl PrintProgramError for OneSolError {
             OneSolError::ConversionFailure => msg!("Error: ConversionFailure"),
             OneSolError::ZeroTradingTokens => msg!("Error: ZeroTradingTokens"),
             OneSolError::InternalError => msg!("Error: InternalError"),
+            OneSolError::OrderNotReady => msg!("Error: OrderNotReady"),
+            OneSolError::OrderFilled => msg!("Error: OrderFilled"),
+            OneSolError::InvalidOracle => msg!("Error: InvalidOracle"),
+            OneSolError::OrderNotTriggered => msg!("Error: OrderNotTriggered"),
+            OneSolError::OrderExpired => msg!("Error: OrderExpired"),
+            OneSolError::OrderActive => msg!("Error: OrderActive"),
+            OneSolError::PaymentNotDue => msg!("Error: PaymentNotDue"),
+            OneSolError::PaymentCapExceeded => msg!("Error: PaymentCapExceeded"),
+            OneSolError::OrderPageFull => msg!("Error: OrderPageFull"),
+            OneSolError::IncorrectDestinationAccount => {
+                msg!("Error: IncorrectDestinationAccount")
+            }
+            OneSolError::OracleStale => msg!("Error: OracleStale"),
+            OneSolError::OraclePriceDeviation => msg!("Error: OraclePriceDeviation"),
+            OneSolError::ExpectedMint => msg!("Error: ExpectedMint"),
+            OneSolError::CircuitBreakerTripped => msg!("Error: CircuitBreakerTripped"),
+            OneSolError::FlashSwapNotRepaid => msg!("Error: FlashSwapNotRepaid"),
+            OneSolError::FlashLoanNotRepaid => msg!("Error: FlashLoanNotRepaid"),
+            OneSolError::CommitmentMismatch => msg!("Error: CommitmentMismatch"),
+            OneSolError::CommitmentTooRecent => msg!("Error: CommitmentTooRecent"),
+            OneSolError::InvalidTipAccount => msg!("Error: InvalidTipAccount"),
+            OneSolError::InvalidStakeAccount => msg!("Error: InvalidStakeAccount"),
+            OneSolError::InsufficientStake => msg!("Error: InsufficientStake"),
+            OneSolError::MissingCredential => msg!("Error: MissingCredential"),
+            OneSolError::RelayerFeeTooHigh => msg!("Error: RelayerFeeTooHigh"),
+            OneSolError::InsufficientInventory => msg!("Error: InsufficientInventory"),
+            OneSolError::RatioSumInvalid => msg!("Error: RatioSumInvalid"),
+            OneSolError::DexProgramNotAllowed => msg!("Error: DexProgramNotAllowed"),
+            OneSolError::AccountCountMismatch => msg!("Error: AccountCountMismatch"),
+            OneSolError::Paused => msg!("Error: Paused"),
+            OneSolError::InsufficientDelegation => msg!("Error: InsufficientDelegation"),
+            OneSolError::AccountNotWritable => msg!("Error: AccountNotWritable"),
+            OneSolError::InvalidTreasury => msg!("Error: InvalidTreasury"),
+            OneSolError::QuoteUnavailable => msg!("Error: QuoteUnavailable"),
+            OneSolError::ReferralFeeTooHigh => msg!("Error: ReferralFeeTooHigh"),
+            OneSolError::SourceAmountMismatch => msg!("Error: SourceAmountMismatch"),
         }
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-
-//     // #[test]
-//     // fn test_find_best_parts() {
-//     //     let r = find_best_parts(10, 2);
-//     //     assert_eq!(r, 8);
-//     //     let r = find_best_parts(10, 8);
-//     //     assert_eq!(r, 2);
-//     //     let r = find_best_parts(10, 9);
-//     //     assert_eq!(r, 2);
-//     //     let r = find_best_parts(10, 1);
-//     //     assert_eq!(r, 16);
-//     // }
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        curve::{CurveFees, SwapCurve},
+        instruction::MAX_SPLIT_PARTS,
+        math::Fraction,
+        swappers::mock::{MockOutcome, MockSwapper},
+    };
+    use proptest::prelude::*;
+    use solana_program::sysvar::instructions::{BorrowedAccountMeta, BorrowedInstruction};
+
+    fn flash_instruction(
+        program_id: &Pubkey,
+        tag: u8,
+        vault_index: usize,
+        vault: &Pubkey,
+    ) -> Instruction {
+        let mut accounts: Vec<AccountMeta> = (0..6)
+            .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+            .collect();
+        accounts[vault_index] = AccountMeta::new(*vault, false);
+        let mut data = vec![tag];
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        Instruction {
+            program_id: *program_id,
+            accounts,
+            data,
+        }
+    }
+
+    fn instructions_sysvar(instructions: &[Instruction], current_index: u16) -> Vec<u8> {
+        let borrowed: Vec<BorrowedInstruction> = instructions
+            .iter()
+            .map(|instruction| BorrowedInstruction {
+                program_id: &instruction.program_id,
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|meta| BorrowedAccountMeta {
+                        pubkey: &meta.pubkey,
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    })
+                    .collect(),
+                data: &instruction.data,
+            })
+            .collect();
+        let mut data = sysvar::instructions::construct_instructions_data(&borrowed);
+        sysvar::instructions::store_current_index(&mut data, current_index);
+        data
+    }
+
+    /// Packs the dex configs of `(dex_type, account_size, ratio)` triples.
+    fn dex_configs_data(configs: &[(u8, usize, u16)]) -> Vec<u8> {
+        let mut data = vec![];
+        for &(dex_type, account_size, ratio) in configs.iter() {
+            DexConfig::new_dex_config(dex_type, account_size, ratio)
+                .pack(&mut data)
+                .unwrap();
+        }
+        data
+    }
+
+    /// An account the tests own the key, lamports and data of, lent to the processor as
+    /// an `AccountInfo`.
+    struct TestAccount {
+        key: Pubkey,
+        owner: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+        is_signer: bool,
+    }
+
+    impl TestAccount {
+        fn new(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> Self {
+            Self {
+                key,
+                owner,
+                lamports: 0,
+                data,
+                is_signer: false,
+            }
+        }
+
+        fn signer(mut self) -> Self {
+            self.is_signer = true;
+            self
+        }
+
+        fn token(mint: &Pubkey, owner: &Pubkey, delegate: Option<Pubkey>, amount: u64) -> Self {
+            let mut data = vec![0; spl_token::state::Account::LEN];
+            spl_token::state::Account {
+                mint: *mint,
+                owner: *owner,
+                amount,
+                delegate: delegate.into(),
+                state: spl_token::state::AccountState::Initialized,
+                ..Default::default()
+            }
+            .pack_into_slice(&mut data);
+            Self::new(Pubkey::new_unique(), spl_token::id(), data)
+        }
+
+        fn info(&mut self) -> AccountInfo<'_> {
+            AccountInfo::new(
+                &self.key,
+                self.is_signer,
+                true,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                false,
+                0,
+            )
+        }
+    }
+
+    fn infos(accounts: &mut [TestAccount]) -> Vec<AccountInfo<'_>> {
+        accounts.iter_mut().map(TestAccount::info).collect()
+    }
+
+    /// Accounts of `Initialize`: the protocol, its authority, token, token program and
+    /// admin.
+    fn initialize_accounts(program_id: &Pubkey) -> (u8, Vec<TestAccount>) {
+        let protocol =
+            TestAccount::new(Pubkey::new_unique(), *program_id, vec![0; OneSolState::LEN]);
+        let (authority, nonce) =
+            Pubkey::find_program_address(&[&protocol.key.to_bytes()], program_id);
+        let token = TestAccount::token(&Pubkey::new_unique(), &authority, None, 0);
+        let accounts = vec![
+            protocol,
+            TestAccount::new(authority, Pubkey::default(), vec![]),
+            token,
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]).signer(),
+        ];
+        (nonce, accounts)
+    }
+
+    #[test]
+    fn test_process_initialize() {
+        let program_id = Pubkey::new_unique();
+        let (nonce, mut accounts) = initialize_accounts(&program_id);
+        Processor::process_initialize(&program_id, nonce, &infos(&mut accounts)).unwrap();
+        assert_eq!(
+            OneSolState::unpack(&accounts[0].data).unwrap(),
+            OneSolState {
+                version: 1,
+                nonce,
+                token_program_id: spl_token::id(),
+                token: accounts[2].key,
+                token_mint: spl_token::state::Account::unpack(&accounts[2].data)
+                    .unwrap()
+                    .mint,
+                admin: accounts[4].key,
+                fee_numerator: 0,
+                fee_denominator: 0,
+                treasury: Pubkey::default(),
+                is_paused: false,
+                max_referral_fee_bps: 0,
+            }
+        );
+
+        // an authority of another nonce
+        let (nonce, mut accounts) = initialize_accounts(&program_id);
+        assert_eq!(
+            Processor::process_initialize(
+                &program_id,
+                nonce.wrapping_sub(1),
+                &infos(&mut accounts)
+            ),
+            Err(OneSolError::InvalidProgramAddress.into())
+        );
+        let (nonce, mut accounts) = initialize_accounts(&program_id);
+        accounts[1].key = Pubkey::new_unique();
+        assert_eq!(
+            Processor::process_initialize(&program_id, nonce, &infos(&mut accounts)),
+            Err(OneSolError::InvalidProgramAddress.into())
+        );
+    }
+
+    #[test]
+    fn test_process_initialize_checks_protocol_and_admin() {
+        let program_id = Pubkey::new_unique();
+
+        // an initialized protocol can't be taken over
+        let (nonce, mut accounts) = initialize_accounts(&program_id);
+        Processor::process_initialize(&program_id, nonce, &infos(&mut accounts)).unwrap();
+        accounts[4] = TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]).signer();
+        assert_eq!(
+            Processor::process_initialize(&program_id, nonce, &infos(&mut accounts)),
+            Err(ProgramError::AccountAlreadyInitialized)
+        );
+
+        // a protocol account of another program
+        let (nonce, mut accounts) = initialize_accounts(&program_id);
+        accounts[0].owner = Pubkey::new_unique();
+        assert_eq!(
+            Processor::process_initialize(&program_id, nonce, &infos(&mut accounts)),
+            Err(ProgramError::IncorrectProgramId)
+        );
+
+        // an admin not signing
+        let (nonce, mut accounts) = initialize_accounts(&program_id);
+        accounts[4].is_signer = false;
+        assert_eq!(
+            Processor::process_initialize(&program_id, nonce, &infos(&mut accounts)),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn test_process_initialize_checks_token_account() {
+        let program_id = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let initialize = |setup: &dyn Fn(&mut Vec<TestAccount>)| {
+            let (nonce, mut accounts) = initialize_accounts(&program_id);
+            setup(&mut accounts);
+            Processor::process_initialize(&program_id, nonce, &infos(&mut accounts))
+        };
+
+        // owned by someone else than the authority
+        assert_eq!(
+            initialize(&|accounts| {
+                accounts[2] = TestAccount::token(&mint, &Pubkey::new_unique(), None, 0)
+            }),
+            Err(OneSolError::InvalidOwner.into())
+        );
+        // delegated to someone else than the authority
+        assert_eq!(
+            initialize(&|accounts| {
+                let delegate = Some(Pubkey::new_unique());
+                accounts[2] = TestAccount::token(&mint, &accounts[1].key, delegate, 0)
+            }),
+            Err(OneSolError::InvalidDelegate.into())
+        );
+        // a delegation to the authority stands for its ownership
+        assert_eq!(
+            initialize(&|accounts| {
+                let delegate = Some(accounts[1].key);
+                accounts[2] = TestAccount::token(&mint, &Pubkey::new_unique(), delegate, 0)
+            }),
+            Ok(())
+        );
+        // of another token program
+        assert_eq!(
+            initialize(&|accounts| accounts[3].key = Pubkey::new_unique()),
+            Err(OneSolError::IncorrectTokenProgramId.into())
+        );
+        // not a token account
+        assert_eq!(
+            initialize(&|accounts| accounts[2].data = vec![0; 64]),
+            Err(OneSolError::ExpectedAccount.into())
+        );
+    }
+
+    /// Accounts of a swap of mint A for mint B, followed by a spl-token-swap leg short of
+    /// its pool accounts, so a swap passing validation fails loading its legs.
+    fn swap_accounts(program_id: &Pubkey) -> Vec<TestAccount> {
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let mut protocol =
+            TestAccount::new(Pubkey::new_unique(), *program_id, vec![0; OneSolState::LEN]);
+        let (authority, nonce) =
+            Pubkey::find_program_address(&[&protocol.key.to_bytes()], program_id);
+        let protocol_token = TestAccount::token(&mint_b, &authority, None, 0);
+        OneSolState {
+            version: 1,
+            nonce,
+            token_program_id: spl_token::id(),
+            token: protocol_token.key,
+            token_mint: mint_b,
+            admin: Pubkey::new_unique(),
+            fee_numerator: 0,
+            fee_denominator: 0,
+            treasury: Pubkey::default(),
+            is_paused: false,
+            max_referral_fee_bps: 0,
+        }
+        .pack_into_slice(&mut protocol.data);
+        vec![
+            protocol,
+            TestAccount::new(authority, Pubkey::default(), vec![]),
+            TestAccount::new(user, Pubkey::default(), vec![]),
+            protocol_token,
+            TestAccount::token(&mint_a, &user, None, 100),
+            TestAccount::token(&mint_b, &user, None, 0),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+        ]
+    }
+
+    #[test]
+    fn test_process_swap_checks_accounts() {
+        let program_id = Pubkey::new_unique();
+        let swap = |amount_in: u64, setup: &dyn Fn(&mut Vec<TestAccount>)| {
+            let mut accounts = swap_accounts(&program_id);
+            setup(&mut accounts);
+            Processor::process_swap(
+                &program_id,
+                amount_in,
+                1,
+                DexConfigs::new(&dex_configs_data(&[(0, 2, 10_000)])).unwrap(),
+                None,
+                0,
+                0,
+                0,
+                LogLevel::Off,
+                &infos(&mut accounts),
+            )
+        };
+
+        // valid accounts get as far as the legs
+        assert_eq!(
+            swap(100, &|_| {}),
+            Err(OneSolError::AccountCountMismatch.into())
+        );
+        assert_eq!(swap(0, &|_| {}), Err(OneSolError::InvalidInput.into()));
+        assert_eq!(
+            swap(100, &|accounts| accounts[0].owner = Pubkey::new_unique()),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        assert_eq!(
+            swap(100, &|accounts| accounts[0].data.truncate(64)),
+            Err(ProgramError::InvalidAccountData)
+        );
+        // swapping out of or into the protocol token account
+        assert_eq!(
+            swap(100, &|accounts| accounts[4].key = accounts[3].key),
+            Err(OneSolError::IncorrectSwapAccount.into())
+        );
+        assert_eq!(
+            swap(100, &|accounts| accounts[5].key = accounts[3].key),
+            Err(OneSolError::IncorrectSwapAccount.into())
+        );
+        assert_eq!(
+            swap(100, &|accounts| accounts[5].key = accounts[4].key),
+            Err(OneSolError::InvalidInput.into())
+        );
+        // a destination of another mint than the protocol token
+        assert_eq!(
+            swap(100, &|accounts| {
+                let owner = accounts[2].key;
+                accounts[5] = TestAccount::token(&Pubkey::new_unique(), &owner, None, 0)
+            }),
+            Err(OneSolError::InvalidInput.into())
+        );
+        assert_eq!(
+            swap(100, &|accounts| accounts[6].key = Pubkey::new_unique()),
+            Err(OneSolError::IncorrectTokenProgramId.into())
+        );
+        assert_eq!(
+            swap(100, &|accounts| accounts[3].data = vec![0; 64]),
+            Err(OneSolError::ExpectedAccount.into())
+        );
+    }
+
+    #[test]
+    fn test_check_flash_repay() {
+        let program_id = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let borrow = flash_instruction(&program_id, 17, 2, &vault);
+        let repay = flash_instruction(&program_id, 18, 0, &vault);
+        let other = Instruction::new_with_bytes(Pubkey::new_unique(), &[18], vec![]);
+        let check = |instructions: &[Instruction], current_index: u16, amount: u64| {
+            let mut data = instructions_sysvar(instructions, current_index);
+            let mut lamports = 0;
+            let key = sysvar::instructions::id();
+            let owner = sysvar::id();
+            let info = AccountInfo::new(
+                &key,
+                false,
+                false,
+                &mut lamports,
+                &mut data,
+                &owner,
+                false,
+                0,
+            );
+            Processor::check_flash_repay(&program_id, &info, &vault, amount)
+        };
+        let not_repaid = Err(OneSolError::FlashLoanNotRepaid.into());
+
+        let instructions = [borrow.clone(), other.clone(), repay.clone()];
+        assert_eq!(check(&instructions, 0, 1_000), Ok(()));
+        let mut short_repay = repay.clone();
+        short_repay.data[1..].copy_from_slice(&999u64.to_le_bytes());
+        assert_eq!(check(&[borrow.clone(), short_repay], 0, 1_000), not_repaid);
+        assert_eq!(
+            check(&[borrow.clone(), other.clone()], 0, 1_000),
+            not_repaid
+        );
+        // a repay preceding the borrow doesn't count
+        assert_eq!(
+            check(&[repay.clone(), borrow.clone()], 1, 1_000),
+            not_repaid
+        );
+        // nor one a second borrow of the vault could claim
+        let instructions = [borrow.clone(), borrow.clone(), repay.clone()];
+        assert_eq!(check(&instructions, 0, 1_000), not_repaid);
+        // a repay into another vault neither
+        let other_repay = flash_instruction(&program_id, 18, 0, &Pubkey::new_unique());
+        assert_eq!(check(&[borrow.clone(), other_repay], 0, 1_000), not_repaid);
+        // borrowing through a CPI of another program
+        let nested = Err(OneSolError::InvalidInput.into());
+        assert_eq!(check(&[other, repay.clone()], 0, 1_000), nested);
+        // or of another instruction of the program, or of another borrow
+        let swap = Instruction::new_with_bytes(program_id, &[1], vec![]);
+        assert_eq!(check(&[swap, repay.clone()], 0, 1_000), nested);
+        assert_eq!(check(&instructions, 0, 999), nested);
+        let other_borrow = flash_instruction(&program_id, 17, 2, &Pubkey::new_unique());
+        assert_eq!(check(&[other_borrow, repay], 0, 1_000), nested);
+    }
+
+    #[test]
+    fn test_check_source_spent() {
+        let mut source =
+            TestAccount::token(&Pubkey::new_unique(), &Pubkey::new_unique(), None, 600);
+        let source_info = source.info();
+        let check = |amount_before, amount_in| {
+            Processor::check_source_spent(&source_info, &spl_token::id(), amount_before, amount_in)
+        };
+        assert_eq!(check(1_000, 400), Ok(()));
+        // legs pulling more or less of the source than routed through them
+        assert_eq!(
+            check(1_000, 300),
+            Err(OneSolError::SourceAmountMismatch.into())
+        );
+        assert_eq!(
+            check(1_000, 500),
+            Err(OneSolError::SourceAmountMismatch.into())
+        );
+        assert_eq!(check(500, 0), Err(OneSolError::SourceAmountMismatch.into()));
+    }
+
+    #[test]
+    fn test_load_swappers_skips_zero_amount_legs() {
+        let keys: Vec<Pubkey> = (0..11).map(|_| Pubkey::new_unique()).collect();
+        let mut lamports = vec![0u64; keys.len()];
+        let mut data = vec![vec![]; keys.len()];
+        let accounts: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| {
+                AccountInfo::new(key, false, true, lamports, data, key, false, 0)
+            })
+            .collect();
+        let context = SwapContext {
+            program_id: &keys[0],
+            token_program: &accounts[0],
+            user_transfer_authority: &accounts[0],
+            source: &accounts[0],
+            destination: &accounts[0],
+        };
+        // a leg of an unknown dex, swapping nothing
+        let data = dex_configs_data(&[(0, 7, 10_000), (9, 3, 0)]);
+        let configs = DexConfigs::new(&data).unwrap();
+
+        let iter = &mut accounts[1..].iter();
+        let swappers = Processor::load_swappers(configs, &[5, 0], iter, &context).unwrap();
+        assert!(swappers[0].is_some());
+        assert!(swappers[1].is_none());
+        assert_eq!(iter.as_slice().len(), 0);
+
+        let iter = &mut accounts[1..].iter();
+        assert_eq!(
+            Processor::load_swappers(configs, &[5, 1], iter, &context).unwrap_err(),
+            OneSolError::DexProgramNotAllowed.into()
+        );
+        // swaps skip it, leaving its accounts
+        let iter = &mut accounts[1..].iter();
+        let swappers = Processor::load_swap_swappers(configs, &[5, 1], iter, &context).unwrap();
+        assert!(swappers[0].is_some());
+        assert!(swappers[1].is_none());
+        assert_eq!(iter.as_slice().len(), 3);
+
+        // a spl-token-swap leg short of its pool accounts
+        let data = dex_configs_data(&[(0, 2, 10_000)]);
+        let configs = DexConfigs::new(&data).unwrap();
+        let iter = &mut accounts[1..].iter();
+        assert_eq!(
+            Processor::load_swappers(configs, &[5], iter, &context).unwrap_err(),
+            OneSolError::AccountCountMismatch.into()
+        );
+    }
+
+    fn rate(numerator: u64, denominator: u64) -> MockOutcome {
+        MockOutcome::Rate {
+            numerator,
+            denominator,
+        }
+    }
+
+    #[test]
+    fn test_run_legs_reports_each_leg() {
+        let mut destination =
+            TestAccount::token(&Pubkey::new_unique(), &Pubkey::new_unique(), None, 1_000);
+        let destination = destination.info();
+        let swappers = [
+            Some(MockSwapper::new(&destination, rate(99, 100))),
+            // a leg swapping nothing
+            None,
+            Some(MockSwapper::new(&destination, rate(2, 1))),
+        ];
+        let data = dex_configs_data(&[(0, 7, 6_000), (1, 8, 0), (0, 7, 4_000)]);
+        let configs = DexConfigs::new(&data).unwrap();
+
+        let (legs, end_amount) = Processor::run_legs(
+            &swappers,
+            configs,
+            &[600, 0, 400],
+            &[0, 0, 0],
+            &destination,
+            1_000,
+            &[],
+            LogLevel::Off,
+        )
+        .unwrap();
+        assert_eq!(
+            legs,
+            vec![
+                LegReport {
+                    dex_type: 0,
+                    amount_in: 600,
+                    amount_out: 594,
+                    price_impact_bps: None,
+                },
+                LegReport {
+                    dex_type: 0,
+                    amount_in: 400,
+                    amount_out: 800,
+                    price_impact_bps: None,
+                },
+            ]
+        );
+        assert_eq!(end_amount, 1_000 + 594 + 800);
+        assert_eq!(token_account_amount(&destination), Ok(end_amount));
+        for (swapper, amount_in) in [&swappers[0], &swappers[2]].iter().zip([600, 400].iter()) {
+            let calls = swapper.as_ref().unwrap().calls.borrow();
+            assert_eq!(calls.len(), 1);
+            assert_eq!(calls[0].0, *amount_in);
+        }
+    }
+
+    #[test]
+    fn test_quote_split() {
+        let mut destination =
+            TestAccount::token(&Pubkey::new_unique(), &Pubkey::new_unique(), None, 0);
+        let destination = destination.info();
+        let swappers = [
+            Some(MockSwapper::new(&destination, rate(99, 100))),
+            None,
+            Some(MockSwapper::new(&destination, rate(2, 1))),
+        ];
+        assert_eq!(
+            Processor::quote_split(&swappers, &[600, 0, 400]),
+            Some(594 + 800)
+        );
+        let swappers = [Some(MockSwapper::new(
+            &destination,
+            MockOutcome::Fail(ProgramError::InsufficientFunds),
+        ))];
+        assert_eq!(Processor::quote_split(&swappers, &[600]), None);
+    }
+
+    #[test]
+    fn test_quote_amounts_in() {
+        let mut destination =
+            TestAccount::token(&Pubkey::new_unique(), &Pubkey::new_unique(), None, 0);
+        let destination = destination.info();
+        let swappers = [
+            Some(MockSwapper::new(&destination, rate(99, 100))),
+            None,
+            Some(MockSwapper::new(&destination, rate(2, 1))),
+        ];
+        // the least amounts quoting the outputs, 599 only quoting 593
+        assert_eq!(
+            Processor::quote_amounts_in(&swappers, &[594, 0, 800], 1_000)
+                .unwrap()
+                .as_slice(),
+            &[600, 0, 400]
+        );
+        assert_eq!(
+            Processor::quote_amounts_in(&swappers, &[594, 0, 800], 999).unwrap_err(),
+            OneSolError::ExceededSlippage.into()
+        );
+        let swappers = [Some(MockSwapper::new(
+            &destination,
+            MockOutcome::Fail(ProgramError::InsufficientFunds),
+        ))];
+        assert_eq!(
+            Processor::quote_amounts_in(&swappers, &[594], 1_000).unwrap_err(),
+            OneSolError::QuoteUnavailable.into()
+        );
+    }
+
+    #[test]
+    fn test_gross_amount_out() {
+        let validated = |fee_numerator, fee_denominator| ValidatedSwap {
+            nonce: 0,
+            protocol_token_amount: 0,
+            destination_mint: Pubkey::default(),
+            fee_numerator,
+            fee_denominator,
+            treasury: Pubkey::default(),
+            max_referral_fee_bps: 0,
+        };
+        assert_eq!(validated(0, 0).gross_amount_out(9_876), Ok(9_876));
+        // 24 of 9_901 to the treasury leaves 9_877
+        let fees = validated(25, 10_000);
+        let gross = fees.gross_amount_out(9_876).unwrap();
+        assert_eq!(gross, 9_901);
+        assert!(gross - fees.protocol_fee(gross).unwrap() >= 9_876);
+        assert!(validated(1, 1).gross_amount_out(1).is_err());
+    }
+
+    #[test]
+    fn test_run_legs_stops_at_failed_leg() {
+        let mut destination =
+            TestAccount::token(&Pubkey::new_unique(), &Pubkey::new_unique(), None, 0);
+        let destination = destination.info();
+        let swappers = [
+            Some(MockSwapper::new(&destination, rate(1, 1))),
+            Some(MockSwapper::new(
+                &destination,
+                MockOutcome::Fail(ProgramError::InsufficientFunds),
+            )),
+            Some(MockSwapper::new(&destination, rate(1, 1))),
+        ];
+        let data = dex_configs_data(&[(0, 7, 3_334), (1, 8, 3_333), (0, 7, 3_333)]);
+        let configs = DexConfigs::new(&data).unwrap();
+
+        assert_eq!(
+            Processor::run_legs(
+                &swappers,
+                configs,
+                &[10, 10, 10],
+                &[0, 0, 0],
+                &destination,
+                0,
+                &[],
+                LogLevel::Off,
+            )
+            .unwrap_err(),
+            LegError::new(1, 1, &ProgramError::InsufficientFunds).into()
+        );
+        // the first leg's output stays, the last leg never runs
+        assert_eq!(token_account_amount(&destination), Ok(10));
+        assert!(swappers[2].as_ref().unwrap().calls.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_get_expected_return_with_gas() {
+        let fees = CurveFees {
+            trade_fee: Fraction::from_bps(25),
+            owner_trade_fee: Fraction::from_bps(5),
+        };
+        let pool = |curve, reserve| PoolCurve {
+            curve,
+            fees,
+            source_reserve: reserve,
+            destination_reserve: reserve,
+        };
+        let pools = [
+            pool(SwapCurve::ConstantProduct, 1_000_000),
+            pool(SwapCurve::Stable { amp: 10 }, 500_000),
+            // a curve failing every quote
+            pool(SwapCurve::Stable { amp: 0 }, 1_000_000),
+        ];
+        let returns =
+            Processor::get_expected_return_with_gas(40_000, 4, &pools, &[0, 100, 0]).unwrap();
+        assert_eq!(
+            returns,
+            vec![
+                vec![0, 9_871, 19_550, 29_041, 38_350],
+                vec![0, 9_852, 19_768, 29_648, 39_492],
+                vec![0; 5],
+            ]
+        );
+
+        assert_eq!(
+            Processor::get_expected_return_with_gas(40_000, 0, &pools, &[0; 3]),
+            Err(OneSolError::InvalidInput.into())
+        );
+        assert_eq!(
+            Processor::get_expected_return_with_gas(40_000, 4, &pools, &[0; 2]),
+            Err(OneSolError::InvalidInput.into())
+        );
+    }
+
+    #[test]
+    fn test_find_best_split() {
+        let fees = CurveFees {
+            trade_fee: Fraction::from_bps(25),
+            owner_trade_fee: Fraction::from_bps(5),
+        };
+        let pool = |reserve| PoolCurve {
+            curve: SwapCurve::ConstantProduct,
+            fees,
+            source_reserve: reserve,
+            destination_reserve: reserve,
+        };
+        let pools = [pool(2_000_000), pool(1_000_000)];
+        assert_eq!(
+            Processor::find_best_split(100_000, 10, &pools, &[0, 0]).unwrap(),
+            vec![70_000, 30_000]
+        );
+        // the second pool's gas outweighs its better marginal output
+        assert_eq!(
+            Processor::find_best_split(100_000, 10, &pools, &[0, 2_000]).unwrap(),
+            vec![100_000, 0]
+        );
+        // the rounding remainder goes to the pool of the most chunks
+        assert_eq!(
+            Processor::find_best_split(100_001, 3, &pools, &[0, 0]).unwrap(),
+            vec![66_668, 33_333]
+        );
+        assert!(Processor::find_best_split(100_000, 0, &pools, &[0, 0]).is_err());
+        assert!(Processor::find_best_split::<PoolCurve>(100_000, 10, &[], &[]).is_err());
+    }
+
+    #[test]
+    fn test_bounded_split_parts() {
+        let pool = |curve| PoolCurve {
+            curve,
+            fees: CurveFees {
+                trade_fee: Fraction::from_bps(0),
+                owner_trade_fee: Fraction::from_bps(0),
+            },
+            source_reserve: 1_000,
+            destination_reserve: 1_000,
+        };
+        let constant_product = [pool(SwapCurve::ConstantProduct); 2];
+        assert_eq!(Processor::bounded_split_parts(10, &constant_product), 10);
+        let stable = [pool(SwapCurve::Stable { amp: 100 }); 3];
+        let units_per_part = 3 * SwapCurve::Stable { amp: 100 }.quote_compute_units();
+        assert_eq!(
+            Processor::bounded_split_parts(MAX_SPLIT_PARTS, &stable),
+            SPLIT_COMPUTE_UNITS / units_per_part
+        );
+        assert!(Processor::bounded_split_parts(MAX_SPLIT_PARTS, &stable) < 10);
+    }
+
+    #[test]
+    fn test_split_by_ratio() {
+        let data = dex_configs_data(&[(0, 7, 6_667), (0, 7, 3_333)]);
+        let configs = DexConfigs::new(&data).unwrap();
+        assert_eq!(
+            Processor::split_by_ratio(100, configs).unwrap().as_slice(),
+            &[66, 34]
+        );
+        assert_eq!(
+            Processor::split_by_ratio(u64::MAX, configs)
+                .unwrap()
+                .iter()
+                .map(|&v| v as u128)
+                .sum::<u128>(),
+            u64::MAX as u128
+        );
+        // the shares of the legs computing in u128
+        let data = dex_configs_data(&[(0, 7, 5_000), (0, 7, 2_500), (0, 7, 2_500)]);
+        let configs = DexConfigs::new(&data).unwrap();
+        assert_eq!(
+            Processor::split_by_ratio(u64::MAX, configs)
+                .unwrap()
+                .as_slice(),
+            &[
+                u64::MAX / 2,
+                u64::MAX / 4,
+                u64::MAX - u64::MAX / 2 - u64::MAX / 4
+            ]
+        );
+        // dust rounds down to nothing but on the last leg
+        assert_eq!(
+            Processor::split_by_ratio(3, configs).unwrap().as_slice(),
+            &[1, 0, 2]
+        );
+        assert_eq!(
+            Processor::split_by_ratio(1, configs).unwrap().as_slice(),
+            &[0, 0, 1]
+        );
+        for ratios in [&[(0, 7, 0)][..], &[(0, 7, 5_000), (0, 7, 5_001)]].iter() {
+            let data = dex_configs_data(ratios);
+            assert_eq!(
+                Processor::split_by_ratio(100, DexConfigs::new(&data).unwrap()).unwrap_err(),
+                OneSolError::RatioSumInvalid.into()
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_split_by_ratio_sums_to_amount(
+            amount in any::<u64>(),
+            mut cuts in prop::collection::vec(0..=BPS_SCALE, 0..15),
+        ) {
+            // the ratios between consecutive cuts of the 10_000 bps
+            cuts.push(0);
+            cuts.push(BPS_SCALE);
+            cuts.sort_unstable();
+            let ratios: Vec<u16> = cuts.windows(2).map(|cut| cut[1] - cut[0]).collect();
+            let configs: Vec<_> = ratios.iter().map(|&ratio| (0, 7, ratio)).collect();
+            let data = dex_configs_data(&configs);
+            let amounts = Processor::split_by_ratio(amount, DexConfigs::new(&data).unwrap()).unwrap();
+
+            prop_assert_eq!(amounts.len(), ratios.len());
+            prop_assert!(amounts.iter().all(|&part| part <= amount));
+            prop_assert_eq!(amounts.iter().map(|&part| part as u128).sum::<u128>(), amount as u128);
+            // only the last leg takes the remainder, the others are their exact share
+            for (part, ratio) in amounts.iter().zip(ratios.iter()).take(ratios.len() - 1) {
+                prop_assert_eq!(*part as u128, amount as u128 * *ratio as u128 / BPS_SCALE as u128);
+            }
+        }
+
+        #[test]
+        fn test_find_best_split_sums_to_amount(
+            amount in 0..=1_000_000_000_000u64,
+            parts in 1..=MAX_SPLIT_PARTS as u64,
+            reserves in prop::collection::vec(1_000..=1_000_000_000_000u64, 1..=MAX_LEGS),
+        ) {
+            let pools: Vec<PoolCurve> = reserves
+                .iter()
+                .map(|&reserve| PoolCurve {
+                    curve: SwapCurve::ConstantProduct,
+                    fees: CurveFees {
+                        trade_fee: Fraction::from_bps(25),
+                        owner_trade_fee: Fraction::from_bps(5),
+                    },
+                    source_reserve: reserve,
+                    destination_reserve: reserve,
+                })
+                .collect();
+            let gas = vec![0; pools.len()];
+            let split = Processor::find_best_split(amount, parts, &pools, &gas).unwrap();
+            prop_assert_eq!(split.len(), pools.len());
+            prop_assert_eq!(split.iter().map(|&part| part as u128).sum::<u128>(), amount as u128);
+        }
+
+        #[test]
+        fn test_split_by_ratio_rejects_ratios_off_10_000_bps(
+            amount in any::<u64>(),
+            ratios in prop::collection::vec(any::<u16>(), 1..16),
+        ) {
+            prop_assume!(ratios.iter().map(|&ratio| ratio as u32).sum::<u32>() != BPS_SCALE as u32);
+            let configs: Vec<_> = ratios.iter().map(|&ratio| (0, 7, ratio)).collect();
+            let data = dex_configs_data(&configs);
+            prop_assert!(Processor::split_by_ratio(amount, DexConfigs::new(&data).unwrap()).is_err());
+        }
+    }
+}
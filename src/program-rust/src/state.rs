@@ -1,10 +1,18 @@
 //! State transition types
+use crate::{
+    error::OneSolError,
+    instruction::{DexConfig, MAX_LEGS},
+    math::{apply_bps, checked_mul_div, mul_div_floor, split_by_bps, Rounding},
+};
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
+    hash::{hash, hashv, Hash},
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
+use std::convert::TryFrom;
 
 /// Program states.
 #[repr(C)]
@@ -24,9 +32,166 @@ pub struct OneSolState {
 
     /// Mint information for token
     pub token_mint: Pubkey,
+
+    /// Admin setting the fee rate and pausing the swaps
+    pub admin: Pubkey,
+
+    /// Numerator of the share of the output swaps pay the treasury
+    pub fee_numerator: u64,
+
+    /// Denominator of the share of the output swaps pay the treasury
+    pub fee_denominator: u64,
+
+    /// Token account of `token_mint` receiving the fees
+    pub treasury: Pubkey,
+
+    /// Whether the admin paused the swaps
+    pub is_paused: bool,
+
+    /// Largest referral fee, in bps of the output net of the protocol fee, swaps may pay
+    pub max_referral_fee_bps: u16,
+}
+
+impl OneSolState {
+    /// Length of the states created before fees, read as charging none.
+    pub const LEGACY_LEN: usize = 98;
+
+    /// Length of the states created before the pause flag, read as never paused.
+    pub const UNPAUSABLE_LEN: usize = 178;
+
+    /// Length of the states created before referral fees, read as allowing none.
+    pub const UNREFERRED_LEN: usize = 179;
+
+    /// Borrows the state packed in `src`, without unpacking it.
+    pub fn view(src: &[u8]) -> Result<OneSolStateView<'_>, ProgramError> {
+        if ![
+            Self::LEN,
+            Self::UNREFERRED_LEN,
+            Self::UNPAUSABLE_LEN,
+            Self::LEGACY_LEN,
+        ]
+        .contains(&src.len())
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let view = OneSolStateView { data: src };
+        if view.version() != 1 {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if src.get(178).copied().unwrap_or(0) > 1 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(view)
+    }
+
+    /// Checks `fee_numerator / fee_denominator` is a share of the output, 0 / 0 charging
+    /// no fee.
+    pub fn check_fee_rate(fee_numerator: u64, fee_denominator: u64) -> Result<(), OneSolError> {
+        if fee_numerator > fee_denominator || (fee_numerator > 0 && fee_denominator == 0) {
+            return Err(OneSolError::InvalidInput);
+        }
+        Ok(())
+    }
+
+    /// Fee at `fee_numerator / fee_denominator` of a swap outputting `amount_out`,
+    /// rounded down.
+    pub fn fee(
+        amount_out: u64,
+        fee_numerator: u64,
+        fee_denominator: u64,
+    ) -> Result<u64, OneSolError> {
+        if fee_numerator == 0 {
+            return Ok(0);
+        }
+        mul_div_floor(amount_out, fee_numerator, fee_denominator)
+    }
+}
+
+/// Zero-copy view of a packed [OneSolState], reading each field in place only when asked
+/// for it.
+#[derive(Clone, Copy, Debug)]
+pub struct OneSolStateView<'a> {
+    data: &'a [u8],
 }
 
-impl OneSolState {}
+impl<'a> OneSolStateView<'a> {
+    /// Initialized state.
+    pub fn version(&self) -> u8 {
+        self.data[0]
+    }
+
+    /// Nonce used in program address.
+    pub fn nonce(&self) -> u8 {
+        self.data[1]
+    }
+
+    /// Program ID of the tokens
+    pub fn token_program_id(&self) -> Pubkey {
+        Pubkey::new_from_array(*array_ref![self.data, 2, 32])
+    }
+
+    /// Token Account
+    pub fn token(&self) -> Pubkey {
+        Pubkey::new_from_array(*array_ref![self.data, 34, 32])
+    }
+
+    /// Mint information for token
+    pub fn token_mint(&self) -> Pubkey {
+        Pubkey::new_from_array(*array_ref![self.data, 66, 32])
+    }
+
+    /// Admin setting the fee rate, the default pubkey for legacy states
+    pub fn admin(&self) -> Pubkey {
+        self.pubkey_at(98)
+    }
+
+    /// Numerator of the fee rate, 0 for legacy states
+    pub fn fee_numerator(&self) -> u64 {
+        self.u64_at(130)
+    }
+
+    /// Denominator of the fee rate, 0 for legacy states
+    pub fn fee_denominator(&self) -> u64 {
+        self.u64_at(138)
+    }
+
+    /// Token account receiving the fees, the default pubkey for legacy states
+    pub fn treasury(&self) -> Pubkey {
+        self.pubkey_at(146)
+    }
+
+    /// Whether the admin paused the swaps, false for states predating the flag
+    pub fn is_paused(&self) -> bool {
+        self.data.get(178) == Some(&1)
+    }
+
+    /// Largest referral fee in bps, 0 for states predating referral fees
+    pub fn max_referral_fee_bps(&self) -> u16 {
+        match self.data.get(179..181) {
+            Some(bytes) => u16::from_le_bytes(*array_ref![bytes, 0, 2]),
+            None => 0,
+        }
+    }
+
+    /// Fee of a swap outputting `amount_out`, rounded down.
+    pub fn fee(&self, amount_out: u64) -> Result<u64, OneSolError> {
+        OneSolState::fee(amount_out, self.fee_numerator(), self.fee_denominator())
+    }
+
+    fn pubkey_at(&self, offset: usize) -> Pubkey {
+        match self.data.get(offset..offset + 32) {
+            Some(bytes) => Pubkey::new_from_array(*array_ref![bytes, 0, 32]),
+            None => Pubkey::default(),
+        }
+    }
+
+    fn u64_at(&self, offset: usize) -> u64 {
+        match self.data.get(offset..offset + 8) {
+            Some(bytes) => u64::from_le_bytes(*array_ref![bytes, 0, 8]),
+            None => 0,
+        }
+    }
+}
 
 impl IsInitialized for OneSolState {
     fn is_initialized(&self) -> bool {
@@ -36,62 +201,2573 @@ impl IsInitialized for OneSolState {
 
 impl Sealed for OneSolState {}
 impl Pack for OneSolState {
-    const LEN: usize = 98;
+    const LEN: usize = 181;
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, 98];
-        let (version, nonce, token_program_id, token, token_mint) =
-            array_refs![src, 1, 1, 32, 32, 32];
+        let src = array_ref![src, 0, 181];
+        let (
+            version,
+            nonce,
+            token_program_id,
+            token,
+            token_mint,
+            admin,
+            fee_numerator,
+            fee_denominator,
+            treasury,
+            is_paused,
+            max_referral_fee_bps,
+        ) = array_refs![src, 1, 1, 32, 32, 32, 32, 8, 8, 32, 1, 2];
         Ok(OneSolState {
             version: version[0],
             nonce: nonce[0],
             token_program_id: Pubkey::new_from_array(*token_program_id),
             token: Pubkey::new_from_array(*token),
             token_mint: Pubkey::new_from_array(*token_mint),
+            admin: Pubkey::new_from_array(*admin),
+            fee_numerator: u64::from_le_bytes(*fee_numerator),
+            fee_denominator: u64::from_le_bytes(*fee_denominator),
+            treasury: Pubkey::new_from_array(*treasury),
+            is_paused: match is_paused[0] {
+                0 => false,
+                1 => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            max_referral_fee_bps: u16::from_le_bytes(*max_referral_fee_bps),
         })
     }
 
     fn pack_into_slice(&self, output: &mut [u8]) {
-        let output = array_mut_ref![output, 0, 98];
-        let (version_dst, nonce_dst, token_program_id, token, token_mint) =
-            mut_array_refs![output, 1, 1, 32, 32, 32];
+        let output = array_mut_ref![output, 0, 181];
+        let (
+            version_dst,
+            nonce_dst,
+            token_program_id,
+            token,
+            token_mint,
+            admin,
+            fee_numerator,
+            fee_denominator,
+            treasury,
+            is_paused,
+            max_referral_fee_bps,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 32, 32, 8, 8, 32, 1, 2];
         version_dst[0] = self.version;
         nonce_dst[0] = self.nonce;
         token_program_id.copy_from_slice(self.token_program_id.as_ref());
         token.copy_from_slice(self.token.as_ref());
         token_mint.copy_from_slice(self.token_mint.as_ref());
+        admin.copy_from_slice(self.admin.as_ref());
+        *fee_numerator = self.fee_numerator.to_le_bytes();
+        *fee_denominator = self.fee_denominator.to_le_bytes();
+        treasury.copy_from_slice(self.treasury.as_ref());
+        is_paused[0] = self.is_paused as u8;
+        *max_referral_fee_bps = self.max_referral_fee_bps.to_le_bytes();
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+/// Order kinds.
+#[repr(u8)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy)]
+pub enum OrderKind {
+    /// Filled in a single slice once the limit price can be met.
+    Limit = 0,
+    /// Split into `num_slices` equal slices, one fillable every `slice_interval` seconds.
+    Twap = 1,
+    /// Fillable once the oracle price falls to or below `trigger_price`.
+    StopLoss = 2,
+    /// Fillable once the oracle price rises to or above `trigger_price`.
+    TakeProfit = 3,
+    /// Fillable once the pool spot price falls to or below `trigger_price`.
+    PoolStopLoss = 4,
+    /// Fillable once the pool spot price rises to or above `trigger_price`.
+    PoolTakeProfit = 5,
+}
 
-    const TEST_VERSION: u8 = 1;
-    const TEST_NONCE: u8 = 255;
-    const TEST_TOKEN_PROGRAM_ID: Pubkey = Pubkey::new_from_array([1u8; 32]);
-    const TEST_TOKEN: Pubkey = Pubkey::new_from_array([2u8; 32]);
-    const TEST_TOKEN_MINT: Pubkey = Pubkey::new_from_array([5u8; 32]);
+impl TryFrom<u8> for OrderKind {
+    type Error = ProgramError;
 
-    #[test]
-    pub fn test_onesol_state_pack() {
-        let test_state = OneSolState {
-            version: TEST_VERSION,
-            nonce: TEST_NONCE,
-            token_program_id: TEST_TOKEN_PROGRAM_ID,
-            token: TEST_TOKEN,
-            token_mint: TEST_TOKEN_MINT,
+    fn try_from(kind: u8) -> Result<Self, Self::Error> {
+        match kind {
+            0 => Ok(OrderKind::Limit),
+            1 => Ok(OrderKind::Twap),
+            2 => Ok(OrderKind::StopLoss),
+            3 => Ok(OrderKind::TakeProfit),
+            4 => Ok(OrderKind::PoolStopLoss),
+            5 => Ok(OrderKind::PoolTakeProfit),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+impl OrderKind {
+    /// Returns true for kinds only fillable once a price crosses their trigger.
+    pub fn is_conditional(&self) -> bool {
+        matches!(
+            self,
+            OrderKind::StopLoss
+                | OrderKind::TakeProfit
+                | OrderKind::PoolStopLoss
+                | OrderKind::PoolTakeProfit
+        )
+    }
+
+    /// Returns true for conditional kinds triggered by an AMM pool price instead of
+    /// an oracle.
+    pub fn is_pool_conditional(&self) -> bool {
+        matches!(self, OrderKind::PoolStopLoss | OrderKind::PoolTakeProfit)
+    }
+
+    /// Returns true for conditional kinds triggered when the price falls.
+    pub fn is_stop_loss(&self) -> bool {
+        matches!(self, OrderKind::StopLoss | OrderKind::PoolStopLoss)
+    }
+}
+
+/// Order state, escrowing `amount_in` source tokens until keepers fill it.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Order {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Nonce used in the order authority program address.
+    pub nonce: u8,
+
+    /// Order kind.
+    pub kind: OrderKind,
+
+    /// Owner of the order, the only account allowed to receive its proceeds.
+    pub owner: Pubkey,
+
+    /// Program ID of the tokens
+    pub token_program_id: Pubkey,
+
+    /// Mint of the escrowed source token
+    pub source_mint: Pubkey,
+
+    /// Mint of the destination token
+    pub destination_mint: Pubkey,
+
+    /// Token account holding the escrowed source tokens, owned by the order authority
+    pub escrow: Pubkey,
+
+    /// Owner's token account receiving the proceeds
+    pub destination: Pubkey,
+
+    /// Total SOURCE amount of the order
+    pub amount_in: u64,
+
+    /// DESTINATION amount expected for the whole `amount_in`, used as the reference price
+    pub expected_amount_out: u64,
+
+    /// Max deviation below the reference price a single slice may execute at, in bps
+    pub max_deviation_bps: u16,
+
+    /// Number of slices the order is split into
+    pub num_slices: u16,
+
+    /// Seconds between two slices becoming fillable
+    pub slice_interval: u32,
+
+    /// Unix timestamp the first slice becomes fillable
+    pub start_time: i64,
+
+    /// SOURCE amount already swapped
+    pub filled_amount_in: u64,
+
+    /// DESTINATION amount already delivered
+    pub filled_amount_out: u64,
+
+    /// Number of slices already filled
+    pub slices_filled: u16,
+
+    /// Pyth or Switchboard price account, or spl-token-swap pool for pool conditional orders,
+    /// conditional orders are triggered by
+    pub oracle: Pubkey,
+
+    /// Price conditional orders are triggered at, in the oracle's own exponent, or
+    /// scaled by `POOL_PRICE_SCALE` for pool conditional orders
+    pub trigger_price: u64,
+
+    /// Last slot the order can be filled at, after which anyone can expire it
+    pub expires_at_slot: u64,
+
+    /// Keeper fee taken from each fill, in bps of the filled SOURCE amount
+    pub keeper_fee_bps: u16,
+
+    /// Keeper fee paid for each completed slice and for the expiry crank, in SOURCE tokens
+    pub keeper_fee_flat: u64,
+
+    /// Order page listing the order
+    pub page: Pubkey,
+}
+
+impl Order {
+    /// Returns true once every slice of the order has been filled.
+    pub fn is_filled(&self) -> bool {
+        self.slices_filled >= self.num_slices
+    }
+
+    /// Cumulative SOURCE amount filled once the current slice completes,
+    /// the last slice absorbing the rounding remainder.
+    pub fn current_slice_target(&self) -> u64 {
+        if self.slices_filled as u32 + 1 >= self.num_slices as u32 {
+            self.amount_in
+        } else {
+            self.amount_in / self.num_slices as u64 * (self.slices_filled as u64 + 1)
+        }
+    }
+
+    /// SOURCE amount the owner deposits on top of `amount_in` to fund every keeper fee:
+    /// the bps fee on the full amount plus a flat fee per slice and for the expiry crank.
+    pub fn keeper_fee_budget(&self) -> Option<u64> {
+        let bps_fee = apply_bps(self.amount_in, self.keeper_fee_bps, Rounding::Down).ok()?;
+        let flat_fee = self
+            .keeper_fee_flat
+            .checked_mul(self.num_slices as u64 + 1)?;
+        bps_fee.checked_add(flat_fee)
+    }
+
+    /// Keeper fee for filling `fill_amount_in`, including the flat fee when the fill
+    /// completes a slice. Rounds down, so fees never exceed `keeper_fee_budget`.
+    pub fn keeper_fee(&self, fill_amount_in: u64, completes_slice: bool) -> u64 {
+        let bps_fee =
+            apply_bps(fill_amount_in, self.keeper_fee_bps, Rounding::Down).unwrap_or(u64::MAX);
+        let flat_fee = if completes_slice {
+            self.keeper_fee_flat
+        } else {
+            0
         };
-        let mut packed = [0u8; OneSolState::LEN];
-        OneSolState::pack_into_slice(&test_state, &mut packed);
-        let unpacked = OneSolState::unpack(&packed).unwrap();
-        assert_eq!(test_state, unpacked);
+        // bps_fee <= fill_amount_in, and both fit in the budget checked at placement
+        bps_fee.saturating_add(flat_fee)
+    }
 
-        let mut packed = vec![1u8, TEST_NONCE];
-        packed.extend_from_slice(&TEST_TOKEN_PROGRAM_ID.to_bytes());
-        packed.extend_from_slice(&TEST_TOKEN.to_bytes());
-        packed.extend_from_slice(&TEST_TOKEN_MINT.to_bytes());
-        let unpacked = OneSolState::unpack(&packed).unwrap();
-        assert_eq!(test_state, unpacked);
+    /// Returns true once `slot` is past the order expiry.
+    pub fn is_expired(&self, slot: u64) -> bool {
+        slot > self.expires_at_slot
+    }
+}
+
+impl IsInitialized for Order {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for Order {}
+impl Pack for Order {
+    const LEN: usize = 335;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 335];
+        let (
+            version,
+            nonce,
+            kind,
+            owner,
+            token_program_id,
+            source_mint,
+            destination_mint,
+            escrow,
+            destination,
+            amount_in,
+            expected_amount_out,
+            max_deviation_bps,
+            num_slices,
+            slice_interval,
+            start_time,
+            filled_amount_in,
+            filled_amount_out,
+            slices_filled,
+            oracle,
+            trigger_price,
+            expires_at_slot,
+            keeper_fee_bps,
+            keeper_fee_flat,
+            page,
+        ) = array_refs![
+            src, 1, 1, 1, 32, 32, 32, 32, 32, 32, 8, 8, 2, 2, 4, 8, 8, 8, 2, 32, 8, 8, 2, 8, 32
+        ];
+        Ok(Order {
+            version: version[0],
+            nonce: nonce[0],
+            kind: OrderKind::try_from(kind[0])?,
+            owner: Pubkey::new_from_array(*owner),
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            source_mint: Pubkey::new_from_array(*source_mint),
+            destination_mint: Pubkey::new_from_array(*destination_mint),
+            escrow: Pubkey::new_from_array(*escrow),
+            destination: Pubkey::new_from_array(*destination),
+            amount_in: u64::from_le_bytes(*amount_in),
+            expected_amount_out: u64::from_le_bytes(*expected_amount_out),
+            max_deviation_bps: u16::from_le_bytes(*max_deviation_bps),
+            num_slices: u16::from_le_bytes(*num_slices),
+            slice_interval: u32::from_le_bytes(*slice_interval),
+            start_time: i64::from_le_bytes(*start_time),
+            filled_amount_in: u64::from_le_bytes(*filled_amount_in),
+            filled_amount_out: u64::from_le_bytes(*filled_amount_out),
+            slices_filled: u16::from_le_bytes(*slices_filled),
+            oracle: Pubkey::new_from_array(*oracle),
+            trigger_price: u64::from_le_bytes(*trigger_price),
+            expires_at_slot: u64::from_le_bytes(*expires_at_slot),
+            keeper_fee_bps: u16::from_le_bytes(*keeper_fee_bps),
+            keeper_fee_flat: u64::from_le_bytes(*keeper_fee_flat),
+            page: Pubkey::new_from_array(*page),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 335];
+        let (
+            version,
+            nonce,
+            kind,
+            owner,
+            token_program_id,
+            source_mint,
+            destination_mint,
+            escrow,
+            destination,
+            amount_in,
+            expected_amount_out,
+            max_deviation_bps,
+            num_slices,
+            slice_interval,
+            start_time,
+            filled_amount_in,
+            filled_amount_out,
+            slices_filled,
+            oracle,
+            trigger_price,
+            expires_at_slot,
+            keeper_fee_bps,
+            keeper_fee_flat,
+            page,
+        ) = mut_array_refs![
+            output, 1, 1, 1, 32, 32, 32, 32, 32, 32, 8, 8, 2, 2, 4, 8, 8, 8, 2, 32, 8, 8, 2, 8, 32
+        ];
+        version[0] = self.version;
+        nonce[0] = self.nonce;
+        kind[0] = self.kind as u8;
+        owner.copy_from_slice(self.owner.as_ref());
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        source_mint.copy_from_slice(self.source_mint.as_ref());
+        destination_mint.copy_from_slice(self.destination_mint.as_ref());
+        escrow.copy_from_slice(self.escrow.as_ref());
+        destination.copy_from_slice(self.destination.as_ref());
+        *amount_in = self.amount_in.to_le_bytes();
+        *expected_amount_out = self.expected_amount_out.to_le_bytes();
+        *max_deviation_bps = self.max_deviation_bps.to_le_bytes();
+        *num_slices = self.num_slices.to_le_bytes();
+        *slice_interval = self.slice_interval.to_le_bytes();
+        *start_time = self.start_time.to_le_bytes();
+        *filled_amount_in = self.filled_amount_in.to_le_bytes();
+        *filled_amount_out = self.filled_amount_out.to_le_bytes();
+        *slices_filled = self.slices_filled.to_le_bytes();
+        oracle.copy_from_slice(self.oracle.as_ref());
+        *trigger_price = self.trigger_price.to_le_bytes();
+        *expires_at_slot = self.expires_at_slot.to_le_bytes();
+        *keeper_fee_bps = self.keeper_fee_bps.to_le_bytes();
+        *keeper_fee_flat = self.keeper_fee_flat.to_le_bytes();
+        page.copy_from_slice(self.page.as_ref());
+    }
+}
+
+/// Number of orders listed by a single order page.
+pub const ORDER_PAGE_CAPACITY: usize = 128;
+
+/// Header of an order page, a program address listing the open orders of a mint pair
+/// derived from `[b"orders", source mint, destination mint, page index]`.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct OrderPageHeader {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Bump seed of the page program address.
+    pub bump_seed: u8,
+
+    /// Index of the page among the pages of the pair
+    pub page_index: u32,
+
+    /// Mint of the orders SOURCE token
+    pub source_mint: Pubkey,
+
+    /// Mint of the orders DESTINATION token
+    pub destination_mint: Pubkey,
+
+    /// Number of listed orders
+    pub len: u16,
+}
+
+impl IsInitialized for OrderPageHeader {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for OrderPageHeader {}
+impl Pack for OrderPageHeader {
+    const LEN: usize = 72;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 72];
+        let (version, bump_seed, page_index, source_mint, destination_mint, len) =
+            array_refs![src, 1, 1, 4, 32, 32, 2];
+        Ok(OrderPageHeader {
+            version: version[0],
+            bump_seed: bump_seed[0],
+            page_index: u32::from_le_bytes(*page_index),
+            source_mint: Pubkey::new_from_array(*source_mint),
+            destination_mint: Pubkey::new_from_array(*destination_mint),
+            len: u16::from_le_bytes(*len),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 72];
+        let (version, bump_seed, page_index, source_mint, destination_mint, len) =
+            mut_array_refs![output, 1, 1, 4, 32, 32, 2];
+        version[0] = self.version;
+        bump_seed[0] = self.bump_seed;
+        *page_index = self.page_index.to_le_bytes();
+        source_mint.copy_from_slice(self.source_mint.as_ref());
+        destination_mint.copy_from_slice(self.destination_mint.as_ref());
+        *len = self.len.to_le_bytes();
+    }
+}
+
+/// An order listed by an order page, with what keepers need to pick fillable orders.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct OrderPageEntry {
+    /// Order account
+    pub order: Pubkey,
+
+    /// Order kind
+    pub kind: OrderKind,
+
+    /// Last slot the order can be filled at
+    pub expires_at_slot: u64,
+}
+
+impl Sealed for OrderPageEntry {}
+impl Pack for OrderPageEntry {
+    const LEN: usize = 41;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 41];
+        let (order, kind, expires_at_slot) = array_refs![src, 32, 1, 8];
+        Ok(OrderPageEntry {
+            order: Pubkey::new_from_array(*order),
+            kind: OrderKind::try_from(kind[0])?,
+            expires_at_slot: u64::from_le_bytes(*expires_at_slot),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 41];
+        let (order, kind, expires_at_slot) = mut_array_refs![output, 32, 1, 8];
+        order.copy_from_slice(self.order.as_ref());
+        kind[0] = self.kind as u8;
+        *expires_at_slot = self.expires_at_slot.to_le_bytes();
+    }
+}
+
+/// Zero-copy access to order page accounts: entries are read and written in place,
+/// without deserializing the whole page.
+pub struct OrderPage;
+
+impl OrderPage {
+    /// Size of an order page account.
+    pub const LEN: usize = OrderPageHeader::LEN + ORDER_PAGE_CAPACITY * OrderPageEntry::LEN;
+
+    fn entry_range(index: usize) -> std::ops::Range<usize> {
+        let start = OrderPageHeader::LEN + index * OrderPageEntry::LEN;
+        start..start + OrderPageEntry::LEN
+    }
+
+    /// Reads the page header.
+    pub fn header(data: &[u8]) -> Result<OrderPageHeader, ProgramError> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        OrderPageHeader::unpack(&data[..OrderPageHeader::LEN])
+    }
+
+    /// Reads the entry at `index`, which must be lower than the header `len`.
+    pub fn entry(data: &[u8], index: usize) -> Result<OrderPageEntry, ProgramError> {
+        if index >= Self::header(data)?.len as usize {
+            return Err(ProgramError::InvalidArgument);
+        }
+        OrderPageEntry::unpack_from_slice(&data[Self::entry_range(index)])
+    }
+
+    /// Appends an entry.
+    pub fn push(data: &mut [u8], entry: &OrderPageEntry) -> Result<(), ProgramError> {
+        let mut header = Self::header(data)?;
+        let len = header.len as usize;
+        if len >= ORDER_PAGE_CAPACITY {
+            return Err(OneSolError::OrderPageFull.into());
+        }
+        entry.pack_into_slice(&mut data[Self::entry_range(len)]);
+        header.len += 1;
+        header.pack_into_slice(&mut data[..OrderPageHeader::LEN]);
+        Ok(())
+    }
+
+    /// Removes the entry of `order`, moving the last entry into its slot.
+    pub fn remove(data: &mut [u8], order: &Pubkey) -> Result<(), ProgramError> {
+        let mut header = Self::header(data)?;
+        let len = header.len as usize;
+        let index = (0..len)
+            .find(|&i| data[Self::entry_range(i)][..32] == order.as_ref()[..])
+            .ok_or(ProgramError::InvalidArgument)?;
+        let last = Self::entry_range(len - 1);
+        data.copy_within(last.clone(), Self::entry_range(index).start);
+        data[last].fill(0);
+        header.len -= 1;
+        header.pack_into_slice(&mut data[..OrderPageHeader::LEN]);
+        Ok(())
+    }
+}
+
+/// Recurring payment state: every `period` seconds a keeper may swap `amount_in` of the
+/// payer's SOURCE tokens into the merchant's DESTINATION token account.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct RecurringPayment {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Nonce used in the recurring payment authority program address.
+    pub nonce: u8,
+
+    /// Payer, owner of the SOURCE token account
+    pub payer: Pubkey,
+
+    /// Program ID of the tokens
+    pub token_program_id: Pubkey,
+
+    /// Payer's token account, delegated to the recurring payment authority for
+    /// `max_total_in`
+    pub source: Pubkey,
+
+    /// Mint of the SOURCE token
+    pub source_mint: Pubkey,
+
+    /// Merchant's settlement token account
+    pub destination: Pubkey,
+
+    /// Mint of the settlement token
+    pub destination_mint: Pubkey,
+
+    /// SOURCE amount swapped each period
+    pub amount_in: u64,
+
+    /// Minimum settlement amount the merchant receives each period
+    pub minimum_amount_out: u64,
+
+    /// Seconds between two payments
+    pub period: u32,
+
+    /// Cap on the SOURCE amount swapped over the lifetime of the payment
+    pub max_total_in: u64,
+
+    /// SOURCE amount swapped so far
+    pub total_in: u64,
+
+    /// Settlement amount delivered so far
+    pub total_out: u64,
+
+    /// Unix timestamp the next payment becomes executable
+    pub next_payment_time: i64,
+
+    /// Token account the period's `amount_in` is moved to and swapped from, owned by
+    /// the escrow authority
+    pub escrow: Pubkey,
+
+    /// Nonce used in the escrow authority program address, derived from `escrow`.
+    pub escrow_nonce: u8,
+}
+
+impl IsInitialized for RecurringPayment {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for RecurringPayment {}
+impl Pack for RecurringPayment {
+    const LEN: usize = 279;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 279];
+        let (
+            version,
+            nonce,
+            payer,
+            token_program_id,
+            source,
+            source_mint,
+            destination,
+            destination_mint,
+            amount_in,
+            minimum_amount_out,
+            period,
+            max_total_in,
+            total_in,
+            total_out,
+            next_payment_time,
+            escrow,
+            escrow_nonce,
+        ) = array_refs![src, 1, 1, 32, 32, 32, 32, 32, 32, 8, 8, 4, 8, 8, 8, 8, 32, 1];
+        Ok(RecurringPayment {
+            version: version[0],
+            nonce: nonce[0],
+            payer: Pubkey::new_from_array(*payer),
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            source: Pubkey::new_from_array(*source),
+            source_mint: Pubkey::new_from_array(*source_mint),
+            destination: Pubkey::new_from_array(*destination),
+            destination_mint: Pubkey::new_from_array(*destination_mint),
+            amount_in: u64::from_le_bytes(*amount_in),
+            minimum_amount_out: u64::from_le_bytes(*minimum_amount_out),
+            period: u32::from_le_bytes(*period),
+            max_total_in: u64::from_le_bytes(*max_total_in),
+            total_in: u64::from_le_bytes(*total_in),
+            total_out: u64::from_le_bytes(*total_out),
+            next_payment_time: i64::from_le_bytes(*next_payment_time),
+            escrow: Pubkey::new_from_array(*escrow),
+            escrow_nonce: escrow_nonce[0],
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 279];
+        let (
+            version,
+            nonce,
+            payer,
+            token_program_id,
+            source,
+            source_mint,
+            destination,
+            destination_mint,
+            amount_in,
+            minimum_amount_out,
+            period,
+            max_total_in,
+            total_in,
+            total_out,
+            next_payment_time,
+            escrow,
+            escrow_nonce,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 32, 32, 32, 32, 8, 8, 4, 8, 8, 8, 8, 32, 1];
+        version[0] = self.version;
+        nonce[0] = self.nonce;
+        payer.copy_from_slice(self.payer.as_ref());
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        source.copy_from_slice(self.source.as_ref());
+        source_mint.copy_from_slice(self.source_mint.as_ref());
+        destination.copy_from_slice(self.destination.as_ref());
+        destination_mint.copy_from_slice(self.destination_mint.as_ref());
+        *amount_in = self.amount_in.to_le_bytes();
+        *minimum_amount_out = self.minimum_amount_out.to_le_bytes();
+        *period = self.period.to_le_bytes();
+        *max_total_in = self.max_total_in.to_le_bytes();
+        *total_in = self.total_in.to_le_bytes();
+        *total_out = self.total_out.to_le_bytes();
+        *next_payment_time = self.next_payment_time.to_le_bytes();
+        escrow.copy_from_slice(self.escrow.as_ref());
+        escrow_nonce[0] = self.escrow_nonce;
+    }
+}
+
+/// Result of a swap, returned to CPI callers and simulating clients.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SwapResult {
+    /// SOURCE amount spent
+    pub amount_in_spent: u64,
+
+    /// DESTINATION amount delivered to the user
+    pub amount_out: u64,
+
+    /// DESTINATION amount paid in fees
+    pub fee_paid: u64,
+
+    /// Number of dex legs executed
+    pub legs_executed: u8,
+}
+
+impl Sealed for SwapResult {}
+impl Pack for SwapResult {
+    const LEN: usize = 25;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 25];
+        let (amount_in_spent, amount_out, fee_paid, legs_executed) = array_refs![src, 8, 8, 8, 1];
+        Ok(SwapResult {
+            amount_in_spent: u64::from_le_bytes(*amount_in_spent),
+            amount_out: u64::from_le_bytes(*amount_out),
+            fee_paid: u64::from_le_bytes(*fee_paid),
+            legs_executed: legs_executed[0],
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 25];
+        let (amount_in_spent, amount_out, fee_paid, legs_executed) =
+            mut_array_refs![output, 8, 8, 8, 1];
+        *amount_in_spent = self.amount_in_spent.to_le_bytes();
+        *amount_out = self.amount_out.to_le_bytes();
+        *fee_paid = self.fee_paid.to_le_bytes();
+        legs_executed[0] = self.legs_executed;
+    }
+}
+
+/// Quote of a swap, returned to simulating clients.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct QuoteResult {
+    /// DESTINATION amount the legs output in total
+    pub amount_out: u64,
+
+    /// Number of legs quoted
+    pub legs_quoted: u8,
+
+    /// DESTINATION amount each leg outputs, in the order of the legs, 0 past the legs
+    pub leg_amounts_out: [u64; MAX_LEGS],
+}
+
+impl Sealed for QuoteResult {}
+impl Pack for QuoteResult {
+    const LEN: usize = 9 + 8 * MAX_LEGS;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 9 + 8 * MAX_LEGS];
+        let (amount_out, legs_quoted, leg_amounts_out) = array_refs![src, 8, 1, 8 * MAX_LEGS];
+        let legs_quoted = legs_quoted[0];
+        let mut result = QuoteResult {
+            amount_out: u64::from_le_bytes(*amount_out),
+            legs_quoted,
+            leg_amounts_out: [0; MAX_LEGS],
+        };
+        for (i, amount) in leg_amounts_out.chunks_exact(8).enumerate() {
+            result.leg_amounts_out[i] = u64::from_le_bytes(*array_ref![amount, 0, 8]);
+        }
+        if legs_quoted as usize > MAX_LEGS
+            || result.leg_amounts_out[legs_quoted as usize..]
+                .iter()
+                .any(|amount| *amount != 0)
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(result)
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 9 + 8 * MAX_LEGS];
+        let (amount_out, legs_quoted, leg_amounts_out) =
+            mut_array_refs![output, 8, 1, 8 * MAX_LEGS];
+        *amount_out = self.amount_out.to_le_bytes();
+        legs_quoted[0] = self.legs_quoted;
+        for (dst, amount) in leg_amounts_out
+            .chunks_exact_mut(8)
+            .zip(self.leg_amounts_out.iter())
+        {
+            dst.copy_from_slice(&amount.to_le_bytes());
+        }
+    }
+}
+
+/// Record of a single swap, written once into a receipt account supplied to the swap.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SwapReceipt {
+    /// Initialized state.
+    pub version: u8,
+
+    /// User transfer authority of the swap
+    pub user: Pubkey,
+
+    /// Mint of the SOURCE token
+    pub source_mint: Pubkey,
+
+    /// Mint of the DESTINATION token
+    pub destination_mint: Pubkey,
+
+    /// Slot the swap executed at
+    pub slot: u64,
+
+    /// Hash of the dex configs and dex accounts the swap was routed through
+    pub route_hash: [u8; 32],
+
+    /// SOURCE amount spent
+    pub amount_in: u64,
+
+    /// DESTINATION amount delivered to the user
+    pub amount_out: u64,
+
+    /// Executed price, scaled by `SWAP_PRICE_SCALE`
+    pub executed_price: u64,
+
+    /// DESTINATION amount paid in fees
+    pub fee: u64,
+}
+
+impl IsInitialized for SwapReceipt {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for SwapReceipt {}
+impl Pack for SwapReceipt {
+    const LEN: usize = 169;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 169];
+        let (
+            version,
+            user,
+            source_mint,
+            destination_mint,
+            slot,
+            route_hash,
+            amount_in,
+            amount_out,
+            executed_price,
+            fee,
+        ) = array_refs![src, 1, 32, 32, 32, 8, 32, 8, 8, 8, 8];
+        Ok(SwapReceipt {
+            version: version[0],
+            user: Pubkey::new_from_array(*user),
+            source_mint: Pubkey::new_from_array(*source_mint),
+            destination_mint: Pubkey::new_from_array(*destination_mint),
+            slot: u64::from_le_bytes(*slot),
+            route_hash: *route_hash,
+            amount_in: u64::from_le_bytes(*amount_in),
+            amount_out: u64::from_le_bytes(*amount_out),
+            executed_price: u64::from_le_bytes(*executed_price),
+            fee: u64::from_le_bytes(*fee),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 169];
+        let (
+            version,
+            user,
+            source_mint,
+            destination_mint,
+            slot,
+            route_hash,
+            amount_in,
+            amount_out,
+            executed_price,
+            fee,
+        ) = mut_array_refs![output, 1, 32, 32, 32, 8, 32, 8, 8, 8, 8];
+        version[0] = self.version;
+        user.copy_from_slice(self.user.as_ref());
+        source_mint.copy_from_slice(self.source_mint.as_ref());
+        destination_mint.copy_from_slice(self.destination_mint.as_ref());
+        *slot = self.slot.to_le_bytes();
+        *route_hash = self.route_hash;
+        *amount_in = self.amount_in.to_le_bytes();
+        *amount_out = self.amount_out.to_le_bytes();
+        *executed_price = self.executed_price.to_le_bytes();
+        *fee = self.fee.to_le_bytes();
+    }
+}
+
+/// Returns the hash of a route: the dex type and ratio of each leg, each followed by the
+/// keys of its `account_size` dex accounts, taken in turn from `dex_keys`.
+pub fn route_hash<'k>(
+    dex_configs: impl Iterator<Item = DexConfig>,
+    dex_keys: impl Iterator<Item = &'k Pubkey>,
+) -> Hash {
+    let mut route = Vec::new();
+    let mut dex_keys = dex_keys;
+    for dex_config in dex_configs {
+        route.push(dex_config.dex_type);
+        route.extend_from_slice(&dex_config.ratio.to_le_bytes());
+        for key in dex_keys.by_ref().take(dex_config.account_size) {
+            route.extend_from_slice(key.as_ref());
+        }
+    }
+    hash(&route)
+}
+
+/// Returns the commitment of `user` to swap `amount_in` for at least
+/// `minimum_amount_out` through the route of `route_hash`, blinded by `salt`.
+pub fn swap_commitment(
+    salt: &[u8; 32],
+    user: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+    route_hash: &Hash,
+) -> Hash {
+    hashv(&[
+        salt,
+        user.as_ref(),
+        &amount_in.to_le_bytes(),
+        &minimum_amount_out.to_le_bytes(),
+        route_hash.as_ref(),
+    ])
+}
+
+/// Swap committed to ahead of its execution, keeping its route and amounts out of
+/// sight until it is revealed.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SwapCommitment {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Bump seed of the commitment program address.
+    pub bump_seed: u8,
+
+    /// User transfer authority allowed to reveal the swap
+    pub user: Pubkey,
+
+    /// Commitment, see [swap_commitment]
+    pub commitment: [u8; 32],
+
+    /// Slot of the commitment, the swap can be revealed from the next one
+    pub slot: u64,
+}
+
+impl IsInitialized for SwapCommitment {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for SwapCommitment {}
+impl Pack for SwapCommitment {
+    const LEN: usize = 74;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 74];
+        let (version, bump_seed, user, commitment, slot) = array_refs![src, 1, 1, 32, 32, 8];
+        Ok(SwapCommitment {
+            version: version[0],
+            bump_seed: bump_seed[0],
+            user: Pubkey::new_from_array(*user),
+            commitment: *commitment,
+            slot: u64::from_le_bytes(*slot),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 74];
+        let (version, bump_seed, user, commitment, slot) = mut_array_refs![output, 1, 1, 32, 32, 8];
+        version[0] = self.version;
+        bump_seed[0] = self.bump_seed;
+        user.copy_from_slice(self.user.as_ref());
+        *commitment = self.commitment;
+        *slot = self.slot.to_le_bytes();
+    }
+}
+
+/// Verbosity of the program logs.
+#[repr(u8)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy)]
+pub enum LogLevel {
+    /// No logs besides events and return data.
+    Off = 0,
+    /// Only errors are logged.
+    Errors = 1,
+    /// Progress of every instruction is logged.
+    Full = 2,
+}
+
+impl TryFrom<u8> for LogLevel {
+    type Error = ProgramError;
+
+    fn try_from(log_level: u8) -> Result<Self, Self::Error> {
+        match log_level {
+            0 => Ok(LogLevel::Off),
+            1 => Ok(LogLevel::Errors),
+            2 => Ok(LogLevel::Full),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+impl LogLevel {
+    /// Returns true if errors are logged, never in `no-logs` builds.
+    pub fn logs_errors(&self) -> bool {
+        !cfg!(feature = "no-logs") && *self != LogLevel::Off
+    }
+
+    /// Returns true if the progress of instructions is logged, never in `no-logs` builds.
+    pub fn logs_progress(&self) -> bool {
+        !cfg!(feature = "no-logs") && *self == LogLevel::Full
+    }
+}
+
+/// Program-wide configuration, held by the program address derived from `[b"config"]`.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct GlobalConfig {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Bump seed of the config program address.
+    pub bump_seed: u8,
+
+    /// Admin allowed to update the config
+    pub admin: Pubkey,
+
+    /// Oldest oracle price, in slots, any oracle-consuming path accepts
+    pub oracle_max_age_slots: u64,
+
+    /// Multiple of the oracle confidence interval, in bps of the price, added to the
+    /// deviation oracle-bounded swaps allow
+    pub oracle_conf_multiplier: u16,
+
+    /// Verbosity of the logs of instructions passed the config
+    pub log_level: LogLevel,
+
+    /// Account swaps may tip lamports to, the default pubkey if tips are disabled
+    pub tip_account: Pubkey,
+
+    /// Largest share, in bps of the output, a relayed swap reimburses its relayer
+    pub relayer_fee_max_bps: u16,
+
+    /// Largest flat amount, in raw DESTINATION units, a relayed swap reimburses its relayer
+    pub relayer_fee_max_flat: u64,
+
+    /// Chunks swaps passed the config split their input into across the legs quoted
+    /// on-chain, 0 keeping the split of the route
+    pub split_parts: u8,
+}
+
+impl GlobalConfig {
+    /// Reimbursement of the relayer of a swap outputting `amount_out`, `fee_flat` plus
+    /// `fee_bps` of the output rounded down. `None` if either part exceeds its cap.
+    pub fn relayer_fee(&self, amount_out: u64, fee_bps: u16, fee_flat: u64) -> Option<u64> {
+        if fee_bps > self.relayer_fee_max_bps || fee_flat > self.relayer_fee_max_flat {
+            return None;
+        }
+        let (fee_variable, _) = split_by_bps(amount_out, fee_bps).ok()?;
+        fee_flat.checked_add(fee_variable)
+    }
+}
+
+impl IsInitialized for GlobalConfig {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for GlobalConfig {}
+impl Pack for GlobalConfig {
+    const LEN: usize = 88;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 88];
+        let (
+            version,
+            bump_seed,
+            admin,
+            oracle_max_age_slots,
+            oracle_conf_multiplier,
+            log_level,
+            tip_account,
+            relayer_fee_max_bps,
+            relayer_fee_max_flat,
+            split_parts,
+        ) = array_refs![src, 1, 1, 32, 8, 2, 1, 32, 2, 8, 1];
+        Ok(GlobalConfig {
+            version: version[0],
+            bump_seed: bump_seed[0],
+            admin: Pubkey::new_from_array(*admin),
+            oracle_max_age_slots: u64::from_le_bytes(*oracle_max_age_slots),
+            oracle_conf_multiplier: u16::from_le_bytes(*oracle_conf_multiplier),
+            log_level: LogLevel::try_from(log_level[0])?,
+            tip_account: Pubkey::new_from_array(*tip_account),
+            relayer_fee_max_bps: u16::from_le_bytes(*relayer_fee_max_bps),
+            relayer_fee_max_flat: u64::from_le_bytes(*relayer_fee_max_flat),
+            split_parts: split_parts[0],
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 88];
+        let (
+            version,
+            bump_seed,
+            admin,
+            oracle_max_age_slots,
+            oracle_conf_multiplier,
+            log_level,
+            tip_account,
+            relayer_fee_max_bps,
+            relayer_fee_max_flat,
+            split_parts,
+        ) = mut_array_refs![output, 1, 1, 32, 8, 2, 1, 32, 2, 8, 1];
+        version[0] = self.version;
+        bump_seed[0] = self.bump_seed;
+        admin.copy_from_slice(self.admin.as_ref());
+        *oracle_max_age_slots = self.oracle_max_age_slots.to_le_bytes();
+        *oracle_conf_multiplier = self.oracle_conf_multiplier.to_le_bytes();
+        log_level[0] = self.log_level as u8;
+        tip_account.copy_from_slice(self.tip_account.as_ref());
+        *relayer_fee_max_bps = self.relayer_fee_max_bps.to_le_bytes();
+        *relayer_fee_max_flat = self.relayer_fee_max_flat.to_le_bytes();
+        split_parts[0] = self.split_parts;
+    }
+}
+
+/// Executed swap prices are DESTINATION raw units per SOURCE raw unit, scaled by this factor.
+pub const SWAP_PRICE_SCALE: u128 = 1_000_000_000;
+
+/// Price accumulator of a mint pair, updated by every swap passing it, derived from
+/// `[b"twap", source mint, destination mint]`. Consumers derive a time-weighted average
+/// price from two observations of `cumulative_price`.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct PriceAccumulator {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Bump seed of the accumulator program address.
+    pub bump_seed: u8,
+
+    /// Mint of the SOURCE token
+    pub source_mint: Pubkey,
+
+    /// Mint of the DESTINATION token
+    pub destination_mint: Pubkey,
+
+    /// Unix timestamp of the last recorded swap
+    pub last_update_time: i64,
+
+    /// Price of the last recorded swap, scaled by `SWAP_PRICE_SCALE`
+    pub last_price: u64,
+
+    /// Sum of `last_price` times the seconds it held
+    pub cumulative_price: u128,
+
+    /// Sum of the SOURCE amounts swapped
+    pub cumulative_amount_in: u128,
+
+    /// Sum of the DESTINATION amounts received
+    pub cumulative_amount_out: u128,
+
+    /// Largest price move, in bps of `reference_price`, swaps may execute at within a
+    /// circuit breaker window, 0 disabling the circuit breaker
+    pub breaker_max_move_bps: u16,
+
+    /// Slots a circuit breaker window lasts before the reference price is re-anchored
+    pub breaker_window_slots: u64,
+
+    /// Price the current window's moves are measured against, 0 if not anchored yet
+    pub reference_price: u64,
+
+    /// Slot the reference price was anchored at
+    pub reference_slot: u64,
+}
+
+impl PriceAccumulator {
+    /// Records a swap of `amount_in` for `amount_out` at unix timestamp `now` and `slot`,
+    /// refusing it if its price moved too far from the window's reference price.
+    pub fn record(
+        &mut self,
+        now: i64,
+        slot: u64,
+        amount_in: u64,
+        amount_out: u64,
+    ) -> Result<(), OneSolError> {
+        if amount_in == 0 {
+            return Err(OneSolError::InvalidInput);
+        }
+        let price = checked_mul_div(amount_out, SWAP_PRICE_SCALE, amount_in)
+            .ok_or(OneSolError::ConversionFailure)?;
+        if self.breaker_max_move_bps > 0 {
+            if self.reference_price == 0
+                || slot.saturating_sub(self.reference_slot) > self.breaker_window_slots
+            {
+                self.reference_price = self.last_price_or(price)?;
+                self.reference_slot = slot;
+            }
+            let reference = self.reference_price as u128;
+            let moved = price.abs_diff(reference);
+            if moved * 10_000 > reference * self.breaker_max_move_bps as u128 {
+                return Err(OneSolError::CircuitBreakerTripped);
+            }
+        }
+        if self.last_update_time > 0 && now > self.last_update_time {
+            let elapsed = (now - self.last_update_time) as u128;
+            self.cumulative_price = self
+                .cumulative_price
+                .wrapping_add(self.last_price as u128 * elapsed);
+        }
+        self.last_price = u64::try_from(price).map_err(|_| OneSolError::ConversionFailure)?;
+        self.last_update_time = self.last_update_time.max(now);
+        self.cumulative_amount_in = self.cumulative_amount_in.wrapping_add(amount_in as u128);
+        self.cumulative_amount_out = self.cumulative_amount_out.wrapping_add(amount_out as u128);
+        Ok(())
+    }
+
+    /// Returns the last recorded price, or `price` if no swap was recorded yet.
+    fn last_price_or(&self, price: u128) -> Result<u64, OneSolError> {
+        if self.last_price > 0 {
+            Ok(self.last_price)
+        } else {
+            u64::try_from(price).map_err(|_| OneSolError::ConversionFailure)
+        }
+    }
+}
+
+impl IsInitialized for PriceAccumulator {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for PriceAccumulator {}
+impl Pack for PriceAccumulator {
+    const LEN: usize = 156;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 156];
+        let (
+            version,
+            bump_seed,
+            source_mint,
+            destination_mint,
+            last_update_time,
+            last_price,
+            cumulative_price,
+            cumulative_amount_in,
+            cumulative_amount_out,
+            breaker_max_move_bps,
+            breaker_window_slots,
+            reference_price,
+            reference_slot,
+        ) = array_refs![src, 1, 1, 32, 32, 8, 8, 16, 16, 16, 2, 8, 8, 8];
+        Ok(PriceAccumulator {
+            version: version[0],
+            bump_seed: bump_seed[0],
+            source_mint: Pubkey::new_from_array(*source_mint),
+            destination_mint: Pubkey::new_from_array(*destination_mint),
+            last_update_time: i64::from_le_bytes(*last_update_time),
+            last_price: u64::from_le_bytes(*last_price),
+            cumulative_price: u128::from_le_bytes(*cumulative_price),
+            cumulative_amount_in: u128::from_le_bytes(*cumulative_amount_in),
+            cumulative_amount_out: u128::from_le_bytes(*cumulative_amount_out),
+            breaker_max_move_bps: u16::from_le_bytes(*breaker_max_move_bps),
+            breaker_window_slots: u64::from_le_bytes(*breaker_window_slots),
+            reference_price: u64::from_le_bytes(*reference_price),
+            reference_slot: u64::from_le_bytes(*reference_slot),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 156];
+        let (
+            version,
+            bump_seed,
+            source_mint,
+            destination_mint,
+            last_update_time,
+            last_price,
+            cumulative_price,
+            cumulative_amount_in,
+            cumulative_amount_out,
+            breaker_max_move_bps,
+            breaker_window_slots,
+            reference_price,
+            reference_slot,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 8, 8, 16, 16, 16, 2, 8, 8, 8];
+        version[0] = self.version;
+        bump_seed[0] = self.bump_seed;
+        source_mint.copy_from_slice(self.source_mint.as_ref());
+        destination_mint.copy_from_slice(self.destination_mint.as_ref());
+        *last_update_time = self.last_update_time.to_le_bytes();
+        *last_price = self.last_price.to_le_bytes();
+        *cumulative_price = self.cumulative_price.to_le_bytes();
+        *cumulative_amount_in = self.cumulative_amount_in.to_le_bytes();
+        *cumulative_amount_out = self.cumulative_amount_out.to_le_bytes();
+        *breaker_max_move_bps = self.breaker_max_move_bps.to_le_bytes();
+        *breaker_window_slots = self.breaker_window_slots.to_le_bytes();
+        *reference_price = self.reference_price.to_le_bytes();
+        *reference_slot = self.reference_slot.to_le_bytes();
+    }
+}
+
+/// Lifetime swap statistics of a mint, derived from `[b"stats", mint]`, updated by swaps
+/// passing it.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct MintStats {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Bump seed of the stats program address.
+    pub bump_seed: u8,
+
+    /// Mint the statistics are about
+    pub mint: Pubkey,
+
+    /// Sum of the amounts swapped from the mint
+    pub volume_in: u128,
+
+    /// Sum of the amounts swapped into the mint
+    pub volume_out: u128,
+
+    /// Number of swaps the mint took part in
+    pub swap_count: u64,
+
+    /// Sum of the fees collected in the mint
+    pub fees: u128,
+}
+
+impl MintStats {
+    /// Records a swap spending `amount` of the mint.
+    pub fn record_in(&mut self, amount: u64) {
+        self.volume_in = self.volume_in.wrapping_add(amount as u128);
+        self.swap_count = self.swap_count.wrapping_add(1);
+    }
+
+    /// Records a swap returning `amount` of the mint, `fee` of it collected.
+    pub fn record_out(&mut self, amount: u64, fee: u64) {
+        self.volume_out = self.volume_out.wrapping_add(amount as u128);
+        self.fees = self.fees.wrapping_add(fee as u128);
+        self.swap_count = self.swap_count.wrapping_add(1);
+    }
+}
+
+impl IsInitialized for MintStats {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for MintStats {}
+impl Pack for MintStats {
+    const LEN: usize = 90;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 90];
+        let (version, bump_seed, mint, volume_in, volume_out, swap_count, fees) =
+            array_refs![src, 1, 1, 32, 16, 16, 8, 16];
+        Ok(MintStats {
+            version: version[0],
+            bump_seed: bump_seed[0],
+            mint: Pubkey::new_from_array(*mint),
+            volume_in: u128::from_le_bytes(*volume_in),
+            volume_out: u128::from_le_bytes(*volume_out),
+            swap_count: u64::from_le_bytes(*swap_count),
+            fees: u128::from_le_bytes(*fees),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 90];
+        let (version, bump_seed, mint, volume_in, volume_out, swap_count, fees) =
+            mut_array_refs![output, 1, 1, 32, 16, 16, 8, 16];
+        version[0] = self.version;
+        bump_seed[0] = self.bump_seed;
+        mint.copy_from_slice(self.mint.as_ref());
+        *volume_in = self.volume_in.to_le_bytes();
+        *volume_out = self.volume_out.to_le_bytes();
+        *swap_count = self.swap_count.to_le_bytes();
+        *fees = self.fees.to_le_bytes();
+    }
+}
+
+/// Number of fee discount tiers of the stake pool.
+pub const STAKE_TIERS: usize = 3;
+
+/// Accumulated stake rewards per staked raw unit are scaled by this factor.
+pub const REWARD_PER_SHARE_SCALE: u128 = 1_000_000_000_000;
+
+/// Fee discount granted to stakers of at least `min_stake`.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub struct StakeTier {
+    /// Least amount staked, in raw 1SOL units, for the discount
+    pub min_stake: u64,
+
+    /// Discount, in bps of the fee
+    pub discount_bps: u16,
+}
+
+impl StakeTier {
+    /// Length of a packed tier
+    pub const LEN: usize = 10;
+
+    /// Unpacks a tier from its 10 bytes.
+    pub fn unpack(src: &[u8; 10]) -> Self {
+        let (min_stake, discount_bps) = array_refs![src, 8, 2];
+        StakeTier {
+            min_stake: u64::from_le_bytes(*min_stake),
+            discount_bps: u16::from_le_bytes(*discount_bps),
+        }
+    }
+
+    /// Packs a tier into its 10 bytes.
+    pub fn pack(&self, output: &mut [u8; 10]) {
+        let (min_stake, discount_bps) = mut_array_refs![output, 8, 2];
+        *min_stake = self.min_stake.to_le_bytes();
+        *discount_bps = self.discount_bps.to_le_bytes();
+    }
+}
+
+/// 1SOL stake pool, derived from `[b"stake_pool"]`. Its authority, derived from
+/// `[pool, nonce]`, owns the stake and rewards vaults.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct StakePool {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Bump seed of the pool program address.
+    pub bump_seed: u8,
+
+    /// Nonce of the pool authority
+    pub nonce: u8,
+
+    /// Mint of the staked 1SOL token
+    pub mint: Pubkey,
+
+    /// Token account holding the staked tokens
+    pub vault: Pubkey,
+
+    /// Token account holding the rewards funded by the config admin
+    pub rewards_vault: Pubkey,
+
+    /// Sum of the amounts staked
+    pub total_staked: u64,
+
+    /// Rewards funded per staked raw unit, scaled by `REWARD_PER_SHARE_SCALE`
+    pub reward_per_share: u128,
+
+    /// Fee discount tiers, by increasing `min_stake`
+    pub tiers: [StakeTier; STAKE_TIERS],
+}
+
+impl StakePool {
+    /// Returns the fee discount, in bps, of a staker of `staked`.
+    pub fn discount_bps(&self, staked: u64) -> u16 {
+        self.tiers
+            .iter()
+            .filter(|tier| tier.min_stake > 0 && staked >= tier.min_stake)
+            .map(|tier| tier.discount_bps)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns `fee` less the discount of a staker of `staked`, the discount rounded down.
+    pub fn discounted_fee(&self, fee: u64, staked: u64) -> u64 {
+        split_by_bps(fee, self.discount_bps(staked)).map_or(fee, |(_discount, fee)| fee)
+    }
+
+    /// Returns the rewards a stake of `amount` would have accrued since the pool was
+    /// created, scaled by `REWARD_PER_SHARE_SCALE`.
+    pub fn accrued_rewards(&self, amount: u64) -> Option<u128> {
+        (amount as u128).checked_mul(self.reward_per_share)
+    }
+
+    /// Shares `amount` of rewards among the stakers, proportionally to their stake.
+    pub fn fund(&mut self, amount: u64) -> Option<()> {
+        let increase = checked_mul_div(amount, REWARD_PER_SHARE_SCALE, self.total_staked)?;
+        self.reward_per_share = self.reward_per_share.checked_add(increase)?;
+        Some(())
+    }
+}
+
+impl IsInitialized for StakePool {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for StakePool {}
+impl Pack for StakePool {
+    const LEN: usize = 153;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 153];
+        let (
+            version,
+            bump_seed,
+            nonce,
+            mint,
+            vault,
+            rewards_vault,
+            total_staked,
+            reward_per_share,
+            tiers,
+        ) = array_refs![src, 1, 1, 1, 32, 32, 32, 8, 16, 30];
+        let mut unpacked_tiers = [StakeTier::default(); STAKE_TIERS];
+        for (i, tier) in unpacked_tiers.iter_mut().enumerate() {
+            *tier = StakeTier::unpack(array_ref![tiers, i * StakeTier::LEN, 10]);
+        }
+        Ok(StakePool {
+            version: version[0],
+            bump_seed: bump_seed[0],
+            nonce: nonce[0],
+            mint: Pubkey::new_from_array(*mint),
+            vault: Pubkey::new_from_array(*vault),
+            rewards_vault: Pubkey::new_from_array(*rewards_vault),
+            total_staked: u64::from_le_bytes(*total_staked),
+            reward_per_share: u128::from_le_bytes(*reward_per_share),
+            tiers: unpacked_tiers,
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 153];
+        let (
+            version,
+            bump_seed,
+            nonce,
+            mint,
+            vault,
+            rewards_vault,
+            total_staked,
+            reward_per_share,
+            tiers,
+        ) = mut_array_refs![output, 1, 1, 1, 32, 32, 32, 8, 16, 30];
+        version[0] = self.version;
+        bump_seed[0] = self.bump_seed;
+        nonce[0] = self.nonce;
+        mint.copy_from_slice(self.mint.as_ref());
+        vault.copy_from_slice(self.vault.as_ref());
+        rewards_vault.copy_from_slice(self.rewards_vault.as_ref());
+        *total_staked = self.total_staked.to_le_bytes();
+        *reward_per_share = self.reward_per_share.to_le_bytes();
+        for (i, tier) in self.tiers.iter().enumerate() {
+            tier.pack(array_mut_ref![tiers, i * StakeTier::LEN, 10]);
+        }
+    }
+}
+
+/// 1SOL stake of a user, derived from `[b"stake", owner]`.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct StakeAccount {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Bump seed of the stake program address.
+    pub bump_seed: u8,
+
+    /// Staker, allowed to unstake and claim the rewards
+    pub owner: Pubkey,
+
+    /// Amount staked
+    pub amount: u64,
+
+    /// Rewards of `amount` already paid out or earned before staking, scaled by
+    /// `REWARD_PER_SHARE_SCALE`
+    pub reward_debt: u128,
+}
+
+impl StakeAccount {
+    /// Returns the rewards of the stake not paid out yet.
+    pub fn pending_rewards(&self, pool: &StakePool) -> Option<u64> {
+        let earned = pool
+            .accrued_rewards(self.amount)?
+            .checked_sub(self.reward_debt)?;
+        u64::try_from(earned / REWARD_PER_SHARE_SCALE).ok()
+    }
+}
+
+impl IsInitialized for StakeAccount {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for StakeAccount {}
+impl Pack for StakeAccount {
+    const LEN: usize = 58;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 58];
+        let (version, bump_seed, owner, amount, reward_debt) = array_refs![src, 1, 1, 32, 8, 16];
+        Ok(StakeAccount {
+            version: version[0],
+            bump_seed: bump_seed[0],
+            owner: Pubkey::new_from_array(*owner),
+            amount: u64::from_le_bytes(*amount),
+            reward_debt: u128::from_le_bytes(*reward_debt),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 58];
+        let (version, bump_seed, owner, amount, reward_debt) =
+            mut_array_refs![output, 1, 1, 32, 8, 16];
+        version[0] = self.version;
+        bump_seed[0] = self.bump_seed;
+        owner.copy_from_slice(self.owner.as_ref());
+        *amount = self.amount.to_le_bytes();
+        *reward_debt = self.reward_debt.to_le_bytes();
+    }
+}
+
+/// Swap volume, in raw SOURCE units, earning the points rate of a pair.
+pub const POINTS_VOLUME_UNIT: u128 = 1_000_000;
+
+/// Liquidity-mining rate of a mint pair, derived from `[b"rewards", source mint,
+/// destination mint]`, set by the config admin.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct PairRewards {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Bump seed of the pair rewards program address.
+    pub bump_seed: u8,
+
+    /// Mint of the SOURCE token
+    pub source_mint: Pubkey,
+
+    /// Mint of the DESTINATION token
+    pub destination_mint: Pubkey,
+
+    /// Points earned per `POINTS_VOLUME_UNIT` swapped, 0 pausing the rewards
+    pub points_per_unit: u64,
+
+    /// Sum of the points earned on the pair
+    pub total_points: u64,
+}
+
+impl PairRewards {
+    /// Returns the points a swap of `amount_in` earns.
+    pub fn points(&self, amount_in: u64) -> u64 {
+        mul_div_floor(amount_in, self.points_per_unit, POINTS_VOLUME_UNIT).unwrap_or(u64::MAX)
+    }
+}
+
+impl IsInitialized for PairRewards {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for PairRewards {}
+impl Pack for PairRewards {
+    const LEN: usize = 82;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 82];
+        let (version, bump_seed, source_mint, destination_mint, points_per_unit, total_points) =
+            array_refs![src, 1, 1, 32, 32, 8, 8];
+        Ok(PairRewards {
+            version: version[0],
+            bump_seed: bump_seed[0],
+            source_mint: Pubkey::new_from_array(*source_mint),
+            destination_mint: Pubkey::new_from_array(*destination_mint),
+            points_per_unit: u64::from_le_bytes(*points_per_unit),
+            total_points: u64::from_le_bytes(*total_points),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 82];
+        let (version, bump_seed, source_mint, destination_mint, points_per_unit, total_points) =
+            mut_array_refs![output, 1, 1, 32, 32, 8, 8];
+        version[0] = self.version;
+        bump_seed[0] = self.bump_seed;
+        source_mint.copy_from_slice(self.source_mint.as_ref());
+        destination_mint.copy_from_slice(self.destination_mint.as_ref());
+        *points_per_unit = self.points_per_unit.to_le_bytes();
+        *total_points = self.total_points.to_le_bytes();
+    }
+}
+
+/// Liquidity-mining points of a user, derived from `[b"points", owner]`, redeemable one
+/// for one against raw units of the rewards vault.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct RewardPoints {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Bump seed of the points program address.
+    pub bump_seed: u8,
+
+    /// User earning the points by swapping, allowed to claim them
+    pub owner: Pubkey,
+
+    /// Points not claimed yet
+    pub points: u64,
+
+    /// Sum of the points claimed
+    pub claimed: u64,
+}
+
+impl IsInitialized for RewardPoints {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for RewardPoints {}
+impl Pack for RewardPoints {
+    const LEN: usize = 50;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 50];
+        let (version, bump_seed, owner, points, claimed) = array_refs![src, 1, 1, 32, 8, 8];
+        Ok(RewardPoints {
+            version: version[0],
+            bump_seed: bump_seed[0],
+            owner: Pubkey::new_from_array(*owner),
+            points: u64::from_le_bytes(*points),
+            claimed: u64::from_le_bytes(*claimed),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 50];
+        let (version, bump_seed, owner, points, claimed) = mut_array_refs![output, 1, 1, 32, 8, 8];
+        version[0] = self.version;
+        bump_seed[0] = self.bump_seed;
+        owner.copy_from_slice(self.owner.as_ref());
+        *points = self.points.to_le_bytes();
+        *claimed = self.claimed.to_le_bytes();
+    }
+}
+
+/// Credential gate of a mint pair, derived from `[b"gate", source mint, destination
+/// mint]`, set by the config admin. Only `permissioned` builds enforce gates.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct PairGate {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Bump seed of the gate program address.
+    pub bump_seed: u8,
+
+    /// Mint of the SOURCE token
+    pub source_mint: Pubkey,
+
+    /// Mint of the DESTINATION token
+    pub destination_mint: Pubkey,
+
+    /// Mint of the credential traders of the pair must hold, the default pubkey if the
+    /// pair is open
+    pub credential_mint: Pubkey,
+
+    /// Least credential balance of a trader, at least 1
+    pub min_balance: u64,
+}
+
+impl PairGate {
+    /// Returns true if traders of the pair need a credential.
+    pub fn is_flagged(&self) -> bool {
+        self.credential_mint != Pubkey::default()
+    }
+}
+
+impl IsInitialized for PairGate {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for PairGate {}
+impl Pack for PairGate {
+    const LEN: usize = 106;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 106];
+        let (version, bump_seed, source_mint, destination_mint, credential_mint, min_balance) =
+            array_refs![src, 1, 1, 32, 32, 32, 8];
+        Ok(PairGate {
+            version: version[0],
+            bump_seed: bump_seed[0],
+            source_mint: Pubkey::new_from_array(*source_mint),
+            destination_mint: Pubkey::new_from_array(*destination_mint),
+            credential_mint: Pubkey::new_from_array(*credential_mint),
+            min_balance: u64::from_le_bytes(*min_balance),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 106];
+        let (version, bump_seed, source_mint, destination_mint, credential_mint, min_balance) =
+            mut_array_refs![output, 1, 1, 32, 32, 32, 8];
+        version[0] = self.version;
+        bump_seed[0] = self.bump_seed;
+        source_mint.copy_from_slice(self.source_mint.as_ref());
+        destination_mint.copy_from_slice(self.destination_mint.as_ref());
+        credential_mint.copy_from_slice(self.credential_mint.as_ref());
+        *min_balance = self.min_balance.to_le_bytes();
+    }
+}
+
+/// Protocol-owned inventory of a mint pair, derived from `[b"inventory", source mint,
+/// destination mint]`, set by the config admin. Swaps fill from the inventory at the
+/// oracle price less the spread.
+#[repr(C)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct InventoryPair {
+    /// Initialized state.
+    pub version: u8,
+
+    /// Bump seed of the inventory pair program address.
+    pub bump_seed: u8,
+
+    /// Mint of the SOURCE token
+    pub source_mint: Pubkey,
+
+    /// Mint of the DESTINATION token
+    pub destination_mint: Pubkey,
+
+    /// Oracle pricing SOURCE in DESTINATION, the default pubkey if fills are disabled
+    pub oracle: Pubkey,
+
+    /// Discount, in bps of the oracle price, the inventory fills at
+    pub spread_bps: u16,
+}
+
+impl IsInitialized for InventoryPair {
+    fn is_initialized(&self) -> bool {
+        self.version == 1
+    }
+}
+
+impl Sealed for InventoryPair {}
+impl Pack for InventoryPair {
+    const LEN: usize = 100;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 100];
+        let (version, bump_seed, source_mint, destination_mint, oracle, spread_bps) =
+            array_refs![src, 1, 1, 32, 32, 32, 2];
+        Ok(InventoryPair {
+            version: version[0],
+            bump_seed: bump_seed[0],
+            source_mint: Pubkey::new_from_array(*source_mint),
+            destination_mint: Pubkey::new_from_array(*destination_mint),
+            oracle: Pubkey::new_from_array(*oracle),
+            spread_bps: u16::from_le_bytes(*spread_bps),
+        })
+    }
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 100];
+        let (version, bump_seed, source_mint, destination_mint, oracle, spread_bps) =
+            mut_array_refs![output, 1, 1, 32, 32, 32, 2];
+        version[0] = self.version;
+        bump_seed[0] = self.bump_seed;
+        source_mint.copy_from_slice(self.source_mint.as_ref());
+        destination_mint.copy_from_slice(self.destination_mint.as_ref());
+        oracle.copy_from_slice(self.oracle.as_ref());
+        *spread_bps = self.spread_bps.to_le_bytes();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEST_VERSION: u8 = 1;
+    const TEST_NONCE: u8 = 255;
+    const TEST_TOKEN_PROGRAM_ID: Pubkey = Pubkey::new_from_array([1u8; 32]);
+    const TEST_TOKEN: Pubkey = Pubkey::new_from_array([2u8; 32]);
+    const TEST_TOKEN_MINT: Pubkey = Pubkey::new_from_array([5u8; 32]);
+    const TEST_ADMIN: Pubkey = Pubkey::new_from_array([6u8; 32]);
+    const TEST_TREASURY: Pubkey = Pubkey::new_from_array([7u8; 32]);
+
+    #[test]
+    pub fn test_onesol_state_pack() {
+        let test_state = OneSolState {
+            version: TEST_VERSION,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token: TEST_TOKEN,
+            token_mint: TEST_TOKEN_MINT,
+            admin: TEST_ADMIN,
+            fee_numerator: 3,
+            fee_denominator: 1_000,
+            treasury: TEST_TREASURY,
+            is_paused: true,
+            max_referral_fee_bps: 50,
+        };
+        let mut packed = [0u8; OneSolState::LEN];
+        OneSolState::pack_into_slice(&test_state, &mut packed);
+        let unpacked = OneSolState::unpack(&packed).unwrap();
+        assert_eq!(test_state, unpacked);
+
+        let view = OneSolState::view(&packed).unwrap();
+        assert_eq!(view.admin(), TEST_ADMIN);
+        assert_eq!(view.fee_numerator(), 3);
+        assert_eq!(view.fee_denominator(), 1_000);
+        assert_eq!(view.treasury(), TEST_TREASURY);
+        assert_eq!(view.fee(10_000).unwrap(), 30);
+        assert_eq!(view.fee(333).unwrap(), 0);
+        assert!(view.is_paused());
+        assert_eq!(view.max_referral_fee_bps(), 50);
+
+        // states packed before referral fees allow none
+        let view = OneSolState::view(&packed[..OneSolState::UNREFERRED_LEN]).unwrap();
+        assert!(view.is_paused());
+        assert_eq!(view.max_referral_fee_bps(), 0);
+
+        // states packed before the pause flag are never paused
+        let view = OneSolState::view(&packed[..OneSolState::UNPAUSABLE_LEN]).unwrap();
+        assert_eq!(view.treasury(), TEST_TREASURY);
+        assert!(!view.is_paused());
+
+        // states packed before fees charge none
+        let mut packed = vec![1u8, TEST_NONCE];
+        packed.extend_from_slice(&TEST_TOKEN_PROGRAM_ID.to_bytes());
+        packed.extend_from_slice(&TEST_TOKEN.to_bytes());
+        packed.extend_from_slice(&TEST_TOKEN_MINT.to_bytes());
+        assert_eq!(packed.len(), OneSolState::LEGACY_LEN);
+        let view = OneSolState::view(&packed).unwrap();
+        assert_eq!(view.version(), TEST_VERSION);
+        assert_eq!(view.nonce(), TEST_NONCE);
+        assert_eq!(view.token_program_id(), TEST_TOKEN_PROGRAM_ID);
+        assert_eq!(view.token(), TEST_TOKEN);
+        assert_eq!(view.token_mint(), TEST_TOKEN_MINT);
+        assert_eq!(view.admin(), Pubkey::default());
+        assert_eq!(view.treasury(), Pubkey::default());
+        assert_eq!(view.fee(10_000).unwrap(), 0);
+        assert_eq!(
+            OneSolState::view(&packed[1..]).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+        packed[0] = 0;
+        assert_eq!(
+            OneSolState::view(&packed).unwrap_err(),
+            ProgramError::UninitializedAccount
+        );
+    }
+
+    #[test]
+    pub fn test_check_fee_rate() {
+        assert!(OneSolState::check_fee_rate(0, 0).is_ok());
+        assert!(OneSolState::check_fee_rate(3, 1_000).is_ok());
+        assert!(OneSolState::check_fee_rate(1_000, 1_000).is_ok());
+        assert_eq!(
+            OneSolState::check_fee_rate(1_001, 1_000).unwrap_err(),
+            OneSolError::InvalidInput
+        );
+        assert_eq!(
+            OneSolState::check_fee_rate(1, 0).unwrap_err(),
+            OneSolError::InvalidInput
+        );
+    }
+
+    #[test]
+    pub fn test_order_pack() {
+        let order = Order {
+            version: 1,
+            nonce: TEST_NONCE,
+            kind: OrderKind::Twap,
+            owner: Pubkey::new_from_array([3u8; 32]),
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            source_mint: TEST_TOKEN_MINT,
+            destination_mint: Pubkey::new_from_array([6u8; 32]),
+            escrow: TEST_TOKEN,
+            destination: Pubkey::new_from_array([4u8; 32]),
+            amount_in: 1_000_000,
+            expected_amount_out: 2_000_000,
+            max_deviation_bps: 50,
+            num_slices: 10,
+            slice_interval: 60,
+            start_time: 1_625_000_000,
+            filled_amount_in: 300_000,
+            filled_amount_out: 599_000,
+            slices_filled: 3,
+            oracle: Pubkey::default(),
+            trigger_price: 0,
+            expires_at_slot: 100,
+            keeper_fee_bps: 10,
+            keeper_fee_flat: 5,
+            page: Pubkey::new_from_array([7u8; 32]),
+        };
+        let mut packed = [0u8; Order::LEN];
+        Order::pack_into_slice(&order, &mut packed);
+        let unpacked = Order::unpack(&packed).unwrap();
+        assert_eq!(order, unpacked);
+        assert!(!unpacked.is_filled());
+        assert!(!unpacked.kind.is_conditional());
+        packed[2] = OrderKind::PoolTakeProfit as u8 + 1;
+        assert_eq!(
+            Order::unpack(&packed).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+        assert!(!unpacked.is_expired(100));
+        assert!(unpacked.is_expired(101));
+        assert_eq!(unpacked.current_slice_target(), 400_000);
+        let last_slice = Order {
+            amount_in: 1_000_003,
+            slices_filled: 9,
+            ..order
+        };
+        assert_eq!(last_slice.current_slice_target(), 1_000_003);
+
+        assert_eq!(order.keeper_fee_budget(), Some(1_000 + 5 * 11));
+        assert_eq!(order.keeper_fee(100_000, false), 100);
+        assert_eq!(order.keeper_fee(100_000, true), 105);
+        let expensive = Order {
+            keeper_fee_flat: u64::MAX,
+            ..order
+        };
+        assert_eq!(expensive.keeper_fee_budget(), None);
+
+        packed[2] = 9;
+        assert_eq!(
+            Order::unpack_from_slice(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    pub fn test_recurring_payment_pack() {
+        let payment = RecurringPayment {
+            version: 1,
+            nonce: TEST_NONCE,
+            payer: Pubkey::new_from_array([3u8; 32]),
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            source: TEST_TOKEN,
+            source_mint: TEST_TOKEN_MINT,
+            destination: Pubkey::new_from_array([4u8; 32]),
+            destination_mint: Pubkey::new_from_array([6u8; 32]),
+            amount_in: 10_000,
+            minimum_amount_out: 9_900,
+            period: 2_592_000,
+            max_total_in: 120_000,
+            total_in: 20_000,
+            total_out: 19_850,
+            next_payment_time: 1_630_000_000,
+            escrow: Pubkey::new_from_array([5u8; 32]),
+            escrow_nonce: 254,
+        };
+        let mut packed = [0u8; RecurringPayment::LEN];
+        RecurringPayment::pack_into_slice(&payment, &mut packed);
+        let unpacked = RecurringPayment::unpack(&packed).unwrap();
+        assert_eq!(payment, unpacked);
+    }
+
+    #[test]
+    pub fn test_swap_result_pack() {
+        let result = SwapResult {
+            amount_in_spent: 1_000,
+            amount_out: 990,
+            fee_paid: 3,
+            legs_executed: 2,
+        };
+        let mut packed = [0u8; SwapResult::LEN];
+        SwapResult::pack_into_slice(&result, &mut packed);
+        assert_eq!(packed[..8], 1_000u64.to_le_bytes());
+        assert_eq!(packed[24], 2);
+        assert_eq!(SwapResult::unpack_from_slice(&packed).unwrap(), result);
+    }
+
+    #[test]
+    pub fn test_quote_result_pack() {
+        let mut result = QuoteResult {
+            amount_out: 990,
+            legs_quoted: 2,
+            leg_amounts_out: [0; MAX_LEGS],
+        };
+        result.leg_amounts_out[..2].copy_from_slice(&[500, 490]);
+        let mut packed = [0u8; QuoteResult::LEN];
+        QuoteResult::pack_into_slice(&result, &mut packed);
+        assert_eq!(packed[8], 2);
+        assert_eq!(packed[9..17], 500u64.to_le_bytes());
+        assert_eq!(QuoteResult::unpack_from_slice(&packed).unwrap(), result);
+        // an amount past the legs quoted
+        packed[25] = 1;
+        assert_eq!(
+            QuoteResult::unpack_from_slice(&packed).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+    }
+
+    #[test]
+    pub fn test_swap_receipt_pack() {
+        let receipt = SwapReceipt {
+            version: 1,
+            user: Pubkey::new_from_array([1u8; 32]),
+            source_mint: Pubkey::new_from_array([2u8; 32]),
+            destination_mint: Pubkey::new_from_array([3u8; 32]),
+            slot: 77,
+            route_hash: [4u8; 32],
+            amount_in: 1_000,
+            amount_out: 1_990,
+            executed_price: 1_990_000_000,
+            fee: 10,
+        };
+        let mut packed = [0u8; SwapReceipt::LEN];
+        SwapReceipt::pack_into_slice(&receipt, &mut packed);
+        assert_eq!(SwapReceipt::unpack(&packed).unwrap(), receipt);
+    }
+
+    #[test]
+    pub fn test_swap_commitment() {
+        let commitment = SwapCommitment {
+            version: 1,
+            bump_seed: 253,
+            user: Pubkey::new_from_array([1u8; 32]),
+            commitment: [2u8; 32],
+            slot: 77,
+        };
+        let mut packed = [0u8; SwapCommitment::LEN];
+        SwapCommitment::pack_into_slice(&commitment, &mut packed);
+        assert_eq!(SwapCommitment::unpack(&packed).unwrap(), commitment);
+
+        let keys = [
+            Pubkey::new_from_array([3u8; 32]),
+            Pubkey::new_from_array([4u8; 32]),
+        ];
+        let configs = [
+            DexConfig::new_dex_config(0, 1, 6_000),
+            DexConfig::new_dex_config(0, 1, 4_000),
+        ];
+        let route = route_hash(configs.iter().copied(), keys.iter());
+        // a leg's accounts and ratio are both part of the route
+        assert_ne!(
+            route,
+            route_hash(configs.iter().copied(), keys.iter().rev())
+        );
+        assert_ne!(
+            route,
+            route_hash(configs.iter().rev().copied(), keys.iter())
+        );
+        let user = Pubkey::new_from_array([5u8; 32]);
+        let committed = swap_commitment(&[6u8; 32], &user, 1_000, 990, &route);
+        assert_ne!(
+            committed,
+            swap_commitment(&[7u8; 32], &user, 1_000, 990, &route)
+        );
+        assert_ne!(
+            committed,
+            swap_commitment(&[6u8; 32], &user, 1_000, 991, &route)
+        );
+    }
+
+    #[test]
+    pub fn test_global_config_pack() {
+        let config = GlobalConfig {
+            version: 1,
+            bump_seed: 255,
+            admin: Pubkey::new_from_array([9u8; 32]),
+            oracle_max_age_slots: 25,
+            oracle_conf_multiplier: 2,
+            log_level: LogLevel::Errors,
+            tip_account: Pubkey::new_from_array([8u8; 32]),
+            relayer_fee_max_bps: 30,
+            relayer_fee_max_flat: 5_000,
+            split_parts: 8,
+        };
+        let mut packed = [0u8; GlobalConfig::LEN];
+        GlobalConfig::pack_into_slice(&config, &mut packed);
+        assert_eq!(GlobalConfig::unpack(&packed).unwrap(), config);
+    }
+
+    #[test]
+    pub fn test_relayer_fee() {
+        let config = GlobalConfig {
+            version: 1,
+            bump_seed: 255,
+            admin: Pubkey::default(),
+            oracle_max_age_slots: 25,
+            oracle_conf_multiplier: 0,
+            log_level: LogLevel::Full,
+            tip_account: Pubkey::default(),
+            relayer_fee_max_bps: 30,
+            relayer_fee_max_flat: 5_000,
+            split_parts: 0,
+        };
+        assert_eq!(config.relayer_fee(1_000_000, 30, 5_000), Some(8_000));
+        assert_eq!(config.relayer_fee(1_000_000, 0, 0), Some(0));
+        assert_eq!(config.relayer_fee(1_000_000, 31, 0), None);
+        assert_eq!(config.relayer_fee(1_000_000, 0, 5_001), None);
+    }
+
+    #[test]
+    pub fn test_mint_stats() {
+        let mut stats = MintStats {
+            version: 1,
+            bump_seed: 254,
+            mint: Pubkey::new_from_array([3u8; 32]),
+            volume_in: 0,
+            volume_out: 0,
+            swap_count: 0,
+            fees: 0,
+        };
+        stats.record_in(1_000);
+        stats.record_out(400, 4);
+        assert_eq!(stats.volume_in, 1_000);
+        assert_eq!(stats.volume_out, 400);
+        assert_eq!(stats.fees, 4);
+        assert_eq!(stats.swap_count, 2);
+
+        let mut packed = [0u8; MintStats::LEN];
+        MintStats::pack_into_slice(&stats, &mut packed);
+        assert_eq!(MintStats::unpack(&packed).unwrap(), stats);
+    }
+
+    #[test]
+    pub fn test_price_accumulator() {
+        let mut accumulator = PriceAccumulator {
+            version: 1,
+            bump_seed: 254,
+            source_mint: TEST_TOKEN_MINT,
+            destination_mint: Pubkey::new_from_array([6u8; 32]),
+            last_update_time: 0,
+            last_price: 0,
+            cumulative_price: 0,
+            cumulative_amount_in: 0,
+            cumulative_amount_out: 0,
+            breaker_max_move_bps: 0,
+            breaker_window_slots: 0,
+            reference_price: 0,
+            reference_slot: 0,
+        };
+        accumulator.record(1_000, 1, 100, 250).unwrap();
+        assert_eq!(accumulator.last_price, 2_500_000_000);
+        assert_eq!(accumulator.cumulative_price, 0);
+        accumulator.record(1_010, 2, 100, 200).unwrap();
+        assert_eq!(accumulator.cumulative_price, 25_000_000_000);
+        assert_eq!(accumulator.last_price, 2_000_000_000);
+        accumulator.record(1_010, 3, 200, 500).unwrap();
+        assert_eq!(accumulator.cumulative_price, 25_000_000_000);
+        assert_eq!(accumulator.cumulative_amount_in, 400);
+        assert_eq!(accumulator.cumulative_amount_out, 950);
+        assert!(accumulator.record(1_020, 4, 0, 1).is_err());
+
+        // 10% moves within 100 slots trip the circuit breaker
+        accumulator.breaker_max_move_bps = 1_000;
+        accumulator.breaker_window_slots = 100;
+        accumulator.record(1_020, 10, 100, 260).unwrap();
+        assert_eq!(accumulator.reference_price, 2_500_000_000);
+        assert_eq!(
+            accumulator.record(1_030, 50, 100, 200),
+            Err(OneSolError::CircuitBreakerTripped)
+        );
+        accumulator.record(1_030, 50, 100, 230).unwrap();
+        // the window ends, re-anchoring the reference at the last price
+        accumulator.record(1_040, 111, 100, 210).unwrap();
+        assert_eq!(accumulator.reference_price, 2_300_000_000);
+        assert_eq!(accumulator.reference_slot, 111);
+
+        let mut packed = [0u8; PriceAccumulator::LEN];
+        PriceAccumulator::pack_into_slice(&accumulator, &mut packed);
+        assert_eq!(PriceAccumulator::unpack(&packed).unwrap(), accumulator);
+    }
+
+    #[test]
+    pub fn test_order_page() {
+        let mut data = vec![0u8; OrderPage::LEN];
+        assert!(OrderPage::header(&data[1..]).is_err());
+        let header = OrderPageHeader {
+            version: 1,
+            bump_seed: 253,
+            page_index: 2,
+            source_mint: TEST_TOKEN_MINT,
+            destination_mint: Pubkey::new_from_array([6u8; 32]),
+            len: 0,
+        };
+        header.pack_into_slice(&mut data);
+        assert_eq!(OrderPage::header(&data).unwrap(), header);
+
+        let entries: Vec<OrderPageEntry> = (0..ORDER_PAGE_CAPACITY)
+            .map(|i| OrderPageEntry {
+                order: Pubkey::new_from_array([i as u8; 32]),
+                kind: OrderKind::Limit,
+                expires_at_slot: i as u64,
+            })
+            .collect();
+        for entry in entries.iter() {
+            OrderPage::push(&mut data, entry).unwrap();
+        }
+        assert_eq!(
+            OrderPage::push(&mut data, &entries[0]),
+            Err(OneSolError::OrderPageFull.into())
+        );
+        assert_eq!(OrderPage::entry(&data, 5).unwrap(), entries[5]);
+
+        OrderPage::remove(&mut data, &entries[5].order).unwrap();
+        let header = OrderPage::header(&data).unwrap();
+        assert_eq!(header.len as usize, ORDER_PAGE_CAPACITY - 1);
+        assert_eq!(
+            OrderPage::entry(&data, 5).unwrap(),
+            entries[ORDER_PAGE_CAPACITY - 1]
+        );
+        assert!(OrderPage::entry(&data, ORDER_PAGE_CAPACITY - 1).is_err());
+        assert!(OrderPage::remove(&mut data, &entries[5].order).is_err());
+    }
+
+    #[test]
+    pub fn test_log_level() {
+        let logs = !cfg!(feature = "no-logs");
+        assert_eq!(LogLevel::Full.logs_progress(), logs);
+        assert_eq!(LogLevel::Full.logs_errors(), logs);
+        assert!(!LogLevel::Errors.logs_progress());
+        assert_eq!(LogLevel::Errors.logs_errors(), logs);
+        assert!(!LogLevel::Off.logs_errors());
+        for log_level in [LogLevel::Off, LogLevel::Errors, LogLevel::Full].iter() {
+            assert_eq!(LogLevel::try_from(*log_level as u8), Ok(*log_level));
+        }
+        assert_eq!(LogLevel::try_from(3), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    pub fn test_stake_pool() {
+        let mut pool = StakePool {
+            version: 1,
+            bump_seed: 252,
+            nonce: 255,
+            mint: Pubkey::new_from_array([1u8; 32]),
+            vault: Pubkey::new_from_array([2u8; 32]),
+            rewards_vault: Pubkey::new_from_array([3u8; 32]),
+            total_staked: 400,
+            reward_per_share: 0,
+            tiers: [
+                StakeTier {
+                    min_stake: 100,
+                    discount_bps: 1_000,
+                },
+                StakeTier {
+                    min_stake: 1_000,
+                    discount_bps: 2_500,
+                },
+                StakeTier::default(),
+            ],
+        };
+        let mut packed = [0u8; StakePool::LEN];
+        StakePool::pack_into_slice(&pool, &mut packed);
+        assert_eq!(StakePool::unpack(&packed).unwrap(), pool);
+
+        assert_eq!(pool.discount_bps(99), 0);
+        assert_eq!(pool.discount_bps(100), 1_000);
+        assert_eq!(pool.discount_bps(5_000), 2_500);
+        assert_eq!(pool.discounted_fee(45, 5_000), 34);
+        assert_eq!(pool.discounted_fee(45, 0), 45);
+
+        let mut stake = StakeAccount {
+            version: 1,
+            bump_seed: 251,
+            owner: Pubkey::new_from_array([4u8; 32]),
+            amount: 100,
+            reward_debt: 0,
+        };
+        let mut packed = [0u8; StakeAccount::LEN];
+        StakeAccount::pack_into_slice(&stake, &mut packed);
+        assert_eq!(StakeAccount::unpack(&packed).unwrap(), stake);
+
+        // a quarter of the pool earns a quarter of the rewards
+        pool.fund(1_000).unwrap();
+        assert_eq!(stake.pending_rewards(&pool), Some(250));
+        stake.reward_debt = pool.accrued_rewards(stake.amount).unwrap();
+        assert_eq!(stake.pending_rewards(&pool), Some(0));
+        pool.total_staked = 0;
+        assert!(pool.fund(1_000).is_none());
+    }
+
+    #[test]
+    pub fn test_liquidity_mining() {
+        let rewards = PairRewards {
+            version: 1,
+            bump_seed: 250,
+            source_mint: Pubkey::new_from_array([1u8; 32]),
+            destination_mint: Pubkey::new_from_array([2u8; 32]),
+            points_per_unit: 5_000,
+            total_points: 12,
+        };
+        let mut packed = [0u8; PairRewards::LEN];
+        PairRewards::pack_into_slice(&rewards, &mut packed);
+        assert_eq!(PairRewards::unpack(&packed).unwrap(), rewards);
+        assert_eq!(rewards.points(1_000_000), 5_000);
+        assert_eq!(rewards.points(199), 0);
+
+        let points = RewardPoints {
+            version: 1,
+            bump_seed: 249,
+            owner: Pubkey::new_from_array([3u8; 32]),
+            points: 40,
+            claimed: 2,
+        };
+        let mut packed = [0u8; RewardPoints::LEN];
+        RewardPoints::pack_into_slice(&points, &mut packed);
+        assert_eq!(RewardPoints::unpack(&packed).unwrap(), points);
+    }
+
+    #[test]
+    pub fn test_pair_gate() {
+        let gate = PairGate {
+            version: 1,
+            bump_seed: 248,
+            source_mint: Pubkey::new_from_array([1u8; 32]),
+            destination_mint: Pubkey::new_from_array([2u8; 32]),
+            credential_mint: Pubkey::new_from_array([3u8; 32]),
+            min_balance: 1,
+        };
+        let mut packed = [0u8; PairGate::LEN];
+        PairGate::pack_into_slice(&gate, &mut packed);
+        assert_eq!(PairGate::unpack(&packed).unwrap(), gate);
+        assert!(gate.is_flagged());
+        let open = PairGate {
+            credential_mint: Pubkey::default(),
+            ..gate
+        };
+        assert!(!open.is_flagged());
+    }
+
+    #[test]
+    pub fn test_inventory_pair() {
+        let pair = InventoryPair {
+            version: 1,
+            bump_seed: 247,
+            source_mint: Pubkey::new_from_array([1u8; 32]),
+            destination_mint: Pubkey::new_from_array([2u8; 32]),
+            oracle: Pubkey::new_from_array([3u8; 32]),
+            spread_bps: 25,
+        };
+        let mut packed = [0u8; InventoryPair::LEN];
+        InventoryPair::pack_into_slice(&pair, &mut packed);
+        assert_eq!(InventoryPair::unpack(&packed).unwrap(), pair);
+    }
+
+    /// Bytes of a `len` long state, each differing from its neighbours, of version 1.
+    fn sample(len: usize) -> Vec<u8> {
+        let mut data: Vec<u8> = (0..len)
+            .map(|i| (i as u8).wrapping_mul(37).wrapping_add(11))
+            .collect();
+        data[0] = 1;
+        data
+    }
+
+    /// Checks `data` unpacks into a state packing back to `data`, that the state is
+    /// rejected once truncated or extended, and that flipping bits of any byte either
+    /// gets the state rejected or unpacks into another state packing back to the flipped
+    /// bytes, so that no byte is ignored or decoded lossily.
+    fn assert_pack_roundtrip<T: Pack + PartialEq + std::fmt::Debug>(
+        data: &[u8],
+        unpack: fn(&[u8]) -> Result<T, ProgramError>,
+    ) {
+        let state = unpack(data).unwrap();
+        let mut packed = vec![0u8; T::LEN];
+        state.pack_into_slice(&mut packed);
+        assert_eq!(packed, data);
+
+        assert_eq!(
+            unpack(&data[..T::LEN - 1]).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+        let mut extended = data.to_vec();
+        extended.push(0);
+        assert_eq!(
+            unpack(&extended).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+
+        for i in 0..T::LEN {
+            for mask in [0x01u8, 0x80, 0xff].iter() {
+                let mut corrupted = data.to_vec();
+                corrupted[i] ^= mask;
+                if let Ok(unpacked) = unpack(&corrupted) {
+                    assert_ne!(unpacked, state, "byte {} ignored", i);
+                    unpacked.pack_into_slice(&mut packed);
+                    assert_eq!(packed, corrupted, "byte {} decoded lossily", i);
+                }
+            }
+        }
+    }
+
+    /// Checks a state of any version but 1 is rejected.
+    fn assert_rejects_version<T: Pack + IsInitialized + std::fmt::Debug>(data: &[u8]) {
+        for version in [0u8, 2, 255].iter() {
+            let mut data = data.to_vec();
+            data[0] = *version;
+            assert_eq!(
+                T::unpack(&data).unwrap_err(),
+                ProgramError::UninitializedAccount
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_pack_roundtrip_and_corruption() {
+        macro_rules! assert_state {
+            ($state:ty) => {
+                assert_state!($state, sample(<$state>::LEN))
+            };
+            ($state:ty, $data:expr) => {
+                let data = $data;
+                assert_pack_roundtrip(&data, <$state>::unpack);
+                assert_rejects_version::<$state>(&data);
+            };
+        }
+        let mut protocol = sample(OneSolState::LEN);
+        protocol[178] = 1;
+        assert_state!(OneSolState, protocol);
+        let mut order = sample(Order::LEN);
+        order[2] = OrderKind::PoolTakeProfit as u8;
+        assert_state!(Order, order);
+        assert_state!(OrderPageHeader);
+        assert_state!(RecurringPayment);
+        assert_state!(SwapReceipt);
+        assert_state!(SwapCommitment);
+        let mut config = sample(GlobalConfig::LEN);
+        config[44] = LogLevel::Errors as u8;
+        assert_state!(GlobalConfig, config);
+        assert_state!(PriceAccumulator);
+        assert_state!(MintStats);
+        assert_state!(StakePool);
+        assert_state!(StakeAccount);
+        assert_state!(PairRewards);
+        assert_state!(RewardPoints);
+        assert_state!(PairGate);
+        assert_state!(InventoryPair);
+
+        // states without a version
+        let mut entry = sample(OrderPageEntry::LEN);
+        entry[32] = OrderKind::Twap as u8;
+        assert_pack_roundtrip(&entry, OrderPageEntry::unpack_unchecked);
+        assert_pack_roundtrip(&sample(SwapResult::LEN), SwapResult::unpack_unchecked);
+        let mut quote = sample(QuoteResult::LEN);
+        quote[8] = MAX_LEGS as u8;
+        assert_pack_roundtrip(&quote, QuoteResult::unpack_unchecked);
+    }
+
+    #[test]
+    pub fn test_onesol_state_view_corruption() {
+        // the view reads what unpacking reads, and rejects what unpacking rejects
+        let mut data = sample(OneSolState::LEN);
+        data[178] = 1;
+        for i in 0..OneSolState::LEN {
+            let mut corrupted = data.clone();
+            corrupted[i] ^= 0xff;
+            match OneSolState::unpack(&corrupted) {
+                Ok(state) => {
+                    let view = OneSolState::view(&corrupted).unwrap();
+                    assert_eq!(view.nonce(), state.nonce);
+                    assert_eq!(view.token_program_id(), state.token_program_id);
+                    assert_eq!(view.token(), state.token);
+                    assert_eq!(view.token_mint(), state.token_mint);
+                    assert_eq!(view.admin(), state.admin);
+                    assert_eq!(view.fee_numerator(), state.fee_numerator);
+                    assert_eq!(view.fee_denominator(), state.fee_denominator);
+                    assert_eq!(view.treasury(), state.treasury);
+                    assert_eq!(view.is_paused(), state.is_paused);
+                    assert_eq!(view.max_referral_fee_bps(), state.max_referral_fee_bps);
+                }
+                Err(error) => assert_eq!(OneSolState::view(&corrupted).unwrap_err(), error),
+            }
+        }
+        assert_eq!(
+            OneSolState::view(&data[..OneSolState::LEN - 1]).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
     }
 }
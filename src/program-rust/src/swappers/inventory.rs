@@ -0,0 +1,290 @@
+//! Protocol-owned inventory
+use crate::{
+    curve::Curve,
+    error::{AdapterError, ErrorCode, OneSolError},
+    instruction::DexConfig,
+    oracle::OraclePrice,
+    processor::Processor,
+    state::InventoryPair,
+    swappers::{DexAdapter, SwapContext},
+    util::{unpack_mint, unpack_token_account},
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    program::invoke_signed,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use std::ops::RangeInclusive;
+
+/// `dex_type` of inventory legs.
+pub const DEX_TYPE: u8 = 1;
+
+/// Errors of inventory legs, in the adapter range of [DEX_TYPE].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InventoryError {
+    /// The inventory pair has no oracle, disabling fills.
+    PairDisabled = 0,
+    /// An inventory vault is not the vault of the pair mint owned by the inventory
+    /// authority.
+    InvalidVault = 1,
+}
+
+impl From<InventoryError> for ProgramError {
+    fn from(e: InventoryError) -> Self {
+        AdapterError {
+            dex_type: DEX_TYPE,
+            code: e as u8,
+        }
+        .into()
+    }
+}
+
+/// The [InventoryError] codes.
+pub const ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode {
+        code: 0x1040,
+        name: "PairDisabled",
+        description: "Inventory pair disabled",
+    },
+    ErrorCode {
+        code: 0x1041,
+        name: "InvalidVault",
+        description: "Invalid inventory vault",
+    },
+];
+
+/// Curve of an inventory pair: the oracle price less the spread, up to the inventory
+/// the DESTINATION vault holds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InventoryCurve {
+    /// Oracle price of SOURCE in DESTINATION units
+    pub price: OraclePrice,
+    /// Spread, in bps, already widened by the oracle confidence
+    pub spread_bps: u16,
+    /// SOURCE mint decimals
+    pub source_decimals: u8,
+    /// DESTINATION mint decimals
+    pub destination_decimals: u8,
+    /// DESTINATION amount held by the inventory
+    pub inventory: u64,
+}
+
+impl InventoryCurve {
+    /// Returns the DESTINATION amount `amount_in` is filled with, whether or not the
+    /// inventory holds it.
+    pub fn amount_out(&self, amount_in: u64) -> Result<u64, OneSolError> {
+        self.price.minimum_amount_out(
+            amount_in,
+            self.source_decimals,
+            self.destination_decimals,
+            self.spread_bps,
+        )
+    }
+}
+
+impl Curve for InventoryCurve {
+    fn simulate_swap(&self, amount_in: u64) -> Option<u64> {
+        self.amount_out(amount_in)
+            .ok()
+            .filter(|amount_out| *amount_out <= self.inventory)
+    }
+
+    fn simulate_compute_units(&self) -> u64 {
+        500
+    }
+}
+
+/// Leg filled out of the inventory, its accounts as in [process_inventory_invoke_swap].
+#[derive(Clone, Copy, Debug)]
+pub struct InventoryLeg<'a, 'b> {
+    accounts: &'a [AccountInfo<'b>],
+}
+
+impl<'a, 'b> DexAdapter<'a, 'b> for InventoryLeg<'a, 'b> {
+    const ACCOUNT_COUNT: RangeInclusive<usize> = 8..=8;
+    const PROGRAM_IDS: &'static [Pubkey] = &[];
+
+    fn from_accounts(
+        _dex_config: &DexConfig,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        Ok(InventoryLeg { accounts })
+    }
+
+    fn venue(&self) -> (&'a AccountInfo<'b>, Option<&'a AccountInfo<'b>>) {
+        (&self.accounts[0], None)
+    }
+
+    fn invoke_swap_signed(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signers_seeds: &[&[&[u8]]],
+    ) -> Result<(), ProgramError> {
+        process_inventory_invoke_swap(
+            context,
+            self.accounts,
+            amount_in,
+            minimum_amount_out,
+            signers_seeds,
+        )
+    }
+}
+
+/// Fills `amount_in` out of the protocol-owned inventory of the pair, at the oracle
+/// price less the pair spread, the spread widened by the oracle confidence as the bound
+/// of oracle-bounded swaps is.
+///
+/// accounts
+///   0. `[]` inventory pair
+///   1. `[]` inventory authority
+///   2. `[writable]` inventory SOURCE vault, owned by the inventory authority
+///   3. `[writable]` inventory DESTINATION vault, owned by the inventory authority
+///   4. `[]` oracle of the inventory pair
+///   5. `[]` SOURCE mint
+///   6. `[]` DESTINATION mint
+///   7. `[]` global config
+pub fn process_inventory_invoke_swap<'a>(
+    context: &SwapContext<'_, 'a>,
+    accounts: &[AccountInfo<'a>],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    signers_seeds: &[&[&[u8]]],
+) -> Result<(), ProgramError> {
+    let account_iters = &mut accounts.iter();
+    let pair_info = next_account_info(account_iters)?;
+    let authority_info = next_account_info(account_iters)?;
+    let source_vault_info = next_account_info(account_iters)?;
+    let destination_vault_info = next_account_info(account_iters)?;
+    let oracle_info = next_account_info(account_iters)?;
+    let source_mint_info = next_account_info(account_iters)?;
+    let destination_mint_info = next_account_info(account_iters)?;
+    let config_info = next_account_info(account_iters)?;
+
+    let program_id = context.program_id;
+    let pair = InventoryPair::unpack(&pair_info.data.borrow())?;
+    if pair.source_mint != *source_mint_info.key
+        || pair.destination_mint != *destination_mint_info.key
+    {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    if pair.oracle == Pubkey::default() {
+        return Err(InventoryError::PairDisabled.into());
+    }
+    if pair.oracle != *oracle_info.key {
+        return Err(OneSolError::InvalidOracle.into());
+    }
+    let (authority, bump_seed) = Processor::find_inventory_authority(program_id);
+    if *authority_info.key != authority {
+        return Err(OneSolError::InvalidProgramAddress.into());
+    }
+    // the signature of the authority must only ever reach the token program
+    let token_program_id = context.token_program.key;
+    if *token_program_id != spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let source_vault = unpack_token_account(source_vault_info, token_program_id)?;
+    let destination_vault = unpack_token_account(destination_vault_info, token_program_id)?;
+    if source_vault.owner != authority
+        || destination_vault.owner != authority
+        || source_vault.mint != pair.source_mint
+        || destination_vault.mint != pair.destination_mint
+    {
+        return Err(InventoryError::InvalidVault.into());
+    }
+
+    let config = Processor::load_config(program_id, config_info)?;
+    let price = Processor::load_fresh_oracle_price(&config, oracle_info)?;
+    let curve = InventoryCurve {
+        price,
+        spread_bps: price.widened_deviation_bps(pair.spread_bps, config.oracle_conf_multiplier),
+        source_decimals: unpack_mint(source_mint_info, token_program_id)?.decimals,
+        destination_decimals: unpack_mint(destination_mint_info, token_program_id)?.decimals,
+        inventory: destination_vault.amount,
+    };
+    let amount_out = curve.amount_out(amount_in)?;
+    if amount_out < minimum_amount_out {
+        return Err(OneSolError::ExceededSlippage.into());
+    }
+    if amount_out > curve.inventory {
+        return Err(OneSolError::InsufficientInventory.into());
+    }
+
+    let ix = spl_token::instruction::transfer(
+        token_program_id,
+        context.source.key,
+        source_vault_info.key,
+        context.user_transfer_authority.key,
+        &[],
+        amount_in,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            context.source.clone(),
+            source_vault_info.clone(),
+            context.user_transfer_authority.clone(),
+            context.token_program.clone(),
+        ],
+        signers_seeds,
+    )?;
+    let ix = spl_token::instruction::transfer(
+        token_program_id,
+        destination_vault_info.key,
+        context.destination.key,
+        authority_info.key,
+        &[],
+        amount_out,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            destination_vault_info.clone(),
+            context.destination.clone(),
+            authority_info.clone(),
+            context.token_program.clone(),
+        ],
+        &[&[b"inventory", &[bump_seed]]],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inventory_error_codes() {
+        for (error, entry) in [InventoryError::PairDisabled, InventoryError::InvalidVault]
+            .iter()
+            .zip(ERROR_CODES.iter())
+        {
+            assert_eq!(ProgramError::from(*error), ProgramError::Custom(entry.code));
+            assert_eq!(format!("{:?}", error), entry.name);
+            assert!(AdapterError::code_range(DEX_TYPE).contains(&entry.code));
+        }
+    }
+
+    #[test]
+    fn test_inventory_curve() {
+        // 1 SOL at 42 USDC, less 50 bps
+        let curve = InventoryCurve {
+            price: OraclePrice {
+                price: 4_200_000_000,
+                conf: 0,
+                expo: -8,
+                publish_slot: 100,
+            },
+            spread_bps: 50,
+            source_decimals: 9,
+            destination_decimals: 6,
+            inventory: 50_000_000,
+        };
+        assert_eq!(curve.simulate_swap(1_000_000_000), Some(41_790_000));
+        // more than the inventory holds
+        assert_eq!(curve.amount_out(2_000_000_000), Ok(83_580_000));
+        assert_eq!(curve.simulate_swap(2_000_000_000), None);
+    }
+}
@@ -0,0 +1,75 @@
+//! Mock swapper of scripted outcomes, unit testing the processor without dex programs.
+
+use crate::{
+    error::OneSolError, math::mul_div_floor, swappers::Swapper, util::token_account_amount,
+};
+use solana_program::{account_info::AccountInfo, program_error::ProgramError};
+use std::cell::RefCell;
+
+/// Outcome of the swaps of a [MockSwapper].
+#[derive(Clone, Debug, PartialEq)]
+pub enum MockOutcome {
+    /// Swaps at `numerator / denominator` DESTINATION tokens per SOURCE token, rounding
+    /// down
+    Rate { numerator: u64, denominator: u64 },
+    /// Fails with the error
+    Fail(ProgramError),
+}
+
+/// Swapper crediting the token account it swaps into as scripted, without moving the
+/// SOURCE tokens, and recording the swaps it is asked for.
+pub struct MockSwapper<'a, 'b> {
+    destination: &'a AccountInfo<'b>,
+    outcome: MockOutcome,
+    /// `(amount_in, minimum_amount_out)` of each swap invoked
+    pub calls: RefCell<Vec<(u64, u64)>>,
+}
+
+impl<'a, 'b> MockSwapper<'a, 'b> {
+    pub fn new(destination: &'a AccountInfo<'b>, outcome: MockOutcome) -> Self {
+        Self {
+            destination,
+            outcome,
+            calls: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl<'a, 'b> Swapper for MockSwapper<'a, 'b> {
+    fn invoke_swap_signed(
+        &self,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        _signers_seeds: &[&[&[u8]]],
+    ) -> Result<(), ProgramError> {
+        self.calls
+            .borrow_mut()
+            .push((amount_in, minimum_amount_out));
+        if let MockOutcome::Fail(error) = &self.outcome {
+            return Err(error.clone());
+        }
+        let amount_out = self
+            .quote(amount_in)
+            .ok_or(OneSolError::ConversionFailure)?;
+        if amount_out < minimum_amount_out {
+            return Err(OneSolError::ExceededSlippage.into());
+        }
+        let balance = token_account_amount(self.destination)?
+            .checked_add(amount_out)
+            .ok_or(OneSolError::ConversionFailure)?;
+        // the amount follows the mint and owner
+        self.destination.data.borrow_mut()[64..72].copy_from_slice(&balance.to_le_bytes());
+        Ok(())
+    }
+
+    /// Output of a swap of `amount_in`, none if the swap fails.
+    fn quote(&self, amount_in: u64) -> Option<u64> {
+        match self.outcome {
+            MockOutcome::Rate {
+                numerator,
+                denominator,
+            } => mul_div_floor(amount_in, numerator, denominator).ok(),
+            MockOutcome::Fail(_) => None,
+        }
+    }
+}
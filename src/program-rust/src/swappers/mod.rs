@@ -1,6 +1,295 @@
 //! mod spl token
 
+#[cfg(feature = "inventory")]
+pub mod inventory;
+#[cfg(test)]
+pub mod mock;
+#[cfg(feature = "raydium")]
+pub mod raydium;
+#[cfg(feature = "serum-dex")]
+pub mod serum_dex;
+#[cfg(feature = "spl-token-swap")]
 pub mod spl_token_swap;
+#[cfg(feature = "stable-swap")]
+pub mod stable_swap;
 pub mod token_swap;
+#[cfg(feature = "whirlpool")]
+pub mod whirlpool;
 
-pub use token_swap::Swapper;
+pub use token_swap::{SwapContext, Swapper};
+
+use crate::{
+    curve::PoolCurve,
+    error::{ErrorCode, OneSolError},
+    instruction::DexConfig,
+};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use std::{convert::Infallible, marker::PhantomData, ops::RangeInclusive};
+
+/// `dex_type`s of the adapters, built in or not.
+pub const KNOWN_DEX_TYPES: RangeInclusive<u8> = 0..=5;
+
+/// Returns the error codes the adapter of `dex_type` defines in its
+/// [AdapterError](crate::error::AdapterError) range.
+pub fn adapter_error_codes(dex_type: u8) -> &'static [ErrorCode] {
+    match dex_type {
+        #[cfg(feature = "inventory")]
+        inventory::DEX_TYPE => inventory::ERROR_CODES,
+        #[cfg(feature = "stable-swap")]
+        stable_swap::DEX_TYPE => stable_swap::ERROR_CODES,
+        _ => &[],
+    }
+}
+
+/// A dex adapter: the accounts of the legs through one kind of venue, and the swap
+/// through them. Adding a venue takes a module implementing it, registered under its
+/// `DEX_TYPE` in [dex_adapters!].
+pub trait DexAdapter<'a, 'b>: Sized {
+    /// Counts of accounts a leg of the dex takes, checked ahead of
+    /// [from_accounts](DexAdapter::from_accounts)
+    const ACCOUNT_COUNT: RangeInclusive<usize>;
+
+    /// Programs the legs of the dex may swap through, empty for venues of the aggregator
+    /// itself
+    const PROGRAM_IDS: &'static [Pubkey];
+
+    /// Takes the accounts of a leg of `dex_config`.
+    fn from_accounts(
+        dex_config: &DexConfig,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError>;
+
+    /// The venue of the leg and the program it swaps through, None for venues of the
+    /// aggregator itself.
+    fn venue(&self) -> (&'a AccountInfo<'b>, Option<&'a AccountInfo<'b>>);
+
+    /// Swaps `amount_in` through the venue, see [Swapper::invoke_swap_signed].
+    fn invoke_swap_signed(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult;
+
+    /// Curve, fees and reserves of the venue, `QuoteUnavailable` if it can't be quoted
+    /// on-chain.
+    fn pool_curve(&self) -> Result<PoolCurve, ProgramError> {
+        Err(OneSolError::QuoteUnavailable.into())
+    }
+}
+
+/// Takes the accounts of a leg through adapter `A`, checking their count.
+fn load_adapter<'a, 'b, A: DexAdapter<'a, 'b>>(
+    dex_config: &DexConfig,
+    accounts: &'a [AccountInfo<'b>],
+) -> Result<A, ProgramError> {
+    if !A::ACCOUNT_COUNT.contains(&accounts.len()) {
+        return Err(OneSolError::AccountCountMismatch.into());
+    }
+    A::from_accounts(dex_config, accounts)
+}
+
+/// Checks the venue of `adapter` is owned by the program the leg swaps through, one of
+/// the [PROGRAM_IDS](DexAdapter::PROGRAM_IDS) of the dex, or by `program_id` for venues
+/// of the aggregator.
+fn check_venue<'a, 'b: 'a, A: DexAdapter<'a, 'b>>(
+    adapter: &A,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let (venue, program) = adapter.venue();
+    let allowed = match program {
+        Some(program) => A::PROGRAM_IDS.contains(program.key) && venue.owner == program.key,
+        None => venue.owner == program_id,
+    };
+    if !allowed {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Registers the adapter of each `dex_type`: `Variant => module::Leg` routes the legs of
+/// `module::DEX_TYPE` through `module::Leg`.
+macro_rules! dex_adapters {
+    ($($(#[$meta:meta])* $variant:ident => $module:ident::$leg:ident,)*) => {
+        /// Adapter of a leg, by `dex_type`.
+        #[derive(Clone, Copy, Debug)]
+        pub enum Adapter<'a, 'b> {
+            $($(#[$meta])* $variant($module::$leg<'a, 'b>),)*
+            /// Never built, keeping the lifetimes of builds without any adapter
+            #[doc(hidden)]
+            #[allow(dead_code)]
+            None(Infallible, PhantomData<&'a AccountInfo<'b>>),
+        }
+
+        impl<'a, 'b> Adapter<'a, 'b> {
+            /// Takes the accounts of a leg of `dex_config` through the adapter of its
+            /// `dex_type`, `DexProgramNotAllowed` if none is built in.
+            pub fn from_accounts(
+                dex_config: &DexConfig,
+                accounts: &'a [AccountInfo<'b>],
+            ) -> Result<Self, ProgramError> {
+                match dex_config.dex_type {
+                    $(
+                        $(#[$meta])*
+                        $module::DEX_TYPE => {
+                            load_adapter(dex_config, accounts).map(Adapter::$variant)
+                        }
+                    )*
+                    _ => Err(OneSolError::DexProgramNotAllowed.into()),
+                }
+            }
+
+            fn check_venue(&self, program_id: &Pubkey) -> ProgramResult {
+                match *self {
+                    $($(#[$meta])* Adapter::$variant(adapter) => check_venue(&adapter, program_id),)*
+                    Adapter::None(never, _) => match never {},
+                }
+            }
+
+            fn invoke_swap_signed(
+                &self,
+                context: &SwapContext<'_, 'b>,
+                amount_in: u64,
+                minimum_amount_out: u64,
+                signers_seeds: &[&[&[u8]]],
+            ) -> ProgramResult {
+                match *self {
+                    $(
+                        $(#[$meta])*
+                        Adapter::$variant(adapter) => adapter.invoke_swap_signed(
+                            context,
+                            amount_in,
+                            minimum_amount_out,
+                            signers_seeds,
+                        ),
+                    )*
+                    Adapter::None(never, _) => match never {},
+                }
+            }
+
+            /// See [DexAdapter::pool_curve].
+            pub fn pool_curve(&self) -> Result<PoolCurve, ProgramError> {
+                match *self {
+                    $($(#[$meta])* Adapter::$variant(adapter) => adapter.pool_curve(),)*
+                    Adapter::None(never, _) => match never {},
+                }
+            }
+        }
+    };
+}
+
+dex_adapters! {
+    #[cfg(feature = "spl-token-swap")]
+    SplTokenSwap => spl_token_swap::SplTokenSwapLeg,
+    #[cfg(feature = "inventory")]
+    Inventory => inventory::InventoryLeg,
+    #[cfg(feature = "serum-dex")]
+    SerumDex => serum_dex::SerumDexLeg,
+    #[cfg(feature = "raydium")]
+    Raydium => raydium::RaydiumLeg,
+    #[cfg(feature = "whirlpool")]
+    Whirlpool => whirlpool::WhirlpoolLeg,
+    #[cfg(feature = "stable-swap")]
+    StableSwap => stable_swap::StableSwapLeg,
+}
+
+/// Swapper of a leg through the adapter of its `dex_type`.
+#[derive(Clone, Copy, Debug)]
+pub struct DexSwapper<'a, 'b> {
+    adapter: Adapter<'a, 'b>,
+    context: &'a SwapContext<'a, 'b>,
+}
+
+impl<'a, 'b> DexSwapper<'a, 'b> {
+    /// Takes the accounts of a leg of `dex_config`, see [Adapter::from_accounts].
+    pub fn from_accounts(
+        context: &'a SwapContext<'a, 'b>,
+        dex_config: &DexConfig,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        Ok(DexSwapper {
+            adapter: Adapter::from_accounts(dex_config, accounts)?,
+            context,
+        })
+    }
+}
+
+impl<'a, 'b> Swapper for DexSwapper<'a, 'b> {
+    fn invoke_swap_signed(
+        &self,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        self.adapter.check_venue(self.context.program_id)?;
+        self.adapter
+            .invoke_swap_signed(self.context, amount_in, minimum_amount_out, signers_seeds)
+    }
+
+    fn pool_curve(&self) -> Option<PoolCurve> {
+        self.adapter.pool_curve().ok()
+    }
+}
+
+#[cfg(all(test, feature = "spl-token-swap", feature = "whirlpool"))]
+mod tests {
+    use super::*;
+
+    /// Checks the venue of a leg of `dex_type` through `count` accounts, the venue owned by
+    /// `owner` and the last account being `program`.
+    fn check(dex_type: u8, count: usize, owner: Pubkey, program: Pubkey) -> ProgramResult {
+        let mut keys: Vec<Pubkey> = (0..count).map(|_| Pubkey::new_unique()).collect();
+        keys[count - 1] = program;
+        let mut lamports = vec![0u64; count];
+        let mut data = vec![vec![]; count];
+        let accounts: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .enumerate()
+            .map(|(i, ((key, lamports), data))| {
+                let owner = if i == 0 { &owner } else { key };
+                AccountInfo::new(key, false, true, lamports, data, owner, false, 0)
+            })
+            .collect();
+        let dex_config = DexConfig::new_dex_config(dex_type, count, 10_000);
+        Adapter::from_accounts(&dex_config, &accounts)?.check_venue(&Pubkey::new_unique())
+    }
+
+    #[test]
+    fn test_adapter_checks() {
+        let program = spl_token_swap::PROGRAM_IDS[0];
+        assert_eq!(check(spl_token_swap::DEX_TYPE, 7, program, program), Ok(()));
+        assert_eq!(
+            check(spl_token_swap::DEX_TYPE, 7, Pubkey::new_unique(), program),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        assert_eq!(
+            check(spl_token_swap::DEX_TYPE, 6, program, program),
+            Err(OneSolError::AccountCountMismatch.into())
+        );
+        assert_eq!(
+            check(u8::MAX, 7, program, program),
+            Err(OneSolError::DexProgramNotAllowed.into())
+        );
+
+        // legs only swap through the programs of their dex
+        let unknown = Pubkey::new_unique();
+        assert_eq!(
+            check(spl_token_swap::DEX_TYPE, 7, unknown, unknown),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        assert_eq!(
+            check(whirlpool::DEX_TYPE, 8, whirlpool::ID, whirlpool::ID),
+            Ok(())
+        );
+        assert_eq!(
+            check(whirlpool::DEX_TYPE, 8, program, program),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+}
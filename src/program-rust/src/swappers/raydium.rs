@@ -0,0 +1,143 @@
+//! Raydium AMM v4 pools
+use crate::{
+    instruction::DexConfig,
+    swappers::{DexAdapter, SwapContext},
+};
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use std::ops::RangeInclusive;
+
+/// `dex_type` of Raydium AMM legs.
+pub const DEX_TYPE: u8 = 3;
+
+/// The Raydium AMM v4 program.
+pub const PROGRAM_IDS: &[Pubkey] = &[solana_program::pubkey!(
+    "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8"
+)];
+
+/// Raydium `AmmInstruction::SwapBaseIn` tag.
+const SWAP_BASE_IN_TAG: u8 = 9;
+
+/// Encodes the data of a Raydium `SwapBaseIn` of `amount_in`.
+pub fn swap_base_in_data(amount_in: u64, minimum_amount_out: u64) -> [u8; 17] {
+    let mut data = [0u8; 17];
+    data[0] = SWAP_BASE_IN_TAG;
+    data[1..9].copy_from_slice(&amount_in.to_le_bytes());
+    data[9..].copy_from_slice(&minimum_amount_out.to_le_bytes());
+    data
+}
+
+/// Leg through a Raydium AMM, its accounts as in [process_raydium_invoke_swap].
+#[derive(Clone, Copy, Debug)]
+pub struct RaydiumLeg<'a, 'b> {
+    accounts: &'a [AccountInfo<'b>],
+}
+
+impl<'a, 'b> DexAdapter<'a, 'b> for RaydiumLeg<'a, 'b> {
+    const ACCOUNT_COUNT: RangeInclusive<usize> = 15..=15;
+    const PROGRAM_IDS: &'static [Pubkey] = PROGRAM_IDS;
+
+    fn from_accounts(
+        _dex_config: &DexConfig,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        Ok(RaydiumLeg { accounts })
+    }
+
+    fn venue(&self) -> (&'a AccountInfo<'b>, Option<&'a AccountInfo<'b>>) {
+        (&self.accounts[0], Some(&self.accounts[14]))
+    }
+
+    fn invoke_swap_signed(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signers_seeds: &[&[&[u8]]],
+    ) -> Result<(), ProgramError> {
+        process_raydium_invoke_swap(
+            context,
+            self.accounts,
+            amount_in,
+            minimum_amount_out,
+            signers_seeds,
+        )
+    }
+}
+
+/// Swaps `amount_in` through a Raydium AMM, which checks `minimum_amount_out` itself.
+///
+/// accounts
+///   0. `[writable]` amm
+///   1. `[]` amm authority
+///   2. `[writable]` amm open orders
+///   3. `[writable]` amm target orders
+///   4. `[writable]` pool coin token account
+///   5. `[writable]` pool pc token account
+///   6. `[]` Serum DEX program id
+///   7. `[writable]` Serum market
+///   8. `[writable]` Serum bids
+///   9. `[writable]` Serum asks
+///   10. `[writable]` Serum event queue
+///   11. `[writable]` Serum coin vault
+///   12. `[writable]` Serum pc vault
+///   13. `[]` Serum vault signer
+///   14. `[]` Raydium AMM program id
+pub fn process_raydium_invoke_swap<'a>(
+    context: &SwapContext<'_, 'a>,
+    accounts: &[AccountInfo<'a>],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    signers_seeds: &[&[&[u8]]],
+) -> Result<(), ProgramError> {
+    let (program_info, amm_accounts) = accounts
+        .split_last()
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+    // the token program first, then the amm and Serum accounts, then the user's
+    let mut metas = Vec::with_capacity(18);
+    metas.push(AccountMeta::new_readonly(*context.token_program.key, false));
+    metas.extend(amm_accounts.iter().map(|account| AccountMeta {
+        pubkey: *account.key,
+        is_signer: false,
+        is_writable: account.is_writable,
+    }));
+    metas.push(AccountMeta::new(*context.source.key, false));
+    metas.push(AccountMeta::new(*context.destination.key, false));
+    metas.push(AccountMeta::new_readonly(
+        *context.user_transfer_authority.key,
+        true,
+    ));
+    let ix = Instruction {
+        program_id: *program_info.key,
+        accounts: metas,
+        data: swap_base_in_data(amount_in, minimum_amount_out).to_vec(),
+    };
+
+    let mut account_infos = Vec::with_capacity(19);
+    account_infos.push(context.token_program.clone());
+    account_infos.extend_from_slice(amm_accounts);
+    account_infos.push(context.source.clone());
+    account_infos.push(context.destination.clone());
+    account_infos.push(context.user_transfer_authority.clone());
+    account_infos.push(program_info.clone());
+    invoke_signed(&ix, &account_infos, signers_seeds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_base_in_data() {
+        let data = swap_base_in_data(1_000, 990);
+        assert_eq!(data[0], 9);
+        assert_eq!(data[1..9], 1_000u64.to_le_bytes());
+        assert_eq!(data[9..], 990u64.to_le_bytes());
+    }
+}
@@ -0,0 +1,512 @@
+//! Serum DEX v3 order books
+use crate::{
+    error::OneSolError,
+    instruction::DexConfig,
+    swappers::{DexAdapter, SwapContext},
+    util::{token_account_amount, unpack_token_account},
+};
+use arrayref::array_ref;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use std::ops::RangeInclusive;
+
+/// `dex_type` of Serum DEX v3 legs.
+pub const DEX_TYPE: u8 = 2;
+
+/// The Serum DEX v3 program and its OpenBook fork.
+pub const PROGRAM_IDS: &[Pubkey] = &[
+    solana_program::pubkey!("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin"),
+    solana_program::pubkey!("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX"),
+];
+
+/// Length of a Serum market account: the `serum` head padding, the `MarketState` and the
+/// tail padding.
+const MARKET_LEN: usize = 388;
+/// Offsets in a Serum market account, past the 5 bytes head padding.
+const COIN_MINT_OFFSET: usize = 53;
+const PC_MINT_OFFSET: usize = 85;
+const COIN_VAULT_OFFSET: usize = 117;
+const PC_VAULT_OFFSET: usize = 165;
+const COIN_LOT_SIZE_OFFSET: usize = 349;
+
+/// Length of a Serum open orders account: the `serum` head padding, the `OpenOrders` and
+/// the tail padding.
+pub const OPEN_ORDERS_LEN: usize = 3228;
+
+/// Serum `MarketInstruction` tags.
+const NEW_ORDER_V3_TAG: u32 = 10;
+const SETTLE_FUNDS_TAG: u32 = 5;
+const CLOSE_OPEN_ORDERS_TAG: u32 = 14;
+const INIT_OPEN_ORDERS_TAG: u32 = 15;
+
+/// Serum `Side`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    /// Buys coin with pc
+    Bid = 0,
+    /// Sells coin for pc
+    Ask = 1,
+}
+
+/// Serum `SelfTradeBehavior::DecrementTake`
+const SELF_TRADE_DECREMENT_TAKE: u32 = 0;
+/// Serum `OrderType::ImmediateOrCancel`
+const ORDER_TYPE_IMMEDIATE_OR_CANCEL: u32 = 1;
+/// Most orders of the book an order matches against
+const MATCH_LIMIT: u16 = u16::MAX;
+
+/// The market fields a leg reads.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Market {
+    /// Mint of the base token
+    pub coin_mint: Pubkey,
+    /// Mint of the quote token
+    pub pc_mint: Pubkey,
+    /// Vault of the base token
+    pub coin_vault: Pubkey,
+    /// Vault of the quote token
+    pub pc_vault: Pubkey,
+    /// Base amount of a lot
+    pub coin_lot_size: u64,
+}
+
+impl Market {
+    /// Reads the market from the data of its account.
+    pub fn unpack(data: &[u8]) -> Result<Market, ProgramError> {
+        if data.len() != MARKET_LEN {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let pubkey_at = |offset| Pubkey::new_from_array(*array_ref![data, offset, 32]);
+        Ok(Market {
+            coin_mint: pubkey_at(COIN_MINT_OFFSET),
+            pc_mint: pubkey_at(PC_MINT_OFFSET),
+            coin_vault: pubkey_at(COIN_VAULT_OFFSET),
+            pc_vault: pubkey_at(PC_VAULT_OFFSET),
+            coin_lot_size: u64::from_le_bytes(*array_ref![data, COIN_LOT_SIZE_OFFSET, 8]),
+        })
+    }
+}
+
+/// Parameters of an immediate-or-cancel order swapping `amount_in` on the `side` of the
+/// book: asks sell whole coin lots at any price, bids spend up to `amount_in` pc.
+///
+/// Returns the limit price, max coin quantity and max pc quantity of the order, failing
+/// if `amount_in` doesn't fill a coin lot.
+pub fn order_limits(
+    side: Side,
+    amount_in: u64,
+    coin_lot_size: u64,
+) -> Result<(u64, u64, u64), ProgramError> {
+    let limits = match side {
+        Side::Bid => (u64::MAX, u64::MAX, amount_in),
+        Side::Ask => (
+            1,
+            amount_in
+                .checked_div(coin_lot_size)
+                .ok_or(OneSolError::InvalidInput)?,
+            u64::MAX,
+        ),
+    };
+    if limits.1 == 0 || limits.2 == 0 {
+        return Err(OneSolError::InvalidInput.into());
+    }
+    Ok(limits)
+}
+
+/// Encodes the data of a Serum `NewOrderV3` immediate-or-cancel order.
+pub fn new_order_data(
+    side: Side,
+    limit_price: u64,
+    max_coin_qty: u64,
+    max_native_pc_qty: u64,
+) -> Vec<u8> {
+    let mut data = Vec::with_capacity(51);
+    // instruction version
+    data.push(0);
+    data.extend_from_slice(&NEW_ORDER_V3_TAG.to_le_bytes());
+    data.extend_from_slice(&(side as u32).to_le_bytes());
+    data.extend_from_slice(&limit_price.to_le_bytes());
+    data.extend_from_slice(&max_coin_qty.to_le_bytes());
+    data.extend_from_slice(&max_native_pc_qty.to_le_bytes());
+    data.extend_from_slice(&SELF_TRADE_DECREMENT_TAKE.to_le_bytes());
+    data.extend_from_slice(&ORDER_TYPE_IMMEDIATE_OR_CANCEL.to_le_bytes());
+    // client order id
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&MATCH_LIMIT.to_le_bytes());
+    data
+}
+
+/// Encodes the data of a Serum instruction of `tag` with no fields.
+fn unit_instruction_data(tag: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity(5);
+    // instruction version
+    data.push(0);
+    data.extend_from_slice(&tag.to_le_bytes());
+    data
+}
+
+/// Encodes the data of a Serum `SettleFunds`.
+pub fn settle_funds_data() -> Vec<u8> {
+    unit_instruction_data(SETTLE_FUNDS_TAG)
+}
+
+/// Encodes the data of a Serum `InitOpenOrders`.
+pub fn init_open_orders_data() -> Vec<u8> {
+    unit_instruction_data(INIT_OPEN_ORDERS_TAG)
+}
+
+/// Encodes the data of a Serum `CloseOpenOrders`.
+pub fn close_open_orders_data() -> Vec<u8> {
+    unit_instruction_data(CLOSE_OPEN_ORDERS_TAG)
+}
+
+/// Accounts of a Serum `SettleFunds`, moving the free funds of open orders into the coin
+/// and pc wallets.
+pub struct SettleFunds<'b, 'a> {
+    /// market
+    pub market: &'b AccountInfo<'a>,
+    /// open orders settled
+    pub open_orders: &'b AccountInfo<'a>,
+    /// owner of the open orders
+    pub owner: &'b AccountInfo<'a>,
+    /// coin vault of the market
+    pub coin_vault: &'b AccountInfo<'a>,
+    /// pc vault of the market
+    pub pc_vault: &'b AccountInfo<'a>,
+    /// token account receiving the free coin
+    pub coin_wallet: &'b AccountInfo<'a>,
+    /// token account receiving the free pc
+    pub pc_wallet: &'b AccountInfo<'a>,
+    /// vault signer of the market
+    pub vault_signer: &'b AccountInfo<'a>,
+    /// Token program
+    pub token_program: &'b AccountInfo<'a>,
+    /// Serum DEX program
+    pub dex_program: &'b AccountInfo<'a>,
+}
+
+impl<'b, 'a> SettleFunds<'b, 'a> {
+    /// Settles the free funds of the open orders, the owner signing with `signers_seeds`
+    /// if a program address.
+    pub fn invoke(&self, signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        let settle_funds = Instruction {
+            program_id: *self.dex_program.key,
+            accounts: vec![
+                AccountMeta::new(*self.market.key, false),
+                AccountMeta::new(*self.open_orders.key, false),
+                AccountMeta::new_readonly(*self.owner.key, true),
+                AccountMeta::new(*self.coin_vault.key, false),
+                AccountMeta::new(*self.pc_vault.key, false),
+                AccountMeta::new(*self.coin_wallet.key, false),
+                AccountMeta::new(*self.pc_wallet.key, false),
+                AccountMeta::new_readonly(*self.vault_signer.key, false),
+                AccountMeta::new_readonly(*self.token_program.key, false),
+            ],
+            data: settle_funds_data(),
+        };
+        invoke_signed(
+            &settle_funds,
+            &[
+                self.market.clone(),
+                self.open_orders.clone(),
+                self.owner.clone(),
+                self.coin_vault.clone(),
+                self.pc_vault.clone(),
+                self.coin_wallet.clone(),
+                self.pc_wallet.clone(),
+                self.vault_signer.clone(),
+                self.token_program.clone(),
+                self.dex_program.clone(),
+            ],
+            signers_seeds,
+        )
+    }
+}
+
+/// Initializes the open orders account `open_orders` of `market`, created owned by the
+/// Serum DEX program, for `owner`, signing with `signers_seeds`.
+pub fn init_open_orders<'a>(
+    open_orders: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    market: &AccountInfo<'a>,
+    rent: &AccountInfo<'a>,
+    dex_program: &AccountInfo<'a>,
+    signers_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let ix = Instruction {
+        program_id: *dex_program.key,
+        accounts: vec![
+            AccountMeta::new(*open_orders.key, false),
+            AccountMeta::new_readonly(*owner.key, true),
+            AccountMeta::new_readonly(*market.key, false),
+            AccountMeta::new_readonly(*rent.key, false),
+        ],
+        data: init_open_orders_data(),
+    };
+    invoke_signed(
+        &ix,
+        &[
+            open_orders.clone(),
+            owner.clone(),
+            market.clone(),
+            rent.clone(),
+            dex_program.clone(),
+        ],
+        signers_seeds,
+    )
+}
+
+/// Closes the open orders account `open_orders` of `market`, which must hold neither
+/// orders nor funds, sending its lamports to `destination`.
+pub fn close_open_orders<'a>(
+    open_orders: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    market: &AccountInfo<'a>,
+    dex_program: &AccountInfo<'a>,
+    signers_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let ix = Instruction {
+        program_id: *dex_program.key,
+        accounts: vec![
+            AccountMeta::new(*open_orders.key, false),
+            AccountMeta::new_readonly(*owner.key, true),
+            AccountMeta::new(*destination.key, false),
+            AccountMeta::new_readonly(*market.key, false),
+        ],
+        data: close_open_orders_data(),
+    };
+    invoke_signed(
+        &ix,
+        &[
+            open_orders.clone(),
+            owner.clone(),
+            destination.clone(),
+            market.clone(),
+            dex_program.clone(),
+        ],
+        signers_seeds,
+    )
+}
+
+/// Leg through a Serum market, its accounts as in [process_serum_dex_invoke_swap].
+#[derive(Clone, Copy, Debug)]
+pub struct SerumDexLeg<'a, 'b> {
+    accounts: &'a [AccountInfo<'b>],
+}
+
+impl<'a, 'b> DexAdapter<'a, 'b> for SerumDexLeg<'a, 'b> {
+    const ACCOUNT_COUNT: RangeInclusive<usize> = 11..=11;
+    const PROGRAM_IDS: &'static [Pubkey] = PROGRAM_IDS;
+
+    fn from_accounts(
+        _dex_config: &DexConfig,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        Ok(SerumDexLeg { accounts })
+    }
+
+    fn venue(&self) -> (&'a AccountInfo<'b>, Option<&'a AccountInfo<'b>>) {
+        (&self.accounts[0], Some(&self.accounts[10]))
+    }
+
+    fn invoke_swap_signed(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signers_seeds: &[&[&[u8]]],
+    ) -> Result<(), ProgramError> {
+        process_serum_dex_invoke_swap(
+            context,
+            self.accounts,
+            amount_in,
+            minimum_amount_out,
+            signers_seeds,
+        )
+    }
+}
+
+/// Swaps `amount_in` through a Serum market: places an immediate-or-cancel order from
+/// SOURCE, then settles the open orders of the user transfer authority into SOURCE and
+/// DESTINATION, refunding any unfilled SOURCE.
+///
+/// accounts
+///   0. `[writable]` market
+///   1. `[writable]` open orders, owned by the user transfer authority, those of
+///      `CreateOpenOrders` on legs the protocol authority signs
+///   2. `[writable]` request queue
+///   3. `[writable]` event queue
+///   4. `[writable]` bids
+///   5. `[writable]` asks
+///   6. `[writable]` coin vault
+///   7. `[writable]` pc vault
+///   8. `[]` vault signer
+///   9. `[]` Rent sysvar
+///   10. `[]` Serum DEX program id
+pub fn process_serum_dex_invoke_swap<'a>(
+    context: &SwapContext<'_, 'a>,
+    accounts: &[AccountInfo<'a>],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    signers_seeds: &[&[&[u8]]],
+) -> Result<(), ProgramError> {
+    let account_iters = &mut accounts.iter();
+    let market_info = next_account_info(account_iters)?;
+    let open_orders_info = next_account_info(account_iters)?;
+    let request_queue_info = next_account_info(account_iters)?;
+    let event_queue_info = next_account_info(account_iters)?;
+    let bids_info = next_account_info(account_iters)?;
+    let asks_info = next_account_info(account_iters)?;
+    let coin_vault_info = next_account_info(account_iters)?;
+    let pc_vault_info = next_account_info(account_iters)?;
+    let vault_signer_info = next_account_info(account_iters)?;
+    let rent_info = next_account_info(account_iters)?;
+    let dex_program_info = next_account_info(account_iters)?;
+
+    let market = Market::unpack(&market_info.data.borrow())?;
+    if market.coin_vault != *coin_vault_info.key || market.pc_vault != *pc_vault_info.key {
+        return Err(OneSolError::IncorrectSwapAccount.into());
+    }
+    let token_program_id = context.token_program.key;
+    let source_mint = unpack_token_account(context.source, token_program_id)?.mint;
+    let destination_mint = unpack_token_account(context.destination, token_program_id)?.mint;
+    // the coin and pc wallets settled into
+    let (side, coin_wallet_info, pc_wallet_info) =
+        if source_mint == market.coin_mint && destination_mint == market.pc_mint {
+            (Side::Ask, context.source, context.destination)
+        } else if source_mint == market.pc_mint && destination_mint == market.coin_mint {
+            (Side::Bid, context.destination, context.source)
+        } else {
+            return Err(OneSolError::IncorrectSwapAccount.into());
+        };
+    let (limit_price, max_coin_qty, max_native_pc_qty) =
+        order_limits(side, amount_in, market.coin_lot_size)?;
+
+    let destination_before = token_account_amount(context.destination)?;
+    let new_order = Instruction {
+        program_id: *dex_program_info.key,
+        accounts: vec![
+            AccountMeta::new(*market_info.key, false),
+            AccountMeta::new(*open_orders_info.key, false),
+            AccountMeta::new(*request_queue_info.key, false),
+            AccountMeta::new(*event_queue_info.key, false),
+            AccountMeta::new(*bids_info.key, false),
+            AccountMeta::new(*asks_info.key, false),
+            AccountMeta::new(*context.source.key, false),
+            AccountMeta::new_readonly(*context.user_transfer_authority.key, true),
+            AccountMeta::new(*coin_vault_info.key, false),
+            AccountMeta::new(*pc_vault_info.key, false),
+            AccountMeta::new_readonly(*token_program_id, false),
+            AccountMeta::new_readonly(*rent_info.key, false),
+        ],
+        data: new_order_data(side, limit_price, max_coin_qty, max_native_pc_qty),
+    };
+    invoke_signed(
+        &new_order,
+        &[
+            market_info.clone(),
+            open_orders_info.clone(),
+            request_queue_info.clone(),
+            event_queue_info.clone(),
+            bids_info.clone(),
+            asks_info.clone(),
+            context.source.clone(),
+            context.user_transfer_authority.clone(),
+            coin_vault_info.clone(),
+            pc_vault_info.clone(),
+            context.token_program.clone(),
+            rent_info.clone(),
+            dex_program_info.clone(),
+        ],
+        signers_seeds,
+    )?;
+
+    SettleFunds {
+        market: market_info,
+        open_orders: open_orders_info,
+        owner: context.user_transfer_authority,
+        coin_vault: coin_vault_info,
+        pc_vault: pc_vault_info,
+        coin_wallet: coin_wallet_info,
+        pc_wallet: pc_wallet_info,
+        vault_signer: vault_signer_info,
+        token_program: context.token_program,
+        dex_program: dex_program_info,
+    }
+    .invoke(signers_seeds)?;
+
+    // an order book fills at the prices it holds: the slippage is checked once settled
+    let amount_out = token_account_amount(context.destination)?
+        .checked_sub(destination_before)
+        .ok_or(OneSolError::InternalError)?;
+    if amount_out < minimum_amount_out {
+        return Err(OneSolError::ExceededSlippage.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_market_unpack() {
+        let mut data = vec![0u8; MARKET_LEN];
+        data[..5].copy_from_slice(b"serum");
+        let coin_mint = Pubkey::new_unique();
+        let pc_vault = Pubkey::new_unique();
+        data[COIN_MINT_OFFSET..COIN_MINT_OFFSET + 32].copy_from_slice(coin_mint.as_ref());
+        data[PC_VAULT_OFFSET..PC_VAULT_OFFSET + 32].copy_from_slice(pc_vault.as_ref());
+        data[COIN_LOT_SIZE_OFFSET..COIN_LOT_SIZE_OFFSET + 8]
+            .copy_from_slice(&100_000u64.to_le_bytes());
+        let market = Market::unpack(&data).unwrap();
+        assert_eq!(market.coin_mint, coin_mint);
+        assert_eq!(market.pc_vault, pc_vault);
+        assert_eq!(market.coin_lot_size, 100_000);
+        assert_eq!(
+            Market::unpack(&data[1..]),
+            Err(OneSolError::InvalidInput.into())
+        );
+    }
+
+    #[test]
+    fn test_order_limits() {
+        assert_eq!(
+            order_limits(Side::Ask, 250_000, 100_000),
+            Ok((1, 2, u64::MAX))
+        );
+        assert_eq!(
+            order_limits(Side::Bid, 42, 100_000),
+            Ok((u64::MAX, u64::MAX, 42))
+        );
+        // less than a lot
+        assert_eq!(
+            order_limits(Side::Ask, 99_999, 100_000),
+            Err(OneSolError::InvalidInput.into())
+        );
+        assert_eq!(
+            order_limits(Side::Ask, 1, 0),
+            Err(OneSolError::InvalidInput.into())
+        );
+    }
+
+    #[test]
+    fn test_new_order_data() {
+        let data = new_order_data(Side::Ask, 1, 2, u64::MAX);
+        assert_eq!(data.len(), 51);
+        assert_eq!(data[1..5], NEW_ORDER_V3_TAG.to_le_bytes());
+        assert_eq!(data[5..9], 1u32.to_le_bytes());
+        assert_eq!(data[17..25], 2u64.to_le_bytes());
+        assert_eq!(data[37..41], ORDER_TYPE_IMMEDIATE_OR_CANCEL.to_le_bytes());
+        assert_eq!(settle_funds_data(), vec![0, 5, 0, 0, 0]);
+        assert_eq!(init_open_orders_data(), vec![0, 15, 0, 0, 0]);
+        assert_eq!(close_open_orders_data(), vec![0, 14, 0, 0, 0]);
+    }
+}
@@ -1,11 +1,52 @@
+use crate::{
+    curve::{CurveFees, PoolCurve, SwapCurve},
+    error::OneSolError,
+    instruction::DexConfig,
+    swappers::{DexAdapter, SwapContext},
+    util::token_account_amount,
+};
+use arrayref::{array_ref, array_refs};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     instruction::{AccountMeta, Instruction},
-    program::invoke,
+    program::invoke_signed,
     program_error::ProgramError,
     pubkey::Pubkey,
 };
-use std::mem::size_of;
+use std::{mem::size_of, ops::RangeInclusive};
+
+/// Offset of the host fee numerator in a spl-token-swap account, following the pool
+/// accounts and the trade, owner trade and owner withdraw fees.
+const HOST_FEE_NUMERATOR_OFFSET: usize = 275;
+
+/// Offset of the fees in a spl-token-swap account, following the pool accounts.
+const FEES_OFFSET: usize = 227;
+
+/// Length of the fees and swap curve of a spl-token-swap account.
+const FEES_AND_CURVE_LEN: usize = 97;
+
+/// spl-token-swap `CurveType`s quoted on-chain.
+const CURVE_TYPE_CONSTANT_PRODUCT: u8 = 0;
+const CURVE_TYPE_STABLE: u8 = 2;
+
+/// `dex_type` of spl-token-swap legs.
+pub const DEX_TYPE: u8 = 0;
+
+/// The spl-token-swap programs, deployed on mainnet-beta and on devnet.
+pub const PROGRAM_IDS: &[Pubkey] = &[
+    solana_program::pubkey!("SwapsVeCiPHMUAtzQWZw7RjsKjgCjhwU55QGu4U1Szw"),
+    solana_program::pubkey!("SwaPpA9LAaLfeLi3a68M4DjnLqgtticKg6CnyNwgAC8"),
+];
+
+/// spl-token-swap `SwapError` codes translated into 1sol errors.
+const SWAP_ERROR_INVALID_PROGRAM_ADDRESS: u32 = 1;
+const SWAP_ERROR_EMPTY_SUPPLY: u32 = 6;
+const SWAP_ERROR_INCORRECT_SWAP_ACCOUNT: u32 = 10;
+const SWAP_ERROR_INCORRECT_POOL_MINT: u32 = 11;
+const SWAP_ERROR_CALCULATION_FAILURE: u32 = 13;
+const SWAP_ERROR_EXCEEDED_SLIPPAGE: u32 = 16;
+const SWAP_ERROR_ZERO_TRADING_TOKENS: u32 = 20;
+const SWAP_ERROR_INCORRECT_TOKEN_PROGRAM_ID: u32 = 24;
 
 /// Swap instruction data
 #[repr(C)]
@@ -55,21 +96,75 @@ impl SwapInstruction {
     }
 }
 
-pub fn process_token_swap_invoke_swap(
-    accounts: &[AccountInfo],
+/// Leg through a spl-token-swap pool.
+///
+/// accounts
+///   0. `[]` token-swap account
+///   1. `[]` token-swap authority
+///   2. `[writable]` token_A Base Account to swap INTO.  Must be the SOURCE token.
+///   3. `[writable]` token_B Base Account to swap FROM.  Must be the DESTINATION token.
+///   4. `[writable]` Pool token mint, to generate trading fees
+///   5. `[writable]` Fee account, to receive trading fees
+///   6. '[]` Token-Swap program id
+///   7 `[optional, writable]` Host fee account to receive additional trading fees
+#[derive(Clone, Copy, Debug)]
+pub struct SplTokenSwapLeg<'a, 'b> {
+    accounts: &'a [AccountInfo<'b>],
+}
+
+impl<'a, 'b> DexAdapter<'a, 'b> for SplTokenSwapLeg<'a, 'b> {
+    const ACCOUNT_COUNT: RangeInclusive<usize> = 7..=8;
+    const PROGRAM_IDS: &'static [Pubkey] = PROGRAM_IDS;
+
+    fn from_accounts(
+        _dex_config: &DexConfig,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        Ok(SplTokenSwapLeg { accounts })
+    }
+
+    fn venue(&self) -> (&'a AccountInfo<'b>, Option<&'a AccountInfo<'b>>) {
+        (&self.accounts[0], Some(&self.accounts[6]))
+    }
+
+    fn invoke_swap_signed(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signers_seeds: &[&[&[u8]]],
+    ) -> Result<(), ProgramError> {
+        process_token_swap_invoke_swap(
+            context,
+            self.accounts,
+            amount_in,
+            minimum_amount_out,
+            signers_seeds,
+        )
+    }
+
+    fn pool_curve(&self) -> Result<PoolCurve, ProgramError> {
+        load_pool_curve(self.accounts)
+    }
+}
+
+pub fn process_token_swap_invoke_swap<'a>(
+    context: &SwapContext<'_, 'a>,
+    accounts: &[AccountInfo<'a>],
     amount_in: u64,
     minimum_amount_out: u64,
+    signers_seeds: &[&[&[u8]]],
 ) -> Result<(), ProgramError> {
     let instruction = Swap {
         amount_in: amount_in,
         minimum_amount_out: minimum_amount_out,
     };
 
+    let token_program_info = context.token_program;
+    let user_transfer_authority_info = context.user_transfer_authority;
+    let middle_source_info = context.source;
+    let middle_destination_info = context.destination;
     let account_iters = &mut accounts.iter();
-    let token_program_info = next_account_info(account_iters)?;
-    let user_transfer_authority_info = next_account_info(account_iters)?;
-    let middle_source_info = next_account_info(account_iters)?;
-    let middle_destination_info = next_account_info(account_iters)?;
     let swap_info = next_account_info(account_iters)?;
     let swap_authority_info = next_account_info(account_iters)?;
     let swap_source_info = next_account_info(account_iters)?;
@@ -79,6 +174,7 @@ pub fn process_token_swap_invoke_swap(
     let token_swap_program_info = next_account_info(account_iters)?;
     let host_fee_account_info = next_account_info(account_iters);
 
+    // the CPI takes owned account infos, cloned here only
     let mut accounts = vec![
         token_swap_program_info.clone(),
         token_program_info.clone(),
@@ -92,12 +188,13 @@ pub fn process_token_swap_invoke_swap(
         pool_mint_info.clone(),
         pool_fee_account_info.clone(),
     ];
-    let host_fee_account_key = if host_fee_account_info.is_ok() {
-        let account = host_fee_account_info?;
-        accounts.push(account.clone());
-        Some(account.key)
-    } else {
-        None
+    // the pool checks a host fee account even when it mints it nothing
+    let host_fee_account_key = match host_fee_account_info {
+        Ok(account) if charges_host_fees(swap_info) => {
+            accounts.push(account.clone());
+            Some(account.key)
+        }
+        _ => None,
     };
 
     let ix = spl_token_swap_instruction(
@@ -115,8 +212,90 @@ pub fn process_token_swap_invoke_swap(
         host_fee_account_key,
         instruction,
     )?;
+    // an empty pool fails the swap with a code of its own, caught here ahead of the CPI
+    if token_account_amount(swap_destination_info)? == 0 {
+        return Err(OneSolError::ZeroTradingTokens.into());
+    }
     // invoke token-swap
-    invoke(&ix, &accounts[..])
+    invoke_signed(&ix, &accounts[..], signers_seeds).map_err(map_token_swap_error)
+}
+
+/// Translates the well-known `SwapError` codes of spl-token-swap into their 1sol
+/// counterparts, passing any other error through.
+///
+/// A cluster aborts the transaction on a failed CPI before the program sees its error, so
+/// this covers the runtimes handing the error back, such as the in-process tests. The
+/// empty pool check ahead of the CPI covers the clusters.
+pub fn map_token_swap_error(error: ProgramError) -> ProgramError {
+    let code = match error {
+        ProgramError::Custom(code) => code,
+        error => return error,
+    };
+    let error = match code {
+        SWAP_ERROR_EXCEEDED_SLIPPAGE => OneSolError::ExceededSlippage,
+        SWAP_ERROR_EMPTY_SUPPLY | SWAP_ERROR_ZERO_TRADING_TOKENS => OneSolError::ZeroTradingTokens,
+        SWAP_ERROR_INVALID_PROGRAM_ADDRESS
+        | SWAP_ERROR_INCORRECT_SWAP_ACCOUNT
+        | SWAP_ERROR_INCORRECT_POOL_MINT => OneSolError::IncorrectSwapAccount,
+        SWAP_ERROR_CALCULATION_FAILURE => OneSolError::ConversionFailure,
+        SWAP_ERROR_INCORRECT_TOKEN_PROGRAM_ID => OneSolError::IncorrectTokenProgramId,
+        code => return ProgramError::Custom(code),
+    };
+    error.into()
+}
+
+/// Whether the pool `swap_info` pays host fees, assuming it does if its fees can't be
+/// read.
+fn charges_host_fees(swap_info: &AccountInfo) -> bool {
+    let data = swap_info.data.borrow();
+    match data.get(HOST_FEE_NUMERATOR_OFFSET..HOST_FEE_NUMERATOR_OFFSET + 8) {
+        Some(numerator) => numerator.iter().any(|byte| *byte != 0),
+        None => true,
+    }
+}
+
+/// Reads the curve, fees and reserves of the pool of a leg, the accounts being those of
+/// [SplTokenSwapLeg].
+pub fn load_pool_curve(accounts: &[AccountInfo]) -> Result<PoolCurve, ProgramError> {
+    let account_iters = &mut accounts.iter();
+    let swap_info = next_account_info(account_iters)?;
+    let _swap_authority_info = next_account_info(account_iters)?;
+    let swap_source_info = next_account_info(account_iters)?;
+    let swap_destination_info = next_account_info(account_iters)?;
+    let (curve, fees) = unpack_curve(&swap_info.data.borrow())?;
+    Ok(PoolCurve {
+        curve,
+        fees,
+        source_reserve: token_account_amount(swap_source_info)?,
+        destination_reserve: token_account_amount(swap_destination_info)?,
+    })
+}
+
+fn unpack_curve(data: &[u8]) -> Result<(SwapCurve, CurveFees), OneSolError> {
+    if data.len() < FEES_OFFSET + FEES_AND_CURVE_LEN {
+        return Err(OneSolError::IncorrectSwapAccount);
+    }
+    let src = array_ref![data, FEES_OFFSET, FEES_AND_CURVE_LEN];
+    #[rustfmt::skip]
+    let (
+        trade_fee_numerator, trade_fee_denominator,
+        owner_trade_fee_numerator, owner_trade_fee_denominator,
+        _owner_withdraw_fees, _host_fees, curve_type, curve_parameter, _curve_rest,
+    ) = array_refs![src, 8, 8, 8, 8, 16, 16, 1, 8, 24];
+    let curve = match curve_type[0] {
+        CURVE_TYPE_CONSTANT_PRODUCT => SwapCurve::ConstantProduct,
+        CURVE_TYPE_STABLE => SwapCurve::Stable {
+            amp: u64::from_le_bytes(*curve_parameter),
+        },
+        _ => return Err(OneSolError::InvalidInput),
+    };
+    let fees = CurveFees::new(
+        u64::from_le_bytes(*trade_fee_numerator),
+        u64::from_le_bytes(*trade_fee_denominator),
+        u64::from_le_bytes(*owner_trade_fee_numerator),
+        u64::from_le_bytes(*owner_trade_fee_denominator),
+    )?;
+    Ok((curve, fees))
 }
 
 /// Creates a 'swap' instruction.
@@ -159,3 +338,76 @@ pub fn spl_token_swap_instruction(
         data,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Fraction;
+
+    fn charges(mut data: Vec<u8>) -> bool {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &key, false, 0);
+        charges_host_fees(&info)
+    }
+
+    #[test]
+    fn test_charges_host_fees() {
+        let mut data = vec![0u8; 324];
+        assert!(!charges(data.clone()));
+        data[HOST_FEE_NUMERATOR_OFFSET] = 20;
+        assert!(charges(data));
+        assert!(charges(vec![0u8; 200]));
+    }
+
+    #[test]
+    fn test_unpack_curve() {
+        let mut data = vec![0u8; FEES_OFFSET];
+        for value in [25u64, 10_000, 0, 0, 0, 1, 0, 1].iter() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.push(CURVE_TYPE_STABLE);
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.resize(324, 0);
+        let (curve, fees) = unpack_curve(&data).unwrap();
+        assert_eq!(curve, SwapCurve::Stable { amp: 100 });
+        assert_eq!(fees.trade_fee, Fraction::from_bps(25));
+        assert_eq!(fees.owner_trade_fee.numerator, 0);
+
+        // the offset curve is not quoted on-chain
+        data[FEES_OFFSET + 64] = 3;
+        assert_eq!(unpack_curve(&data), Err(OneSolError::InvalidInput));
+        // a fee of a zero denominator
+        data[FEES_OFFSET + 64] = CURVE_TYPE_CONSTANT_PRODUCT;
+        data[FEES_OFFSET + 8..FEES_OFFSET + 16].copy_from_slice(&0u64.to_le_bytes());
+        assert_eq!(unpack_curve(&data), Err(OneSolError::ConversionFailure));
+        assert_eq!(
+            unpack_curve(&data[..300]),
+            Err(OneSolError::IncorrectSwapAccount)
+        );
+    }
+
+    #[test]
+    fn test_map_token_swap_error() {
+        assert_eq!(
+            map_token_swap_error(ProgramError::Custom(SWAP_ERROR_EXCEEDED_SLIPPAGE)),
+            OneSolError::ExceededSlippage.into()
+        );
+        assert_eq!(
+            map_token_swap_error(ProgramError::Custom(SWAP_ERROR_EMPTY_SUPPLY)),
+            OneSolError::ZeroTradingTokens.into()
+        );
+        assert_eq!(
+            map_token_swap_error(ProgramError::Custom(SWAP_ERROR_INCORRECT_POOL_MINT)),
+            OneSolError::IncorrectSwapAccount.into()
+        );
+        assert_eq!(
+            map_token_swap_error(ProgramError::Custom(27)),
+            ProgramError::Custom(27)
+        );
+        assert_eq!(
+            map_token_swap_error(ProgramError::InvalidAccountData),
+            ProgramError::InvalidAccountData
+        );
+    }
+}
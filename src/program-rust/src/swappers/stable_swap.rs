@@ -0,0 +1,189 @@
+//! Saber StableSwap pools
+use crate::{
+    error::{AdapterError, ErrorCode},
+    instruction::DexConfig,
+    swappers::{DexAdapter, SwapContext},
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use std::ops::RangeInclusive;
+
+/// `dex_type` of Saber StableSwap legs.
+pub const DEX_TYPE: u8 = 5;
+
+/// The Saber StableSwap program.
+pub const PROGRAM_IDS: &[Pubkey] = &[solana_program::pubkey!(
+    "SSwpkEEcbUqx4vtoEByFjSkhKdCT862DNVb52nZg1UZ"
+)];
+
+/// StableSwap `SwapInstruction::Swap` tag.
+const SWAP_TAG: u8 = 1;
+
+/// Errors of StableSwap legs, in the adapter range of [DEX_TYPE].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StableSwapError {
+    /// The pool admin paused the pool.
+    PoolPaused = 0,
+}
+
+impl From<StableSwapError> for ProgramError {
+    fn from(e: StableSwapError) -> Self {
+        AdapterError {
+            dex_type: DEX_TYPE,
+            code: e as u8,
+        }
+        .into()
+    }
+}
+
+/// The [StableSwapError] codes.
+pub const ERROR_CODES: &[ErrorCode] = &[ErrorCode {
+    code: 0x1140,
+    name: "PoolPaused",
+    description: "StableSwap pool paused",
+}];
+
+/// Offset of the `is_paused` flag in a StableSwap account, following `is_initialized`.
+const IS_PAUSED_OFFSET: usize = 1;
+
+/// Encodes the data of a StableSwap `Swap` of `amount_in`.
+pub fn swap_data(amount_in: u64, minimum_amount_out: u64) -> [u8; 17] {
+    let mut data = [0u8; 17];
+    data[0] = SWAP_TAG;
+    data[1..9].copy_from_slice(&amount_in.to_le_bytes());
+    data[9..].copy_from_slice(&minimum_amount_out.to_le_bytes());
+    data
+}
+
+/// Leg through a StableSwap pool, its accounts as in [process_stable_swap_invoke_swap].
+#[derive(Clone, Copy, Debug)]
+pub struct StableSwapLeg<'a, 'b> {
+    accounts: &'a [AccountInfo<'b>],
+}
+
+impl<'a, 'b> DexAdapter<'a, 'b> for StableSwapLeg<'a, 'b> {
+    const ACCOUNT_COUNT: RangeInclusive<usize> = 7..=7;
+    const PROGRAM_IDS: &'static [Pubkey] = PROGRAM_IDS;
+
+    fn from_accounts(
+        _dex_config: &DexConfig,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        Ok(StableSwapLeg { accounts })
+    }
+
+    fn venue(&self) -> (&'a AccountInfo<'b>, Option<&'a AccountInfo<'b>>) {
+        (&self.accounts[0], Some(&self.accounts[6]))
+    }
+
+    fn invoke_swap_signed(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signers_seeds: &[&[&[u8]]],
+    ) -> Result<(), ProgramError> {
+        process_stable_swap_invoke_swap(
+            context,
+            self.accounts,
+            amount_in,
+            minimum_amount_out,
+            signers_seeds,
+        )
+    }
+}
+
+/// Swaps `amount_in` through a StableSwap pool, which checks `minimum_amount_out` itself.
+///
+/// accounts
+///   0. `[]` StableSwap account
+///   1. `[]` swap authority
+///   2. `[writable]` reserve of the SOURCE token
+///   3. `[writable]` reserve of the DESTINATION token
+///   4. `[writable]` admin fee account of the DESTINATION token
+///   5. `[]` Clock sysvar
+///   6. `[]` StableSwap program id
+pub fn process_stable_swap_invoke_swap<'a>(
+    context: &SwapContext<'_, 'a>,
+    accounts: &[AccountInfo<'a>],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    signers_seeds: &[&[&[u8]]],
+) -> Result<(), ProgramError> {
+    let account_iters = &mut accounts.iter();
+    let swap_info = next_account_info(account_iters)?;
+    let swap_authority_info = next_account_info(account_iters)?;
+    let swap_source_info = next_account_info(account_iters)?;
+    let swap_destination_info = next_account_info(account_iters)?;
+    let admin_destination_info = next_account_info(account_iters)?;
+    let clock_info = next_account_info(account_iters)?;
+    let program_info = next_account_info(account_iters)?;
+
+    // a paused pool fails the swap with a code of its own, caught here ahead of the CPI
+    if swap_info.data.borrow().get(IS_PAUSED_OFFSET) != Some(&0) {
+        return Err(StableSwapError::PoolPaused.into());
+    }
+
+    let ix = Instruction {
+        program_id: *program_info.key,
+        accounts: vec![
+            AccountMeta::new_readonly(*swap_info.key, false),
+            AccountMeta::new_readonly(*swap_authority_info.key, false),
+            AccountMeta::new_readonly(*context.user_transfer_authority.key, true),
+            AccountMeta::new(*context.source.key, false),
+            AccountMeta::new(*swap_source_info.key, false),
+            AccountMeta::new(*swap_destination_info.key, false),
+            AccountMeta::new(*context.destination.key, false),
+            AccountMeta::new(*admin_destination_info.key, false),
+            AccountMeta::new_readonly(*context.token_program.key, false),
+            AccountMeta::new_readonly(*clock_info.key, false),
+        ],
+        data: swap_data(amount_in, minimum_amount_out).to_vec(),
+    };
+    invoke_signed(
+        &ix,
+        &[
+            swap_info.clone(),
+            swap_authority_info.clone(),
+            context.user_transfer_authority.clone(),
+            context.source.clone(),
+            swap_source_info.clone(),
+            swap_destination_info.clone(),
+            context.destination.clone(),
+            admin_destination_info.clone(),
+            context.token_program.clone(),
+            clock_info.clone(),
+            program_info.clone(),
+        ],
+        signers_seeds,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stable_swap_error_codes() {
+        let entry = &ERROR_CODES[0];
+        assert_eq!(
+            ProgramError::from(StableSwapError::PoolPaused),
+            ProgramError::Custom(entry.code)
+        );
+        assert_eq!(format!("{:?}", StableSwapError::PoolPaused), entry.name);
+        assert!(AdapterError::code_range(DEX_TYPE).contains(&entry.code));
+    }
+
+    #[test]
+    fn test_swap_data() {
+        let data = swap_data(1_000, 990);
+        assert_eq!(data[0], 1);
+        assert_eq!(data[1..9], 1_000u64.to_le_bytes());
+        assert_eq!(data[9..], 990u64.to_le_bytes());
+    }
+}
@@ -1,77 +1,72 @@
-//! TokenSwap
-use crate::swappers;
-use solana_program::{account_info::AccountInfo, program_error::ProgramError};
-// use spl_token_swap::curve::base::SwapCurve;
-
-#[derive(Clone, Debug)]
-#[allow(dead_code)]
-pub enum SwapperType {
-    SplTokenSwap,
-    Test,
-}
+//! Swappers and the accounts shared by their legs
+use crate::{
+    curve::{Curve, PoolCurve},
+    error::OneSolError,
+};
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
 
 pub trait Swapper {
-    fn invoke_swap(&self, amount_in: u64, minimum_amount_out: u64) -> Result<(), ProgramError>;
-}
-
-#[derive(Clone, Debug)]
-pub struct TokenSwap<'a> {
-    swapper_type: SwapperType,
-    accounts: Vec<AccountInfo<'a>>,
-}
+    /// Swaps `amount_in` through the leg, `signers_seeds` signing for the user transfer
+    /// authority if a program address, empty otherwise.
+    fn invoke_swap_signed(
+        &self,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signers_seeds: &[&[&[u8]]],
+    ) -> Result<(), ProgramError>;
 
-impl<'a> TokenSwap<'a> {
-    /// accounts
-    ///   0. `[]` token_program_info
-    ///   1. `[]` user_transfer_authority_info
-    ///   2. `[]` middle_source_info
-    ///   3. `[]` middle_destination_info
-    ///   4. `[]` token-swap account
-    ///   5. `[]` token-swap authority
-    ///   6. `[writable]` token_A Base Account to swap INTO.  Must be the SOURCE token.
-    ///   7. `[writable]` token_B Base Account to swap FROM.  Must be the DESTINATION token.
-    ///   8. `[writable]` Pool token mint, to generate trading fees
-    ///   9. `[writable]` Fee account, to receive trading fees
-    ///   10. '[]` Token-Swap program id
-    ///   11 `[optional, writable]` Host fee account to receive additional trading fees
-    pub fn new_spl_token_swap(
-        token_program_info: AccountInfo<'a>,
-        user_transfer_authority_info: AccountInfo<'a>,
-        middle_source_info: AccountInfo<'a>,
-        middle_destination_info: AccountInfo<'a>,
-        accounts: &[AccountInfo<'a>],
-    ) -> Result<TokenSwap<'a>, ProgramError> {
-        let mut a = vec![
-            token_program_info,
-            user_transfer_authority_info,
-            middle_source_info,
-            middle_destination_info,
-        ];
-        a.extend(accounts.iter().cloned());
-        Ok(TokenSwap {
-            swapper_type: SwapperType::SplTokenSwap,
-            accounts: a,
-        })
+    /// Curve, fees and reserves of the pool the swapper trades through, None if it can't
+    /// be quoted on-chain.
+    fn pool_curve(&self) -> Option<PoolCurve> {
+        None
     }
 
-    #[allow(dead_code)]
-    pub fn new_test_swap() -> Result<TokenSwap<'a>, ProgramError> {
-        Ok(TokenSwap {
-            swapper_type: SwapperType::Test,
-            accounts: vec![],
-        })
+    /// Returns the DESTINATION amount a swap of `amount_in` through the leg receives,
+    /// from the reserves and curve parameters of its venue, None if it can't be quoted
+    /// on-chain.
+    fn quote(&self, amount_in: u64) -> Option<u64> {
+        self.pool_curve()?.simulate_swap(amount_in)
     }
-}
 
-impl<'a> Swapper for TokenSwap<'a> {
-    fn invoke_swap(&self, amount_in: u64, minimum_amount_out: u64) -> Result<(), ProgramError> {
-        match self.swapper_type {
-            SwapperType::SplTokenSwap => swappers::spl_token_swap::process_token_swap_invoke_swap(
-                &self.accounts[..],
-                amount_in,
-                minimum_amount_out,
-            ),
-            SwapperType::Test => Ok(()),
+    /// Returns the least SOURCE amount whose swap through the leg outputs `amount_out`,
+    /// searching the quotes of the leg up to `maximum_amount_in`. Fails with
+    /// `QuoteUnavailable` if the leg can't be quoted on-chain, `ExceededSlippage` if
+    /// `maximum_amount_in` falls short.
+    fn quote_exact_out(&self, amount_out: u64, maximum_amount_in: u64) -> Result<u64, OneSolError> {
+        if amount_out == 0 {
+            return Ok(0);
+        }
+        let most = self
+            .quote(maximum_amount_in)
+            .ok_or(OneSolError::QuoteUnavailable)?;
+        if most < amount_out {
+            return Err(OneSolError::ExceededSlippage);
+        }
+        // quotes grow with the amount in: the least amount is above `low`, at most `high`
+        let (mut low, mut high) = (0, maximum_amount_in);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if matches!(self.quote(mid), Some(quoted) if quoted >= amount_out) {
+                high = mid;
+            } else {
+                low = mid;
+            }
         }
+        Ok(high)
     }
 }
+
+/// Accounts shared by the legs of a route, borrowed by each of its swappers.
+#[derive(Clone, Copy, Debug)]
+pub struct SwapContext<'a, 'b> {
+    /// Program executing the legs
+    pub program_id: &'a Pubkey,
+    /// Token program
+    pub token_program: &'a AccountInfo<'b>,
+    /// Authority over the SOURCE tokens of the legs
+    pub user_transfer_authority: &'a AccountInfo<'b>,
+    /// Token account the legs swap from
+    pub source: &'a AccountInfo<'b>,
+    /// Token account the legs swap into
+    pub destination: &'a AccountInfo<'b>,
+}
@@ -0,0 +1,237 @@
+//! Orca Whirlpool concentrated liquidity pools
+use crate::{
+    error::OneSolError,
+    instruction::DexConfig,
+    swappers::{DexAdapter, SwapContext},
+    util::unpack_token_account,
+};
+use arrayref::array_ref;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use std::ops::RangeInclusive;
+
+solana_program::declare_id!("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc");
+
+/// `dex_type` of Orca Whirlpool legs.
+pub const DEX_TYPE: u8 = 4;
+
+/// Length of a whirlpool account.
+const WHIRLPOOL_LEN: usize = 653;
+/// Offsets of the mints and vaults in a whirlpool account.
+const TOKEN_MINT_A_OFFSET: usize = 101;
+const TOKEN_VAULT_A_OFFSET: usize = 133;
+const TOKEN_MINT_B_OFFSET: usize = 181;
+const TOKEN_VAULT_B_OFFSET: usize = 213;
+
+/// Anchor discriminator of the whirlpool `swap` instruction.
+const SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+
+/// Lowest sqrt price of a whirlpool, as Q64.64.
+pub const MIN_SQRT_PRICE: u128 = 4_295_048_016;
+/// Highest sqrt price of a whirlpool, as Q64.64.
+pub const MAX_SQRT_PRICE: u128 = 79_226_673_515_401_279_992_447_579_055;
+
+/// The whirlpool fields a leg reads.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Whirlpool {
+    /// Mint of token A
+    pub token_mint_a: Pubkey,
+    /// Vault of token A
+    pub token_vault_a: Pubkey,
+    /// Mint of token B
+    pub token_mint_b: Pubkey,
+    /// Vault of token B
+    pub token_vault_b: Pubkey,
+}
+
+impl Whirlpool {
+    /// Reads the whirlpool from the data of its account.
+    pub fn unpack(data: &[u8]) -> Result<Whirlpool, ProgramError> {
+        if data.len() != WHIRLPOOL_LEN {
+            return Err(OneSolError::InvalidInput.into());
+        }
+        let pubkey_at = |offset| Pubkey::new_from_array(*array_ref![data, offset, 32]);
+        Ok(Whirlpool {
+            token_mint_a: pubkey_at(TOKEN_MINT_A_OFFSET),
+            token_vault_a: pubkey_at(TOKEN_VAULT_A_OFFSET),
+            token_mint_b: pubkey_at(TOKEN_MINT_B_OFFSET),
+            token_vault_b: pubkey_at(TOKEN_VAULT_B_OFFSET),
+        })
+    }
+}
+
+/// Encodes the data of a whirlpool `swap` of exactly `amount_in`, in the `a_to_b`
+/// direction, up to the price limit of that direction: the output is bounded by
+/// `minimum_amount_out` instead.
+pub fn swap_data(amount_in: u64, minimum_amount_out: u64, a_to_b: bool) -> Vec<u8> {
+    let sqrt_price_limit = if a_to_b {
+        MIN_SQRT_PRICE
+    } else {
+        MAX_SQRT_PRICE
+    };
+    let mut data = Vec::with_capacity(42);
+    data.extend_from_slice(&SWAP_DISCRIMINATOR);
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+    data.extend_from_slice(&sqrt_price_limit.to_le_bytes());
+    // amount_specified_is_input
+    data.push(1);
+    data.push(a_to_b as u8);
+    data
+}
+
+/// Leg through a whirlpool, its accounts as in [process_whirlpool_invoke_swap].
+#[derive(Clone, Copy, Debug)]
+pub struct WhirlpoolLeg<'a, 'b> {
+    accounts: &'a [AccountInfo<'b>],
+}
+
+impl<'a, 'b> DexAdapter<'a, 'b> for WhirlpoolLeg<'a, 'b> {
+    const ACCOUNT_COUNT: RangeInclusive<usize> = 8..=8;
+    const PROGRAM_IDS: &'static [Pubkey] = &[ID];
+
+    fn from_accounts(
+        _dex_config: &DexConfig,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        Ok(WhirlpoolLeg { accounts })
+    }
+
+    fn venue(&self) -> (&'a AccountInfo<'b>, Option<&'a AccountInfo<'b>>) {
+        (&self.accounts[0], Some(&self.accounts[7]))
+    }
+
+    fn invoke_swap_signed(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signers_seeds: &[&[&[u8]]],
+    ) -> Result<(), ProgramError> {
+        process_whirlpool_invoke_swap(
+            context,
+            self.accounts,
+            amount_in,
+            minimum_amount_out,
+            signers_seeds,
+        )
+    }
+}
+
+/// Swaps `amount_in` through a whirlpool, the direction following from the SOURCE mint.
+/// The whirlpool checks `minimum_amount_out` itself.
+///
+/// accounts
+///   0. `[writable]` whirlpool
+///   1. `[writable]` token A vault
+///   2. `[writable]` token B vault
+///   3. `[writable]` tick array 0, holding the current tick
+///   4. `[writable]` tick array 1, next in the swap direction
+///   5. `[writable]` tick array 2, next in the swap direction
+///   6. `[]` oracle, derived from `[b"oracle", whirlpool]`
+///   7. `[]` Whirlpool program id
+pub fn process_whirlpool_invoke_swap<'a>(
+    context: &SwapContext<'_, 'a>,
+    accounts: &[AccountInfo<'a>],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    signers_seeds: &[&[&[u8]]],
+) -> Result<(), ProgramError> {
+    let account_iters = &mut accounts.iter();
+    let whirlpool_info = next_account_info(account_iters)?;
+    let vault_a_info = next_account_info(account_iters)?;
+    let vault_b_info = next_account_info(account_iters)?;
+    let tick_array_0_info = next_account_info(account_iters)?;
+    let tick_array_1_info = next_account_info(account_iters)?;
+    let tick_array_2_info = next_account_info(account_iters)?;
+    let oracle_info = next_account_info(account_iters)?;
+    let program_info = next_account_info(account_iters)?;
+
+    let whirlpool = Whirlpool::unpack(&whirlpool_info.data.borrow())?;
+    if whirlpool.token_vault_a != *vault_a_info.key || whirlpool.token_vault_b != *vault_b_info.key
+    {
+        return Err(OneSolError::IncorrectSwapAccount.into());
+    }
+    let source_mint = unpack_token_account(context.source, context.token_program.key)?.mint;
+    let (a_to_b, owner_account_a, owner_account_b) = if source_mint == whirlpool.token_mint_a {
+        (true, context.source, context.destination)
+    } else if source_mint == whirlpool.token_mint_b {
+        (false, context.destination, context.source)
+    } else {
+        return Err(OneSolError::IncorrectSwapAccount.into());
+    };
+
+    let ix = Instruction {
+        program_id: *program_info.key,
+        accounts: vec![
+            AccountMeta::new_readonly(*context.token_program.key, false),
+            AccountMeta::new_readonly(*context.user_transfer_authority.key, true),
+            AccountMeta::new(*whirlpool_info.key, false),
+            AccountMeta::new(*owner_account_a.key, false),
+            AccountMeta::new(*vault_a_info.key, false),
+            AccountMeta::new(*owner_account_b.key, false),
+            AccountMeta::new(*vault_b_info.key, false),
+            AccountMeta::new(*tick_array_0_info.key, false),
+            AccountMeta::new(*tick_array_1_info.key, false),
+            AccountMeta::new(*tick_array_2_info.key, false),
+            AccountMeta {
+                pubkey: *oracle_info.key,
+                is_signer: false,
+                is_writable: oracle_info.is_writable,
+            },
+        ],
+        data: swap_data(amount_in, minimum_amount_out, a_to_b),
+    };
+    invoke_signed(
+        &ix,
+        &[
+            context.token_program.clone(),
+            context.user_transfer_authority.clone(),
+            whirlpool_info.clone(),
+            owner_account_a.clone(),
+            vault_a_info.clone(),
+            owner_account_b.clone(),
+            vault_b_info.clone(),
+            tick_array_0_info.clone(),
+            tick_array_1_info.clone(),
+            tick_array_2_info.clone(),
+            oracle_info.clone(),
+            program_info.clone(),
+        ],
+        signers_seeds,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::hash::hash;
+
+    #[test]
+    fn test_swap_data() {
+        assert_eq!(SWAP_DISCRIMINATOR, hash(b"global:swap").to_bytes()[..8]);
+        let data = swap_data(1_000, 990, true);
+        assert_eq!(data.len(), 42);
+        assert_eq!(data[8..16], 1_000u64.to_le_bytes());
+        assert_eq!(data[16..24], 990u64.to_le_bytes());
+        assert_eq!(data[24..40], MIN_SQRT_PRICE.to_le_bytes());
+        assert_eq!(data[40..], [1, 1]);
+        let data = swap_data(1_000, 990, false);
+        assert_eq!(data[24..40], MAX_SQRT_PRICE.to_le_bytes());
+        assert_eq!(data[41], 0);
+    }
+
+    #[test]
+    fn test_whirlpool_unpack() {
+        let mut data = vec![0u8; WHIRLPOOL_LEN];
+        let token_mint_b = Pubkey::new_unique();
+        data[TOKEN_MINT_B_OFFSET..TOKEN_MINT_B_OFFSET + 32].copy_from_slice(token_mint_b.as_ref());
+        assert_eq!(Whirlpool::unpack(&data).unwrap().token_mint_b, token_mint_b);
+        assert!(Whirlpool::unpack(&data[..600]).is_err());
+    }
+}
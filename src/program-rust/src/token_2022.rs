@@ -0,0 +1,246 @@
+//! SPL Token-2022 accounts and transfer fees
+//!
+//! Token-2022 accounts start with the layout of their spl_token counterpart, mints
+//! padded to the length of an account, then extend it with an account type byte and
+//! the TLV entries of their extensions.
+use crate::error::OneSolError;
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use std::convert::TryInto;
+
+solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Offset of the account type of an account with extensions.
+const ACCOUNT_TYPE_OFFSET: usize = spl_token::state::Account::LEN;
+/// Account type of mints.
+const ACCOUNT_TYPE_MINT: u8 = 1;
+/// Account type of token accounts.
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+/// Extension type of the transfer fee config of a mint.
+const TRANSFER_FEE_CONFIG: u16 = 1;
+/// Offset of the older transfer fee in the transfer fee config, following the two
+/// authorities and the withheld amount.
+const OLDER_TRANSFER_FEE_OFFSET: usize = 72;
+/// Offset of the newer transfer fee in the transfer fee config.
+const NEWER_TRANSFER_FEE_OFFSET: usize = 90;
+
+/// Tag of the `TransferChecked` instruction, shared by both token programs.
+const TRANSFER_CHECKED_TAG: u8 = 12;
+
+/// Returns true for the spl_token and Token-2022 program ids.
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::id() || *program_id == id()
+}
+
+/// Borrows the spl_token layout of a token account of `owner`, ahead of the extensions of
+/// a Token-2022 one.
+pub fn account_base<'a>(data: &'a [u8], owner: &Pubkey) -> Option<&'a [u8]> {
+    base(
+        data,
+        owner,
+        spl_token::state::Account::LEN,
+        ACCOUNT_TYPE_ACCOUNT,
+    )
+}
+
+/// Borrows the spl_token layout of a mint of `owner`, ahead of the padding and extensions
+/// of a Token-2022 one.
+pub fn mint_base<'a>(data: &'a [u8], owner: &Pubkey) -> Option<&'a [u8]> {
+    base(data, owner, spl_token::state::Mint::LEN, ACCOUNT_TYPE_MINT)
+}
+
+fn base<'a>(data: &'a [u8], owner: &Pubkey, len: usize, account_type: u8) -> Option<&'a [u8]> {
+    if data.len() == len {
+        Some(data)
+    } else if *owner == id() && data.get(ACCOUNT_TYPE_OFFSET) == Some(&account_type) {
+        data.get(..len)
+    } else {
+        None
+    }
+}
+
+/// Walks the TLV entries of the extensions of a Token-2022 account, returning the value
+/// of the extension `extension_type` if any.
+fn extension(data: &[u8], extension_type: u16) -> Option<&[u8]> {
+    let mut entries = data.get(ACCOUNT_TYPE_OFFSET + 1..)?;
+    while entries.len() >= 4 {
+        let entry_type = u16::from_le_bytes([entries[0], entries[1]]);
+        let len = u16::from_le_bytes([entries[2], entries[3]]) as usize;
+        let value = entries.get(4..4 + len)?;
+        // the uninitialized type pads the end of the data
+        if entry_type == 0 {
+            return None;
+        }
+        if entry_type == extension_type {
+            return Some(value);
+        }
+        entries = &entries[4 + len..];
+    }
+    None
+}
+
+/// Transfer fee of a Token-2022 mint, withheld in the account receiving a transfer.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TransferFee {
+    /// First epoch the fee applies
+    pub epoch: u64,
+    /// Largest fee of a transfer
+    pub maximum_fee: u64,
+    /// Fee in bps of the transferred amount, rounded up
+    pub transfer_fee_basis_points: u16,
+}
+
+impl TransferFee {
+    /// Length of a packed transfer fee
+    pub const LEN: usize = 18;
+
+    fn from_bytes(bytes: &[u8]) -> TransferFee {
+        TransferFee {
+            epoch: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            maximum_fee: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            transfer_fee_basis_points: u16::from_le_bytes([bytes[16], bytes[17]]),
+        }
+    }
+
+    /// Fee withheld from a transfer of `amount`, as Token-2022 computes it.
+    pub fn fee(&self, amount: u64) -> Result<u64, OneSolError> {
+        if self.transfer_fee_basis_points == 0 || amount == 0 {
+            return Ok(0);
+        }
+        let fee = (amount as u128)
+            .checked_mul(self.transfer_fee_basis_points as u128)
+            .and_then(|numerator| numerator.checked_add(9_999))
+            .map(|numerator| numerator / 10_000)
+            .ok_or(OneSolError::ConversionFailure)?;
+        Ok(fee.min(self.maximum_fee as u128) as u64)
+    }
+}
+
+/// Transfer fee of the mint `data`, owned by `owner`, in effect at `epoch`: none unless a
+/// Token-2022 mint with a transfer fee config.
+pub fn transfer_fee(
+    data: &[u8],
+    owner: &Pubkey,
+    epoch: u64,
+) -> Result<Option<TransferFee>, OneSolError> {
+    if *owner != id() || data.len() == spl_token::state::Mint::LEN {
+        return Ok(None);
+    }
+    mint_base(data, owner).ok_or(OneSolError::ExpectedMint)?;
+    let config = match extension(data, TRANSFER_FEE_CONFIG) {
+        Some(config) => config,
+        None => return Ok(None),
+    };
+    let fee_at = |offset: usize| {
+        config
+            .get(offset..offset + TransferFee::LEN)
+            .map(TransferFee::from_bytes)
+            .ok_or(OneSolError::ExpectedMint)
+    };
+    let newer = fee_at(NEWER_TRANSFER_FEE_OFFSET)?;
+    if epoch >= newer.epoch {
+        Ok(Some(newer))
+    } else {
+        fee_at(OLDER_TRANSFER_FEE_OFFSET).map(Some)
+    }
+}
+
+/// Encodes the data of a `TransferChecked` of `amount` of a mint of `decimals`.
+pub fn transfer_checked_data(amount: u64, decimals: u8) -> [u8; 10] {
+    let mut data = [0u8; 10];
+    data[0] = TRANSFER_CHECKED_TAG;
+    data[1..9].copy_from_slice(&amount.to_le_bytes());
+    data[9] = decimals;
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Data of a Token-2022 mint with a transfer fee config changing from `older` to
+    /// `newer`.
+    fn fee_mint(older: TransferFee, newer: TransferFee) -> Vec<u8> {
+        let mut data = vec![0u8; ACCOUNT_TYPE_OFFSET];
+        data[44] = 6;
+        data.push(ACCOUNT_TYPE_MINT);
+        data.extend_from_slice(&TRANSFER_FEE_CONFIG.to_le_bytes());
+        data.extend_from_slice(&108u16.to_le_bytes());
+        data.extend_from_slice(&[0u8; OLDER_TRANSFER_FEE_OFFSET]);
+        for fee in [older, newer].iter() {
+            data.extend_from_slice(&fee.epoch.to_le_bytes());
+            data.extend_from_slice(&fee.maximum_fee.to_le_bytes());
+            data.extend_from_slice(&fee.transfer_fee_basis_points.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_transfer_fee() {
+        let older = TransferFee {
+            epoch: 0,
+            maximum_fee: u64::MAX,
+            transfer_fee_basis_points: 100,
+        };
+        let newer = TransferFee {
+            epoch: 10,
+            maximum_fee: 5,
+            transfer_fee_basis_points: 250,
+        };
+        let data = fee_mint(older, newer);
+        assert_eq!(transfer_fee(&data, &id(), 9).unwrap(), Some(older));
+        assert_eq!(transfer_fee(&data, &id(), 10).unwrap(), Some(newer));
+        assert_eq!(mint_base(&data, &id()).unwrap()[44], 6);
+
+        // rounded up, then capped
+        assert_eq!(older.fee(1_000).unwrap(), 10);
+        assert_eq!(older.fee(1_001).unwrap(), 11);
+        assert_eq!(older.fee(0).unwrap(), 0);
+        assert_eq!(newer.fee(1_000).unwrap(), 5);
+        assert_eq!(TransferFee::default().fee(1_000).unwrap(), 0);
+
+        // spl_token mints and Token-2022 ones without the extension charge none
+        assert_eq!(transfer_fee(&data, &spl_token::id(), 10).unwrap(), None);
+        let plain = vec![0u8; spl_token::state::Mint::LEN];
+        assert_eq!(transfer_fee(&plain, &id(), 10).unwrap(), None);
+        let mut padded = data[..ACCOUNT_TYPE_OFFSET + 1].to_vec();
+        padded.extend_from_slice(&[0u8; 8]);
+        assert_eq!(transfer_fee(&padded, &id(), 10).unwrap(), None);
+        // nor do token accounts pass for mints
+        let mut account = data.clone();
+        account[ACCOUNT_TYPE_OFFSET] = ACCOUNT_TYPE_ACCOUNT;
+        assert!(transfer_fee(&account, &id(), 10).is_err());
+    }
+
+    #[test]
+    fn test_account_base() {
+        let mut data = vec![7u8; spl_token::state::Account::LEN];
+        assert_eq!(account_base(&data, &spl_token::id()).unwrap(), &data[..]);
+        data.push(ACCOUNT_TYPE_ACCOUNT);
+        data.extend_from_slice(&[0u8; 12]);
+        assert_eq!(account_base(&data, &spl_token::id()), None);
+        assert_eq!(
+            account_base(&data, &id()).unwrap(),
+            &data[..spl_token::state::Account::LEN]
+        );
+        data[ACCOUNT_TYPE_OFFSET] = ACCOUNT_TYPE_MINT;
+        assert_eq!(account_base(&data, &id()), None);
+    }
+
+    #[test]
+    fn test_transfer_checked_data() {
+        let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let ix = spl_token::instruction::transfer_checked(
+            &spl_token::id(),
+            &keys[0],
+            &keys[1],
+            &keys[2],
+            &keys[3],
+            &[],
+            1_000,
+            6,
+        )
+        .unwrap();
+        assert_eq!(transfer_checked_data(1_000, 6).to_vec(), ix.data);
+    }
+}
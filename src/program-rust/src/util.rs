@@ -1,26 +1,182 @@
-use crate::error::OneSolError;
+use crate::{error::OneSolError, token_2022};
 use solana_program::{account_info::AccountInfo, program_pack::Pack, pubkey::Pubkey};
-// use std::convert::TryInto;
+use spl_token::state::AccountState;
+use std::convert::TryInto;
 
-/// Unpacks a spl_token `Account`.
+/// Unpacks a spl_token `Account`, or the spl_token layout of a Token-2022 one.
 pub fn unpack_token_account(
     account_info: &AccountInfo,
     token_program_id: &Pubkey,
 ) -> Result<spl_token::state::Account, OneSolError> {
-    if account_info.owner != token_program_id {
+    if account_info.owner != token_program_id || !token_2022::is_token_program(token_program_id) {
         Err(OneSolError::IncorrectTokenProgramId)
     } else {
-        spl_token::state::Account::unpack(&account_info.data.borrow())
-            .map_err(|_| OneSolError::ExpectedAccount)
+        let data = account_info.data.borrow();
+        token_2022::account_base(&data, token_program_id)
+            .and_then(|base| spl_token::state::Account::unpack(base).ok())
+            .ok_or(OneSolError::ExpectedAccount)
     }
 }
 
-// /// convert u64 to u128
-// pub fn to_u128(val: u64) -> Result<u128, OneSolError> {
-//     val.try_into().map_err(|_| OneSolError::ConversionFailure)
-// }
+/// Reads the mint of an initialized spl_token `Account`, or Token-2022 one, with the checks
+/// of [unpack_token_account] but without unpacking the rest of it.
+pub fn token_account_mint(
+    account_info: &AccountInfo,
+    token_program_id: &Pubkey,
+) -> Result<Pubkey, OneSolError> {
+    if account_info.owner != token_program_id || !token_2022::is_token_program(token_program_id) {
+        return Err(OneSolError::IncorrectTokenProgramId);
+    }
+    let data = account_info.data.borrow();
+    let base =
+        token_2022::account_base(&data, token_program_id).ok_or(OneSolError::ExpectedAccount)?;
+    // the state follows mint, owner, amount and delegate
+    let state = base[108];
+    if state == AccountState::Initialized as u8 || state == AccountState::Frozen as u8 {
+        Ok(Pubkey::new(&base[..32]))
+    } else {
+        Err(OneSolError::ExpectedAccount)
+    }
+}
+
+/// Reads the amount of a spl_token `Account` already unpacked once, without unpacking it
+/// again.
+pub fn token_account_amount(account_info: &AccountInfo) -> Result<u64, OneSolError> {
+    let data = account_info.data.borrow();
+    // Token-2022 extensions follow the spl_token layout
+    if data.len() < spl_token::state::Account::LEN {
+        return Err(OneSolError::ExpectedAccount);
+    }
+    // mint and owner come first
+    Ok(u64::from_le_bytes(data[64..72].try_into().unwrap()))
+}
+
+/// Tag of the spl_token `Transfer` instruction.
+const TOKEN_TRANSFER_TAG: u8 = 3;
+
+/// Encodes the data of a spl_token `Transfer` of `amount` in a stack buffer, without the
+/// `TokenInstruction` packing of `spl_token::instruction::transfer`.
+pub fn token_transfer_data(amount: u64) -> [u8; 9] {
+    let mut data = [0u8; 9];
+    data[0] = TOKEN_TRANSFER_TAG;
+    data[1..].copy_from_slice(&amount.to_le_bytes());
+    data
+}
 
-// /// convert u128 to u64
-// pub fn to_u64(val: u128) -> Result<u64, OneSolError> {
-//     val.try_into().map_err(|_| OneSolError::ConversionFailure)
-// }
+/// Unpacks a spl_token `Mint`, or the spl_token layout of a Token-2022 one.
+pub fn unpack_mint(
+    account_info: &AccountInfo,
+    token_program_id: &Pubkey,
+) -> Result<spl_token::state::Mint, OneSolError> {
+    if account_info.owner != token_program_id || !token_2022::is_token_program(token_program_id) {
+        Err(OneSolError::IncorrectTokenProgramId)
+    } else {
+        let data = account_info.data.borrow();
+        token_2022::mint_base(&data, token_program_id)
+            .and_then(|base| spl_token::state::Mint::unpack(base).ok())
+            .ok_or(OneSolError::ExpectedMint)
+    }
+}
+
+/// convert u128 to u64
+pub fn to_u64(val: u128) -> Result<u64, OneSolError> {
+    val.try_into().map_err(|_| OneSolError::ConversionFailure)
+}
+
+/// Logs like `msg!` when the log level is `LogLevel::Full`, see `LogLevel::logs_progress`.
+macro_rules! verbose {
+    ($log_level:expr, $($arg:tt)+) => {
+        if $log_level.logs_progress() {
+            solana_program::msg!($($arg)+);
+        }
+    };
+}
+pub(crate) use verbose;
+
+/// Logs like `verbose!` in builds with the `debug-logs` feature, compiled out otherwise.
+macro_rules! debug {
+    ($log_level:expr, $($arg:tt)+) => {
+        if cfg!(feature = "debug-logs") {
+            $crate::util::verbose!($log_level, $($arg)+);
+        }
+    };
+}
+pub(crate) use debug;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_transfer_data() {
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        for amount in [0, 1, 1_000_000, u64::MAX].iter() {
+            let ix = spl_token::instruction::transfer(
+                &spl_token::id(),
+                &keys[0],
+                &keys[1],
+                &keys[2],
+                &[],
+                *amount,
+            )
+            .unwrap();
+            assert_eq!(token_transfer_data(*amount).to_vec(), ix.data);
+        }
+    }
+
+    #[test]
+    fn test_token_account_mint() {
+        let key = Pubkey::new_unique();
+        let owner = spl_token::id();
+        let mut lamports = 0;
+        let mut data = vec![0u8; spl_token::state::Account::LEN];
+        let account = spl_token::state::Account {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            amount: 42,
+            state: AccountState::Frozen,
+            ..Default::default()
+        };
+        spl_token::state::Account::pack(account, &mut data).unwrap();
+        let info = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        assert_eq!(token_account_mint(&info, &owner), Ok(account.mint));
+        assert_eq!(
+            token_account_mint(&info, &Pubkey::new_unique()),
+            Err(OneSolError::IncorrectTokenProgramId)
+        );
+        assert_eq!(
+            unpack_token_account(&info, &owner).unwrap().mint,
+            account.mint
+        );
+
+        info.data.borrow_mut()[108] = AccountState::Uninitialized as u8;
+        assert_eq!(
+            token_account_mint(&info, &owner),
+            Err(OneSolError::ExpectedAccount)
+        );
+        let (mut short_lamports, mut short) = (0, vec![0u8; 64]);
+        let short_info = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut short_lamports,
+            &mut short,
+            &owner,
+            false,
+            0,
+        );
+        assert_eq!(
+            token_account_mint(&short_info, &owner),
+            Err(OneSolError::ExpectedAccount)
+        );
+    }
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use common::{swap_instruction, Harness, TestPool, TestProtocol, TestSwapAccounts};
+use onesol::{error::OneSolError, state::OneSolState};
+use onesol_client::instruction::{close_protocol_account, set_paused, transfer_admin};
+use solana_program::{
+    instruction::Instruction,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+};
+
+struct AdminSetup {
+    harness: Harness,
+    protocol: TestProtocol,
+    pool: TestPool,
+    user: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+}
+
+/// A protocol swapping mint A for mint B.
+fn setup() -> AdminSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    AdminSetup {
+        harness,
+        protocol,
+        pool,
+        user,
+        source,
+        destination,
+    }
+}
+
+fn swap(setup: &AdminSetup) -> Instruction {
+    swap_instruction(
+        &setup.harness.program_id,
+        &setup.protocol,
+        &TestSwapAccounts {
+            user: setup.user,
+            source: setup.source,
+            destination: setup.destination,
+        },
+        10_000,
+        0,
+        &[(setup.pool.leg_a_to_b(), 10_000)],
+    )
+}
+
+fn state(setup: &AdminSetup) -> OneSolState {
+    OneSolState::unpack(&setup.harness.account(&setup.protocol.protocol).data).unwrap()
+}
+
+#[test]
+fn test_pause_rejects_swaps_until_unpaused() {
+    let mut setup = setup();
+    let program_id = setup.harness.program_id;
+    let (admin, protocol) = (setup.protocol.admin, setup.protocol.protocol);
+    setup
+        .harness
+        .process(&set_paused(&program_id, &admin, &protocol, true))
+        .unwrap();
+    assert!(state(&setup).is_paused);
+    assert_eq!(
+        setup.harness.process(&swap(&setup)).unwrap_err(),
+        OneSolError::Paused.into()
+    );
+    assert_eq!(setup.harness.token_balance(&setup.source), 50_000);
+
+    setup
+        .harness
+        .process(&set_paused(&program_id, &admin, &protocol, false))
+        .unwrap();
+    assert!(!state(&setup).is_paused);
+    setup.harness.process(&swap(&setup)).unwrap();
+    assert_eq!(setup.harness.token_balance(&setup.destination), 9_900);
+}
+
+#[test]
+fn test_pause_requires_admin() {
+    let mut setup = setup();
+    let program_id = setup.harness.program_id;
+    let ix = set_paused(
+        &program_id,
+        &Pubkey::new_unique(),
+        &setup.protocol.protocol,
+        true,
+    );
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidOwner.into()
+    );
+    assert!(!state(&setup).is_paused);
+}
+
+#[test]
+fn test_transfer_admin() {
+    let mut setup = setup();
+    let program_id = setup.harness.program_id;
+    let (admin, protocol) = (setup.protocol.admin, setup.protocol.protocol);
+    let new_admin = Pubkey::new_unique();
+    setup
+        .harness
+        .process(&transfer_admin(&program_id, &admin, &protocol, &new_admin))
+        .unwrap();
+    assert_eq!(state(&setup).admin, new_admin);
+
+    // the former admin no longer pauses the protocol, the new one does
+    assert_eq!(
+        setup
+            .harness
+            .process(&set_paused(&program_id, &admin, &protocol, true))
+            .unwrap_err(),
+        OneSolError::InvalidOwner.into()
+    );
+    setup
+        .harness
+        .process(&set_paused(&program_id, &new_admin, &protocol, true))
+        .unwrap();
+    assert!(state(&setup).is_paused);
+}
+
+fn close(setup: &AdminSetup, admin: &Pubkey, receiver: &Pubkey) -> Instruction {
+    close_protocol_account(
+        &setup.harness.program_id,
+        admin,
+        &setup.protocol.protocol,
+        &setup.protocol.authority,
+        &setup.protocol.token,
+        &setup.destination,
+        receiver,
+        &spl_token::id(),
+    )
+}
+
+#[test]
+fn test_close_protocol_account() {
+    let mut setup = setup();
+    setup.harness.set_token_balance(&setup.protocol.token, 7);
+    let protocol_lamports = setup.harness.account(&setup.protocol.protocol).lamports;
+    let receiver = setup
+        .harness
+        .add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let ix = close(&setup, &setup.protocol.admin, &receiver);
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.protocol.token), 0);
+    assert_eq!(harness.token_balance(&setup.destination), 7);
+    let protocol = harness.account(&setup.protocol.protocol);
+    assert_eq!(protocol.lamports, 0);
+    assert!(protocol.data.iter().all(|&byte| byte == 0));
+    assert_eq!(
+        harness.account(&receiver).lamports,
+        1_000_000_000 + protocol_lamports
+    );
+}
+
+#[test]
+fn test_close_protocol_account_requires_admin() {
+    let mut setup = setup();
+    let receiver = Pubkey::new_unique();
+    let ix = close(&setup, &Pubkey::new_unique(), &receiver);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidOwner.into()
+    );
+
+    // the lamports can't go back to the protocol account itself
+    let protocol = setup.protocol.protocol;
+    let ix = close(&setup, &setup.protocol.admin, &protocol);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidInput.into()
+    );
+    assert!(state(&setup).is_initialized());
+}
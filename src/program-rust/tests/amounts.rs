@@ -0,0 +1,135 @@
+//! Edge-case amounts: near `u64::MAX`, dust, ratios off 10_000 bps and empty pools.
+
+mod common;
+
+use common::{swap_instruction, Harness, TestPool, TestProtocol, TestSwapAccounts};
+use onesol::error::{LegError, OneSolError};
+use solana_program::{entrypoint::ProgramResult, pubkey::Pubkey};
+use spl_token::error::TokenError;
+
+struct AmountsSetup {
+    harness: Harness,
+    protocol: TestProtocol,
+    user: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+    pools: Vec<TestPool>,
+}
+
+/// A user holding `balance` of mint A, swapping for mint B through pools of the given
+/// A / B reserves.
+fn setup(balance: u64, reserves: &[(u64, u64)]) -> AmountsSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pools = reserves
+        .iter()
+        .map(|(reserve_a, reserve_b)| harness.create_pool(&mint_a, &mint_b, *reserve_a, *reserve_b))
+        .collect();
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, balance);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    AmountsSetup {
+        harness,
+        protocol,
+        user,
+        source,
+        destination,
+        pools,
+    }
+}
+
+impl AmountsSetup {
+    /// Swaps `amount_in` through the pools, one leg of the given ratio each.
+    fn swap(&mut self, amount_in: u64, minimum_amount_out: u64, ratios: &[u16]) -> ProgramResult {
+        let legs: Vec<_> = self
+            .pools
+            .iter()
+            .zip(ratios.iter())
+            .map(|(pool, ratio)| (pool.leg_a_to_b(), *ratio))
+            .collect();
+        let ix = swap_instruction(
+            &self.harness.program_id,
+            &self.protocol,
+            &TestSwapAccounts {
+                user: self.user,
+                source: self.source,
+                destination: self.destination,
+            },
+            amount_in,
+            minimum_amount_out,
+            &legs,
+        );
+        self.harness.process(&ix)
+    }
+}
+
+#[test]
+fn test_swap_amount_in_near_max() {
+    let amount_in = u64::MAX - 1_000_000;
+    let mut setup = setup(amount_in, &[(1_000_000, 1_000_000)]);
+    setup.swap(amount_in, 999_999, &[10_000]).unwrap();
+
+    // the pool reserve reaches u64::MAX, the quote computes in u128
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.pools[0].token_a), u64::MAX);
+    assert_eq!(harness.token_balance(&setup.destination), 999_999);
+    assert_eq!(harness.token_balance(&setup.source), 0);
+}
+
+#[test]
+fn test_swap_overflowing_pool_reserve() {
+    let amount_in = u64::MAX - 999_999;
+    let mut setup = setup(amount_in, &[(1_000_000, 1_000_000)]);
+    assert_eq!(
+        setup.swap(amount_in, 0, &[10_000]).unwrap_err(),
+        LegError::new(0, 0, &TokenError::Overflow.into()).into()
+    );
+}
+
+#[test]
+fn test_swap_dust_amount() {
+    // a single token buys nothing of a deep pool
+    let mut setup = setup(10, &[(1_000_000, 1_000_000)]);
+    setup.swap(1, 0, &[10_000]).unwrap();
+    assert_eq!(setup.harness.token_balance(&setup.source), 9);
+    assert_eq!(setup.harness.token_balance(&setup.destination), 0);
+
+    assert_eq!(
+        setup.swap(1, 1, &[10_000]).unwrap_err(),
+        LegError::new(0, 0, &OneSolError::ExceededSlippage.into()).into()
+    );
+}
+
+#[test]
+fn test_swap_leg_into_empty_pool() {
+    let mut setup = setup(100_000, &[(1_000_000, 1_000_000), (1_000_000, 0)]);
+    assert_eq!(
+        setup.swap(10_000, 0, &[5_000, 5_000]).unwrap_err(),
+        LegError::new(1, 0, &OneSolError::ZeroTradingTokens.into()).into()
+    );
+}
+
+#[test]
+fn test_swap_ratios_off_10_000_bps() {
+    let mut setup = setup(100_000, &[(1_000_000, 1_000_000), (1_000_000, 1_000_000)]);
+    for ratios in [[5_000, 4_999], [5_000, 5_001], [u16::MAX, 0]].iter() {
+        assert_eq!(
+            setup.swap(10_000, 0, ratios).unwrap_err(),
+            OneSolError::RatioSumInvalid.into()
+        );
+    }
+    assert_eq!(setup.harness.token_balance(&setup.source), 100_000);
+}
+
+#[test]
+fn test_swap_dust_split() {
+    // the first leg's share rounds down to nothing, skipping it
+    let mut setup = setup(100_000, &[(1_000_000, 1_000_000), (1_000, 1_000)]);
+    setup.swap(1, 0, &[5_000, 5_000]).unwrap();
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.pools[0].token_a), 1_000_000);
+    assert_eq!(harness.token_balance(&setup.pools[1].token_a), 1_001);
+    assert_eq!(harness.token_balance(&setup.source), 99_999);
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use common::{Harness, TestPool};
+use onesol::{
+    error::OneSolError,
+    instruction::{BatchFillOrders, DexConfig, DexConfigs, OneSolInstruction},
+    processor::Processor,
+    state::{Order, OrderKind},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+
+struct FillSetup {
+    harness: Harness,
+    pool: TestPool,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    batch_authority: Pubkey,
+    batch_nonce: u8,
+    batch_source: Pubkey,
+    batch_destination: Pubkey,
+    keeper: Pubkey,
+}
+
+/// Accounts of a limit order in the batch, escrowed.
+struct TestOrder {
+    order: Pubkey,
+    order_authority: Pubkey,
+    escrow: Pubkey,
+    destination: Pubkey,
+}
+
+/// A keeper batching limit orders selling mint A for mint B through a pool of the pair.
+fn setup() -> FillSetup {
+    let mut harness = Harness::new();
+    let program_id = harness.program_id;
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let batch_source = harness.create_token_account(&mint_a, &Pubkey::default(), 0);
+    let (batch_authority, batch_nonce) =
+        Processor::find_authority_address(&program_id, &batch_source);
+    harness.set_token_owner(&batch_source, &batch_authority);
+    let batch_destination = harness.create_token_account(&mint_b, &batch_authority, 0);
+    let keeper = harness.create_token_account(&mint_a, &Pubkey::new_unique(), 0);
+    FillSetup {
+        harness,
+        pool,
+        mint_a,
+        mint_b,
+        batch_authority,
+        batch_nonce,
+        batch_source,
+        batch_destination,
+        keeper,
+    }
+}
+
+/// Places a limit order selling `amount_in` of mint A for at least 95% of as much mint B.
+fn add_order(setup: &mut FillSetup, amount_in: u64) -> TestOrder {
+    let program_id = setup.harness.program_id;
+    let owner = Pubkey::new_unique();
+    let order = Pubkey::new_unique();
+    let (order_authority, nonce) = Processor::find_authority_address(&program_id, &order);
+    let escrow = setup
+        .harness
+        .create_token_account(&setup.mint_a, &order_authority, amount_in);
+    let destination = setup.harness.create_token_account(&setup.mint_b, &owner, 0);
+    let mut data = vec![0u8; Order::LEN];
+    Order {
+        version: 1,
+        nonce,
+        kind: OrderKind::Limit,
+        owner,
+        token_program_id: spl_token::id(),
+        source_mint: setup.mint_a,
+        destination_mint: setup.mint_b,
+        escrow,
+        destination,
+        amount_in,
+        expected_amount_out: amount_in,
+        max_deviation_bps: 500,
+        num_slices: 1,
+        slice_interval: 0,
+        start_time: 0,
+        filled_amount_in: 0,
+        filled_amount_out: 0,
+        slices_filled: 0,
+        oracle: Pubkey::default(),
+        trigger_price: 0,
+        expires_at_slot: u64::MAX,
+        keeper_fee_bps: 0,
+        keeper_fee_flat: 0,
+        page: Pubkey::default(),
+    }
+    .pack_into_slice(&mut data);
+    setup.harness.add_account(order, program_id, data);
+    TestOrder {
+        order,
+        order_authority,
+        escrow,
+        destination,
+    }
+}
+
+fn batch_fill(setup: &FillSetup, orders: &[&TestOrder]) -> Instruction {
+    let leg = setup.pool.leg_a_to_b();
+    let mut configs = vec![];
+    DexConfig::new_dex_config(0, leg.len(), 10_000)
+        .pack(&mut configs)
+        .unwrap();
+    let data = OneSolInstruction::BatchFillOrders(BatchFillOrders {
+        nonce: setup.batch_nonce,
+        num_orders: orders.len() as u8,
+        dex_configs: DexConfigs::new(&configs).unwrap(),
+    })
+    .pack();
+    let mut accounts = vec![
+        AccountMeta::new_readonly(setup.batch_authority, false),
+        AccountMeta::new(setup.batch_source, false),
+        AccountMeta::new(setup.batch_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(setup.keeper, false),
+    ];
+    for order in orders.iter() {
+        accounts.push(AccountMeta::new(order.order, false));
+        accounts.push(AccountMeta::new_readonly(order.order_authority, false));
+        accounts.push(AccountMeta::new(order.escrow, false));
+        accounts.push(AccountMeta::new(order.destination, false));
+    }
+    accounts.extend(leg);
+    Instruction {
+        program_id: setup.harness.program_id,
+        accounts,
+        data,
+    }
+}
+
+#[test]
+fn test_batch_fill_orders() {
+    let mut setup = setup();
+    let first = add_order(&mut setup, 10_000);
+    let second = add_order(&mut setup, 30_000);
+    let ix = batch_fill(&setup, &[&first, &second]);
+    setup.harness.process(&ix).unwrap();
+
+    // the route output shared pro-rata to the inputs
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&first.escrow), 0);
+    assert_eq!(harness.token_balance(&second.escrow), 0);
+    assert_eq!(harness.token_balance(&first.destination), 9_615);
+    assert_eq!(harness.token_balance(&second.destination), 28_846);
+    assert_eq!(harness.token_balance(&setup.batch_destination), 0);
+}
+
+#[test]
+fn test_batch_fill_orders_rejects_a_repeated_order() {
+    let mut setup = setup();
+    let order = add_order(&mut setup, 10_000);
+    let ix = batch_fill(&setup, &[&order, &order]);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidInput.into()
+    );
+    assert_eq!(setup.harness.token_balance(&order.escrow), 10_000);
+}
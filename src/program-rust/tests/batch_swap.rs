@@ -0,0 +1,91 @@
+mod common;
+
+use common::{Harness, TestPool};
+use onesol::error::OneSolError;
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{batch_swap, BatchSwapUser, SwapAccounts},
+};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+struct BatchSetup {
+    harness: Harness,
+    accounts: SwapAccounts,
+    pool: TestPool,
+    users: Vec<BatchSwapUser>,
+}
+
+fn setup() -> BatchSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let mut users = vec![];
+    for amount_in in [10_000, 20_000].iter() {
+        let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+        users.push(BatchSwapUser {
+            user_transfer_authority: user,
+            source: harness.create_token_account(&mint_a, &user, 100_000),
+            destination: harness.create_token_account(&mint_b, &user, 0),
+            amount_in: *amount_in,
+            minimum_amount_out: amount_in * 9 / 10,
+            trailing_accounts: vec![],
+        });
+    }
+    BatchSetup {
+        harness,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: Pubkey::default(),
+            protocol_token: protocol.token,
+            source: Pubkey::default(),
+            destination: Pubkey::default(),
+            token_program_id: spl_token::id(),
+        },
+        pool,
+        users,
+    }
+}
+
+fn batch_instruction(setup: &BatchSetup) -> Instruction {
+    let leg = DexLeg {
+        dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+        accounts: setup.pool.leg_a_to_b(),
+    };
+    batch_swap(
+        &setup.harness.program_id,
+        &setup.accounts,
+        &setup.users,
+        &[(leg, 10_000)],
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_batch_swap() {
+    let mut setup = setup();
+    let ix = batch_instruction(&setup);
+    setup.harness.process(&ix).unwrap();
+
+    // the second swap prices against the pool the first one left
+    let harness = &setup.harness;
+    let users = &setup.users;
+    assert_eq!(harness.token_balance(&users[0].source), 90_000);
+    assert_eq!(harness.token_balance(&users[0].destination), 9_900);
+    assert_eq!(harness.token_balance(&users[1].source), 80_000);
+    assert_eq!(harness.token_balance(&users[1].destination), 19_225);
+    assert_eq!(harness.token_balance(&setup.accounts.protocol_token), 0);
+}
+
+#[test]
+fn test_batch_swap_fails_as_a_whole() {
+    let mut setup = setup();
+    setup.users[1].destination = setup.accounts.protocol_token;
+    let ix = batch_instruction(&setup);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::IncorrectSwapAccount.into()
+    );
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use common::{Harness, TestPool};
+use onesol::{error::OneSolError, processor::Processor, state::SwapCommitment};
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{reveal_swap, route_commitment, SwapAccounts},
+};
+use solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+
+const SALT: [u8; 32] = [42u8; 32];
+
+struct RevealSetup {
+    harness: Harness,
+    accounts: SwapAccounts,
+    pool: TestPool,
+}
+
+fn setup() -> RevealSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    RevealSetup {
+        harness,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        pool,
+    }
+}
+
+fn legs(setup: &RevealSetup) -> Vec<(DexLeg, u16)> {
+    vec![(
+        DexLeg {
+            dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+            accounts: setup.pool.leg_a_to_b(),
+        },
+        10_000,
+    )]
+}
+
+/// Adds the commitment a `CommitSwap` at `slot` of a swap of `amount_in` would create.
+fn commit(setup: &mut RevealSetup, amount_in: u64, slot: u64) -> Pubkey {
+    let user = setup.accounts.user_transfer_authority;
+    let legs = legs(setup);
+    let commitment = route_commitment(&user, &SALT, amount_in, 9_800, &legs).to_bytes();
+    let (address, bump_seed) =
+        Processor::find_swap_commitment_address(&setup.harness.program_id, &user, &commitment);
+    let mut data = vec![0u8; SwapCommitment::LEN];
+    SwapCommitment {
+        version: 1,
+        bump_seed,
+        user,
+        commitment,
+        slot,
+    }
+    .pack_into_slice(&mut data);
+    let program_id = setup.harness.program_id;
+    setup.harness.add_account(address, program_id, data)
+}
+
+fn reveal(setup: &RevealSetup, amount_in: u64) -> Instruction {
+    reveal_swap(
+        &setup.harness.program_id,
+        &setup.accounts,
+        &SALT,
+        amount_in,
+        9_800,
+        &legs(setup),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_reveal_swap() {
+    let mut setup = setup();
+    let commitment = commit(&mut setup, 10_000, 5);
+    setup.harness.set_slot(6);
+    let ix = reveal(&setup, 10_000);
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.accounts.source), 90_000);
+    assert_eq!(harness.token_balance(&setup.accounts.destination), 9_900);
+    assert_eq!(harness.account(&commitment).lamports, 0);
+    assert_eq!(
+        harness
+            .account(&setup.accounts.user_transfer_authority)
+            .lamports,
+        2_000_000_000
+    );
+}
+
+#[test]
+fn test_reveal_swap_in_commit_slot() {
+    let mut setup = setup();
+    commit(&mut setup, 10_000, 5);
+    setup.harness.set_slot(5);
+    let ix = reveal(&setup, 10_000);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::CommitmentTooRecent.into()
+    );
+}
+
+#[test]
+fn test_reveal_swap_other_than_committed() {
+    let mut setup = setup();
+    let commitment = commit(&mut setup, 10_000, 5);
+    setup.harness.set_slot(6);
+    let mut ix = reveal(&setup, 20_000);
+    // the commitment of the swap actually committed to
+    ix.accounts[7].pubkey = commitment;
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::CommitmentMismatch.into()
+    );
+}
@@ -0,0 +1,1299 @@
+//! In-process integration harness: runs the 1sol processor against in-memory accounts.
+//!
+//! Cross-program invocations are dispatched by syscall stubs to the spl-token processor
+//! and to an emulated Token-Swap program. It prices swaps through spl-token-swap pools
+//! with the pool's fees and curve, and through the pools of [Harness::create_pool] on a
+//! fee-less constant product curve.
+//!
+//! Account dumps in the layout of `solana account --output json` load with
+//! [Harness::load_account_dumps]. [Harness::replay] runs a transaction recorded in that
+//! layout against them, reporting where the current build diverges from the recording.
+//! The recordings in the tree are synthetic ones of [Harness::record].
+//!
+//! [Harness::process_metered] counts the syscalls an instruction makes, charged at the
+//! runtime's compute costs. It does not see the BPF instructions executed in between, so
+//! its units are a lower bound of the units consumed on a cluster, meant to be compared
+//! across revisions rather than with the cluster's.
+//!
+//! [Harness::return_data] reads back the data the last instruction returned.
+
+#![allow(dead_code)]
+
+use onesol::{
+    bridge,
+    instruction::{DexConfig, OneSolInstruction},
+    lending, liquid_staking,
+    processor::Processor,
+    state::{
+        GlobalConfig, InventoryPair, LogLevel, OneSolState, StakeAccount, StakePool, StakeTier,
+        STAKE_TIERS,
+    },
+};
+use onesol_client::{
+    dex::SplTokenSwapPool,
+    instruction::StakeVaults,
+    quote::{Quote, SplTokenSwapQuoter},
+};
+use serde_json::{json, Value};
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::{ProgramResult, SUCCESS},
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+    sysvar::{
+        self,
+        instructions::{BorrowedAccountMeta, BorrowedInstruction},
+    },
+};
+use spl_token::state::{Account, AccountState, Mint};
+use std::{
+    cell::{Cell, RefCell},
+    convert::TryInto,
+    fs,
+    path::Path,
+    str::FromStr,
+    sync::Once,
+};
+
+/// Program id of the mock Token-Swap program: the mainnet spl-token-swap, so legs through
+/// it pass the program checks of spl-token-swap legs.
+pub fn mock_swap_program_id() -> Pubkey {
+    Pubkey::from_str("SwapsVeCiPHMUAtzQWZw7RjsKjgCjhwU55QGu4U1Szw").unwrap()
+}
+
+/// Program id of the mock flash swap borrower, transferring the amount of its
+/// instruction data from its first account to its second, signed by its third.
+pub fn mock_flash_borrower_id() -> Pubkey {
+    Pubkey::new_from_array([8u8; 32])
+}
+
+/// Program id of the mock Wormhole token bridge, whose `TransferNative` moves the amount
+/// of its instruction data from its first account into its second, the custody, as the
+/// delegate its third account is.
+pub fn mock_token_bridge_id() -> Pubkey {
+    Pubkey::new_from_array([9u8; 32])
+}
+
+/// Program id of the mock token-lending program, whose
+/// `DepositReserveLiquidityAndObligationCollateral` moves the amount of its instruction
+/// data from its first account, the source liquidity, into its fourth, the reserve
+/// liquidity supply, signed by its thirteenth. No collateral is minted.
+pub fn mock_lending_id() -> Pubkey {
+    Pubkey::new_from_array([10u8; 32])
+}
+
+/// Program id of the mock SPL stake pool, whose `DepositSol` moves the lamports of its
+/// instruction data from its fourth account into its third, the reserve, crediting as
+/// many pool tokens to its fifth.
+pub fn mock_stake_pool_id() -> Pubkey {
+    Pubkey::new_from_array([11u8; 32])
+}
+
+/// Address of the spl-token-swap program.
+pub const SPL_TOKEN_SWAP_PROGRAM_ID: &str = "SwaPpA9LAaLfeLi3a68M4DjnLqgtticKg6CnyNwgAC8";
+
+/// Error of the emulated Token-Swap program when a swap returns less than its minimum.
+pub const MOCK_SWAP_EXCEEDED_SLIPPAGE: u32 = 16;
+
+/// Error of the emulated Token-Swap program when the host fee account isn't of the
+/// pool mint.
+pub const SWAP_ERROR_INCORRECT_POOL_MINT: u32 = 11;
+
+/// Offsets of the owner trade fee and host fee fractions in a spl-token-swap account.
+const OWNER_TRADE_FEE_OFFSET: usize = 243;
+const HOST_FEE_OFFSET: usize = 275;
+
+/// Compute units charged per cross-program invocation.
+pub const INVOKE_COMPUTE_UNITS: u64 = 1_000;
+
+/// Compute units charged per logged message.
+pub const LOG_COMPUTE_UNITS: u64 = 100;
+
+/// Syscalls made by an instruction, see [Harness::process_metered].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SyscallUsage {
+    /// Cross-program invocations, including the nested ones
+    pub invokes: u64,
+    /// Logged messages, not counting the `cu-telemetry` ones
+    pub logs: u64,
+}
+
+impl SyscallUsage {
+    /// Compute units charged for the syscalls.
+    pub fn compute_units(&self) -> u64 {
+        self.invokes * INVOKE_COMPUTE_UNITS + self.logs * LOG_COMPUTE_UNITS
+    }
+}
+
+thread_local! {
+    static SYSCALL_USAGE: Cell<SyscallUsage> = Cell::new(SyscallUsage::default());
+    /// Program running the instruction, signing for the addresses derived from it
+    static CALLER: Cell<Pubkey> = Cell::new(Pubkey::default());
+    /// Slot of the Clock sysvar
+    static SLOT: Cell<u64> = const { Cell::new(0) };
+    /// Data the last instruction returned
+    static RETURN_DATA: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
+fn meter(charge: impl FnOnce(&mut SyscallUsage)) {
+    SYSCALL_USAGE.with(|usage| {
+        let mut current = usage.get();
+        charge(&mut current);
+        usage.set(current);
+    });
+}
+
+struct TestSyscallStubs;
+
+impl SyscallStubs for TestSyscallStubs {
+    fn sol_log(&self, message: &str) {
+        // telemetry lines measure the program rather than being part of it
+        if !message.starts_with("cu-telemetry:") {
+            meter(|usage| usage.logs += 1);
+        }
+        println!("{}", message);
+    }
+
+    fn sol_log_compute_units(&self) {}
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        RETURN_DATA.with(|return_data| *return_data.borrow_mut() = Some(data.to_vec()));
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = Clock {
+            slot: SLOT.with(Cell::get),
+            ..Clock::default()
+        };
+        // the runtime writes the sysvar where the program asks, as solana-program-test does
+        #[allow(clippy::cast_ptr_alignment)]
+        unsafe {
+            *(var_addr as *mut Clock) = clock;
+        }
+        SUCCESS
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        meter(|usage| usage.invokes += 1);
+        let caller = CALLER.with(Cell::get);
+        let signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &caller))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| ProgramError::InvalidSeeds)?;
+        let accounts = instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                let mut info = account_infos
+                    .iter()
+                    .find(|info| *info.key == meta.pubkey)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?
+                    .clone();
+                // as the runtime, only grant signatures of the transaction or of the seeds
+                if meta.is_signer && !info.is_signer && !signers.contains(info.key) {
+                    return Err(ProgramError::MissingRequiredSignature);
+                }
+                info.is_signer = meta.is_signer;
+                info.is_writable = meta.is_writable;
+                Ok(info)
+            })
+            .collect::<Result<Vec<_>, ProgramError>>()?;
+        if instruction.program_id == spl_token::id() {
+            spl_token::processor::Processor::process(
+                &instruction.program_id,
+                &accounts,
+                &instruction.data,
+            )
+        } else if instruction.program_id == mock_flash_borrower_id() {
+            let data: [u8; 8] = instruction
+                .data
+                .as_slice()
+                .try_into()
+                .map_err(|_| ProgramError::InvalidInstructionData)?;
+            token_transfer(
+                &accounts[0],
+                &accounts[1],
+                &accounts[2],
+                u64::from_le_bytes(data),
+            )
+        } else if instruction.program_id == system_program::id() {
+            process_system_transfer(&accounts, &instruction.data)
+        } else if instruction.program_id == mock_token_bridge_id() {
+            process_bridge_transfer(&accounts, &instruction.data)
+        } else if instruction.program_id == mock_lending_id() {
+            process_lending_deposit(&accounts, &instruction.data)
+        } else if instruction.program_id == mock_stake_pool_id() {
+            process_stake_pool_deposit_sol(&accounts, &instruction.data)
+        } else if accounts
+            .first()
+            .is_some_and(|swap| *swap.owner == instruction.program_id)
+        {
+            process_token_swap(&instruction.program_id, &accounts, &instruction.data)
+        } else {
+            Err(ProgramError::IncorrectProgramId)
+        }
+    }
+}
+
+/// Emulates a Token-Swap `Swap` of the program `program_id`. Owner fees are kept in the
+/// pool rather than minted as pool tokens, which leaves the swapped amounts unchanged,
+/// but for the host's share of them, minted one pool token per SOURCE token into the
+/// host fee account if passed.
+fn process_token_swap(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if data.len() != 17 || data[0] != 1 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let amount_in = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    let minimum_amount_out = u64::from_le_bytes(data[9..17].try_into().unwrap());
+    let swap = &accounts[0];
+    let swap_authority = &accounts[1];
+    let user_transfer_authority = &accounts[2];
+    let source = &accounts[3];
+    let swap_source = &accounts[4];
+    let swap_destination = &accounts[5];
+    let destination = &accounts[6];
+
+    let amount_out = if *program_id == mock_swap_program_id() {
+        let reserve_in = Account::unpack(&swap_source.data.borrow())?.amount as u128;
+        let reserve_out = Account::unpack(&swap_destination.data.borrow())?.amount as u128;
+        (reserve_out * amount_in as u128 / (reserve_in + amount_in as u128)) as u64
+    } else {
+        let pool = SplTokenSwapPool::unpack(swap.key, program_id, &swap.data.borrow())?;
+        let (token_a, token_b) = if *swap_source.key == pool.token_a {
+            (swap_source, swap_destination)
+        } else {
+            (swap_destination, swap_source)
+        };
+        let quoter = SplTokenSwapQuoter::unpack(
+            pool,
+            &swap.data.borrow(),
+            &token_a.data.borrow(),
+            &token_b.data.borrow(),
+        )?;
+        let source_mint = Account::unpack(&swap_source.data.borrow())?.mint;
+        quoter
+            .quote(&source_mint, amount_in)
+            .ok_or(ProgramError::InvalidArgument)?
+    };
+    if amount_out < minimum_amount_out {
+        return Err(ProgramError::Custom(MOCK_SWAP_EXCEEDED_SLIPPAGE));
+    }
+    if let Some(host_fee_account) = accounts.get(10) {
+        let pool_mint = accounts[7].key;
+        let mut host = Account::unpack(&host_fee_account.data.borrow())?;
+        if host.mint != *pool_mint {
+            return Err(ProgramError::Custom(SWAP_ERROR_INCORRECT_POOL_MINT));
+        }
+        let fees = |offset: usize| {
+            let data = swap.data.borrow();
+            let value = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+            (value(offset), value(offset + 8))
+        };
+        let (owner_numerator, owner_denominator) = fees(OWNER_TRADE_FEE_OFFSET);
+        let (host_numerator, host_denominator) = fees(HOST_FEE_OFFSET);
+        let owner_fee = amount_in * owner_numerator / owner_denominator;
+        host.amount += owner_fee * host_numerator / host_denominator;
+        host.pack_into_slice(&mut host_fee_account.data.borrow_mut());
+    }
+
+    let mut swap_authority = swap_authority.clone();
+    swap_authority.is_signer = true;
+    token_transfer(source, swap_source, user_transfer_authority, amount_in)?;
+    token_transfer(swap_destination, destination, &swap_authority, amount_out)
+}
+
+/// Emulates a System program `Transfer`, the only System instruction the program sends
+/// that the harness can run.
+fn process_system_transfer(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if data.len() != 12 || data[..4] != 2u32.to_le_bytes() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let lamports = u64::from_le_bytes(data[4..].try_into().unwrap());
+    if !accounts[0].is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let from_lamports = accounts[0]
+        .lamports()
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    **accounts[0].lamports.borrow_mut() = from_lamports;
+    **accounts[1].lamports.borrow_mut() += lamports;
+    Ok(())
+}
+
+/// Emulates a token bridge `TransferNative`, locking the tokens in the custody account.
+fn process_bridge_transfer(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if data.len() != bridge::TRANSFER_DATA_LEN || data[0] != bridge::TRANSFER_NATIVE {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let amount = u64::from_le_bytes(data[5..13].try_into().unwrap());
+    let mut authority_signer = accounts[2].clone();
+    if *authority_signer.key != bridge::authority_signer(&mock_token_bridge_id()) {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    authority_signer.is_signer = true;
+    token_transfer(&accounts[0], &accounts[1], &authority_signer, amount)
+}
+
+/// Emulates a token-lending `DepositReserveLiquidityAndObligationCollateral`, supplying
+/// the liquidity to the reserve.
+fn process_lending_deposit(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if data.len() != lending::DEPOSIT_DATA_LEN
+        || data[0] != lending::DEPOSIT_RESERVE_LIQUIDITY_AND_OBLIGATION_COLLATERAL
+    {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let amount = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    token_transfer(&accounts[0], &accounts[3], &accounts[12], amount)
+}
+
+/// Emulates a stake pool `DepositSol`, pool tokens being worth a lamport each.
+fn process_stake_pool_deposit_sol(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if data.len() != liquid_staking::DEPOSIT_SOL_DATA_LEN || data[0] != liquid_staking::DEPOSIT_SOL
+    {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let lamports = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    let mut transfer_data = 2u32.to_le_bytes().to_vec();
+    transfer_data.extend_from_slice(&lamports.to_le_bytes());
+    process_system_transfer(&[accounts[3].clone(), accounts[2].clone()], &transfer_data)?;
+    let mut pool_tokens = Account::unpack(&accounts[4].data.borrow())?;
+    pool_tokens.amount += lamports;
+    Account::pack(pool_tokens, &mut accounts[4].data.borrow_mut())
+}
+
+fn token_transfer<'a>(
+    source: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    // a CPI of the Token-Swap program on a cluster
+    meter(|usage| usage.invokes += 1);
+    let ix = spl_token::instruction::transfer(
+        &spl_token::id(),
+        source.key,
+        destination.key,
+        authority.key,
+        &[],
+        amount,
+    )?;
+    spl_token::processor::Processor::process(
+        &spl_token::id(),
+        &[source.clone(), destination.clone(), authority.clone()],
+        &ix.data,
+    )
+}
+
+/// Renders `error` as the runtime reports the `InstructionError` of a transaction.
+fn instruction_error(error: &ProgramError) -> Value {
+    match error {
+        ProgramError::Custom(code) => json!({ "Custom": code }),
+        error => json!(format!("{:?}", error)),
+    }
+}
+
+/// Length of a dex config in the data of a `Swap` recorded before leg ratios moved to
+/// basis points: the dex type, the account count and a u8 ratio.
+const LEGACY_DEX_CONFIG_LEN: usize = 3;
+
+/// Returns the data of a recorded instruction as the current build reads it.
+///
+/// A `Swap` recorded before leg ratios moved to basis points has 3-byte dex configs, each
+/// leg swapping `ratio` times the amount. It is re-encoded with each leg keeping its share
+/// of the ratios in basis points, the last one taking the rounding remainder, which
+/// leaves a single leg swapping the whole amount as before.
+fn upgrade_recorded_data(data: Vec<u8>) -> Vec<u8> {
+    if OneSolInstruction::unpack(&data).is_ok() {
+        return data;
+    }
+    let legacy_swap = match data.split_first() {
+        Some((1, rest)) if rest.len() > 16 => Some(rest.split_at(16)),
+        _ => None,
+    };
+    let (amounts, configs) = match legacy_swap {
+        Some((amounts, [count, configs @ ..]))
+            if configs.len() == *count as usize * LEGACY_DEX_CONFIG_LEN =>
+        {
+            (amounts, configs)
+        }
+        _ => return data,
+    };
+    let total_ratio: u32 = configs
+        .chunks(LEGACY_DEX_CONFIG_LEN)
+        .map(|config| config[2] as u32)
+        .sum();
+    if total_ratio == 0 {
+        return data;
+    }
+    let mut upgraded = vec![1];
+    upgraded.extend_from_slice(amounts);
+    upgraded.push((configs.len() / LEGACY_DEX_CONFIG_LEN) as u8);
+    let mut remaining_bps = 10_000;
+    for (i, config) in configs.chunks(LEGACY_DEX_CONFIG_LEN).enumerate() {
+        let bps = if (i + 1) * LEGACY_DEX_CONFIG_LEN == configs.len() {
+            remaining_bps
+        } else {
+            config[2] as u32 * 10_000 / total_ratio
+        };
+        remaining_bps -= bps;
+        DexConfig::new_dex_config(config[0], config[1] as usize, bps as u16)
+            .pack(&mut upgraded)
+            .unwrap();
+    }
+    upgraded
+}
+
+/// An account of the in-memory ledger.
+pub struct TestAccount {
+    /// Address
+    pub key: Pubkey,
+    /// Owner program
+    pub owner: Pubkey,
+    /// Lamports
+    pub lamports: u64,
+    /// Data
+    pub data: Vec<u8>,
+}
+
+/// Returns the data of an spl-token-swap constant product pool charging 0.25% trade and
+/// 0.05% owner fees.
+pub fn spl_token_swap_pool_data(
+    bump_seed: u8,
+    token_a: &Pubkey,
+    token_b: &Pubkey,
+    pool_mint: &Pubkey,
+    mints: (&Pubkey, &Pubkey),
+    fee_account: &Pubkey,
+) -> Vec<u8> {
+    let mut data = vec![1u8, 1, bump_seed];
+    for key in [
+        &spl_token::id(),
+        token_a,
+        token_b,
+        pool_mint,
+        mints.0,
+        mints.1,
+        fee_account,
+    ]
+    .iter()
+    {
+        data.extend_from_slice(key.as_ref());
+    }
+    for value in [25u64, 10_000, 5, 10_000, 0, 1, 0, 1].iter() {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.resize(324, 0);
+    data
+}
+
+/// Mock Token-Swap pool.
+pub struct TestPool {
+    /// Pool account
+    pub swap: Pubkey,
+    /// Pool authority
+    pub authority: Pubkey,
+    /// Token A reserve
+    pub token_a: Pubkey,
+    /// Token B reserve
+    pub token_b: Pubkey,
+    /// Pool token mint
+    pub pool_mint: Pubkey,
+    /// Pool fee account
+    pub fee_account: Pubkey,
+}
+
+impl TestPool {
+    /// Dex accounts of a leg swapping A to B through the pool.
+    pub fn leg_a_to_b(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(self.swap, false),
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new(self.token_a, false),
+            AccountMeta::new(self.token_b, false),
+            AccountMeta::new(self.pool_mint, false),
+            AccountMeta::new(self.fee_account, false),
+            AccountMeta::new_readonly(mock_swap_program_id(), false),
+        ]
+    }
+}
+
+/// 1sol protocol account with its DESTINATION token account.
+pub struct TestProtocol {
+    /// Protocol account
+    pub protocol: Pubkey,
+    /// Protocol authority
+    pub authority: Pubkey,
+    /// Protocol DESTINATION token account
+    pub token: Pubkey,
+    /// Admin setting the fee rate
+    pub admin: Pubkey,
+}
+
+/// In-memory ledger the 1sol program runs against.
+pub struct Harness {
+    /// 1sol program id
+    pub program_id: Pubkey,
+    /// Accounts of the ledger
+    pub accounts: Vec<TestAccount>,
+}
+
+impl Harness {
+    /// Creates an empty ledger, installing the syscall stubs once per test binary.
+    pub fn new() -> Self {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(TestSyscallStubs));
+        });
+        let program_id = Pubkey::new_unique();
+        let mut harness = Harness {
+            program_id,
+            accounts: vec![],
+        };
+        for program in [
+            program_id,
+            spl_token::id(),
+            mock_swap_program_id(),
+            mock_flash_borrower_id(),
+            mock_token_bridge_id(),
+            mock_lending_id(),
+            mock_stake_pool_id(),
+            system_program::id(),
+        ]
+        .iter()
+        {
+            harness.add_program(program);
+        }
+        harness
+    }
+
+    /// Adds the executable account of `program_id`. Its instructions run only if they are
+    /// dispatched by the syscall stubs.
+    pub fn add_program(&mut self, program_id: &Pubkey) {
+        self.add_account(*program_id, solana_program::bpf_loader::id(), vec![]);
+    }
+
+    /// Adds an account owned by `owner` holding `data`.
+    pub fn add_account(&mut self, key: Pubkey, owner: Pubkey, data: Vec<u8>) -> Pubkey {
+        self.accounts.push(TestAccount {
+            key,
+            owner,
+            lamports: 1_000_000_000,
+            data,
+        });
+        key
+    }
+
+    /// Loads an account dumped by `solana account <ADDRESS> --output json --output-file
+    /// <FILE>`, replacing the account of the same address if any.
+    pub fn load_account_dump(&mut self, path: &Path) -> Pubkey {
+        let dump: Value = serde_json::from_slice(&fs::read(path).unwrap())
+            .unwrap_or_else(|e| panic!("invalid dump {}: {}", path.display(), e));
+        let key = Pubkey::from_str(dump["pubkey"].as_str().unwrap()).unwrap();
+        let account = &dump["account"];
+        let owner = Pubkey::from_str(account["owner"].as_str().unwrap()).unwrap();
+        let data = base64::decode(account["data"][0].as_str().unwrap()).unwrap();
+        self.accounts.retain(|account| account.key != key);
+        self.add_account(key, owner, data);
+        key
+    }
+
+    /// Loads the account dumps of the `.json` files of `dir`, see
+    /// [Harness::load_account_dump].
+    pub fn load_account_dumps(&mut self, dir: &Path) -> Vec<Pubkey> {
+        let mut paths: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| self.load_account_dump(path))
+            .collect()
+    }
+
+    /// Writes the account `key` to `dir` as `solana account --output json` dumps it.
+    pub fn dump_account(&self, dir: &Path, key: &Pubkey) {
+        let account = self.account(key);
+        let dump = json!({
+            "pubkey": key.to_string(),
+            "account": {
+                "lamports": account.lamports,
+                "data": [base64::encode(&account.data), "base64"],
+                "owner": account.owner.to_string(),
+                "executable": false,
+                "rentEpoch": 0,
+            },
+        });
+        fs::write(dir.join(format!("{}.json", key)), dump.to_string()).unwrap();
+    }
+
+    /// Runs `instructions` as a transaction at `slot`, recording it into `dir` as
+    /// [Harness::replay] reads it: the address of the program under `program_id`, the
+    /// dumps of the accounts of the transaction before it under `accounts`, and the
+    /// transaction under `transaction.json` as `getTransaction` returns it.
+    pub fn record(&mut self, dir: &Path, instructions: &[Instruction], slot: u64) {
+        let message = Message::new(instructions, None);
+        let accounts_dir = dir.join("accounts");
+        fs::create_dir_all(&accounts_dir).unwrap();
+        fs::write(dir.join("program_id"), self.program_id.to_string()).unwrap();
+        for key in message.account_keys.iter() {
+            let recorded = self.accounts.iter().any(|account| {
+                account.key == *key && account.owner != solana_program::bpf_loader::id()
+            });
+            if recorded {
+                self.dump_account(&accounts_dir, key);
+            }
+        }
+
+        self.set_slot(slot);
+        let mut err = Value::Null;
+        for (index, instruction) in instructions.iter().enumerate() {
+            if let Err(error) = self.process(instruction) {
+                err = json!({ "InstructionError": [index, instruction_error(&error)] });
+                break;
+            }
+        }
+        let post_token_balances: Vec<Value> = message
+            .account_keys
+            .iter()
+            .enumerate()
+            .filter_map(|(index, key)| {
+                let account = self.accounts.iter().find(|account| account.key == *key)?;
+                if account.owner != spl_token::id() || account.data.len() != Account::LEN {
+                    return None;
+                }
+                let token = Account::unpack(&account.data).ok()?;
+                Some(json!({
+                    "accountIndex": index,
+                    "mint": token.mint.to_string(),
+                    "uiTokenAmount": { "amount": token.amount.to_string() },
+                }))
+            })
+            .collect();
+        let transaction = json!({
+            "slot": slot,
+            "transaction": {
+                "message": {
+                    "header": {
+                        "numRequiredSignatures": message.header.num_required_signatures,
+                        "numReadonlySignedAccounts": message.header.num_readonly_signed_accounts,
+                        "numReadonlyUnsignedAccounts":
+                            message.header.num_readonly_unsigned_accounts,
+                    },
+                    "accountKeys": message
+                        .account_keys
+                        .iter()
+                        .map(Pubkey::to_string)
+                        .collect::<Vec<_>>(),
+                    "instructions": message
+                        .instructions
+                        .iter()
+                        .map(|instruction| json!({
+                            "programIdIndex": instruction.program_id_index,
+                            "accounts": instruction.accounts,
+                            "data": bs58::encode(&instruction.data).into_string(),
+                        }))
+                        .collect::<Vec<_>>(),
+                },
+            },
+            "meta": {
+                "err": err,
+                "postTokenBalances": post_token_balances,
+            },
+        });
+        fs::write(dir.join("transaction.json"), transaction.to_string()).unwrap();
+    }
+
+    /// Replays the transaction recorded in `dir`, see [Harness::record], against the
+    /// current build of the program.
+    ///
+    /// Deploys the program at the address of `dir/program_id` and loads the account dumps
+    /// of `dir/accounts`, then runs the instructions of the program in
+    /// `dir/transaction.json`, the result of `getTransaction` with the `json` encoding.
+    /// Instructions of other programs are skipped, so the dumps must be of the accounts
+    /// as the program instructions found them. Swaps recorded before leg ratios moved to
+    /// basis points are re-encoded first. Returns how the replay diverges from the
+    /// recorded error and, for a transaction that succeeded, from the recorded token
+    /// balances after it.
+    pub fn replay(&mut self, dir: &Path) -> Vec<String> {
+        let program_id = fs::read_to_string(dir.join("program_id")).unwrap();
+        self.program_id = Pubkey::from_str(program_id.trim()).unwrap();
+        let program_id = self.program_id;
+        if !self
+            .accounts
+            .iter()
+            .any(|account| account.key == program_id)
+        {
+            self.add_program(&program_id);
+        }
+        self.load_account_dumps(&dir.join("accounts"));
+        let path = dir.join("transaction.json");
+        let recorded: Value = serde_json::from_slice(&fs::read(&path).unwrap())
+            .unwrap_or_else(|e| panic!("invalid transaction {}: {}", path.display(), e));
+        self.set_slot(recorded["slot"].as_u64().unwrap());
+
+        let message = &recorded["transaction"]["message"];
+        let header = &message["header"];
+        let num_signed = header["numRequiredSignatures"].as_u64().unwrap() as usize;
+        let num_readonly_signed = header["numReadonlySignedAccounts"].as_u64().unwrap() as usize;
+        let num_readonly_unsigned =
+            header["numReadonlyUnsignedAccounts"].as_u64().unwrap() as usize;
+        let keys: Vec<Pubkey> = message["accountKeys"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|key| Pubkey::from_str(key.as_str().unwrap()).unwrap())
+            .collect();
+        let meta = |index: usize| {
+            let is_signer = index < num_signed;
+            let is_writable = if is_signer {
+                index < num_signed - num_readonly_signed
+            } else {
+                index < keys.len() - num_readonly_unsigned
+            };
+            AccountMeta {
+                pubkey: keys[index],
+                is_signer,
+                is_writable,
+            }
+        };
+
+        let mut err = Value::Null;
+        for (index, instruction) in message["instructions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .enumerate()
+        {
+            let instruction_program_id =
+                keys[instruction["programIdIndex"].as_u64().unwrap() as usize];
+            if instruction_program_id != program_id {
+                continue;
+            }
+            let instruction = Instruction {
+                program_id,
+                accounts: instruction["accounts"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|index| meta(index.as_u64().unwrap() as usize))
+                    .collect(),
+                data: upgrade_recorded_data(
+                    bs58::decode(instruction["data"].as_str().unwrap())
+                        .into_vec()
+                        .unwrap(),
+                ),
+            };
+            if let Err(error) = self.process(&instruction) {
+                err = json!({ "InstructionError": [index, instruction_error(&error)] });
+                break;
+            }
+        }
+
+        let mut divergences = vec![];
+        let recorded_err = &recorded["meta"]["err"];
+        if err != *recorded_err {
+            divergences.push(format!(
+                "error: recorded {}, replayed {}",
+                recorded_err, err
+            ));
+        }
+        if err.is_null() && recorded_err.is_null() {
+            for balance in recorded["meta"]["postTokenBalances"]
+                .as_array()
+                .unwrap()
+                .iter()
+            {
+                let key = keys[balance["accountIndex"].as_u64().unwrap() as usize];
+                let recorded_amount = balance["uiTokenAmount"]["amount"].as_str().unwrap();
+                let amount = self.token_balance(&key);
+                if amount.to_string() != recorded_amount {
+                    divergences.push(format!(
+                        "balance of {}: recorded {}, replayed {}",
+                        key, recorded_amount, amount
+                    ));
+                }
+            }
+        }
+        divergences
+    }
+
+    /// Adds a mint.
+    /// Adds the wrapped SOL mint.
+    pub fn create_native_mint(&mut self) -> Pubkey {
+        let mut data = vec![0u8; Mint::LEN];
+        Mint {
+            mint_authority: COption::None,
+            supply: 0,
+            decimals: 9,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+        .pack_into_slice(&mut data);
+        self.add_account(spl_token::native_mint::id(), spl_token::id(), data)
+    }
+
+    pub fn create_mint(&mut self) -> Pubkey {
+        let mut data = vec![0u8; Mint::LEN];
+        Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            supply: u64::MAX / 2,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+        .pack_into_slice(&mut data);
+        self.add_account(Pubkey::new_unique(), spl_token::id(), data)
+    }
+
+    /// Adds a token account of `mint` owned by `owner` holding `amount`.
+    pub fn create_token_account(&mut self, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+        // wrapped SOL accounts hold their balance in lamports, on top of their rent
+        let native_rent = if *mint == spl_token::native_mint::id() {
+            COption::Some(Rent::default().minimum_balance(Account::LEN))
+        } else {
+            COption::None
+        };
+        let mut data = vec![0u8; Account::LEN];
+        Account {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            is_native: native_rent,
+            ..Account::default()
+        }
+        .pack_into_slice(&mut data);
+        let key = self.add_account(Pubkey::new_unique(), spl_token::id(), data);
+        if let COption::Some(rent) = native_rent {
+            self.accounts.last_mut().unwrap().lamports = rent + amount;
+        }
+        key
+    }
+
+    /// Returns the amount held by the token account `key`.
+    pub fn token_balance(&self, key: &Pubkey) -> u64 {
+        Account::unpack(&self.account(key).data).unwrap().amount
+    }
+
+    /// Makes the spl-token-swap pool `pool` pay hosts `numerator / denominator` of its
+    /// owner trade fees.
+    pub fn set_host_fee(&mut self, pool: &Pubkey, numerator: u64, denominator: u64) {
+        let account = self
+            .accounts
+            .iter_mut()
+            .find(|account| account.key == *pool)
+            .expect("unknown account");
+        account.data[HOST_FEE_OFFSET..HOST_FEE_OFFSET + 8]
+            .copy_from_slice(&numerator.to_le_bytes());
+        account.data[HOST_FEE_OFFSET + 8..HOST_FEE_OFFSET + 16]
+            .copy_from_slice(&denominator.to_le_bytes());
+    }
+
+    /// Sets the amount held by the token account `key`.
+    pub fn set_token_balance(&mut self, key: &Pubkey, amount: u64) {
+        let account = self
+            .accounts
+            .iter_mut()
+            .find(|account| account.key == *key)
+            .expect("unknown account");
+        let mut token = Account::unpack(&account.data).unwrap();
+        token.amount = amount;
+        token.pack_into_slice(&mut account.data);
+    }
+
+    /// Sets the owner of the token account `key`.
+    pub fn set_token_owner(&mut self, key: &Pubkey, owner: &Pubkey) {
+        let account = self
+            .accounts
+            .iter_mut()
+            .find(|account| account.key == *key)
+            .expect("unknown account");
+        let mut token = Account::unpack(&account.data).unwrap();
+        token.owner = *owner;
+        token.pack_into_slice(&mut account.data);
+    }
+
+    /// Returns the account `key`.
+    pub fn account(&self, key: &Pubkey) -> &TestAccount {
+        self.accounts
+            .iter()
+            .find(|account| account.key == *key)
+            .expect("unknown account")
+    }
+
+    /// Adds a mock Token-Swap pool of `mint_a` / `mint_b` holding the given reserves.
+    pub fn create_pool(
+        &mut self,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+        reserve_a: u64,
+        reserve_b: u64,
+    ) -> TestPool {
+        let swap = self.add_account(Pubkey::new_unique(), mock_swap_program_id(), vec![0u8; 1]);
+        let (authority, _) =
+            Pubkey::find_program_address(&[&swap.to_bytes()[..32]], &mock_swap_program_id());
+        let pool_mint = self.create_mint();
+        TestPool {
+            swap,
+            authority,
+            token_a: self.create_token_account(mint_a, &authority, reserve_a),
+            token_b: self.create_token_account(mint_b, &authority, reserve_b),
+            pool_mint,
+            fee_account: self.create_token_account(&pool_mint, &Pubkey::new_unique(), 0),
+        }
+    }
+
+    /// Adds an spl-token-swap pool of `mint_a` / `mint_b` holding the given reserves, see
+    /// [spl_token_swap_pool_data].
+    pub fn create_spl_token_swap_pool(
+        &mut self,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+        reserve_a: u64,
+        reserve_b: u64,
+    ) -> SplTokenSwapPool {
+        let swap_program_id = Pubkey::from_str(SPL_TOKEN_SWAP_PROGRAM_ID).unwrap();
+        if !self
+            .accounts
+            .iter()
+            .any(|account| account.key == swap_program_id)
+        {
+            self.add_program(&swap_program_id);
+        }
+        let address = Pubkey::new_unique();
+        let (authority, bump_seed) =
+            Pubkey::find_program_address(&[&address.to_bytes()[..32]], &swap_program_id);
+        let token_a = self.create_token_account(mint_a, &authority, reserve_a);
+        let token_b = self.create_token_account(mint_b, &authority, reserve_b);
+        let pool_mint = self.create_mint();
+        let fee_account = self.create_token_account(&pool_mint, &Pubkey::new_unique(), 0);
+        let data = spl_token_swap_pool_data(
+            bump_seed,
+            &token_a,
+            &token_b,
+            &pool_mint,
+            (mint_a, mint_b),
+            &fee_account,
+        );
+        self.add_account(address, swap_program_id, data);
+        SplTokenSwapPool::unpack(&address, &swap_program_id, &self.account(&address).data).unwrap()
+    }
+
+    /// Adds a 1sol protocol account swapping into `mint`, as `Initialize` leaves it.
+    pub fn create_protocol(&mut self, mint: &Pubkey) -> TestProtocol {
+        let protocol = Pubkey::new_unique();
+        let (authority, nonce) =
+            Pubkey::find_program_address(&[&protocol.to_bytes()[..32]], &self.program_id);
+        let token = self.create_token_account(mint, &authority, 0);
+        let admin = Pubkey::new_unique();
+        let mut data = vec![0u8; OneSolState::LEN];
+        OneSolState {
+            version: 1,
+            nonce,
+            token_program_id: spl_token::id(),
+            token,
+            token_mint: *mint,
+            admin,
+            fee_numerator: 0,
+            fee_denominator: 0,
+            treasury: Pubkey::default(),
+            is_paused: false,
+            max_referral_fee_bps: 0,
+        }
+        .pack_into_slice(&mut data);
+        let program_id = self.program_id;
+        self.add_account(protocol, program_id, data);
+        TestProtocol {
+            protocol,
+            authority,
+            token,
+            admin,
+        }
+    }
+
+    /// Adds the vault of `mint` of the 1sol protocol account `protocol` of `authority`, as
+    /// `InitializeVault` creates it, holding `amount`.
+    pub fn create_vault(
+        &mut self,
+        protocol: &Pubkey,
+        authority: &Pubkey,
+        mint: &Pubkey,
+        amount: u64,
+    ) -> Pubkey {
+        let (vault, _) = Processor::find_vault_address(&self.program_id, protocol, mint);
+        let mut data = vec![0u8; Account::LEN];
+        Account {
+            mint: *mint,
+            owner: *authority,
+            amount,
+            state: AccountState::Initialized,
+            ..Account::default()
+        }
+        .pack_into_slice(&mut data);
+        self.add_account(vault, spl_token::id(), data)
+    }
+
+    /// Adds the global config of `admin`, logging fully, tipping `tip_account` and capping
+    /// relayer fees at 30 bps plus 1_000.
+    pub fn create_config(&mut self, admin: &Pubkey, tip_account: &Pubkey) -> Pubkey {
+        let (config, bump_seed) = Processor::find_config_address(&self.program_id);
+        let mut data = vec![0u8; GlobalConfig::LEN];
+        GlobalConfig {
+            version: 1,
+            bump_seed,
+            admin: *admin,
+            oracle_max_age_slots: 25,
+            oracle_conf_multiplier: 0,
+            log_level: LogLevel::Full,
+            tip_account: *tip_account,
+            relayer_fee_max_bps: 30,
+            relayer_fee_max_flat: 1_000,
+            split_parts: 0,
+        }
+        .pack_into_slice(&mut data);
+        let program_id = self.program_id;
+        self.add_account(config, program_id, data)
+    }
+
+    /// Adds a trading Pyth price account quoting `price` at exponent `expo`, published
+    /// at `publish_slot`.
+    pub fn create_pyth_price(&mut self, price: i64, expo: i32, publish_slot: u64) -> Pubkey {
+        let mut data = vec![0u8; 3312];
+        data[0..4].copy_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        data[4..8].copy_from_slice(&2u32.to_le_bytes());
+        data[8..12].copy_from_slice(&3u32.to_le_bytes());
+        data[20..24].copy_from_slice(&expo.to_le_bytes());
+        data[208..216].copy_from_slice(&price.to_le_bytes());
+        data[224..228].copy_from_slice(&1u32.to_le_bytes());
+        data[232..240].copy_from_slice(&publish_slot.to_le_bytes());
+        self.add_account(Pubkey::new_unique(), Pubkey::new_unique(), data)
+    }
+
+    /// Adds the inventory pair of `source_mint` / `destination_mint`, as
+    /// `ConfigureInventoryPair` leaves it.
+    pub fn create_inventory_pair(
+        &mut self,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+        oracle: &Pubkey,
+        spread_bps: u16,
+    ) -> Pubkey {
+        let (pair, bump_seed) =
+            Processor::find_inventory_pair_address(&self.program_id, source_mint, destination_mint);
+        let mut data = vec![0u8; InventoryPair::LEN];
+        InventoryPair {
+            version: 1,
+            bump_seed,
+            source_mint: *source_mint,
+            destination_mint: *destination_mint,
+            oracle: *oracle,
+            spread_bps,
+        }
+        .pack_into_slice(&mut data);
+        let program_id = self.program_id;
+        self.add_account(pair, program_id, data)
+    }
+
+    /// Adds the stake pool of `mint`, as `InitStakePool` leaves it, with a rewards vault
+    /// of the same mint.
+    pub fn create_stake_pool(
+        &mut self,
+        mint: &Pubkey,
+        tiers: [StakeTier; STAKE_TIERS],
+    ) -> StakeVaults {
+        let (pool, bump_seed) = Processor::find_stake_pool_address(&self.program_id);
+        let (authority, nonce) =
+            Pubkey::find_program_address(&[&pool.to_bytes()[..32]], &self.program_id);
+        let vaults = StakeVaults {
+            vault: self.create_token_account(mint, &authority, 0),
+            rewards_vault: self.create_token_account(mint, &authority, 0),
+        };
+        let mut data = vec![0u8; StakePool::LEN];
+        StakePool {
+            version: 1,
+            bump_seed,
+            nonce,
+            mint: *mint,
+            vault: vaults.vault,
+            rewards_vault: vaults.rewards_vault,
+            total_staked: 0,
+            reward_per_share: 0,
+            tiers,
+        }
+        .pack_into_slice(&mut data);
+        let program_id = self.program_id;
+        self.add_account(pool, program_id, data);
+        vaults
+    }
+
+    /// Adds the empty stake account of `owner`, as a first `Stake` creates it.
+    pub fn create_stake_account(&mut self, owner: &Pubkey) -> Pubkey {
+        let (stake, bump_seed) = Processor::find_stake_address(&self.program_id, owner);
+        let mut data = vec![0u8; StakeAccount::LEN];
+        StakeAccount {
+            version: 1,
+            bump_seed,
+            owner: *owner,
+            amount: 0,
+            reward_debt: 0,
+        }
+        .pack_into_slice(&mut data);
+        let program_id = self.program_id;
+        self.add_account(stake, program_id, data)
+    }
+
+    /// Runs `instruction` through the 1sol processor. As in a transaction, the accounts
+    /// keep their changes even if the instruction fails, so check the result first.
+    pub fn process(&mut self, instruction: &Instruction) -> ProgramResult {
+        // as on a cluster, unknown accounts are empty system accounts
+        for meta in instruction.accounts.iter() {
+            if !self
+                .accounts
+                .iter()
+                .any(|account| account.key == meta.pubkey)
+            {
+                self.accounts.push(TestAccount {
+                    key: meta.pubkey,
+                    owner: Pubkey::default(),
+                    lamports: 0,
+                    data: vec![],
+                });
+            }
+        }
+        let infos: Vec<AccountInfo> = self
+            .accounts
+            .iter_mut()
+            .map(|account| {
+                AccountInfo::new(
+                    &account.key,
+                    false,
+                    false,
+                    &mut account.lamports,
+                    &mut account.data,
+                    &account.owner,
+                    false,
+                    0,
+                )
+            })
+            .collect();
+        let accounts: Vec<AccountInfo> = instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                let mut info = infos
+                    .iter()
+                    .find(|info| *info.key == meta.pubkey)
+                    .unwrap()
+                    .clone();
+                info.is_signer = meta.is_signer;
+                info.is_writable = meta.is_writable;
+                info
+            })
+            .collect();
+        CALLER.with(|caller| caller.set(instruction.program_id));
+        RETURN_DATA.with(|return_data| *return_data.borrow_mut() = None);
+        Processor::process(&instruction.program_id, &accounts, &instruction.data)
+    }
+
+    /// Returns the data the last instruction processed returned.
+    pub fn return_data(&self) -> Option<Vec<u8>> {
+        RETURN_DATA.with(|return_data| return_data.borrow().clone())
+    }
+
+    /// Sets the Instructions sysvar to the serialized `instructions` of a transaction,
+    /// running the one at `current_index`.
+    pub fn set_instructions_sysvar(&mut self, instructions: &[Instruction], current_index: u16) {
+        let borrowed: Vec<BorrowedInstruction> = instructions
+            .iter()
+            .map(|instruction| BorrowedInstruction {
+                program_id: &instruction.program_id,
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|meta| BorrowedAccountMeta {
+                        pubkey: &meta.pubkey,
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    })
+                    .collect(),
+                data: &instruction.data,
+            })
+            .collect();
+        let mut data = sysvar::instructions::construct_instructions_data(&borrowed);
+        sysvar::instructions::store_current_index(&mut data, current_index);
+        let key = sysvar::instructions::id();
+        self.accounts.retain(|account| account.key != key);
+        self.add_account(key, sysvar::id(), data);
+    }
+
+    /// Sets the slot of the Clock sysvar.
+    pub fn set_slot(&mut self, slot: u64) {
+        SLOT.with(|current| current.set(slot));
+    }
+
+    /// Runs `instruction` as [Harness::process] does, also returning the syscalls it made.
+    pub fn process_metered(&mut self, instruction: &Instruction) -> (ProgramResult, SyscallUsage) {
+        SYSCALL_USAGE.with(|usage| usage.set(SyscallUsage::default()));
+        let result = self.process(instruction);
+        (result, SYSCALL_USAGE.with(Cell::get))
+    }
+}
+
+/// Token accounts of a user swapping, with the authority over SOURCE.
+pub struct TestSwapAccounts {
+    pub user: Pubkey,
+    pub source: Pubkey,
+    pub destination: Pubkey,
+}
+
+/// Creates a `Swap` instruction routing `amount_in` through `legs`, each paired with
+/// its ratio in bps.
+pub fn swap_instruction(
+    program_id: &Pubkey,
+    protocol: &TestProtocol,
+    user_accounts: &TestSwapAccounts,
+    amount_in: u64,
+    minimum_amount_out: u64,
+    legs: &[(Vec<AccountMeta>, u16)],
+) -> Instruction {
+    let mut data = vec![1];
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+    data.push(legs.len() as u8);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(protocol.protocol, false),
+        AccountMeta::new_readonly(protocol.authority, false),
+        AccountMeta::new_readonly(user_accounts.user, true),
+        AccountMeta::new(protocol.token, false),
+        AccountMeta::new(user_accounts.source, false),
+        AccountMeta::new(user_accounts.destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    for (leg, ratio) in legs.iter() {
+        data.extend_from_slice(&[0, leg.len() as u8]);
+        data.extend_from_slice(&ratio.to_le_bytes());
+        accounts.extend_from_slice(leg);
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
@@ -0,0 +1,204 @@
+//! Compute-budget regression tests of representative routes.
+//!
+//! Each route fails its test once the syscall units it charges exceed its budget, so a
+//! change adding a check or a log to a common route shows up here rather than as
+//! transactions running out of compute on the cluster. Budgets are exact as of their
+//! last change: raise one only along with the change that needs it.
+
+mod common;
+
+use common::{mock_lending_id, mock_token_bridge_id, Harness, SyscallUsage};
+use onesol::{
+    bridge,
+    error::{LegError, OneSolError},
+    instruction::{BridgeTransfer, MAX_LEGS},
+    math::BPS_SCALE,
+};
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{swap, swap_and_bridge, swap_and_deposit, SwapAccounts},
+};
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey, sysvar};
+
+/// Compute units a transaction may consume by default.
+const TRANSACTION_COMPUTE_UNITS: u64 = 200_000;
+
+struct Route {
+    harness: Harness,
+    mint_b: Pubkey,
+    accounts: SwapAccounts,
+    legs: Vec<(DexLeg, u16)>,
+}
+
+/// A route of mint A for mint B through mock pools of the given A / B reserves.
+fn route(reserves: &[(u64, u64)]) -> Route {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let legs = reserves
+        .iter()
+        .map(|(reserve_a, reserve_b)| {
+            let pool = harness.create_pool(&mint_a, &mint_b, *reserve_a, *reserve_b);
+            let leg = DexLeg {
+                dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+                accounts: pool.leg_a_to_b(),
+            };
+            (leg, BPS_SCALE / reserves.len() as u16)
+        })
+        .collect();
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 1_000_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    Route {
+        harness,
+        mint_b,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        legs,
+    }
+}
+
+/// Fails unless `usage` of `route` fits `budget`, itself within a transaction.
+fn assert_within_budget(route: &str, usage: SyscallUsage, budget: u64) {
+    assert!(budget <= TRANSACTION_COMPUTE_UNITS);
+    assert!(
+        usage.compute_units() <= budget,
+        "{} charged {} units ({} invokes, {} logs), over its budget of {}",
+        route,
+        usage.compute_units(),
+        usage.invokes,
+        usage.logs,
+        budget
+    );
+}
+
+#[test]
+fn test_longest_route_budget() {
+    let mut route = route(&[(1_000_000, 1_000_000); MAX_LEGS]);
+    let ix = swap(
+        &route.harness.program_id,
+        &route.accounts,
+        10_000,
+        0,
+        &route.legs,
+    )
+    .unwrap();
+    let (result, usage) = route.harness.process_metered(&ix);
+    result.unwrap();
+    assert_within_budget("swap of MAX_LEGS legs", usage, 54_500);
+}
+
+#[test]
+fn test_failed_route_budget() {
+    // the last leg into an empty pool, after three filled ones
+    let mut route = route(&[
+        (1_000_000, 1_000_000),
+        (1_000_000, 1_000_000),
+        (1_000_000, 1_000_000),
+        (1_000_000, 0),
+    ]);
+    let ix = swap(
+        &route.harness.program_id,
+        &route.accounts,
+        10_000,
+        0,
+        &route.legs,
+    )
+    .unwrap();
+    let (result, usage) = route.harness.process_metered(&ix);
+    assert_eq!(
+        result.unwrap_err(),
+        LegError::new(
+            3,
+            DEX_TYPE_SPL_TOKEN_SWAP,
+            &OneSolError::ZeroTradingTokens.into()
+        )
+        .into()
+    );
+    assert_within_budget("swap failing at its last leg", usage, 10_300);
+}
+
+#[test]
+fn test_swap_and_deposit_budget() {
+    let mut route = route(&[(1_000_000, 1_000_000)]);
+    let user = route.accounts.user_transfer_authority;
+    let liquidity_supply =
+        route
+            .harness
+            .create_token_account(&route.mint_b, &Pubkey::new_unique(), 0);
+    let lending_accounts = [
+        AccountMeta::new(route.accounts.destination, false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(liquidity_supply, false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(user, true),
+        AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(user, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    let ix = swap_and_deposit(
+        &route.harness.program_id,
+        &route.accounts,
+        &mock_lending_id(),
+        &lending_accounts,
+        10_000,
+        0,
+        &route.legs,
+    )
+    .unwrap();
+    let (result, usage) = route.harness.process_metered(&ix);
+    result.unwrap();
+    assert_within_budget("swap and deposit", usage, 7_200);
+}
+
+#[test]
+fn test_swap_and_bridge_budget() {
+    let mut route = route(&[(1_000_000, 1_000_000)]);
+    let authority_signer = bridge::authority_signer(&mock_token_bridge_id());
+    route
+        .harness
+        .add_account(authority_signer, Pubkey::default(), vec![]);
+    let custody = route
+        .harness
+        .create_token_account(&route.mint_b, &authority_signer, 0);
+    let bridge_accounts = [
+        AccountMeta::new(route.accounts.destination, false),
+        AccountMeta::new(custody, false),
+        AccountMeta::new_readonly(authority_signer, false),
+    ];
+    let ix = swap_and_bridge(
+        &route.harness.program_id,
+        &route.accounts,
+        &mock_token_bridge_id(),
+        &bridge_accounts,
+        10_000,
+        0,
+        &BridgeTransfer {
+            wrapped: false,
+            nonce: 1,
+            target_chain: 2,
+            target_address: [7u8; 32],
+            relayer_fee: 0,
+        },
+        &route.legs,
+    )
+    .unwrap();
+    let (result, usage) = route.harness.process_metered(&ix);
+    result.unwrap();
+    assert_within_budget("swap and bridge", usage, 8_300);
+}
@@ -0,0 +1,183 @@
+//! Compute unit benchmarks of the swap per adapter leg and route shape.
+//!
+//! Writes a report to `compute-units.txt` under the test target directory and fails if a
+//! shape charges more syscall units than its budget. Raise a budget only along with the
+//! change that needs it.
+
+mod common;
+
+use common::{Harness, SyscallUsage};
+use onesol::{math::BPS_SCALE, processor::Processor};
+use onesol_client::{
+    dex::{self, DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{swap, SwapAccounts},
+};
+use solana_program::pubkey::Pubkey;
+use std::{fmt::Write, fs, path::Path};
+
+/// Adapter of a benchmarked leg.
+#[derive(Clone, Copy)]
+enum Adapter {
+    /// Pool of the emulated Token-Swap program, without fees
+    Mock,
+    /// spl-token-swap pool
+    SplTokenSwap,
+    /// Protocol inventory, priced by an oracle
+    Inventory,
+}
+
+/// Route shapes benchmarked, with their budgets in syscall compute units.
+const SHAPES: &[(&str, &[Adapter], u64)] = &[
+    ("mock x1", &[Adapter::Mock], 3_700),
+    ("mock x2", &[Adapter::Mock, Adapter::Mock], 8_200),
+    (
+        "mock x3",
+        &[Adapter::Mock, Adapter::Mock, Adapter::Mock],
+        11_500,
+    ),
+    (
+        "mock x4",
+        &[Adapter::Mock, Adapter::Mock, Adapter::Mock, Adapter::Mock],
+        14_800,
+    ),
+    ("spl-token-swap x1", &[Adapter::SplTokenSwap], 3_700),
+    (
+        "spl-token-swap x2",
+        &[Adapter::SplTokenSwap, Adapter::SplTokenSwap],
+        8_200,
+    ),
+    ("mixed", &[Adapter::Mock, Adapter::SplTokenSwap], 8_200),
+    ("inventory x1", &[Adapter::Inventory], 2_700),
+    (
+        "mixed x4",
+        &[
+            Adapter::Mock,
+            Adapter::SplTokenSwap,
+            Adapter::Inventory,
+            Adapter::SplTokenSwap,
+        ],
+        13_800,
+    ),
+];
+
+/// Runs a swap of 10_000 through a leg of each of `adapters`.
+fn measure(adapters: &[Adapter]) -> SyscallUsage {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let mut inventory_leg = None;
+    let legs: Vec<(DexLeg, u16)> = adapters
+        .iter()
+        .enumerate()
+        .map(|(i, adapter)| {
+            let leg = match adapter {
+                Adapter::Mock => DexLeg {
+                    dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+                    accounts: harness
+                        .create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000)
+                        .leg_a_to_b(),
+                },
+                Adapter::SplTokenSwap => harness
+                    .create_spl_token_swap_pool(&mint_a, &mint_b, 1_000_000, 1_000_000)
+                    .leg(&mint_a, None)
+                    .unwrap(),
+                // the legs of a route share the inventory of its pair
+                Adapter::Inventory => inventory_leg
+                    .get_or_insert_with(|| create_inventory(&mut harness, &mint_a, &mint_b))
+                    .clone(),
+            };
+            // the first leg takes the rounding remainder of the ratios
+            let ratio = BPS_SCALE / adapters.len() as u16;
+            let remainder = if i == 0 {
+                BPS_SCALE - ratio * adapters.len() as u16
+            } else {
+                0
+            };
+            (leg, ratio + remainder)
+        })
+        .collect();
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+
+    let accounts = SwapAccounts {
+        protocol: protocol.protocol,
+        protocol_authority: protocol.authority,
+        user_transfer_authority: user,
+        protocol_token: protocol.token,
+        source,
+        destination,
+        token_program_id: spl_token::id(),
+    };
+    let ix = swap(&harness.program_id, &accounts, 10_000, 0, &legs).unwrap();
+    let (result, usage) = harness.process_metered(&ix);
+    result.unwrap();
+    usage
+}
+
+/// Adds the inventory of mint A / mint B at 1 A for 1 B, returning a leg filling from it.
+fn create_inventory(harness: &mut Harness, mint_a: &Pubkey, mint_b: &Pubkey) -> DexLeg {
+    harness.create_config(&Pubkey::new_unique(), &Pubkey::default());
+    let oracle = harness.create_pyth_price(100_000_000, -8, 0);
+    harness.create_inventory_pair(mint_a, mint_b, &oracle, 50);
+    let (authority, _) = Processor::find_inventory_authority(&harness.program_id);
+    let vault_a = harness.create_token_account(mint_a, &authority, 0);
+    let vault_b = harness.create_token_account(mint_b, &authority, 100_000);
+    dex::inventory_leg(
+        &harness.program_id,
+        mint_a,
+        mint_b,
+        &vault_a,
+        &vault_b,
+        &oracle,
+    )
+}
+
+#[test]
+fn test_compute_unit_report() {
+    let mut report = String::new();
+    writeln!(
+        report,
+        "{:<20} {:>8} {:>6} {:>8} {:>8}",
+        "shape", "invokes", "logs", "units", "budget"
+    )
+    .unwrap();
+    let mut over_budget = vec![];
+    for (name, adapters, budget) in SHAPES.iter() {
+        let usage = measure(adapters);
+        let units = usage.compute_units();
+        writeln!(
+            report,
+            "{:<20} {:>8} {:>6} {:>8} {:>8}",
+            name, usage.invokes, usage.logs, units, budget
+        )
+        .unwrap();
+        if units > *budget {
+            over_budget.push(*name);
+        }
+    }
+
+    writeln!(report).unwrap();
+    for (name, adapter) in [
+        ("mock", Adapter::Mock),
+        ("spl-token-swap", Adapter::SplTokenSwap),
+        ("inventory", Adapter::Inventory),
+    ]
+    .iter()
+    {
+        let one = measure(&[*adapter]).compute_units();
+        let two = measure(&[*adapter, *adapter]).compute_units();
+        writeln!(report, "{} leg: {} units", name, two - one).unwrap();
+    }
+
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("compute-units.txt");
+    fs::write(&path, &report).unwrap();
+    println!("{}", report);
+    assert!(
+        over_budget.is_empty(),
+        "over budget: {:?}, see {}",
+        over_budget,
+        path.display()
+    );
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use common::Harness;
+use onesol::{error::OneSolError, instruction::flash_fee};
+use onesol_client::instruction::{flash_borrow, flash_repay};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+struct FlashLoanSetup {
+    harness: Harness,
+    borrow: Instruction,
+    repay: Instruction,
+    vault: Pubkey,
+    borrower_token: Pubkey,
+}
+
+fn setup(amount: u64) -> FlashLoanSetup {
+    let mut harness = Harness::new();
+    let mint = harness.create_mint();
+    let protocol = harness.create_protocol(&mint);
+    let vault = harness.create_token_account(&mint, &protocol.authority, 100_000);
+    let borrower = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let borrower_token = harness.create_token_account(&mint, &borrower, 1_000);
+    let borrow = flash_borrow(
+        &harness.program_id,
+        &protocol.protocol,
+        &protocol.authority,
+        &vault,
+        &borrower_token,
+        &spl_token::id(),
+        amount,
+    );
+    let repay = flash_repay(
+        &harness.program_id,
+        &vault,
+        &borrower_token,
+        &borrower,
+        &spl_token::id(),
+        amount,
+    );
+    FlashLoanSetup {
+        harness,
+        borrow,
+        repay,
+        vault,
+        borrower_token,
+    }
+}
+
+#[test]
+fn test_flash_loan_repaid() {
+    let mut setup = setup(50_000);
+    let fee = flash_fee(50_000).unwrap();
+    assert_eq!(fee, 45);
+    let instructions = [setup.borrow.clone(), setup.repay.clone()];
+
+    setup.harness.set_instructions_sysvar(&instructions, 0);
+    setup.harness.process(&setup.borrow).unwrap();
+    assert_eq!(setup.harness.token_balance(&setup.vault), 50_000);
+    assert_eq!(setup.harness.token_balance(&setup.borrower_token), 51_000);
+
+    setup.harness.set_instructions_sysvar(&instructions, 1);
+    setup.harness.process(&setup.repay).unwrap();
+    assert_eq!(setup.harness.token_balance(&setup.vault), 100_000 + fee);
+    assert_eq!(
+        setup.harness.token_balance(&setup.borrower_token),
+        1_000 - fee
+    );
+}
+
+#[test]
+fn test_flash_loan_without_repay() {
+    let mut setup = setup(50_000);
+    setup
+        .harness
+        .set_instructions_sysvar(&[setup.borrow.clone()], 0);
+    assert_eq!(
+        setup.harness.process(&setup.borrow).unwrap_err(),
+        OneSolError::FlashLoanNotRepaid.into()
+    );
+    assert_eq!(setup.harness.token_balance(&setup.vault), 100_000);
+}
+
+#[test]
+fn test_flash_loan_nested_under_another_instruction() {
+    let mut setup = setup(50_000);
+    // an instruction of the program borrowing twice by CPI, then a single repay
+    let outer = Instruction::new_with_bytes(
+        setup.harness.program_id,
+        &[1],
+        vec![AccountMeta::new(setup.vault, false)],
+    );
+    let instructions = [outer, setup.repay.clone()];
+    setup.harness.set_instructions_sysvar(&instructions, 0);
+    for _ in 0..2 {
+        assert_eq!(
+            setup.harness.process(&setup.borrow).unwrap_err(),
+            OneSolError::InvalidInput.into()
+        );
+    }
+    assert_eq!(setup.harness.token_balance(&setup.vault), 100_000);
+}
+
+#[test]
+fn test_flash_loan_repay_settles_a_single_borrow() {
+    let mut setup = setup(50_000);
+    let instructions = [
+        setup.borrow.clone(),
+        setup.borrow.clone(),
+        setup.repay.clone(),
+    ];
+    setup.harness.set_instructions_sysvar(&instructions, 0);
+    assert_eq!(
+        setup.harness.process(&setup.borrow).unwrap_err(),
+        OneSolError::FlashLoanNotRepaid.into()
+    );
+    assert_eq!(setup.harness.token_balance(&setup.vault), 100_000);
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use common::{mock_flash_borrower_id, Harness, TestPool};
+use onesol::{error::OneSolError, instruction::FlashSwap};
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{flash_swap, FlashSwapAccounts},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+struct FlashSwapSetup {
+    harness: Harness,
+    accounts: FlashSwapAccounts,
+    pool: TestPool,
+    borrower: Pubkey,
+    borrower_source: Pubkey,
+}
+
+fn setup() -> FlashSwapSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let vault = harness.create_token_account(&mint_a, &protocol.authority, 100_000);
+    let borrower = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let borrower_source = harness.create_token_account(&mint_a, &borrower, 50_000);
+    let destination = harness.create_token_account(&mint_b, &borrower, 0);
+    FlashSwapSetup {
+        harness,
+        accounts: FlashSwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            protocol_token: protocol.token,
+            vault,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        pool,
+        borrower,
+        borrower_source,
+    }
+}
+
+fn flash_swap_repaying(setup: &FlashSwapSetup, amount_in: u64, repaid: u64) -> Instruction {
+    let callback = Instruction {
+        program_id: mock_flash_borrower_id(),
+        accounts: vec![
+            AccountMeta::new(setup.borrower_source, false),
+            AccountMeta::new(setup.accounts.vault, false),
+            AccountMeta::new_readonly(setup.borrower, true),
+        ],
+        data: repaid.to_le_bytes().to_vec(),
+    };
+    let leg = DexLeg {
+        dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+        accounts: setup.pool.leg_a_to_b(),
+    };
+    flash_swap(
+        &setup.harness.program_id,
+        &setup.accounts,
+        amount_in,
+        9_900,
+        &callback,
+        &[(leg, 10_000)],
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_flash_swap_repaid() {
+    let mut setup = setup();
+    let fee = FlashSwap::fee(10_000).unwrap();
+    assert_eq!(fee, 9);
+    let ix = flash_swap_repaying(&setup, 10_000, 10_000 + fee);
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.accounts.destination), 9_900);
+    assert_eq!(harness.token_balance(&setup.accounts.vault), 100_000 + fee);
+    assert_eq!(
+        harness.token_balance(&setup.borrower_source),
+        50_000 - 10_000 - fee
+    );
+    assert_eq!(harness.token_balance(&setup.accounts.protocol_token), 0);
+}
+
+#[test]
+fn test_flash_swap_not_repaid() {
+    let mut setup = setup();
+    let ix = flash_swap_repaying(&setup, 10_000, 10_000);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::FlashSwapNotRepaid.into()
+    );
+}
+
+#[test]
+fn test_flash_swap_rejects_foreign_vault() {
+    let mut setup = setup();
+    // a vault the protocol authority doesn't own can't be borrowed from
+    setup.accounts.vault = setup.borrower_source;
+    let ix = flash_swap_repaying(&setup, 10_000, 10_009);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidOwner.into()
+    );
+}
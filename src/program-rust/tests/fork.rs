@@ -0,0 +1,146 @@
+mod common;
+
+use common::{
+    spl_token_swap_pool_data, swap_instruction, Harness, TestSwapAccounts,
+    SPL_TOKEN_SWAP_PROGRAM_ID,
+};
+use onesol_client::{
+    dex::SplTokenSwapPool,
+    quote::{Quote, SplTokenSwapQuoter},
+};
+use serde_json::json;
+use solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey};
+use spl_token::state::{Account, AccountState, Mint};
+use std::{fs, path::Path, str::FromStr};
+
+fn write_dump(dir: &Path, key: &Pubkey, owner: &Pubkey, data: &[u8]) {
+    let dump = json!({
+        "pubkey": key.to_string(),
+        "account": {
+            "lamports": 2039280,
+            "data": [base64::encode(data), "base64"],
+            "owner": owner.to_string(),
+            "executable": false,
+            "rentEpoch": 0,
+        },
+    });
+    fs::write(dir.join(format!("{}.json", key)), dump.to_string()).unwrap();
+}
+
+fn token_account_data(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Vec<u8> {
+    let mut data = vec![0u8; Account::LEN];
+    Account {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        state: AccountState::Initialized,
+        ..Account::default()
+    }
+    .pack_into_slice(&mut data);
+    data
+}
+
+/// Dumps an spl-token-swap constant product pool charging 0.25% trade and 0.05% owner
+/// fees, as `solana account --output json` writes it, returning the pool address.
+fn dump_pool(dir: &Path, mint_a: &Pubkey, mint_b: &Pubkey) -> Pubkey {
+    let swap_program_id = Pubkey::from_str(SPL_TOKEN_SWAP_PROGRAM_ID).unwrap();
+    let address = Pubkey::new_unique();
+    let (authority, bump_seed) =
+        Pubkey::find_program_address(&[&address.to_bytes()[..32]], &swap_program_id);
+    let (token_a, token_b, pool_mint, fee_account) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+
+    let pool_data = spl_token_swap_pool_data(
+        bump_seed,
+        &token_a,
+        &token_b,
+        &pool_mint,
+        (mint_a, mint_b),
+        &fee_account,
+    );
+    write_dump(dir, &address, &swap_program_id, &pool_data);
+    write_dump(
+        dir,
+        &token_a,
+        &spl_token::id(),
+        &token_account_data(mint_a, &authority, 5_000_000_000),
+    );
+    write_dump(
+        dir,
+        &token_b,
+        &spl_token::id(),
+        &token_account_data(mint_b, &authority, 20_000_000_000),
+    );
+    let mut mint_data = vec![0u8; Mint::LEN];
+    Mint {
+        mint_authority: COption::Some(authority),
+        supply: 1_000_000,
+        decimals: 6,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    }
+    .pack_into_slice(&mut mint_data);
+    write_dump(dir, &pool_mint, &spl_token::id(), &mint_data);
+    write_dump(
+        dir,
+        &fee_account,
+        &spl_token::id(),
+        &token_account_data(&pool_mint, &Pubkey::new_unique(), 0),
+    );
+    address
+}
+
+#[test]
+fn test_swap_against_dumped_pool() {
+    let dir = std::env::temp_dir().join(format!("onesol-fork-{}", Pubkey::new_unique()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool_address = dump_pool(&dir, &mint_a, &mint_b);
+    assert_eq!(harness.load_account_dumps(&dir).len(), 5);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let swap_program_id = Pubkey::from_str(SPL_TOKEN_SWAP_PROGRAM_ID).unwrap();
+    harness.add_program(&swap_program_id);
+    let pool_data = harness.account(&pool_address).data.clone();
+    let pool = SplTokenSwapPool::unpack(&pool_address, &swap_program_id, &pool_data).unwrap();
+    let quoter = SplTokenSwapQuoter::unpack(
+        pool.clone(),
+        &pool_data,
+        &harness.account(&pool.token_a).data,
+        &harness.account(&pool.token_b).data,
+    )
+    .unwrap();
+    let expected_amount_out = quoter.quote(&mint_a, 1_000_000).unwrap();
+
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 1_000_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        1_000_000,
+        expected_amount_out,
+        &[(pool.leg(&mint_a, None).unwrap().accounts, 10_000)],
+    );
+    harness.process(&ix).unwrap();
+
+    assert_eq!(harness.token_balance(&source), 0);
+    assert_eq!(harness.token_balance(&destination), expected_amount_out);
+    assert_eq!(harness.token_balance(&pool.token_a), 5_001_000_000);
+    assert_eq!(
+        harness.token_balance(&pool.token_b),
+        20_000_000_000 - expected_amount_out
+    );
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::Harness;
+use onesol::{
+    error::OneSolError,
+    instruction::ConfigParams,
+    state::{GlobalConfig, LogLevel},
+};
+use onesol_client::instruction::{governance_address, set_config_admin, update_config};
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+
+struct GovernanceSetup {
+    harness: Harness,
+    admin: Pubkey,
+    config: Pubkey,
+    governance: Pubkey,
+}
+
+fn setup() -> GovernanceSetup {
+    let mut harness = Harness::new();
+    let admin = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let config = harness.create_config(&admin, &Pubkey::default());
+    let governance = governance_address(&Pubkey::new_unique(), &Pubkey::new_unique(), &config);
+    harness.add_account(governance, Pubkey::default(), vec![]);
+    GovernanceSetup {
+        harness,
+        admin,
+        config,
+        governance,
+    }
+}
+
+fn params() -> ConfigParams {
+    ConfigParams {
+        oracle_max_age_slots: 50,
+        oracle_conf_multiplier: 1,
+        log_level: LogLevel::Errors,
+        tip_account: Pubkey::default(),
+        relayer_fee_max_bps: 20,
+        relayer_fee_max_flat: 500,
+        split_parts: 0,
+    }
+}
+
+fn config(setup: &GovernanceSetup) -> GlobalConfig {
+    GlobalConfig::unpack(&setup.harness.account(&setup.config).data).unwrap()
+}
+
+#[test]
+fn test_governance_admin() {
+    let mut setup = setup();
+    let program_id = setup.harness.program_id;
+    let ix = set_config_admin(&program_id, &setup.admin, &setup.governance);
+    setup.harness.process(&ix).unwrap();
+    assert_eq!(config(&setup).admin, setup.governance);
+
+    // the former admin lost the config
+    let ix = update_config(&program_id, &setup.admin, &params());
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidOwner.into()
+    );
+
+    // the governance signs only as a passed proposal executes
+    let mut ix = update_config(&program_id, &setup.governance, &params());
+    ix.accounts[0].is_signer = false;
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        ProgramError::MissingRequiredSignature
+    );
+    let ix = update_config(&program_id, &setup.governance, &params());
+    setup.harness.process(&ix).unwrap();
+    assert_eq!(config(&setup).oracle_max_age_slots, 50);
+    assert_eq!(config(&setup).log_level, LogLevel::Errors);
+    assert_eq!(config(&setup).relayer_fee_max_bps, 20);
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::{swap_instruction, Harness, TestProtocol, TestSwapAccounts};
+use onesol::error::{LegError, OneSolError};
+use onesol_client::dex::SplTokenSwapPool;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+struct HostFeeSetup {
+    harness: Harness,
+    protocol: TestProtocol,
+    pool: SplTokenSwapPool,
+    user: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+    host_fee_account: Pubkey,
+}
+
+/// A spl-token-swap pool of mint A / mint B charging 0.25% trade and 0.05% owner fees,
+/// an integrator holding a token account of its pool mint.
+fn setup() -> HostFeeSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_spl_token_swap_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let host_fee_account = harness.create_token_account(&pool.pool_mint, &Pubkey::new_unique(), 0);
+    HostFeeSetup {
+        harness,
+        protocol,
+        pool,
+        user,
+        source,
+        destination,
+        host_fee_account,
+    }
+}
+
+fn swap(setup: &HostFeeSetup, host_fee_account: &Pubkey) -> Instruction {
+    let leg = setup
+        .pool
+        .leg(&setup.pool.token_a_mint, Some(host_fee_account))
+        .unwrap();
+    swap_instruction(
+        &setup.harness.program_id,
+        &setup.protocol,
+        &TestSwapAccounts {
+            user: setup.user,
+            source: setup.source,
+            destination: setup.destination,
+        },
+        20_000,
+        0,
+        &[(leg.accounts, 10_000)],
+    )
+}
+
+#[test]
+fn test_swap_forwards_host_fee_account() {
+    let mut setup = setup();
+    setup.harness.set_host_fee(&setup.pool.address, 20, 100);
+    let ix = swap(&setup, &setup.host_fee_account);
+    setup.harness.process(&ix).unwrap();
+
+    // 20% of the owner fee of 10 goes to the host
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.host_fee_account), 2);
+    assert_eq!(harness.token_balance(&setup.source), 80_000);
+    assert!(harness.token_balance(&setup.destination) > 0);
+}
+
+#[test]
+fn test_swap_drops_host_fee_account_of_pools_without_host_fees() {
+    let mut setup = setup();
+    // a pool without host fees never sees the account, whatever its mint
+    let ix = swap(&setup, &setup.source.clone());
+    setup.harness.process(&ix).unwrap();
+    assert_eq!(setup.harness.token_balance(&setup.source), 80_000);
+    assert_eq!(setup.harness.token_balance(&setup.host_fee_account), 0);
+}
+
+#[test]
+fn test_swap_rejects_host_fee_account_of_other_mint() {
+    let mut setup = setup();
+    setup.harness.set_host_fee(&setup.pool.address, 20, 100);
+    let ix = swap(&setup, &setup.destination.clone());
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        LegError::new(0, 0, &OneSolError::IncorrectSwapAccount.into()).into()
+    );
+}
@@ -0,0 +1,223 @@
+mod common;
+
+use common::{Harness, TestPool};
+use onesol::{
+    error::{LegError, OneSolError},
+    processor::Processor,
+    state::InventoryPair,
+};
+use onesol_client::{
+    dex::{inventory_leg, DexLeg, DEX_TYPE_INVENTORY, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{
+        configure_inventory_pair, fund_inventory, swap, withdraw_inventory, SwapAccounts,
+    },
+};
+use solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+
+struct InventorySetup {
+    harness: Harness,
+    accounts: SwapAccounts,
+    pool: TestPool,
+    admin: Pubkey,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    oracle: Pubkey,
+    vault_a: Pubkey,
+    vault_b: Pubkey,
+}
+
+fn setup() -> InventorySetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let admin = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    harness.create_config(&admin, &Pubkey::default());
+    // 1 A for 1 B
+    let oracle = harness.create_pyth_price(100_000_000, -8, 0);
+    harness.create_inventory_pair(&mint_a, &mint_b, &oracle, 50);
+    let (authority, _) = Processor::find_inventory_authority(&harness.program_id);
+    let vault_a = harness.create_token_account(&mint_a, &authority, 0);
+    let vault_b = harness.create_token_account(&mint_b, &authority, 50_000);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    InventorySetup {
+        harness,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        pool,
+        admin,
+        mint_a,
+        mint_b,
+        oracle,
+        vault_a,
+        vault_b,
+    }
+}
+
+fn inventory_swap(setup: &InventorySetup, oracle: &Pubkey, amount_in: u64) -> Instruction {
+    let leg = inventory_leg(
+        &setup.harness.program_id,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.vault_a,
+        &setup.vault_b,
+        oracle,
+    );
+    swap(
+        &setup.harness.program_id,
+        &setup.accounts,
+        amount_in,
+        amount_in * 9 / 10,
+        &[(leg, 10_000)],
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_swap_fills_from_inventory() {
+    let mut setup = setup();
+    let ix = inventory_swap(&setup, &setup.oracle, 10_000);
+    setup.harness.process(&ix).unwrap();
+
+    // the oracle price less the 50 bps spread
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.accounts.source), 90_000);
+    assert_eq!(harness.token_balance(&setup.accounts.destination), 9_950);
+    assert_eq!(harness.token_balance(&setup.vault_a), 10_000);
+    assert_eq!(harness.token_balance(&setup.vault_b), 40_050);
+    assert_eq!(harness.token_balance(&setup.accounts.protocol_token), 0);
+}
+
+#[test]
+fn test_swap_split_between_inventory_and_pool() {
+    let mut setup = setup();
+    let inventory = inventory_leg(
+        &setup.harness.program_id,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.vault_a,
+        &setup.vault_b,
+        &setup.oracle,
+    );
+    let pool = DexLeg {
+        dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+        accounts: setup.pool.leg_a_to_b(),
+    };
+    let ix = swap(
+        &setup.harness.program_id,
+        &setup.accounts,
+        40_000,
+        0,
+        &[(inventory, 2_500), (pool, 7_500)],
+    )
+    .unwrap();
+    setup.harness.process(&ix).unwrap();
+
+    // 10_000 filled from the inventory and 30_000 through the pool
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.vault_a), 10_000);
+    assert_eq!(harness.token_balance(&setup.pool.token_a), 1_030_000);
+    assert_eq!(harness.token_balance(&setup.accounts.source), 60_000);
+    assert_eq!(
+        harness.token_balance(&setup.accounts.destination),
+        9_950 + 29_126
+    );
+}
+
+/// The error of the inventory leg of a single-leg swap failing with `error`.
+fn inventory_leg_error(error: OneSolError) -> LegError {
+    LegError::new(0, DEX_TYPE_INVENTORY, &error.into())
+}
+
+#[test]
+fn test_swap_rejects_foreign_oracle() {
+    let mut setup = setup();
+    let oracle = setup.harness.create_pyth_price(200_000_000, -8, 0);
+    let ix = inventory_swap(&setup, &oracle, 10_000);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        inventory_leg_error(OneSolError::InvalidOracle).into()
+    );
+}
+
+#[test]
+fn test_swap_rejects_insufficient_inventory() {
+    let mut setup = setup();
+    let ix = inventory_swap(&setup, &setup.oracle, 60_000);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        inventory_leg_error(OneSolError::InsufficientInventory).into()
+    );
+}
+
+#[test]
+fn test_configure_fund_and_withdraw_inventory() {
+    let mut setup = setup();
+    let program_id = setup.harness.program_id;
+    let mint_c = setup.harness.create_mint();
+    let oracle = setup.harness.create_pyth_price(100_000_000, -8, 0);
+    // as a first ConfigureInventoryPair creates it
+    setup
+        .harness
+        .create_inventory_pair(&setup.mint_a, &mint_c, &Pubkey::default(), 0);
+    let ix = configure_inventory_pair(
+        &program_id,
+        &setup.admin,
+        &setup.mint_a,
+        &mint_c,
+        &oracle,
+        30,
+    );
+    setup.harness.process(&ix).unwrap();
+    let (pair, _) = Processor::find_inventory_pair_address(&program_id, &setup.mint_a, &mint_c);
+    let pair = InventoryPair::unpack(&setup.harness.account(&pair).data).unwrap();
+    assert_eq!(pair.oracle, oracle);
+    assert_eq!(pair.spread_bps, 30);
+
+    let admin_token = setup
+        .harness
+        .create_token_account(&setup.mint_b, &setup.admin, 20_000);
+    let ix = fund_inventory(
+        &program_id,
+        &setup.admin,
+        &admin_token,
+        &setup.vault_b,
+        20_000,
+    );
+    setup.harness.process(&ix).unwrap();
+    assert_eq!(setup.harness.token_balance(&setup.vault_b), 70_000);
+
+    let ix = withdraw_inventory(
+        &program_id,
+        &setup.admin,
+        &setup.vault_b,
+        &admin_token,
+        70_000,
+    );
+    setup.harness.process(&ix).unwrap();
+    assert_eq!(setup.harness.token_balance(&setup.vault_b), 0);
+    assert_eq!(setup.harness.token_balance(&admin_token), 70_000);
+
+    // only the config admin moves the inventory
+    let ix = withdraw_inventory(
+        &program_id,
+        &Pubkey::new_unique(),
+        &setup.vault_b,
+        &admin_token,
+        1,
+    );
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidOwner.into()
+    );
+}
@@ -0,0 +1,169 @@
+//! Golden tests of the wire formats deployed clients depend on.
+//!
+//! The fixtures of `tests/fixtures/layout` hold the bytes of representative instructions
+//! and states, in hex. A failing test here is a breaking change of the wire format: fix
+//! the code rather than the fixture, unless the break is intended and versioned.
+
+use onesol::{
+    instruction::{DexConfig, Initialize, OneSolInstruction},
+    state::OneSolState,
+};
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{initialize, swap, SwapAccounts},
+};
+use solana_program::{instruction::AccountMeta, program_pack::Pack, pubkey::Pubkey};
+use std::{fs, path::Path};
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/layout")
+        .join(format!("{}.hex", name));
+    let hex = fs::read_to_string(&path).unwrap();
+    let hex: String = hex.split_whitespace().collect();
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn assert_fixture(name: &str, data: &[u8]) {
+    assert_eq!(
+        to_hex(data),
+        to_hex(&fixture(name)),
+        "{} no longer matches tests/fixtures/layout/{}.hex",
+        name,
+        name
+    );
+}
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+fn leg(accounts: u8) -> DexLeg {
+    DexLeg {
+        dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+        accounts: (0..accounts)
+            .map(|i| AccountMeta::new(key(0x40 + i), false))
+            .collect(),
+    }
+}
+
+#[test]
+fn test_initialize_layout() {
+    let ix = initialize(&key(1), &key(2), &key(3), &key(4), &key(5), &key(6), 254);
+    assert_fixture("initialize", &ix.data);
+    assert_eq!(
+        OneSolInstruction::unpack(&fixture("initialize")).unwrap(),
+        OneSolInstruction::Initialize(Initialize { nonce: 254 })
+    );
+}
+
+#[test]
+fn test_swap_layout() {
+    let accounts = SwapAccounts {
+        protocol: key(2),
+        protocol_authority: key(3),
+        user_transfer_authority: key(6),
+        protocol_token: key(4),
+        source: key(7),
+        destination: key(8),
+        token_program_id: key(5),
+    };
+    let ix = swap(
+        &key(1),
+        &accounts,
+        1_000_000_000,
+        987_654_321,
+        &[(leg(8), 3_000), (leg(8), 7_000)],
+    )
+    .unwrap();
+    assert_fixture("swap", &ix.data);
+    let data = fixture("swap");
+    let swap = match OneSolInstruction::unpack(&data).unwrap() {
+        OneSolInstruction::Swap(swap) => swap,
+        instruction => panic!("unexpected instruction {:?}", instruction),
+    };
+    assert_eq!(swap.amount_in, 1_000_000_000);
+    assert_eq!(swap.minimum_amount_out, 987_654_321);
+    assert_eq!(swap.oracle_max_deviation_bps, None);
+    assert_eq!(
+        swap.dex_configs.iter().collect::<Vec<_>>(),
+        vec![
+            DexConfig {
+                dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+                account_size: 8,
+                ratio: 3_000,
+                minimum_amount_out: None,
+            },
+            DexConfig {
+                dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+                account_size: 8,
+                ratio: 7_000,
+                minimum_amount_out: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_onesol_state_layout() {
+    // states initialized before fees keep their 98 bytes, and charge none
+    let data = fixture("onesol_state");
+    assert_eq!(data.len(), OneSolState::LEGACY_LEN);
+    let view = OneSolState::view(&data).unwrap();
+    assert_eq!(view.nonce(), 253);
+    assert_eq!(view.token_program_id(), key(5));
+    assert_eq!(view.token(), key(4));
+    assert_eq!(view.token_mint(), key(9));
+    assert_eq!(view.fee(1_000_000).unwrap(), 0);
+
+    let state = OneSolState {
+        version: 1,
+        nonce: 253,
+        token_program_id: key(5),
+        token: key(4),
+        token_mint: key(9),
+        admin: key(7),
+        fee_numerator: 25,
+        fee_denominator: 10_000,
+        treasury: key(8),
+        is_paused: true,
+        max_referral_fee_bps: 50,
+    };
+    let mut data = vec![0u8; OneSolState::LEN];
+    state.pack_into_slice(&mut data);
+    assert_fixture("onesol_state_referral", &data);
+    assert_fixture("onesol_state_paused", &data[..OneSolState::UNREFERRED_LEN]);
+    let data = fixture("onesol_state_referral");
+    assert_eq!(OneSolState::unpack(&data).unwrap(), state);
+    let view = OneSolState::view(&data).unwrap();
+    assert_eq!(view.nonce(), state.nonce);
+    assert_eq!(view.token(), state.token);
+    assert_eq!(view.token_mint(), state.token_mint);
+    assert_eq!(view.admin(), state.admin);
+    assert_eq!(view.treasury(), state.treasury);
+    assert_eq!(view.fee(1_000_000).unwrap(), 2_500);
+    assert!(view.is_paused());
+    assert_eq!(view.max_referral_fee_bps(), 50);
+
+    // states initialized before referral fees keep their 179 bytes, and allow none
+    let data = fixture("onesol_state_paused");
+    assert_eq!(data.len(), OneSolState::UNREFERRED_LEN);
+    let view = OneSolState::view(&data).unwrap();
+    assert!(view.is_paused());
+    assert_eq!(view.max_referral_fee_bps(), 0);
+
+    // states initialized before the pause flag keep their 178 bytes, and never pause
+    let data = fixture("onesol_state_fees");
+    assert_eq!(data.len(), OneSolState::UNPAUSABLE_LEN);
+    let view = OneSolState::view(&data).unwrap();
+    assert_eq!(view.admin(), state.admin);
+    assert_eq!(view.fee(1_000_000).unwrap(), 2_500);
+    assert!(!view.is_paused());
+}
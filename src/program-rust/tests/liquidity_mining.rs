@@ -0,0 +1,95 @@
+mod common;
+
+use common::Harness;
+use onesol::{
+    error::OneSolError,
+    processor::Processor,
+    state::{PairRewards, RewardPoints},
+};
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{claim_rewards, configure_pair_rewards, earn_points, swap, SwapAccounts},
+};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+
+#[test]
+fn test_swap_earns_points() {
+    let mut harness = Harness::new();
+    let program_id = harness.program_id;
+    let admin = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    harness.create_config(&admin, &Pubkey::default());
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+
+    let (rewards, bump_seed) = Processor::find_pair_rewards_address(&program_id, &mint_a, &mint_b);
+    let mut data = vec![0u8; PairRewards::LEN];
+    PairRewards {
+        version: 1,
+        bump_seed,
+        source_mint: mint_a,
+        destination_mint: mint_b,
+        points_per_unit: 0,
+        total_points: 0,
+    }
+    .pack_into_slice(&mut data);
+    harness.add_account(rewards, program_id, data);
+    let (points, bump_seed) = Processor::find_reward_points_address(&program_id, &user);
+    let mut data = vec![0u8; RewardPoints::LEN];
+    RewardPoints {
+        version: 1,
+        bump_seed,
+        owner: user,
+        points: 0,
+        claimed: 0,
+    }
+    .pack_into_slice(&mut data);
+    harness.add_account(points, program_id, data);
+
+    let ix = configure_pair_rewards(&program_id, &admin, &mint_a, &mint_b, 2_000_000);
+    harness.process(&ix).unwrap();
+
+    let accounts = SwapAccounts {
+        protocol: protocol.protocol,
+        protocol_authority: protocol.authority,
+        user_transfer_authority: user,
+        protocol_token: protocol.token,
+        source,
+        destination,
+        token_program_id: spl_token::id(),
+    };
+    let leg = DexLeg {
+        dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+        accounts: pool.leg_a_to_b(),
+    };
+    let mut ix = swap(&program_id, &accounts, 10_000, 9_800, &[(leg, 10_000)]).unwrap();
+    earn_points(&mut ix, &mint_a, &mint_b);
+    harness.process(&ix).unwrap();
+    let earned = RewardPoints::unpack(&harness.account(&points).data).unwrap();
+    assert_eq!(earned.points, 20_000);
+    let pair = PairRewards::unpack(&harness.account(&rewards).data).unwrap();
+    assert_eq!(pair.total_points, 20_000);
+
+    // a vault short of the points pays what it holds
+    let (authority, _) = Processor::find_rewards_vault_authority(&program_id);
+    let reward_mint = harness.create_mint();
+    let vault = harness.create_token_account(&reward_mint, &authority, 15_000);
+    let user_rewards = harness.create_token_account(&reward_mint, &user, 0);
+    let ix = claim_rewards(&program_id, &user, &vault, &user_rewards);
+    harness.process(&ix).unwrap();
+    assert_eq!(harness.token_balance(&user_rewards), 15_000);
+    let left = RewardPoints::unpack(&harness.account(&points).data).unwrap();
+    assert_eq!((left.points, left.claimed), (5_000, 15_000));
+
+    let other = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let mut ix = claim_rewards(&program_id, &other, &vault, &user_rewards);
+    ix.accounts[1].pubkey = points;
+    assert_eq!(
+        harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidOwner.into()
+    );
+}
@@ -0,0 +1,326 @@
+//! Swaps with one of their accounts swapped for a malicious one, each of which the
+//! validation must reject with the expected error.
+
+mod common;
+
+use common::{swap_instruction, Harness, TestSwapAccounts};
+use onesol::{
+    error::{AdapterError, LegError, OneSolError},
+    processor::Processor,
+};
+use onesol_client::{
+    dex::{inventory_leg, DEX_TYPE_INVENTORY},
+    instruction::{swap, SwapAccounts},
+};
+use solana_program::{
+    instruction::Instruction, program_error::ProgramError, pubkey::Pubkey, system_program,
+};
+
+const PROTOCOL: usize = 0;
+const PROTOCOL_AUTHORITY: usize = 1;
+const USER: usize = 2;
+const PROTOCOL_TOKEN: usize = 3;
+const SOURCE: usize = 4;
+const DESTINATION: usize = 5;
+const TOKEN_PROGRAM: usize = 6;
+/// Index of the first account of the first leg
+const LEG: usize = 7;
+
+/// A way to tamper with a valid swap, and the error the swap must then fail with.
+struct Case {
+    name: &'static str,
+    tamper: fn(&mut Harness, &mut Instruction),
+    error: ProgramError,
+}
+
+/// Runs the swap of `setup` tampered with by each of `cases`, after checking the
+/// untampered swap succeeds.
+fn assert_rejected(setup: fn() -> (Harness, Instruction), cases: &[Case]) {
+    let (mut harness, ix) = setup();
+    harness.process(&ix).unwrap();
+
+    let mut failures = vec![];
+    for case in cases.iter() {
+        let (mut harness, mut ix) = setup();
+        (case.tamper)(&mut harness, &mut ix);
+        let result = harness.process(&ix);
+        if result != Err(case.error.clone()) {
+            failures.push(format!(
+                "{}: expected {:?}, got {:?}",
+                case.name, case.error, result
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{:#?}", failures);
+}
+
+/// Copies the account `key` to a new address, owned by `owner`.
+fn copy_account(harness: &mut Harness, key: &Pubkey, owner: &Pubkey) -> Pubkey {
+    let data = harness.account(key).data.clone();
+    harness.add_account(Pubkey::new_unique(), *owner, data)
+}
+
+/// A swap of 10_000 mint A for mint B through a mock pool.
+fn pool_swap() -> (Harness, Instruction) {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        10_000,
+        0,
+        &[(pool.leg_a_to_b(), 10_000)],
+    );
+    (harness, ix)
+}
+
+#[test]
+fn test_swap_rejects_malicious_accounts() {
+    assert_rejected(
+        pool_swap,
+        &[
+            Case {
+                name: "protocol owned by another program",
+                tamper: |harness, ix| {
+                    let protocol = ix.accounts[PROTOCOL].pubkey;
+                    ix.accounts[PROTOCOL].pubkey =
+                        copy_account(harness, &protocol, &Pubkey::new_unique());
+                },
+                error: ProgramError::IncorrectProgramId,
+            },
+            Case {
+                name: "protocol of another layout",
+                tamper: |harness, ix| {
+                    let program_id = harness.program_id;
+                    ix.accounts[PROTOCOL].pubkey =
+                        harness.add_account(Pubkey::new_unique(), program_id, vec![1; 64]);
+                },
+                error: ProgramError::InvalidAccountData,
+            },
+            Case {
+                name: "fake token program",
+                tamper: |harness, ix| {
+                    let fake = Pubkey::new_unique();
+                    harness.add_program(&fake);
+                    ix.accounts[TOKEN_PROGRAM].pubkey = fake;
+                },
+                error: OneSolError::IncorrectTokenProgramId.into(),
+            },
+            Case {
+                name: "destination owned by a fake token program",
+                tamper: |harness, ix| {
+                    let destination = ix.accounts[DESTINATION].pubkey;
+                    ix.accounts[DESTINATION].pubkey =
+                        copy_account(harness, &destination, &Pubkey::new_unique());
+                },
+                error: OneSolError::IncorrectTokenProgramId.into(),
+            },
+            Case {
+                name: "destination of another mint",
+                tamper: |harness, ix| {
+                    let mint = harness.create_mint();
+                    let user = ix.accounts[USER].pubkey;
+                    ix.accounts[DESTINATION].pubkey = harness.create_token_account(&mint, &user, 0);
+                },
+                error: OneSolError::InvalidInput.into(),
+            },
+            Case {
+                name: "destination aliasing the source",
+                tamper: |_, ix| ix.accounts[DESTINATION].pubkey = ix.accounts[SOURCE].pubkey,
+                error: OneSolError::InvalidInput.into(),
+            },
+            Case {
+                name: "destination aliasing the protocol token",
+                tamper: |_, ix| {
+                    ix.accounts[DESTINATION].pubkey = ix.accounts[PROTOCOL_TOKEN].pubkey
+                },
+                error: OneSolError::IncorrectSwapAccount.into(),
+            },
+            Case {
+                name: "source aliasing the protocol token",
+                tamper: |_, ix| ix.accounts[SOURCE].pubkey = ix.accounts[PROTOCOL_TOKEN].pubkey,
+                error: OneSolError::IncorrectSwapAccount.into(),
+            },
+            Case {
+                name: "user not signing",
+                tamper: |_, ix| ix.accounts[USER].is_signer = false,
+                error: LegError::new(0, 0, &ProgramError::MissingRequiredSignature).into(),
+            },
+            Case {
+                name: "pool of another swap program",
+                tamper: |_, ix| ix.accounts[LEG + 6].pubkey = system_program::id(),
+                error: LegError::new(0, 0, &ProgramError::IncorrectProgramId).into(),
+            },
+        ],
+    );
+}
+
+/// A swap of 10_000 mint A for mint B split across two legs of a mock pool, paid out of
+/// the protocol token account. Single legs owing no fee swap straight into DESTINATION,
+/// never signing with the protocol authority.
+fn split_pool_swap() -> (Harness, Instruction) {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        10_000,
+        0,
+        &[(pool.leg_a_to_b(), 5_000), (pool.leg_a_to_b(), 5_000)],
+    );
+    (harness, ix)
+}
+
+#[test]
+fn test_payout_rejects_spoofed_protocol_authority() {
+    assert_rejected(
+        split_pool_swap,
+        &[Case {
+            name: "spoofed protocol authority",
+            tamper: |harness, ix| {
+                // the authority of another protocol, a valid program address too
+                let (authority, _) = Pubkey::find_program_address(
+                    &[&Pubkey::new_unique().to_bytes()],
+                    &harness.program_id,
+                );
+                ix.accounts[PROTOCOL_AUTHORITY].pubkey = authority;
+            },
+            error: ProgramError::MissingRequiredSignature,
+        }],
+    );
+}
+
+/// A swap of 10_000 mint A for mint B filled from the inventory.
+fn inventory_swap() -> (Harness, Instruction) {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let protocol = harness.create_protocol(&mint_b);
+    harness.create_config(&Pubkey::new_unique(), &Pubkey::default());
+    let oracle = harness.create_pyth_price(100_000_000, -8, 0);
+    harness.create_inventory_pair(&mint_a, &mint_b, &oracle, 50);
+    let (authority, _) = Processor::find_inventory_authority(&harness.program_id);
+    let vault_a = harness.create_token_account(&mint_a, &authority, 0);
+    let vault_b = harness.create_token_account(&mint_b, &authority, 50_000);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let leg = inventory_leg(
+        &harness.program_id,
+        &mint_a,
+        &mint_b,
+        &vault_a,
+        &vault_b,
+        &oracle,
+    );
+    let ix = swap(
+        &harness.program_id,
+        &SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        10_000,
+        0,
+        &[(leg, 10_000)],
+    )
+    .unwrap();
+    (harness, ix)
+}
+
+/// `InvalidVault` of the inventory adapter
+const INVALID_VAULT: AdapterError = AdapterError {
+    dex_type: DEX_TYPE_INVENTORY,
+    code: 1,
+};
+
+fn inventory_error(error: ProgramError) -> ProgramError {
+    LegError::new(0, DEX_TYPE_INVENTORY, &error).into()
+}
+
+#[test]
+fn test_inventory_swap_rejects_malicious_accounts() {
+    assert_rejected(
+        inventory_swap,
+        &[
+            Case {
+                name: "pair owned by another program",
+                tamper: |harness, ix| {
+                    let pair = ix.accounts[LEG].pubkey;
+                    ix.accounts[LEG].pubkey = copy_account(harness, &pair, &Pubkey::new_unique());
+                },
+                error: inventory_error(ProgramError::IncorrectProgramId),
+            },
+            Case {
+                name: "spoofed inventory authority",
+                tamper: |_, ix| ix.accounts[LEG + 1].pubkey = Pubkey::new_unique(),
+                error: inventory_error(OneSolError::InvalidProgramAddress.into()),
+            },
+            Case {
+                name: "vault owned by someone else",
+                tamper: |harness, ix| {
+                    let mint = ix.accounts[LEG + 6].pubkey;
+                    ix.accounts[LEG + 3].pubkey =
+                        harness.create_token_account(&mint, &Pubkey::new_unique(), 50_000);
+                },
+                error: inventory_error(INVALID_VAULT.into()),
+            },
+            Case {
+                name: "vaults of swapped mints",
+                tamper: |_, ix| {
+                    let source_vault = ix.accounts[LEG + 2].pubkey;
+                    ix.accounts[LEG + 2].pubkey = ix.accounts[LEG + 3].pubkey;
+                    ix.accounts[LEG + 3].pubkey = source_vault;
+                },
+                error: inventory_error(INVALID_VAULT.into()),
+            },
+            Case {
+                name: "foreign oracle",
+                tamper: |harness, ix| {
+                    ix.accounts[LEG + 4].pubkey = harness.create_pyth_price(200_000_000, -8, 0)
+                },
+                error: inventory_error(OneSolError::InvalidOracle.into()),
+            },
+            Case {
+                name: "mints of another pair",
+                tamper: |_, ix| ix.accounts[LEG + 5].pubkey = ix.accounts[LEG + 6].pubkey,
+                error: inventory_error(OneSolError::InvalidInput.into()),
+            },
+            Case {
+                name: "config owned by another program",
+                tamper: |harness, ix| {
+                    let config = ix.accounts[LEG + 7].pubkey;
+                    ix.accounts[LEG + 7].pubkey =
+                        copy_account(harness, &config, &Pubkey::new_unique());
+                },
+                error: inventory_error(ProgramError::IncorrectProgramId),
+            },
+        ],
+    );
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::{swap_instruction, Harness, TestSwapAccounts};
+use onesol::{error::OneSolError, processor::Processor, state::MintStats};
+use onesol_client::instruction::{
+    init_mint_stats, pay_protocol_fee, record_mint_stats, set_fee_rate,
+};
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+
+/// Adds the empty statistics account of `mint`, as `InitMintStats` creates it.
+fn create_mint_stats(harness: &mut Harness, mint: &Pubkey) -> Pubkey {
+    let program_id = harness.program_id;
+    let (stats, bump_seed) = Processor::find_mint_stats_address(&program_id, mint);
+    let mut data = vec![0u8; MintStats::LEN];
+    MintStats {
+        version: 1,
+        bump_seed,
+        mint: *mint,
+        volume_in: 0,
+        volume_out: 0,
+        swap_count: 0,
+        fees: 0,
+    }
+    .pack_into_slice(&mut data);
+    harness.add_account(stats, program_id, data)
+}
+
+#[test]
+fn test_swap_records_mint_stats() {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let treasury = harness.create_token_account(&mint_b, &Pubkey::new_unique(), 0);
+    let ix = set_fee_rate(
+        &harness.program_id,
+        &protocol.admin,
+        &protocol.protocol,
+        &treasury,
+        25,
+        10_000,
+    );
+    harness.process(&ix).unwrap();
+    let stats_a = create_mint_stats(&mut harness, &mint_a);
+    let stats_b = create_mint_stats(&mut harness, &mint_b);
+
+    for _ in 0..2 {
+        let mut ix = swap_instruction(
+            &harness.program_id,
+            &protocol,
+            &TestSwapAccounts {
+                user,
+                source,
+                destination,
+            },
+            10_000,
+            1,
+            &[(pool.leg_a_to_b(), 10_000)],
+        );
+        pay_protocol_fee(&mut ix, &treasury);
+        record_mint_stats(&mut ix, &[mint_a, mint_b]);
+        harness.process(&ix).unwrap();
+    }
+
+    let received = harness.token_balance(&destination) as u128;
+    let fees = harness.token_balance(&treasury) as u128;
+    let source_stats = MintStats::unpack(&harness.account(&stats_a).data).unwrap();
+    assert_eq!(source_stats.swap_count, 2);
+    assert_eq!(source_stats.volume_in, 20_000);
+    assert_eq!(source_stats.volume_out, 0);
+    assert_eq!(source_stats.fees, 0);
+    let destination_stats = MintStats::unpack(&harness.account(&stats_b).data).unwrap();
+    assert_eq!(destination_stats.swap_count, 2);
+    assert_eq!(destination_stats.volume_in, 0);
+    assert_eq!(destination_stats.volume_out, received);
+    assert!(fees > 0);
+    assert_eq!(destination_stats.fees, fees);
+}
+
+#[test]
+fn test_swap_rejects_stats_of_other_mint() {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let mint_c = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let stats_c = create_mint_stats(&mut harness, &mint_c);
+
+    let mut ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        10_000,
+        1,
+        &[(pool.leg_a_to_b(), 10_000)],
+    );
+    record_mint_stats(&mut ix, &[mint_c]);
+    assert_eq!(harness.process(&ix), Err(OneSolError::InvalidInput.into()));
+    let stats = MintStats::unpack(&harness.account(&stats_c).data).unwrap();
+    assert_eq!(stats.swap_count, 0);
+}
+
+#[test]
+fn test_init_mint_stats_checks_address() {
+    let mut harness = Harness::new();
+    let mint = harness.create_mint();
+    let payer = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+
+    let mut ix = init_mint_stats(&harness.program_id, &payer, &mint);
+    ix.accounts[0].is_signer = false;
+    assert_eq!(
+        harness.process(&ix),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+
+    let mut ix = init_mint_stats(&harness.program_id, &payer, &mint);
+    ix.accounts[1].pubkey = Pubkey::new_unique();
+    assert_eq!(
+        harness.process(&ix),
+        Err(OneSolError::InvalidProgramAddress.into())
+    );
+}
@@ -0,0 +1,166 @@
+//! Routes mixing the legs of several adapters.
+
+mod common;
+
+use common::{Harness, TestPool};
+use onesol::{
+    error::{LegError, OneSolError},
+    instruction::DexConfig,
+    math::BPS_SCALE,
+    processor::Processor,
+};
+use onesol_client::{
+    dex::{inventory_leg, DexLeg, SplTokenSwapPool, DEX_TYPE_INVENTORY, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{swap, SwapAccounts},
+};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+struct RouteSetup {
+    harness: Harness,
+    accounts: SwapAccounts,
+    mock_pool: TestPool,
+    spl_pools: [SplTokenSwapPool; 2],
+    vault_a: Pubkey,
+    vault_b: Pubkey,
+    legs: Vec<DexLeg>,
+}
+
+/// A route of mint A for mint B through a mock pool, a spl-token-swap pool, the
+/// inventory and a second spl-token-swap pool twice as deep, in that order.
+fn setup(inventory_b: u64) -> RouteSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let mock_pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let spl_pools = [
+        harness.create_spl_token_swap_pool(&mint_a, &mint_b, 1_000_000, 1_000_000),
+        harness.create_spl_token_swap_pool(&mint_a, &mint_b, 2_000_000, 2_000_000),
+    ];
+    harness.create_config(&Pubkey::new_unique(), &Pubkey::default());
+    // 1 A for 1 B
+    let oracle = harness.create_pyth_price(100_000_000, -8, 0);
+    harness.create_inventory_pair(&mint_a, &mint_b, &oracle, 50);
+    let (authority, _) = Processor::find_inventory_authority(&harness.program_id);
+    let vault_a = harness.create_token_account(&mint_a, &authority, 0);
+    let vault_b = harness.create_token_account(&mint_b, &authority, inventory_b);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+
+    let legs = vec![
+        DexLeg {
+            dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+            accounts: mock_pool.leg_a_to_b(),
+        },
+        spl_pools[0].leg(&mint_a, None).unwrap(),
+        inventory_leg(
+            &harness.program_id,
+            &mint_a,
+            &mint_b,
+            &vault_a,
+            &vault_b,
+            &oracle,
+        ),
+        spl_pools[1].leg(&mint_a, None).unwrap(),
+    ];
+    RouteSetup {
+        harness,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        mock_pool,
+        spl_pools,
+        vault_a,
+        vault_b,
+        legs,
+    }
+}
+
+/// Swaps 40_000 of mint A, evenly across the legs of the route.
+fn route_swap(setup: &RouteSetup, minimum_amount_out: u64) -> Instruction {
+    let ratio = BPS_SCALE / setup.legs.len() as u16;
+    let legs: Vec<(DexLeg, u16)> = setup.legs.iter().map(|leg| (leg.clone(), ratio)).collect();
+    swap(
+        &setup.harness.program_id,
+        &setup.accounts,
+        40_000,
+        minimum_amount_out,
+        &legs,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_swap_across_adapters() {
+    let mut setup = setup(50_000);
+    let ix = route_swap(&setup, 39_000);
+    setup.harness.process(&ix).unwrap();
+
+    // 10_000 through each leg
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.mock_pool.token_a), 1_010_000);
+    assert_eq!(harness.token_balance(&setup.mock_pool.token_b), 990_100);
+    // fees 25 + 5, reserve B ceil(1e12 / 1_009_970) = 990_129
+    assert_eq!(
+        harness.token_balance(&setup.spl_pools[0].token_a),
+        1_010_000
+    );
+    assert_eq!(harness.token_balance(&setup.spl_pools[0].token_b), 990_129);
+    // the oracle price less the 50 bps spread
+    assert_eq!(harness.token_balance(&setup.vault_a), 10_000);
+    assert_eq!(harness.token_balance(&setup.vault_b), 40_050);
+    // fees 25 + 5, reserve B ceil(4e12 / 2_009_970) = 1_990_080
+    assert_eq!(
+        harness.token_balance(&setup.spl_pools[1].token_a),
+        2_010_000
+    );
+    assert_eq!(
+        harness.token_balance(&setup.spl_pools[1].token_b),
+        1_990_080
+    );
+
+    assert_eq!(harness.token_balance(&setup.accounts.source), 60_000);
+    assert_eq!(
+        harness.token_balance(&setup.accounts.destination),
+        9_900 + 9_871 + 9_950 + 9_920
+    );
+    assert_eq!(harness.token_balance(&setup.accounts.protocol_token), 0);
+}
+
+#[test]
+fn test_swap_across_adapters_reports_failed_leg() {
+    // an inventory too short to fill its leg, after two legs of other adapters
+    let mut setup = setup(100);
+    let ix = route_swap(&setup, 0);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        LegError::new(
+            2,
+            DEX_TYPE_INVENTORY,
+            &OneSolError::InsufficientInventory.into()
+        )
+        .into()
+    );
+}
+
+#[test]
+fn test_swap_across_adapters_checks_leg_accounts() {
+    // the inventory leg short of its config, shifting the accounts of the last leg
+    let mut setup = setup(50_000);
+    let mut ix = route_swap(&setup, 0);
+    let config_index = ix.accounts.len() - setup.legs[3].accounts.len() - 1;
+    ix.accounts.remove(config_index);
+    let dex_configs_offset = ix.data.len() - DexConfig::LEN * setup.legs.len();
+    ix.data[dex_configs_offset + 2 * DexConfig::LEN + 1] -= 1;
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::AccountCountMismatch.into()
+    );
+}
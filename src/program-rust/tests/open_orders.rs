@@ -0,0 +1,108 @@
+mod common;
+
+use common::Harness;
+use onesol::error::OneSolError;
+use onesol_client::{
+    dex::SerumMarket,
+    instruction::{close_open_orders, create_open_orders},
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+/// A Serum market owned by `dex_program_id`, whose data the checks don't read.
+fn market(harness: &mut Harness, dex_program_id: &Pubkey) -> SerumMarket {
+    let address = harness.add_account(Pubkey::new_unique(), *dex_program_id, vec![0u8; 388]);
+    SerumMarket {
+        address,
+        program_id: *dex_program_id,
+        vault_signer: Pubkey::new_unique(),
+        coin_mint: Pubkey::new_unique(),
+        pc_mint: Pubkey::new_unique(),
+        coin_vault: Pubkey::new_unique(),
+        pc_vault: Pubkey::new_unique(),
+        request_queue: Pubkey::new_unique(),
+        event_queue: Pubkey::new_unique(),
+        bids: Pubkey::new_unique(),
+        asks: Pubkey::new_unique(),
+    }
+}
+
+#[test]
+fn test_create_open_orders_checks_accounts() {
+    let mut harness = Harness::new();
+    let mint = harness.create_mint();
+    let protocol = harness.create_protocol(&mint);
+    let dex_program_id = Pubkey::new_unique();
+    let market = market(&mut harness, &dex_program_id);
+    let program_id = harness.program_id;
+    let ix = |admin: &Pubkey, authority: &Pubkey| {
+        create_open_orders(&program_id, admin, &protocol.protocol, authority, &market)
+    };
+
+    assert_eq!(
+        harness
+            .process(&ix(&Pubkey::new_unique(), &protocol.authority))
+            .unwrap_err(),
+        OneSolError::InvalidOwner.into()
+    );
+    let mut unsigned = ix(&protocol.admin, &protocol.authority);
+    unsigned.accounts[0].is_signer = false;
+    assert_eq!(
+        harness.process(&unsigned).unwrap_err(),
+        ProgramError::MissingRequiredSignature
+    );
+    assert_eq!(
+        harness
+            .process(&ix(&protocol.admin, &Pubkey::new_unique()))
+            .unwrap_err(),
+        OneSolError::InvalidProgramAddress.into()
+    );
+
+    // the open orders of another protocol account
+    let mut foreign = ix(&protocol.admin, &protocol.authority);
+    foreign.accounts[3].pubkey = Pubkey::new_unique();
+    assert_eq!(
+        harness.process(&foreign).unwrap_err(),
+        OneSolError::InvalidProgramAddress.into()
+    );
+
+    // a market of another program than the one passed
+    let mut foreign_dex = ix(&protocol.admin, &protocol.authority);
+    foreign_dex.accounts[6].pubkey = Pubkey::new_unique();
+    assert_eq!(
+        harness.process(&foreign_dex).unwrap_err(),
+        ProgramError::IncorrectProgramId
+    );
+}
+
+#[test]
+fn test_close_open_orders_checks_accounts() {
+    let mut harness = Harness::new();
+    let mint = harness.create_mint();
+    let protocol = harness.create_protocol(&mint);
+    let market = market(&mut harness, &Pubkey::new_unique());
+    let wallet = harness.create_token_account(&mint, &protocol.authority, 0);
+    let program_id = harness.program_id;
+    let ix = |admin: &Pubkey| {
+        close_open_orders(
+            &program_id,
+            admin,
+            &protocol.protocol,
+            &protocol.authority,
+            &market,
+            &wallet,
+            &wallet,
+            &protocol.admin,
+        )
+    };
+
+    assert_eq!(
+        harness.process(&ix(&Pubkey::new_unique())).unwrap_err(),
+        OneSolError::InvalidOwner.into()
+    );
+    let mut foreign = ix(&protocol.admin);
+    foreign.accounts[3].pubkey = Pubkey::new_unique();
+    assert_eq!(
+        harness.process(&foreign).unwrap_err(),
+        OneSolError::InvalidProgramAddress.into()
+    );
+}
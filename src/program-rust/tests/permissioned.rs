@@ -0,0 +1,246 @@
+#![cfg(feature = "permissioned")]
+
+mod common;
+
+use common::{Harness, TestPool};
+use onesol::{
+    error::OneSolError,
+    instruction::{DexConfig, DexConfigs, FillOrder, OneSolInstruction},
+    processor::Processor,
+    state::{Order, OrderKind, PairGate},
+};
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{configure_pair_gate, gate_swap, swap, SwapAccounts},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+
+struct GateSetup {
+    harness: Harness,
+    accounts: SwapAccounts,
+    pool: TestPool,
+    admin: Pubkey,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+}
+
+fn setup() -> GateSetup {
+    let mut harness = Harness::new();
+    let admin = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    harness.create_config(&admin, &Pubkey::default());
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    GateSetup {
+        harness,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        pool,
+        admin,
+        mint_a,
+        mint_b,
+    }
+}
+
+/// Flags the pair, requiring a credential of a fresh mint, returned.
+fn flag_pair(setup: &mut GateSetup) -> Pubkey {
+    let program_id = setup.harness.program_id;
+    let (gate, bump_seed) =
+        Processor::find_pair_gate_address(&program_id, &setup.mint_a, &setup.mint_b);
+    let mut data = vec![0u8; PairGate::LEN];
+    PairGate {
+        version: 1,
+        bump_seed,
+        source_mint: setup.mint_a,
+        destination_mint: setup.mint_b,
+        credential_mint: Pubkey::default(),
+        min_balance: 1,
+    }
+    .pack_into_slice(&mut data);
+    setup.harness.add_account(gate, program_id, data);
+
+    let credential_mint = setup.harness.create_mint();
+    let ix = configure_pair_gate(
+        &program_id,
+        &setup.admin,
+        &setup.mint_a,
+        &setup.mint_b,
+        &credential_mint,
+        1,
+    );
+    setup.harness.process(&ix).unwrap();
+    credential_mint
+}
+
+fn gated_swap(setup: &GateSetup, credential: &Pubkey) -> Instruction {
+    let leg = DexLeg {
+        dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+        accounts: setup.pool.leg_a_to_b(),
+    };
+    let mut ix = swap(
+        &setup.harness.program_id,
+        &setup.accounts,
+        10_000,
+        9_800,
+        &[(leg, 10_000)],
+    )
+    .unwrap();
+    gate_swap(&mut ix, &setup.mint_a, &setup.mint_b, credential);
+    ix
+}
+
+/// A limit order of the user selling 10_000 of mint A, escrowed, and a `FillOrder` of it
+/// through the pool taking the gate accounts with `credential`.
+fn gated_fill_order(setup: &mut GateSetup, credential: &Pubkey) -> Instruction {
+    let program_id = setup.harness.program_id;
+    let order = Pubkey::new_unique();
+    let (order_authority, nonce) = Processor::find_authority_address(&program_id, &order);
+    let escrow = setup
+        .harness
+        .create_token_account(&setup.mint_a, &order_authority, 10_000);
+    let mut data = vec![0u8; Order::LEN];
+    Order {
+        version: 1,
+        nonce,
+        kind: OrderKind::Limit,
+        owner: setup.accounts.user_transfer_authority,
+        token_program_id: spl_token::id(),
+        source_mint: setup.mint_a,
+        destination_mint: setup.mint_b,
+        escrow,
+        destination: setup.accounts.destination,
+        amount_in: 10_000,
+        expected_amount_out: 9_900,
+        max_deviation_bps: 100,
+        num_slices: 1,
+        slice_interval: 0,
+        start_time: 0,
+        filled_amount_in: 0,
+        filled_amount_out: 0,
+        slices_filled: 0,
+        oracle: Pubkey::default(),
+        trigger_price: 0,
+        expires_at_slot: u64::MAX,
+        keeper_fee_bps: 0,
+        keeper_fee_flat: 0,
+        page: Pubkey::default(),
+    }
+    .pack_into_slice(&mut data);
+    setup.harness.add_account(order, program_id, data);
+
+    let leg = setup.pool.leg_a_to_b();
+    let mut configs = vec![];
+    DexConfig::new_dex_config(0, leg.len(), 10_000)
+        .pack(&mut configs)
+        .unwrap();
+    let data = OneSolInstruction::FillOrder(FillOrder {
+        amount_in: 10_000,
+        dex_configs: DexConfigs::new(&configs).unwrap(),
+    })
+    .pack();
+    let mut accounts = vec![
+        AccountMeta::new(order, false),
+        AccountMeta::new_readonly(order_authority, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new(setup.accounts.destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(escrow, false),
+    ];
+    accounts.extend(leg);
+    let mut ix = Instruction {
+        program_id,
+        accounts,
+        data,
+    };
+    gate_swap(&mut ix, &setup.mint_a, &setup.mint_b, credential);
+    ix
+}
+
+#[test]
+fn test_open_pair() {
+    let mut setup = setup();
+    let ix = gated_swap(&setup, &setup.accounts.source);
+    setup.harness.process(&ix).unwrap();
+    assert_eq!(
+        setup.harness.token_balance(&setup.accounts.destination),
+        9_900
+    );
+}
+
+#[test]
+fn test_flagged_pair_without_credential() {
+    let mut setup = setup();
+    flag_pair(&mut setup);
+    let ix = gated_swap(&setup, &setup.accounts.source);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::MissingCredential.into()
+    );
+}
+
+#[test]
+fn test_flagged_pair_with_credential() {
+    let mut setup = setup();
+    let credential_mint = flag_pair(&mut setup);
+    let user = setup.accounts.user_transfer_authority;
+    let credential = setup
+        .harness
+        .create_token_account(&credential_mint, &user, 1);
+    let ix = gated_swap(&setup, &credential);
+    setup.harness.process(&ix).unwrap();
+    assert_eq!(
+        setup.harness.token_balance(&setup.accounts.destination),
+        9_900
+    );
+}
+
+#[test]
+fn test_swap_without_gate() {
+    let mut setup = setup();
+    let mut ix = gated_swap(&setup, &setup.accounts.source);
+    ix.accounts.truncate(ix.accounts.len() - 2);
+    assert!(setup.harness.process(&ix).is_err());
+}
+
+#[test]
+fn test_fill_order_on_flagged_pair_without_credential() {
+    let mut setup = setup();
+    flag_pair(&mut setup);
+    let source = setup.accounts.source;
+    let ix = gated_fill_order(&mut setup, &source);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::MissingCredential.into()
+    );
+}
+
+#[test]
+fn test_fill_order_on_flagged_pair_with_credential() {
+    let mut setup = setup();
+    let credential_mint = flag_pair(&mut setup);
+    let user = setup.accounts.user_transfer_authority;
+    let credential = setup
+        .harness
+        .create_token_account(&credential_mint, &user, 1);
+    let ix = gated_fill_order(&mut setup, &credential);
+    setup.harness.process(&ix).unwrap();
+    assert_eq!(
+        setup.harness.token_balance(&setup.accounts.destination),
+        9_900
+    );
+}
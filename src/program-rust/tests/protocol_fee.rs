@@ -0,0 +1,157 @@
+mod common;
+
+use common::{swap_instruction, Harness, TestPool, TestProtocol, TestSwapAccounts};
+use onesol::{error::OneSolError, state::OneSolState};
+use onesol_client::instruction::{pay_protocol_fee, set_fee_rate};
+use solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+
+struct FeeSetup {
+    harness: Harness,
+    protocol: TestProtocol,
+    pool: TestPool,
+    user: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+    treasury: Pubkey,
+}
+
+/// A protocol swapping mint A for mint B, its admin having set a fee of 25 bps.
+fn setup() -> FeeSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let treasury = harness.create_token_account(&mint_b, &Pubkey::new_unique(), 0);
+    let ix = set_fee_rate(
+        &harness.program_id,
+        &protocol.admin,
+        &protocol.protocol,
+        &treasury,
+        25,
+        10_000,
+    );
+    harness.process(&ix).unwrap();
+    FeeSetup {
+        harness,
+        protocol,
+        pool,
+        user,
+        source,
+        destination,
+        treasury,
+    }
+}
+
+fn fee_swap(setup: &FeeSetup, minimum_amount_out: u64) -> Instruction {
+    swap_instruction(
+        &setup.harness.program_id,
+        &setup.protocol,
+        &TestSwapAccounts {
+            user: setup.user,
+            source: setup.source,
+            destination: setup.destination,
+        },
+        10_000,
+        minimum_amount_out,
+        &[(setup.pool.leg_a_to_b(), 10_000)],
+    )
+}
+
+#[test]
+fn test_swap_pays_protocol_fee() {
+    let mut setup = setup();
+    let state = OneSolState::unpack(&setup.harness.account(&setup.protocol.protocol).data).unwrap();
+    assert_eq!(state.fee_numerator, 25);
+    assert_eq!(state.fee_denominator, 10_000);
+    assert_eq!(state.treasury, setup.treasury);
+
+    let mut ix = fee_swap(&setup, 9_876);
+    pay_protocol_fee(&mut ix, &setup.treasury);
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    // 9_900 out, 24.75 of it owed to the treasury
+    assert_eq!(harness.token_balance(&setup.destination), 9_876);
+    assert_eq!(harness.token_balance(&setup.treasury), 24);
+    assert_eq!(harness.token_balance(&setup.protocol.token), 0);
+}
+
+#[test]
+fn test_swap_minimum_bounds_output_net_of_fee() {
+    let mut setup = setup();
+    let mut ix = fee_swap(&setup, 9_877);
+    pay_protocol_fee(&mut ix, &setup.treasury);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::ExceededSlippage.into()
+    );
+}
+
+#[test]
+fn test_swap_requires_treasury() {
+    let mut setup = setup();
+    let ix = fee_swap(&setup, 0);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidTreasury.into()
+    );
+}
+
+#[test]
+fn test_set_fee_rate_checks_admin_and_treasury() {
+    let mut setup = setup();
+    let program_id = setup.harness.program_id;
+    let ix = set_fee_rate(
+        &program_id,
+        &Pubkey::new_unique(),
+        &setup.protocol.protocol,
+        &setup.treasury,
+        0,
+        0,
+    );
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidOwner.into()
+    );
+
+    // a treasury of the SOURCE mint
+    let ix = set_fee_rate(
+        &program_id,
+        &setup.protocol.admin,
+        &setup.protocol.protocol,
+        &setup.source,
+        0,
+        0,
+    );
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidInput.into()
+    );
+}
+
+#[test]
+fn test_set_fee_rate_checks_rate() {
+    let mut setup = setup();
+    let program_id = setup.harness.program_id;
+    // a fee above the output, and a fee over no denominator
+    for (fee_numerator, fee_denominator) in [(10_001, 10_000), (25, 0)].iter() {
+        let ix = set_fee_rate(
+            &program_id,
+            &setup.protocol.admin,
+            &setup.protocol.protocol,
+            &setup.treasury,
+            *fee_numerator,
+            *fee_denominator,
+        );
+        assert_eq!(
+            setup.harness.process(&ix).unwrap_err(),
+            OneSolError::InvalidInput.into()
+        );
+    }
+    let state = OneSolState::unpack(&setup.harness.account(&setup.protocol.protocol).data).unwrap();
+    assert_eq!((state.fee_numerator, state.fee_denominator), (25, 10_000));
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::Harness;
+use onesol::{error::OneSolError, instruction::MAX_LEGS, state::QuoteResult};
+use onesol_client::{
+    dex::{DexLeg, SplTokenSwapPool, DEX_TYPE_SERUM_DEX},
+    instruction::{quote, swap, SwapAccounts},
+};
+use solana_program::{instruction::AccountMeta, program_pack::Pack, pubkey::Pubkey};
+
+fn leg(pool: &SplTokenSwapPool) -> DexLeg {
+    pool.leg(&pool.token_a_mint, None).unwrap()
+}
+
+#[test]
+fn test_quote_matches_swap() {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool1 = harness.create_spl_token_swap_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let pool2 = harness.create_spl_token_swap_pool(&mint_a, &mint_b, 3_000_000, 3_000_000);
+    let legs = [(leg(&pool1), 2_500), (leg(&pool2), 7_500)];
+
+    let ix = quote(&harness.program_id, 40_000, &legs).unwrap();
+    harness.process(&ix).unwrap();
+    let quoted = QuoteResult::unpack_unchecked(&harness.return_data().unwrap()).unwrap();
+    assert_eq!(quoted.legs_quoted, 2);
+    assert_eq!(
+        quoted.amount_out,
+        quoted.leg_amounts_out[0] + quoted.leg_amounts_out[1]
+    );
+    assert_eq!(quoted.leg_amounts_out[2..], [0; MAX_LEGS - 2]);
+    // quoting moves no tokens
+    assert_eq!(harness.token_balance(&pool1.token_a), 1_000_000);
+
+    // the swap through the same legs outputs the quote
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 40_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let accounts = SwapAccounts {
+        protocol: protocol.protocol,
+        protocol_authority: protocol.authority,
+        user_transfer_authority: user,
+        protocol_token: protocol.token,
+        source,
+        destination,
+        token_program_id: spl_token::id(),
+    };
+    let ix = swap(&harness.program_id, &accounts, 40_000, 1, &legs).unwrap();
+    harness.process(&ix).unwrap();
+    assert_eq!(harness.token_balance(&destination), quoted.amount_out);
+}
+
+#[test]
+fn test_quote_unquotable_leg() {
+    let mut harness = Harness::new();
+    let serum_leg = DexLeg {
+        dex_type: DEX_TYPE_SERUM_DEX,
+        accounts: (0..11)
+            .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+            .collect(),
+    };
+    let ix = quote(&harness.program_id, 40_000, &[(serum_leg, 10_000)]).unwrap();
+    assert_eq!(
+        harness.process(&ix).unwrap_err(),
+        OneSolError::QuoteUnavailable.into()
+    );
+    assert_eq!(harness.return_data(), None);
+}
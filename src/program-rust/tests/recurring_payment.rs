@@ -0,0 +1,171 @@
+mod common;
+
+use common::{Harness, TestPool};
+use onesol::{
+    error::OneSolError,
+    instruction::{
+        CreateRecurringPayment, DexConfig, DexConfigs, ExecuteRecurringPayment, OneSolInstruction,
+    },
+    processor::Processor,
+    state::RecurringPayment,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+
+struct PaymentSetup {
+    harness: Harness,
+    pool: TestPool,
+    payment: Pubkey,
+    payment_authority: Pubkey,
+    payer: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+    escrow: Pubkey,
+    escrow_authority: Pubkey,
+}
+
+/// A payer paying a merchant 10_000 of mint A in mint B every day, at most 30_000 of
+/// mint A in all, swapped through a pool of the pair.
+fn setup() -> PaymentSetup {
+    let mut harness = Harness::new();
+    let program_id = harness.program_id;
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let payer = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &payer, 100_000);
+    let destination = harness.create_token_account(&mint_b, &Pubkey::new_unique(), 0);
+    let payment = harness.add_account(
+        Pubkey::new_unique(),
+        program_id,
+        vec![0; RecurringPayment::LEN],
+    );
+    let (payment_authority, nonce) = Processor::find_authority_address(&program_id, &payment);
+    let escrow = harness.create_token_account(&mint_a, &Pubkey::default(), 0);
+    let (escrow_authority, escrow_nonce) = Processor::find_authority_address(&program_id, &escrow);
+    harness.set_token_owner(&escrow, &escrow_authority);
+
+    let data = OneSolInstruction::CreateRecurringPayment(CreateRecurringPayment {
+        nonce,
+        amount_in: 10_000,
+        minimum_amount_out: 9_800,
+        period: 86_400,
+        max_total_in: 30_000,
+        escrow_nonce,
+    })
+    .pack();
+    let create = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(payment, false),
+            AccountMeta::new_readonly(payment_authority, false),
+            AccountMeta::new_readonly(payer, true),
+            AccountMeta::new(source, false),
+            AccountMeta::new_readonly(destination, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(escrow, false),
+        ],
+        data,
+    };
+    harness.process(&create).unwrap();
+    PaymentSetup {
+        harness,
+        pool,
+        payment,
+        payment_authority,
+        payer,
+        source,
+        destination,
+        escrow,
+        escrow_authority,
+    }
+}
+
+/// `ExecuteRecurringPayment` swapping through `leg`.
+fn execute(setup: &PaymentSetup, leg: Vec<AccountMeta>) -> Instruction {
+    let mut configs = vec![];
+    DexConfig::new_dex_config(0, leg.len(), 10_000)
+        .pack(&mut configs)
+        .unwrap();
+    let data = OneSolInstruction::ExecuteRecurringPayment(ExecuteRecurringPayment {
+        dex_configs: DexConfigs::new(&configs).unwrap(),
+    })
+    .pack();
+    let mut accounts = vec![
+        AccountMeta::new(setup.payment, false),
+        AccountMeta::new_readonly(setup.payment_authority, false),
+        AccountMeta::new(setup.source, false),
+        AccountMeta::new(setup.destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(setup.escrow, false),
+        AccountMeta::new_readonly(setup.escrow_authority, false),
+    ];
+    accounts.extend(leg);
+    Instruction {
+        program_id: setup.harness.program_id,
+        accounts,
+        data,
+    }
+}
+
+#[test]
+fn test_execute_recurring_payment_swaps_from_escrow() {
+    let mut setup = setup();
+    let ix = execute(&setup, setup.pool.leg_a_to_b());
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.source), 90_000);
+    assert_eq!(harness.token_balance(&setup.escrow), 0);
+    assert_eq!(harness.token_balance(&setup.destination), 9_900);
+    let payment = RecurringPayment::unpack(&harness.account(&setup.payment).data).unwrap();
+    assert_eq!(payment.total_in, 10_000);
+    assert_eq!(payment.next_payment_time, 86_400);
+
+    // the next period isn't due yet
+    assert_eq!(
+        setup.harness.process(&ix),
+        Err(OneSolError::PaymentNotDue.into())
+    );
+}
+
+#[test]
+fn test_execute_recurring_payment_checks_escrow_authority() {
+    let mut setup = setup();
+    let mut ix = execute(&setup, setup.pool.leg_a_to_b());
+    // legs signed by the payment authority, delegated the whole cap
+    ix.accounts[6].pubkey = setup.payment_authority;
+    assert_eq!(
+        setup.harness.process(&ix),
+        Err(OneSolError::InvalidProgramAddress.into())
+    );
+}
+
+#[test]
+fn test_cancel_recurring_payment_closes_escrow() {
+    let mut setup = setup();
+    // tokens sent to the escrow outside of a payment go back to the payer
+    setup.harness.set_token_balance(&setup.escrow, 500);
+    let cancel = Instruction {
+        program_id: setup.harness.program_id,
+        accounts: vec![
+            AccountMeta::new(setup.payment, false),
+            AccountMeta::new(setup.payer, true),
+            AccountMeta::new(setup.source, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(setup.escrow, false),
+            AccountMeta::new_readonly(setup.escrow_authority, false),
+        ],
+        data: OneSolInstruction::CancelRecurringPayment.pack(),
+    };
+    setup.harness.process(&cancel).unwrap();
+
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.source), 100_500);
+    assert_eq!(harness.account(&setup.escrow).lamports, 0);
+    assert_eq!(harness.account(&setup.payment).lamports, 0);
+    assert_eq!(harness.account(&setup.payer).lamports, 3_000_000_000);
+}
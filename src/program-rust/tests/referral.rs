@@ -0,0 +1,147 @@
+mod common;
+
+use common::{swap_instruction, Harness, TestPool, TestProtocol, TestSwapAccounts};
+use onesol::{error::OneSolError, state::OneSolState};
+use onesol_client::instruction::{
+    pay_protocol_fee, refer_swap, set_fee_rate, set_referral_fee_cap,
+};
+use solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+
+struct ReferralSetup {
+    harness: Harness,
+    protocol: TestProtocol,
+    pool: TestPool,
+    user: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+    treasury: Pubkey,
+    referrer: Pubkey,
+}
+
+/// A protocol swapping mint A for mint B, its admin having set a fee of 25 bps and
+/// capped referral fees at 50 bps.
+fn setup() -> ReferralSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let treasury = harness.create_token_account(&mint_b, &Pubkey::new_unique(), 0);
+    let referrer = harness.create_token_account(&mint_b, &Pubkey::new_unique(), 0);
+    let program_id = harness.program_id;
+    let (admin, protocol_key) = (protocol.admin, protocol.protocol);
+    let ix = set_fee_rate(&program_id, &admin, &protocol_key, &treasury, 25, 10_000);
+    harness.process(&ix).unwrap();
+    let ix = set_referral_fee_cap(&program_id, &admin, &protocol_key, 50);
+    harness.process(&ix).unwrap();
+    ReferralSetup {
+        harness,
+        protocol,
+        pool,
+        user,
+        source,
+        destination,
+        treasury,
+        referrer,
+    }
+}
+
+fn referred_swap(
+    setup: &ReferralSetup,
+    referrer: &Pubkey,
+    referral_fee_bps: u16,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let mut ix = swap_instruction(
+        &setup.harness.program_id,
+        &setup.protocol,
+        &TestSwapAccounts {
+            user: setup.user,
+            source: setup.source,
+            destination: setup.destination,
+        },
+        10_000,
+        minimum_amount_out,
+        &[(setup.pool.leg_a_to_b(), 10_000)],
+    );
+    refer_swap(&mut ix, referrer, referral_fee_bps).unwrap();
+    pay_protocol_fee(&mut ix, &setup.treasury);
+    ix
+}
+
+#[test]
+fn test_swap_pays_referrer() {
+    let mut setup = setup();
+    let state = OneSolState::unpack(&setup.harness.account(&setup.protocol.protocol).data).unwrap();
+    assert_eq!(state.max_referral_fee_bps, 50);
+
+    let ix = referred_swap(&setup, &setup.referrer, 20, 9_857);
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    // 9_900 out, 24 of it to the treasury, then 19.75 of the 9_876 left to the referrer
+    assert_eq!(harness.token_balance(&setup.treasury), 24);
+    assert_eq!(harness.token_balance(&setup.referrer), 19);
+    assert_eq!(harness.token_balance(&setup.destination), 9_857);
+    assert_eq!(harness.token_balance(&setup.protocol.token), 0);
+}
+
+#[test]
+fn test_swap_minimum_bounds_output_net_of_referral_fee() {
+    let mut setup = setup();
+    let ix = referred_swap(&setup, &setup.referrer, 20, 9_858);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::ExceededSlippage.into()
+    );
+}
+
+#[test]
+fn test_swap_referral_fee_capped() {
+    let mut setup = setup();
+    let ix = referred_swap(&setup, &setup.referrer, 51, 0);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::ReferralFeeTooHigh.into()
+    );
+
+    // a cap of 0 disables referral fees
+    let program_id = setup.harness.program_id;
+    let (admin, protocol) = (setup.protocol.admin, setup.protocol.protocol);
+    let ix = set_referral_fee_cap(&program_id, &admin, &protocol, 0);
+    setup.harness.process(&ix).unwrap();
+    let ix = referred_swap(&setup, &setup.referrer, 1, 0);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::ReferralFeeTooHigh.into()
+    );
+}
+
+#[test]
+fn test_swap_rejects_referrer_of_other_mint() {
+    let mut setup = setup();
+    let ix = referred_swap(&setup, &setup.source.clone(), 20, 0);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidInput.into()
+    );
+}
+
+#[test]
+fn test_set_referral_fee_cap_requires_admin() {
+    let mut setup = setup();
+    let program_id = setup.harness.program_id;
+    let ix = set_referral_fee_cap(
+        &program_id,
+        &Pubkey::new_unique(),
+        &setup.protocol.protocol,
+        100,
+    );
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidOwner.into()
+    );
+}
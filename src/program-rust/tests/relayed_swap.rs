@@ -0,0 +1,110 @@
+mod common;
+
+use common::{Harness, TestPool};
+use onesol::error::OneSolError;
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{relayed_swap, SwapAccounts},
+};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+struct RelayedSetup {
+    harness: Harness,
+    accounts: SwapAccounts,
+    pool: TestPool,
+    relayer_token: Pubkey,
+}
+
+fn setup() -> RelayedSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let admin = Pubkey::new_unique();
+    harness.create_config(&admin, &Pubkey::default());
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let relayer = Pubkey::new_unique();
+    let relayer_token = harness.create_token_account(&mint_b, &relayer, 0);
+    RelayedSetup {
+        harness,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        pool,
+        relayer_token,
+    }
+}
+
+fn relayed_instruction(
+    setup: &RelayedSetup,
+    minimum_amount_out: u64,
+    fee_bps: u16,
+    fee_flat: u64,
+) -> Instruction {
+    let leg = DexLeg {
+        dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+        accounts: setup.pool.leg_a_to_b(),
+    };
+    relayed_swap(
+        &setup.harness.program_id,
+        &setup.accounts,
+        &setup.relayer_token,
+        10_000,
+        minimum_amount_out,
+        fee_bps,
+        fee_flat,
+        &[(leg, 10_000)],
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_relayed_swap_reimburses_relayer() {
+    let mut setup = setup();
+    let ix = relayed_instruction(&setup, 9_700, 30, 100);
+    setup.harness.process(&ix).unwrap();
+
+    // 9_900 out, 100 flat plus 30 bps of it to the relayer
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.accounts.source), 90_000);
+    assert_eq!(harness.token_balance(&setup.relayer_token), 129);
+    assert_eq!(harness.token_balance(&setup.accounts.destination), 9_771);
+    assert_eq!(harness.token_balance(&setup.accounts.protocol_token), 0);
+}
+
+#[test]
+fn test_relayed_swap_rejects_fee_above_cap() {
+    let mut setup = setup();
+    let ix = relayed_instruction(&setup, 9_000, 31, 0);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::RelayerFeeTooHigh.into()
+    );
+
+    let mut setup = self::setup();
+    let ix = relayed_instruction(&setup, 9_000, 0, 1_001);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::RelayerFeeTooHigh.into()
+    );
+}
+
+#[test]
+fn test_relayed_swap_bounds_net_output() {
+    let mut setup = setup();
+    // the gross output clears the minimum, the output net of the reimbursement does not
+    let ix = relayed_instruction(&setup, 9_800, 30, 100);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::ExceededSlippage.into()
+    );
+}
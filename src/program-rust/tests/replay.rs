@@ -0,0 +1,121 @@
+//! Self-consistency check of the replay harness: replays the transactions recorded under
+//! `tests/fixtures/synthetic_snapshots` against the current build, flagging any
+//! divergence from the recorded outcome.
+//!
+//! A recording is a directory as [Harness::record] writes it: the address of the program
+//! in `program_id`, the dumps of the accounts of the transaction before it under
+//! `accounts`, and the transaction in `transaction.json` as `getTransaction` returns it
+//! with the `json` encoding.
+//!
+//! The snapshots there are synthetic: [Harness::record] generated them in-process, against
+//! accounts set up by the harness at the made-up slot 120000000. Replaying them checks the
+//! build against recordings the harness made itself, not against mainnet, so it is no
+//! regression check of a deployed program. No cluster recordings are checked in yet; one
+//! takes the same layout, with the account dumps written by
+//! `solana account <ADDRESS> --output json --output-file accounts/<ADDRESS>.json` at the
+//! slot before the transaction.
+
+mod common;
+
+use common::Harness;
+use onesol_client::instruction::{swap, SwapAccounts};
+use serde_json::{json, Value};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[test]
+fn test_replay_is_self_consistent() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/synthetic_snapshots");
+    let mut recordings: Vec<PathBuf> = fs::read_dir(&fixtures)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    recordings.sort();
+    assert!(!recordings.is_empty());
+
+    let mut divergences = vec![];
+    for recording in recordings.iter() {
+        let name = recording.file_name().unwrap().to_string_lossy();
+        for divergence in Harness::new().replay(recording) {
+            divergences.push(format!("{}: {}", name, divergence));
+        }
+    }
+    assert!(divergences.is_empty(), "{:#?}", divergences);
+}
+
+/// Sets up a swap of 1_000_000 mint A for mint B through an spl-token-swap pool,
+/// requiring `minimum_amount_out`.
+fn spl_token_swap(harness: &mut Harness, minimum_amount_out: u64) -> Instruction {
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_spl_token_swap_pool(&mint_a, &mint_b, 50_000_000, 200_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let accounts = SwapAccounts {
+        protocol: protocol.protocol,
+        protocol_authority: protocol.authority,
+        user_transfer_authority: user,
+        protocol_token: protocol.token,
+        source: harness.create_token_account(&mint_a, &user, 1_000_000),
+        destination: harness.create_token_account(&mint_b, &user, 0),
+        token_program_id: spl_token::id(),
+    };
+    let leg = pool.leg(&mint_a, None).unwrap();
+    swap(
+        &harness.program_id,
+        &accounts,
+        1_000_000,
+        minimum_amount_out,
+        &[(leg, 10_000)],
+    )
+    .unwrap()
+}
+
+fn read_transaction(dir: &Path) -> Value {
+    serde_json::from_slice(&fs::read(dir.join("transaction.json")).unwrap()).unwrap()
+}
+
+fn write_transaction(dir: &Path, transaction: &Value) {
+    fs::write(dir.join("transaction.json"), transaction.to_string()).unwrap();
+}
+
+#[test]
+fn test_replay_flags_divergences() {
+    let dir = std::env::temp_dir().join(format!("onesol-replay-{}", Pubkey::new_unique()));
+    let mut harness = Harness::new();
+    let ix = spl_token_swap(&mut harness, 0);
+    harness.record(&dir, &[ix], 100);
+    let recorded = read_transaction(&dir);
+    assert!(recorded["meta"]["err"].is_null());
+    assert!(Harness::new().replay(&dir).is_empty());
+
+    // another output than the recorded one
+    let mut transaction = recorded.clone();
+    for balance in transaction["meta"]["postTokenBalances"]
+        .as_array_mut()
+        .unwrap()
+        .iter_mut()
+    {
+        balance["uiTokenAmount"]["amount"] = json!("1");
+    }
+    write_transaction(&dir, &transaction);
+    let divergences = Harness::new().replay(&dir);
+    assert!(!divergences.is_empty());
+    assert!(divergences
+        .iter()
+        .all(|divergence| divergence.starts_with("balance of")));
+
+    // another error than the recorded one
+    let mut transaction = recorded;
+    transaction["meta"]["err"] = json!({ "InstructionError": [0, { "Custom": 16 }] });
+    write_transaction(&dir, &transaction);
+    assert_eq!(
+        Harness::new().replay(&dir),
+        vec![r#"error: recorded {"InstructionError":[0,{"Custom":16}]}, replayed null"#]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
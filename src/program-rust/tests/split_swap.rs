@@ -0,0 +1,183 @@
+mod common;
+
+use common::{swap_instruction, Harness, TestProtocol, TestSwapAccounts};
+use onesol::{
+    error::{LegError, OneSolError},
+    instruction::ConfigParams,
+    state::LogLevel,
+};
+use onesol_client::{
+    dex::{SplTokenSwapPool, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{split_swap, update_config},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+struct SplitSetup {
+    harness: Harness,
+    protocol: TestProtocol,
+    user: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+    pool1: SplTokenSwapPool,
+    pool2: SplTokenSwapPool,
+}
+
+/// Two spl-token-swap pools of mint A / mint B, the first twice as deep as the second,
+/// both charging 0.25% trade and 0.05% owner fees.
+fn setup() -> SplitSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool1 = harness.create_spl_token_swap_pool(&mint_a, &mint_b, 2_000_000, 2_000_000);
+    let pool2 = harness.create_spl_token_swap_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 200_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    SplitSetup {
+        harness,
+        protocol,
+        user,
+        source,
+        destination,
+        pool1,
+        pool2,
+    }
+}
+
+/// Swaps 100_000 of mint A, 70% through the first pool and 30% through the second.
+fn split_instruction(setup: &SplitSetup, minimum_amount_out: u64) -> Instruction {
+    let legs = [&setup.pool1, &setup.pool2]
+        .iter()
+        .map(|pool| pool.leg(&pool.token_a_mint, None).unwrap().accounts)
+        .zip([7_000, 3_000].iter().copied())
+        .collect::<Vec<_>>();
+    swap_instruction(
+        &setup.harness.program_id,
+        &setup.protocol,
+        &TestSwapAccounts {
+            user: setup.user,
+            source: setup.source,
+            destination: setup.destination,
+        },
+        100_000,
+        minimum_amount_out,
+        &legs,
+    )
+}
+
+#[test]
+fn test_split_swap_70_30() {
+    let mut setup = setup();
+    let ix = split_instruction(&setup, 95_000);
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.source), 100_000);
+    // 70_000 in, fees 175 + 35, reserve B ceil(4e12 / 2_069_790) = 1_932_564
+    assert_eq!(harness.token_balance(&setup.pool1.token_a), 2_070_000);
+    assert_eq!(harness.token_balance(&setup.pool1.token_b), 1_932_564);
+    // 30_000 in, fees 75 + 15, reserve B ceil(1e12 / 1_029_910) = 970_959
+    assert_eq!(harness.token_balance(&setup.pool2.token_a), 1_030_000);
+    assert_eq!(harness.token_balance(&setup.pool2.token_b), 970_959);
+    assert_eq!(harness.token_balance(&setup.destination), 67_436 + 29_041);
+    assert_eq!(harness.token_balance(&setup.protocol.token), 0);
+}
+
+#[test]
+fn test_split_swap_exceeded_slippage() {
+    let mut setup = setup();
+    // the first leg falls short of its 70% of the minimum
+    let ix = split_instruction(&setup, 96_478);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        LegError::new(
+            0,
+            DEX_TYPE_SPL_TOKEN_SWAP,
+            &OneSolError::ExceededSlippage.into()
+        )
+        .into()
+    );
+    assert_eq!(setup.harness.token_balance(&setup.destination), 0);
+}
+
+/// Passes `setup` a global config splitting swaps into `split_parts` chunks.
+fn pass_split_config(setup: &mut SplitSetup, ix: &mut Instruction, split_parts: u8) {
+    let admin = Pubkey::new_unique();
+    let config = setup.harness.create_config(&admin, &Pubkey::default());
+    let params = ConfigParams {
+        oracle_max_age_slots: 25,
+        oracle_conf_multiplier: 0,
+        log_level: LogLevel::Full,
+        tip_account: Pubkey::default(),
+        relayer_fee_max_bps: 0,
+        relayer_fee_max_flat: 0,
+        split_parts,
+    };
+    let program_id = setup.harness.program_id;
+    setup
+        .harness
+        .process(&update_config(&program_id, &admin, &params))
+        .unwrap();
+    ix.accounts.push(AccountMeta::new_readonly(config, false));
+}
+
+#[test]
+fn test_split_swap_optimized() {
+    let mut setup = setup();
+    // the route's ratios are replaced by the split the pools quote best
+    let legs = [&setup.pool1, &setup.pool2]
+        .iter()
+        .map(|pool| (pool.leg(&pool.token_a_mint, None).unwrap().accounts, 5_000))
+        .collect::<Vec<_>>();
+    let mut ix = swap_instruction(
+        &setup.harness.program_id,
+        &setup.protocol,
+        &TestSwapAccounts {
+            user: setup.user,
+            source: setup.source,
+            destination: setup.destination,
+        },
+        100_000,
+        96_477,
+        &legs,
+    );
+    pass_split_config(&mut setup, &mut ix, 10);
+    setup.harness.process(&ix).unwrap();
+
+    // 7 chunks into the deeper pool, 3 into the other, as in test_split_swap_70_30
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.source), 100_000);
+    assert_eq!(harness.token_balance(&setup.pool1.token_a), 2_070_000);
+    assert_eq!(harness.token_balance(&setup.pool2.token_a), 1_030_000);
+    assert_eq!(harness.token_balance(&setup.destination), 67_436 + 29_041);
+}
+
+#[test]
+fn test_split_swap_optimized_by_the_swap() {
+    let mut setup = setup();
+    // no global config, the swap opts into the split itself
+    let mut ix = split_instruction(&setup, 96_477);
+    split_swap(&mut ix, 10).unwrap();
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.pool1.token_a), 2_070_000);
+    assert_eq!(harness.token_balance(&setup.pool2.token_a), 1_030_000);
+    assert_eq!(harness.token_balance(&setup.destination), 67_436 + 29_041);
+}
+
+#[test]
+fn test_split_swap_optimized_checks_route_minimum() {
+    let mut setup = setup();
+    let mut ix = split_instruction(&setup, 96_478);
+    pass_split_config(&mut setup, &mut ix, 10);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::ExceededSlippage.into()
+    );
+    assert_eq!(setup.harness.token_balance(&setup.destination), 0);
+}
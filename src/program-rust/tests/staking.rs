@@ -0,0 +1,195 @@
+mod common;
+
+use common::Harness;
+use onesol::{
+    error::OneSolError,
+    instruction::flash_fee,
+    processor::Processor,
+    state::{StakeAccount, StakePool, StakeTier},
+};
+use onesol_client::instruction::{
+    discount_by_stake, flash_repay, fund_stake_rewards, stake, unstake, StakeVaults,
+};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+
+struct StakingSetup {
+    harness: Harness,
+    admin: Pubkey,
+    staker: Pubkey,
+    staker_token: Pubkey,
+    admin_token: Pubkey,
+    vaults: StakeVaults,
+}
+
+fn setup() -> StakingSetup {
+    let mut harness = Harness::new();
+    let admin = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    harness.create_config(&admin, &Pubkey::default());
+    let mint = harness.create_mint();
+    let vaults = harness.create_stake_pool(
+        &mint,
+        [
+            StakeTier {
+                min_stake: 1_000,
+                discount_bps: 1_000,
+            },
+            StakeTier {
+                min_stake: 10_000,
+                discount_bps: 2_500,
+            },
+            StakeTier::default(),
+        ],
+    );
+    let staker = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    harness.create_stake_account(&staker);
+    let staker_token = harness.create_token_account(&mint, &staker, 5_000);
+    let admin_token = harness.create_token_account(&mint, &admin, 1_000);
+    StakingSetup {
+        harness,
+        admin,
+        staker,
+        staker_token,
+        admin_token,
+        vaults,
+    }
+}
+
+fn stake_account(setup: &StakingSetup) -> StakeAccount {
+    let (stake, _) = Processor::find_stake_address(&setup.harness.program_id, &setup.staker);
+    StakeAccount::unpack(&setup.harness.account(&stake).data).unwrap()
+}
+
+fn stake_pool(setup: &StakingSetup) -> StakePool {
+    let (pool, _) = Processor::find_stake_pool_address(&setup.harness.program_id);
+    StakePool::unpack(&setup.harness.account(&pool).data).unwrap()
+}
+
+#[test]
+fn test_stake_rewards() {
+    let mut setup = setup();
+    let program_id = setup.harness.program_id;
+    let (staker, staker_token) = (setup.staker, setup.staker_token);
+    let ix = stake(
+        &program_id,
+        &setup.vaults,
+        &staker,
+        &staker_token,
+        &staker_token,
+        4_000,
+    );
+    setup.harness.process(&ix).unwrap();
+    assert_eq!(setup.harness.token_balance(&setup.vaults.vault), 4_000);
+    assert_eq!(stake_account(&setup).amount, 4_000);
+    assert_eq!(stake_pool(&setup).total_staked, 4_000);
+
+    let ix = fund_stake_rewards(
+        &program_id,
+        &setup.admin,
+        &setup.admin_token,
+        &setup.vaults.rewards_vault,
+        1_000,
+    );
+    setup.harness.process(&ix).unwrap();
+    assert_eq!(
+        setup.harness.token_balance(&setup.vaults.rewards_vault),
+        1_000
+    );
+
+    // unstaking pays the rewards out along
+    let ix = unstake(
+        &program_id,
+        &setup.vaults,
+        &staker,
+        &staker_token,
+        &staker_token,
+        1_500,
+    );
+    setup.harness.process(&ix).unwrap();
+    assert_eq!(setup.harness.token_balance(&staker_token), 3_500);
+    assert_eq!(setup.harness.token_balance(&setup.vaults.vault), 2_500);
+    assert_eq!(setup.harness.token_balance(&setup.vaults.rewards_vault), 0);
+    assert_eq!(stake_account(&setup).amount, 2_500);
+
+    let ix = unstake(
+        &program_id,
+        &setup.vaults,
+        &staker,
+        &staker_token,
+        &staker_token,
+        2_501,
+    );
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InsufficientStake.into()
+    );
+}
+
+#[test]
+fn test_fund_stake_rewards_without_stakers() {
+    let mut setup = setup();
+    let ix = fund_stake_rewards(
+        &setup.harness.program_id,
+        &setup.admin,
+        &setup.admin_token,
+        &setup.vaults.rewards_vault,
+        1_000,
+    );
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidInput.into()
+    );
+}
+
+#[test]
+fn test_flash_repay_stake_discount() {
+    let mut setup = setup();
+    let program_id = setup.harness.program_id;
+    let (staker, staker_token) = (setup.staker, setup.staker_token);
+    let ix = stake(
+        &program_id,
+        &setup.vaults,
+        &staker,
+        &staker_token,
+        &staker_token,
+        1_000,
+    );
+    setup.harness.process(&ix).unwrap();
+    let mint = stake_pool(&setup).mint;
+    let loan_vault = setup
+        .harness
+        .create_token_account(&mint, &Pubkey::new_unique(), 0);
+    let borrower_token = setup.harness.create_token_account(&mint, &staker, 200_000);
+
+    let mut ix = flash_repay(
+        &program_id,
+        &loan_vault,
+        &borrower_token,
+        &staker,
+        &spl_token::id(),
+        100_000,
+    );
+    discount_by_stake(&mut ix, &staker);
+    setup.harness.process(&ix).unwrap();
+    let fee = flash_fee(100_000).unwrap();
+    assert_eq!(fee, 90);
+    // the first tier takes 10% off the fee
+    assert_eq!(setup.harness.token_balance(&loan_vault), 100_000 + 81);
+
+    // the stake of the staker does not discount the fee of another signer
+    let other = setup
+        .harness
+        .add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let mut ix = flash_repay(
+        &program_id,
+        &loan_vault,
+        &borrower_token,
+        &other,
+        &spl_token::id(),
+        100_000,
+    );
+    discount_by_stake(&mut ix, &staker);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidStakeAccount.into()
+    );
+}
@@ -0,0 +1,234 @@
+mod common;
+
+use common::{swap_instruction, Harness, TestSwapAccounts};
+use onesol::error::{LegError, OneSolError};
+use onesol_client::instruction::bound_legs;
+use solana_program::pubkey::Pubkey;
+
+#[test]
+fn test_swap_single_leg() {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+
+    let ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        10_000,
+        9_900,
+        &[(pool.leg_a_to_b(), 10_000)],
+    );
+    harness.process(&ix).unwrap();
+
+    assert_eq!(harness.token_balance(&source), 40_000);
+    assert_eq!(harness.token_balance(&destination), 9_900);
+    assert_eq!(harness.token_balance(&protocol.token), 0);
+    assert_eq!(harness.token_balance(&pool.token_a), 1_010_000);
+    assert_eq!(harness.token_balance(&pool.token_b), 990_100);
+}
+
+#[test]
+fn test_swap_exceeded_slippage() {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+
+    let ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        10_000,
+        9_901,
+        &[(pool.leg_a_to_b(), 10_000)],
+    );
+    // the slippage code of the pool is reported as the 1sol one
+    assert_eq!(
+        harness.process(&ix).unwrap_err(),
+        LegError::new(0, 0, &OneSolError::ExceededSlippage.into()).into()
+    );
+}
+
+#[test]
+fn test_swap_empty_pool() {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 0);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+
+    let ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        10_000,
+        0,
+        &[(pool.leg_a_to_b(), 10_000)],
+    );
+    assert_eq!(
+        harness.process(&ix).unwrap_err(),
+        LegError::new(0, 0, &OneSolError::ZeroTradingTokens.into()).into()
+    );
+}
+
+#[test]
+fn test_swap_rejects_foreign_protocol_authority() {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let mut protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+
+    // the persisted nonce only signs for the real authority, owning the protocol token
+    // the split swap pays out of
+    protocol.authority = Pubkey::new_unique();
+    let ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        10_000,
+        0,
+        &[(pool.leg_a_to_b(), 5_000), (pool.leg_a_to_b(), 5_000)],
+    );
+    assert!(harness.process(&ix).is_err());
+}
+
+#[test]
+fn test_swap_single_leg_outputs_into_destination() {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 300);
+    harness.set_token_balance(&protocol.token, 500);
+
+    let ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        10_000,
+        9_900,
+        &[(pool.leg_a_to_b(), 10_000)],
+    );
+    let (result, usage) = harness.process_metered(&ix);
+    result.unwrap();
+
+    // the pool swap and its two transfers, no payout
+    assert_eq!(usage.invokes, 3);
+    assert_eq!(harness.token_balance(&destination), 10_200);
+    assert_eq!(harness.token_balance(&protocol.token), 500);
+}
+
+#[test]
+fn test_swap_split_across_pools() {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool1 = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let pool2 = harness.create_pool(&mint_a, &mint_b, 3_000_000, 3_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+
+    let ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        40_000,
+        0,
+        &[(pool1.leg_a_to_b(), 2_500), (pool2.leg_a_to_b(), 7_500)],
+    );
+    harness.process(&ix).unwrap();
+
+    // 10_000 through the first pool and 30_000 through the second
+    assert_eq!(harness.token_balance(&pool1.token_a), 1_010_000);
+    assert_eq!(harness.token_balance(&pool2.token_a), 3_030_000);
+    assert_eq!(harness.token_balance(&source), 60_000);
+    assert_eq!(harness.token_balance(&destination), 9_900 + 29_702);
+    assert_eq!(harness.token_balance(&protocol.token), 0);
+}
+
+#[test]
+fn test_swap_bounds_each_leg() {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool1 = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let pool2 = harness.create_pool(&mint_a, &mint_b, 3_000_000, 3_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+
+    // 9_900 out of the first pool, 29_702 out of the second
+    let ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        40_000,
+        39_000,
+        &[(pool1.leg_a_to_b(), 2_500), (pool2.leg_a_to_b(), 7_500)],
+    );
+    // the total clears the route's minimum, the first leg doesn't clear its own
+    let mut bounded = ix.clone();
+    bound_legs(&mut bounded, &[9_901, 0]).unwrap();
+    assert_eq!(
+        harness.process(&bounded).unwrap_err(),
+        LegError::new(0, 0, &OneSolError::ExceededSlippage.into()).into()
+    );
+    assert_eq!(harness.token_balance(&source), 100_000);
+
+    // unbounded legs keep their share of the route's minimum
+    let mut bounded = ix;
+    bound_legs(&mut bounded, &[0, 29_702]).unwrap();
+    harness.process(&bounded).unwrap();
+    assert_eq!(harness.token_balance(&destination), 9_900 + 29_702);
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use common::{mock_token_bridge_id, Harness, TestPool};
+use onesol::{bridge, error::OneSolError, instruction::BridgeTransfer};
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{swap_and_bridge, SwapAccounts},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+struct BridgeSetup {
+    harness: Harness,
+    accounts: SwapAccounts,
+    pool: TestPool,
+    custody: Pubkey,
+}
+
+fn setup() -> BridgeSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let authority_signer = bridge::authority_signer(&mock_token_bridge_id());
+    harness.add_account(authority_signer, Pubkey::default(), vec![]);
+    let custody = harness.create_token_account(&mint_b, &authority_signer, 0);
+    BridgeSetup {
+        harness,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        pool,
+        custody,
+    }
+}
+
+fn bridge_instruction(setup: &BridgeSetup, from: &Pubkey, relayer_fee: u64) -> Instruction {
+    let bridge_accounts = vec![
+        AccountMeta::new(*from, false),
+        AccountMeta::new(setup.custody, false),
+        AccountMeta::new_readonly(bridge::authority_signer(&mock_token_bridge_id()), false),
+    ];
+    let leg = DexLeg {
+        dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+        accounts: setup.pool.leg_a_to_b(),
+    };
+    swap_and_bridge(
+        &setup.harness.program_id,
+        &setup.accounts,
+        &mock_token_bridge_id(),
+        &bridge_accounts,
+        10_000,
+        9_900,
+        &BridgeTransfer {
+            wrapped: false,
+            nonce: 1,
+            target_chain: 2,
+            target_address: [7u8; 32],
+            relayer_fee,
+        },
+        &[(leg, 10_000)],
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_swap_and_bridge() {
+    let mut setup = setup();
+    let ix = bridge_instruction(&setup, &setup.accounts.destination, 10);
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.accounts.source), 90_000);
+    assert_eq!(harness.token_balance(&setup.accounts.destination), 0);
+    assert_eq!(harness.token_balance(&setup.custody), 9_900);
+    assert_eq!(harness.token_balance(&setup.accounts.protocol_token), 0);
+}
+
+#[test]
+fn test_swap_and_bridge_rejects_foreign_source() {
+    let mut setup = setup();
+    // the bridge may only pull the output of the swap
+    let ix = bridge_instruction(&setup, &setup.accounts.source, 10);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::IncorrectSwapAccount.into()
+    );
+}
+
+#[test]
+fn test_swap_and_bridge_rejects_relayer_fee_above_output() {
+    let mut setup = setup();
+    let ix = bridge_instruction(&setup, &setup.accounts.destination, 9_901);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidInput.into()
+    );
+}
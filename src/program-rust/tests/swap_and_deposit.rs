@@ -0,0 +1,123 @@
+mod common;
+
+use common::{mock_lending_id, Harness, TestPool};
+use onesol::error::OneSolError;
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{swap_and_deposit, SwapAccounts},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    sysvar,
+};
+
+struct DepositSetup {
+    harness: Harness,
+    accounts: SwapAccounts,
+    pool: TestPool,
+    liquidity_supply: Pubkey,
+}
+
+fn setup() -> DepositSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let reserve_authority = Pubkey::new_unique();
+    let liquidity_supply = harness.create_token_account(&mint_b, &reserve_authority, 0);
+    DepositSetup {
+        harness,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        pool,
+        liquidity_supply,
+    }
+}
+
+/// Accounts of `DepositReserveLiquidityAndObligationCollateral`, depositing from `from`.
+fn lending_accounts(setup: &DepositSetup, from: &Pubkey) -> Vec<AccountMeta> {
+    let user = setup.accounts.user_transfer_authority;
+    vec![
+        AccountMeta::new(*from, false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(setup.liquidity_supply, false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(user, true),
+        AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(user, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ]
+}
+
+fn deposit_instruction(setup: &DepositSetup, lending_accounts: &[AccountMeta]) -> Instruction {
+    let leg = DexLeg {
+        dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+        accounts: setup.pool.leg_a_to_b(),
+    };
+    swap_and_deposit(
+        &setup.harness.program_id,
+        &setup.accounts,
+        &mock_lending_id(),
+        lending_accounts,
+        10_000,
+        9_900,
+        &[(leg, 10_000)],
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_swap_and_deposit() {
+    let mut setup = setup();
+    let lending_accounts = lending_accounts(&setup, &setup.accounts.destination);
+    let ix = deposit_instruction(&setup, &lending_accounts);
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.accounts.source), 90_000);
+    assert_eq!(harness.token_balance(&setup.accounts.destination), 0);
+    assert_eq!(harness.token_balance(&setup.liquidity_supply), 9_900);
+    assert_eq!(harness.token_balance(&setup.accounts.protocol_token), 0);
+}
+
+#[test]
+fn test_swap_and_deposit_rejects_foreign_source() {
+    let mut setup = setup();
+    // the deposit may only take the output of the swap
+    let lending_accounts = lending_accounts(&setup, &setup.accounts.source);
+    let ix = deposit_instruction(&setup, &lending_accounts);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::IncorrectSwapAccount.into()
+    );
+}
+
+#[test]
+fn test_swap_and_deposit_requires_obligation() {
+    let mut setup = setup();
+    let lending_accounts = lending_accounts(&setup, &setup.accounts.destination);
+    let ix = deposit_instruction(&setup, &lending_accounts[..8]);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::AccountCountMismatch.into()
+    );
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::{mock_stake_pool_id, Harness, TestPool};
+use onesol::error::OneSolError;
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{swap_and_stake, SwapAccounts},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+};
+use spl_token::state::Account;
+
+struct StakeSetup {
+    harness: Harness,
+    accounts: SwapAccounts,
+    pool: TestPool,
+    reserve: Pubkey,
+    pool_tokens: Pubkey,
+}
+
+fn setup() -> StakeSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let sol = harness.create_native_mint();
+    let pool = harness.create_pool(&mint_a, &sol, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&sol);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&sol, &user, 0);
+    let reserve = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let pool_token_mint = harness.create_mint();
+    let pool_tokens = harness.create_token_account(&pool_token_mint, &user, 0);
+    StakeSetup {
+        harness,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        pool,
+        reserve,
+        pool_tokens,
+    }
+}
+
+/// Accounts of `DepositSol`, funded by `from`.
+fn stake_pool_accounts(setup: &StakeSetup, from: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        AccountMeta::new(setup.reserve, false),
+        AccountMeta::new(*from, true),
+        AccountMeta::new(setup.pool_tokens, false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ]
+}
+
+fn stake_instruction(setup: &StakeSetup, stake_pool_accounts: &[AccountMeta]) -> Instruction {
+    let leg = DexLeg {
+        dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+        accounts: setup.pool.leg_a_to_b(),
+    };
+    swap_and_stake(
+        &setup.harness.program_id,
+        &setup.accounts,
+        &mock_stake_pool_id(),
+        stake_pool_accounts,
+        10_000,
+        9_900,
+        &[(leg, 10_000)],
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_swap_and_stake() {
+    let mut setup = setup();
+    let user = setup.accounts.user_transfer_authority;
+    let stake_pool_accounts = stake_pool_accounts(&setup, &user);
+    let ix = stake_instruction(&setup, &stake_pool_accounts);
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.accounts.source), 90_000);
+    assert_eq!(harness.token_balance(&setup.pool_tokens), 9_900);
+    assert_eq!(
+        harness.account(&setup.reserve).lamports,
+        1_000_000_000 + 9_900
+    );
+    // the wrapped SOL account is closed, its rent back to the user
+    assert_eq!(harness.account(&setup.accounts.destination).lamports, 0);
+    assert_eq!(
+        harness.account(&user).lamports,
+        1_000_000_000 + Rent::default().minimum_balance(Account::LEN)
+    );
+}
+
+#[test]
+fn test_swap_and_stake_rejects_foreign_funding() {
+    let mut setup = setup();
+    let stake_pool_accounts = stake_pool_accounts(&setup, &Pubkey::new_unique());
+    let ix = stake_instruction(&setup, &stake_pool_accounts);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::IncorrectSwapAccount.into()
+    );
+}
+
+#[test]
+fn test_swap_and_stake_requires_wrapped_sol() {
+    let mut setup = setup();
+    let mint = setup.harness.create_mint();
+    let user = setup.accounts.user_transfer_authority;
+    // a swap into another mint than wrapped SOL
+    setup.accounts.destination = setup.harness.create_token_account(&mint, &user, 0);
+    setup.accounts.protocol_token = setup.harness.create_vault(
+        &setup.accounts.protocol,
+        &setup.accounts.protocol_authority,
+        &mint,
+        0,
+    );
+    let stake_pool_accounts = stake_pool_accounts(&setup, &user);
+    let ix = stake_instruction(&setup, &stake_pool_accounts);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidInput.into()
+    );
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use common::Harness;
+use onesol::error::OneSolError;
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{swap_batch, SwapAccounts, SwapBatchPair},
+};
+use solana_program::pubkey::Pubkey;
+
+struct BatchSetup {
+    harness: Harness,
+    accounts: SwapAccounts,
+    pairs: Vec<SwapBatchPair>,
+}
+
+/// A user swapping mint A for mint B, paid through the protocol token account, and mint
+/// C for mint D, paid through the protocol vault of mint D, each through a pool of its
+/// pair.
+fn setup() -> BatchSetup {
+    let mut harness = Harness::new();
+    let mints: Vec<Pubkey> = (0..4).map(|_| harness.create_mint()).collect();
+    let protocol = harness.create_protocol(&mints[1]);
+    let vault_d = harness.create_vault(&protocol.protocol, &protocol.authority, &mints[3], 0);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let mut pairs = vec![];
+    for (i, (protocol_token, amount_in)) in [(protocol.token, 10_000), (vault_d, 20_000)]
+        .iter()
+        .enumerate()
+    {
+        let (source_mint, destination_mint) = (&mints[2 * i], &mints[2 * i + 1]);
+        let pool = harness.create_pool(source_mint, destination_mint, 1_000_000, 1_000_000);
+        pairs.push(SwapBatchPair {
+            protocol_token: *protocol_token,
+            source: harness.create_token_account(source_mint, &user, 100_000),
+            destination: harness.create_token_account(destination_mint, &user, 0),
+            amount_in: *amount_in,
+            minimum_amount_out: amount_in * 9 / 10,
+            legs: vec![(
+                DexLeg {
+                    dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+                    accounts: pool.leg_a_to_b(),
+                },
+                10_000,
+            )],
+            trailing_accounts: vec![],
+        });
+    }
+    BatchSetup {
+        harness,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: Pubkey::default(),
+            source: Pubkey::default(),
+            destination: Pubkey::default(),
+            token_program_id: spl_token::id(),
+        },
+        pairs,
+    }
+}
+
+#[test]
+fn test_swap_batch() {
+    let mut setup = setup();
+    let ix = swap_batch(&setup.harness.program_id, &setup.accounts, &setup.pairs).unwrap();
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    let pairs = &setup.pairs;
+    assert_eq!(harness.token_balance(&pairs[0].source), 90_000);
+    assert_eq!(harness.token_balance(&pairs[0].destination), 9_900);
+    assert_eq!(harness.token_balance(&pairs[1].source), 80_000);
+    assert_eq!(harness.token_balance(&pairs[1].destination), 19_607);
+    assert_eq!(harness.token_balance(&pairs[0].protocol_token), 0);
+    assert_eq!(harness.token_balance(&pairs[1].protocol_token), 0);
+}
+
+#[test]
+fn test_swap_batch_fails_as_a_whole() {
+    let mut setup = setup();
+    setup.pairs[1].destination = setup.pairs[0].protocol_token;
+    let ix = swap_batch(&setup.harness.program_id, &setup.accounts, &setup.pairs).unwrap();
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::IncorrectSwapAccount.into()
+    );
+}
+
+#[test]
+fn test_swap_batch_checks_protocol_token_of_each_pair() {
+    let mut setup = setup();
+    // the protocol token account of mint B can't pay out mint D
+    setup.pairs[1].protocol_token = setup.pairs[0].protocol_token;
+    let ix = swap_batch(&setup.harness.program_id, &setup.accounts, &setup.pairs).unwrap();
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidInput.into()
+    );
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::{Harness, TestProtocol};
+use onesol::error::OneSolError;
+use onesol_client::{
+    dex::SplTokenSwapPool,
+    instruction::{pay_protocol_fee, set_fee_rate, swap_exact_out, SwapAccounts},
+};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+struct ExactOutSetup {
+    harness: Harness,
+    protocol: TestProtocol,
+    mint_b: Pubkey,
+    accounts: SwapAccounts,
+    pool1: SplTokenSwapPool,
+    pool2: SplTokenSwapPool,
+}
+
+/// Two spl-token-swap pools of mint A / mint B, a 1M / 1M one and a 3M / 3M one, both
+/// charging 0.25% trade and 0.05% owner fees.
+fn setup() -> ExactOutSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool1 = harness.create_spl_token_swap_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let pool2 = harness.create_spl_token_swap_pool(&mint_a, &mint_b, 3_000_000, 3_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    ExactOutSetup {
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        harness,
+        protocol,
+        mint_b,
+        pool1,
+        pool2,
+    }
+}
+
+fn exact_out(setup: &ExactOutSetup, amount_out: u64, maximum_amount_in: u64) -> Instruction {
+    let leg = |pool: &SplTokenSwapPool| pool.leg(&pool.token_a_mint, None).unwrap();
+    swap_exact_out(
+        &setup.harness.program_id,
+        &setup.accounts,
+        amount_out,
+        maximum_amount_in,
+        &[(leg(&setup.pool1), 2_500), (leg(&setup.pool2), 7_500)],
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_swap_exact_out() {
+    let mut setup = setup();
+    let ix = exact_out(&setup, 39_602, 50_000);
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    // 10_029 in for 9_900 out of the first pool, 30_090 for 29_702 out of the second
+    assert_eq!(harness.token_balance(&setup.pool1.token_a), 1_010_029);
+    assert_eq!(harness.token_balance(&setup.pool1.token_b), 990_100);
+    assert_eq!(harness.token_balance(&setup.pool2.token_a), 3_030_090);
+    assert_eq!(harness.token_balance(&setup.pool2.token_b), 2_970_298);
+    assert_eq!(harness.token_balance(&setup.accounts.source), 59_881);
+    assert_eq!(harness.token_balance(&setup.accounts.destination), 39_602);
+    assert_eq!(harness.token_balance(&setup.protocol.token), 0);
+}
+
+#[test]
+fn test_swap_exact_out_exceeded_maximum_in() {
+    let mut setup = setup();
+    let ix = exact_out(&setup, 39_602, 40_118);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::ExceededSlippage.into()
+    );
+    assert_eq!(setup.harness.token_balance(&setup.accounts.source), 100_000);
+}
+
+#[test]
+fn test_swap_exact_out_net_of_protocol_fee() {
+    let mut setup = setup();
+    let treasury = setup
+        .harness
+        .create_token_account(&setup.mint_b, &Pubkey::new_unique(), 0);
+    let fee_rate = set_fee_rate(
+        &setup.harness.program_id,
+        &setup.protocol.admin,
+        &setup.protocol.protocol,
+        &treasury,
+        25,
+        10_000,
+    );
+    setup.harness.process(&fee_rate).unwrap();
+
+    let mut ix = exact_out(&setup, 39_602, 50_000);
+    pay_protocol_fee(&mut ix, &treasury);
+    setup.harness.process(&ix).unwrap();
+
+    // the legs output 39_702, 99 of it owed to the treasury
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&treasury), 99);
+    assert_eq!(harness.token_balance(&setup.accounts.destination), 39_603);
+    assert_eq!(harness.token_balance(&setup.protocol.token), 0);
+}
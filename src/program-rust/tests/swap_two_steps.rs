@@ -0,0 +1,106 @@
+mod common;
+
+use common::{Harness, TestPool};
+use onesol::error::OneSolError;
+use onesol_client::{
+    dex::{DexLeg, DEX_TYPE_SPL_TOKEN_SWAP},
+    instruction::{swap_two_steps, SwapAccounts},
+};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+struct TwoStepsSetup {
+    harness: Harness,
+    accounts: SwapAccounts,
+    intermediate: Pubkey,
+    pool_ab: TestPool,
+    pool_bc: TestPool,
+}
+
+/// A route of mint A for mint C, with no A / C pool, through an A / B pool then a
+/// B / C pool.
+fn setup() -> TwoStepsSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let mint_c = harness.create_mint();
+    let pool_ab = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let pool_bc = harness.create_pool(&mint_b, &mint_c, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_c);
+    // tokens already there stay there
+    let intermediate = harness.create_token_account(&mint_b, &protocol.authority, 500);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_c, &user, 0);
+    TwoStepsSetup {
+        harness,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        intermediate,
+        pool_ab,
+        pool_bc,
+    }
+}
+
+fn two_steps_swap(setup: &TwoStepsSetup, minimum_amount_out: u64) -> Instruction {
+    let leg = |pool: &TestPool| DexLeg {
+        dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+        accounts: pool.leg_a_to_b(),
+    };
+    swap_two_steps(
+        &setup.harness.program_id,
+        &setup.accounts,
+        &setup.intermediate,
+        10_000,
+        minimum_amount_out,
+        &[(leg(&setup.pool_ab), 10_000)],
+        &[(leg(&setup.pool_bc), 10_000)],
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_swap_two_steps() {
+    let mut setup = setup();
+    let ix = two_steps_swap(&setup, 9_800);
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    // 10_000 A for 9_900 B, all of it for 9_802 C
+    assert_eq!(harness.token_balance(&setup.pool_ab.token_a), 1_010_000);
+    assert_eq!(harness.token_balance(&setup.pool_ab.token_b), 990_100);
+    assert_eq!(harness.token_balance(&setup.pool_bc.token_a), 1_009_900);
+    assert_eq!(harness.token_balance(&setup.accounts.source), 90_000);
+    assert_eq!(harness.token_balance(&setup.accounts.destination), 9_802);
+    assert_eq!(harness.token_balance(&setup.intermediate), 500);
+    assert_eq!(harness.token_balance(&setup.accounts.protocol_token), 0);
+}
+
+#[test]
+fn test_swap_two_steps_exceeded_slippage() {
+    let mut setup = setup();
+    // the first step output clears the minimum, the second's doesn't
+    let ix = two_steps_swap(&setup, 9_850);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::ExceededSlippage.into()
+    );
+}
+
+#[test]
+fn test_swap_two_steps_rejects_foreign_intermediate() {
+    let mut setup = setup();
+    // an intermediate account the protocol authority doesn't own
+    setup.intermediate = setup.pool_ab.token_b;
+    let ix = two_steps_swap(&setup, 0);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidOwner.into()
+    );
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{Harness, TestPool};
+use onesol_client::prelude::*;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+struct TipSetup {
+    harness: Harness,
+    accounts: SwapAccounts,
+    pool: TestPool,
+    tip_account: Pubkey,
+}
+
+fn setup() -> TipSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 100_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    let tip_account = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    harness.create_config(&Pubkey::new_unique(), &tip_account);
+    TipSetup {
+        harness,
+        accounts: SwapAccounts {
+            protocol: protocol.protocol,
+            protocol_authority: protocol.authority,
+            user_transfer_authority: user,
+            protocol_token: protocol.token,
+            source,
+            destination,
+            token_program_id: spl_token::id(),
+        },
+        pool,
+        tip_account,
+    }
+}
+
+fn tipped_swap(setup: &TipSetup, tip_account: &Pubkey, lamports: u64) -> Instruction {
+    let leg = DexLeg {
+        dex_type: DEX_TYPE_SPL_TOKEN_SWAP,
+        accounts: setup.pool.leg_a_to_b(),
+    };
+    let mut ix = swap(
+        &setup.harness.program_id,
+        &setup.accounts,
+        10_000,
+        9_800,
+        &[(leg, 10_000)],
+    )
+    .unwrap();
+    tip_swap(&mut ix, tip_account, lamports).unwrap();
+    ix
+}
+
+#[test]
+fn test_swap_with_tip() {
+    let mut setup = setup();
+    let ix = tipped_swap(&setup, &setup.tip_account, 5_000);
+    setup.harness.process(&ix).unwrap();
+
+    let harness = &setup.harness;
+    assert_eq!(harness.token_balance(&setup.accounts.destination), 9_900);
+    assert_eq!(harness.account(&setup.tip_account).lamports, 1_000_005_000);
+    assert_eq!(
+        harness
+            .account(&setup.accounts.user_transfer_authority)
+            .lamports,
+        999_995_000
+    );
+}
+
+#[test]
+fn test_swap_tip_to_other_account() {
+    let mut setup = setup();
+    let other = setup
+        .harness
+        .add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let ix = tipped_swap(&setup, &other, 5_000);
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidTipAccount.into()
+    );
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::{swap_instruction, Harness, TestPool, TestProtocol, TestSwapAccounts};
+use onesol::error::OneSolError;
+use onesol_client::instruction::check_destination_mint;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+struct MintSetup {
+    harness: Harness,
+    protocol: TestProtocol,
+    pool: TestPool,
+    mint_b: Pubkey,
+    user: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+}
+
+/// A protocol swapping mint A for mint B.
+fn setup() -> MintSetup {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let protocol = harness.create_protocol(&mint_b);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+    MintSetup {
+        harness,
+        protocol,
+        pool,
+        mint_b,
+        user,
+        source,
+        destination,
+    }
+}
+
+fn swap(setup: &MintSetup, minimum_amount_out: u64) -> Instruction {
+    swap_instruction(
+        &setup.harness.program_id,
+        &setup.protocol,
+        &TestSwapAccounts {
+            user: setup.user,
+            source: setup.source,
+            destination: setup.destination,
+        },
+        10_000,
+        minimum_amount_out,
+        &[(setup.pool.leg_a_to_b(), 10_000)],
+    )
+}
+
+#[test]
+fn test_swap_pays_out_checked_against_destination_mint() {
+    let mut setup = setup();
+    let mut ix = swap(&setup, 9_900);
+    check_destination_mint(&mut ix, &setup.mint_b);
+    setup.harness.process(&ix).unwrap();
+    assert_eq!(setup.harness.token_balance(&setup.destination), 9_900);
+    assert_eq!(setup.harness.token_balance(&setup.protocol.token), 0);
+}
+
+#[test]
+fn test_swap_rejects_other_token_programs() {
+    let mut setup = setup();
+    let mut ix = swap(&setup, 0);
+    ix.accounts[6].pubkey = Pubkey::new_unique();
+    assert_eq!(
+        setup.harness.process(&ix).unwrap_err(),
+        OneSolError::IncorrectTokenProgramId.into()
+    );
+}
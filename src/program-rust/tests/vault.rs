@@ -0,0 +1,118 @@
+mod common;
+
+use common::{swap_instruction, Harness, TestSwapAccounts};
+use onesol::error::OneSolError;
+use onesol_client::instruction::initialize_vault;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+#[test]
+fn test_swap_pays_out_of_vault() {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let mint_c = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    // a protocol account of another mint swaps into B through its B vault
+    let mut protocol = harness.create_protocol(&mint_c);
+    protocol.token = harness.create_vault(&protocol.protocol, &protocol.authority, &mint_b, 500);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+
+    let ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        10_000,
+        9_800,
+        &[(pool.leg_a_to_b(), 5_000), (pool.leg_a_to_b(), 5_000)],
+    );
+    harness.process(&ix).unwrap();
+
+    assert!(harness.token_balance(&destination) >= 9_800);
+    // tokens already in the vault stay there
+    assert_eq!(harness.token_balance(&protocol.token), 500);
+}
+
+#[test]
+fn test_swap_rejects_underived_protocol_token() {
+    let mut harness = Harness::new();
+    let mint_a = harness.create_mint();
+    let mint_b = harness.create_mint();
+    let mint_c = harness.create_mint();
+    let pool = harness.create_pool(&mint_a, &mint_b, 1_000_000, 1_000_000);
+    let mut protocol = harness.create_protocol(&mint_c);
+    // owned by the protocol authority, but not the derived vault
+    protocol.token = harness.create_token_account(&mint_b, &protocol.authority, 0);
+    let user = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let source = harness.create_token_account(&mint_a, &user, 50_000);
+    let destination = harness.create_token_account(&mint_b, &user, 0);
+
+    let ix = swap_instruction(
+        &harness.program_id,
+        &protocol,
+        &TestSwapAccounts {
+            user,
+            source,
+            destination,
+        },
+        10_000,
+        0,
+        &[(pool.leg_a_to_b(), 5_000), (pool.leg_a_to_b(), 5_000)],
+    );
+    assert_eq!(
+        harness.process(&ix).unwrap_err(),
+        OneSolError::InvalidProgramAddress.into()
+    );
+}
+
+#[test]
+fn test_initialize_vault_checks_accounts() {
+    let mut harness = Harness::new();
+    let mint = harness.create_mint();
+    let protocol = harness.create_protocol(&mint);
+    let payer = harness.add_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+    let program_id = harness.program_id;
+    let ix = || {
+        initialize_vault(
+            &program_id,
+            &payer,
+            &protocol.protocol,
+            &protocol.authority,
+            &mint,
+        )
+    };
+
+    let mut unsigned = ix();
+    unsigned.accounts[0].is_signer = false;
+    assert_eq!(
+        harness.process(&unsigned).unwrap_err(),
+        ProgramError::MissingRequiredSignature
+    );
+
+    let mut foreign_authority = ix();
+    foreign_authority.accounts[2].pubkey = Pubkey::new_unique();
+    assert_eq!(
+        harness.process(&foreign_authority).unwrap_err(),
+        OneSolError::InvalidProgramAddress.into()
+    );
+
+    // the vault of another mint
+    let mut other_vault = ix();
+    other_vault.accounts[4].pubkey = harness.create_mint();
+    assert_eq!(
+        harness.process(&other_vault).unwrap_err(),
+        OneSolError::InvalidProgramAddress.into()
+    );
+
+    let mut token_2022 = ix();
+    token_2022.accounts[5].pubkey = onesol::token_2022::id();
+    assert_eq!(
+        harness.process(&token_2022).unwrap_err(),
+        OneSolError::IncorrectTokenProgramId.into()
+    );
+}